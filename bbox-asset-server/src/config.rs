@@ -1,5 +1,7 @@
+use crate::fonts::FontDirCfg;
 use crate::qgis_plugins::QgisPluginRepoCfg;
 use crate::runtime_templates::TemplateDirCfg;
+use crate::sprites::SpriteDirCfg;
 use bbox_core::config::{from_config_opt_or_exit, ConfigError};
 use bbox_core::service::ServiceConfig;
 use clap::ArgMatches;
@@ -12,6 +14,8 @@ pub struct AssetServiceCfg {
     pub static_: Vec<StaticDirCfg>,
     pub template: Vec<TemplateDirCfg>,
     pub repo: Vec<QgisPluginRepoCfg>,
+    pub sprites: Vec<SpriteDirCfg>,
+    pub fonts: Vec<FontDirCfg>,
 }
 
 #[derive(Deserialize, Debug)]
@@ -1,7 +1,9 @@
 use crate::config::AssetServiceCfg;
+use crate::fonts::{parse_range, FontError, Fonts};
 use crate::qgis_plugins::*;
 use crate::runtime_templates::RuntimeTemplates;
 use crate::service::{AssetService, PluginIndex};
+use crate::sprites::{parse_sprite_name, Sprites};
 use actix_files::{Files, NamedFile};
 use actix_web::{web, HttpRequest, HttpResponse, Result};
 use bbox_core::app_dir;
@@ -49,6 +51,49 @@ async fn plugin_xml(plugins_index: web::Data<PluginIndex>, req: HttpRequest) ->
     Ok(NamedFile::from_file(file, "plugin.xml")?)
 }
 
+/// Sprite index or image, e.g. `/sprites/icons@2x.png`
+async fn sprite(sprites: web::Data<Sprites>, name: web::Path<String>) -> HttpResponse {
+    let Some((id, pixel_ratio, suffix)) = parse_sprite_name(&name) else {
+        return HttpResponse::NotFound().finish();
+    };
+    let Some(sheet) = sprites.get(id, pixel_ratio) else {
+        return HttpResponse::NotFound().finish();
+    };
+    if suffix == "png" {
+        HttpResponse::Ok()
+            .content_type("image/png")
+            .body(sheet.png.clone())
+    } else {
+        HttpResponse::Ok()
+            .content_type("application/json")
+            .body(sheet.index.clone())
+    }
+}
+
+/// Glyph range of a comma separated font stack, e.g. `/fonts/Open Sans Regular/0-255.pbf`
+async fn glyphs(
+    fonts: web::Data<Fonts>,
+    path: web::Path<(String, String)>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let (fontstack, range) = path.into_inner();
+    let Some((start, end)) = parse_range(&range) else {
+        return Ok(HttpResponse::NotFound().finish());
+    };
+    let pbf = web::block(move || fonts.glyphs(&fontstack, start, end)).await?;
+    match pbf {
+        Ok(pbf) => Ok(HttpResponse::Ok()
+            .content_type("application/x-protobuf")
+            .body(pbf)),
+        Err(FontError::FontNotFound(name)) => {
+            Ok(HttpResponse::NotFound().body(format!("Font `{name}` not found")))
+        }
+        Err(e) => {
+            warn!("Glyph generation failed: {e}");
+            Ok(HttpResponse::InternalServerError().finish())
+        }
+    }
+}
+
 impl ServiceEndpoints for AssetService {
     fn register_endpoints(&self, cfg: &mut web::ServiceConfig) {
         let service_cfg = AssetServiceCfg::from_config();
@@ -85,6 +130,18 @@ impl ServiceEndpoints for AssetService {
 
         cfg.app_data(web::Data::new(self.plugins_index.clone()));
 
+        if !self.sprites.is_empty() {
+            info!("Serving sprites on '/sprites'");
+            cfg.app_data(web::Data::new(self.sprites.clone()))
+                .route("/sprites/{name}", web::get().to(sprite));
+        }
+
+        if !self.fonts.is_empty() {
+            info!("Serving glyphs on '/fonts'");
+            cfg.app_data(web::Data::new(self.fonts.clone()))
+                .route("/fonts/{fontstack}/{range}.pbf", web::get().to(glyphs));
+        }
+
         for repo in &service_cfg.repo {
             let dir = app_dir(&repo.dir);
             if Path::new(&dir).is_dir() {
@@ -0,0 +1,129 @@
+//! MapLibre glyph ranges generated from TTF/OTF fonts.
+
+use bbox_core::file_search;
+use log::{info, warn};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// Number of codepoints per glyph range
+const RANGE_SIZE: u32 = 256;
+/// Highest codepoint served
+const MAX_CODEPOINT: u32 = 0xFFFF;
+/// SDF rendering parameters used by MapLibre
+#[cfg(feature = "fonts")]
+const FONT_SIZE: usize = 24;
+#[cfg(feature = "fonts")]
+const RADIUS: usize = 8;
+#[cfg(feature = "fonts")]
+const CUTOFF: f64 = 0.25;
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct FontDirCfg {
+    /// Font directory with TTF or OTF files
+    pub dir: String,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum FontError {
+    #[cfg(feature = "fonts")]
+    #[error(transparent)]
+    FreeTypeError(#[from] pbf_font_tools::freetype::Error),
+    #[cfg(feature = "fonts")]
+    #[error(transparent)]
+    PbfFontError(#[from] pbf_font_tools::PbfFontError),
+    #[cfg(feature = "fonts")]
+    #[error(transparent)]
+    ProtobufError(#[from] pbf_font_tools::protobuf::Error),
+    #[error("font `{0}` not found")]
+    FontNotFound(String),
+    #[error("glyph generation requires the `fonts` feature")]
+    FeatureNotEnabled,
+}
+
+/// Font files by font name (`{family} {style}`)
+#[derive(Clone, Default)]
+pub struct Fonts {
+    files: HashMap<String, PathBuf>,
+}
+
+impl Fonts {
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+    /// Register all fonts in `dir`
+    pub fn add_dir(&mut self, dir: &str) {
+        info!("Scanning font directory '{dir}'");
+        let base = Path::new(dir);
+        for pattern in ["*.ttf", "*.otf"] {
+            for path in file_search::search(base, pattern) {
+                match font_name(&path) {
+                    Ok(name) => {
+                        info!("Registering font '{name}'");
+                        self.files.insert(name, path);
+                    }
+                    Err(e) => warn!("Font '{}' ignored: {e}", path.display()),
+                }
+            }
+        }
+    }
+    /// Protobuf encoded glyphs of a comma separated font stack
+    pub fn glyphs(&self, fontstack: &str, start: u32, end: u32) -> Result<Vec<u8>, FontError> {
+        let paths = fontstack
+            .split(',')
+            .map(|name| {
+                let name = name.trim();
+                self.files
+                    .get(name)
+                    .ok_or_else(|| FontError::FontNotFound(name.to_string()))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        render_glyphs(&paths, start, end)
+    }
+}
+
+/// Parse glyph range like `0-255`
+pub fn parse_range(range: &str) -> Option<(u32, u32)> {
+    let (start, end) = range.split_once('-')?;
+    let start: u32 = start.parse().ok()?;
+    let end: u32 = end.parse().ok()?;
+    (start % RANGE_SIZE == 0 && end == start + RANGE_SIZE - 1 && end <= MAX_CODEPOINT)
+        .then_some((start, end))
+}
+
+#[cfg(feature = "fonts")]
+fn font_name(path: &Path) -> Result<String, FontError> {
+    let lib = pbf_font_tools::freetype::Library::init()?;
+    let face = lib.new_face(path, 0)?;
+    let family = face.family_name().unwrap_or_default();
+    Ok(match face.style_name() {
+        Some(style) => format!("{family} {style}"),
+        None => family,
+    })
+}
+
+#[cfg(not(feature = "fonts"))]
+fn font_name(_path: &Path) -> Result<String, FontError> {
+    Err(FontError::FeatureNotEnabled)
+}
+
+#[cfg(feature = "fonts")]
+fn render_glyphs(paths: &[&PathBuf], start: u32, end: u32) -> Result<Vec<u8>, FontError> {
+    use pbf_font_tools::protobuf::Message;
+    use pbf_font_tools::{combine_glyphs, generate::glyph_range_for_font};
+
+    let mut glyphs = Vec::with_capacity(paths.len());
+    for path in paths {
+        glyphs.push(glyph_range_for_font(
+            path, start, end, FONT_SIZE, RADIUS, CUTOFF,
+        )?);
+    }
+    let combined = combine_glyphs(glyphs).unwrap_or_default();
+    Ok(combined.write_to_bytes()?)
+}
+
+#[cfg(not(feature = "fonts"))]
+fn render_glyphs(_paths: &[&PathBuf], _start: u32, _end: u32) -> Result<Vec<u8>, FontError> {
+    Err(FontError::FeatureNotEnabled)
+}
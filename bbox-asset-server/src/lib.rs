@@ -1,7 +1,9 @@
 pub mod config;
 mod endpoints;
+pub mod fonts;
 pub mod qgis_plugins;
 pub mod runtime_templates;
 pub mod service;
+pub mod sprites;
 
 pub use service::*;
@@ -1,5 +1,7 @@
 use crate::config::AssetServiceCfg;
+use crate::fonts::Fonts;
 use crate::qgis_plugins::plugin_files;
+use crate::sprites::Sprites;
 use async_trait::async_trait;
 use bbox_core::app_dir;
 use bbox_core::cli::{NoArgs, NoCommands};
@@ -15,6 +17,8 @@ pub type PluginIndex = HashMap<String, Vec<PathBuf>>;
 #[derive(Clone)]
 pub struct AssetService {
     pub plugins_index: PluginIndex,
+    pub sprites: Sprites,
+    pub fonts: Fonts,
 }
 
 #[async_trait]
@@ -36,8 +40,30 @@ impl OgcApiService for AssetService {
                 warn!("QGIS plugin repository file directory '{dir}' not found");
             }
         }
+        let mut sprites = Sprites::default();
+        for sprite_dir in &service_cfg.sprites {
+            let dir = app_dir(&sprite_dir.dir);
+            if Path::new(&dir).is_dir() {
+                sprites.add(&sprite_dir.id, &dir);
+            } else {
+                warn!("Sprite directory '{dir}' not found");
+            }
+        }
+        let mut fonts = Fonts::default();
+        for font_dir in &service_cfg.fonts {
+            let dir = app_dir(&font_dir.dir);
+            if Path::new(&dir).is_dir() {
+                fonts.add_dir(&dir);
+            } else {
+                warn!("Font directory '{dir}' not found");
+            }
+        }
         // static and template dir config is processed in register_endpoints
-        AssetService { plugins_index }
+        AssetService {
+            plugins_index,
+            sprites,
+            fonts,
+        }
     }
     fn metrics(&self) -> &'static Self::Metrics {
         no_metrics()
@@ -0,0 +1,117 @@
+//! MapLibre sprites generated from SVG icons.
+
+use log::{info, warn};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+
+/// Generated pixel ratios
+const PIXEL_RATIOS: [u8; 2] = [1, 2];
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct SpriteDirCfg {
+    /// Sprite id, visible part of endpoint
+    pub id: String,
+    /// SVG icon directory
+    pub dir: String,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum SpriteError {
+    #[cfg(feature = "sprites")]
+    #[error(transparent)]
+    SpreetError(#[from] spreet::SpreetError),
+    #[cfg(feature = "sprites")]
+    #[error(transparent)]
+    JsonError(#[from] serde_json::Error),
+    #[error("no valid SVG icons found")]
+    NoIcons,
+    #[error("sprite generation requires the `sprites` feature")]
+    FeatureNotEnabled,
+}
+
+/// Sprite index and image
+pub struct SpriteSheet {
+    pub index: Vec<u8>,
+    pub png: Vec<u8>,
+}
+
+/// Sprite sheets by id and pixel ratio
+#[derive(Clone, Default)]
+pub struct Sprites {
+    sheets: HashMap<(String, u8), Arc<SpriteSheet>>,
+}
+
+impl Sprites {
+    pub fn is_empty(&self) -> bool {
+        self.sheets.is_empty()
+    }
+    /// Generate sprite sheets of SVG icons in `dir`
+    pub fn add(&mut self, id: &str, dir: &str) {
+        info!("Generating sprites '{id}' from directory '{dir}'");
+        for pixel_ratio in PIXEL_RATIOS {
+            match generate(Path::new(dir), pixel_ratio) {
+                Ok(sheet) => {
+                    self.sheets
+                        .insert((id.to_string(), pixel_ratio), Arc::new(sheet));
+                }
+                Err(e) => {
+                    warn!("Sprite generation for '{dir}' failed: {e}");
+                    return;
+                }
+            }
+        }
+    }
+    pub fn get(&self, id: &str, pixel_ratio: u8) -> Option<Arc<SpriteSheet>> {
+        self.sheets.get(&(id.to_string(), pixel_ratio)).cloned()
+    }
+}
+
+/// Split sprite file name like `icons@2x.png` into id, pixel ratio and suffix
+pub fn parse_sprite_name(name: &str) -> Option<(&str, u8, &str)> {
+    let (stem, suffix) = name.rsplit_once('.')?;
+    if !["json", "png"].contains(&suffix) {
+        return None;
+    }
+    match stem.rsplit_once('@') {
+        Some((id, ratio)) => {
+            let pixel_ratio = ratio.strip_suffix('x')?.parse().ok()?;
+            Some((id, pixel_ratio, suffix))
+        }
+        None => Some((stem, 1, suffix)),
+    }
+}
+
+#[cfg(feature = "sprites")]
+fn generate(dir: &Path, pixel_ratio: u8) -> Result<SpriteSheet, SpriteError> {
+    use spreet::{get_svg_input_paths, load_svg, sprite_name, Sprite, Spritesheet};
+    use std::collections::BTreeMap;
+
+    let mut sprites = BTreeMap::new();
+    for path in get_svg_input_paths(dir, true)? {
+        let name = sprite_name(&path, dir)?;
+        match Sprite::new(load_svg(&path)?, pixel_ratio) {
+            Some(sprite) => {
+                sprites.insert(name, sprite);
+            }
+            None => warn!("Invalid SVG icon '{}'", path.display()),
+        }
+    }
+    if sprites.is_empty() {
+        return Err(SpriteError::NoIcons);
+    }
+    let mut builder = Spritesheet::build();
+    builder.sprites(sprites);
+    let sheet = builder.generate().ok_or(SpriteError::NoIcons)?;
+    Ok(SpriteSheet {
+        index: serde_json::to_vec(sheet.get_index())?,
+        png: sheet.encode_png()?,
+    })
+}
+
+#[cfg(not(feature = "sprites"))]
+fn generate(_dir: &Path, _pixel_ratio: u8) -> Result<SpriteSheet, SpriteError> {
+    Err(SpriteError::FeatureNotEnabled)
+}
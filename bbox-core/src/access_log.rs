@@ -0,0 +1,239 @@
+//! Structured JSON access logs with request correlation ids.
+
+use actix_web::dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::http::header::{HeaderName, HeaderValue};
+use actix_web::{Error, HttpMessage, HttpRequest};
+use chrono::Utc;
+use log::info;
+use serde_json::{json, Map, Value};
+use std::future::{ready, Future, Ready};
+use std::pin::Pin;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Instant;
+
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+/// Log target of access log entries
+pub const LOG_TARGET: &str = "bbox::access";
+/// Maximal length of request ids passed by clients
+const MAX_ID_LEN: usize = 128;
+
+tokio::task_local! {
+    static REQUEST_ID: String;
+}
+
+/// Correlation id of the request processed by the current task
+pub fn request_id() -> Option<String> {
+    REQUEST_ID.try_with(|id| id.clone()).ok()
+}
+
+/// Authenticated user or API key of a request, recorded by authentication middlewares
+#[derive(Clone, Default)]
+struct RequestIdentity {
+    user: Option<String>,
+    api_key: Option<String>,
+}
+
+fn update_identity(req: &HttpRequest, update: impl FnOnce(&mut RequestIdentity)) {
+    let mut extensions = req.extensions_mut();
+    if let Some(identity) = extensions.get_mut::<RequestIdentity>() {
+        update(identity);
+    } else {
+        let mut identity = RequestIdentity::default();
+        update(&mut identity);
+        extensions.insert(identity);
+    }
+}
+
+/// Record user name for access log
+pub fn log_user(req: &HttpRequest, user: &str) {
+    update_identity(req, |identity| identity.user = Some(user.to_string()));
+}
+
+/// Record API key name for access log
+pub fn log_api_key(req: &HttpRequest, name: &str) {
+    update_identity(req, |identity| identity.api_key = Some(name.to_string()));
+}
+
+/// Middleware writing an access log entry per request and adding a `X-Request-Id` response header.
+///
+/// Request ids passed by clients are kept, otherwise a new id is generated.
+#[derive(Clone, Default)]
+pub struct AccessLog;
+
+impl<S, B> Transform<S, ServiceRequest> for AccessLog
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = AccessLogMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(AccessLogMiddleware {
+            service: Rc::new(service),
+        }))
+    }
+}
+
+pub struct AccessLogMiddleware<S> {
+    service: Rc<S>,
+}
+
+impl<S, B> Service<ServiceRequest> for AccessLogMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let service = self.service.clone();
+        let start = Instant::now();
+        let request_id = req
+            .headers()
+            .get(REQUEST_ID_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .filter(|id| valid_request_id(id))
+            .map(str::to_string)
+            .unwrap_or_else(new_request_id);
+        let method = req.method().to_string();
+        let path = req.path().to_string();
+        let trace_id = req
+            .headers()
+            .get("traceparent")
+            .and_then(|value| value.to_str().ok())
+            .and_then(trace_id);
+        Box::pin(REQUEST_ID.scope(request_id.clone(), async move {
+            let result = service.call(req).await;
+            let mut entry = Map::new();
+            entry.insert("timestamp".to_string(), json!(Utc::now().to_rfc3339()));
+            entry.insert("request_id".to_string(), json!(request_id));
+            entry.insert("method".to_string(), json!(method));
+            entry.insert("path".to_string(), json!(path));
+            match &result {
+                Ok(res) => {
+                    entry.insert("status".to_string(), json!(res.status().as_u16()));
+                    add_request_attrs(&mut entry, res.request());
+                }
+                Err(e) => {
+                    let status = e.as_response_error().status_code();
+                    entry.insert("status".to_string(), json!(status.as_u16()));
+                }
+            }
+            entry.insert(
+                "latency_ms".to_string(),
+                json!(start.elapsed().as_secs_f64() * 1000.0),
+            );
+            if let Some(trace_id) = trace_id {
+                entry.insert("trace_id".to_string(), json!(trace_id));
+            }
+            info!(target: LOG_TARGET, "{}", Value::Object(entry));
+            result.map(|mut res| {
+                if let Ok(value) = HeaderValue::from_str(&request_id) {
+                    res.headers_mut()
+                        .insert(HeaderName::from_static(REQUEST_ID_HEADER), value);
+                }
+                res
+            })
+        }))
+    }
+}
+
+/// Collection or tileset id and identity of request
+fn add_request_attrs(entry: &mut Map<String, Value>, req: &HttpRequest) {
+    for (param, key) in [("collectionId", "collection"), ("tileset", "tileset")] {
+        if let Some(id) = req.match_info().get(param) {
+            entry.insert(key.to_string(), json!(id));
+        }
+    }
+    if let Some(identity) = req.extensions().get::<RequestIdentity>() {
+        if let Some(user) = &identity.user {
+            entry.insert("user".to_string(), json!(user));
+        }
+        if let Some(api_key) = &identity.api_key {
+            entry.insert("api_key".to_string(), json!(api_key));
+        }
+    }
+}
+
+fn valid_request_id(id: &str) -> bool {
+    !id.is_empty()
+        && id.len() <= MAX_ID_LEN
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | ':'))
+}
+
+fn new_request_id() -> String {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let cnt = COUNTER.fetch_add(1, Ordering::Relaxed);
+    format!(
+        "{:x}-{:x}-{:x}",
+        Utc::now().timestamp_millis(),
+        std::process::id(),
+        cnt
+    )
+}
+
+/// Trace id of W3C `traceparent` header
+fn trace_id(traceparent: &str) -> Option<String> {
+    let mut parts = traceparent.split('-');
+    let _version = parts.next()?;
+    let trace_id = parts.next()?;
+    (trace_id.len() == 32 && trace_id.chars().all(|c| c.is_ascii_hexdigit()))
+        .then(|| trace_id.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test::{call_service, init_service, TestRequest};
+    use actix_web::{web, App, HttpResponse};
+
+    #[actix_web::test]
+    async fn request_id_header() {
+        let app = init_service(App::new().wrap(AccessLog).route(
+            "/collections/{collectionId}",
+            web::get().to(|req: HttpRequest| async move {
+                log_user(&req, "alice");
+                HttpResponse::Ok().body(request_id().unwrap_or_default())
+            }),
+        ))
+        .await;
+
+        let req = TestRequest::get()
+            .uri("/collections/countries")
+            .insert_header((REQUEST_ID_HEADER, "abc-123"))
+            .to_request();
+        let resp = call_service(&app, req).await;
+        assert_eq!(resp.headers().get(REQUEST_ID_HEADER).unwrap(), "abc-123");
+        let body = actix_web::test::read_body(resp).await;
+        assert_eq!(body, "abc-123");
+
+        let req = TestRequest::get()
+            .uri("/collections/countries")
+            .insert_header((REQUEST_ID_HEADER, "invalid id"))
+            .to_request();
+        let resp = call_service(&app, req).await;
+        let id = resp.headers().get(REQUEST_ID_HEADER).unwrap();
+        assert_ne!(id, "invalid id");
+        assert!(valid_request_id(id.to_str().unwrap()));
+    }
+
+    #[test]
+    fn traceparent() {
+        assert_eq!(
+            trace_id("00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01").as_deref(),
+            Some("4bf92f3577b34da6a3ce929d0e0e4736")
+        );
+        assert_eq!(trace_id("00-xyz-00f067aa0ba902b7-01"), None);
+    }
+}
@@ -1,4 +1,6 @@
 use crate::ogcapi::*;
+use async_trait::async_trait;
+use std::sync::Arc;
 
 #[derive(Clone, Default)]
 pub struct OgcApiInventory {
@@ -7,32 +9,66 @@ pub struct OgcApiInventory {
     pub collections: Vec<CoreCollection>,
 }
 
+/// OpenAPI paths and components generated at runtime, e.g. from the configured collections
+#[async_trait]
+pub trait OpenApiProvider: Send + Sync {
+    /// OpenAPI document with `paths` and `components` to merge into the static document
+    async fn openapi_doc(&self) -> serde_json::Value;
+}
+
 /// OpenAPi doc collection
 #[derive(Default, Clone)]
-pub struct OpenApiDoc(serde_yaml::Value);
+pub struct OpenApiDoc {
+    doc: serde_yaml::Value,
+    providers: Vec<Arc<dyn OpenApiProvider>>,
+}
 
 impl OpenApiDoc {
     pub fn new() -> Self {
         Self::from_yaml("{}", "")
     }
     pub fn from_yaml(yaml: &str, _prefix: &str) -> Self {
-        OpenApiDoc(serde_yaml::from_str(yaml).unwrap())
+        OpenApiDoc {
+            doc: serde_yaml::from_str(yaml).unwrap(),
+            providers: Vec::new(),
+        }
     }
     pub fn is_empty(&self) -> bool {
-        self.0 == Self::new().0
+        self.doc == Self::new().doc
     }
     /// Merge `paths` and `components` of new yaml into exisiting yaml
     pub fn extend(&mut self, yaml: &str, _prefix: &str) {
         let rhs_yaml = serde_yaml::from_str(yaml).unwrap();
-        merge_level(&mut self.0, &rhs_yaml, "paths");
+        self.merge(&rhs_yaml);
+    }
+    /// Add generator of runtime paths
+    pub fn add_provider(&mut self, provider: Arc<dyn OpenApiProvider>) {
+        self.providers.push(provider);
+    }
+    /// Static document extended with the paths of all providers
+    pub async fn generate(&self) -> Self {
+        let mut doc = OpenApiDoc {
+            doc: self.doc.clone(),
+            providers: Vec::new(),
+        };
+        for provider in &self.providers {
+            match serde_yaml::to_value(provider.openapi_doc().await) {
+                Ok(rhs_yaml) => doc.merge(&rhs_yaml),
+                Err(e) => log::warn!("Invalid generated OpenAPI document: {e}"),
+            }
+        }
+        doc
+    }
+    fn merge(&mut self, rhs_yaml: &serde_yaml::Value) {
+        merge_level(&mut self.doc, rhs_yaml, "paths");
         if let Some(rhs_components) = rhs_yaml.get("components") {
-            if let Some(components) = self.0.get_mut("components") {
+            if let Some(components) = self.doc.get_mut("components") {
                 // merge 1st level children ("parameters", "responses", "schemas")
                 for (key, _val) in rhs_components.as_mapping().unwrap().iter() {
                     merge_level(components, rhs_components, key.as_str().unwrap());
                 }
             } else {
-                self.0
+                self.doc
                     .as_mapping_mut()
                     .unwrap()
                     .insert("components".into(), rhs_components.clone());
@@ -41,7 +77,7 @@ impl OpenApiDoc {
     }
     /// Set url of first server entry
     pub fn set_server_url(&mut self, url: &str) {
-        if let Some(servers) = self.0.get_mut("servers") {
+        if let Some(servers) = self.doc.get_mut("servers") {
             if let Some(server) = servers.get_mut(0) {
                 if let Some(server) = server.as_mapping_mut() {
                     server[&"url".to_string().into()] = url.to_string().into();
@@ -52,12 +88,12 @@ impl OpenApiDoc {
     pub fn as_yaml(&self, public_server_url: &str) -> String {
         let mut doc = self.clone();
         doc.set_server_url(public_server_url);
-        serde_yaml::to_string(&doc.0).unwrap()
+        serde_yaml::to_string(&doc.doc).unwrap()
     }
     pub fn as_json(&self, public_server_url: &str) -> serde_json::Value {
         let mut doc = self.clone();
         doc.set_server_url(public_server_url);
-        serde_yaml::from_value::<serde_json::Value>(doc.0).unwrap()
+        serde_yaml::from_value::<serde_json::Value>(doc.doc).unwrap()
     }
 }
 
@@ -313,4 +349,27 @@ components:
         doc.extend(yaml, "");
         assert_eq!(doc.as_yaml("http://bbox:8080/"), yamlout);
     }
+
+    struct Paths;
+
+    #[async_trait]
+    impl OpenApiProvider for Paths {
+        async fn openapi_doc(&self) -> serde_json::Value {
+            serde_json::json!({"paths": {"/collections/lakes/items": {"get": {"operationId": "getLakes"}}}})
+        }
+    }
+
+    #[actix_web::test]
+    async fn generated_paths() {
+        let mut doc = OpenApiDoc::from_yaml(YAML_BASE, "");
+        doc.add_provider(Arc::new(Paths));
+        let json = doc.generate().await.as_json("http://bbox:8080/");
+        assert_eq!(
+            json["paths"]["/collections/lakes/items"]["get"]["operationId"],
+            "getLakes"
+        );
+        assert!(json["paths"]["/conformance"].is_object());
+        // Static document is unchanged
+        assert!(doc.as_json("http://bbox:8080/")["paths"]["/collections/lakes/items"].is_null());
+    }
 }
@@ -0,0 +1,113 @@
+//! JSON error responses with configurable level of detail.
+
+use crate::access_log::request_id;
+use crate::config::ErrorMode;
+use actix_web::{http::StatusCode, HttpResponse};
+use log::{error, warn};
+use once_cell::sync::OnceCell;
+use serde_json::json;
+use std::error::Error;
+
+static ERROR_MODE: OnceCell<ErrorMode> = OnceCell::new();
+
+/// Set error mode of API responses. Can be set only once.
+pub fn set_error_mode(mode: ErrorMode) {
+    if ERROR_MODE.set(mode).is_err() {
+        warn!("Error mode already set");
+    }
+}
+
+fn error_mode() -> ErrorMode {
+    ERROR_MODE.get().copied().unwrap_or_default()
+}
+
+/// Error response with stable error `code`.
+///
+/// Responses contain the request id of the corresponding log entry.
+/// In development mode, the error chain and SQL state are included.
+pub fn error_response(
+    status: StatusCode,
+    code: &str,
+    description: &str,
+    err: &(dyn Error + 'static),
+) -> HttpResponse {
+    if status.is_server_error() {
+        error!("{code}: {}", error_chain(err).join(": "));
+    }
+    let mut body = json!({
+        "code": code,
+        "description": description,
+    });
+    if let Some(request_id) = request_id() {
+        body["request_id"] = json!(request_id);
+    }
+    if error_mode() == ErrorMode::Development {
+        body["detail"] = json!(error_chain(err));
+        if let Some(sqlstate) = sqlstate(err) {
+            body["sqlstate"] = json!(sqlstate);
+        }
+    }
+    HttpResponse::build(status).json(body)
+}
+
+/// Messages of error and its sources
+fn error_chain(err: &(dyn Error + 'static)) -> Vec<String> {
+    let mut chain = vec![err.to_string()];
+    let mut source = err.source();
+    while let Some(err) = source {
+        let msg = err.to_string();
+        // Transparent errors repeat the message of their source
+        if chain.last() != Some(&msg) {
+            chain.push(msg);
+        }
+        source = err.source();
+    }
+    chain
+}
+
+/// SQLSTATE of database error
+pub fn sqlstate(err: &(dyn Error + 'static)) -> Option<String> {
+    let mut source = Some(err);
+    while let Some(err) = source {
+        if let Some(sqlx::Error::Database(db_err)) = err.downcast_ref::<sqlx::Error>() {
+            return db_err.code().map(|code| code.to_string());
+        }
+        source = err.source();
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::body::to_bytes;
+
+    #[derive(thiserror::Error, Debug)]
+    #[error("query failed")]
+    struct QueryError(#[source] std::io::Error);
+
+    #[actix_web::test]
+    async fn production_response() {
+        let err = QueryError(std::io::Error::new(
+            std::io::ErrorKind::Other,
+            "connection refused",
+        ));
+        assert_eq!(
+            error_chain(&err),
+            vec!["query failed", "connection refused"]
+        );
+
+        let resp = error_response(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "DatasourceUnavailable",
+            "Service Unavailable",
+            &err,
+        );
+        assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+        let body = to_bytes(resp.into_body()).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["code"], "DatasourceUnavailable");
+        // Error details are not exposed by default
+        assert!(body.get("detail").is_none());
+    }
+}
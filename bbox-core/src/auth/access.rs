@@ -0,0 +1,721 @@
+//! Access control middleware.
+
+use super::jwt::JwtValidator;
+use super::Identity;
+use crate::access_log::log_user;
+use crate::config::{AccessRuleCfg, AuthCfg};
+use actix_session::SessionExt;
+use actix_web::body::EitherBody;
+use actix_web::dev::{forward_ready, Payload, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::error::PayloadError;
+use actix_web::guard::{Guard, GuardContext};
+use actix_web::web::{self, Bytes, BytesMut};
+use actix_web::{http::header, http::Method, Error, HttpMessage, HttpRequest, HttpResponse};
+use futures::StreamExt;
+use log::{debug, error};
+use serde::Deserialize;
+use std::future::{ready, Future, Ready};
+use std::pin::Pin;
+use std::rc::Rc;
+use std::sync::Arc;
+
+/// Routes reading a collection, followed by the collection id
+const COLLECTION_ROUTES: &[&str] = &["/collections/", "/admin/collections/"];
+
+/// Routes reading a tileset, followed by the tileset id
+const TILESET_ROUTES: &[&str] = &[
+    "/xyz/",
+    "/map/tiles/",
+    "/tiles/",
+    "/maplibre/config/",
+    "/admin/tilesets/",
+];
+
+/// Maximal size of a search request body inspected for access rules
+const MAX_SEARCH_BODY: usize = 1024 * 1024;
+
+/// Path of administration endpoints
+const ADMIN_PATH: &str = "/admin";
+
+/// Default group with access to administration endpoints
+const ADMIN_GROUP: &str = "admin";
+
+/// Collections read by a cross-collection search request
+#[derive(Debug, PartialEq)]
+pub enum SearchedCollections {
+    /// Search without `collections` parameter
+    All,
+    List(Vec<String>),
+}
+
+impl SearchedCollections {
+    /// Collections of `/search` GET request parameters
+    pub fn from_query(query_string: &str) -> Self {
+        let Ok(params) = web::Query::<Vec<(String, String)>>::from_query(query_string) else {
+            return SearchedCollections::All;
+        };
+        let mut collections = None;
+        for (key, val) in params.iter() {
+            if key.eq_ignore_ascii_case("collections") {
+                collections
+                    .get_or_insert_with(Vec::new)
+                    .extend(val.split(',').map(str::to_string));
+            }
+        }
+        collections.map_or(SearchedCollections::All, SearchedCollections::List)
+    }
+    /// Collections of `/search` POST request body
+    pub fn from_body(body: &[u8]) -> Self {
+        #[derive(Deserialize)]
+        struct SearchBody {
+            collections: Option<Vec<String>>,
+        }
+        match serde_json::from_slice::<SearchBody>(body) {
+            Ok(SearchBody {
+                collections: Some(collections),
+            }) => SearchedCollections::List(collections),
+            _ => SearchedCollections::All,
+        }
+    }
+}
+
+impl AccessRuleCfg {
+    /// Check whether the rule applies to a request path
+    pub fn matches(&self, path: &str) -> bool {
+        let matches_prefix = |prefix: &str, separators: &[char]| {
+            path.strip_prefix(prefix)
+                .map(|rest| rest.is_empty() || rest.starts_with(separators))
+                .unwrap_or(false)
+        };
+        if let Some(prefix) = &self.path {
+            if matches_prefix(prefix.trim_end_matches('/'), &['/']) {
+                return true;
+            }
+        }
+        if let Some(collection) = &self.collection {
+            if COLLECTION_ROUTES
+                .iter()
+                .any(|route| matches_prefix(&format!("{route}{collection}"), &['/', '.']))
+            {
+                return true;
+            }
+        }
+        if let Some(tileset) = &self.tileset {
+            if TILESET_ROUTES
+                .iter()
+                .any(|route| matches_prefix(&format!("{route}{tileset}"), &['/', '.']))
+            {
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Check whether the rule applies to the collections of a search request
+    pub fn matches_search(&self, searched: &SearchedCollections) -> bool {
+        match (&self.collection, searched) {
+            (None, _) => false,
+            (Some(_), SearchedCollections::All) => true,
+            (Some(collection), SearchedCollections::List(collections)) => {
+                collections.contains(collection)
+            }
+        }
+    }
+
+    /// Check whether the rule applies to a request method
+    pub fn matches_method(&self, method: &Method) -> bool {
+        self.methods.is_empty()
+            || self
+                .methods
+                .iter()
+                .any(|m| m.eq_ignore_ascii_case(method.as_str()))
+    }
+
+    /// Check whether the identity is granted access
+    pub fn allows(&self, identity: &Identity) -> bool {
+        if self.groups.is_empty() && self.users.is_empty() {
+            return true;
+        }
+        self.users.contains(&identity.username)
+            || identity
+                .groups
+                .iter()
+                .any(|group| self.groups.contains(group))
+    }
+}
+
+/// Request extension of admin requests authorized by the access control middleware
+#[derive(Clone, Copy, Debug)]
+struct AdminAccess;
+
+/// Identity of a request authorized by the [AccessControl] middleware.
+/// Requests to paths without matching access rule have no authorized identity.
+pub fn authorized_identity(req: &HttpRequest) -> Option<Identity> {
+    req.extensions().get::<Identity>().cloned()
+}
+
+/// Route guard for admin endpoints.
+/// Only requests authorized by the [AccessControl] middleware are passed,
+/// so that admin endpoints are not reachable in applications without access control.
+pub struct AdminGuard;
+
+impl Guard for AdminGuard {
+    fn check(&self, ctx: &GuardContext<'_>) -> bool {
+        ctx.req_data().contains::<AdminAccess>()
+    }
+}
+
+/// Access control middleware factory
+#[derive(Clone)]
+pub struct AccessControl {
+    rules: Arc<Vec<AccessRuleCfg>>,
+    /// Built-in rule for administration endpoints, which are never public
+    admin_rule: Arc<AccessRuleCfg>,
+    jwt: Option<Arc<JwtValidator>>,
+    /// Prefix of request paths, which is not part of the rule paths
+    path_prefix: Option<String>,
+}
+
+impl Default for AccessControl {
+    fn default() -> Self {
+        AccessControl {
+            rules: Arc::new(Vec::new()),
+            admin_rule: Arc::new(admin_rule(vec![ADMIN_GROUP.to_string()])),
+            jwt: None,
+            path_prefix: None,
+        }
+    }
+}
+
+fn admin_rule(groups: Vec<String>) -> AccessRuleCfg {
+    AccessRuleCfg {
+        path: Some(ADMIN_PATH.to_string()),
+        collection: None,
+        tileset: None,
+        methods: Vec::new(),
+        groups,
+        users: Vec::new(),
+    }
+}
+
+impl AccessControl {
+    pub async fn from_config(cfg: &AuthCfg) -> Self {
+        // Bearer tokens are rejected with an invalid JWT configuration
+        let jwt = match cfg.jwt.as_ref().map(JwtValidator::from_config) {
+            Some(Ok(jwt)) => Some(Arc::new(jwt)),
+            Some(Err(e)) => {
+                error!("Invalid JWT configuration: {e}");
+                None
+            }
+            None => None,
+        };
+        let admin_groups = cfg
+            .admin_groups
+            .clone()
+            .unwrap_or(vec![ADMIN_GROUP.to_string()]);
+        AccessControl {
+            rules: Arc::new(cfg.rules.clone()),
+            admin_rule: Arc::new(admin_rule(admin_groups)),
+            jwt,
+            path_prefix: None,
+        }
+    }
+    /// Apply rules to endpoints mounted under a path prefix, e.g. `/t/{tenant}`
+    pub fn with_path_prefix(mut self, prefix: &str) -> Self {
+        self.path_prefix = Some(prefix.to_string());
+        self
+    }
+    pub fn has_rules(&self) -> bool {
+        !self.rules.is_empty()
+    }
+    fn has_collection_rules(&self) -> bool {
+        self.rules.iter().any(|rule| rule.collection.is_some())
+    }
+    /// Request path relative to the path prefix
+    fn rule_path<'a>(&self, path: &'a str) -> &'a str {
+        match &self.path_prefix {
+            Some(prefix) => path.strip_prefix(prefix.as_str()).unwrap_or(path),
+            None => path,
+        }
+    }
+    /// Identity from bearer token or login session
+    async fn identity(&self, req: &ServiceRequest) -> Option<Identity> {
+        let token = req
+            .headers()
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .map(str::to_string);
+        if let Some(token) = token {
+            let Some(jwt) = &self.jwt else {
+                debug!("Bearer token without JWT configuration");
+                return None;
+            };
+            return match jwt.validate(&token).await {
+                Ok(identity) => Some(identity),
+                Err(e) => {
+                    debug!("Invalid bearer token: {e}");
+                    None
+                }
+            };
+        }
+        let session = req.get_session();
+        let username = session.get::<String>("username").ok().flatten()?;
+        let groups = session
+            .get::<Vec<String>>("groups")
+            .ok()
+            .flatten()
+            .unwrap_or_default();
+        Some(Identity { username, groups })
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for AccessControl
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Transform = AccessControlMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(AccessControlMiddleware {
+            service: Rc::new(service),
+            access: self.clone(),
+        }))
+    }
+}
+
+pub struct AccessControlMiddleware<S> {
+    service: Rc<S>,
+    access: AccessControl,
+}
+
+impl<S, B> Service<ServiceRequest> for AccessControlMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    #[allow(clippy::type_complexity)]
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    forward_ready!(service);
+
+    fn call(&self, mut req: ServiceRequest) -> Self::Future {
+        let service = self.service.clone();
+        let access = self.access.clone();
+        Box::pin(async move {
+            // Percent-decoded request path as used for routing
+            let path = access.rule_path(req.match_info().as_str()).to_string();
+            let searched = if path == "/search" && access.has_collection_rules() {
+                if req.method() == Method::POST {
+                    let mut payload = req.take_payload();
+                    let mut body = BytesMut::new();
+                    while let Some(chunk) = payload.next().await {
+                        let chunk = chunk?;
+                        if body.len() + chunk.len() > MAX_SEARCH_BODY {
+                            let response = HttpResponse::PayloadTooLarge().finish();
+                            return Ok(req.into_response(response).map_into_right_body());
+                        }
+                        body.extend_from_slice(&chunk);
+                    }
+                    let body: Bytes = body.freeze();
+                    let searched = SearchedCollections::from_body(&body);
+                    req.set_payload(Payload::Stream {
+                        payload: Box::pin(futures::stream::once(async move {
+                            Ok::<_, PayloadError>(body)
+                        })),
+                    });
+                    Some(searched)
+                } else {
+                    Some(SearchedCollections::from_query(req.query_string()))
+                }
+            } else {
+                None
+            };
+            let rules: Vec<&AccessRuleCfg> = access
+                .rules
+                .iter()
+                .chain(std::iter::once(access.admin_rule.as_ref()))
+                .filter(|rule| {
+                    rule.matches_method(req.method())
+                        && (rule.matches(&path)
+                            || searched
+                                .as_ref()
+                                .map(|searched| rule.matches_search(searched))
+                                .unwrap_or(false))
+                })
+                .collect();
+            if !rules.is_empty() {
+                let response = match access.identity(&req).await {
+                    None => Some(
+                        HttpResponse::Unauthorized()
+                            .insert_header((header::WWW_AUTHENTICATE, "Bearer"))
+                            .finish(),
+                    ),
+                    Some(identity) if !rules.iter().all(|rule| rule.allows(&identity)) => {
+                        debug!("Access denied for user `{}`", identity.username);
+                        Some(HttpResponse::Forbidden().finish())
+                    }
+                    Some(identity) => {
+                        log_user(req.request(), &identity.username);
+                        if access.admin_rule.matches(&path) {
+                            req.extensions_mut().insert(AdminAccess);
+                        }
+                        req.extensions_mut().insert(identity);
+                        None
+                    }
+                };
+                if let Some(response) = response {
+                    return Ok(req.into_response(response).map_into_right_body());
+                }
+            }
+            service
+                .call(req)
+                .await
+                .map(ServiceResponse::map_into_left_body)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test::{call_service, init_service, TestRequest};
+    use actix_web::{http::StatusCode, web, App};
+    use figment::providers::{Format, Toml};
+
+    fn rule(toml: &str) -> AccessRuleCfg {
+        figment::Figment::new()
+            .merge(Toml::string(toml))
+            .extract()
+            .unwrap()
+    }
+
+    #[test]
+    fn rule_matching() {
+        let r = rule(r#"collection = "private""#);
+        assert!(r.matches("/collections/private"));
+        assert!(r.matches("/collections/private/items/1"));
+        assert!(r.matches("/collections/private.json"));
+        assert!(!r.matches("/collections/private_public"));
+        assert!(!r.matches("/collections"));
+        assert!(r.matches("/admin/collections/private/refresh"));
+        assert!(!r.matches("/search"));
+
+        let r = rule(r#"tileset = "cadastre""#);
+        assert!(r.matches("/xyz/cadastre/1/0/0.mvt"));
+        assert!(r.matches("/xyz/cadastre.style.json"));
+        assert!(!r.matches("/xyz/cadastre2.json"));
+        assert!(r.matches("/map/tiles/cadastre/1/0/0"));
+        assert!(r.matches("/tiles/cadastre"));
+        assert!(!r.matches("/tiles/cadastre2"));
+        assert!(r.matches("/admin/tilesets/cadastre/seed"));
+
+        let r = rule(r#"path = "/processes/""#);
+        assert!(r.matches("/processes"));
+        assert!(r.matches("/processes/job/execution"));
+        assert!(!r.matches("/processes-list"));
+    }
+
+    #[test]
+    fn search_collections() {
+        use SearchedCollections::*;
+        assert_eq!(SearchedCollections::from_query(""), All);
+        assert_eq!(SearchedCollections::from_query("limit=10"), All);
+        assert_eq!(
+            SearchedCollections::from_query("collections=a,b&COLLECTIONS=c"),
+            List(vec!["a".to_string(), "b".to_string(), "c".to_string()])
+        );
+        assert_eq!(SearchedCollections::from_body(b"{}"), All);
+        assert_eq!(SearchedCollections::from_body(b"invalid"), All);
+        assert_eq!(
+            SearchedCollections::from_body(br#"{"collections": ["a"], "limit": 5}"#),
+            List(vec!["a".to_string()])
+        );
+
+        let r = rule(r#"collection = "private""#);
+        assert!(r.matches_search(&All));
+        assert!(r.matches_search(&List(vec!["a".to_string(), "private".to_string()])));
+        assert!(!r.matches_search(&List(vec!["a".to_string()])));
+        assert!(!rule(r#"tileset = "cadastre""#).matches_search(&All));
+    }
+
+    #[actix_web::test]
+    async fn admin_endpoints() {
+        let app = init_service(
+            App::new()
+                .wrap(AccessControl::default())
+                .default_service(web::to(|| async { HttpResponse::Ok().finish() })),
+        )
+        .await;
+        let req = TestRequest::post().uri("/admin/reload").to_request();
+        let resp = call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+        let req = TestRequest::get().uri("/collections").to_request();
+        let resp = call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let identity = Identity {
+            username: "alice".to_string(),
+            groups: vec!["staff".to_string()],
+        };
+        let access = AccessControl::default();
+        assert!(!access.admin_rule.allows(&identity));
+        let cfg = AuthCfg {
+            admin_groups: Some(vec!["staff".to_string()]),
+            ..Default::default()
+        };
+        let access = AccessControl::from_config(&cfg).await;
+        assert!(access.admin_rule.allows(&identity));
+    }
+
+    #[actix_web::test]
+    async fn admin_guard() {
+        let admin_resource = || {
+            web::resource("/admin/jobs")
+                .guard(AdminGuard)
+                .to(HttpResponse::Ok)
+        };
+        // Not reachable without access control
+        let app = init_service(App::new().service(admin_resource())).await;
+        let req = TestRequest::get().uri("/admin/jobs").to_request();
+        let resp = call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+        let app = init_service(
+            App::new()
+                .service(admin_resource())
+                .wrap(AccessControl::default())
+                .wrap_fn(|req, srv| {
+                    let session = req.get_session();
+                    session.insert("username", "alice").unwrap();
+                    session.insert("groups", vec!["admin"]).unwrap();
+                    srv.call(req)
+                }),
+        )
+        .await;
+        let req = TestRequest::get().uri("/admin/jobs").to_request();
+        let resp = call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+
+    #[test]
+    fn rule_access() {
+        let identity = Identity {
+            username: "alice".to_string(),
+            groups: vec!["staff".to_string()],
+        };
+        assert!(rule(r#"path = "/""#).allows(&identity));
+        assert!(rule(r#"groups = ["admin", "staff"]"#).allows(&identity));
+        assert!(rule(r#"users = ["alice"]"#).allows(&identity));
+        assert!(!rule(r#"groups = ["admin"]"#).allows(&identity));
+    }
+
+    #[actix_web::test]
+    async fn protected_endpoints() {
+        let access = AccessControl {
+            rules: Arc::new(vec![rule(
+                r#"collection = "private"
+                   groups = ["admin"]"#,
+            )]),
+            ..Default::default()
+        };
+        let app = init_service(
+            App::new()
+                .wrap(access)
+                .default_service(web::to(|| async { HttpResponse::Ok().finish() })),
+        )
+        .await;
+
+        let req = TestRequest::get()
+            .uri("/collections/public/items")
+            .to_request();
+        let resp = call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let req = TestRequest::get()
+            .uri("/collections/private/items")
+            .to_request();
+        let resp = call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(
+            resp.headers().get(header::WWW_AUTHENTICATE).unwrap(),
+            "Bearer"
+        );
+
+        // Tokens can't be validated without JWT configuration
+        let req = TestRequest::get()
+            .uri("/collections/private/items")
+            .insert_header((header::AUTHORIZATION, "Bearer abc"))
+            .to_request();
+        let resp = call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[actix_web::test]
+    async fn protected_routes() {
+        let access = AccessControl {
+            rules: Arc::new(vec![
+                rule(
+                    r#"collection = "private"
+                       groups = ["admin"]"#,
+                ),
+                rule(
+                    r#"tileset = "cadastre"
+                       groups = ["admin"]"#,
+                ),
+            ]),
+            ..Default::default()
+        };
+        let app = init_service(App::new().wrap(access).default_service(web::to(
+            |body: Bytes| async move { HttpResponse::Ok().body(body) },
+        )))
+        .await;
+
+        let refused = [
+            "/collections/private",
+            "/collections/private.json",
+            "/collections/private/queryables",
+            "/collections/private/schema",
+            "/collections/private/items",
+            "/collections/private/items.fgb",
+            "/collections/private/items/1",
+            "/collections/private/changes",
+            "/collections/private/aggregate",
+            "/collections/private/download",
+            "/collections/priv%61te/items",
+            "/admin/collections/private/refresh",
+            "/search",
+            "/search?limit=10",
+            "/search?collections=private",
+            "/search?collections=public,private",
+            "/xyz/cadastre/1/0/0.mvt",
+            "/xyz/cadastre.json",
+            "/xyz/cadastre.style.json",
+            "/xyz/cadastre/metadata.json",
+            "/map/tiles/cadastre/1/0/0",
+            "/tiles/cadastre",
+            "/maplibre/config/cadastre.json",
+            "/admin/tilesets/cadastre/seed",
+            "/admin/reload",
+            "/admin/jobs",
+            "/admin/jobs/1",
+            "/admin/tilesets/osm/expire",
+            "/admin/collections/public/refresh",
+        ];
+        for uri in refused {
+            let req = TestRequest::get().uri(uri).to_request();
+            let resp = call_service(&app, req).await;
+            assert_eq!(resp.status(), StatusCode::UNAUTHORIZED, "{uri}");
+        }
+        for body in [r#"{"collections": ["private"]}"#, r#"{"limit": 10}"#] {
+            let req = TestRequest::post()
+                .uri("/search")
+                .set_payload(body)
+                .to_request();
+            let resp = call_service(&app, req).await;
+            assert_eq!(resp.status(), StatusCode::UNAUTHORIZED, "{body}");
+        }
+
+        let allowed = [
+            "/collections",
+            "/collections/public/items",
+            "/search?collections=public",
+            "/xyz/osm/1/0/0.mvt",
+            "/map/tiles/osm/1/0/0",
+            "/tiles/osm",
+            "/tiles",
+        ];
+        for uri in allowed {
+            let req = TestRequest::get().uri(uri).to_request();
+            let resp = call_service(&app, req).await;
+            assert_eq!(resp.status(), StatusCode::OK, "{uri}");
+        }
+        // Search body is passed to the endpoint
+        let body = r#"{"collections": ["public"]}"#;
+        let req = TestRequest::post()
+            .uri("/search")
+            .set_payload(body)
+            .to_request();
+        let resp = call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(actix_web::test::read_body(resp).await, body);
+    }
+
+    #[actix_web::test]
+    async fn write_rules() {
+        let access = AccessControl {
+            rules: Arc::new(vec![rule(
+                r#"collection = "places"
+                   methods = ["POST", "put", "PATCH", "DELETE"]
+                   groups = ["editors"]"#,
+            )]),
+            ..Default::default()
+        };
+        let app = init_service(
+            App::new()
+                .wrap(access)
+                .default_service(web::to(|req: HttpRequest| async move {
+                    match authorized_identity(&req) {
+                        Some(identity) => HttpResponse::Ok().body(identity.username),
+                        None => HttpResponse::Ok().finish(),
+                    }
+                }))
+                .wrap_fn(|req, srv| {
+                    if req.headers().contains_key("X-Test-User") {
+                        let session = req.get_session();
+                        session.insert("username", "alice").unwrap();
+                        session.insert("groups", vec!["editors"]).unwrap();
+                    }
+                    srv.call(req)
+                }),
+        )
+        .await;
+
+        let req = TestRequest::get()
+            .uri("/collections/places/items/1")
+            .to_request();
+        let resp = call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(actix_web::test::read_body(resp).await, "");
+
+        for method in [Method::POST, Method::PUT, Method::PATCH, Method::DELETE] {
+            let req = TestRequest::default()
+                .method(method.clone())
+                .uri("/collections/places/items/1")
+                .to_request();
+            let resp = call_service(&app, req).await;
+            assert_eq!(resp.status(), StatusCode::UNAUTHORIZED, "{method}");
+        }
+
+        let req = TestRequest::put()
+            .uri("/collections/places/items/1")
+            .insert_header(("X-Test-User", "1"))
+            .to_request();
+        let resp = call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(actix_web::test::read_body(resp).await, "alice");
+    }
+
+    #[test]
+    fn tenant_rule_paths() {
+        let access = AccessControl {
+            rules: Arc::new(vec![rule(r#"collection = "private""#)]),
+            ..Default::default()
+        }
+        .with_path_prefix("/t/acme");
+        let path = access.rule_path("/t/acme/collections/private/items");
+        assert_eq!(path, "/collections/private/items");
+        assert!(access.rules[0].matches(path));
+        assert!(!access.rules[0].matches(access.rule_path("/collections/public")));
+        assert!(access
+            .admin_rule
+            .matches(access.rule_path("/t/acme/admin/jobs")));
+    }
+}
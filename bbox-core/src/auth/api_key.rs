@@ -0,0 +1,552 @@
+//! API key authentication with per-key rate limiting.
+
+use crate::access_log::log_api_key;
+use crate::config::{ApiKeyTableCfg, ApiKeysCfg};
+use crate::pg_ds::{self, PgDatasource};
+use crate::sql::{InvalidSqlIdent, SqlIdent};
+use actix_web::body::EitherBody;
+use actix_web::dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::http::{header, Uri};
+use actix_web::{web, Error, HttpResponse};
+use log::{info, warn};
+use once_cell::sync::OnceCell;
+use prometheus::{IntCounterVec, Registry};
+use sqlx::Row;
+use std::collections::HashMap;
+use std::future::{ready, Future, Ready};
+use std::pin::Pin;
+use std::rc::Rc;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+use thiserror::Error;
+
+/// Tile and feature endpoints
+const DEFAULT_PATHS: [&str; 5] = ["/xyz", "/map/tiles", "/tiles", "/collections", "/search"];
+const RATE_LIMIT_WINDOW: Duration = Duration::from_secs(60);
+const HEADER_NAME: &str = "X-Api-Key";
+const QUERY_PARAM: &str = "key";
+/// Default caching duration of keys from key table
+const KEY_CACHE_TTL: Duration = Duration::from_secs(60);
+/// Caching duration of keys not found in key table
+const INVALID_KEY_CACHE_TTL: Duration = Duration::from_secs(10);
+/// Maximal number of cached key table lookups
+const MAX_CACHED_KEYS: usize = 10_000;
+
+#[derive(Error, Debug)]
+pub enum ApiKeyError {
+    #[error("invalid API key table name: {0}")]
+    InvalidTableName(#[from] InvalidSqlIdent),
+    #[error("invalid API key database: {0}")]
+    DbError(#[from] pg_ds::Error),
+}
+
+#[derive(Clone, Debug)]
+struct ApiKey {
+    name: String,
+    rate_limit: Option<u32>,
+}
+
+impl ApiKey {
+    fn new(key: &str, name: Option<String>, rate_limit: Option<u32>) -> Self {
+        ApiKey {
+            name: name.unwrap_or_else(|| key.chars().take(4).collect::<String>() + "..."),
+            rate_limit,
+        }
+    }
+}
+
+/// Result of a key table lookup
+struct CachedKey {
+    /// None for keys not found in table
+    api_key: Option<ApiKey>,
+    expires: Instant,
+}
+
+/// PostgreSQL table with API keys
+struct KeyTable {
+    ds: PgDatasource,
+    /// Lookup query
+    sql: String,
+    cache_ttl: Duration,
+    cache: RwLock<HashMap<String, CachedKey>>,
+}
+
+impl KeyTable {
+    fn from_config(cfg: &ApiKeyTableCfg) -> Result<Self, ApiKeyError> {
+        let table = cfg.table.as_deref().unwrap_or("api_keys");
+        let table_name = table
+            .split('.')
+            .map(|part| SqlIdent::new(part).map(|ident| ident.quoted()))
+            .collect::<Result<Vec<_>, _>>()?
+            .join(".");
+        let ds = PgDatasource::new_lazy_pool(&cfg.url)?;
+        info!("Checking API keys in table `{table}`");
+        Ok(KeyTable {
+            ds,
+            sql: format!("SELECT name, rate_limit FROM {table_name} WHERE key = $1"),
+            cache_ttl: cfg.cache_ttl.map_or(KEY_CACHE_TTL, Duration::from_secs),
+            cache: RwLock::new(HashMap::new()),
+        })
+    }
+
+    /// Cached lookup result, if not expired
+    fn cached(&self, key: &str) -> Option<Option<ApiKey>> {
+        self.cache
+            .read()
+            .unwrap()
+            .get(key)
+            .filter(|cached| cached.expires > Instant::now())
+            .map(|cached| cached.api_key.clone())
+    }
+
+    fn cache_result(&self, key: &str, api_key: Option<ApiKey>) {
+        let ttl = if api_key.is_some() {
+            self.cache_ttl
+        } else {
+            INVALID_KEY_CACHE_TTL.min(self.cache_ttl)
+        };
+        let now = Instant::now();
+        let mut cache = self.cache.write().unwrap();
+        if cache.len() >= MAX_CACHED_KEYS {
+            cache.retain(|_, cached| cached.expires > now);
+            if cache.len() >= MAX_CACHED_KEYS {
+                return;
+            }
+        }
+        cache.insert(
+            key.to_string(),
+            CachedKey {
+                api_key,
+                expires: now + ttl,
+            },
+        );
+    }
+
+    /// Lookup key in cache or table
+    async fn lookup(&self, key: &str) -> Option<ApiKey> {
+        if let Some(api_key) = self.cached(key) {
+            return api_key;
+        }
+        let api_key = match sqlx::query(&self.sql)
+            .bind(key)
+            .fetch_optional(&self.ds.pool)
+            .await
+        {
+            Ok(Some(row)) => {
+                let name = row.try_get::<Option<String>, _>("name").ok().flatten();
+                let rate_limit = row
+                    .try_get::<Option<i32>, _>("rate_limit")
+                    .ok()
+                    .flatten()
+                    .map(|limit| limit.max(0) as u32);
+                Some(ApiKey::new(key, name, rate_limit))
+            }
+            Ok(None) => None,
+            Err(e) => {
+                // Not cached, the next request is checked again
+                warn!("API key lookup failed: {e}");
+                return None;
+            }
+        };
+        self.cache_result(key, api_key.clone());
+        api_key
+    }
+}
+
+/// Requests in current rate limit window
+struct Usage {
+    window_start: Instant,
+    requests: u32,
+}
+
+#[derive(Debug, PartialEq)]
+enum KeyCheck {
+    Missing,
+    Invalid,
+    RateLimited(Duration),
+    Granted,
+}
+
+struct ApiKeyStore {
+    paths: Vec<String>,
+    default_rate_limit: Option<u32>,
+    keys: HashMap<String, ApiKey>,
+    table: Option<KeyTable>,
+    usage: Mutex<HashMap<String, Usage>>,
+}
+
+impl ApiKeyStore {
+    fn protects(&self, path: &str) -> bool {
+        self.paths.iter().any(|prefix| {
+            path.strip_prefix(prefix.trim_end_matches('/'))
+                .map(|rest| rest.is_empty() || rest.starts_with('/'))
+                .unwrap_or(false)
+        })
+    }
+
+    async fn check(&self, key: Option<&str>) -> KeyCheck {
+        let Some(key) = key else {
+            return KeyCheck::Missing;
+        };
+        let Some(api_key) = self.lookup(key).await else {
+            api_key_metrics().with_label_values(&["", "invalid"]).inc();
+            return KeyCheck::Invalid;
+        };
+        let result = self.count_request(key, api_key.rate_limit.or(self.default_rate_limit));
+        let status = if result == KeyCheck::Granted {
+            "granted"
+        } else {
+            "rate_limited"
+        };
+        api_key_metrics()
+            .with_label_values(&[&api_key.name, status])
+            .inc();
+        result
+    }
+
+    /// Lookup configured key, falling back to key table
+    async fn lookup(&self, key: &str) -> Option<ApiKey> {
+        if let Some(api_key) = self.keys.get(key) {
+            return Some(api_key.clone());
+        }
+        self.table.as_ref()?.lookup(key).await
+    }
+
+    /// Name of a key checked before
+    fn key_name(&self, key: &str) -> Option<String> {
+        match self.keys.get(key) {
+            Some(api_key) => Some(api_key.name.clone()),
+            None => self
+                .table
+                .as_ref()?
+                .cached(key)
+                .flatten()
+                .map(|api_key| api_key.name),
+        }
+    }
+
+    /// Count request in fixed rate limit window
+    fn count_request(&self, key: &str, rate_limit: Option<u32>) -> KeyCheck {
+        let Some(rate_limit) = rate_limit else {
+            return KeyCheck::Granted;
+        };
+        let now = Instant::now();
+        let mut usage = self.usage.lock().unwrap();
+        let entry = usage.entry(key.to_string()).or_insert(Usage {
+            window_start: now,
+            requests: 0,
+        });
+        if now.duration_since(entry.window_start) >= RATE_LIMIT_WINDOW {
+            entry.window_start = now;
+            entry.requests = 0;
+        }
+        if entry.requests >= rate_limit {
+            return KeyCheck::RateLimited(
+                RATE_LIMIT_WINDOW.saturating_sub(now.duration_since(entry.window_start)),
+            );
+        }
+        entry.requests += 1;
+        KeyCheck::Granted
+    }
+}
+
+/// Request counter per API key
+fn api_key_metrics() -> &'static IntCounterVec {
+    static METRICS: OnceCell<IntCounterVec> = OnceCell::new();
+    METRICS.get_or_init(|| {
+        let opts =
+            prometheus::opts!("requests_total", "Requests with API key").namespace("bbox_api_key");
+        IntCounterVec::new(opts, &["key", "status"]).unwrap()
+    })
+}
+
+/// API key middleware factory
+#[derive(Clone, Default)]
+pub struct ApiKeyAuth {
+    store: Option<Arc<ApiKeyStore>>,
+}
+
+impl ApiKeyAuth {
+    pub async fn from_config(cfg: &ApiKeysCfg) -> Result<Self, ApiKeyError> {
+        let table = cfg
+            .postgres
+            .as_ref()
+            .map(KeyTable::from_config)
+            .transpose()?;
+        Ok(Self::with_table(cfg, table))
+    }
+    /// Check configured keys only, e.g. if the key table is not available
+    pub fn configured_keys(cfg: &ApiKeysCfg) -> Self {
+        Self::with_table(cfg, None)
+    }
+    fn with_table(cfg: &ApiKeysCfg, table: Option<KeyTable>) -> Self {
+        let keys = cfg
+            .keys
+            .iter()
+            .map(|k| {
+                (
+                    k.key.clone(),
+                    ApiKey::new(&k.key, k.name.clone(), k.rate_limit),
+                )
+            })
+            .collect();
+        let paths = cfg
+            .paths
+            .clone()
+            .unwrap_or_else(|| DEFAULT_PATHS.iter().map(|p| p.to_string()).collect());
+        let store = ApiKeyStore {
+            paths,
+            default_rate_limit: cfg.rate_limit,
+            keys,
+            table,
+            usage: Mutex::new(HashMap::new()),
+        };
+        ApiKeyAuth {
+            store: Some(Arc::new(store)),
+        }
+    }
+    pub fn is_enabled(&self) -> bool {
+        self.store.is_some()
+    }
+    pub fn register_metrics(&self, prometheus: &Registry) {
+        prometheus
+            .register(Box::new(api_key_metrics().clone()))
+            .unwrap();
+    }
+}
+
+/// API key from header or query parameter
+fn request_key(req: &ServiceRequest) -> Option<String> {
+    if let Some(key) = req
+        .headers()
+        .get(HEADER_NAME)
+        .and_then(|value| value.to_str().ok())
+    {
+        return Some(key.to_string());
+    }
+    web::Query::<HashMap<String, String>>::from_query(req.query_string())
+        .ok()
+        .and_then(|params| params.get(QUERY_PARAM).cloned())
+}
+
+/// Remove key parameter, which would otherwise be interpreted as filter
+fn strip_key_param(req: &mut ServiceRequest) {
+    let Some(query) = req.uri().query() else {
+        return;
+    };
+    let params: Vec<&str> = query
+        .split('&')
+        .filter(|param| param.split('=').next() != Some(QUERY_PARAM))
+        .collect();
+    let uri = if params.is_empty() {
+        req.path().to_string()
+    } else {
+        format!("{}?{}", req.path(), params.join("&"))
+    };
+    if let Ok(uri) = uri.parse::<Uri>() {
+        req.head_mut().uri = uri;
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for ApiKeyAuth
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Transform = ApiKeyMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(ApiKeyMiddleware {
+            service: Rc::new(service),
+            store: self.store.clone(),
+        }))
+    }
+}
+
+pub struct ApiKeyMiddleware<S> {
+    service: Rc<S>,
+    store: Option<Arc<ApiKeyStore>>,
+}
+
+impl<S, B> Service<ServiceRequest> for ApiKeyMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    #[allow(clippy::type_complexity)]
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    forward_ready!(service);
+
+    fn call(&self, mut req: ServiceRequest) -> Self::Future {
+        let service = self.service.clone();
+        let store = self.store.clone();
+        Box::pin(async move {
+            // Percent-decoded request path as used for routing
+            let path = req.match_info().as_str().to_string();
+            if let Some(store) = store.filter(|store| store.protects(&path)) {
+                let key = request_key(&req);
+                let response = match store.check(key.as_deref()).await {
+                    KeyCheck::Missing => {
+                        Some(HttpResponse::Unauthorized().body("API key required"))
+                    }
+                    KeyCheck::Invalid => Some(HttpResponse::Unauthorized().body("Invalid API key")),
+                    KeyCheck::RateLimited(retry) => Some(
+                        HttpResponse::TooManyRequests()
+                            .insert_header((header::RETRY_AFTER, retry.as_secs().max(1)))
+                            .body("Rate limit exceeded"),
+                    ),
+                    KeyCheck::Granted => None,
+                };
+                if let Some(response) = response {
+                    return Ok(req.into_response(response).map_into_right_body());
+                }
+                if let Some(name) = key.as_deref().and_then(|key| store.key_name(key)) {
+                    log_api_key(req.request(), &name);
+                }
+                strip_key_param(&mut req);
+            }
+            service
+                .call(req)
+                .await
+                .map(ServiceResponse::map_into_left_body)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ApiKeyCfg;
+    use actix_web::test::{call_service, init_service, read_body, TestRequest};
+    use actix_web::{http::StatusCode, App, HttpRequest};
+
+    async fn api_keys() -> ApiKeyAuth {
+        let cfg = ApiKeysCfg {
+            paths: None,
+            rate_limit: Some(2),
+            keys: vec![ApiKeyCfg {
+                key: "secret".to_string(),
+                name: Some("client".to_string()),
+                rate_limit: None,
+            }],
+            postgres: None,
+        };
+        ApiKeyAuth::from_config(&cfg).await.unwrap()
+    }
+
+    #[actix_web::test]
+    async fn key_check() {
+        let auth = api_keys().await;
+        let store = auth.store.as_ref().unwrap();
+        assert!(store.protects("/xyz/ne/0/0/0.mvt"));
+        assert!(store.protects("/collections"));
+        assert!(!store.protects("/xyzabc"));
+        assert!(!store.protects("/openapi.json"));
+
+        assert_eq!(store.check(None).await, KeyCheck::Missing);
+        assert_eq!(store.check(Some("wrong")).await, KeyCheck::Invalid);
+        assert_eq!(store.check(Some("secret")).await, KeyCheck::Granted);
+        assert_eq!(store.check(Some("secret")).await, KeyCheck::Granted);
+        assert!(matches!(
+            store.check(Some("secret")).await,
+            KeyCheck::RateLimited(_)
+        ));
+        assert_eq!(
+            api_key_metrics()
+                .with_label_values(&["client", "rate_limited"])
+                .get(),
+            1
+        );
+    }
+
+    #[actix_web::test]
+    async fn key_table() {
+        let table_cfg = |table: &str| ApiKeyTableCfg {
+            url: "postgresql://bbox@localhost/bbox".to_string(),
+            table: Some(table.to_string()),
+            cache_ttl: None,
+        };
+        let table = KeyTable::from_config(&table_cfg("auth.api_keys")).unwrap();
+        assert_eq!(
+            table.sql,
+            r#"SELECT name, rate_limit FROM "auth"."api_keys" WHERE key = $1"#
+        );
+        let table = KeyTable::from_config(&table_cfg("keys; DROP TABLE x")).unwrap();
+        assert!(table.sql.contains(r#"FROM "keys; DROP TABLE x" WHERE"#));
+        assert!(matches!(
+            KeyTable::from_config(&table_cfg("auth.")),
+            Err(ApiKeyError::InvalidTableName(_))
+        ));
+        let cfg = ApiKeyTableCfg {
+            url: "invalid".to_string(),
+            table: None,
+            cache_ttl: None,
+        };
+        assert!(matches!(
+            KeyTable::from_config(&cfg),
+            Err(ApiKeyError::DbError(_))
+        ));
+
+        let table = KeyTable::from_config(&table_cfg("api_keys")).unwrap();
+        assert!(table.cached("secret").is_none());
+        table.cache_result("secret", Some(ApiKey::new("secret", None, None)));
+        table.cache_result("wrong", None);
+        assert_eq!(table.cached("secret").unwrap().unwrap().name, "secr...");
+        assert!(table.cached("wrong").unwrap().is_none());
+        table
+            .cache
+            .write()
+            .unwrap()
+            .get_mut("secret")
+            .unwrap()
+            .expires = Instant::now();
+        assert!(table.cached("secret").is_none());
+    }
+
+    #[actix_web::test]
+    async fn key_param() {
+        let app =
+            init_service(App::new().wrap(api_keys().await).default_service(web::to(
+                |req: HttpRequest| async move {
+                    HttpResponse::Ok().body(req.query_string().to_string())
+                },
+            )))
+            .await;
+
+        let req = TestRequest::get().uri("/collections").to_request();
+        let resp = call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+
+        let req = TestRequest::get()
+            .uri("/%63ollections/ne/items")
+            .to_request();
+        let resp = call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+
+        let req = TestRequest::get()
+            .uri("/collections/ne/items?key=secret&limit=10")
+            .to_request();
+        let resp = call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(read_body(resp).await, "limit=10");
+
+        let req = TestRequest::get()
+            .uri("/collections")
+            .insert_header((HEADER_NAME, "secret"))
+            .to_request();
+        let resp = call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        // Public endpoint
+        let req = TestRequest::get().uri("/conformance").to_request();
+        let resp = call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+}
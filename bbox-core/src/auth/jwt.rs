@@ -0,0 +1,221 @@
+use super::oidc::AuthError;
+use super::Identity;
+use crate::config::JwtAuthCfg;
+use jsonwebtoken::{decode, decode_header, jwk::JwkSet, Algorithm, DecodingKey, Validation};
+use log::{info, warn};
+use serde_json::Value;
+use std::str::FromStr;
+use std::sync::RwLock;
+use std::time::{Duration, Instant};
+use tokio::sync::OnceCell;
+
+/// Minimal interval between JWKS requests triggered by unknown key ids
+const MIN_JWKS_REFRESH: Duration = Duration::from_secs(60);
+
+/// Signature algorithms accepted for keys without `alg` parameter
+const DEFAULT_ALGORITHMS: &[Algorithm] = &[
+    Algorithm::RS256,
+    Algorithm::RS384,
+    Algorithm::RS512,
+    Algorithm::PS256,
+    Algorithm::PS384,
+    Algorithm::PS512,
+    Algorithm::ES256,
+    Algorithm::ES384,
+    Algorithm::EdDSA,
+];
+
+/// Bearer token validation with signing keys of an OIDC issuer
+pub struct JwtValidator {
+    client: reqwest::Client,
+    issuer: String,
+    /// Configured or discovered JWKS URL
+    jwks_uri: OnceCell<String>,
+    audience: Option<String>,
+    algorithms: Vec<Algorithm>,
+    refresh_interval: Duration,
+    username_claim: Option<String>,
+    groupinfo_claim: String,
+    /// Signing keys with time of last fetch
+    jwks: RwLock<(JwkSet, Option<Instant>)>,
+}
+
+impl JwtValidator {
+    /// Create validator. Signing keys are fetched with the first token validation.
+    pub fn from_config(cfg: &JwtAuthCfg) -> Result<Self, AuthError> {
+        let algorithms = match &cfg.algorithms {
+            Some(names) => names
+                .iter()
+                .map(|name| Algorithm::from_str(name))
+                .collect::<Result<Vec<_>, _>>()?,
+            None => DEFAULT_ALGORITHMS.to_vec(),
+        };
+        let jwks_uri = OnceCell::new();
+        if let Some(uri) = &cfg.jwks_uri {
+            jwks_uri.set(uri.clone()).ok();
+        }
+        Ok(JwtValidator {
+            client: reqwest::Client::new(),
+            issuer: cfg.issuer_url.clone(),
+            jwks_uri,
+            audience: cfg.audience.clone(),
+            algorithms,
+            refresh_interval: Duration::from_secs(cfg.jwks_refresh.unwrap_or(3600)),
+            username_claim: cfg.username_claim.clone(),
+            groupinfo_claim: cfg.groupinfo_claim.clone().unwrap_or("group".to_string()),
+            jwks: RwLock::new((JwkSet { keys: Vec::new() }, None)),
+        })
+    }
+
+    /// Validate token signature and claims
+    pub async fn validate(&self, token: &str) -> Result<Identity, AuthError> {
+        let header = decode_header(token)?;
+        let kid = header.kid.ok_or(AuthError::JwtKeyIdMissing)?;
+        let (key, key_alg) = self.decoding_key(&kid).await?;
+        // The algorithm of the key takes precedence over the client controlled header
+        let allowed = match key_alg {
+            Some(alg) => self.algorithms.contains(&alg) && alg == header.alg,
+            None => self.algorithms.contains(&header.alg),
+        };
+        if !allowed {
+            return Err(AuthError::JwtAlgorithmNotAllowed(header.alg));
+        }
+        let mut validation = Validation::new(header.alg);
+        validation.set_issuer(&[&self.issuer]);
+        // `aud` is not validated without expected audience
+        if let Some(audience) = &self.audience {
+            validation.set_audience(&[audience]);
+        }
+        let data = decode::<Value>(token, &key, &validation)?;
+        Ok(Identity::from_claims(
+            &data.claims,
+            self.username_claim.as_deref(),
+            &self.groupinfo_claim,
+        ))
+    }
+
+    /// Lookup signing key, refreshing the key set when expired or for unknown keys
+    async fn decoding_key(&self, kid: &str) -> Result<(DecodingKey, Option<Algorithm>), AuthError> {
+        let (key, age) = self.cached_key(kid);
+        let refresh = match (&key, age) {
+            (_, None) => true,
+            (Some(_), Some(age)) => age > self.refresh_interval,
+            (None, Some(age)) => age > MIN_JWKS_REFRESH,
+        };
+        if refresh {
+            match self.fetch_jwks().await {
+                Ok(jwks) => {
+                    *self.jwks.write().unwrap() = (jwks, Some(Instant::now()));
+                    let (key, _) = self.cached_key(kid);
+                    return key.unwrap_or(Err(AuthError::JwtKeyNotFound(kid.to_string())));
+                }
+                Err(e) => warn!("Refreshing JWKS failed: {e}"),
+            }
+        }
+        key.unwrap_or(Err(AuthError::JwtKeyNotFound(kid.to_string())))
+    }
+
+    #[allow(clippy::type_complexity)]
+    fn cached_key(
+        &self,
+        kid: &str,
+    ) -> (
+        Option<Result<(DecodingKey, Option<Algorithm>), AuthError>>,
+        Option<Duration>,
+    ) {
+        let jwks = self.jwks.read().unwrap();
+        let key = jwks.0.find(kid).map(|jwk| {
+            DecodingKey::from_jwk(jwk)
+                .map(|key| (key, jwk.common.algorithm))
+                .map_err(Into::into)
+        });
+        (key, jwks.1.map(|fetched| fetched.elapsed()))
+    }
+
+    async fn fetch_jwks(&self) -> Result<JwkSet, AuthError> {
+        let jwks_uri = self
+            .jwks_uri
+            .get_or_try_init(|| async {
+                let url = format!(
+                    "{}/.well-known/openid-configuration",
+                    self.issuer.trim_end_matches('/')
+                );
+                info!("Fetching {url}");
+                let metadata: Value = fetch_json(&self.client, &url).await?;
+                metadata["jwks_uri"]
+                    .as_str()
+                    .map(str::to_string)
+                    .ok_or(AuthError::JwksUriMissing)
+            })
+            .await?;
+        fetch_json(&self.client, jwks_uri).await
+    }
+}
+
+async fn fetch_json<T: serde::de::DeserializeOwned>(
+    client: &reqwest::Client,
+    url: &str,
+) -> Result<T, AuthError> {
+    let text = client
+        .get(url)
+        .send()
+        .await?
+        .error_for_status()?
+        .text()
+        .await?;
+    Ok(serde_json::from_str(&text)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jsonwebtoken::{encode, EncodingKey, Header};
+    use serde_json::json;
+
+    fn jwt_validator(algorithms: Option<Vec<String>>) -> JwtValidator {
+        let cfg = JwtAuthCfg {
+            issuer_url: "https://idp.example.com".to_string(),
+            jwks_uri: Some("https://idp.example.com/certs".to_string()),
+            audience: None,
+            jwks_refresh: None,
+            algorithms,
+            username_claim: None,
+            groupinfo_claim: None,
+        };
+        let validator = JwtValidator::from_config(&cfg).unwrap();
+        let jwks = serde_json::from_value(json!({
+            "keys": [{"kty": "oct", "kid": "k1", "k": "c2VjcmV0"}]
+        }))
+        .unwrap();
+        *validator.jwks.write().unwrap() = (jwks, Some(Instant::now()));
+        validator
+    }
+
+    fn token(kid: Option<&str>) -> String {
+        let mut header = Header::new(Algorithm::HS256);
+        header.kid = kid.map(str::to_string);
+        let claims = json!({
+            "iss": "https://idp.example.com",
+            "exp": 4102444800u64,
+            "preferred_username": "alice"
+        });
+        encode(&header, &claims, &EncodingKey::from_secret(b"secret")).unwrap()
+    }
+
+    #[actix_web::test]
+    async fn token_validation() {
+        let validator = jwt_validator(None);
+        assert!(matches!(
+            validator.validate(&token(None)).await,
+            Err(AuthError::JwtKeyIdMissing)
+        ));
+        // Symmetric algorithms are not accepted by default
+        assert!(matches!(
+            validator.validate(&token(Some("k1"))).await,
+            Err(AuthError::JwtAlgorithmNotAllowed(Algorithm::HS256))
+        ));
+
+        let validator = jwt_validator(Some(vec!["HS256".to_string()]));
+        assert!(validator.validate(&token(Some("k1"))).await.is_ok());
+    }
+}
@@ -1,11 +1,48 @@
+pub mod access;
+pub mod api_key;
+#[cfg(feature = "oidc")]
+pub mod jwt;
 #[cfg(feature = "oidc")]
 pub mod oidc;
 
+use serde_json::Value;
+
+#[derive(Clone, Debug, PartialEq)]
 pub struct Identity {
     pub username: String,
     pub groups: Vec<String>,
 }
 
+impl Identity {
+    /// Extract identity from ID token or JWT claims
+    pub fn from_claims(
+        claims: &Value,
+        username_claim: Option<&str>,
+        groupinfo_claim: &str,
+    ) -> Self {
+        let username = if let Some(claim) = username_claim {
+            claims[claim].as_str()
+        } else {
+            claims
+                .get("preferred_username")
+                .or(claims.get("upn"))
+                .or(claims.get("email"))
+                .and_then(|v| v.as_str())
+        }
+        .unwrap_or("")
+        .to_string();
+        let groups = match &claims[groupinfo_claim] {
+            Value::String(s) => vec![s.as_str().to_string()],
+            Value::Array(arr) => arr
+                .iter()
+                .filter_map(|v| v.as_str().map(str::to_string))
+                .collect(),
+            _ => Vec::new(),
+        };
+        Identity { username, groups }
+    }
+}
+
 #[cfg(not(feature = "oidc"))]
 pub mod oidc {
     use super::Identity;
@@ -36,3 +73,26 @@ pub mod oidc {
         }
     }
 }
+
+#[cfg(not(feature = "oidc"))]
+pub mod jwt {
+    use super::Identity;
+    use crate::config::JwtAuthCfg;
+
+    type AuthError = std::io::Error;
+
+    pub struct JwtValidator;
+
+    impl JwtValidator {
+        pub fn from_config(_cfg: &JwtAuthCfg) -> Result<Self, AuthError> {
+            log::warn!("JWT validation requires feature `oidc`");
+            Ok(JwtValidator)
+        }
+        pub async fn validate(&self, _token: &str) -> Result<Identity, AuthError> {
+            Err(std::io::Error::new(
+                std::io::ErrorKind::Unsupported,
+                "JWT validation not supported",
+            ))
+        }
+    }
+}
@@ -8,7 +8,6 @@ use openidconnect::{
     StandardErrorResponse,
 };
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
 
 #[derive(thiserror::Error, Debug)]
 pub enum AuthError {
@@ -24,6 +23,20 @@ pub enum AuthError {
     OidcClaimsVerificationError(#[from] ClaimsVerificationError),
     #[error("Server did not return an ID token")]
     OpenidIdTokenError,
+    #[error(transparent)]
+    JwtError(#[from] jsonwebtoken::errors::Error),
+    #[error("JWT signing key `{0}` not found")]
+    JwtKeyNotFound(String),
+    #[error("JWT without key id")]
+    JwtKeyIdMissing,
+    #[error("JWT algorithm `{0:?}` not allowed")]
+    JwtAlgorithmNotAllowed(jsonwebtoken::Algorithm),
+    #[error("OpenID configuration without `jwks_uri`")]
+    JwksUriMissing,
+    #[error(transparent)]
+    HttpError(#[from] reqwest::Error),
+    #[error(transparent)]
+    JsonError(#[from] serde_json::Error),
 }
 
 #[derive(Deserialize, Serialize, Default, Clone, Debug)]
@@ -124,25 +137,10 @@ impl AuthRequest {
         let userinfo = serde_json::to_value(id_token_claims).unwrap();
         info!("userinfo: {userinfo:#?}");
 
-        let username = if let Some(claim) = &oidc.username_claim {
-            userinfo[claim].as_str()
-        } else {
-            userinfo
-                .get("preferred_username")
-                .or(userinfo.get("upn"))
-                .or(userinfo.get("email"))
-                .and_then(|v| v.as_str())
-        }
-        .unwrap_or("")
-        .to_string();
-        let groups = match &userinfo[&oidc.groupinfo_claim] {
-            Value::String(s) => vec![s.as_str().to_string()],
-            Value::Array(arr) => arr
-                .iter()
-                .filter_map(|v| v.as_str().map(str::to_string))
-                .collect(),
-            _ => Vec::new(),
-        };
-        Ok(Identity { username, groups })
+        Ok(Identity::from_claims(
+            &userinfo,
+            oidc.username_claim.as_deref(),
+            &oidc.groupinfo_claim,
+        ))
     }
 }
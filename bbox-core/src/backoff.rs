@@ -0,0 +1,78 @@
+//! Retry timing with exponential backoff.
+
+use std::time::{Duration, Instant};
+
+/// Exponential backoff state for reconnecting unavailable resources
+#[derive(Clone, Debug)]
+pub struct Backoff {
+    initial_delay: Duration,
+    max_delay: Duration,
+    delay: Duration,
+    next_attempt: Option<Instant>,
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Self::new(Duration::from_secs(1), Duration::from_secs(60))
+    }
+}
+
+impl Backoff {
+    pub fn new(initial_delay: Duration, max_delay: Duration) -> Self {
+        Backoff {
+            initial_delay,
+            max_delay,
+            delay: initial_delay,
+            next_attempt: None,
+        }
+    }
+    /// Check whether a new attempt is due
+    pub fn ready(&self) -> bool {
+        self.next_attempt
+            .map(|next| Instant::now() >= next)
+            .unwrap_or(true)
+    }
+    /// Register failed attempt and double the delay until the next attempt
+    pub fn failed(&mut self) {
+        self.next_attempt = Some(Instant::now() + self.delay);
+        self.delay = std::cmp::min(self.delay * 2, self.max_delay);
+    }
+    /// Register successful attempt
+    pub fn reset(&mut self) {
+        self.delay = self.initial_delay;
+        self.next_attempt = None;
+    }
+    /// Time until the next attempt is due
+    pub fn remaining(&self) -> Duration {
+        self.next_attempt
+            .map(|next| next.saturating_duration_since(Instant::now()))
+            .unwrap_or(Duration::ZERO)
+    }
+    /// Delay until the next attempt applied by the next call of [Backoff::failed]
+    pub fn delay(&self) -> Duration {
+        self.delay
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backoff_delays() {
+        let mut backoff = Backoff::new(Duration::from_secs(1), Duration::from_secs(5));
+        assert!(backoff.ready());
+        assert_eq!(backoff.remaining(), Duration::ZERO);
+        backoff.failed();
+        assert!(!backoff.ready());
+        assert!(backoff.remaining() > Duration::ZERO);
+        assert!(backoff.remaining() <= Duration::from_secs(1));
+        assert_eq!(backoff.delay(), Duration::from_secs(2));
+        backoff.failed();
+        backoff.failed();
+        assert_eq!(backoff.delay(), Duration::from_secs(5));
+        backoff.reset();
+        assert!(backoff.ready());
+        assert_eq!(backoff.delay(), Duration::from_secs(1));
+    }
+}
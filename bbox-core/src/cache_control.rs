@@ -0,0 +1,182 @@
+//! Configured `Cache-Control` headers.
+
+use actix_web::dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::http::{header, header::HeaderValue, Method};
+use actix_web::Error;
+use log::warn;
+use std::collections::BTreeMap;
+use std::future::{ready, Future, Ready};
+use std::pin::Pin;
+use std::rc::Rc;
+use std::sync::Arc;
+
+/// Middleware adding `Cache-Control` headers to successful GET responses
+#[derive(Clone, Default)]
+pub struct CacheControl {
+    /// Header values by path prefix, longest prefix first
+    rules: Arc<Vec<(String, HeaderValue)>>,
+}
+
+impl CacheControl {
+    pub fn new(cfg: &BTreeMap<String, String>) -> Self {
+        let mut rules: Vec<_> = cfg
+            .iter()
+            .filter_map(|(path, value)| match HeaderValue::from_str(value) {
+                Ok(value) => Some((path.trim_end_matches('/').to_string(), value)),
+                Err(_) => {
+                    warn!("Invalid Cache-Control value `{value}` for `{path}`");
+                    None
+                }
+            })
+            .collect();
+        rules.sort_by_key(|(path, _)| std::cmp::Reverse(path.len()));
+        CacheControl {
+            rules: Arc::new(rules),
+        }
+    }
+    pub fn is_enabled(&self) -> bool {
+        !self.rules.is_empty()
+    }
+    fn header_value(&self, path: &str) -> Option<&HeaderValue> {
+        self.rules
+            .iter()
+            .find(|(prefix, _)| {
+                path.strip_prefix(prefix.as_str())
+                    .map(|rest| rest.is_empty() || rest.starts_with(['/', '.']))
+                    .unwrap_or(false)
+            })
+            .map(|(_, value)| value)
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for CacheControl
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = CacheControlMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(CacheControlMiddleware {
+            service: Rc::new(service),
+            cache_control: self.clone(),
+        }))
+    }
+}
+
+pub struct CacheControlMiddleware<S> {
+    service: Rc<S>,
+    cache_control: CacheControl,
+}
+
+impl<S, B> Service<ServiceRequest> for CacheControlMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    #[allow(clippy::type_complexity)]
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let value = if [Method::GET, Method::HEAD].contains(req.method()) {
+            // Percent-decoded request path as used for routing
+            self.cache_control
+                .header_value(req.match_info().as_str())
+                .cloned()
+        } else {
+            None
+        };
+        let fut = self.service.call(req);
+        Box::pin(async move {
+            let mut res = fut.await?;
+            if let Some(value) = value {
+                let status = res.status();
+                if (status.is_success() || status.as_u16() == 304)
+                    && !res.headers().contains_key(header::CACHE_CONTROL)
+                {
+                    res.headers_mut().insert(header::CACHE_CONTROL, value);
+                }
+            }
+            Ok(res)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test::{call_service, init_service, TestRequest};
+    use actix_web::{web, App, HttpResponse};
+
+    #[actix_web::test]
+    async fn cache_headers() {
+        let cfg = BTreeMap::from([
+            ("/collections".to_string(), "max-age=60".to_string()),
+            (
+                "/collections/static/".to_string(),
+                "max-age=3600".to_string(),
+            ),
+            ("/xyz".to_string(), "max-age=600".to_string()),
+        ]);
+        let app = init_service(
+            App::new()
+                .wrap(CacheControl::new(&cfg))
+                .route(
+                    "/xyz/tileset/0/0/0.mvt",
+                    web::get().to(|| async {
+                        HttpResponse::Ok()
+                            .insert_header((header::CACHE_CONTROL, "no-cache"))
+                            .finish()
+                    }),
+                )
+                .default_service(web::to(|| async { HttpResponse::Ok().finish() })),
+        )
+        .await;
+
+        let cache_control = |resp: &ServiceResponse| {
+            resp.headers()
+                .get(header::CACHE_CONTROL)
+                .map(|v| v.to_str().unwrap().to_string())
+        };
+        let req = TestRequest::get().uri("/collections/ne").to_request();
+        let resp = call_service(&app, req).await;
+        assert_eq!(cache_control(&resp).as_deref(), Some("max-age=60"));
+
+        let req = TestRequest::get()
+            .uri("/collections/static/items.json")
+            .to_request();
+        let resp = call_service(&app, req).await;
+        assert_eq!(cache_control(&resp).as_deref(), Some("max-age=3600"));
+
+        let req = TestRequest::get()
+            .uri("/collections/st%61tic/items.json")
+            .to_request();
+        let resp = call_service(&app, req).await;
+        assert_eq!(cache_control(&resp).as_deref(), Some("max-age=3600"));
+
+        // Header set by endpoint
+        let req = TestRequest::get()
+            .uri("/xyz/tileset/0/0/0.mvt")
+            .to_request();
+        let resp = call_service(&app, req).await;
+        assert_eq!(cache_control(&resp).as_deref(), Some("no-cache"));
+
+        let req = TestRequest::post()
+            .uri("/collections/ne/items")
+            .to_request();
+        let resp = call_service(&app, req).await;
+        assert_eq!(cache_control(&resp), None);
+
+        let req = TestRequest::get().uri("/conformance").to_request();
+        let resp = call_service(&app, req).await;
+        assert_eq!(cache_control(&resp), None);
+    }
+}
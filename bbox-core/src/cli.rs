@@ -25,6 +25,9 @@ pub enum CommonCommands {
 pub struct ServeArgs {
     /// Serve service from file or URL
     pub file_or_url: Option<String>,
+    /// Validate configuration and datasources without starting the web server
+    #[arg(long)]
+    pub check_config: bool,
 }
 
 /* t-rex serve:
@@ -63,6 +66,10 @@ impl CliArgs {
         // cli.about("BBOX tile server")
         self.cli.clone().get_matches()
     }
+    /// Matches without command line arguments, used for tenant configurations
+    pub fn matches_without_args(&self) -> ArgMatches {
+        self.cli.clone().get_matches_from([self.cli.get_name()])
+    }
     pub fn apply_global_args(&self) {
         let Ok(args) = GlobalArgs::from_arg_matches(&self.cli_matches()) else {
             warn!("GlobalArgs::from_arg_matches error");
@@ -0,0 +1,167 @@
+//! Configuration validation without starting the web server (`serve --check-config`).
+
+use crate::cli::CommonCommands;
+use crate::service::{OgcApiService, ServiceConfig};
+use clap::{ArgMatches, FromArgMatches};
+use serde::Serialize;
+use std::fmt::Display;
+use std::path::Path;
+
+#[derive(Serialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum CheckStatus {
+    Ok,
+    Warning,
+    Error,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct CheckResult {
+    pub service: String,
+    /// Checked configuration item, e.g. "collection `places`"
+    pub item: String,
+    pub status: CheckStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+
+/// Results of configuration checks of all services
+#[derive(Serialize, Default, Debug)]
+pub struct ConfigReport {
+    pub results: Vec<CheckResult>,
+}
+
+impl ConfigReport {
+    fn add(&mut self, service: &str, item: String, status: CheckStatus, message: Option<String>) {
+        self.results.push(CheckResult {
+            service: service.to_string(),
+            item,
+            status,
+            message,
+        });
+    }
+    pub fn ok(&mut self, service: &str, item: impl Into<String>) {
+        self.add(service, item.into(), CheckStatus::Ok, None);
+    }
+    pub fn warning(&mut self, service: &str, item: impl Into<String>, message: impl Display) {
+        let message = Some(message.to_string());
+        self.add(service, item.into(), CheckStatus::Warning, message);
+    }
+    pub fn error(&mut self, service: &str, item: impl Into<String>, message: impl Display) {
+        let message = Some(message.to_string());
+        self.add(service, item.into(), CheckStatus::Error, message);
+    }
+    /// Add ok or error result
+    pub fn check<T, E: Display>(
+        &mut self,
+        service: &str,
+        item: impl Into<String>,
+        result: &Result<T, E>,
+    ) {
+        match result {
+            Ok(_) => self.ok(service, item),
+            Err(e) => self.error(service, item, e),
+        }
+    }
+    /// Check whether files can be created in a directory, creating it if missing
+    pub fn check_writable_dir(&mut self, service: &str, item: impl Into<String>, dir: &Path) {
+        let result = std::fs::create_dir_all(dir).and_then(|_| {
+            let probe = dir.join(".bbox-check");
+            std::fs::write(&probe, b"")?;
+            std::fs::remove_file(probe)
+        });
+        let result = result.map_err(|e| format!("`{}` not writable: {e}", dir.display()));
+        self.check(service, item, &result);
+    }
+    pub fn has_errors(&self) -> bool {
+        self.results
+            .iter()
+            .any(|result| result.status == CheckStatus::Error)
+    }
+    /// Report as text table
+    pub fn to_text(&self) -> String {
+        let width = |f: fn(&CheckResult) -> usize| self.results.iter().map(f).max().unwrap_or(0);
+        let service_width = width(|r| r.service.len());
+        let item_width = width(|r| r.item.len());
+        let mut text = String::new();
+        for result in &self.results {
+            let status = match result.status {
+                CheckStatus::Ok => "OK",
+                CheckStatus::Warning => "WARNING",
+                CheckStatus::Error => "ERROR",
+            };
+            let line = format!(
+                "{:service_width$}  {:item_width$}  {status:7}  {}",
+                result.service,
+                result.item,
+                result.message.as_deref().unwrap_or_default()
+            );
+            text.push_str(line.trim_end());
+            text.push('\n');
+        }
+        let errors = self
+            .results
+            .iter()
+            .filter(|result| result.status == CheckStatus::Error)
+            .count();
+        text.push_str(&format!(
+            "{} checks, {errors} error(s)\n",
+            self.results.len()
+        ));
+        text
+    }
+}
+
+/// Configuration check requested with `serve --check-config`
+pub fn check_config_requested(matches: &ArgMatches) -> bool {
+    matches!(
+        CommonCommands::from_arg_matches(matches),
+        Ok(CommonCommands::Serve(args)) if args.check_config
+    )
+}
+
+/// Initialize service configuration and run its checks
+pub async fn check_service_config<S: OgcApiService>(
+    matches: &ArgMatches,
+    report: &mut ConfigReport,
+) {
+    match S::Config::initialize(matches) {
+        Ok(cfg) => S::check_config(&cfg, report).await,
+        Err(e) => report.error("config", std::any::type_name::<S::Config>(), e),
+    }
+}
+
+/// Print report and exit with non-zero status on errors
+pub fn exit_with_report(report: &ConfigReport) -> ! {
+    print!("{}", report.to_text());
+    std::process::exit(if report.has_errors() { 1 } else { 0 });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn report() {
+        let mut report = ConfigReport::default();
+        report.ok("core", "webserver");
+        report.check::<(), _>(
+            "feature-server",
+            "collection `lakes`",
+            &Err("table not found"),
+        );
+        assert!(report.has_errors());
+        let text = report.to_text();
+        assert_eq!(
+            text,
+            "core            webserver           OK\n\
+             feature-server  collection `lakes`  ERROR    table not found\n\
+             2 checks, 1 error(s)\n"
+        );
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut report = ConfigReport::default();
+        report.check_writable_dir("tile-server", "cache", &dir.path().join("tiles"));
+        assert!(!report.has_errors());
+    }
+}
@@ -1,7 +1,11 @@
 use crate::api::{OgcApiInventory, OpenApiDoc};
+use crate::auth::access::AdminGuard;
 use crate::auth::oidc::{AuthRequest, OidcClient};
 use crate::config::WebserverCfg;
+use crate::health::{HealthChecks, HealthStatus};
+use crate::jobs::JobQueue;
 use crate::ogcapi::*;
+use crate::reload::config_watcher;
 use crate::service::{CoreService, ServiceEndpoints};
 use crate::static_assets::favicon;
 use crate::TileResponse;
@@ -115,7 +119,10 @@ async fn openapi_yaml(
     cfg: web::Data<WebserverCfg>,
     req: HttpRequest,
 ) -> HttpResponse {
-    let yaml = openapi.as_yaml(&cfg.public_server_url(req));
+    let yaml = openapi
+        .generate()
+        .await
+        .as_yaml(&cfg.public_server_url(req));
     HttpResponse::Ok()
         .content_type("application/x-yaml")
         .body(yaml)
@@ -127,7 +134,10 @@ async fn openapi_json(
     cfg: web::Data<WebserverCfg>,
     req: HttpRequest,
 ) -> HttpResponse {
-    let json = openapi.as_json(&cfg.public_server_url(req));
+    let json = openapi
+        .generate()
+        .await
+        .as_json(&cfg.public_server_url(req));
     HttpResponse::Ok().json(json)
 }
 
@@ -135,6 +145,48 @@ async fn health() -> HttpResponse {
     HttpResponse::Ok().body("OK")
 }
 
+/// Liveness probe
+async fn health_live(health: web::Data<HealthChecks>) -> HttpResponse {
+    HttpResponse::Ok().json(health.liveness())
+}
+
+/// Readiness probe with status of service dependencies
+async fn health_ready(health: web::Data<HealthChecks>) -> HttpResponse {
+    let report = health.readiness().await;
+    if report.status == HealthStatus::Up {
+        HttpResponse::Ok().json(report)
+    } else {
+        HttpResponse::ServiceUnavailable().json(report)
+    }
+}
+
+/// List of background jobs
+async fn jobs(jobs: web::Data<JobQueue>) -> HttpResponse {
+    HttpResponse::Ok().json(serde_json::json!({ "jobs": jobs.jobs() }))
+}
+
+/// Status of a background job
+async fn job_status(jobs: web::Data<JobQueue>, job_id: web::Path<String>) -> HttpResponse {
+    match jobs.job(&job_id) {
+        Some(info) => HttpResponse::Ok().json(info),
+        None => HttpResponse::NotFound().finish(),
+    }
+}
+
+/// Cancel a background job
+async fn job_cancel(jobs: web::Data<JobQueue>, job_id: web::Path<String>) -> HttpResponse {
+    match jobs.cancel(&job_id).await {
+        Some(info) => HttpResponse::Ok().json(info),
+        None => HttpResponse::NotFound().finish(),
+    }
+}
+
+/// Reload configuration
+async fn reload() -> HttpResponse {
+    config_watcher().trigger();
+    HttpResponse::Accepted().finish()
+}
+
 async fn login(oidc: web::Data<OidcClient>) -> impl Responder {
     web::Redirect::to(oidc.authorize_url.clone()).using_status_code(StatusCode::FOUND)
 }
@@ -166,6 +218,8 @@ impl ServiceEndpoints for CoreService {
         cfg.app_data(web::Data::new(self.web_config.clone()))
             .app_data(web::Data::new(self.ogcapi.clone()))
             .app_data(web::Data::new(self.openapi.clone()))
+            .app_data(web::Data::new(self.jobs.clone()))
+            .app_data(web::Data::new(self.health.clone()))
             // OGC validator checks "{URL}/" and "{URL}/conformance" based on server URL from openapi.json
             .service(
                 web::resource("/")
@@ -192,7 +246,25 @@ impl ServiceEndpoints for CoreService {
                     .guard(JsonContentGuard)
                     .route(web::get().to(openapi_json)),
             )
-            .service(web::resource("/health").to(health));
+            .service(web::resource("/health").to(health))
+            .service(web::resource("/health/live").route(web::get().to(health_live)))
+            .service(web::resource("/health/ready").route(web::get().to(health_ready)))
+            .service(
+                web::resource("/admin/reload")
+                    .guard(AdminGuard)
+                    .route(web::post().to(reload)),
+            )
+            .service(
+                web::resource("/admin/jobs")
+                    .guard(AdminGuard)
+                    .route(web::get().to(jobs)),
+            )
+            .service(
+                web::resource("/admin/jobs/{jobId}")
+                    .guard(AdminGuard)
+                    .route(web::get().to(job_status))
+                    .route(web::delete().to(job_cancel)),
+            );
 
         if let Some(oidc) = &self.oidc {
             cfg.app_data(web::Data::new(oidc.clone()))
@@ -0,0 +1,222 @@
+//! Event notifications sent to webhooks.
+//!
+//! Services publish events like invalidated tiles or changed features.
+//! Matching events are POSTed as JSON to the configured webhooks in the background.
+
+use crate::config::WebhookCfg;
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum EventKind {
+    #[serde(rename = "tiles.invalidated")]
+    TilesInvalidated,
+    #[serde(rename = "seed.completed")]
+    SeedCompleted,
+    #[serde(rename = "feature.created")]
+    FeatureCreated,
+    #[serde(rename = "feature.updated")]
+    FeatureUpdated,
+    #[serde(rename = "feature.deleted")]
+    FeatureDeleted,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct Event {
+    #[serde(rename = "type")]
+    pub kind: EventKind,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tileset: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub collection: Option<String>,
+    pub time: DateTime<Utc>,
+    /// Event specific details, e.g. zoom levels or feature id
+    pub data: serde_json::Value,
+}
+
+impl Event {
+    pub fn tileset(kind: EventKind, tileset: &str, data: serde_json::Value) -> Self {
+        Event {
+            kind,
+            tileset: Some(tileset.to_string()),
+            collection: None,
+            time: Utc::now(),
+            data,
+        }
+    }
+    pub fn collection(kind: EventKind, collection: &str, data: serde_json::Value) -> Self {
+        Event {
+            kind,
+            tileset: None,
+            collection: Some(collection.to_string()),
+            time: Utc::now(),
+            data,
+        }
+    }
+}
+
+impl WebhookCfg {
+    #[cfg_attr(not(feature = "webhooks"), allow(dead_code))]
+    fn matches(&self, event: &Event) -> bool {
+        let in_list = |list: &[String], name: &Option<String>| {
+            list.is_empty() || name.as_ref().map(|n| list.contains(n)).unwrap_or(true)
+        };
+        (self.events.is_empty() || self.events.contains(&event.kind))
+            && in_list(&self.tilesets, &event.tileset)
+            && in_list(&self.collections, &event.collection)
+    }
+}
+
+/// HMAC-SHA256 of the request body, hex encoded
+#[cfg_attr(not(feature = "webhooks"), allow(dead_code))]
+fn signature(secret: &str, body: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts any key size");
+    mac.update(body);
+    hex::encode(mac.finalize().into_bytes())
+}
+
+#[cfg(feature = "webhooks")]
+mod delivery {
+    use super::{signature, Event};
+    use crate::config::WebhookCfg;
+    use log::{info, warn};
+    use once_cell::sync::OnceCell;
+    use std::time::Duration;
+
+    /// Delivery attempts per webhook and event
+    const MAX_ATTEMPTS: u32 = 3;
+
+    struct Webhooks {
+        hooks: Vec<WebhookCfg>,
+        client: reqwest::Client,
+    }
+
+    static WEBHOOKS: OnceCell<Webhooks> = OnceCell::new();
+
+    /// Register configured webhooks. Called once at startup.
+    pub fn init_webhooks(hooks: &[WebhookCfg]) {
+        if hooks.is_empty() {
+            return;
+        }
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(10))
+            .build()
+            .unwrap_or_default();
+        for hook in hooks {
+            info!("Sending events to webhook {}", hook.url);
+        }
+        let _ = WEBHOOKS.set(Webhooks {
+            hooks: hooks.to_vec(),
+            client,
+        });
+    }
+
+    async fn deliver(client: reqwest::Client, hook: WebhookCfg, kind: String, body: Vec<u8>) {
+        let signature = hook
+            .secret
+            .as_ref()
+            .map(|secret| format!("sha256={}", signature(secret, &body)));
+        for attempt in 1..=MAX_ATTEMPTS {
+            let mut req = client
+                .post(&hook.url)
+                .header("Content-Type", "application/json")
+                .header("X-Bbox-Event", &kind)
+                .body(body.clone());
+            if let Some(signature) = &signature {
+                req = req.header("X-Bbox-Signature", signature);
+            }
+            match req.send().await.and_then(|resp| resp.error_for_status()) {
+                Ok(_) => return,
+                Err(e) => warn!(
+                    "Webhook {} failed (attempt {attempt}/{MAX_ATTEMPTS}): {e}",
+                    hook.url
+                ),
+            }
+            tokio::time::sleep(Duration::from_secs(2u64.pow(attempt))).await;
+        }
+    }
+
+    /// Send event to all matching webhooks in the background
+    pub fn publish(event: Event) {
+        let Some(webhooks) = WEBHOOKS.get() else {
+            return;
+        };
+        let hooks: Vec<_> = webhooks
+            .hooks
+            .iter()
+            .filter(|hook| hook.matches(&event))
+            .cloned()
+            .collect();
+        if hooks.is_empty() {
+            return;
+        }
+        let Ok(body) = serde_json::to_vec(&event) else {
+            return;
+        };
+        let kind = serde_json::json!(event.kind)
+            .as_str()
+            .unwrap_or_default()
+            .to_string();
+        for hook in hooks {
+            tokio::spawn(deliver(
+                webhooks.client.clone(),
+                hook,
+                kind.clone(),
+                body.clone(),
+            ));
+        }
+    }
+}
+
+#[cfg(feature = "webhooks")]
+pub use delivery::{init_webhooks, publish};
+
+/// Register configured webhooks. Called once at startup.
+#[cfg(not(feature = "webhooks"))]
+pub fn init_webhooks(hooks: &[WebhookCfg]) {
+    if !hooks.is_empty() {
+        log::warn!("Webhooks require feature `webhooks`");
+    }
+}
+
+/// Send event to all matching webhooks in the background
+#[cfg(not(feature = "webhooks"))]
+pub fn publish(_event: Event) {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn webhook_filter() {
+        let hook: WebhookCfg = serde_json::from_value(json!({
+            "url": "http://localhost/hook",
+            "events": ["tiles.invalidated", "seed.completed"],
+            "tilesets": ["osm"]
+        }))
+        .unwrap();
+        let invalidated = Event::tileset(EventKind::TilesInvalidated, "osm", json!({}));
+        assert!(hook.matches(&invalidated));
+        let other = Event::tileset(EventKind::TilesInvalidated, "ne", json!({}));
+        assert!(!hook.matches(&other));
+        let created = Event::collection(EventKind::FeatureCreated, "places", json!({}));
+        assert!(!hook.matches(&created));
+
+        let all: WebhookCfg =
+            serde_json::from_value(json!({"url": "http://localhost/hook"})).unwrap();
+        assert!(all.matches(&created));
+    }
+
+    #[test]
+    fn hmac_signature() {
+        // RFC 4231 test case 2
+        assert_eq!(
+            signature("Jefe", b"what do ya want for nothing?"),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+}
@@ -4,8 +4,9 @@
 
 use std::fmt::Display;
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum Format {
+    Avif,
     Gif,
     Jpeg,
     Json,
@@ -18,6 +19,7 @@ impl Format {
     #[must_use]
     pub fn from_suffix(value: &str) -> Option<Self> {
         Some(match value.to_ascii_lowercase().as_str() {
+            "avif" => Self::Avif,
             "gif" => Self::Gif,
             "jpg" | "jpeg" => Self::Jpeg,
             "json" => Self::Json,
@@ -30,6 +32,7 @@ impl Format {
 
     pub fn from_content_type(mime: &str) -> Option<Self> {
         Some(match mime {
+            "image/avif" => Self::Avif,
             "image/gif" => Self::Gif,
             "image/jpeg" => Self::Jpeg,
             "application/json" => Self::Json,
@@ -42,6 +45,7 @@ impl Format {
 
     pub fn file_suffix(&self) -> &str {
         match *self {
+            Self::Avif => "avif",
             Self::Gif => "gif",
             Self::Jpeg => "jpg",
             Self::Json => "json",
@@ -54,6 +58,7 @@ impl Format {
     #[must_use]
     pub fn content_type(&self) -> &str {
         match *self {
+            Self::Avif => "image/avif",
             Self::Gif => "image/gif",
             Self::Jpeg => "image/jpeg",
             Self::Json => "application/json",
@@ -66,7 +71,7 @@ impl Format {
     #[must_use]
     pub fn is_detectable(&self) -> bool {
         match *self {
-            Self::Png | Self::Jpeg | Self::Gif | Self::Webp => true,
+            Self::Png | Self::Jpeg | Self::Gif | Self::Webp | Self::Avif => true,
             // TODO: Json can be detected, but currently we only detect it
             //       when it's not compressed, so to avoid a warning, keeping it as false for now.
             //       Once we can detect it inside a compressed data, change it to true.
@@ -78,6 +83,7 @@ impl Format {
 impl Display for Format {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match *self {
+            Self::Avif => write!(f, "avif"),
             Self::Gif => write!(f, "gif"),
             Self::Jpeg => write!(f, "jpeg"),
             Self::Json => write!(f, "json"),
@@ -167,6 +173,7 @@ impl TileInfo {
             v if v.starts_with(b"RIFF") && v.len() > 8 && v[8..].starts_with(b"WEBP") => {
                 Self::new(Webp, Internal)
             }
+            v if v.len() > 12 && &v[4..12] == b"ftypavif" => Self::new(Avif, Internal),
             v if v.starts_with(b"{") => Self::new(Json, Uncompressed),
             _ => None?,
         })
@@ -183,7 +190,9 @@ impl From<Format> for TileInfo {
         Self::new(
             format,
             match format {
-                Format::Png | Format::Jpeg | Format::Webp | Format::Gif => Encoding::Internal,
+                Format::Png | Format::Jpeg | Format::Webp | Format::Gif | Format::Avif => {
+                    Encoding::Internal
+                }
                 Format::Mvt | Format::Json => Encoding::Uncompressed,
             },
         )
@@ -207,7 +216,7 @@ mod tests {
     use std::fs::read;
 
     use Encoding::{Internal, Uncompressed};
-    use Format::{Jpeg, Json, Png, Webp};
+    use Format::{Avif, Jpeg, Json, Png, Webp};
 
     use super::*;
 
@@ -236,6 +245,15 @@ mod tests {
         assert_eq!(TileInfo::detect(br#"RIFF"#), None);
     }
 
+    #[test]
+    fn test_data_format_avif() {
+        assert_eq!(
+            TileInfo::detect(b"\x00\x00\x00\x1cftypavif\x00\x00\x00\x00"),
+            info(Avif, Internal)
+        );
+        assert_eq!(Format::from_content_type("image/avif"), Some(Avif));
+    }
+
     #[test]
     fn test_data_format_json() {
         assert_eq!(
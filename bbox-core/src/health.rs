@@ -0,0 +1,152 @@
+//! Liveness and readiness checks.
+
+use async_trait::async_trait;
+use futures::future::join_all;
+use log::warn;
+use serde::Serialize;
+use std::fmt::Display;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Maximal duration of the checks of one service
+const CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Serialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum HealthStatus {
+    Up,
+    Down,
+}
+
+/// Status of a service dependency like a database or a backend process
+#[derive(Serialize, Clone, Debug)]
+pub struct DependencyHealth {
+    /// Dependency type, e.g. `postgis` or `s3`
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub name: String,
+    pub status: HealthStatus,
+    /// Error details, which are logged but not included in the public health report
+    #[serde(skip)]
+    pub error: Option<String>,
+}
+
+impl DependencyHealth {
+    pub fn from_result<E: Display>(kind: &str, name: &str, result: Result<(), E>) -> Self {
+        let (status, error) = match result {
+            Ok(()) => (HealthStatus::Up, None),
+            Err(e) => {
+                warn!("Health check of {kind} `{name}` failed: {e}");
+                (HealthStatus::Down, Some(e.to_string()))
+            }
+        };
+        DependencyHealth {
+            kind: kind.to_string(),
+            name: name.to_string(),
+            status,
+            error,
+        }
+    }
+}
+
+/// Readiness check of the dependencies of a service
+#[async_trait]
+pub trait HealthCheck: Send + Sync {
+    /// Service name
+    fn name(&self) -> &str;
+    async fn check(&self) -> Vec<DependencyHealth>;
+}
+
+#[derive(Serialize, Debug)]
+pub struct HealthReport {
+    pub status: HealthStatus,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub checks: Vec<DependencyHealth>,
+}
+
+/// Health checks of all registered services
+#[derive(Clone, Default)]
+pub struct HealthChecks {
+    checks: Vec<Arc<dyn HealthCheck>>,
+}
+
+impl HealthChecks {
+    pub fn add(&mut self, check: Arc<dyn HealthCheck>) {
+        self.checks.push(check);
+    }
+    /// Server process is running
+    pub fn liveness(&self) -> HealthReport {
+        HealthReport {
+            status: HealthStatus::Up,
+            checks: Vec::new(),
+        }
+    }
+    /// Run all checks concurrently. The server is ready, if all dependencies are up.
+    pub async fn readiness(&self) -> HealthReport {
+        let results = join_all(self.checks.iter().map(|check| async move {
+            tokio::time::timeout(CHECK_TIMEOUT, check.check())
+                .await
+                .unwrap_or_else(|_| {
+                    vec![DependencyHealth::from_result(
+                        "service",
+                        check.name(),
+                        Err("Health check timed out"),
+                    )]
+                })
+        }))
+        .await;
+        let checks: Vec<_> = results.into_iter().flatten().collect();
+        let status = if checks.iter().all(|c| c.status == HealthStatus::Up) {
+            HealthStatus::Up
+        } else {
+            HealthStatus::Down
+        };
+        HealthReport { status, checks }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct BackendCheck {
+        running: bool,
+    }
+
+    #[async_trait]
+    impl HealthCheck for BackendCheck {
+        fn name(&self) -> &str {
+            "map-server"
+        }
+        async fn check(&self) -> Vec<DependencyHealth> {
+            let result = if self.running {
+                Ok(())
+            } else {
+                Err("Connection refused")
+            };
+            vec![DependencyHealth::from_result("fcgi", "qgis[0]", result)]
+        }
+    }
+
+    #[actix_web::test]
+    async fn readiness() {
+        let mut health = HealthChecks::default();
+        assert_eq!(health.readiness().await.status, HealthStatus::Up);
+
+        health.add(Arc::new(BackendCheck { running: true }));
+        assert_eq!(health.readiness().await.status, HealthStatus::Up);
+
+        health.add(Arc::new(BackendCheck { running: false }));
+        let report = health.readiness().await;
+        assert_eq!(report.status, HealthStatus::Down);
+        assert_eq!(report.checks.len(), 2);
+        assert_eq!(
+            serde_json::to_value(&report.checks[1]).unwrap(),
+            serde_json::json!({"type": "fcgi", "name": "qgis[0]", "status": "down"})
+        );
+        assert_eq!(
+            report.checks[1].error.as_deref(),
+            Some("Connection refused")
+        );
+    }
+}
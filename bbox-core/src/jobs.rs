@@ -0,0 +1,482 @@
+//! Background jobs for long-running operations like tile seeding.
+
+use crate::config::{JobTableCfg, JobsCfg};
+use crate::pg_ds::{self, PgDatasource};
+use crate::sql::{InvalidSqlIdent, SqlIdent};
+use chrono::{DateTime, Utc};
+use log::{error, info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::{Arc, RwLock};
+use thiserror::Error;
+use tokio::sync::Semaphore;
+use tokio::task::AbortHandle;
+
+/// Default number of jobs running in parallel
+const MAX_CONCURRENT_JOBS: usize = 2;
+/// Default number of finished jobs kept in memory
+const JOB_HISTORY: usize = 100;
+
+#[derive(Error, Debug)]
+pub enum JobError {
+    #[error("invalid job table name: {0}")]
+    InvalidTableName(#[from] InvalidSqlIdent),
+    #[error("invalid job database: {0}")]
+    DbError(#[from] pg_ds::Error),
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum JobStatus {
+    /// Queued, waiting for a free slot
+    Accepted,
+    Running,
+    Successful,
+    Failed,
+    /// Cancelled
+    Dismissed,
+}
+
+impl JobStatus {
+    pub fn is_finished(&self) -> bool {
+        !matches!(self, JobStatus::Accepted | JobStatus::Running)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct JobInfo {
+    #[serde(rename = "jobID")]
+    pub job_id: String,
+    /// Job type, e.g. `seed`
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub status: JobStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+    /// Progress in percent
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub progress: Option<f32>,
+    pub created: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub started: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub finished: Option<DateTime<Utc>>,
+    /// Result of a successful job
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<serde_json::Value>,
+}
+
+struct JobEntry {
+    info: JobInfo,
+    cancelled: Arc<AtomicBool>,
+    abort: Option<AbortHandle>,
+}
+
+/// Table for persisting job states
+struct JobTable {
+    ds: PgDatasource,
+    /// Configured table name
+    name: String,
+    /// Quoted table name
+    table: String,
+}
+
+impl JobTable {
+    fn from_config(cfg: &JobTableCfg) -> Result<Self, JobError> {
+        let name = cfg.table.as_deref().unwrap_or("bbox_jobs");
+        let table = name
+            .split('.')
+            .map(|part| SqlIdent::new(part).map(|ident| ident.quoted()))
+            .collect::<Result<Vec<_>, _>>()?
+            .join(".");
+        Ok(JobTable {
+            ds: PgDatasource::new_lazy_pool(&cfg.url)?,
+            name: name.to_string(),
+            table,
+        })
+    }
+    async fn create_table(&self) -> Result<(), sqlx::Error> {
+        let sql = format!(
+            "CREATE TABLE IF NOT EXISTS {} (job_id TEXT PRIMARY KEY, info JSONB NOT NULL, updated TIMESTAMPTZ NOT NULL DEFAULT now())",
+            self.table
+        );
+        sqlx::query(&sql).execute(&self.ds.pool).await?;
+        Ok(())
+    }
+    async fn load(&self) -> Result<Vec<JobInfo>, sqlx::Error> {
+        let sql = format!("SELECT info::text FROM {} ORDER BY updated", self.table);
+        let rows: Vec<String> = sqlx::query_scalar(&sql).fetch_all(&self.ds.pool).await?;
+        Ok(rows
+            .iter()
+            .filter_map(|json| serde_json::from_str(json).ok())
+            .collect())
+    }
+    async fn save(&self, info: &JobInfo) {
+        let sql = format!(
+            "INSERT INTO {} (job_id, info) VALUES ($1, $2::jsonb) ON CONFLICT (job_id) DO UPDATE SET info = EXCLUDED.info, updated = now()",
+            self.table
+        );
+        let json = serde_json::to_string(info).unwrap_or_default();
+        if let Err(e) = sqlx::query(&sql)
+            .bind(&info.job_id)
+            .bind(json)
+            .execute(&self.ds.pool)
+            .await
+        {
+            error!("Saving job {} failed: {e}", info.job_id);
+        }
+    }
+}
+
+struct JobRegistry {
+    jobs: RwLock<HashMap<String, JobEntry>>,
+    slots: Arc<Semaphore>,
+    history: usize,
+    table: Option<JobTable>,
+    counter: AtomicU32,
+}
+
+/// Queue running jobs in the background
+#[derive(Clone)]
+pub struct JobQueue {
+    registry: Arc<JobRegistry>,
+}
+
+impl Default for JobQueue {
+    fn default() -> Self {
+        JobQueue::new(MAX_CONCURRENT_JOBS, JOB_HISTORY, None)
+    }
+}
+
+impl JobQueue {
+    fn new(max_concurrent: usize, history: usize, table: Option<JobTable>) -> Self {
+        JobQueue {
+            registry: Arc::new(JobRegistry {
+                jobs: RwLock::new(HashMap::new()),
+                slots: Arc::new(Semaphore::new(max_concurrent.max(1))),
+                history,
+                table,
+                counter: AtomicU32::new(0),
+            }),
+        }
+    }
+    pub async fn from_config(cfg: &JobsCfg) -> Result<Self, JobError> {
+        let table = cfg
+            .postgres
+            .as_ref()
+            .map(JobTable::from_config)
+            .transpose()?;
+        let queue = JobQueue::new(
+            cfg.max_concurrent.unwrap_or(MAX_CONCURRENT_JOBS),
+            cfg.history.unwrap_or(JOB_HISTORY),
+            table,
+        );
+        if let Some(table) = &queue.registry.table {
+            info!("Persisting jobs in table `{}`", table.name);
+            if let Err(e) = queue.restore(table).await {
+                warn!("Loading jobs from `{}` failed: {e}", table.name);
+            }
+        }
+        Ok(queue)
+    }
+    /// Queue keeping jobs in memory only
+    pub fn in_memory(cfg: &JobsCfg) -> Self {
+        JobQueue::new(
+            cfg.max_concurrent.unwrap_or(MAX_CONCURRENT_JOBS),
+            cfg.history.unwrap_or(JOB_HISTORY),
+            None,
+        )
+    }
+    /// Load jobs of previous runs. Unfinished jobs were interrupted and are marked as failed.
+    async fn restore(&self, table: &JobTable) -> Result<(), sqlx::Error> {
+        table.create_table().await?;
+        for mut info in table.load().await? {
+            if !info.status.is_finished() {
+                info.status = JobStatus::Failed;
+                info.message = Some("Interrupted by server shutdown".to_string());
+                info.finished = Some(Utc::now());
+                table.save(&info).await;
+            }
+            let entry = JobEntry {
+                info,
+                cancelled: Arc::new(AtomicBool::new(false)),
+                abort: None,
+            };
+            self.registry
+                .jobs
+                .write()
+                .unwrap()
+                .insert(entry.info.job_id.clone(), entry);
+        }
+        Ok(())
+    }
+    fn next_id(&self) -> String {
+        let cnt = self.registry.counter.fetch_add(1, Ordering::Relaxed);
+        format!("{:x}{:04x}", Utc::now().timestamp_millis(), cnt & 0xffff)
+    }
+    /// Start a job in the background. The job is queued until a slot is available.
+    pub fn submit<F, Fut, E>(&self, kind: &str, job: F) -> JobInfo
+    where
+        F: FnOnce(JobHandle) -> Fut + Send + 'static,
+        Fut: Future<Output = Result<serde_json::Value, E>> + Send + 'static,
+        E: Display + Send + 'static,
+    {
+        let info = JobInfo {
+            job_id: self.next_id(),
+            kind: kind.to_string(),
+            status: JobStatus::Accepted,
+            message: None,
+            progress: None,
+            created: Utc::now(),
+            started: None,
+            finished: None,
+            result: None,
+        };
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let handle = JobHandle {
+            job_id: info.job_id.clone(),
+            queue: self.clone(),
+            cancelled: cancelled.clone(),
+        };
+        self.registry.jobs.write().unwrap().insert(
+            info.job_id.clone(),
+            JobEntry {
+                info: info.clone(),
+                cancelled,
+                abort: None,
+            },
+        );
+        let queue = self.clone();
+        let job_id = info.job_id.clone();
+        let task = tokio::spawn(async move {
+            queue.persist(&job_id).await;
+            let Ok(_permit) = queue.registry.slots.clone().acquire_owned().await else {
+                return;
+            };
+            queue.update(&job_id, |info| {
+                info.status = JobStatus::Running;
+                info.started = Some(Utc::now());
+            });
+            queue.persist(&job_id).await;
+            let result = job(handle).await;
+            queue.update(&job_id, |info| {
+                info.finished = Some(Utc::now());
+                match result {
+                    Ok(result) => {
+                        info.status = JobStatus::Successful;
+                        info.progress = Some(100.0);
+                        info.result = Some(result);
+                    }
+                    Err(e) => {
+                        info.status = JobStatus::Failed;
+                        info.message = Some(e.to_string());
+                    }
+                }
+            });
+            queue.persist(&job_id).await;
+            queue.cleanup();
+        });
+        if let Some(entry) = self.registry.jobs.write().unwrap().get_mut(&info.job_id) {
+            entry.abort = Some(task.abort_handle());
+        }
+        info!("Job {} ({kind}) submitted", info.job_id);
+        info
+    }
+    pub fn job(&self, job_id: &str) -> Option<JobInfo> {
+        self.registry
+            .jobs
+            .read()
+            .unwrap()
+            .get(job_id)
+            .map(|entry| entry.info.clone())
+    }
+    /// All jobs, newest first
+    pub fn jobs(&self) -> Vec<JobInfo> {
+        let mut jobs: Vec<JobInfo> = self
+            .registry
+            .jobs
+            .read()
+            .unwrap()
+            .values()
+            .map(|entry| entry.info.clone())
+            .collect();
+        jobs.sort_by(|a, b| b.created.cmp(&a.created).then(b.job_id.cmp(&a.job_id)));
+        jobs
+    }
+    /// Cancel a queued or running job. Returns `None` if the job doesn't exist.
+    pub async fn cancel(&self, job_id: &str) -> Option<JobInfo> {
+        let info = {
+            let mut jobs = self.registry.jobs.write().unwrap();
+            let entry = jobs.get_mut(job_id)?;
+            if !entry.info.status.is_finished() {
+                entry.cancelled.store(true, Ordering::Relaxed);
+                if let Some(abort) = &entry.abort {
+                    abort.abort();
+                }
+                entry.info.status = JobStatus::Dismissed;
+                entry.info.finished = Some(Utc::now());
+                info!("Job {job_id} cancelled");
+            }
+            entry.info.clone()
+        };
+        self.persist(job_id).await;
+        Some(info)
+    }
+    fn update<F: FnOnce(&mut JobInfo)>(&self, job_id: &str, f: F) {
+        if let Some(entry) = self.registry.jobs.write().unwrap().get_mut(job_id) {
+            // Don't overwrite state of cancelled jobs
+            if entry.info.status != JobStatus::Dismissed {
+                f(&mut entry.info);
+            }
+        }
+    }
+    async fn persist(&self, job_id: &str) {
+        if let Some(table) = &self.registry.table {
+            if let Some(info) = self.job(job_id) {
+                table.save(&info).await;
+            }
+        }
+    }
+    /// Remove oldest finished jobs exceeding the history size
+    fn cleanup(&self) {
+        let mut jobs = self.registry.jobs.write().unwrap();
+        let mut finished: Vec<(DateTime<Utc>, String)> = jobs
+            .values()
+            .filter(|entry| entry.info.status.is_finished())
+            .map(|entry| (entry.info.created, entry.info.job_id.clone()))
+            .collect();
+        if finished.len() > self.registry.history {
+            finished.sort();
+            let remove = finished.len() - self.registry.history;
+            for (_, job_id) in finished.into_iter().take(remove) {
+                jobs.remove(&job_id);
+            }
+        }
+    }
+}
+
+/// Handle passed to a running job for reporting progress
+#[derive(Clone)]
+pub struct JobHandle {
+    job_id: String,
+    queue: JobQueue,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl JobHandle {
+    pub fn job_id(&self) -> &str {
+        &self.job_id
+    }
+    /// Report progress in percent with an optional status message
+    pub fn set_progress(&self, progress: f32, message: Option<String>) {
+        self.queue.update(&self.job_id, |info| {
+            info.progress = Some(progress.clamp(0.0, 100.0));
+            if message.is_some() {
+                info.message = message;
+            }
+        });
+    }
+    /// Job was cancelled. Long-running jobs should check this to stop cleanly.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    async fn wait_finished(queue: &JobQueue, job_id: &str) -> JobInfo {
+        for _ in 0..100 {
+            let info = queue.job(job_id).unwrap();
+            if info.status.is_finished() {
+                return info;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("Job {job_id} not finished");
+    }
+
+    #[actix_web::test]
+    async fn job_lifecycle() {
+        let queue = JobQueue::new(1, 10, None);
+        let job = queue.submit("test", |handle| async move {
+            handle.set_progress(50.0, Some("half done".to_string()));
+            Ok::<_, String>(serde_json::json!({"tiles": 42}))
+        });
+        assert_eq!(job.status, JobStatus::Accepted);
+        let info = wait_finished(&queue, &job.job_id).await;
+        assert_eq!(info.status, JobStatus::Successful);
+        assert_eq!(info.progress, Some(100.0));
+        assert_eq!(info.message.as_deref(), Some("half done"));
+        assert_eq!(info.result, Some(serde_json::json!({"tiles": 42})));
+
+        let job = queue.submit("test", |_| async {
+            Err::<serde_json::Value, _>("no tiles")
+        });
+        let info = wait_finished(&queue, &job.job_id).await;
+        assert_eq!(info.status, JobStatus::Failed);
+        assert_eq!(info.message.as_deref(), Some("no tiles"));
+        assert_eq!(queue.jobs().len(), 2);
+    }
+
+    #[actix_web::test]
+    async fn job_cancel() {
+        let queue = JobQueue::new(1, 10, None);
+        let running = queue.submit("test", |_| async {
+            tokio::time::sleep(Duration::from_secs(60)).await;
+            Ok::<_, String>(serde_json::Value::Null)
+        });
+        let queued = queue.submit("test", |_| async {
+            Ok::<_, String>(serde_json::Value::Null)
+        });
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert_eq!(
+            queue.job(&running.job_id).unwrap().status,
+            JobStatus::Running
+        );
+        assert_eq!(
+            queue.job(&queued.job_id).unwrap().status,
+            JobStatus::Accepted
+        );
+
+        let info = queue.cancel(&running.job_id).await.unwrap();
+        assert_eq!(info.status, JobStatus::Dismissed);
+        // Slot is released for the queued job
+        let info = wait_finished(&queue, &queued.job_id).await;
+        assert_eq!(info.status, JobStatus::Successful);
+        assert!(queue.cancel("unknown").await.is_none());
+    }
+
+    #[actix_web::test]
+    async fn job_table() {
+        let table_cfg = |table: &str| JobTableCfg {
+            url: "postgresql://bbox@localhost/bbox".to_string(),
+            table: Some(table.to_string()),
+        };
+        let table = JobTable::from_config(&table_cfg("admin.jobs")).unwrap();
+        assert_eq!(table.name, "admin.jobs");
+        assert_eq!(table.table, r#""admin"."jobs""#);
+        let table = JobTable::from_config(&table_cfg("jobs; DROP TABLE x")).unwrap();
+        assert_eq!(table.table, r#""jobs; DROP TABLE x""#);
+        assert!(matches!(
+            JobTable::from_config(&table_cfg("admin.")),
+            Err(JobError::InvalidTableName(_))
+        ));
+        let cfg = JobsCfg {
+            postgres: Some(JobTableCfg {
+                url: "invalid".to_string(),
+                table: None,
+            }),
+            ..Default::default()
+        };
+        assert!(matches!(
+            JobQueue::from_config(&cfg).await,
+            Err(JobError::DbError(_))
+        ));
+    }
+}
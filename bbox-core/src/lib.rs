@@ -1,20 +1,31 @@
+pub mod access_log;
 pub mod api;
+pub mod api_error;
 pub mod auth;
+pub mod backoff;
+pub mod cache_control;
 pub mod cli;
 pub mod config;
+pub mod config_check;
 mod dir;
 pub mod endpoints;
+pub mod events;
 pub mod file_search;
 mod formats;
+pub mod health;
+pub mod jobs;
 pub mod logger;
 pub mod metrics;
 pub mod ogcapi;
 pub mod pg_ds;
+pub mod reload;
 pub mod service;
 mod service_utils;
+pub mod sql;
 pub mod static_assets;
 pub mod static_files;
 pub mod templates;
+pub mod tenant;
 mod tile_response;
 pub mod tls;
 
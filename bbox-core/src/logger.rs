@@ -1,5 +1,7 @@
+use crate::access_log::{request_id, LOG_TARGET};
 use crate::config::Loglevel;
 use std::env;
+use std::io::Write;
 
 pub fn init(level: Option<Loglevel>) {
     if let Some(level) = level {
@@ -17,5 +19,29 @@ pub fn init(level: Option<Loglevel>) {
             "info,bbox_map_server=debug,bbox_feature_server=debug,bbox_frontend=debug,sqlx=warn",
         );
     }
-    env_logger::init();
+    env_logger::Builder::from_default_env()
+        .format(|buf, record| {
+            // Access log entries are JSON lines
+            if record.target() == LOG_TARGET {
+                return writeln!(buf, "{}", record.args());
+            }
+            let level = buf.default_styled_level(record.level());
+            match request_id() {
+                Some(id) => writeln!(
+                    buf,
+                    "[{} {level:<5} {} {id}] {}",
+                    buf.timestamp(),
+                    record.target(),
+                    record.args()
+                ),
+                None => writeln!(
+                    buf,
+                    "[{} {level:<5} {}] {}",
+                    buf.timestamp(),
+                    record.target(),
+                    record.args()
+                ),
+            }
+        })
+        .init();
 }
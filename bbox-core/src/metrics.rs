@@ -1,18 +1,39 @@
 use crate::config::MetricsCfg;
 use once_cell::sync::OnceCell;
 use opentelemetry::{
-    global,
+    global::{self, BoxedSpan},
     sdk::{
         export::metrics::aggregation,
         metrics::{controllers, processors, selectors},
         propagation::TraceContextPropagator,
+        trace, Resource,
     },
+    trace::{Span, Tracer},
+    KeyValue,
 };
+use opentelemetry_otlp::WithExportConfig;
 use opentelemetry_prometheus::PrometheusExporter;
 
 fn init_tracer(config: &MetricsCfg) {
-    if let Some(cfg) = &config.jaeger {
+    if config.jaeger.is_some() || config.otlp.is_some() {
         global::set_text_map_propagator(TraceContextPropagator::new()); // default header: traceparent
+    }
+    if let Some(cfg) = &config.otlp {
+        let service_name = cfg.service_name.clone().unwrap_or("bbox".to_string());
+        opentelemetry_otlp::new_pipeline()
+            .tracing()
+            .with_exporter(
+                opentelemetry_otlp::new_exporter()
+                    .tonic()
+                    .with_endpoint(cfg.endpoint.clone()),
+            )
+            .with_trace_config(
+                trace::config()
+                    .with_resource(Resource::new([KeyValue::new("service.name", service_name)])),
+            )
+            .install_batch(opentelemetry::runtime::Tokio)
+            .expect("Failed to initialize OTLP tracer");
+    } else if let Some(cfg) = &config.jaeger {
         opentelemetry_jaeger::new_agent_pipeline()
             .with_endpoint(cfg.agent_endpoint.clone())
             .with_service_name("bbox")
@@ -51,6 +72,15 @@ pub(crate) fn init_metrics_exporter() -> Option<PrometheusExporter> {
     Some(exporter)
 }
 
+/// Start a span as child of the current request span.
+///
+/// The span ends when it is dropped. Without configured exporter, a no-op span is returned.
+pub fn start_span(name: &'static str, attributes: Vec<KeyValue>) -> BoxedSpan {
+    let mut span = global::tracer("bbox").start(name);
+    span.set_attributes(attributes);
+    span
+}
+
 #[derive(Default)]
 pub struct NoMetrics;
 
@@ -53,6 +53,10 @@ pub struct CoreCollection {
     pub title: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub attribution: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub license: Option<String>,
     pub links: Vec<ApiLink>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub extent: Option<CoreExtent>,
@@ -60,6 +64,9 @@ pub struct CoreCollection {
     pub item_type: Option<String>,
     #[serde(skip_serializing_if = "Vec::is_empty")]
     pub crs: Vec<String>,
+    /// STAC summaries: value ranges or distinct values of properties
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub summaries: Option<serde_json::Map<String, serde_json::Value>>,
 }
 
 #[derive(Clone, Debug, Serialize)]
@@ -110,6 +117,9 @@ pub struct CoreFeatures {
 pub struct CoreFeature {
     #[serde(rename = "type")]
     pub type_: String, // Feature
+    /// Bounding box of the geometry (4 or 6 values)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bbox: Option<Vec<f64>>,
     pub geometry: GeoJsonGeometry,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub properties: Option<GeoJsonProperties>,
@@ -143,6 +153,14 @@ pub struct QueryableProperty {
     pub title: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub format: Option<String>,
+    /// Allowed values
+    #[serde(rename = "enum")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub enum_: Option<Vec<serde_json::Value>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub minimum: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub maximum: Option<serde_json::Value>,
 }
 
 #[derive(Clone, Debug, Serialize)]
@@ -1,6 +1,8 @@
-use crate::config::DsPostgisCfg;
+use crate::config::{DsPostgisCfg, PgPoolCfg, PgSslModeCfg};
 use log::info;
-use sqlx::postgres::{PgPool, PgPoolOptions};
+use sqlx::postgres::{PgConnectOptions, PgPool, PgPoolOptions, PgSslMode};
+use std::str::FromStr;
+use std::time::Duration;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -14,19 +16,124 @@ pub type Result<T> = std::result::Result<T, Error>;
 #[derive(Clone, Debug)]
 pub struct PgDatasource {
     pub pool: PgPool,
+    /// Pool was created without connecting
+    lazy: bool,
 }
 
 impl PgDatasource {
     pub async fn from_config(ds: &DsPostgisCfg, envvar: Option<String>) -> Result<Self> {
-        Self::new_pool(&envvar.unwrap_or(ds.url.clone())).await
+        let url = envvar.unwrap_or(ds.url.clone());
+        if ds.lazy {
+            Self::lazy_pool_with_config(&url, ds)
+        } else {
+            Self::pool_with_config(&url, ds).await
+        }
     }
     pub async fn new_pool(url: &str) -> Result<Self> {
+        Self::pool_with_config(url, &DsPostgisCfg::default()).await
+    }
+    /// Create pool without connecting. Connections are established on first use.
+    pub fn new_lazy_pool(url: &str) -> Result<Self> {
+        Self::lazy_pool_with_config(url, &DsPostgisCfg::default())
+    }
+    async fn pool_with_config(url: &str, ds: &DsPostgisCfg) -> Result<Self> {
         info!("Connecting to {url}");
-        let pool = PgPoolOptions::new()
-            .min_connections(0)
-            .max_connections(8)
-            .connect(url)
+        let pool = pool_options(&ds.pool)
+            .connect_with(connect_options(url, ds)?)
             .await?;
-        Ok(PgDatasource { pool })
+        Ok(PgDatasource { pool, lazy: false })
+    }
+    fn lazy_pool_with_config(url: &str, ds: &DsPostgisCfg) -> Result<Self> {
+        info!("Creating lazy connection pool for {url}");
+        let pool = pool_options(&ds.pool)
+            .acquire_timeout(Duration::from_secs(ds.pool.acquire_timeout.unwrap_or(5)))
+            .connect_lazy_with(connect_options(url, ds)?);
+        Ok(PgDatasource { pool, lazy: true })
+    }
+    /// Failed setup should be retried instead of aborting
+    pub fn is_lazy(&self) -> bool {
+        self.lazy
+    }
+    /// Check database connectivity
+    pub async fn check_connection(&self) -> Result<()> {
+        sqlx::query("SELECT 1").execute(&self.pool).await?;
+        Ok(())
+    }
+}
+
+fn pool_options(cfg: &PgPoolCfg) -> PgPoolOptions {
+    let mut options = PgPoolOptions::new()
+        .min_connections(cfg.min_connections.unwrap_or(0))
+        .max_connections(cfg.max_connections.unwrap_or(8));
+    if let Some(secs) = cfg.acquire_timeout {
+        options = options.acquire_timeout(Duration::from_secs(secs));
+    }
+    if let Some(secs) = cfg.idle_timeout {
+        options = options.idle_timeout(Duration::from_secs(secs));
+    }
+    options
+}
+
+fn connect_options(url: &str, ds: &DsPostgisCfg) -> Result<PgConnectOptions> {
+    let mut options = PgConnectOptions::from_str(url)?;
+    if !ds.search_path.is_empty() {
+        options = options.options([("search_path", ds.search_path.join(","))]);
+    }
+    if let Some(sslmode) = &ds.sslmode {
+        options = options.ssl_mode(sslmode.into());
+    }
+    if let Some(cert) = &ds.sslrootcert {
+        options = options.ssl_root_cert(cert);
+    }
+    if let Some(capacity) = ds.pool.statement_cache_capacity {
+        options = options.statement_cache_capacity(capacity);
+    }
+    Ok(options)
+}
+
+impl From<&PgSslModeCfg> for PgSslMode {
+    fn from(mode: &PgSslModeCfg) -> Self {
+        match mode {
+            PgSslModeCfg::Disable => PgSslMode::Disable,
+            PgSslModeCfg::Allow => PgSslMode::Allow,
+            PgSslModeCfg::Prefer => PgSslMode::Prefer,
+            PgSslModeCfg::Require => PgSslMode::Require,
+            PgSslModeCfg::VerifyCa => PgSslMode::VerifyCa,
+            PgSslModeCfg::VerifyFull => PgSslMode::VerifyFull,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use figment::providers::{Format, Toml};
+    use figment::Figment;
+
+    #[test]
+    fn pool_config() {
+        let options = pool_options(&PgPoolCfg::default());
+        assert_eq!(options.get_max_connections(), 8);
+        assert_eq!(options.get_min_connections(), 0);
+
+        let cfg: DsPostgisCfg = Figment::from(Toml::string(
+            r#"
+            url = "postgresql://bbox@localhost/osm"
+            search_path = ["osm", "public"]
+            sslmode = "verify-full"
+            [pool]
+            max_connections = 32
+            acquire_timeout = 10
+            idle_timeout = 60
+            "#,
+        ))
+        .extract()
+        .unwrap();
+        let options = pool_options(&cfg.pool);
+        assert_eq!(options.get_max_connections(), 32);
+        assert_eq!(options.get_acquire_timeout(), Duration::from_secs(10));
+        assert_eq!(options.get_idle_timeout(), Some(Duration::from_secs(60)));
+        assert!(connect_options(&cfg.url, &cfg).is_ok());
+        assert!(connect_options("postgresql://localhost:x", &cfg).is_err());
     }
 }
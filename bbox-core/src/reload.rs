@@ -0,0 +1,308 @@
+//! Configuration reloading without server restart.
+
+use crate::config::{config_path, ReloadCfg};
+use crate::file_search;
+use actix_web::{dev::Payload, error::ErrorInternalServerError, web, FromRequest, HttpRequest};
+use log::{info, warn};
+use once_cell::sync::OnceCell;
+use std::collections::BTreeMap;
+use std::future::{ready, Future, Ready};
+use std::ops::Deref;
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant, SystemTime};
+use tokio::sync::watch;
+
+/// Shared state, which can be replaced while serving requests
+pub struct Reloadable<T> {
+    current: Arc<RwLock<Arc<T>>>,
+}
+
+impl<T> Clone for Reloadable<T> {
+    fn clone(&self) -> Self {
+        Reloadable {
+            current: self.current.clone(),
+        }
+    }
+}
+
+impl<T> Reloadable<T> {
+    pub fn new(value: T) -> Self {
+        Reloadable {
+            current: Arc::new(RwLock::new(Arc::new(value))),
+        }
+    }
+    /// Current state. Requests keep using their state until they are finished.
+    pub fn current(&self) -> Arc<T> {
+        self.current.read().unwrap().clone()
+    }
+    /// Atomically replace state for subsequent requests
+    pub fn replace(&self, value: T) {
+        *self.current.write().unwrap() = Arc::new(value);
+    }
+    /// Atomically replace state with a modified copy of the current state
+    pub fn update(&self, f: impl FnOnce(&T) -> T) {
+        let mut current = self.current.write().unwrap();
+        *current = Arc::new(f(&current));
+    }
+}
+
+/// Request extractor for the current state of a `Reloadable<T>` registered as app data
+pub struct Current<T>(Arc<T>);
+
+impl<T> Deref for Current<T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T: 'static> FromRequest for Current<T> {
+    type Error = actix_web::Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        ready(
+            req.app_data::<web::Data<Reloadable<T>>>()
+                .map(|state| Current(state.current()))
+                .ok_or_else(|| ErrorInternalServerError("Reloadable app data not configured")),
+        )
+    }
+}
+
+/// Notifies services about configuration changes
+pub struct ConfigWatcher {
+    generation: watch::Sender<u64>,
+}
+
+/// Configuration watcher singleton
+pub fn config_watcher() -> &'static ConfigWatcher {
+    static WATCHER: OnceCell<ConfigWatcher> = OnceCell::new();
+    WATCHER.get_or_init(|| ConfigWatcher {
+        generation: watch::channel(0).0,
+    })
+}
+
+impl ConfigWatcher {
+    /// Receiver notified after each reload trigger
+    pub fn subscribe(&self) -> watch::Receiver<u64> {
+        self.generation.subscribe()
+    }
+    /// Request reloading of configuration
+    pub fn trigger(&self) {
+        info!("Reloading configuration");
+        self.generation.send_modify(|generation| *generation += 1);
+    }
+    /// Start watching for configuration file changes and SIGHUP
+    pub fn start(&'static self, cfg: &ReloadCfg) {
+        if cfg.watch {
+            let interval = Duration::from_secs(cfg.interval.unwrap_or(5).max(1));
+            let path = config_path();
+            info!("Watching `{}` for changes", path.display());
+            tokio::spawn(async move {
+                let modified = |path: &std::path::Path| -> Option<SystemTime> {
+                    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+                };
+                let mut last_modified = modified(&path);
+                let mut ticks = tokio::time::interval(interval);
+                loop {
+                    ticks.tick().await;
+                    let current = modified(&path);
+                    if current != last_modified {
+                        last_modified = current;
+                        self.trigger();
+                    }
+                }
+            });
+        }
+        #[cfg(unix)]
+        {
+            use tokio::signal::unix::{signal, SignalKind};
+            match signal(SignalKind::hangup()) {
+                Ok(mut hangup) => {
+                    tokio::spawn(async move {
+                        while hangup.recv().await.is_some() {
+                            self.trigger();
+                        }
+                    });
+                }
+                Err(e) => warn!("Installing SIGHUP handler failed: {e}"),
+            }
+        }
+    }
+}
+
+/// Files with modification time
+type DirSnapshot = BTreeMap<PathBuf, Option<SystemTime>>;
+
+/// Files changed in a watched directory
+#[derive(Debug, Default, PartialEq)]
+pub struct DirChanges {
+    pub added: Vec<PathBuf>,
+    pub modified: Vec<PathBuf>,
+    pub removed: Vec<PathBuf>,
+}
+
+impl DirChanges {
+    fn diff(before: &DirSnapshot, after: &DirSnapshot) -> Self {
+        let mut changes = DirChanges::default();
+        for (path, modified) in after {
+            match before.get(path) {
+                None => changes.added.push(path.clone()),
+                Some(prev) if prev != modified => changes.modified.push(path.clone()),
+                _ => {}
+            }
+        }
+        changes.removed = before
+            .keys()
+            .filter(|path| !after.contains_key(*path))
+            .cloned()
+            .collect();
+        changes
+    }
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.modified.is_empty() && self.removed.is_empty()
+    }
+}
+
+/// Polling watcher for files matching a pattern in a directory
+pub struct DirectoryWatcher {
+    dir: String,
+    patterns: Vec<String>,
+    debounce: Duration,
+    /// State at last poll
+    current: DirSnapshot,
+    /// State at last reported change
+    reported: DirSnapshot,
+    /// Time of last detected change
+    changed_at: Option<Instant>,
+}
+
+impl DirectoryWatcher {
+    /// Watch `dir` for files matching one of `patterns`.
+    /// Existing files are not reported as added.
+    pub fn new(dir: &str, patterns: &[&str], debounce: Duration) -> Self {
+        let mut watcher = DirectoryWatcher {
+            dir: dir.to_string(),
+            patterns: patterns.iter().map(|p| p.to_string()).collect(),
+            debounce,
+            current: DirSnapshot::new(),
+            reported: DirSnapshot::new(),
+            changed_at: None,
+        };
+        watcher.current = watcher.scan();
+        watcher.reported = watcher.current.clone();
+        watcher
+    }
+    fn scan(&self) -> DirSnapshot {
+        self.patterns
+            .iter()
+            .flat_map(|pattern| file_search::search(&self.dir, pattern))
+            .map(|path| {
+                let modified = std::fs::metadata(&path).and_then(|m| m.modified()).ok();
+                (path, modified)
+            })
+            .collect()
+    }
+    /// Check for changes. Changes are reported, when the directory
+    /// content was stable for the debounce duration.
+    pub fn poll(&mut self) -> Option<DirChanges> {
+        let snapshot = self.scan();
+        if snapshot != self.current {
+            self.current = snapshot;
+            self.changed_at = Some(Instant::now());
+            if !self.debounce.is_zero() {
+                return None;
+            }
+        }
+        match self.changed_at {
+            Some(changed_at) if changed_at.elapsed() >= self.debounce => {
+                self.changed_at = None;
+                let changes = DirChanges::diff(&self.reported, &self.current);
+                self.reported = self.current.clone();
+                (!changes.is_empty()).then_some(changes)
+            }
+            _ => None,
+        }
+    }
+    /// Poll directory in a background task and call `on_change` for detected changes
+    pub fn spawn<F, Fut>(mut self, interval: Duration, mut on_change: F)
+    where
+        F: FnMut(DirChanges) -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + Send,
+    {
+        info!("Watching directory `{}` for changes", self.dir);
+        tokio::spawn(async move {
+            let mut ticks = tokio::time::interval(interval);
+            loop {
+                ticks.tick().await;
+                if let Some(changes) = self.poll() {
+                    for path in &changes.added {
+                        info!("File `{}` added", path.display());
+                    }
+                    for path in &changes.modified {
+                        info!("File `{}` modified", path.display());
+                    }
+                    for path in &changes.removed {
+                        info!("File `{}` removed", path.display());
+                    }
+                    on_change(changes).await;
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[actix_web::test]
+    async fn reload_state() {
+        let state = Reloadable::new(vec!["ne_countries"]);
+        let before = state.current();
+        let mut rx = config_watcher().subscribe();
+        let shared = state.clone();
+        let task = tokio::spawn(async move {
+            rx.changed().await.unwrap();
+            shared.replace(vec!["ne_countries", "ne_rivers"]);
+        });
+        config_watcher().trigger();
+        task.await.unwrap();
+        assert_eq!(state.current().len(), 2);
+        // Running requests keep their state
+        assert_eq!(before.len(), 1);
+
+        state.update(|names| names[..1].to_vec());
+        assert_eq!(state.current().len(), 1);
+    }
+
+    #[test]
+    fn directory_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("ne_countries.gpkg");
+        let mut watcher =
+            DirectoryWatcher::new(&dir.path().to_string_lossy(), &["*.gpkg"], Duration::ZERO);
+        assert_eq!(watcher.poll(), None);
+
+        std::fs::write(&path, b"").unwrap();
+        std::fs::write(dir.path().join("README.md"), b"").unwrap();
+        let changes = watcher.poll().unwrap();
+        assert_eq!(changes.added, vec![path.clone()]);
+        assert_eq!(watcher.poll(), None);
+
+        std::fs::remove_file(&path).unwrap();
+        let changes = watcher.poll().unwrap();
+        assert_eq!(changes.removed, vec![path.clone()]);
+
+        // Changes are reported after debounce period
+        let mut watcher = DirectoryWatcher::new(
+            &dir.path().to_string_lossy(),
+            &["*.gpkg"],
+            Duration::from_secs(3600),
+        );
+        std::fs::write(&path, b"").unwrap();
+        assert_eq!(watcher.poll(), None);
+        assert_eq!(watcher.poll(), None);
+    }
+}
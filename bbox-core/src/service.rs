@@ -1,10 +1,24 @@
-use crate::api::{OgcApiInventory, OpenApiDoc};
+use crate::access_log::AccessLog;
+use crate::api::{OgcApiInventory, OpenApiDoc, OpenApiProvider};
+use crate::api_error::set_error_mode;
+use crate::auth::access::AccessControl;
+use crate::auth::api_key::ApiKeyAuth;
 use crate::auth::oidc::OidcClient;
+use crate::cache_control::CacheControl;
 use crate::cli::{CliArgs, CommonCommands, GlobalArgs, NoArgs, NoCommands};
 use crate::config::{ConfigError, CoreServiceCfg, WebserverCfg};
+use crate::config_check::{
+    check_config_requested, check_service_config, exit_with_report, ConfigReport,
+};
+use crate::events::init_webhooks;
+use crate::health::{HealthCheck, HealthChecks};
+use crate::jobs::JobQueue;
 use crate::logger;
 use crate::metrics::{init_metrics_exporter, no_metrics, NoMetrics};
 use crate::ogcapi::{ApiLink, CoreCollection};
+use crate::pg_ds::PgDatasource;
+use crate::reload::config_watcher;
+use crate::sql::SqlIdent;
 use crate::tls::load_rustls_config;
 use actix_cors::Cors;
 use actix_session::{config::PersistentSession, storage::CookieSessionStore, SessionMiddleware};
@@ -17,10 +31,11 @@ use actix_web::{
 use actix_web_opentelemetry::{RequestMetrics, RequestMetricsBuilder, RequestTracing};
 use async_trait::async_trait;
 use clap::{ArgMatches, Args, Parser, Subcommand};
-use log::info;
+use log::{error, info};
 use once_cell::sync::OnceCell;
 use opentelemetry_prometheus::PrometheusExporter;
 use prometheus::Registry;
+use std::sync::Arc;
 
 pub trait ServiceConfig: Sized {
     /// Initialize service config from config files, environment variables and cli args
@@ -48,13 +63,23 @@ pub trait OgcApiService: Clone + Send {
     fn openapi_yaml(&self) -> Option<&str> {
         None
     }
+    /// OpenAPI paths generated at runtime
+    fn openapi_provider(&self) -> Option<Arc<dyn OpenApiProvider>> {
+        None
+    }
     /// Service metrics
     fn metrics(&self) -> &'static Self::Metrics;
     /// Add metrics to Prometheus registry
     fn add_metrics(&self, _prometheus: &Registry) {}
+    /// Readiness check of service dependencies
+    fn health_check(&self) -> Option<Arc<dyn HealthCheck>> {
+        None
+    }
     async fn cli_run(&self, _cli: &ArgMatches) -> bool {
         false
     }
+    /// Validate configuration and dependencies without creating the service
+    async fn check_config(_cfg: &Self::Config, _report: &mut ConfigReport) {}
 }
 
 pub trait ServiceEndpoints {
@@ -99,6 +124,10 @@ pub struct CoreService {
     pub(crate) openapi: OpenApiDoc,
     pub(crate) metrics: Option<PrometheusExporter>,
     pub(crate) oidc: Option<OidcClient>,
+    pub(crate) access: AccessControl,
+    pub(crate) api_keys: ApiKeyAuth,
+    pub(crate) jobs: JobQueue,
+    pub(crate) health: HealthChecks,
 }
 
 impl CoreService {
@@ -120,19 +149,48 @@ impl CoreService {
                 self.openapi.extend(yaml, api_base);
             }
         }
+        if let Some(provider) = svc.openapi_provider() {
+            self.openapi.add_provider(provider);
+        }
 
         if let Some(metrics) = &self.metrics {
             svc.add_metrics(metrics.registry())
         }
+
+        if let Some(check) = svc.health_check() {
+            self.health.add(check);
+        }
     }
     pub fn has_cors(&self) -> bool {
         self.web_config.cors.is_some()
     }
     pub fn cors(&self) -> Cors {
         if let Some(cors_cfg) = self.web_config.cors.as_ref() {
-            let mut cors = Cors::default().allowed_methods(vec!["GET"]);
+            let methods = cors_cfg
+                .allowed_methods
+                .as_ref()
+                .map(|methods| methods.iter().map(String::as_str).collect())
+                .unwrap_or(vec!["GET"]);
+            let mut cors = Cors::default().allowed_methods(methods);
             if cors_cfg.allow_all_origins {
-                cors = cors.allow_any_origin().send_wildcard();
+                cors = cors.allow_any_origin();
+                if !cors_cfg.allow_credentials {
+                    cors = cors.send_wildcard();
+                }
+            }
+            for origin in &cors_cfg.allowed_origins {
+                cors = cors.allowed_origin(origin);
+            }
+            if let Some(headers) = &cors_cfg.allowed_headers {
+                cors = cors.allowed_headers(headers.iter().map(String::as_str));
+            } else {
+                cors = cors.allow_any_header();
+            }
+            if let Some(max_age) = cors_cfg.max_age {
+                cors = cors.max_age(max_age);
+            }
+            if cors_cfg.allow_credentials {
+                cors = cors.supports_credentials();
             }
             cors
         } else {
@@ -142,6 +200,35 @@ impl CoreService {
     pub fn has_metrics(&self) -> bool {
         self.metrics.is_some()
     }
+    pub fn has_access_rules(&self) -> bool {
+        self.access.has_rules()
+    }
+    /// Access control middleware
+    pub fn access_control(&self) -> AccessControl {
+        self.access.clone()
+    }
+    pub fn has_api_keys(&self) -> bool {
+        self.api_keys.is_enabled()
+    }
+    /// API key middleware
+    pub fn api_key_auth(&self) -> ApiKeyAuth {
+        self.api_keys.clone()
+    }
+    /// Background job queue
+    pub fn jobs(&self) -> &JobQueue {
+        &self.jobs
+    }
+    /// Health checks of registered services
+    pub fn health_checks(&self) -> &HealthChecks {
+        &self.health
+    }
+    pub fn has_cache_control(&self) -> bool {
+        !self.web_config.cache_control.is_empty()
+    }
+    /// Cache-Control header middleware
+    pub fn cache_control(&self) -> CacheControl {
+        CacheControl::new(&self.web_config.cache_control)
+    }
     /// Request tracing middleware
     pub fn middleware(&self) -> RequestTracing {
         RequestTracing::new()
@@ -181,12 +268,75 @@ impl OgcApiService for CoreService {
         } else {
             None
         };
+        let access = if let Some(auth_cfg) = &cfg.auth {
+            AccessControl::from_config(auth_cfg).await
+        } else {
+            AccessControl::default()
+        };
+        let api_keys =
+            if let Some(api_keys_cfg) = cfg.auth.as_ref().and_then(|a| a.api_keys.as_ref()) {
+                match ApiKeyAuth::from_config(api_keys_cfg).await {
+                    Ok(api_keys) => api_keys,
+                    Err(e) => {
+                        // Keys in the table are refused
+                        error!("{e}");
+                        ApiKeyAuth::configured_keys(api_keys_cfg)
+                    }
+                }
+            } else {
+                ApiKeyAuth::default()
+            };
+        if let Some(metrics) = &metrics {
+            if api_keys.is_enabled() {
+                api_keys.register_metrics(metrics.registry());
+            }
+        }
+        let jobs = match JobQueue::from_config(&cfg.jobs).await {
+            Ok(jobs) => jobs,
+            Err(e) => {
+                // Job states are not persisted
+                error!("{e}");
+                JobQueue::in_memory(&cfg.jobs)
+            }
+        };
+        init_webhooks(&cfg.webhooks);
+        config_watcher().start(&cfg.reload);
+        let web_config = cfg.webserver.clone().unwrap_or_default();
+        set_error_mode(web_config.error_mode);
         CoreService {
-            web_config: cfg.webserver.clone().unwrap_or_default(),
+            web_config,
             ogcapi: OgcApiInventory::default(),
             openapi: OpenApiDoc::new(),
             metrics,
             oidc,
+            access,
+            api_keys,
+            jobs,
+            health: HealthChecks::default(),
+        }
+    }
+    async fn check_config(cfg: &Self::Config, report: &mut ConfigReport) {
+        let web_config = cfg.webserver.clone().unwrap_or_default();
+        let addr = web_config.server_addr.parse::<std::net::SocketAddr>();
+        report.check("core", "webserver.server_addr", &addr);
+        for file in [&web_config.tls_cert, &web_config.tls_key]
+            .into_iter()
+            .flatten()
+        {
+            let result = std::fs::metadata(file).map_err(|e| format!("`{file}`: {e}"));
+            report.check("core", "webserver TLS", &result);
+        }
+        if let Some(pg) = &cfg.jobs.postgres {
+            let result = PgDatasource::new_pool(&pg.url).await;
+            report.check("core", "jobs.postgres", &result);
+            let result = pg
+                .table
+                .as_deref()
+                .unwrap_or("bbox_jobs")
+                .split('.')
+                .map(SqlIdent::new)
+                .collect::<Result<Vec<_>, _>>();
+            report.check("core", "jobs.postgres.table", &result);
         }
     }
     fn landing_page_links(&self, _api_base: &str) -> Vec<ApiLink> {
@@ -253,6 +403,12 @@ pub async fn run_service<T: OgcApiService + ServiceEndpoints + Sync + 'static>(
     let matches = cli.cli_matches();
 
     let core_cfg = CoreServiceCfg::initialize(&matches).unwrap();
+    if check_config_requested(&matches) {
+        let mut report = ConfigReport::default();
+        CoreService::check_config(&core_cfg, &mut report).await;
+        check_service_config::<T>(&matches, &mut report).await;
+        exit_with_report(&report);
+    }
     let mut core = CoreService::create(&core_cfg, &core_cfg).await;
 
     let service_cfg = T::Config::initialize(&matches).unwrap();
@@ -274,6 +430,13 @@ pub async fn run_service<T: OgcApiService + ServiceEndpoints + Sync + 'static>(
         App::new()
             .configure(|cfg| core.register_endpoints(cfg))
             .configure(|cfg| service.register_endpoints(cfg))
+            .wrap(Condition::new(
+                core.has_cache_control(),
+                core.cache_control(),
+            ))
+            // Always active for protecting admin endpoints
+            .wrap(core.access_control())
+            .wrap(Condition::new(core.has_api_keys(), core.api_key_auth()))
             .wrap(
                 SessionMiddleware::builder(CookieSessionStore::default(), secret_key.clone())
                     .cookie_name("bbox".to_owned())
@@ -284,7 +447,7 @@ pub async fn run_service<T: OgcApiService + ServiceEndpoints + Sync + 'static>(
             .wrap(Condition::new(core.has_cors(), core.cors()))
             .wrap(middleware::Compress::default())
             .wrap(middleware::NormalizePath::trim())
-            .wrap(middleware::Logger::default())
+            .wrap(AccessLog)
     });
     if let Some(tls_config) = tls_config {
         info!("Starting web server at https://{server_addr}");
@@ -295,3 +458,72 @@ pub async fn run_service<T: OgcApiService + ServiceEndpoints + Sync + 'static>(
     }
     server.workers(workers).run().await
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::CorsCfg;
+    use actix_web::http::{header, Method, StatusCode};
+    use actix_web::test::{call_service, init_service, TestRequest};
+    use actix_web::{web, App, HttpResponse};
+
+    fn core_service(cors: CorsCfg) -> CoreService {
+        CoreService {
+            web_config: WebserverCfg {
+                cors: Some(cors),
+                ..Default::default()
+            },
+            ogcapi: OgcApiInventory::default(),
+            openapi: OpenApiDoc::new(),
+            metrics: None,
+            oidc: None,
+            access: AccessControl::default(),
+            api_keys: ApiKeyAuth::default(),
+            jobs: JobQueue::default(),
+            health: HealthChecks::default(),
+        }
+    }
+
+    #[actix_web::test]
+    async fn cors_preflight() {
+        let core = core_service(CorsCfg {
+            allowed_origins: vec!["https://maps.example.com".to_string()],
+            max_age: Some(3600),
+            ..Default::default()
+        });
+        let app = init_service(
+            App::new()
+                .wrap(core.cors())
+                .default_service(web::to(|| async { HttpResponse::Ok().finish() })),
+        )
+        .await;
+
+        let req = TestRequest::default()
+            .method(Method::OPTIONS)
+            .uri("/xyz/ne/0/0/0.mvt")
+            .insert_header((header::ORIGIN, "https://maps.example.com"))
+            .insert_header((header::ACCESS_CONTROL_REQUEST_METHOD, "GET"))
+            .insert_header((header::ACCESS_CONTROL_REQUEST_HEADERS, "x-api-key"))
+            .to_request();
+        let resp = call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let headers = resp.headers();
+        assert_eq!(
+            headers.get(header::ACCESS_CONTROL_ALLOW_ORIGIN).unwrap(),
+            "https://maps.example.com"
+        );
+        assert_eq!(headers.get(header::ACCESS_CONTROL_MAX_AGE).unwrap(), "3600");
+
+        let req = TestRequest::get()
+            .uri("/collections")
+            .insert_header((header::ORIGIN, "https://maps.example.com"))
+            .to_request();
+        let resp = call_service(&app, req).await;
+        assert_eq!(
+            resp.headers()
+                .get(header::ACCESS_CONTROL_ALLOW_ORIGIN)
+                .unwrap(),
+            "https://maps.example.com"
+        );
+    }
+}
@@ -35,6 +35,11 @@ impl<T> NamedObjectStore<T> {
         self.lookup.get_mut(name)
     }
 
+    /// Iterate over all named objects
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &T)> {
+        self.lookup.iter()
+    }
+
     pub fn get_default(&self) -> Option<&T> {
         let no_default = "".to_string();
         let name = self.default.as_ref().unwrap_or(&no_default);
@@ -0,0 +1,65 @@
+//! SQL identifier handling shared by datasources.
+
+use std::fmt;
+use thiserror::Error;
+
+#[derive(Error, Debug, PartialEq)]
+#[error("invalid SQL identifier `{0}`")]
+pub struct InvalidSqlIdent(pub String);
+
+/// Validated identifier like a table or column name
+///
+/// Identifiers are always quoted when used in SQL, so only empty names and
+/// control characters are rejected.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct SqlIdent(String);
+
+impl SqlIdent {
+    pub fn new(name: &str) -> Result<Self, InvalidSqlIdent> {
+        if name.is_empty() || name.chars().any(char::is_control) {
+            return Err(InvalidSqlIdent(name.escape_debug().to_string()));
+        }
+        Ok(SqlIdent(name.to_string()))
+    }
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+    /// Double quoted identifier (PostgreSQL, SQLite)
+    pub fn quoted(&self) -> String {
+        format!("\"{}\"", self.0.replace('"', "\"\""))
+    }
+    /// Backtick quoted identifier (MySQL)
+    pub fn backquoted(&self) -> String {
+        format!("`{}`", self.0.replace('`', "``"))
+    }
+    /// Identifier as string literal, e.g. for JSON object keys
+    pub fn literal(&self) -> String {
+        format!("'{}'", self.0.replace('\'', "''"))
+    }
+}
+
+impl fmt::Display for SqlIdent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.quoted())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quoting() {
+        let ident = SqlIdent::new(r#"my "col""#).unwrap();
+        assert_eq!(ident.quoted(), r#""my ""col""""#);
+        assert_eq!(ident.to_string(), ident.quoted());
+        let ident = SqlIdent::new("it's`").unwrap();
+        assert_eq!(ident.backquoted(), "`it's```");
+        assert_eq!(ident.literal(), "'it''s`'");
+        assert!(SqlIdent::new("").is_err());
+        assert_eq!(
+            SqlIdent::new("fid\0; DROP TABLE x"),
+            Err(InvalidSqlIdent("fid\\0; DROP TABLE x".to_string()))
+        );
+    }
+}
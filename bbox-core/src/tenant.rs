@@ -0,0 +1,137 @@
+//! Isolated configurations served under the URL prefix `/t/{tenant}`.
+//!
+//! Each tenant has its own configuration file with collections, tilesets and access rules.
+//! Services of a tenant are created from this file and their endpoints are registered
+//! in a scope, so their inventory and tileset registry are resolved from the request path.
+
+use crate::auth::access::AccessControl;
+use crate::config::{config_error_exit, from_config_opt_or_exit, AuthCfg, ConfigError, TenantCfg};
+use crate::service::ServiceConfig;
+use clap::ArgMatches;
+use figment::providers::{Format, Toml};
+use figment::Figment;
+use log::info;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+
+#[derive(Clone)]
+struct TenantConfig {
+    path: PathBuf,
+    figment: &'static Figment,
+}
+
+tokio::task_local! {
+    /// Configuration of the tenant whose services are created in the current task
+    static TENANT_CONFIG: TenantConfig;
+}
+
+/// Configuration of the current tenant, if any
+pub(crate) fn tenant_config() -> Option<&'static Figment> {
+    TENANT_CONFIG.try_with(|cfg| cfg.figment).ok()
+}
+
+/// Configuration file of the current tenant, if any
+pub(crate) fn tenant_config_path() -> Option<PathBuf> {
+    TENANT_CONFIG.try_with(|cfg| cfg.path.clone()).ok()
+}
+
+/// Read tenant configuration file
+pub fn read_tenant_config(path: &Path) -> Figment {
+    Figment::new().merge(Toml::file(path))
+}
+
+/// Tenant with its own configuration
+#[derive(Clone)]
+pub struct Tenant {
+    pub name: String,
+    config: TenantConfig,
+    access: AccessControl,
+}
+
+impl Tenant {
+    pub async fn from_config(cfg: &TenantCfg) -> Self {
+        if cfg.name.is_empty() || cfg.name.contains('/') {
+            config_error_exit(format!("Invalid tenant name `{}`", cfg.name));
+        }
+        info!(
+            "Reading configuration of tenant `{}` from `{}`",
+            cfg.name,
+            cfg.config.display()
+        );
+        // Tenant configurations are kept like the application configuration singleton
+        let figment: &'static Figment = Box::leak(Box::new(read_tenant_config(&cfg.config)));
+        let config = TenantConfig {
+            path: cfg.config.clone(),
+            figment,
+        };
+        let auth: Option<AuthCfg> =
+            TENANT_CONFIG.sync_scope(config.clone(), || from_config_opt_or_exit("auth"));
+        let path_prefix = format!("/t/{}", cfg.name);
+        let access = if let Some(auth_cfg) = &auth {
+            AccessControl::from_config(auth_cfg)
+                .await
+                .with_path_prefix(&path_prefix)
+        } else {
+            AccessControl::default()
+        };
+        Tenant {
+            name: cfg.name.clone(),
+            config,
+            access,
+        }
+    }
+    /// URL path prefix of tenant endpoints
+    pub fn path_prefix(&self) -> String {
+        format!("/t/{}", self.name)
+    }
+    /// Initialize service config from the tenant configuration file
+    pub fn service_config<T: ServiceConfig>(&self, cli: &ArgMatches) -> Result<T, ConfigError> {
+        TENANT_CONFIG.sync_scope(self.config.clone(), || T::initialize(cli))
+    }
+    /// Run service creation with the tenant configuration
+    pub async fn scope<F: Future>(&self, f: F) -> F::Output {
+        TENANT_CONFIG.scope(self.config.clone(), f).await
+    }
+    pub fn has_access_rules(&self) -> bool {
+        self.access.has_rules()
+    }
+    /// Access control middleware with rule paths relative to the tenant prefix
+    pub fn access_control(&self) -> AccessControl {
+        self.access.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::app_config;
+    use std::io::Write;
+
+    #[actix_web::test]
+    async fn tenant_configuration() {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        writeln!(
+            file,
+            r#"
+            [[auth.rule]]
+            collection = "private"
+            groups = ["admin"]
+            "#
+        )
+        .unwrap();
+        let tenant = Tenant::from_config(&TenantCfg {
+            name: "acme".to_string(),
+            config: file.path().to_path_buf(),
+        })
+        .await;
+        assert_eq!(tenant.path_prefix(), "/t/acme");
+        assert!(tenant.has_access_rules());
+        assert!(tenant_config().is_none());
+        let path = tenant.scope(async { tenant_config_path() }).await;
+        assert_eq!(path.as_deref(), Some(file.path()));
+        let rules = tenant
+            .scope(async { app_config().find_value("auth.rule").is_ok() })
+            .await;
+        assert!(rules);
+    }
+}
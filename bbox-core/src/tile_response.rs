@@ -1,8 +1,9 @@
 use actix_web::http::header::{
-    self, HeaderMap, HeaderValue, TryIntoHeaderPair, TryIntoHeaderValue,
+    self, EntityTag, HeaderMap, HeaderValue, TryIntoHeaderPair, TryIntoHeaderValue,
 };
 use flate2::{read::GzDecoder, read::GzEncoder, Compression as GzCompression};
 use std::io::{Cursor, Read};
+use std::time::SystemTime;
 
 /// Tile data compression
 #[derive(Clone, PartialEq, Debug)]
@@ -10,10 +11,86 @@ pub enum Compression {
     // Unknown,
     None,
     Gzip,
-    // Brotli,
+    Brotli,
     // Zstd,
 }
 
+/// Brotli compression level used for tiles
+const BROTLI_QUALITY: u32 = 6;
+/// Brotli window size (log2)
+const BROTLI_LGWIN: u32 = 22;
+const BROTLI_BUFFER_SIZE: usize = 4096;
+
+type TileBody = Box<dyn Read + Send + Sync>;
+
+impl Compression {
+    /// Compression from `Content-Encoding` header value
+    pub fn from_content_encoding(encoding: &str) -> Self {
+        match encoding {
+            "gzip" => Compression::Gzip,
+            "br" => Compression::Brotli,
+            _ => Compression::None,
+        }
+    }
+    /// `Content-Encoding` header value
+    pub fn content_encoding(&self) -> Option<&'static str> {
+        match self {
+            Compression::None => None,
+            Compression::Gzip => Some("gzip"),
+            Compression::Brotli => Some("br"),
+        }
+    }
+    fn encoder(&self, body: TileBody) -> TileBody {
+        match self {
+            Compression::None => body,
+            Compression::Gzip => Box::new(GzEncoder::new(body, GzCompression::fast())),
+            Compression::Brotli => Box::new(brotli::CompressorReader::new(
+                body,
+                BROTLI_BUFFER_SIZE,
+                BROTLI_QUALITY,
+                BROTLI_LGWIN,
+            )),
+        }
+    }
+    fn decoder(&self, body: TileBody) -> TileBody {
+        match self {
+            Compression::None => body,
+            Compression::Gzip => Box::new(GzDecoder::new(body)),
+            Compression::Brotli => Box::new(brotli::Decompressor::new(body, BROTLI_BUFFER_SIZE)),
+        }
+    }
+}
+
+/// Recompress tile body and set `Content-Encoding` header accordingly
+fn recompress(
+    headers: &mut HeaderMap,
+    body: TileBody,
+    from: &Compression,
+    to: &Compression,
+) -> TileBody {
+    if from == to {
+        return body;
+    }
+    let body = to.encoder(from.decoder(body));
+    match to.content_encoding() {
+        Some(encoding) => {
+            headers.insert(header::CONTENT_ENCODING, HeaderValue::from_static(encoding));
+        }
+        None => {
+            headers.remove(header::CONTENT_ENCODING);
+        }
+    }
+    body
+}
+
+fn content_compression(headers: &HeaderMap) -> Compression {
+    headers
+        .get(header::CONTENT_ENCODING)
+        .and_then(|v| v.to_str().ok())
+        .map(Compression::from_content_encoding)
+        .unwrap_or(Compression::None)
+}
+
 /// Tile reader response
 pub struct TileResponse {
     headers: HeaderMap,
@@ -21,6 +98,7 @@ pub struct TileResponse {
 }
 
 /// Tile response data
+#[derive(Clone)]
 pub struct TileResponseData {
     headers: HeaderMap,
     pub body: Vec<u8>,
@@ -47,6 +125,14 @@ impl TileResponse {
         }
         self
     }
+    /// Set modification time of stored tile (`Last-Modified` header).
+    pub fn set_modified(&mut self, time: SystemTime) -> &mut Self {
+        self.insert_header(header::LastModified(time.into()))
+    }
+    /// Set entity tag (`ETag` header).
+    pub fn set_etag(&mut self, etag: EntityTag) -> &mut Self {
+        self.insert_header(header::ETag(etag))
+    }
     pub fn set_headers(&mut self, headers: &HeaderMap) -> &mut Self {
         for (key, value) in headers {
             self.insert_header((key, value));
@@ -59,52 +145,36 @@ impl TileResponse {
     }
     /// Apply optional de-/compression
     pub fn with_compression(mut self, compression: &Compression) -> TileResponse {
-        match (self.compression(), compression) {
-            (Compression::None, Compression::Gzip) => {
-                let gz = GzEncoder::new(self.body, GzCompression::fast());
-                self.body = Box::new(gz);
-                self.insert_header(("Content-Encoding", "gzip"));
-            }
-            (Compression::Gzip, Compression::None) => {
-                let gz = GzDecoder::new(self.body);
-                self.body = Box::new(gz);
-                self.headers.remove(header::CONTENT_ENCODING);
-            }
-            _ => {}
-        }
+        let from = self.compression();
+        self.body = recompress(&mut self.headers, self.body, &from, compression);
         self
     }
     pub fn content_type(&self) -> Option<&HeaderValue> {
         self.headers.get(header::CONTENT_TYPE)
     }
     pub fn compression(&self) -> Compression {
-        match self.headers.get(header::CONTENT_ENCODING) {
-            Some(v) if v == HeaderValue::from_static("gzip") => Compression::Gzip,
-            _ => Compression::None,
-        }
+        content_compression(&self.headers)
+    }
+    /// Modification time of stored tile
+    pub fn modified(&self) -> Option<SystemTime> {
+        last_modified(&self.headers)
+    }
+    /// Entity tag of stored tile
+    pub fn etag(&self) -> Option<EntityTag> {
+        self.headers.get(header::ETAG)?.to_str().ok()?.parse().ok()
     }
     pub fn headers(&self) -> &HeaderMap {
         &self.headers
     }
     /// Read tile body with optional compression
-    pub fn read_bytes(
-        mut self,
-        compression: &Compression,
-    ) -> Result<TileResponseData, std::io::Error> {
+    pub fn read_bytes(self, compression: &Compression) -> Result<TileResponseData, std::io::Error> {
+        let from = self.compression();
         let mut response = TileResponseData {
             headers: self.headers,
             body: Vec::new(),
         };
-        match compression {
-            Compression::Gzip => {
-                let mut gz = GzEncoder::new(self.body, GzCompression::fast());
-                gz.read_to_end(&mut response.body)?;
-                response.insert_header(("Content-Encoding", "gzip"));
-            }
-            Compression::None => {
-                self.body.read_to_end(&mut response.body)?;
-            }
-        }
+        let mut body = recompress(&mut response.headers, self.body, &from, compression);
+        body.read_to_end(&mut response.body)?;
         Ok(response)
     }
 }
@@ -116,6 +186,12 @@ impl Default for TileResponse {
 }
 
 impl TileResponseData {
+    pub fn new(body: Vec<u8>) -> Self {
+        TileResponseData {
+            headers: HeaderMap::new(),
+            body,
+        }
+    }
     /// Insert a header, replacing any that were set with an equivalent field name.
     pub fn insert_header(&mut self, header: impl TryIntoHeaderPair) -> &mut Self {
         if let Ok((key, value)) = header.try_into_pair() {
@@ -123,29 +199,83 @@ impl TileResponseData {
         }
         self
     }
+    /// Set modification time of stored tile (`Last-Modified` header).
+    pub fn set_modified(&mut self, time: SystemTime) -> &mut Self {
+        self.insert_header(header::LastModified(time.into()))
+    }
+    /// Set entity tag (`ETag` header) from tile body.
+    pub fn set_content_etag(&mut self) -> &mut Self {
+        let etag = content_etag(&self.body);
+        self.insert_header(header::ETag(etag))
+    }
     pub fn compression(&self) -> Compression {
-        match self.headers.get(header::CONTENT_ENCODING) {
-            Some(v) if v == HeaderValue::from_static("gzip") => Compression::Gzip,
-            _ => Compression::None,
-        }
+        content_compression(&self.headers)
     }
     /// Read tile body with optional compression
     pub fn as_response(self, compression: &Compression) -> TileResponse {
         let mut response = TileResponse::new();
         response.set_headers(&self.headers);
-        match (self.compression(), compression) {
-            (Compression::None, Compression::Gzip) => {
-                let gz = GzEncoder::new(Cursor::new(self.body), GzCompression::fast());
-                response.body = Box::new(gz);
-                response.insert_header(("Content-Encoding", "gzip"));
-            }
-            (Compression::Gzip, Compression::None) => {
-                let gz = GzDecoder::new(Cursor::new(self.body));
-                response.body = Box::new(gz);
-                response.headers.remove(header::CONTENT_ENCODING);
-            }
-            _ => response.body = Box::new(Cursor::new(self.body)),
-        }
+        let from = self.compression();
+        response.body = recompress(
+            &mut response.headers,
+            Box::new(Cursor::new(self.body)),
+            &from,
+            compression,
+        );
         response
     }
 }
+
+/// Entity tag of tile content.
+///
+/// Tags are weak, because the same tile may be delivered with different content encodings.
+pub fn content_etag(data: &[u8]) -> EntityTag {
+    EntityTag::new_weak(format!("{:08x}-{:x}", crc32fast::hash(data), data.len()))
+}
+
+fn last_modified(headers: &HeaderMap) -> Option<SystemTime> {
+    let value = headers.get(header::LAST_MODIFIED)?.to_str().ok()?;
+    value.parse::<header::HttpDate>().ok().map(Into::into)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tile_metadata() {
+        let mut response = TileResponse::new();
+        assert_eq!(response.modified(), None);
+        assert_eq!(response.etag(), None);
+        let modified = SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1700000000);
+        response.set_modified(modified);
+        response.set_etag(content_etag(&[1, 0, 1]));
+        assert_eq!(response.modified(), Some(modified));
+        let etag = response.etag().unwrap();
+        assert!(etag.weak);
+        assert!(etag.weak_eq(&content_etag(&[1, 0, 1])));
+        assert!(!etag.weak_eq(&content_etag(&[1, 0, 0])));
+    }
+
+    #[test]
+    fn recompression() {
+        let data = b"tile data tile data tile data".to_vec();
+        let brotli = TileResponse::new()
+            .with_body(Box::new(Cursor::new(data.clone())))
+            .read_bytes(&Compression::Brotli)
+            .unwrap();
+        assert_eq!(brotli.compression(), Compression::Brotli);
+        assert_ne!(brotli.body, data);
+        // Brotli to Gzip
+        let gzip = brotli.as_response(&Compression::Gzip);
+        assert_eq!(gzip.compression(), Compression::Gzip);
+        assert_eq!(
+            gzip.headers().get(header::CONTENT_ENCODING).unwrap(),
+            "gzip"
+        );
+        // Gzip to uncompressed
+        let plain = gzip.read_bytes(&Compression::None).unwrap();
+        assert_eq!(plain.compression(), Compression::None);
+        assert_eq!(plain.body, data);
+    }
+}
@@ -0,0 +1,161 @@
+//! Programmatic setup of a feature service, e.g. for embedding into other actix applications.
+//! Endpoints are added with `App::new().configure(|cfg| service.register_endpoints(cfg))`.
+
+use crate::config::{
+    CollectionSourceCfg, ConfiguredCollectionCfg, DerivedCollectionsCfg, DownloadsCfg,
+    DsFiledirCfg, FeatureServiceCfg, GpkgCollectionCfg, PostgisCollectionCfg,
+};
+use crate::error::Result;
+use crate::inventory::FeatureCollection;
+use crate::FeatureService;
+use bbox_core::config::{DatasourceCfg, DsGpkgCfg, DsPostgisCfg, NamedDatasourceCfg};
+use std::path::PathBuf;
+
+/// Feature service configured without configuration files
+#[derive(Default)]
+pub struct FeatureServiceBuilder {
+    config: FeatureServiceCfg,
+    collections: Vec<FeatureCollection>,
+}
+
+impl FeatureServiceBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Start with a deserialized or otherwise prepared configuration
+    pub fn from_config(config: FeatureServiceCfg) -> Self {
+        FeatureServiceBuilder {
+            config,
+            collections: Vec::new(),
+        }
+    }
+    pub fn add_datasource(mut self, name: &str, datasource: DatasourceCfg) -> Self {
+        self.config.datasources.push(NamedDatasourceCfg {
+            name: name.to_string(),
+            datasource,
+        });
+        self
+    }
+    pub fn add_postgis_datasource(self, name: &str, url: &str) -> Self {
+        let cfg = DsPostgisCfg {
+            url: url.to_string(),
+            ..Default::default()
+        };
+        self.add_datasource(name, DatasourceCfg::Postgis(cfg))
+    }
+    pub fn add_gpkg_datasource(self, name: &str, path: impl Into<PathBuf>) -> Self {
+        let cfg = DsGpkgCfg { path: path.into() };
+        self.add_datasource(name, DatasourceCfg::Gpkg(cfg))
+    }
+    pub fn add_collection(mut self, collection: ConfiguredCollectionCfg) -> Self {
+        self.config.collections.push(collection);
+        self
+    }
+    /// Publish PostGIS table with detected id and geometry columns
+    pub fn add_postgis_collection(
+        self,
+        name: &str,
+        datasource: &str,
+        table_schema: &str,
+        table_name: &str,
+    ) -> Self {
+        let source = CollectionSourceCfg::Postgis(PostgisCollectionCfg {
+            datasource: Some(datasource.to_string()),
+            table_schema: Some(table_schema.to_string()),
+            table_name: Some(table_name.to_string()),
+            ..Default::default()
+        });
+        self.add_collection(ConfiguredCollectionCfg::new(name, source))
+    }
+    /// Publish GeoPackage table
+    pub fn add_gpkg_collection(self, name: &str, datasource: &str, table_name: &str) -> Self {
+        let source = CollectionSourceCfg::Gpkg(GpkgCollectionCfg {
+            datasource: Some(datasource.to_string()),
+            table_name: Some(table_name.to_string()),
+            ..Default::default()
+        });
+        self.add_collection(ConfiguredCollectionCfg::new(name, source))
+    }
+    /// Add collection with custom `CollectionSource` implementation
+    pub fn add_feature_collection(mut self, collection: FeatureCollection) -> Self {
+        self.collections.push(collection);
+        self
+    }
+    /// Publish GeoPackage and FlatGeobuf files found in directory
+    pub fn scan_directory(mut self, dir: &str) -> Self {
+        self.config.auto_collections.directory.push(DsFiledirCfg {
+            dir: dir.to_string(),
+            watch: false,
+            debounce: None,
+        });
+        self
+    }
+    /// Maximal duration of PostGIS collection queries in seconds
+    pub fn query_timeout(mut self, seconds: u64) -> Self {
+        self.config.query_timeout = Some(seconds);
+        self
+    }
+    /// Generate collection downloads on demand in this directory
+    pub fn downloads_dir(mut self, dir: &str) -> Self {
+        self.config.downloads = Some(DownloadsCfg {
+            dir: dir.to_string(),
+            max_jobs: None,
+            max_age: None,
+        });
+        self
+    }
+    /// Connect datasources and setup collections.
+    /// Unlike `FeatureService::create`, errors are returned and configuration files are not watched.
+    pub async fn build(self) -> Result<FeatureService> {
+        if let Some(downloads) = &self.config.downloads {
+            std::fs::create_dir_all(&downloads.dir)?;
+        }
+        let derived = DerivedCollectionsCfg::default();
+        let (datasources, mut inventory) =
+            FeatureService::load_inventory(&self.config, &derived).await?;
+        for collection in self.collections {
+            inventory.add_collection(collection);
+        }
+        Ok(FeatureService::from_parts(
+            datasources,
+            inventory,
+            derived,
+            self.config.downloads,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::Error;
+
+    #[tokio::test]
+    async fn gpkg_service() {
+        let service = FeatureServiceBuilder::new()
+            .add_gpkg_datasource("ne", "../assets/ne_extracts.gpkg")
+            .add_gpkg_collection("lakes", "ne", "ne_10m_lakes")
+            .build()
+            .await
+            .unwrap();
+        let inventory = service.inventory.current();
+        assert!(inventory.core_collection("lakes").is_some());
+        let collection = service.refresh_collection("lakes").await.unwrap();
+        assert_eq!(collection.id, "lakes");
+        assert!(service
+            .inventory
+            .current()
+            .core_collection("lakes")
+            .is_some());
+        assert!(matches!(
+            service.refresh_collection("missing").await,
+            Err(Error::CollectionNotFound(_))
+        ));
+
+        let result = FeatureServiceBuilder::new()
+            .add_gpkg_collection("lakes", "missing", "ne_10m_lakes")
+            .build()
+            .await;
+        assert!(result.is_err());
+    }
+}
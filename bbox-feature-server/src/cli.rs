@@ -0,0 +1,28 @@
+use clap::{Args, Parser};
+use std::path::PathBuf;
+
+#[derive(Parser, Debug)]
+pub enum Commands {
+    /// Export a feature collection into a GeoPackage
+    #[command(arg_required_else_help = true)]
+    Dump(DumpArgs),
+}
+
+#[derive(Debug, Args)]
+pub struct DumpArgs {
+    /// Collection id
+    #[arg(long)]
+    pub collection: String,
+    /// GeoPackage file to create
+    #[arg(long)]
+    pub output: PathBuf,
+    /// Extent minx,miny,maxx,maxy (in WGS 84)
+    #[arg(long)]
+    pub bbox: Option<String>,
+    /// Date-time or interval (e.g. 2018-02-12T00:00:00Z/..)
+    #[arg(long)]
+    pub datetime: Option<String>,
+    /// CQL2 text filter expression
+    #[arg(long)]
+    pub filter: Option<String>,
+}
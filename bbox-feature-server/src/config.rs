@@ -1,4 +1,5 @@
 use bbox_core::config::{from_config_root_or_exit, ConfigError, DsPostgisCfg, NamedDatasourceCfg};
+use bbox_core::ogcapi::ApiLink;
 use bbox_core::service::ServiceConfig;
 use clap::ArgMatches;
 use serde::Deserialize;
@@ -12,6 +13,37 @@ pub struct FeatureServiceCfg {
     pub auto_collections: CollectionsCfg,
     #[serde(rename = "collection")]
     pub collections: Vec<ConfiguredCollectionCfg>,
+    pub resilience: ResilienceCfg,
+    /// Maximal duration of collection queries in seconds (PostGIS collections)
+    pub query_timeout: Option<u64>,
+    /// Show items on a map in HTML pages (Default: true)
+    pub html_map: Option<bool>,
+    /// Generate collection downloads on demand
+    pub downloads: Option<DownloadsCfg>,
+    /// Recompute extents and queryables of configured collections periodically (seconds)
+    pub metadata_refresh: Option<u64>,
+}
+
+/// On-demand generation of GeoPackage downloads
+#[derive(Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct DownloadsCfg {
+    /// Directory of generated files
+    pub dir: String,
+    /// Maximal number of queued or running download jobs (default: 2)
+    pub max_jobs: Option<usize>,
+    /// Generated files are removed after this number of seconds (default: 86400)
+    pub max_age: Option<u64>,
+}
+
+/// Behaviour with unavailable datasources
+#[derive(Deserialize, Default, Clone, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct ResilienceCfg {
+    /// Keep running when datasources are unavailable at startup and reconnect in the background
+    pub enabled: bool,
+    /// List unavailable collections in `/collections`
+    pub list_unavailable: bool,
 }
 
 /// Collections with auto-detection
@@ -20,33 +52,89 @@ pub struct FeatureServiceCfg {
 pub struct CollectionsCfg {
     pub directory: Vec<DsFiledirCfg>,
     pub postgis: Vec<DsPostgisCfg>,
+    pub s3: Vec<DsS3Cfg>,
 }
 
 #[derive(Deserialize, Debug)]
 #[serde(deny_unknown_fields)]
 pub struct DsFiledirCfg {
     pub dir: String,
+    /// Register and deregister collections when files are added or removed
+    #[serde(default)]
+    pub watch: bool,
+    /// Wait time in seconds after the last detected change (Default: 2)
+    pub debounce: Option<u64>,
 }
 
-#[derive(Deserialize, Debug)]
+/// GeoPackages in an S3 bucket
+#[derive(Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct DsS3Cfg {
+    /// S3 URL with optional prefix (`s3://bucket/prefix`)
+    pub url: String,
+    /// Local directory for downloaded GeoPackages
+    pub cache_dir: String,
+    /// Refresh interval in seconds (Default: no refresh)
+    pub refresh: Option<u64>,
+}
+
+/// Datasources and collections derived from QGIS projects of the map service
+#[derive(Default, Clone, Debug)]
+pub struct DerivedCollectionsCfg {
+    pub datasources: Vec<NamedDatasourceCfg>,
+    pub collections: Vec<ConfiguredCollectionCfg>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
 #[serde(deny_unknown_fields)]
 pub struct ConfiguredCollectionCfg {
     pub name: String,
     pub title: Option<String>,
     pub description: Option<String>,
+    /// Acknowledgment of ownership, authorship or copyright
+    pub attribution: Option<String>,
+    /// License of collection data, e.g. SPDX identifier or URL
+    pub license: Option<String>,
+    /// Pre-generated bulk downloads, advertised as `enclosure` links
+    #[serde(default, rename = "download")]
+    pub downloads: Vec<DownloadCfg>,
     // extent: Option<CoreExtent>
     #[serde(flatten)]
     pub source: CollectionSourceCfg,
 }
 
+/// Bulk download of collection data, e.g. a GeoPackage or GeoParquet file on S3
+#[derive(Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct DownloadCfg {
+    pub href: String,
+    /// Media type (Default: derived from file extension)
+    #[serde(rename = "type")]
+    pub type_: Option<String>,
+    pub title: Option<String>,
+    /// File size in bytes
+    pub length: Option<u64>,
+}
+
 /// Collections with configuration
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Clone, Debug)]
 #[serde(deny_unknown_fields)]
 pub enum CollectionSourceCfg {
     #[serde(rename = "postgis")]
     Postgis(PostgisCollectionCfg),
     #[serde(rename = "gpkg")]
     Gpkg(GpkgCollectionCfg),
+    #[serde(rename = "geoparquet")]
+    Geoparquet(GeoparquetCollectionCfg),
+    #[serde(rename = "flatgeobuf")]
+    Flatgeobuf(FlatgeobufCollectionCfg),
+    #[serde(rename = "elasticsearch")]
+    Elasticsearch(ElasticsearchCollectionCfg),
+    #[serde(rename = "mysql")]
+    Mysql(MysqlCollectionCfg),
+    /// Collection of a registered datasource driver
+    #[serde(rename = "custom")]
+    Custom(CustomCollectionCfg),
 }
 
 #[derive(Deserialize, Default, Clone, Debug)]
@@ -67,12 +155,67 @@ pub struct PostgisCollectionCfg {
     pub temporal_field: Option<String>,
     /// Field used for temporal end filter expressions
     pub temporal_end_field: Option<String>,
+    /// Timestamp field with the time of the last change, used for `/collections/{id}/changes`
+    pub updated_field: Option<String>,
+    /// Fields which can be used in filter expressions
+    #[serde(default)]
+    pub queryable_fields: Vec<String>,
+    /// Allow creating, updating and deleting features (requires `table_name` and `fid_field` or primary key)
+    #[serde(default)]
+    pub writable: bool,
+    /// Maximal duration of item queries in seconds (Default: global `query_timeout`)
+    pub query_timeout: Option<u64>,
+    /// Page through items with a `cursor` on the primary key instead of `offset`
+    #[serde(default)]
+    pub cursor_pagination: bool,
+    /// Maximal number of decimal digits of GeoJSON coordinates
+    pub geojson_precision: Option<u8>,
+    /// Simplify geometries with a tolerance of this fraction of the requested `bbox` size
+    pub simplify: Option<f64>,
+    /// Maximal deviation of linearized curve geometries in storage CRS units (Default: 32 segments per quarter circle)
+    pub curve_tolerance: Option<f64>,
+    /// Coordinate dimension of geometries. 3D collections have bboxes with Z values. (Default: 2)
+    pub dimension: Option<u8>,
+    /// Fields with value ranges or distinct values in the STAC `summaries` of the collection
+    #[serde(default)]
+    pub summary_fields: Vec<String>,
+    /// Queryable fields with `enum` values (text fields) or `minimum`/`maximum` (numeric fields) in the queryables
+    #[serde(default)]
+    pub queryable_value_fields: Vec<String>,
+    /// Fields searched with the `q` parameter
+    #[serde(default)]
+    pub search_fields: Vec<String>,
+    /// PostgreSQL text search configuration of full-text searches, e.g. `english` (Default: `simple`)
+    pub search_config: Option<String>,
+    /// Match `q` as substring with ILIKE (supported by trigram indexes) instead of full-text search
+    #[serde(default)]
+    pub search_ilike: bool,
+    /// Interval in seconds for recomputing the temporal extent from `temporal_field` (Default: computed at startup only)
+    pub temporal_extent_refresh: Option<u64>,
+}
+
+#[derive(Deserialize, Default, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct MysqlCollectionCfg {
+    /// Name of datasource.mysql config (Default: first with matching type)
+    pub datasource: Option<String>,
+    /// Database name (Default: database of connection URL)
+    pub table_schema: Option<String>,
+    pub table_name: Option<String>,
+    /// Custom SQL query
+    pub sql: Option<String>,
+    pub fid_field: Option<String>,
+    pub geometry_field: Option<String>,
+    /// Field used for temporal filter expressions
+    pub temporal_field: Option<String>,
+    /// Field used for temporal end filter expressions
+    pub temporal_end_field: Option<String>,
     /// Fields which can be used in filter expressions
     #[serde(default)]
     pub queryable_fields: Vec<String>,
 }
 
-#[derive(Deserialize, Default, Debug)]
+#[derive(Deserialize, Default, Clone, Debug)]
 #[serde(deny_unknown_fields)]
 pub struct GpkgCollectionCfg {
     /// Name of datasource.gpkg config (Default: first with matching type)
@@ -82,7 +225,88 @@ pub struct GpkgCollectionCfg {
     pub sql: Option<String>,
     pub fid_field: Option<String>,
     pub geometry_field: Option<String>,
+    /// Geometry encoding (Default: detected from GeoPackage or SpatiaLite metadata, otherwise WKB)
+    pub geometry_format: Option<GeometryFormat>,
     //pub field_list: Option<Vec<String>>,
+    /// Field used for temporal filter expressions
+    pub temporal_field: Option<String>,
+    /// Field used for temporal end filter expressions
+    pub temporal_end_field: Option<String>,
+    /// Fields which can be used in filter expressions
+    #[serde(default)]
+    pub queryable_fields: Vec<String>,
+}
+
+/// Geometry encoding of SQLite columns
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum GeometryFormat {
+    /// GeoPackage binary
+    #[serde(rename = "gpkg")]
+    Gpkg,
+    /// SpatiaLite BLOB-Geometry
+    #[serde(rename = "spatialite")]
+    Spatialite,
+    #[serde(rename = "wkb")]
+    Wkb,
+    #[serde(rename = "wkt")]
+    Wkt,
+}
+
+#[derive(Deserialize, Default, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct GeoparquetCollectionCfg {
+    /// File path or S3 URL (`s3://bucket/key`)
+    pub path: String,
+    pub fid_field: Option<String>,
+    /// Geometry column (Default: primary column from GeoParquet metadata)
+    pub geometry_field: Option<String>,
+    /// Fields which can be used in filter expressions
+    #[serde(default)]
+    pub queryable_fields: Vec<String>,
+}
+
+#[derive(Deserialize, Default, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct FlatgeobufCollectionCfg {
+    /// File path or URL
+    pub path: String,
+    pub fid_field: Option<String>,
+    /// Fields which can be used in filter expressions
+    #[serde(default)]
+    pub queryable_fields: Vec<String>,
+}
+
+#[derive(Deserialize, Default, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct ElasticsearchCollectionCfg {
+    /// Elasticsearch or OpenSearch URL
+    pub url: String,
+    /// Index, alias or index pattern
+    pub index: String,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// `geo_point` or `geo_shape` field (Default: first geo field in index mapping)
+    pub geometry_field: Option<String>,
+    /// Field used as feature id (Default: document `_id`)
+    pub fid_field: Option<String>,
+    /// Field used for temporal filter expressions
+    pub temporal_field: Option<String>,
+    /// Field used for temporal end filter expressions
+    pub temporal_end_field: Option<String>,
+    /// Fields which can be used in filter expressions
+    #[serde(default)]
+    pub queryable_fields: Vec<String>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct CustomCollectionCfg {
+    /// Name of datasource.custom config (Default: first custom datasource)
+    pub datasource: Option<String>,
+    /// Registered driver creating a datasource from the collection parameters, if no `datasource` is set
+    pub driver: Option<String>,
+    /// Driver specific parameters
+    #[serde(flatten)]
+    pub params: serde_json::Map<String, serde_json::Value>,
 }
 
 impl ServiceConfig for FeatureServiceCfg {
@@ -92,13 +316,118 @@ impl ServiceConfig for FeatureServiceCfg {
     }
 }
 
+impl ConfiguredCollectionCfg {
+    pub fn new(name: &str, source: CollectionSourceCfg) -> Self {
+        ConfiguredCollectionCfg {
+            name: name.to_string(),
+            title: None,
+            description: None,
+            attribution: None,
+            license: None,
+            downloads: Vec::new(),
+            source,
+        }
+    }
+    /// Collection configuration with global query timeout, if not set for the collection
+    pub fn with_query_timeout(&self, timeout: Option<u64>) -> Self {
+        let mut cfg = self.clone();
+        if let CollectionSourceCfg::Postgis(ref mut pg_cfg) = cfg.source {
+            pg_cfg.query_timeout = pg_cfg.query_timeout.or(timeout);
+        }
+        cfg
+    }
+    /// `enclosure` links of configured downloads
+    pub fn download_links(&self) -> Vec<ApiLink> {
+        self.downloads
+            .iter()
+            .map(|download| ApiLink {
+                href: download.href.clone(),
+                rel: Some("enclosure".to_string()),
+                type_: download
+                    .type_
+                    .clone()
+                    .or_else(|| download_media_type(&download.href).map(str::to_string)),
+                title: download.title.clone(),
+                hreflang: None,
+                length: download.length,
+            })
+            .collect()
+    }
+}
+
+/// Media type of common bulk download formats
+fn download_media_type(href: &str) -> Option<&'static str> {
+    let path = href.split(['?', '#']).next().unwrap_or_default();
+    let ext = path.rsplit_once('.')?.1.to_lowercase();
+    match ext.as_str() {
+        "gpkg" => Some("application/geopackage+sqlite3"),
+        "parquet" => Some("application/vnd.apache.parquet"),
+        "fgb" => Some("application/flatgeobuf"),
+        "geojson" => Some("application/geo+json"),
+        "zip" => Some("application/zip"),
+        _ => None,
+    }
+}
+
+impl CollectionSourceCfg {
+    /// Configured temporal field and temporal end field
+    pub fn temporal_properties(&self) -> Option<(&str, Option<&str>)> {
+        let (start, end) = match self {
+            CollectionSourceCfg::Postgis(cfg) => (&cfg.temporal_field, &cfg.temporal_end_field),
+            CollectionSourceCfg::Gpkg(cfg) => (&cfg.temporal_field, &cfg.temporal_end_field),
+            CollectionSourceCfg::Mysql(cfg) => (&cfg.temporal_field, &cfg.temporal_end_field),
+            CollectionSourceCfg::Elasticsearch(cfg) => {
+                (&cfg.temporal_field, &cfg.temporal_end_field)
+            }
+            CollectionSourceCfg::Geoparquet(_)
+            | CollectionSourceCfg::Flatgeobuf(_)
+            | CollectionSourceCfg::Custom(_) => return None,
+        };
+        start.as_deref().map(|start| (start, end.as_deref()))
+    }
+}
+
 impl CollectionsCfg {
     #[allow(dead_code)]
     pub fn from_path(path: &str) -> Self {
         let mut cfg = CollectionsCfg::default();
         cfg.directory.push(DsFiledirCfg {
             dir: path.to_string(),
+            watch: false,
+            debounce: None,
         });
         cfg
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn enclosure_links() {
+        let cfg: ConfiguredCollectionCfg = serde_json::from_value(serde_json::json!({
+            "name": "lakes",
+            "gpkg": { "table_name": "ne_10m_lakes" },
+            "download": [
+                { "href": "https://example.com/lakes.gpkg", "length": 1024 },
+                { "href": "https://example.com/lakes.parquet?v=2", "title": "GeoParquet" },
+                { "href": "https://example.com/lakes", "type": "application/x-custom" }
+            ]
+        }))
+        .unwrap();
+        let links = cfg.download_links();
+        assert_eq!(links.len(), 3);
+        assert_eq!(links[0].rel.as_deref(), Some("enclosure"));
+        assert_eq!(
+            links[0].type_.as_deref(),
+            Some("application/geopackage+sqlite3")
+        );
+        assert_eq!(links[0].length, Some(1024));
+        assert_eq!(
+            links[1].type_.as_deref(),
+            Some("application/vnd.apache.parquet")
+        );
+        assert_eq!(links[2].type_.as_deref(), Some("application/x-custom"));
+    }
+}
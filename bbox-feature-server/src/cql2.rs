@@ -0,0 +1,969 @@
+//! CQL2 text filter expressions (OGC API Features Part 3).
+//!
+//! Supports comparisons, `AND`/`OR`/`NOT`, `LIKE`, `BETWEEN`, `IN`, `IS NULL`
+//! and the spatial functions `S_INTERSECTS`, `S_EQUALS`, `S_DISJOINT`, `S_TOUCHES`,
+//! `S_WITHIN`, `S_OVERLAPS`, `S_CROSSES` and `S_CONTAINS` with WKT or `BBOX` literals.
+
+use chrono::{DateTime, FixedOffset, NaiveDate};
+use sqlx::{Postgres, QueryBuilder};
+use thiserror::Error;
+
+#[derive(Error, Debug, PartialEq)]
+pub enum Cql2Error {
+    #[error("unexpected end of filter expression")]
+    UnexpectedEnd,
+    #[error("unexpected token `{0}`")]
+    UnexpectedToken(String),
+    #[error("invalid character `{0}`")]
+    InvalidChar(char),
+    #[error("unterminated literal")]
+    Unterminated,
+    #[error("invalid number `{0}`")]
+    InvalidNumber(String),
+    #[error("invalid temporal literal `{0}`")]
+    InvalidTemporal(String),
+    #[error("invalid bbox literal")]
+    InvalidBbox,
+    #[error("property `{0}` is not queryable")]
+    UnknownProperty(String),
+    #[error("unsupported operand `{0}`")]
+    UnsupportedOperand(String),
+    #[error("filter expression nested deeper than {MAX_DEPTH} levels")]
+    TooDeeplyNested,
+    #[error("filter expression with more than {MAX_TERMS} predicates")]
+    TooManyTerms,
+}
+
+/// Maximal nesting of parentheses and `NOT` operators
+const MAX_DEPTH: usize = 64;
+/// Maximal number of predicates. Long value lists can be expressed with `IN`.
+const MAX_TERMS: usize = 256;
+
+pub type Result<T> = std::result::Result<T, Cql2Error>;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Expr {
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Comparison(Operand, CmpOp, Operand),
+    Like {
+        value: Operand,
+        pattern: Operand,
+        negated: bool,
+    },
+    Between {
+        value: Operand,
+        low: Operand,
+        high: Operand,
+        negated: bool,
+    },
+    In {
+        value: Operand,
+        list: Vec<Operand>,
+        negated: bool,
+    },
+    IsNull {
+        value: Operand,
+        negated: bool,
+    },
+    Spatial(SpatialOp, Operand, Operand),
+    Literal(bool),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Operand {
+    Property(String),
+    String(String),
+    Number(f64),
+    Bool(bool),
+    Timestamp(DateTime<FixedOffset>),
+    Date(NaiveDate),
+    /// WKT geometry literal
+    Geometry(String),
+    /// minx, miny, maxx, maxy
+    Bbox([f64; 4]),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CmpOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SpatialOp {
+    Intersects,
+    Equals,
+    Disjoint,
+    Touches,
+    Within,
+    Overlaps,
+    Crosses,
+    Contains,
+}
+
+impl CmpOp {
+    fn from_op(op: &str) -> Option<Self> {
+        match op {
+            "=" => Some(CmpOp::Eq),
+            "<>" => Some(CmpOp::Ne),
+            "<" => Some(CmpOp::Lt),
+            "<=" => Some(CmpOp::Le),
+            ">" => Some(CmpOp::Gt),
+            ">=" => Some(CmpOp::Ge),
+            _ => None,
+        }
+    }
+    fn sql(&self) -> &'static str {
+        match self {
+            CmpOp::Eq => "=",
+            CmpOp::Ne => "<>",
+            CmpOp::Lt => "<",
+            CmpOp::Le => "<=",
+            CmpOp::Gt => ">",
+            CmpOp::Ge => ">=",
+        }
+    }
+}
+
+impl SpatialOp {
+    fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_uppercase().as_str() {
+            "S_INTERSECTS" => Some(SpatialOp::Intersects),
+            "S_EQUALS" => Some(SpatialOp::Equals),
+            "S_DISJOINT" => Some(SpatialOp::Disjoint),
+            "S_TOUCHES" => Some(SpatialOp::Touches),
+            "S_WITHIN" => Some(SpatialOp::Within),
+            "S_OVERLAPS" => Some(SpatialOp::Overlaps),
+            "S_CROSSES" => Some(SpatialOp::Crosses),
+            "S_CONTAINS" => Some(SpatialOp::Contains),
+            _ => None,
+        }
+    }
+    fn sql(&self) -> &'static str {
+        match self {
+            SpatialOp::Intersects => "ST_Intersects",
+            SpatialOp::Equals => "ST_Equals",
+            SpatialOp::Disjoint => "ST_Disjoint",
+            SpatialOp::Touches => "ST_Touches",
+            SpatialOp::Within => "ST_Within",
+            SpatialOp::Overlaps => "ST_Overlaps",
+            SpatialOp::Crosses => "ST_Crosses",
+            SpatialOp::Contains => "ST_Contains",
+        }
+    }
+}
+
+/// Parse a CQL2 text expression.
+pub fn parse_text(input: &str) -> Result<Expr> {
+    let mut parser = Parser {
+        tokens: tokenize(input)?,
+        pos: 0,
+        depth: 0,
+        terms: 0,
+    };
+    let expr = parser.parse_or()?;
+    if let Some(token) = parser.peek() {
+        return Err(Cql2Error::UnexpectedToken(format!("{token:?}")));
+    }
+    Ok(expr)
+}
+
+// --- Tokenizer ---
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Ident(String),
+    QuotedIdent(String),
+    Str(String),
+    Num(f64),
+    Wkt(String),
+    Op(&'static str),
+    LParen,
+    RParen,
+    Comma,
+}
+
+const WKT_TYPES: [&str; 7] = [
+    "POINT",
+    "LINESTRING",
+    "POLYGON",
+    "MULTIPOINT",
+    "MULTILINESTRING",
+    "MULTIPOLYGON",
+    "GEOMETRYCOLLECTION",
+];
+
+const RESERVED: [&str; 8] = ["AND", "OR", "NOT", "LIKE", "BETWEEN", "IN", "IS", "NULL"];
+
+fn tokenize(input: &str) -> Result<Vec<Token>> {
+    let chars: Vec<char> = input.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let next = chars.get(i + 1).copied();
+        match c {
+            c if c.is_whitespace() => i += 1,
+            '(' => {
+                tokens.push(Token::LParen);
+                i += 1;
+            }
+            ')' => {
+                tokens.push(Token::RParen);
+                i += 1;
+            }
+            ',' => {
+                tokens.push(Token::Comma);
+                i += 1;
+            }
+            '=' => {
+                tokens.push(Token::Op("="));
+                i += 1;
+            }
+            '<' => match next {
+                Some('=') => {
+                    tokens.push(Token::Op("<="));
+                    i += 2;
+                }
+                Some('>') => {
+                    tokens.push(Token::Op("<>"));
+                    i += 2;
+                }
+                _ => {
+                    tokens.push(Token::Op("<"));
+                    i += 1;
+                }
+            },
+            '>' => {
+                if next == Some('=') {
+                    tokens.push(Token::Op(">="));
+                    i += 2;
+                } else {
+                    tokens.push(Token::Op(">"));
+                    i += 1;
+                }
+            }
+            '\'' => {
+                let mut s = String::new();
+                let mut j = i + 1;
+                loop {
+                    match chars.get(j) {
+                        None => return Err(Cql2Error::Unterminated),
+                        Some('\'') if chars.get(j + 1) == Some(&'\'') => {
+                            s.push('\'');
+                            j += 2;
+                        }
+                        Some('\'') => break,
+                        Some(ch) => {
+                            s.push(*ch);
+                            j += 1;
+                        }
+                    }
+                }
+                tokens.push(Token::Str(s));
+                i = j + 1;
+            }
+            '"' => {
+                let Some(len) = chars[i + 1..].iter().position(|ch| *ch == '"') else {
+                    return Err(Cql2Error::Unterminated);
+                };
+                tokens.push(Token::QuotedIdent(
+                    chars[i + 1..i + 1 + len].iter().collect(),
+                ));
+                i += len + 2;
+            }
+            c if c.is_ascii_digit()
+                || ((c == '-' || c == '.') && next.map_or(false, |n| n.is_ascii_digit())) =>
+            {
+                let mut j = i + 1;
+                while j < chars.len() {
+                    let ch = chars[j];
+                    let exp_sign = (ch == '-' || ch == '+') && matches!(chars[j - 1], 'e' | 'E');
+                    if ch.is_ascii_digit() || ch == '.' || ch == 'e' || ch == 'E' || exp_sign {
+                        j += 1;
+                    } else {
+                        break;
+                    }
+                }
+                let numstr: String = chars[i..j].iter().collect();
+                let num = numstr
+                    .parse::<f64>()
+                    .map_err(|_| Cql2Error::InvalidNumber(numstr.clone()))?;
+                tokens.push(Token::Num(num));
+                i = j;
+            }
+            c if c.is_alphabetic() || c == '_' => {
+                let mut j = i + 1;
+                while j < chars.len()
+                    && (chars[j].is_alphanumeric() || chars[j] == '_' || chars[j] == ':')
+                {
+                    j += 1;
+                }
+                let word: String = chars[i..j].iter().collect();
+                let mut k = j;
+                while k < chars.len() && chars[k].is_whitespace() {
+                    k += 1;
+                }
+                if WKT_TYPES.contains(&word.to_ascii_uppercase().as_str())
+                    && chars.get(k) == Some(&'(')
+                {
+                    // Keep the geometry literal as WKT text
+                    let mut depth = 0;
+                    let mut end = None;
+                    for (pos, ch) in chars.iter().enumerate().skip(k) {
+                        match ch {
+                            '(' => depth += 1,
+                            ')' => {
+                                depth -= 1;
+                                if depth == 0 {
+                                    end = Some(pos);
+                                    break;
+                                }
+                            }
+                            _ => {}
+                        }
+                    }
+                    let Some(end) = end else {
+                        return Err(Cql2Error::Unterminated);
+                    };
+                    tokens.push(Token::Wkt(chars[i..=end].iter().collect()));
+                    i = end + 1;
+                } else {
+                    tokens.push(Token::Ident(word));
+                    i = j;
+                }
+            }
+            c => return Err(Cql2Error::InvalidChar(c)),
+        }
+    }
+    Ok(tokens)
+}
+
+// --- Parser ---
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+    /// Current nesting level
+    depth: usize,
+    /// Number of parsed predicates
+    terms: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+    fn next(&mut self) -> Result<Token> {
+        let token = self
+            .tokens
+            .get(self.pos)
+            .cloned()
+            .ok_or(Cql2Error::UnexpectedEnd)?;
+        self.pos += 1;
+        Ok(token)
+    }
+    fn peek_keyword(&self, keyword: &str) -> bool {
+        matches!(self.peek(), Some(Token::Ident(word)) if word.eq_ignore_ascii_case(keyword))
+    }
+    fn eat_keyword(&mut self, keyword: &str) -> bool {
+        if self.peek_keyword(keyword) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+    fn expect(&mut self, expected: Token) -> Result<()> {
+        let token = self.next()?;
+        if token == expected {
+            Ok(())
+        } else {
+            Err(Cql2Error::UnexpectedToken(format!("{token:?}")))
+        }
+    }
+    fn expect_keyword(&mut self, keyword: &str) -> Result<()> {
+        if self.eat_keyword(keyword) {
+            Ok(())
+        } else {
+            match self.peek() {
+                Some(token) => Err(Cql2Error::UnexpectedToken(format!("{token:?}"))),
+                None => Err(Cql2Error::UnexpectedEnd),
+            }
+        }
+    }
+
+    /// Parse a nested expression, limiting the recursion depth
+    fn parse_nested(&mut self, parse: fn(&mut Self) -> Result<Expr>) -> Result<Expr> {
+        if self.depth >= MAX_DEPTH {
+            return Err(Cql2Error::TooDeeplyNested);
+        }
+        self.depth += 1;
+        let expr = parse(self)?;
+        self.depth -= 1;
+        Ok(expr)
+    }
+
+    fn parse_or(&mut self) -> Result<Expr> {
+        let mut expr = self.parse_and()?;
+        while self.eat_keyword("OR") {
+            let rhs = self.parse_and()?;
+            expr = Expr::Or(Box::new(expr), Box::new(rhs));
+        }
+        Ok(expr)
+    }
+    fn parse_and(&mut self) -> Result<Expr> {
+        let mut expr = self.parse_not()?;
+        while self.eat_keyword("AND") {
+            let rhs = self.parse_not()?;
+            expr = Expr::And(Box::new(expr), Box::new(rhs));
+        }
+        Ok(expr)
+    }
+    fn parse_not(&mut self) -> Result<Expr> {
+        if self.eat_keyword("NOT") {
+            Ok(Expr::Not(Box::new(self.parse_nested(Self::parse_not)?)))
+        } else {
+            self.parse_primary()
+        }
+    }
+    fn parse_primary(&mut self) -> Result<Expr> {
+        if let Some(Token::LParen) = self.peek() {
+            self.pos += 1;
+            let expr = self.parse_nested(Self::parse_or)?;
+            self.expect(Token::RParen)?;
+            return Ok(expr);
+        }
+        self.terms += 1;
+        if self.terms > MAX_TERMS {
+            return Err(Cql2Error::TooManyTerms);
+        }
+        if let Some(Token::Ident(name)) = self.peek() {
+            if let Some(op) = SpatialOp::from_name(name) {
+                self.pos += 1;
+                self.expect(Token::LParen)?;
+                let lhs = self.parse_geom_operand()?;
+                self.expect(Token::Comma)?;
+                let rhs = self.parse_geom_operand()?;
+                self.expect(Token::RParen)?;
+                return Ok(Expr::Spatial(op, lhs, rhs));
+            }
+        }
+        let value = self.parse_scalar()?;
+        self.parse_predicate(value)
+    }
+    fn parse_predicate(&mut self, value: Operand) -> Result<Expr> {
+        if let Some(Token::Op(op)) = self.peek() {
+            let op = CmpOp::from_op(op).expect("tokenizer operator");
+            self.pos += 1;
+            let rhs = self.parse_scalar()?;
+            return Ok(Expr::Comparison(value, op, rhs));
+        }
+        if self.eat_keyword("IS") {
+            let negated = self.eat_keyword("NOT");
+            self.expect_keyword("NULL")?;
+            return Ok(Expr::IsNull { value, negated });
+        }
+        let negated = self.eat_keyword("NOT");
+        if self.eat_keyword("LIKE") {
+            let pattern = self.parse_scalar()?;
+            Ok(Expr::Like {
+                value,
+                pattern,
+                negated,
+            })
+        } else if self.eat_keyword("BETWEEN") {
+            let low = self.parse_scalar()?;
+            self.expect_keyword("AND")?;
+            let high = self.parse_scalar()?;
+            Ok(Expr::Between {
+                value,
+                low,
+                high,
+                negated,
+            })
+        } else if self.eat_keyword("IN") {
+            self.expect(Token::LParen)?;
+            let mut list = vec![self.parse_scalar()?];
+            while self.peek() == Some(&Token::Comma) {
+                self.pos += 1;
+                list.push(self.parse_scalar()?);
+            }
+            self.expect(Token::RParen)?;
+            Ok(Expr::In {
+                value,
+                list,
+                negated,
+            })
+        } else if let (Operand::Bool(b), false) = (&value, negated) {
+            Ok(Expr::Literal(*b))
+        } else {
+            match self.peek() {
+                Some(token) => Err(Cql2Error::UnexpectedToken(format!("{token:?}"))),
+                None => Err(Cql2Error::UnexpectedEnd),
+            }
+        }
+    }
+    fn parse_scalar(&mut self) -> Result<Operand> {
+        match self.next()? {
+            Token::Str(s) => Ok(Operand::String(s)),
+            Token::Num(n) => Ok(Operand::Number(n)),
+            Token::QuotedIdent(name) => Ok(Operand::Property(name)),
+            Token::Ident(word) => match word.to_ascii_uppercase().as_str() {
+                "TRUE" => Ok(Operand::Bool(true)),
+                "FALSE" => Ok(Operand::Bool(false)),
+                "TIMESTAMP" => {
+                    let s = self.parse_temporal_arg()?;
+                    DateTime::parse_from_rfc3339(&s)
+                        .map(Operand::Timestamp)
+                        .map_err(|_| Cql2Error::InvalidTemporal(s))
+                }
+                "DATE" => {
+                    let s = self.parse_temporal_arg()?;
+                    NaiveDate::parse_from_str(&s, "%Y-%m-%d")
+                        .map(Operand::Date)
+                        .map_err(|_| Cql2Error::InvalidTemporal(s))
+                }
+                kw if RESERVED.contains(&kw) => Err(Cql2Error::UnexpectedToken(word)),
+                _ => Ok(Operand::Property(word)),
+            },
+            token => Err(Cql2Error::UnexpectedToken(format!("{token:?}"))),
+        }
+    }
+    fn parse_temporal_arg(&mut self) -> Result<String> {
+        self.expect(Token::LParen)?;
+        let Token::Str(s) = self.next()? else {
+            return Err(Cql2Error::InvalidTemporal(String::new()));
+        };
+        self.expect(Token::RParen)?;
+        Ok(s)
+    }
+    fn parse_geom_operand(&mut self) -> Result<Operand> {
+        if let Some(Token::Wkt(wkt)) = self.peek() {
+            let wkt = wkt.clone();
+            self.pos += 1;
+            return Ok(Operand::Geometry(wkt));
+        }
+        if self.peek_keyword("BBOX") || self.peek_keyword("ENVELOPE") {
+            self.pos += 1;
+            self.expect(Token::LParen)?;
+            let mut coords = Vec::new();
+            loop {
+                match self.next()? {
+                    Token::Num(n) => coords.push(n),
+                    _ => return Err(Cql2Error::InvalidBbox),
+                }
+                match self.next()? {
+                    Token::Comma => {}
+                    Token::RParen => break,
+                    _ => return Err(Cql2Error::InvalidBbox),
+                }
+            }
+            return match coords[..] {
+                [x1, y1, x2, y2] | [x1, y1, _, x2, y2, _] => Ok(Operand::Bbox([x1, y1, x2, y2])),
+                _ => Err(Cql2Error::InvalidBbox),
+            };
+        }
+        self.parse_scalar()
+    }
+}
+
+// --- SQL generation ---
+
+impl Expr {
+    /// Append expression as parameterized SQL.
+    ///
+    /// `column` returns the quoted column name of a queryable property, or `None` if the property is not queryable.
+    pub fn push_sql<F>(&self, builder: &mut QueryBuilder<'_, Postgres>, column: &F) -> Result<()>
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        match self {
+            Expr::And(lhs, rhs) | Expr::Or(lhs, rhs) => {
+                builder.push("(");
+                lhs.push_sql(builder, column)?;
+                builder.push(if matches!(self, Expr::And(..)) {
+                    " AND "
+                } else {
+                    " OR "
+                });
+                rhs.push_sql(builder, column)?;
+                builder.push(")");
+            }
+            Expr::Not(expr) => {
+                builder.push("(NOT ");
+                expr.push_sql(builder, column)?;
+                builder.push(")");
+            }
+            Expr::Comparison(lhs, op, rhs) => {
+                builder.push("(");
+                lhs.push_scalar(builder, column)?;
+                builder.push(format!(" {} ", op.sql()));
+                rhs.push_scalar(builder, column)?;
+                builder.push(")");
+            }
+            Expr::Like {
+                value,
+                pattern,
+                negated,
+            } => {
+                builder.push("(");
+                value.push_scalar(builder, column)?;
+                builder.push(if *negated {
+                    "::text NOT LIKE "
+                } else {
+                    "::text LIKE "
+                });
+                pattern.push_scalar(builder, column)?;
+                builder.push(")");
+            }
+            Expr::Between {
+                value,
+                low,
+                high,
+                negated,
+            } => {
+                builder.push("(");
+                value.push_scalar(builder, column)?;
+                builder.push(if *negated {
+                    " NOT BETWEEN "
+                } else {
+                    " BETWEEN "
+                });
+                low.push_scalar(builder, column)?;
+                builder.push(" AND ");
+                high.push_scalar(builder, column)?;
+                builder.push(")");
+            }
+            Expr::In {
+                value,
+                list,
+                negated,
+            } => {
+                builder.push("(");
+                value.push_scalar(builder, column)?;
+                builder.push(if *negated { " NOT IN (" } else { " IN (" });
+                for (i, item) in list.iter().enumerate() {
+                    if i > 0 {
+                        builder.push(", ");
+                    }
+                    item.push_scalar(builder, column)?;
+                }
+                builder.push("))");
+            }
+            Expr::IsNull { value, negated } => {
+                builder.push("(");
+                value.push_scalar(builder, column)?;
+                builder.push(if *negated {
+                    " IS NOT NULL)"
+                } else {
+                    " IS NULL)"
+                });
+            }
+            Expr::Spatial(op, lhs, rhs) => {
+                builder.push(format!("{}(", op.sql()));
+                lhs.push_geometry(builder, column, rhs)?;
+                builder.push(", ");
+                rhs.push_geometry(builder, column, lhs)?;
+                builder.push(")");
+            }
+            Expr::Literal(b) => {
+                builder.push(if *b { "TRUE" } else { "FALSE" });
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Operand {
+    fn push_scalar<F>(&self, builder: &mut QueryBuilder<'_, Postgres>, column: &F) -> Result<()>
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        match self {
+            Operand::Property(name) => {
+                let col = column(name).ok_or(Cql2Error::UnknownProperty(name.clone()))?;
+                builder.push(col);
+            }
+            Operand::String(s) => {
+                builder.push_bind(s.clone());
+            }
+            Operand::Number(n) => {
+                builder.push_bind(*n);
+            }
+            Operand::Bool(b) => {
+                builder.push_bind(*b);
+            }
+            Operand::Timestamp(dt) => {
+                builder.push_bind(*dt);
+            }
+            Operand::Date(d) => {
+                builder.push_bind(*d);
+            }
+            Operand::Geometry(_) | Operand::Bbox(_) => {
+                return Err(Cql2Error::UnsupportedOperand(format!("{self:?}")));
+            }
+        }
+        Ok(())
+    }
+    /// Append geometry operand. Literals are assigned the SRID of the geometry property they are compared with.
+    fn push_geometry<F>(
+        &self,
+        builder: &mut QueryBuilder<'_, Postgres>,
+        column: &F,
+        other: &Operand,
+    ) -> Result<()>
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        let srid_col = match other {
+            Operand::Property(name) => {
+                Some(column(name).ok_or(Cql2Error::UnknownProperty(name.clone()))?)
+            }
+            _ => None,
+        };
+        match self {
+            Operand::Property(name) => {
+                let col = column(name).ok_or(Cql2Error::UnknownProperty(name.clone()))?;
+                builder.push(col);
+            }
+            Operand::Geometry(wkt) => {
+                if let Some(col) = &srid_col {
+                    builder.push("ST_SetSRID(ST_GeomFromText(");
+                    builder.push_bind(wkt.clone());
+                    builder.push(format!("), ST_SRID({col}))"));
+                } else {
+                    builder.push("ST_GeomFromText(");
+                    builder.push_bind(wkt.clone());
+                    builder.push(")");
+                }
+            }
+            Operand::Bbox(bbox) => {
+                builder.push("ST_MakeEnvelope(");
+                let mut separated = builder.separated(", ");
+                for v in bbox {
+                    separated.push_bind(*v);
+                }
+                if let Some(col) = &srid_col {
+                    separated.push(format!("ST_SRID({col})"));
+                }
+                builder.push(")");
+            }
+            _ => return Err(Cql2Error::UnsupportedOperand(format!("{self:?}"))),
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn prop(name: &str) -> Operand {
+        Operand::Property(name.to_string())
+    }
+
+    fn to_sql(filter: &str) -> Result<String> {
+        let expr = parse_text(filter)?;
+        let mut builder: QueryBuilder<Postgres> = QueryBuilder::new("");
+        let column = |name: &str| {
+            ["name", "pop", "geom", "ts"]
+                .contains(&name)
+                .then(|| format!(r#""{name}""#))
+        };
+        expr.push_sql(&mut builder, &column)?;
+        Ok(builder.sql().to_string())
+    }
+
+    #[test]
+    fn parse_comparisons() {
+        assert_eq!(
+            parse_text("name = 'Rhein'").unwrap(),
+            Expr::Comparison(
+                prop("name"),
+                CmpOp::Eq,
+                Operand::String("Rhein".to_string())
+            )
+        );
+        assert_eq!(
+            parse_text("pop>=-1.5e3").unwrap(),
+            Expr::Comparison(prop("pop"), CmpOp::Ge, Operand::Number(-1500.0))
+        );
+        assert_eq!(
+            parse_text(r#""the name" <> 'it''s'"#).unwrap(),
+            Expr::Comparison(
+                prop("the name"),
+                CmpOp::Ne,
+                Operand::String("it's".to_string())
+            )
+        );
+        assert!(matches!(
+            parse_text("ts < TIMESTAMP('2024-01-01T00:00:00Z')").unwrap(),
+            Expr::Comparison(_, CmpOp::Lt, Operand::Timestamp(_))
+        ));
+        assert!(matches!(
+            parse_text("ts > DATE('2024-01-01')").unwrap(),
+            Expr::Comparison(_, CmpOp::Gt, Operand::Date(_))
+        ));
+    }
+
+    #[test]
+    fn parse_logical() {
+        assert_eq!(
+            parse_text("a = 1 OR b = 2 AND NOT c = 3").unwrap(),
+            Expr::Or(
+                Box::new(Expr::Comparison(prop("a"), CmpOp::Eq, Operand::Number(1.0))),
+                Box::new(Expr::And(
+                    Box::new(Expr::Comparison(prop("b"), CmpOp::Eq, Operand::Number(2.0))),
+                    Box::new(Expr::Not(Box::new(Expr::Comparison(
+                        prop("c"),
+                        CmpOp::Eq,
+                        Operand::Number(3.0)
+                    ))))
+                ))
+            )
+        );
+        assert!(matches!(
+            parse_text("(a = 1 or b = 2) and true").unwrap(),
+            Expr::And(_, _)
+        ));
+    }
+
+    #[test]
+    fn parse_predicates() {
+        assert_eq!(
+            parse_text("name NOT LIKE 'Rh%'").unwrap(),
+            Expr::Like {
+                value: prop("name"),
+                pattern: Operand::String("Rh%".to_string()),
+                negated: true
+            }
+        );
+        assert_eq!(
+            parse_text("pop BETWEEN 10 AND 20").unwrap(),
+            Expr::Between {
+                value: prop("pop"),
+                low: Operand::Number(10.0),
+                high: Operand::Number(20.0),
+                negated: false
+            }
+        );
+        assert_eq!(
+            parse_text("name IN ('a', 'b')").unwrap(),
+            Expr::In {
+                value: prop("name"),
+                list: vec![
+                    Operand::String("a".to_string()),
+                    Operand::String("b".to_string())
+                ],
+                negated: false
+            }
+        );
+        assert_eq!(
+            parse_text("name IS NOT NULL").unwrap(),
+            Expr::IsNull {
+                value: prop("name"),
+                negated: true
+            }
+        );
+    }
+
+    #[test]
+    fn parse_spatial() {
+        assert_eq!(
+            parse_text("S_INTERSECTS(geom, POINT(7.5 47.2))").unwrap(),
+            Expr::Spatial(
+                SpatialOp::Intersects,
+                prop("geom"),
+                Operand::Geometry("POINT(7.5 47.2)".to_string())
+            )
+        );
+        assert_eq!(
+            parse_text("s_within(geom, BBOX(1, 2, 3, 4))").unwrap(),
+            Expr::Spatial(
+                SpatialOp::Within,
+                prop("geom"),
+                Operand::Bbox([1.0, 2.0, 3.0, 4.0])
+            )
+        );
+        assert!(matches!(
+            parse_text("S_CONTAINS(POLYGON((0 0, 1 0, 1 1, 0 0)), geom)").unwrap(),
+            Expr::Spatial(SpatialOp::Contains, Operand::Geometry(_), _)
+        ));
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert_eq!(parse_text("name ="), Err(Cql2Error::UnexpectedEnd));
+        assert_eq!(parse_text("name = 'abc"), Err(Cql2Error::Unterminated));
+        assert_eq!(parse_text("name ; 1"), Err(Cql2Error::InvalidChar(';')));
+        assert!(parse_text("name = 1 name").is_err());
+        assert!(parse_text("name").is_err());
+        assert!(parse_text("S_INTERSECTS(geom, BBOX(1, 2, 3))").is_err());
+        assert!(parse_text("ts = TIMESTAMP('yesterday')").is_err());
+    }
+
+    #[test]
+    fn parse_limits() {
+        let nested = |depth: usize| format!("{}pop = 1{}", "(".repeat(depth), ")".repeat(depth));
+        assert!(parse_text(&nested(MAX_DEPTH)).is_ok());
+        assert_eq!(
+            parse_text(&nested(MAX_DEPTH + 1)),
+            Err(Cql2Error::TooDeeplyNested)
+        );
+        assert_eq!(
+            parse_text(&format!("{}pop = 1", "NOT ".repeat(100_000))),
+            Err(Cql2Error::TooDeeplyNested)
+        );
+        assert_eq!(
+            parse_text(&format!("{}pop = 1", "(".repeat(100_000))),
+            Err(Cql2Error::TooDeeplyNested)
+        );
+
+        let terms = |count: usize| vec!["pop = 1"; count].join(" OR ");
+        assert!(to_sql(&terms(MAX_TERMS)).is_ok());
+        assert_eq!(
+            parse_text(&terms(MAX_TERMS + 1)),
+            Err(Cql2Error::TooManyTerms)
+        );
+        assert_eq!(
+            parse_text(&vec!["pop = 1"; 100_000].join(" AND ")),
+            Err(Cql2Error::TooManyTerms)
+        );
+    }
+
+    #[test]
+    fn sql_generation() {
+        assert_eq!(
+            to_sql("name = 'Rhein' AND pop > 1000").unwrap(),
+            r#"(("name" = $1) AND ("pop" > $2))"#
+        );
+        assert_eq!(
+            to_sql("NOT name LIKE 'R%' OR pop IN (1, 2)").unwrap(),
+            r#"((NOT ("name"::text LIKE $1)) OR ("pop" IN ($2, $3)))"#
+        );
+        assert_eq!(
+            to_sql("pop NOT BETWEEN 1 AND 5").unwrap(),
+            r#"("pop" NOT BETWEEN $1 AND $2)"#
+        );
+        assert_eq!(
+            to_sql("S_INTERSECTS(geom, POINT(1 2))").unwrap(),
+            r#"ST_Intersects("geom", ST_SetSRID(ST_GeomFromText($1), ST_SRID("geom")))"#
+        );
+        assert_eq!(
+            to_sql("S_WITHIN(geom, BBOX(1,2,3,4))").unwrap(),
+            r#"ST_Within("geom", ST_MakeEnvelope($1, $2, $3, $4, ST_SRID("geom")))"#
+        );
+        assert_eq!(
+            to_sql("unknown = 1"),
+            Err(Cql2Error::UnknownProperty("unknown".to_string()))
+        );
+        assert!(to_sql("name = POINT(1 2)").is_err());
+    }
+}
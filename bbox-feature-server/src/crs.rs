@@ -0,0 +1,105 @@
+//! Coordinate reference systems (OGC API Features Part 2).
+
+use thiserror::Error;
+
+pub const CRS84: &str = "http://www.opengis.net/def/crs/OGC/1.3/CRS84";
+const EPSG_PREFIX: &str = "http://www.opengis.net/def/crs/EPSG/0/";
+
+#[derive(Error, Debug)]
+#[error("unsupported CRS `{0}`")]
+pub struct UnknownCrs(pub String);
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Crs {
+    pub srid: i32,
+    /// Latitude/longitude axis order (EPSG:4326) instead of x/y order used by PostGIS
+    pub lat_lon: bool,
+}
+
+impl Crs {
+    /// Parse OGC CRS URI or `EPSG:` code
+    pub fn from_uri(uri: &str) -> Result<Crs, UnknownCrs> {
+        if uri == CRS84 {
+            return Ok(Crs::crs84());
+        }
+        let srid = uri
+            .strip_prefix(EPSG_PREFIX)
+            .or_else(|| uri.strip_prefix("EPSG:"))
+            .and_then(|code| code.parse::<i32>().ok())
+            .ok_or(UnknownCrs(uri.to_string()))?;
+        Ok(Crs {
+            srid,
+            lat_lon: srid == 4326,
+        })
+    }
+    /// WGS84 with longitude/latitude axis order, the default output CRS
+    pub fn crs84() -> Crs {
+        Crs {
+            srid: 4326,
+            lat_lon: false,
+        }
+    }
+    /// CRS of geometries stored with `srid`
+    pub fn storage(srid: i32) -> Crs {
+        Crs {
+            srid,
+            lat_lon: false,
+        }
+    }
+    pub fn uri(&self) -> String {
+        if self.srid == 4326 && !self.lat_lon {
+            CRS84.to_string()
+        } else {
+            format!("{EPSG_PREFIX}{}", self.srid)
+        }
+    }
+    /// Output CRS URIs of a collection stored with `srid`. The default CRS84 is the first entry.
+    pub fn supported_uris(srid: i32) -> Vec<String> {
+        let mut uris = vec![CRS84.to_string()];
+        for uri in [Crs::storage(srid).uri(), format!("{EPSG_PREFIX}4326")] {
+            if !uris.contains(&uri) {
+                uris.push(uri);
+            }
+        }
+        uris
+    }
+    /// Check whether CRS is contained in a list of CRS URIs
+    pub fn is_supported(&self, uris: &[String]) -> bool {
+        uris.iter()
+            .any(|uri| Crs::from_uri(uri).map_or(false, |crs| &crs == self))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn crs_uris() {
+        let crs = Crs::from_uri("http://www.opengis.net/def/crs/EPSG/0/2056").unwrap();
+        assert_eq!(
+            crs,
+            Crs {
+                srid: 2056,
+                lat_lon: false
+            }
+        );
+        assert_eq!(crs.uri(), "http://www.opengis.net/def/crs/EPSG/0/2056");
+        assert!(Crs::from_uri("EPSG:4326").unwrap().lat_lon);
+        assert!(!Crs::from_uri(CRS84).unwrap().lat_lon);
+        assert!(Crs::from_uri("urn:ogc:def:crs:EPSG::4326").is_err());
+
+        let uris = Crs::supported_uris(3857);
+        assert_eq!(
+            uris,
+            vec![
+                CRS84,
+                "http://www.opengis.net/def/crs/EPSG/0/3857",
+                "http://www.opengis.net/def/crs/EPSG/0/4326"
+            ]
+        );
+        assert_eq!(Crs::supported_uris(4326).len(), 2);
+        assert!(Crs::from_uri("EPSG:3857").unwrap().is_supported(&uris));
+        assert!(!Crs::from_uri("EPSG:2056").unwrap().is_supported(&uris));
+    }
+}
@@ -0,0 +1,221 @@
+//! Datasources implemented outside of bbox.
+//!
+//! Applications embedding the feature service register a driver under a name:
+//!
+//! ```toml
+//! [[datasource]]
+//! name = "warehouse"
+//! [datasource.custom]
+//! driver = "mydriver"
+//! url = "https://warehouse.example.com"
+//!
+//! [[collection]]
+//! name = "parcels"
+//! [collection.custom]
+//! datasource = "warehouse"
+//! dataset = "parcels"
+//! ```
+//!
+//! The driver creates a datasource from the `[datasource.custom]` parameters, which sets up
+//! collections with a `CollectionSource` implementation. Collections with a `driver` and without
+//! `datasource` get their own datasource created from the collection parameters.
+
+use crate::config::{CollectionSourceCfg, ConfiguredCollectionCfg};
+use crate::datasource::CollectionDatasource;
+use crate::error::{Error, Result};
+use crate::inventory::FeatureCollection;
+use async_trait::async_trait;
+use bbox_core::ogcapi::CoreExtent;
+use dyn_clone::{clone_trait_object, DynClone};
+use log::info;
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+/// Driver specific configuration parameters
+pub type DriverParams = serde_json::Map<String, serde_json::Value>;
+
+#[async_trait]
+pub trait DatasourceDriver: Send + Sync {
+    /// Create datasource, e.g. a connection pool
+    async fn create_datasource(&self, params: &DriverParams) -> Result<Box<dyn CustomDatasource>>;
+}
+
+/// Datasource created by a driver.
+/// Collection parameters are contained in `CollectionSourceCfg::Custom` of the collection configuration.
+pub trait CustomDatasource: CollectionDatasource + DynClone + Send + Sync {}
+
+impl<T: CollectionDatasource + Clone + Send + Sync> CustomDatasource for T {}
+
+clone_trait_object!(CustomDatasource);
+
+#[async_trait]
+impl CollectionDatasource for Box<dyn CustomDatasource> {
+    async fn setup_collection(
+        &mut self,
+        cfg: &ConfiguredCollectionCfg,
+        extent: Option<CoreExtent>,
+    ) -> Result<FeatureCollection> {
+        (**self).setup_collection(cfg, extent).await
+    }
+}
+
+static DRIVERS: Lazy<RwLock<HashMap<String, Arc<dyn DatasourceDriver>>>> =
+    Lazy::new(Default::default);
+
+/// Register driver for `[datasource.custom]` and `[collection.custom]` configurations.
+/// Has to be called before the feature service is created.
+pub fn register_driver(name: &str, driver: impl DatasourceDriver + 'static) {
+    info!("Registering datasource driver `{name}`");
+    DRIVERS
+        .write()
+        .expect("driver registry")
+        .insert(name.to_string(), Arc::new(driver));
+}
+
+fn driver(name: &str) -> Result<Arc<dyn DatasourceDriver>> {
+    DRIVERS
+        .read()
+        .expect("driver registry")
+        .get(name)
+        .cloned()
+        .ok_or_else(|| Error::DatasourceSetupError(format!("driver `{name}` not registered")))
+}
+
+/// Create datasource with registered driver
+pub(crate) async fn create_datasource(
+    driver_name: &str,
+    params: &DriverParams,
+) -> Result<Box<dyn CustomDatasource>> {
+    driver(driver_name)?.create_datasource(params).await
+}
+
+/// Datasource of a collection with its own driver instance
+pub(crate) async fn collection_datasource(
+    cfg: &ConfiguredCollectionCfg,
+) -> Result<Option<Box<dyn CustomDatasource>>> {
+    let CollectionSourceCfg::Custom(custom_cfg) = &cfg.source else {
+        return Ok(None);
+    };
+    match (&custom_cfg.driver, &custom_cfg.datasource) {
+        (Some(driver_name), None) => Ok(Some(
+            create_datasource(driver_name, &custom_cfg.params).await?,
+        )),
+        _ => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::CustomCollectionCfg;
+    use crate::datasource::{CollectionSource, ItemsResult};
+    use crate::filter_params::FilterParams;
+    use crate::inventory::Inventory;
+    use bbox_core::ogcapi::{CoreCollection, CoreFeature, Queryables};
+    use serde_json::json;
+
+    #[derive(Clone)]
+    struct StaticSource {
+        name: String,
+    }
+
+    #[async_trait]
+    impl CollectionSource for StaticSource {
+        async fn items(&self, _filter: &FilterParams) -> Result<ItemsResult> {
+            let feature = CoreFeature {
+                type_: "Feature".to_string(),
+                bbox: None,
+                id: Some("1".to_string()),
+                geometry: json!({"type": "Point", "coordinates": [7.5, 47.2]}),
+                properties: Some(json!({"name": self.name})),
+                links: vec![],
+            };
+            Ok(ItemsResult {
+                features: vec![feature],
+                number_matched: 1,
+                number_returned: 1,
+                paging: Default::default(),
+            })
+        }
+        async fn item(
+            &self,
+            _collection_id: &str,
+            _feature_id: &str,
+            _crs: Option<&crate::crs::Crs>,
+        ) -> Result<Option<CoreFeature>> {
+            Ok(None)
+        }
+        async fn queryables(&self, _collection_id: &str) -> Result<Option<Queryables>> {
+            Ok(None)
+        }
+    }
+
+    #[derive(Clone)]
+    struct StaticDatasource {
+        name: String,
+    }
+
+    #[async_trait]
+    impl CollectionDatasource for StaticDatasource {
+        async fn setup_collection(
+            &mut self,
+            cfg: &ConfiguredCollectionCfg,
+            _extent: Option<CoreExtent>,
+        ) -> Result<FeatureCollection> {
+            let collection = CoreCollection {
+                id: cfg.name.clone(),
+                title: Some(cfg.name.clone()),
+                description: None,
+                attribution: None,
+                license: None,
+                extent: None,
+                item_type: None,
+                crs: vec![],
+                summaries: None,
+                links: vec![],
+            };
+            Ok(FeatureCollection {
+                collection,
+                source: Box::new(StaticSource {
+                    name: self.name.clone(),
+                }),
+            })
+        }
+    }
+
+    struct StaticDriver;
+
+    #[async_trait]
+    impl DatasourceDriver for StaticDriver {
+        async fn create_datasource(
+            &self,
+            params: &DriverParams,
+        ) -> Result<Box<dyn CustomDatasource>> {
+            let name = params["name"].as_str().unwrap_or_default().to_string();
+            Ok(Box::new(StaticDatasource { name }))
+        }
+    }
+
+    #[tokio::test]
+    async fn custom_driver() {
+        register_driver("static", StaticDriver);
+        let cfg: CustomCollectionCfg =
+            serde_json::from_value(json!({"driver": "static", "name": "test"})).unwrap();
+        let cfg = ConfiguredCollectionCfg::new("custom", CollectionSourceCfg::Custom(cfg));
+        let mut ds = collection_datasource(&cfg).await.unwrap().unwrap();
+        let mut inventory = Inventory::new();
+        inventory.add_collection(ds.setup_collection(&cfg, None).await.unwrap());
+        let items = inventory
+            .collection_items("custom", &FilterParams::default())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(items.features[0].properties, Some(json!({"name": "test"})));
+
+        let cfg: CustomCollectionCfg =
+            serde_json::from_value(json!({"driver": "unknown"})).unwrap();
+        let cfg = ConfiguredCollectionCfg::new("unknown", CollectionSourceCfg::Custom(cfg));
+        assert!(collection_datasource(&cfg).await.is_err());
+    }
+}
@@ -0,0 +1,620 @@
+//! Elasticsearch and OpenSearch feature source.
+
+use crate::crs::Crs;
+use crate::datasource::{
+    unsupported_param, CollectionSource, CollectionSourceCfg, ConfiguredCollectionCfg, ItemsResult,
+    Paging,
+};
+use crate::error::{Error, Result};
+use crate::filter_params::{FilterParams, TemporalType};
+use crate::inventory::FeatureCollection;
+use async_trait::async_trait;
+use bbox_core::ogcapi::*;
+use geozero::{wkt::WktStr, ToJson};
+use log::{debug, error, info, warn};
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+
+/// Maximal `from` + `size` of a search request (ES default `index.max_result_window`)
+const MAX_RESULT_WINDOW: u64 = 10000;
+
+#[derive(Clone, Debug)]
+pub struct ElasticsearchCollectionSource {
+    client: reqwest::Client,
+    url: String,
+    index: String,
+    username: Option<String>,
+    password: Option<String>,
+    geometry_field: String,
+    /// Id field, `_id` if None
+    fid_field: Option<String>,
+    temporal_field: Option<String>,
+    temporal_end_field: Option<String>,
+    /// Queryable fields
+    other_columns: HashMap<String, QueryableType>,
+}
+
+impl ElasticsearchCollectionSource {
+    pub async fn setup_collection(cfg: &ConfiguredCollectionCfg) -> Result<FeatureCollection> {
+        info!("Setup Elasticsearch Collection `{}`", &cfg.name);
+        let CollectionSourceCfg::Elasticsearch(ref srccfg) = cfg.source else {
+            panic!();
+        };
+        let id = &cfg.name;
+        let mut source = ElasticsearchCollectionSource {
+            client: reqwest::Client::new(),
+            url: srccfg.url.trim_end_matches('/').to_string(),
+            index: srccfg.index.clone(),
+            username: srccfg.username.clone(),
+            password: srccfg.password.clone(),
+            geometry_field: String::new(),
+            fid_field: srccfg.fid_field.clone(),
+            temporal_field: srccfg.temporal_field.clone(),
+            temporal_end_field: srccfg.temporal_end_field.clone(),
+            other_columns: HashMap::new(),
+        };
+        let fields = source.field_types().await?;
+        source.geometry_field = if let Some(field) = &srccfg.geometry_field {
+            match fields.get(field).map(String::as_str) {
+                Some("geo_point" | "geo_shape") => field.clone(),
+                _ => {
+                    return Err(Error::DatasourceSetupError(format!(
+                        "Datasource `{id}`: `{field}` is not a geo_point or geo_shape field"
+                    )))
+                }
+            }
+        } else {
+            let mut geo_fields: Vec<&String> = fields
+                .iter()
+                .filter(|(_, ty)| *ty == "geo_point" || *ty == "geo_shape")
+                .map(|(name, _)| name)
+                .collect();
+            geo_fields.sort();
+            geo_fields
+                .first()
+                .map(|f| f.to_string())
+                .ok_or(Error::DatasourceSetupError(format!(
+                    "Datasource `{id}`: no geo_point or geo_shape field found"
+                )))?
+        };
+        let mut queryable_fields = srccfg.queryable_fields.clone();
+        queryable_fields.extend(source.temporal_field.clone());
+        queryable_fields.extend(source.temporal_end_field.clone());
+        for field in &queryable_fields {
+            let Some(ty) = fields.get(field) else {
+                return Err(Error::DatasourceSetupError(format!(
+                    "Queryable field `{field}` not found"
+                )));
+            };
+            source
+                .other_columns
+                .insert(field.clone(), queryable_type(field, ty)?);
+        }
+        let extent = match source.extent().await {
+            Ok(extent) => extent,
+            Err(e) => {
+                warn!("Datasource `{id}`: failed to query extent: {e}");
+                None
+            }
+        };
+
+        let collection = CoreCollection {
+            id: id.clone(),
+            title: cfg.title.clone(),
+            description: cfg.description.clone(),
+            attribution: cfg.attribution.clone(),
+            license: cfg.license.clone(),
+            extent,
+            item_type: None,
+            crs: vec![],
+            summaries: None,
+            links: vec![ApiLink {
+                href: format!("/collections/{id}/items"),
+                rel: Some("items".to_string()),
+                type_: Some("application/geo+json".to_string()),
+                title: cfg.title.clone(),
+                hreflang: None,
+                length: None,
+            }],
+        };
+        Ok(FeatureCollection {
+            collection,
+            source: Box::new(source),
+        })
+    }
+
+    async fn request(
+        &self,
+        method: reqwest::Method,
+        path: &str,
+        body: Option<&Value>,
+    ) -> Result<Value> {
+        let url = format!("{}/{}/{path}", self.url, self.index);
+        let mut req = self.client.request(method, &url);
+        if let Some(username) = &self.username {
+            req = req.basic_auth(username, self.password.as_ref());
+        }
+        if let Some(body) = body {
+            debug!("ES query: {body}");
+            req = req
+                .header(reqwest::header::CONTENT_TYPE, "application/json")
+                .body(body.to_string());
+        }
+        let resp = req.send().await?;
+        let status = resp.status();
+        let text = resp.text().await?;
+        if !status.is_success() {
+            return Err(Error::ElasticsearchError(format!("{url}: {status} {text}")));
+        }
+        Ok(serde_json::from_str(&text)?)
+    }
+
+    /// Field types from index mapping (nested fields with dotted names)
+    async fn field_types(&self) -> Result<HashMap<String, String>> {
+        let mapping = self.request(reqwest::Method::GET, "_mapping", None).await?;
+        let mut fields = HashMap::new();
+        // Index patterns and aliases return the mappings of all matching indices
+        if let Some(indices) = mapping.as_object() {
+            for index in indices.values() {
+                collect_field_types(&index["mappings"]["properties"], "", &mut fields);
+            }
+        }
+        Ok(fields)
+    }
+
+    async fn extent(&self) -> Result<Option<CoreExtent>> {
+        let query = json!({
+            "size": 0,
+            "aggs": { "extent": { "geo_bounds": { "field": self.geometry_field } } }
+        });
+        let resp = self
+            .request(reqwest::Method::POST, "_search", Some(&query))
+            .await?;
+        let bounds = &resp["aggregations"]["extent"]["bounds"];
+        let (Some(xmin), Some(ymin), Some(xmax), Some(ymax)) = (
+            bounds["top_left"]["lon"].as_f64(),
+            bounds["bottom_right"]["lat"].as_f64(),
+            bounds["bottom_right"]["lon"].as_f64(),
+            bounds["top_left"]["lat"].as_f64(),
+        ) else {
+            return Ok(None);
+        };
+        Ok(Some(CoreExtent {
+            spatial: Some(CoreExtentSpatial {
+                bbox: vec![vec![xmin, ymin, xmax, ymax]],
+                crs: None,
+            }),
+            temporal: None,
+        }))
+    }
+
+    /// Translate filter parameters into a bool query
+    fn query(&self, filter: &FilterParams) -> Result<Value> {
+        let mut filters = Vec::new();
+        match filter.bbox() {
+            Ok(Some(bbox)) => {
+                unsupported_param("bbox-crs", filter.bbox_crs.is_some())?;
+                let (xmin, ymin, xmax, ymax) = if bbox.len() == 6 {
+                    (bbox[0], bbox[1], bbox[3], bbox[4])
+                } else {
+                    (bbox[0], bbox[1], bbox[2], bbox[3])
+                };
+                filters.push(json!({"geo_bounding_box": {
+                    &self.geometry_field: {
+                        "top_left": {"lat": ymax, "lon": xmin},
+                        "bottom_right": {"lat": ymin, "lon": xmax}
+                    }
+                }}));
+            }
+            Ok(None) => {}
+            Err(e) => {
+                error!("Ignoring invalid bbox: {e}");
+                return Err(Error::QueryParams);
+            }
+        }
+        if let Some(geojson) = &filter.intersects {
+            let shape: Value = serde_json::from_str(geojson).map_err(|_| Error::QueryParams)?;
+            filters.push(json!({"geo_shape": {
+                &self.geometry_field: {"shape": shape, "relation": "intersects"}
+            }}));
+        }
+        if let Some(temporal_field) = &self.temporal_field {
+            let temporal_end_field = self.temporal_end_field.as_ref().unwrap_or(temporal_field);
+            match filter.temporal() {
+                Ok(Some(parts)) => match parts.as_slice() {
+                    [TemporalType::DateTime(dt)] => {
+                        filters.push(json!({"range": {
+                            temporal_field: {"lte": dt.to_rfc3339()}
+                        }}));
+                        filters.push(json!({"range": {
+                            temporal_end_field: {"gte": dt.to_rfc3339()}
+                        }}));
+                    }
+                    [TemporalType::Open, TemporalType::DateTime(dt)] => {
+                        filters.push(json!({"range": {
+                            temporal_field: {"lte": dt.to_rfc3339()}
+                        }}));
+                    }
+                    [TemporalType::DateTime(dt), TemporalType::Open] => {
+                        filters.push(json!({"range": {
+                            temporal_end_field: {"gte": dt.to_rfc3339()}
+                        }}));
+                    }
+                    [TemporalType::DateTime(dt1), TemporalType::DateTime(dt2)] => {
+                        filters.push(json!({"range": {
+                            temporal_field: {"gte": dt1.to_rfc3339()}
+                        }}));
+                        filters.push(json!({"range": {
+                            temporal_end_field: {"lte": dt2.to_rfc3339()}
+                        }}));
+                    }
+                    _ => {
+                        error!("Invalid datetime interval");
+                        return Err(Error::QueryParams);
+                    }
+                },
+                Ok(None) => {}
+                Err(e) => {
+                    error!("Ignoring invalid temporal field: {e}");
+                    return Err(Error::QueryParams);
+                }
+            }
+        }
+        for (key, val) in &filter.filters {
+            let Some(field) = self
+                .other_columns
+                .keys()
+                .find(|k| k.eq_ignore_ascii_case(key))
+            else {
+                error!("Invalid query param {key}");
+                return Err(Error::QueryParams);
+            };
+            if val.contains('*') {
+                filters.push(json!({"wildcard": {field: {"value": val}}}));
+            } else {
+                filters.push(json!({"term": {field: val}}));
+            }
+        }
+        if let Some(ids) = filter.ids() {
+            filters.push(self.ids_query(&ids));
+        }
+        unsupported_param("crs", filter.crs.is_some())?;
+        unsupported_param("filter", filter.filter.is_some())?;
+        Ok(json!({"bool": {"filter": filters}}))
+    }
+
+    fn ids_query(&self, ids: &[String]) -> Value {
+        if let Some(fid) = &self.fid_field {
+            json!({"terms": {fid: ids}})
+        } else {
+            json!({"ids": {"values": ids}})
+        }
+    }
+
+    /// Search documents. Pages beyond the result window are reached with `search_after`.
+    async fn search(&self, query: Value, offset: u64, limit: u64) -> Result<ItemsResult> {
+        let limit = if limit == 0 {
+            MAX_RESULT_WINDOW
+        } else {
+            limit.min(MAX_RESULT_WINDOW)
+        };
+        let mut body = json!({
+            "query": query,
+            "track_total_hits": true,
+            "sort": ["_doc"],
+        });
+        if offset + limit > MAX_RESULT_WINDOW {
+            // Skip documents before offset
+            let mut skip = offset;
+            while skip > 0 {
+                let size = skip.min(MAX_RESULT_WINDOW);
+                body["size"] = json!(size);
+                body["_source"] = json!(false);
+                let resp = self
+                    .request(reqwest::Method::POST, "_search", Some(&body))
+                    .await?;
+                let hits = resp["hits"]["hits"].as_array().cloned().unwrap_or_default();
+                match hits.last() {
+                    Some(last) if hits.len() as u64 == size => {
+                        body["search_after"] = last["sort"].clone();
+                        skip -= size;
+                    }
+                    _ => {
+                        // Offset beyond number of matches
+                        return Ok(ItemsResult {
+                            features: Vec::new(),
+                            number_matched: total_hits(&resp),
+                            number_returned: 0,
+                            paging: Paging::Offset,
+                        });
+                    }
+                }
+            }
+            if let Some(body) = body.as_object_mut() {
+                body.remove("_source");
+            }
+        } else {
+            body["from"] = json!(offset);
+        }
+        body["size"] = json!(limit);
+        let resp = self
+            .request(reqwest::Method::POST, "_search", Some(&body))
+            .await?;
+        let features: Vec<CoreFeature> = resp["hits"]["hits"]
+            .as_array()
+            .map(|hits| hits.iter().map(|hit| self.hit_to_feature(hit)).collect())
+            .unwrap_or_default();
+        Ok(ItemsResult {
+            number_matched: total_hits(&resp),
+            number_returned: features.len() as u64,
+            features,
+            paging: Paging::Offset,
+        })
+    }
+
+    fn hit_to_feature(&self, hit: &Value) -> CoreFeature {
+        let mut properties = hit["_source"].as_object().cloned().unwrap_or_default();
+        let geometry = take_field(&mut properties, &self.geometry_field)
+            .map(|geom| geo_value_to_geojson(&geom))
+            .unwrap_or(Value::Null);
+        let id = if let Some(fid) = &self.fid_field {
+            properties.get(fid).cloned()
+        } else {
+            Some(hit["_id"].clone())
+        }
+        .map(|id| match id {
+            Value::String(s) => s,
+            v => v.to_string(),
+        });
+        CoreFeature {
+            type_: "Feature".to_string(),
+            bbox: None,
+            id,
+            geometry,
+            properties: Some(Value::Object(properties)),
+            links: vec![],
+        }
+    }
+}
+
+#[async_trait]
+impl CollectionSource for ElasticsearchCollectionSource {
+    async fn items(&self, filter: &FilterParams) -> Result<ItemsResult> {
+        let query = self.query(filter)?;
+        self.search(
+            query,
+            filter.offset.unwrap_or(0) as u64,
+            filter.limit_or_default() as u64,
+        )
+        .await
+    }
+
+    async fn item(
+        &self,
+        collection_id: &str,
+        feature_id: &str,
+        crs: Option<&Crs>,
+    ) -> Result<Option<CoreFeature>> {
+        unsupported_param("crs", crs.is_some())?;
+        let query = json!({"bool": {"filter": [self.ids_query(&[feature_id.to_string()])]}});
+        let result = self.search(query, 0, 1).await?;
+        let Some(mut item) = result.features.into_iter().next() else {
+            return Ok(None);
+        };
+        item.links = vec![
+            ApiLink {
+                href: format!("/collections/{collection_id}/items/{feature_id}"),
+                rel: Some("self".to_string()),
+                type_: Some("application/geo+json".to_string()),
+                title: Some("this document".to_string()),
+                hreflang: None,
+                length: None,
+            },
+            ApiLink {
+                href: format!("/collections/{collection_id}"),
+                rel: Some("collection".to_string()),
+                type_: Some("application/geo+json".to_string()),
+                title: Some("the collection document".to_string()),
+                hreflang: None,
+                length: None,
+            },
+        ];
+        Ok(Some(item))
+    }
+
+    fn temporal_properties(&self) -> Option<(&str, Option<&str>)> {
+        self.temporal_field
+            .as_ref()
+            .map(|col| (col.as_str(), self.temporal_end_field.as_deref()))
+    }
+    async fn queryables(&self, collection_id: &str) -> Result<Option<Queryables>> {
+        let properties: HashMap<String, QueryableProperty> = self
+            .other_columns
+            .iter()
+            .map(|(name, type_)| {
+                (
+                    name.clone(),
+                    QueryableProperty {
+                        title: Some(name.clone()),
+                        type_: Some(type_.clone()),
+                        format: None,
+                        enum_: None,
+                        minimum: None,
+                        maximum: None,
+                    },
+                )
+            })
+            .collect();
+        Ok(Some(Queryables {
+            id: format!("/collections/{collection_id}/queryables"),
+            title: Some(collection_id.to_string()),
+            schema: "http://json-schema.org/draft/2019-09/schema".to_string(),
+            type_: "object".to_string(),
+            properties,
+        }))
+    }
+}
+
+fn total_hits(resp: &Value) -> u64 {
+    resp["hits"]["total"]["value"].as_u64().unwrap_or(0)
+}
+
+fn collect_field_types(properties: &Value, prefix: &str, fields: &mut HashMap<String, String>) {
+    let Some(properties) = properties.as_object() else {
+        return;
+    };
+    for (name, mapping) in properties {
+        let path = format!("{prefix}{name}");
+        if let Some(ty) = mapping["type"].as_str() {
+            fields.insert(path.clone(), ty.to_string());
+        }
+        collect_field_types(&mapping["properties"], &format!("{path}."), fields);
+    }
+}
+
+fn queryable_type(field: &str, es_type: &str) -> Result<QueryableType> {
+    let queryable_type = match es_type {
+        "keyword" | "constant_keyword" | "wildcard" | "text" => QueryableType::String,
+        "long" | "integer" | "short" | "byte" | "unsigned_long" => QueryableType::Integer,
+        "double" | "float" | "half_float" | "scaled_float" => QueryableType::Number,
+        "boolean" => QueryableType::Bool,
+        "date" | "date_nanos" => QueryableType::Datetime,
+        ty => {
+            return Err(Error::DatasourceSetupError(format!(
+                "{field} has an Elasticsearch type {ty} which is not currently handled and can't be used a queryable"
+            )))
+        }
+    };
+    Ok(queryable_type)
+}
+
+/// Remove a (dotted) field from a document source
+fn take_field(source: &mut Map<String, Value>, field: &str) -> Option<Value> {
+    if let Some(value) = source.remove(field) {
+        return Some(value);
+    }
+    let (parent, name) = field.split_once('.')?;
+    take_field(source.get_mut(parent)?.as_object_mut()?, name)
+}
+
+/// Convert geo_point or geo_shape values into GeoJSON
+fn geo_value_to_geojson(value: &Value) -> Value {
+    match value {
+        // GeoJSON
+        Value::Object(obj) if obj.contains_key("type") => value.clone(),
+        Value::Object(obj) => match (obj.get("lon"), obj.get("lat")) {
+            (Some(lon), Some(lat)) => json!({"type": "Point", "coordinates": [lon, lat]}),
+            _ => Value::Null,
+        },
+        Value::Array(coords) if coords.len() >= 2 && coords[0].is_number() => {
+            json!({"type": "Point", "coordinates": coords})
+        }
+        Value::String(s) if s.contains('(') => WktStr(s)
+            .to_json()
+            .ok()
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or(Value::Null),
+        Value::String(s) => {
+            // "lat,lon"
+            let coords: Vec<f64> = s.split(',').filter_map(|v| v.trim().parse().ok()).collect();
+            match coords.as_slice() {
+                [lat, lon] => json!({"type": "Point", "coordinates": [lon, lat]}),
+                _ => {
+                    warn!("Unsupported geo_point value `{s}`");
+                    Value::Null
+                }
+            }
+        }
+        _ => Value::Null,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn source() -> ElasticsearchCollectionSource {
+        ElasticsearchCollectionSource {
+            client: reqwest::Client::new(),
+            url: "http://localhost:9200".to_string(),
+            index: "events".to_string(),
+            username: None,
+            password: None,
+            geometry_field: "location".to_string(),
+            fid_field: None,
+            temporal_field: Some("timestamp".to_string()),
+            temporal_end_field: None,
+            other_columns: [("status".to_string(), QueryableType::String)].into(),
+        }
+    }
+
+    #[test]
+    fn query_dsl() {
+        let filter = FilterParams {
+            bbox: Some("5.6,45.8,10.9,47.6".to_string()),
+            datetime: Some("2023-01-01T00:00:00Z/..".to_string()),
+            filters: [("STATUS".to_string(), "open".to_string())].into(),
+            ids: Some("a,b".to_string()),
+            ..Default::default()
+        };
+        let query = source().query(&filter).unwrap();
+        assert_eq!(
+            query,
+            json!({"bool": {"filter": [
+                {"geo_bounding_box": {"location": {
+                    "top_left": {"lat": 47.6, "lon": 5.6},
+                    "bottom_right": {"lat": 45.8, "lon": 10.9}
+                }}},
+                {"range": {"timestamp": {"gte": "2023-01-01T00:00:00+00:00"}}},
+                {"term": {"status": "open"}},
+                {"ids": {"values": ["a", "b"]}}
+            ]}})
+        );
+
+        let filter = FilterParams {
+            filters: [("severity".to_string(), "1".to_string())].into(),
+            ..Default::default()
+        };
+        assert!(source().query(&filter).is_err());
+    }
+
+    #[test]
+    fn hit_conversion() {
+        let hit = json!({
+            "_id": "abc",
+            "_source": {"status": "open", "location": {"lat": 47.2, "lon": 7.5}}
+        });
+        let feature = source().hit_to_feature(&hit);
+        assert_eq!(feature.id.as_deref(), Some("abc"));
+        assert_eq!(
+            feature.geometry,
+            json!({"type": "Point", "coordinates": [7.5, 47.2]})
+        );
+        assert_eq!(feature.properties, Some(json!({"status": "open"})));
+
+        assert_eq!(
+            geo_value_to_geojson(&json!("47.2,7.5")),
+            json!({"type": "Point", "coordinates": [7.5, 47.2]})
+        );
+        assert_eq!(
+            geo_value_to_geojson(&json!([7.5, 47.2])),
+            json!({"type": "Point", "coordinates": [7.5, 47.2]})
+        );
+        assert_eq!(
+            geo_value_to_geojson(&json!("POINT (7.5 47.2)")),
+            json!({"type": "Point", "coordinates": [7.5, 47.2]})
+        );
+    }
+
+    #[test]
+    fn mapping_fields() {
+        let properties = json!({
+            "location": {"type": "geo_point"},
+            "device": {"properties": {"name": {"type": "keyword"}}}
+        });
+        let mut fields = HashMap::new();
+        collect_field_types(&properties, "", &mut fields);
+        assert_eq!(fields["location"], "geo_point");
+        assert_eq!(fields["device.name"], "keyword");
+    }
+}
@@ -0,0 +1,519 @@
+//! FlatGeobuf feature source.
+
+use crate::config::FlatgeobufCollectionCfg;
+use crate::crs::Crs;
+use crate::datasource::{
+    unsupported_param, CollectionSource, CollectionSourceCfg, ConfiguredCollectionCfg, ItemsResult,
+    Paging,
+};
+use crate::error::{Error, Result};
+use crate::filter_params::FilterParams;
+use crate::inventory::FeatureCollection;
+use async_trait::async_trait;
+use bbox_core::ogcapi::*;
+use flatgeobuf::{
+    ColumnType, FallibleStreamingIterator, FeatureProperties, FgbFeature, FgbReader, Header,
+    HttpFgbReader,
+};
+use geozero::geojson::GeoJsonWriter;
+use log::{error, info, warn};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+#[derive(Clone, Debug)]
+pub struct FlatgeobufCollectionSource {
+    /// File path or URL
+    path: String,
+    fid_column: Option<String>,
+    /// Queryable fields
+    other_columns: HashMap<String, QueryableType>,
+}
+
+/// Metadata from FlatGeobuf header
+struct HeaderInfo {
+    title: Option<String>,
+    description: Option<String>,
+    envelope: Option<Vec<f64>>,
+    columns: HashMap<String, ColumnType>,
+}
+
+impl HeaderInfo {
+    fn from_header(header: Header) -> Self {
+        HeaderInfo {
+            title: header.title().map(str::to_string),
+            description: header.description().map(str::to_string),
+            envelope: header.envelope().map(|env| env.iter().collect()),
+            columns: header
+                .columns()
+                .map(|cols| {
+                    cols.iter()
+                        .map(|col| (col.name().to_string(), col.type_()))
+                        .collect()
+                })
+                .unwrap_or_default(),
+        }
+    }
+}
+
+impl FlatgeobufCollectionSource {
+    pub async fn setup_collection(cfg: &ConfiguredCollectionCfg) -> Result<FeatureCollection> {
+        info!("Setup FlatGeobuf Collection `{}`", &cfg.name);
+        let CollectionSourceCfg::Flatgeobuf(ref srccfg) = cfg.source else {
+            panic!();
+        };
+        let id = &cfg.name;
+        let info = if is_url(&srccfg.path) {
+            let fgb = HttpFgbReader::open(&srccfg.path).await?;
+            HeaderInfo::from_header(fgb.header())
+        } else {
+            let mut file = BufReader::new(File::open(&srccfg.path)?);
+            let fgb = FgbReader::open(&mut file)?;
+            HeaderInfo::from_header(fgb.header())
+        };
+        if let Some(fid) = &srccfg.fid_field {
+            if !info.columns.contains_key(fid) {
+                return Err(Error::DatasourceSetupError(format!(
+                    "Datasource `{id}`: `fid_field` `{fid}` not found"
+                )));
+            }
+        } else {
+            warn!("Datasource `{id}`: `fid_field` missing - single item queries will be ignored");
+        }
+        let mut other_columns = HashMap::new();
+        for field in &srccfg.queryable_fields {
+            let Some(column_type) = info.columns.get(field) else {
+                return Err(Error::DatasourceSetupError(format!(
+                    "Queryable field `{field}` not found"
+                )));
+            };
+            other_columns.insert(field.clone(), queryable_type(field, *column_type)?);
+        }
+        let extent = match info.envelope.as_deref() {
+            Some([xmin, ymin, xmax, ymax, ..]) => Some(CoreExtent {
+                spatial: Some(CoreExtentSpatial {
+                    bbox: vec![vec![*xmin, *ymin, *xmax, *ymax]],
+                    crs: None,
+                }),
+                temporal: None,
+            }),
+            _ => None,
+        };
+        let title = cfg.title.clone().or(info.title);
+        let source = FlatgeobufCollectionSource {
+            path: srccfg.path.clone(),
+            fid_column: srccfg.fid_field.clone(),
+            other_columns,
+        };
+
+        let collection = CoreCollection {
+            id: id.clone(),
+            title: title.clone(),
+            description: cfg.description.clone().or(info.description),
+            attribution: cfg.attribution.clone(),
+            license: cfg.license.clone(),
+            extent,
+            item_type: None,
+            crs: vec![],
+            summaries: None,
+            links: vec![ApiLink {
+                href: format!("/collections/{id}/items"),
+                rel: Some("items".to_string()),
+                type_: Some("application/geo+json".to_string()),
+                title,
+                hreflang: None,
+                length: None,
+            }],
+        };
+        Ok(FeatureCollection {
+            collection,
+            source: Box::new(source),
+        })
+    }
+
+    /// Collections of FlatGeobuf files found in directory scan
+    pub async fn file_collection(path: &Path) -> Result<FeatureCollection> {
+        let name = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let cfg = ConfiguredCollectionCfg {
+            source: CollectionSourceCfg::Flatgeobuf(FlatgeobufCollectionCfg {
+                path: path.to_string_lossy().to_string(),
+                ..Default::default()
+            }),
+            name,
+            title: None,
+            description: None,
+            attribution: None,
+            license: None,
+            downloads: Vec::new(),
+        };
+        Self::setup_collection(&cfg).await
+    }
+
+    /// Read features within `bbox` using the spatial index and apply filters
+    async fn query(
+        &self,
+        bbox: Option<[f64; 4]>,
+        ids: Option<Vec<String>>,
+        filters: Vec<(String, String)>,
+        offset: u64,
+        limit: u64,
+    ) -> Result<ItemsResult> {
+        let mut collector = FeatureCollector {
+            fid_column: self.fid_column.as_deref(),
+            ids,
+            filters,
+            offset,
+            limit,
+            features_count: None,
+            features: Vec::new(),
+            number_matched: 0,
+        };
+        if is_url(&self.path) {
+            let fgb = HttpFgbReader::open(&self.path).await?;
+            let mut iter = if let Some(bbox) = bbox {
+                fgb.select_bbox(bbox[0], bbox[1], bbox[2], bbox[3]).await?
+            } else {
+                fgb.select_all().await?
+            };
+            collector.features_count = iter.features_count().map(|cnt| cnt as u64);
+            while let Some(feature) = iter.next().await? {
+                if !collector.add(feature)? {
+                    break;
+                }
+            }
+        } else {
+            let mut file = BufReader::new(File::open(&self.path)?);
+            let fgb = FgbReader::open(&mut file)?;
+            let mut iter = if let Some(bbox) = bbox {
+                fgb.select_bbox(bbox[0], bbox[1], bbox[2], bbox[3])?
+            } else {
+                fgb.select_all()?
+            };
+            collector.features_count = iter.features_count().map(|cnt| cnt as u64);
+            while let Some(feature) = iter.next()? {
+                if !collector.add(feature)? {
+                    break;
+                }
+            }
+        }
+        Ok(collector.result())
+    }
+}
+
+/// Filter features and collect the requested page
+struct FeatureCollector<'a> {
+    fid_column: Option<&'a str>,
+    ids: Option<Vec<String>>,
+    filters: Vec<(String, String)>,
+    offset: u64,
+    limit: u64,
+    /// Number of selected features, if known in advance
+    features_count: Option<u64>,
+    features: Vec<CoreFeature>,
+    number_matched: u64,
+}
+
+impl FeatureCollector<'_> {
+    /// Add feature if matching. Returns `false` when no more features are needed.
+    fn add(&mut self, feature: &FgbFeature) -> Result<bool> {
+        let has_filters = self.ids.is_some() || !self.filters.is_empty();
+        if has_filters {
+            let properties = feature.properties()?;
+            let value = |field: &str| properties.get(field).map(String::as_str);
+            if let (Some(ids), Some(fid)) = (&self.ids, self.fid_column) {
+                if !ids.iter().any(|id| Some(id.as_str()) == value(fid)) {
+                    return Ok(true);
+                }
+            }
+            if !self
+                .filters
+                .iter()
+                .all(|(field, filter)| value(field) == Some(filter.as_str()))
+            {
+                return Ok(true);
+            }
+        }
+        if self.number_matched >= self.offset
+            && (self.limit == 0 || self.number_matched < self.offset + self.limit)
+        {
+            self.features
+                .push(fgb_to_feature(feature, self.fid_column)?);
+        }
+        self.number_matched += 1;
+        // Without filters, the number of matching features is known from the index
+        let complete = self.limit > 0 && self.number_matched >= self.offset + self.limit;
+        Ok(!(complete && !has_filters && self.features_count.is_some()))
+    }
+
+    fn result(self) -> ItemsResult {
+        let number_matched = match self.features_count {
+            Some(cnt) if self.ids.is_none() && self.filters.is_empty() => cnt,
+            _ => self.number_matched,
+        };
+        ItemsResult {
+            number_returned: self.features.len() as u64,
+            features: self.features,
+            number_matched,
+            paging: Paging::Offset,
+        }
+    }
+}
+
+#[async_trait]
+impl CollectionSource for FlatgeobufCollectionSource {
+    async fn items(&self, filter: &FilterParams) -> Result<ItemsResult> {
+        let bbox = match filter.bbox() {
+            Ok(Some(bbox)) => {
+                unsupported_param("bbox-crs", filter.bbox_crs.is_some())?;
+                if bbox.len() == 6 {
+                    Some([bbox[0], bbox[1], bbox[3], bbox[4]])
+                } else {
+                    Some([bbox[0], bbox[1], bbox[2], bbox[3]])
+                }
+            }
+            Ok(None) => None,
+            Err(e) => {
+                error!("Ignoring invalid bbox: {e}");
+                return Err(Error::QueryParams);
+            }
+        };
+        let mut filters = Vec::new();
+        for (key, val) in &filter.filters {
+            let Some(k) = self
+                .other_columns
+                .keys()
+                .find(|k| k.eq_ignore_ascii_case(key))
+            else {
+                error!("Invalid query param {key}");
+                return Err(Error::QueryParams);
+            };
+            filters.push((k.clone(), val.clone()));
+        }
+        let ids = filter.ids();
+        if ids.is_some() && self.fid_column.is_none() {
+            error!("Filtering by ids requires `fid_field`");
+            return Err(Error::QueryParams);
+        }
+        unsupported_param("intersects", filter.intersects.is_some())?;
+        unsupported_param("datetime", filter.datetime.is_some())?;
+        unsupported_param("crs", filter.crs.is_some())?;
+        unsupported_param("filter", filter.filter.is_some())?;
+        self.query(
+            bbox,
+            ids,
+            filters,
+            filter.offset.unwrap_or(0) as u64,
+            filter.limit_or_default() as u64,
+        )
+        .await
+    }
+
+    async fn item(
+        &self,
+        collection_id: &str,
+        feature_id: &str,
+        crs: Option<&Crs>,
+    ) -> Result<Option<CoreFeature>> {
+        unsupported_param("crs", crs.is_some())?;
+        if self.fid_column.is_none() {
+            warn!("Ignoring error getting item for {collection_id} without `fid_field`");
+            return Ok(None);
+        }
+        let result = self
+            .query(None, Some(vec![feature_id.to_string()]), Vec::new(), 0, 1)
+            .await?;
+        let Some(mut item) = result.features.into_iter().next() else {
+            return Ok(None);
+        };
+        item.links = vec![
+            ApiLink {
+                href: format!("/collections/{collection_id}/items/{feature_id}"),
+                rel: Some("self".to_string()),
+                type_: Some("application/geo+json".to_string()),
+                title: Some("this document".to_string()),
+                hreflang: None,
+                length: None,
+            },
+            ApiLink {
+                href: format!("/collections/{collection_id}"),
+                rel: Some("collection".to_string()),
+                type_: Some("application/geo+json".to_string()),
+                title: Some("the collection document".to_string()),
+                hreflang: None,
+                length: None,
+            },
+        ];
+        Ok(Some(item))
+    }
+
+    async fn queryables(&self, collection_id: &str) -> Result<Option<Queryables>> {
+        let properties: HashMap<String, QueryableProperty> = self
+            .other_columns
+            .iter()
+            .map(|(name, type_)| {
+                (
+                    name.clone(),
+                    QueryableProperty {
+                        title: Some(name.clone()),
+                        type_: Some(type_.clone()),
+                        format: None,
+                        enum_: None,
+                        minimum: None,
+                        maximum: None,
+                    },
+                )
+            })
+            .collect();
+        Ok(Some(Queryables {
+            id: format!("/collections/{collection_id}/queryables"),
+            title: Some(collection_id.to_string()),
+            schema: "http://json-schema.org/draft/2019-09/schema".to_string(),
+            type_: "object".to_string(),
+            properties,
+        }))
+    }
+}
+
+fn is_url(path: &str) -> bool {
+    path.starts_with("http://") || path.starts_with("https://")
+}
+
+fn queryable_type(field: &str, column_type: ColumnType) -> Result<QueryableType> {
+    let queryable_type = match column_type {
+        ColumnType::String => QueryableType::String,
+        ColumnType::Byte
+        | ColumnType::UByte
+        | ColumnType::Short
+        | ColumnType::UShort
+        | ColumnType::Int
+        | ColumnType::UInt
+        | ColumnType::Long
+        | ColumnType::ULong => QueryableType::Integer,
+        ColumnType::Float | ColumnType::Double => QueryableType::Number,
+        ColumnType::Bool => QueryableType::Bool,
+        ColumnType::DateTime => QueryableType::Datetime,
+        ty => {
+            return Err(Error::DatasourceSetupError(format!(
+                "{field} has a FlatGeobuf type {ty:?} which is not currently handled and can't be used a queryable"
+            )))
+        }
+    };
+    Ok(queryable_type)
+}
+
+fn fgb_to_feature(feature: &FgbFeature, fid_column: Option<&str>) -> Result<CoreFeature> {
+    let mut json = Vec::new();
+    let mut writer = GeoJsonWriter::new(&mut json);
+    feature.process(&mut writer, 0)?;
+    let mut geojson: Value = serde_json::from_slice(&json)?;
+    let mut properties = geojson["properties"].take();
+    let id = fid_column
+        .and_then(|fid| properties.as_object_mut()?.remove(fid))
+        .map(|id| match id {
+            Value::String(s) => s,
+            v => v.to_string(),
+        });
+    Ok(CoreFeature {
+        type_: "Feature".to_string(),
+        bbox: None,
+        id,
+        geometry: geojson["geometry"].take(),
+        properties: Some(properties),
+        links: vec![],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output::flatgeobuf;
+    use futures::{stream, StreamExt};
+    use serde_json::json;
+    use std::io::Write;
+
+    async fn places_fgb() -> tempfile::NamedTempFile {
+        let places = [
+            ("Bern", 7.44, 46.95),
+            ("Zurich", 8.54, 47.37),
+            ("Paris", 2.35, 48.86),
+        ];
+        let features = places
+            .iter()
+            .enumerate()
+            .map(|(i, (name, x, y))| {
+                Ok(CoreFeature {
+                    type_: "Feature".to_string(),
+                    bbox: None,
+                    id: None,
+                    geometry: json!({"type": "Point", "coordinates": [x, y]}),
+                    properties: Some(json!({"fid": i + 1, "name": name})),
+                    links: vec![],
+                })
+            })
+            .collect::<Vec<_>>();
+        let buf = flatgeobuf("places", stream::iter(features).boxed())
+            .await
+            .unwrap();
+        let mut file = tempfile::Builder::new().suffix(".fgb").tempfile().unwrap();
+        file.write_all(&buf).unwrap();
+        file
+    }
+
+    #[tokio::test]
+    async fn fgb_filters() {
+        let file = places_fgb().await;
+        let cfg = ConfiguredCollectionCfg {
+            source: CollectionSourceCfg::Flatgeobuf(FlatgeobufCollectionCfg {
+                path: file.path().to_string_lossy().to_string(),
+                fid_field: Some("fid".to_string()),
+                queryable_fields: vec!["name".to_string()],
+            }),
+            name: "places".to_string(),
+            title: None,
+            description: None,
+            attribution: None,
+            license: None,
+            downloads: Vec::new(),
+        };
+        let fc = FlatgeobufCollectionSource::setup_collection(&cfg)
+            .await
+            .unwrap();
+        let source = fc.source;
+
+        let items = source.items(&FilterParams::default()).await.unwrap();
+        assert_eq!(items.number_matched, 3);
+
+        let filter = FilterParams {
+            bbox: Some("5.6,45.8,10.9,47.6".to_string()),
+            ..Default::default()
+        };
+        let items = source.items(&filter).await.unwrap();
+        assert_eq!(items.number_matched, 2);
+
+        let filter = FilterParams {
+            filters: [("NAME".to_string(), "Paris".to_string())].into(),
+            ..Default::default()
+        };
+        let items = source.items(&filter).await.unwrap();
+        assert_eq!(items.number_matched, 1);
+        assert_eq!(items.features[0].id.as_deref(), Some("3"));
+
+        let item = source.item("places", "2", None).await.unwrap().unwrap();
+        assert_eq!(item.properties.unwrap()["name"], "Zurich");
+        assert!(source.item("places", "4", None).await.unwrap().is_none());
+
+        let filter = FilterParams {
+            filter: Some("name = 'Paris'".to_string()),
+            ..Default::default()
+        };
+        assert!(matches!(
+            source.items(&filter).await,
+            Err(Error::UnsupportedParam("filter"))
+        ));
+    }
+}
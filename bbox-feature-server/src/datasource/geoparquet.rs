@@ -0,0 +1,688 @@
+//! GeoParquet feature source.
+
+use crate::config::GeoparquetCollectionCfg;
+use crate::crs::Crs;
+use crate::datasource::gpkg::geojson_bbox;
+use crate::datasource::{
+    unsupported_param, CollectionSource, CollectionSourceCfg, ConfiguredCollectionCfg, ItemsResult,
+    Paging,
+};
+use crate::error::{Error, Result};
+use crate::filter_params::FilterParams;
+use crate::inventory::FeatureCollection;
+use arrow_array::cast::AsArray;
+use arrow_array::types::Float64Type;
+use arrow_array::{Array, ArrayRef, RecordBatch};
+use arrow_cast::display::{ArrayFormatter, FormatOptions};
+use arrow_schema::{DataType, Schema};
+use async_trait::async_trait;
+use bbox_core::ogcapi::*;
+use futures::TryStreamExt;
+use geozero::{wkb::Wkb, ToJson};
+use log::{debug, error, info, warn};
+use object_store::{aws::AmazonS3Builder, local::LocalFileSystem, path::Path, ObjectStore};
+use parquet::arrow::async_reader::{ParquetObjectReader, ParquetRecordBatchStreamBuilder};
+use parquet::file::metadata::RowGroupMetaData;
+use parquet::file::statistics::Statistics;
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// GeoParquet file metadata (`geo` key)
+#[derive(Deserialize, Debug)]
+struct GeoMetadata {
+    primary_column: String,
+    columns: HashMap<String, GeoColumnMetadata>,
+}
+
+#[derive(Deserialize, Debug)]
+struct GeoColumnMetadata {
+    encoding: String,
+    #[serde(default)]
+    bbox: Vec<f64>,
+    covering: Option<GeoCovering>,
+}
+
+#[derive(Deserialize, Debug)]
+struct GeoCovering {
+    bbox: BboxCovering,
+}
+
+/// Column paths of bbox covering (e.g. `["bbox", "xmin"]`)
+#[derive(Deserialize, Clone, Debug)]
+struct BboxCovering {
+    xmin: Vec<String>,
+    ymin: Vec<String>,
+    xmax: Vec<String>,
+    ymax: Vec<String>,
+}
+
+#[derive(Clone, Debug)]
+pub struct GeoparquetCollectionSource {
+    store: Arc<dyn ObjectStore>,
+    location: Path,
+    geometry_column: String,
+    fid_column: Option<String>,
+    covering: Option<BboxCovering>,
+    /// Queryable fields
+    other_columns: HashMap<String, QueryableType>,
+}
+
+impl GeoparquetCollectionSource {
+    pub async fn setup_collection(cfg: &ConfiguredCollectionCfg) -> Result<FeatureCollection> {
+        info!("Setup GeoParquet Collection `{}`", &cfg.name);
+        let CollectionSourceCfg::Geoparquet(ref srccfg) = cfg.source else {
+            panic!();
+        };
+        let id = &cfg.name;
+        let (store, location) = object_store(srccfg)?;
+        let reader = ParquetObjectReader::new(store.clone(), store.head(&location).await?);
+        let builder = ParquetRecordBatchStreamBuilder::new(reader).await?;
+        let geo = geo_metadata(builder.metadata().file_metadata().key_value_metadata()).ok_or(
+            Error::DatasourceSetupError(format!("Datasource `{id}`: GeoParquet metadata missing")),
+        )?;
+        let geometry_column = srccfg
+            .geometry_field
+            .clone()
+            .unwrap_or(geo.primary_column.clone());
+        let Some(column_meta) = geo.columns.get(&geometry_column) else {
+            return Err(Error::DatasourceSetupError(format!(
+                "Datasource `{id}`: geometry column `{geometry_column}` not found"
+            )));
+        };
+        if !column_meta.encoding.eq_ignore_ascii_case("WKB") {
+            return Err(Error::DatasourceSetupError(format!(
+                "Datasource `{id}`: unsupported geometry encoding `{}`",
+                column_meta.encoding
+            )));
+        }
+        let covering = column_meta.covering.as_ref().map(|c| c.bbox.clone());
+        if covering.is_none() {
+            info!("Datasource `{id}`: no bbox covering column - bbox filters will be ignored");
+        }
+        if srccfg.fid_field.is_none() {
+            warn!("Datasource `{id}`: `fid_field` missing - single item queries will be ignored");
+        }
+        let other_columns = get_column_info(builder.schema(), &srccfg.queryable_fields)?;
+        let extent = match column_meta.bbox.as_slice() {
+            [xmin, ymin, xmax, ymax] | [xmin, ymin, _, xmax, ymax, _] => Some(CoreExtent {
+                spatial: Some(CoreExtentSpatial {
+                    bbox: vec![vec![*xmin, *ymin, *xmax, *ymax]],
+                    crs: None,
+                }),
+                temporal: None,
+            }),
+            _ => None,
+        };
+        let source = GeoparquetCollectionSource {
+            store,
+            location,
+            geometry_column,
+            fid_column: srccfg.fid_field.clone(),
+            covering,
+            other_columns,
+        };
+
+        let collection = CoreCollection {
+            id: id.clone(),
+            title: cfg.title.clone(),
+            description: cfg.description.clone(),
+            attribution: cfg.attribution.clone(),
+            license: cfg.license.clone(),
+            extent,
+            item_type: None,
+            crs: vec![],
+            summaries: None,
+            links: vec![ApiLink {
+                href: format!("/collections/{id}/items"),
+                rel: Some("items".to_string()),
+                type_: Some("application/geo+json".to_string()),
+                title: cfg.title.clone(),
+                hreflang: None,
+                length: None,
+            }],
+        };
+        Ok(FeatureCollection {
+            collection,
+            source: Box::new(source),
+        })
+    }
+
+    /// Scan row groups intersecting `bbox` and return features matching all filters
+    async fn query(
+        &self,
+        bbox: Option<[f64; 4]>,
+        ids: Option<Vec<String>>,
+        filters: &[(String, String)],
+        offset: u64,
+        limit: u64,
+    ) -> Result<ItemsResult> {
+        let reader =
+            ParquetObjectReader::new(self.store.clone(), self.store.head(&self.location).await?);
+        let mut builder = ParquetRecordBatchStreamBuilder::new(reader).await?;
+        if let (Some(bbox), Some(covering)) = (bbox, &self.covering) {
+            let row_groups: Vec<usize> = builder
+                .metadata()
+                .row_groups()
+                .iter()
+                .enumerate()
+                .filter(|(_, rg)| row_group_intersects(rg, covering, &bbox))
+                .map(|(i, _)| i)
+                .collect();
+            debug!(
+                "Reading {} of {} row groups",
+                row_groups.len(),
+                builder.metadata().num_row_groups()
+            );
+            builder = builder.with_row_groups(row_groups);
+        }
+        let mut stream = builder.build()?;
+
+        let mut features = Vec::new();
+        let mut number_matched = 0;
+        while let Some(batch) = stream.try_next().await? {
+            let mut matching = vec![true; batch.num_rows()];
+            if let (Some(bbox), Some(covering)) = (bbox, &self.covering) {
+                filter_bbox(&batch, covering, &bbox, &mut matching)?;
+            }
+            if let Some(ids) = &ids {
+                let fid = self.fid_column.as_ref().ok_or(Error::QueryParams)?;
+                filter_values(&batch, fid, |v| ids.iter().any(|id| id == v), &mut matching)?;
+            }
+            for (field, value) in filters {
+                filter_values(&batch, field, |v| v == value, &mut matching)?;
+            }
+            for (row, _) in matching.iter().enumerate().filter(|(_, m)| **m) {
+                if number_matched >= offset && (limit == 0 || number_matched < offset + limit) {
+                    features.push(self.row_to_feature(&batch, row)?);
+                }
+                number_matched += 1;
+            }
+        }
+        let number_returned = features.len() as u64;
+        Ok(ItemsResult {
+            features,
+            number_matched,
+            number_returned,
+            paging: Paging::Offset,
+        })
+    }
+
+    fn row_to_feature(&self, batch: &RecordBatch, row: usize) -> Result<CoreFeature> {
+        let schema = batch.schema();
+        let covering_column = self.covering.as_ref().map(|c| &c.xmin[0]);
+        let mut id = None;
+        let mut property_columns = Vec::new();
+        for (i, field) in schema.fields().iter().enumerate() {
+            if field.name() == &self.geometry_column || Some(field.name()) == covering_column {
+                // Skip geometry and bbox
+            } else if Some(field.name()) == self.fid_column.as_ref() {
+                let fid = batch.column(i);
+                if fid.is_valid(row) {
+                    id = Some(formatted_value(fid, row)?);
+                }
+            } else {
+                property_columns.push(i);
+            }
+        }
+        let property_row = batch.project(&property_columns)?.slice(row, 1);
+        let properties = arrow_json::writer::record_batches_to_json_rows(&[&property_row])?
+            .pop()
+            .unwrap_or_default();
+
+        let geometry = match batch.column_by_name(&self.geometry_column) {
+            Some(col) if col.is_valid(row) => {
+                let wkb = binary_value(col, row).ok_or(Error::GeometryFormatError)?;
+                let json = Wkb(wkb.to_vec())
+                    .to_json()
+                    .map_err(|_| Error::GeometryFormatError)?;
+                serde_json::from_str(&json).map_err(|_| Error::GeometryFormatError)?
+            }
+            _ => Value::Null,
+        };
+        Ok(CoreFeature {
+            type_: "Feature".to_string(),
+            bbox: None,
+            id,
+            geometry,
+            properties: Some(Value::Object(properties)),
+            links: vec![],
+        })
+    }
+}
+
+#[async_trait]
+impl CollectionSource for GeoparquetCollectionSource {
+    async fn items(&self, filter: &FilterParams) -> Result<ItemsResult> {
+        let mut bbox = match filter.bbox() {
+            Ok(Some(bbox)) => {
+                unsupported_param("bbox-crs", filter.bbox_crs.is_some())?;
+                if bbox.len() == 6 {
+                    Some([bbox[0], bbox[1], bbox[3], bbox[4]])
+                } else {
+                    Some([bbox[0], bbox[1], bbox[2], bbox[3]])
+                }
+            }
+            Ok(None) => None,
+            Err(e) => {
+                error!("Ignoring invalid bbox: {e}");
+                return Err(Error::QueryParams);
+            }
+        };
+        if let Some(geojson) = &filter.intersects {
+            // Envelope intersection only
+            let geom: Value = serde_json::from_str(geojson).map_err(|_| Error::QueryParams)?;
+            let envelope = geojson_bbox(&geom).ok_or(Error::QueryParams)?;
+            bbox = Some(match bbox {
+                Some(b) => [
+                    b[0].max(envelope[0]),
+                    b[1].max(envelope[1]),
+                    b[2].min(envelope[2]),
+                    b[3].min(envelope[3]),
+                ],
+                None => envelope,
+            });
+        }
+        if bbox.is_some() && self.covering.is_none() {
+            warn!("Ignoring bbox filter (no bbox covering column)");
+        }
+        let mut filters = Vec::new();
+        for (key, val) in &filter.filters {
+            let Some(k) = self
+                .other_columns
+                .keys()
+                .find(|k| k.eq_ignore_ascii_case(key))
+            else {
+                error!("Invalid query param {key}");
+                return Err(Error::QueryParams);
+            };
+            filters.push((k.clone(), val.clone()));
+        }
+        let ids = filter.ids();
+        if ids.is_some() && self.fid_column.is_none() {
+            error!("Filtering by ids requires `fid_field`");
+            return Err(Error::QueryParams);
+        }
+        unsupported_param("datetime", filter.datetime.is_some())?;
+        unsupported_param("crs", filter.crs.is_some())?;
+        unsupported_param("filter", filter.filter.is_some())?;
+        self.query(
+            bbox,
+            ids,
+            &filters,
+            filter.offset.unwrap_or(0) as u64,
+            filter.limit_or_default() as u64,
+        )
+        .await
+    }
+
+    async fn item(
+        &self,
+        collection_id: &str,
+        feature_id: &str,
+        crs: Option<&Crs>,
+    ) -> Result<Option<CoreFeature>> {
+        unsupported_param("crs", crs.is_some())?;
+        if self.fid_column.is_none() {
+            warn!("Ignoring error getting item for {collection_id} without `fid_field`");
+            return Ok(None);
+        }
+        let result = self
+            .query(None, Some(vec![feature_id.to_string()]), &[], 0, 1)
+            .await?;
+        let Some(mut item) = result.features.into_iter().next() else {
+            return Ok(None);
+        };
+        item.links = vec![
+            ApiLink {
+                href: format!("/collections/{collection_id}/items/{feature_id}"),
+                rel: Some("self".to_string()),
+                type_: Some("application/geo+json".to_string()),
+                title: Some("this document".to_string()),
+                hreflang: None,
+                length: None,
+            },
+            ApiLink {
+                href: format!("/collections/{collection_id}"),
+                rel: Some("collection".to_string()),
+                type_: Some("application/geo+json".to_string()),
+                title: Some("the collection document".to_string()),
+                hreflang: None,
+                length: None,
+            },
+        ];
+        Ok(Some(item))
+    }
+
+    async fn queryables(&self, collection_id: &str) -> Result<Option<Queryables>> {
+        let properties: HashMap<String, QueryableProperty> = self
+            .other_columns
+            .iter()
+            .map(|(name, type_)| {
+                (
+                    name.clone(),
+                    QueryableProperty {
+                        title: Some(name.clone()),
+                        type_: Some(type_.clone()),
+                        format: None,
+                        enum_: None,
+                        minimum: None,
+                        maximum: None,
+                    },
+                )
+            })
+            .collect();
+        Ok(Some(Queryables {
+            id: format!("/collections/{collection_id}/queryables"),
+            title: Some(collection_id.to_string()),
+            schema: "http://json-schema.org/draft/2019-09/schema".to_string(),
+            type_: "object".to_string(),
+            properties,
+        }))
+    }
+}
+
+/// Object store and location of a local file or S3 object.
+/// S3 credentials and region are read from the `AWS_*` environment variables.
+fn object_store(cfg: &GeoparquetCollectionCfg) -> Result<(Arc<dyn ObjectStore>, Path)> {
+    if cfg.path.starts_with("s3://") {
+        let url = url::Url::parse(&cfg.path)
+            .map_err(|e| Error::DatasourceSetupError(format!("Invalid URL `{}`: {e}", cfg.path)))?;
+        let store = AmazonS3Builder::from_env().with_url(&cfg.path).build()?;
+        let location = Path::parse(url.path().trim_start_matches('/'))
+            .map_err(|e| Error::DatasourceSetupError(e.to_string()))?;
+        Ok((Arc::new(store), location))
+    } else {
+        let path = std::fs::canonicalize(&cfg.path)
+            .map_err(|e| Error::DatasourceSetupError(format!("`{}`: {e}", cfg.path)))?;
+        let location = Path::from_filesystem_path(path)
+            .map_err(|e| Error::DatasourceSetupError(e.to_string()))?;
+        Ok((Arc::new(LocalFileSystem::new()), location))
+    }
+}
+
+fn geo_metadata(kv: Option<&Vec<parquet::format::KeyValue>>) -> Option<GeoMetadata> {
+    let json = kv?.iter().find(|kv| kv.key == "geo")?.value.as_ref()?;
+    match serde_json::from_str(json) {
+        Ok(geo) => Some(geo),
+        Err(e) => {
+            warn!("Invalid GeoParquet metadata: {e}");
+            None
+        }
+    }
+}
+
+/// Queryable types of `fields` in schema
+fn get_column_info(schema: &Schema, fields: &[String]) -> Result<HashMap<String, QueryableType>> {
+    let mut types = HashMap::new();
+    for field in fields {
+        let Ok(col) = schema.field_with_name(field) else {
+            return Err(Error::DatasourceSetupError(format!(
+                "Queryable field `{field}` not found"
+            )));
+        };
+        let queryable_type = match col.data_type() {
+            DataType::Utf8 | DataType::LargeUtf8 => QueryableType::String,
+            DataType::Int8
+            | DataType::Int16
+            | DataType::Int32
+            | DataType::Int64
+            | DataType::UInt8
+            | DataType::UInt16
+            | DataType::UInt32
+            | DataType::UInt64 => QueryableType::Integer,
+            DataType::Float16
+            | DataType::Float32
+            | DataType::Float64
+            | DataType::Decimal128(_, _)
+            | DataType::Decimal256(_, _) => QueryableType::Number,
+            DataType::Boolean => QueryableType::Bool,
+            DataType::Date32 | DataType::Date64 | DataType::Timestamp(_, _) => {
+                QueryableType::Datetime
+            }
+            ty => {
+                return Err(Error::DatasourceSetupError(format!(
+                    "{field} has an Arrow type {ty} which is not currently handled and can't be used a queryable"
+                )))
+            }
+        };
+        types.insert(field.clone(), queryable_type);
+    }
+    Ok(types)
+}
+
+/// Check row group statistics of bbox covering columns against `bbox` ([xmin, ymin, xmax, ymax]).
+/// Row groups without statistics are always read.
+fn row_group_intersects(rg: &RowGroupMetaData, covering: &BboxCovering, bbox: &[f64; 4]) -> bool {
+    let min_max = |path: &[String]| {
+        rg.columns()
+            .iter()
+            .find(|col| col.column_path().parts() == path)
+            .and_then(|col| col.statistics())
+            .and_then(stats_min_max)
+    };
+    let disjoint = min_max(&covering.xmin).map_or(false, |(min, _)| min > bbox[2])
+        || min_max(&covering.ymin).map_or(false, |(min, _)| min > bbox[3])
+        || min_max(&covering.xmax).map_or(false, |(_, max)| max < bbox[0])
+        || min_max(&covering.ymax).map_or(false, |(_, max)| max < bbox[1]);
+    !disjoint
+}
+
+fn stats_min_max(stats: &Statistics) -> Option<(f64, f64)> {
+    if !stats.has_min_max_set() {
+        return None;
+    }
+    match stats {
+        Statistics::Double(s) => Some((*s.min(), *s.max())),
+        Statistics::Float(s) => Some((*s.min() as f64, *s.max() as f64)),
+        _ => None,
+    }
+}
+
+/// Unset `matching` for rows with a bbox covering outside of `bbox`
+fn filter_bbox(
+    batch: &RecordBatch,
+    covering: &BboxCovering,
+    bbox: &[f64; 4],
+    matching: &mut [bool],
+) -> Result<()> {
+    let column = |path: &[String]| -> Result<ArrayRef> {
+        let mut col = batch.column_by_name(&path[0]).cloned();
+        for name in &path[1..] {
+            col = col
+                .as_ref()
+                .and_then(|c| c.as_struct_opt())
+                .and_then(|s| s.column_by_name(name))
+                .cloned();
+        }
+        let col = col.ok_or(Error::DatasourceSetupError(format!(
+            "bbox covering column `{}` not found",
+            path.join(".")
+        )))?;
+        Ok(arrow_cast::cast(&col, &DataType::Float64)?)
+    };
+    let xmin = column(&covering.xmin)?;
+    let ymin = column(&covering.ymin)?;
+    let xmax = column(&covering.xmax)?;
+    let ymax = column(&covering.ymax)?;
+    let (xmin, ymin, xmax, ymax) = (
+        xmin.as_primitive::<Float64Type>(),
+        ymin.as_primitive::<Float64Type>(),
+        xmax.as_primitive::<Float64Type>(),
+        ymax.as_primitive::<Float64Type>(),
+    );
+    for (row, m) in matching.iter_mut().enumerate() {
+        if *m
+            && xmin.is_valid(row)
+            && ymin.is_valid(row)
+            && xmax.is_valid(row)
+            && ymax.is_valid(row)
+        {
+            *m = xmin.value(row) <= bbox[2]
+                && ymin.value(row) <= bbox[3]
+                && xmax.value(row) >= bbox[0]
+                && ymax.value(row) >= bbox[1];
+        }
+    }
+    Ok(())
+}
+
+/// Unset `matching` for rows where the formatted value of `field` doesn't satisfy `pred`
+fn filter_values<F: Fn(&str) -> bool>(
+    batch: &RecordBatch,
+    field: &str,
+    pred: F,
+    matching: &mut [bool],
+) -> Result<()> {
+    let col = batch.column_by_name(field).ok_or(Error::QueryParams)?;
+    let formatter = ArrayFormatter::try_new(col.as_ref(), &FormatOptions::default())?;
+    for (row, m) in matching.iter_mut().enumerate() {
+        if *m {
+            *m = col.is_valid(row) && pred(&formatter.value(row).to_string());
+        }
+    }
+    Ok(())
+}
+
+fn formatted_value(col: &ArrayRef, row: usize) -> Result<String> {
+    let formatter = ArrayFormatter::try_new(col.as_ref(), &FormatOptions::default())?;
+    Ok(formatter.value(row).to_string())
+}
+
+fn binary_value(col: &ArrayRef, row: usize) -> Option<&[u8]> {
+    if let Some(values) = col.as_binary_opt::<i32>() {
+        Some(values.value(row))
+    } else {
+        col.as_binary_opt::<i64>().map(|values| values.value(row))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow_array::{Float64Array, StructArray};
+    use arrow_schema::{Field, Fields};
+    use parquet::basic::Type as PhysicalType;
+    use parquet::file::metadata::ColumnChunkMetaData;
+    use parquet::schema::types::{SchemaDescriptor, Type};
+
+    fn covering() -> BboxCovering {
+        let path = |name: &str| vec!["bbox".to_string(), name.to_string()];
+        BboxCovering {
+            xmin: path("xmin"),
+            ymin: path("ymin"),
+            xmax: path("xmax"),
+            ymax: path("ymax"),
+        }
+    }
+
+    #[test]
+    fn geoparquet_metadata() {
+        let kv = vec![parquet::format::KeyValue {
+            key: "geo".to_string(),
+            value: Some(
+                r#"{"version":"1.1.0","primary_column":"geometry","columns":{"geometry":{
+                    "encoding":"WKB","geometry_types":["Point"],"bbox":[-180,-90,180,83.6],
+                    "covering":{"bbox":{"xmin":["bbox","xmin"],"ymin":["bbox","ymin"],
+                    "xmax":["bbox","xmax"],"ymax":["bbox","ymax"]}}}}}"#
+                    .to_string(),
+            ),
+        }];
+        let geo = geo_metadata(Some(&kv)).unwrap();
+        assert_eq!(geo.primary_column, "geometry");
+        let column = &geo.columns["geometry"];
+        assert_eq!(column.bbox, vec![-180.0, -90.0, 180.0, 83.6]);
+        assert_eq!(
+            column.covering.as_ref().unwrap().bbox.ymax,
+            vec!["bbox", "ymax"]
+        );
+        assert!(geo_metadata(None).is_none());
+    }
+
+    #[test]
+    fn row_group_statistics() {
+        let field = |name: &str| {
+            Arc::new(
+                Type::primitive_type_builder(name, PhysicalType::DOUBLE)
+                    .build()
+                    .unwrap(),
+            )
+        };
+        let bbox_group = Type::group_type_builder("bbox")
+            .with_fields(vec![
+                field("xmin"),
+                field("ymin"),
+                field("xmax"),
+                field("ymax"),
+            ])
+            .build()
+            .unwrap();
+        let schema = Type::group_type_builder("schema")
+            .with_fields(vec![Arc::new(bbox_group)])
+            .build()
+            .unwrap();
+        let descr = Arc::new(SchemaDescriptor::new(Arc::new(schema)));
+        // Row group with features within [5, 45, 11, 48]
+        let stats = [(5.0, 10.0), (45.0, 47.0), (6.0, 11.0), (46.0, 48.0)];
+        let columns = stats
+            .iter()
+            .enumerate()
+            .map(|(i, (min, max))| {
+                ColumnChunkMetaData::builder(descr.column(i))
+                    .set_statistics(Statistics::double(Some(*min), Some(*max), None, 0, false))
+                    .build()
+                    .unwrap()
+            })
+            .collect();
+        let rg = RowGroupMetaData::builder(descr)
+            .set_column_metadata(columns)
+            .build()
+            .unwrap();
+
+        assert!(row_group_intersects(
+            &rg,
+            &covering(),
+            &[7.0, 46.0, 8.0, 47.0]
+        ));
+        assert!(row_group_intersects(
+            &rg,
+            &covering(),
+            &[0.0, 0.0, 5.0, 45.0]
+        ));
+        assert!(!row_group_intersects(
+            &rg,
+            &covering(),
+            &[12.0, 46.0, 13.0, 47.0]
+        ));
+        assert!(!row_group_intersects(
+            &rg,
+            &covering(),
+            &[7.0, 48.5, 8.0, 49.0]
+        ));
+    }
+
+    #[test]
+    fn bbox_covering_filter() {
+        let values = |v: &[f64]| Arc::new(Float64Array::from(v.to_vec())) as ArrayRef;
+        let fields = Fields::from(
+            ["xmin", "ymin", "xmax", "ymax"]
+                .map(|name| Field::new(name, DataType::Float64, true))
+                .to_vec(),
+        );
+        let bbox = StructArray::new(
+            fields,
+            vec![
+                values(&[7.0, 20.0, 0.0]),
+                values(&[46.0, 46.0, 40.0]),
+                values(&[7.5, 21.0, 10.0]),
+                values(&[47.0, 47.0, 50.0]),
+            ],
+            None,
+        );
+        let batch = RecordBatch::try_from_iter(vec![("bbox", Arc::new(bbox) as ArrayRef)]).unwrap();
+        let mut matching = vec![true; 3];
+        filter_bbox(&batch, &covering(), &[6.0, 45.0, 8.0, 48.0], &mut matching).unwrap();
+        assert_eq!(matching, vec![true, false, true]);
+    }
+}
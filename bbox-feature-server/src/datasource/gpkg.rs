@@ -1,22 +1,26 @@
-//! GeoPackage feature source.
+//! GeoPackage, SpatiaLite and SQLite feature source.
 
-use crate::config::GpkgCollectionCfg;
+use crate::config::{GeometryFormat, GpkgCollectionCfg};
+use crate::crs::Crs;
 use crate::datasource::{
-    AutoscanCollectionDatasource, CollectionDatasource, CollectionSource, CollectionSourceCfg,
-    ConfiguredCollectionCfg, ItemsResult,
+    unsupported_param, AutoscanCollectionDatasource, CollectionDatasource, CollectionSource,
+    CollectionSourceCfg, ConfiguredCollectionCfg, ItemsResult, Paging,
 };
 use crate::error::{self, Error, Result};
-use crate::filter_params::FilterParams;
+use crate::filter_params::{FilterParams, TemporalType};
 use crate::inventory::FeatureCollection;
 use async_trait::async_trait;
 use bbox_core::config::DsGpkgCfg;
 use bbox_core::ogcapi::*;
+use bbox_core::sql::SqlIdent;
 use futures::TryStreamExt;
-use geozero::{geojson, wkb};
+use geozero::{geojson, wkb, wkt, ToJson};
 use log::{debug, error, info, warn};
-use serde_json::json;
+use serde_json::{json, Value};
 use sqlx::sqlite::{SqliteConnectOptions, SqlitePool, SqlitePoolOptions, SqliteRow};
-use sqlx::{Column, Row, TypeInfo};
+use sqlx::{Column, QueryBuilder, Row, Sqlite, TypeInfo};
+use std::collections::HashMap;
+use std::time::Duration;
 
 #[derive(Clone, Debug)]
 pub struct SqliteDatasource {
@@ -36,6 +40,21 @@ impl SqliteDatasource {
             .await?;
         Ok(SqliteDatasource { pool })
     }
+    /// Create pool without opening the file. Connections are established on first use.
+    pub fn new_lazy_pool(gpkg: &str) -> Self {
+        let conn_options = SqliteConnectOptions::new().filename(gpkg).read_only(true);
+        let pool = SqlitePoolOptions::new()
+            .min_connections(0)
+            .max_connections(8)
+            .acquire_timeout(Duration::from_secs(5))
+            .connect_lazy_with(conn_options);
+        SqliteDatasource { pool }
+    }
+    /// Check database connectivity
+    pub async fn check_connection(&self) -> Result<()> {
+        sqlx::query("SELECT 1").execute(&self.pool).await?;
+        Ok(())
+    }
 }
 
 pub type Datasource = SqliteDatasource;
@@ -60,14 +79,27 @@ impl CollectionDatasource for SqliteDatasource {
         } else if srccfg.table_name.is_some() && srccfg.sql.is_some() {
             warn!("Datasource`{id}`: configuration `table_name` ignored, using `sql` instead");
         }
-        let (pk_column, geometry_column, sql) = if let Some(table_name) = &srccfg.table_name {
+        let default_format = detect_geometry_format(self).await?;
+        let (pk_column, geometry_column, geometry_format, sql) = if let Some(table_name) =
+            &srccfg.table_name
+        {
             let pk_column = srccfg
                 .fid_field
                 .clone()
                 .or(detect_pk(self, table_name).await?);
-            let geometry_column = detect_geometry(self, table_name).await?;
-            let sql = check_query(self, format!("SELECT * FROM {table_name}")).await?;
-            (pk_column, geometry_column, sql)
+            let geometry_column = if let Some(field) = &srccfg.geometry_field {
+                field.clone()
+            } else {
+                detect_geometry(self, table_name, default_format)
+                    .await?
+                    .ok_or(Error::DatasourceSetupError(format!(
+                        "Datasource `{id}`: no geometry metadata - configuration `geometry_field` missing"
+                    )))?
+            };
+            let geometry_format = srccfg.geometry_format.unwrap_or(default_format);
+            let sql =
+                check_query(self, format!("SELECT * FROM {}", quote_ident(table_name)?)).await?;
+            (pk_column, geometry_column, geometry_format, sql)
         } else {
             let pk_column = srccfg.fid_field.clone();
             // TODO: We should also allow user queries without geometry
@@ -78,26 +110,54 @@ impl CollectionDatasource for SqliteDatasource {
                     .ok_or(Error::DatasourceSetupError(format!(
                         "Datasource `{id}`: configuration `geometry_field` missing"
                     )))?;
+            let geometry_format = srccfg.geometry_format.unwrap_or(default_format);
             let sql = check_query(self, srccfg.sql.clone().expect("config checked")).await?;
-            (pk_column, geometry_column, sql)
+            (pk_column, geometry_column, geometry_format, sql)
         };
         if pk_column.is_none() {
             warn!("Datasource `{id}`: `fid_field` missing - single item queries will be ignored");
         }
+        let rtree_table = match (&srccfg.table_name, &srccfg.sql, &pk_column) {
+            (Some(table_name), None, Some(_)) => {
+                detect_rtree(self, table_name, &geometry_column, geometry_format).await?
+            }
+            _ => None,
+        };
+        if rtree_table.is_none() {
+            info!("Datasource `{id}`: no R-tree spatial index - bbox filters will be ignored");
+        }
+        let temporal_column = srccfg.temporal_field.clone();
+        let temporal_end_column = srccfg.temporal_end_field.clone();
+        let mut queryable_fields = srccfg.queryable_fields.clone();
+        if let Some(ref t) = temporal_column {
+            queryable_fields.push(t.clone());
+        }
+        if let Some(ref t) = temporal_end_column {
+            queryable_fields.push(t.clone());
+        }
+        let other_columns = get_column_info(self, &sql, &queryable_fields).await?;
         let source = GpkgCollectionSource {
             ds: self.clone(),
             sql,
             geometry_column,
+            geometry_format,
             pk_column,
+            rtree_table,
+            temporal_column,
+            temporal_end_column,
+            other_columns,
         };
 
         let collection = CoreCollection {
             id: id.clone(),
             title: cfg.title.clone(),
             description: cfg.description.clone(),
+            attribution: cfg.attribution.clone(),
+            license: cfg.license.clone(),
             extent,
             item_type: None,
             crs: vec![],
+            summaries: None,
             links: vec![ApiLink {
                 href: format!("/collections/{id}/items"),
                 rel: Some("items".to_string()),
@@ -151,6 +211,9 @@ impl AutoscanCollectionDatasource for SqliteDatasource {
                 name: id.clone(),
                 title: Some(title),
                 description: row.try_get("description")?,
+                attribution: None,
+                license: None,
+                downloads: Vec::new(),
             };
             let fc = self.setup_collection(&coll_cfg, Some(extent)).await?;
             collections.push(fc);
@@ -164,31 +227,184 @@ pub struct GpkgCollectionSource {
     ds: SqliteDatasource,
     sql: String,
     geometry_column: String,
+    geometry_format: GeometryFormat,
     // geometry_type_name: String,
     /// Primary key column, None if multi column key.
     pk_column: Option<String>,
+    /// R-tree spatial index table
+    /// (`rtree_<table>_<geometry column>`, SpatiaLite: `idx_<table>_<geometry column>`)
+    rtree_table: Option<String>,
+    temporal_column: Option<String>,
+    temporal_end_column: Option<String>,
+    /// Queryable fields
+    other_columns: HashMap<String, QueryableType>,
 }
 
 #[async_trait]
 impl CollectionSource for GpkgCollectionSource {
     async fn items(&self, filter: &FilterParams) -> Result<ItemsResult> {
-        let mut sql = format!(
+        let mut builder: QueryBuilder<Sqlite> = QueryBuilder::new(format!(
             "
             WITH query AS ({sql})
             SELECT *, count(*) OVER() AS __total_cnt FROM query",
             sql = &self.sql
-        );
-        if let Some(_bboxstr) = &filter.bbox {
-            warn!("Ignoring bbox filter (not supported for this datasource)");
+        ));
+        let mut where_term = false;
+        match filter.bbox() {
+            Ok(Some(bbox)) => {
+                if let (Some(rtree), Some(pk)) = (&self.rtree_table, &self.pk_column) {
+                    unsupported_param("bbox-crs", filter.bbox_crs.is_some())?;
+                    push_where(&mut builder, &mut where_term);
+                    push_rtree_filter(
+                        &mut builder,
+                        rtree,
+                        &quote_ident(pk)?,
+                        [bbox[0], bbox[1], bbox[2], bbox[3]],
+                        self.geometry_format,
+                    );
+                } else {
+                    warn!("Ignoring bbox filter (no spatial index)");
+                }
+            }
+            Ok(None) => {}
+            Err(e) => {
+                error!("Ignoring invalid bbox: {e}");
+                return Err(Error::QueryParams);
+            }
+        }
+        if let Some(temporal_column) = &self.temporal_column {
+            let temporal_end_column = self.temporal_end_column.as_ref().unwrap_or(temporal_column);
+            let temporal_column = quote_ident(temporal_column)?;
+            let temporal_end_column = quote_ident(temporal_end_column)?;
+            match filter.temporal() {
+                Ok(Some(parts)) => {
+                    // GeoPackage stores datetimes as ISO 8601 text
+                    let iso = |dt: &chrono::DateTime<chrono::FixedOffset>| {
+                        dt.to_rfc3339_opts(chrono::SecondsFormat::Millis, true)
+                    };
+                    match parts.as_slice() {
+                        [TemporalType::DateTime(dt)] => {
+                            push_where(&mut builder, &mut where_term);
+                            builder.push(format!(" julianday({temporal_column}) = julianday("));
+                            builder.push_bind(iso(dt));
+                            builder.push(")");
+                        }
+                        [TemporalType::Open, TemporalType::DateTime(dt)] => {
+                            push_where(&mut builder, &mut where_term);
+                            builder.push(format!(" julianday({temporal_column}) <= julianday("));
+                            builder.push_bind(iso(dt));
+                            builder.push(")");
+                        }
+                        [TemporalType::DateTime(dt), TemporalType::Open] => {
+                            push_where(&mut builder, &mut where_term);
+                            builder.push(format!(" julianday({temporal_column}) >= julianday("));
+                            builder.push_bind(iso(dt));
+                            builder.push(")");
+                        }
+                        [TemporalType::DateTime(dt1), TemporalType::DateTime(dt2)] => {
+                            push_where(&mut builder, &mut where_term);
+                            builder.push(format!(" julianday({temporal_column}) >= julianday("));
+                            builder.push_bind(iso(dt1));
+                            builder.push(format!(
+                                ") AND julianday({temporal_end_column}) <= julianday("
+                            ));
+                            builder.push_bind(iso(dt2));
+                            builder.push(")");
+                        }
+                        _ => {
+                            error!("Invalid datetime interval");
+                            return Err(Error::QueryParams);
+                        }
+                    }
+                }
+                Ok(None) => {}
+                Err(e) => {
+                    error!("Ignoring invalid temporal field: {e}");
+                    return Err(Error::QueryParams);
+                }
+            }
+        }
+        for (key, val) in &filter.filters {
+            // check if the passed in field matches queryables
+            // (query parameter names are lowercase, GeoPackage columns often uppercase)
+            let Some((k, v)) = self
+                .other_columns
+                .iter()
+                .find(|(k, _)| k.eq_ignore_ascii_case(key))
+            else {
+                error!("Invalid query param {key}");
+                return Err(Error::QueryParams);
+            };
+            let k = quote_ident(k)?;
+            push_where(&mut builder, &mut where_term);
+            if val.rfind('*').is_some() {
+                builder.push(format!(" CAST({k} AS TEXT) LIKE "));
+                builder.push_bind(val.replace('*', "%"));
+            } else {
+                match v {
+                    QueryableType::String => {
+                        builder.push(format!(" {k} = "));
+                        builder.push_bind(val.clone());
+                    }
+                    QueryableType::Integer => {
+                        builder.push(format!(" {k} = "));
+                        builder.push_bind(val.parse::<i64>().map_err(|_| Error::QueryParams)?);
+                    }
+                    QueryableType::Number => {
+                        builder.push(format!(" {k} = "));
+                        builder.push_bind(val.parse::<f64>().map_err(|_| Error::QueryParams)?);
+                    }
+                    QueryableType::Bool => {
+                        builder.push(format!(" {k} = "));
+                        builder.push_bind(val.parse::<bool>().map_err(|_| Error::QueryParams)?);
+                    }
+                    QueryableType::Datetime => {
+                        chrono::DateTime::parse_from_rfc3339(val)
+                            .map_err(|_| Error::QueryParams)?;
+                        builder.push(format!(" julianday({k}) = julianday("));
+                        builder.push_bind(val.clone());
+                        builder.push(")");
+                    }
+                }
+            }
+        }
+        if let Some(ids) = filter.ids() {
+            let Some(pk) = &self.pk_column else {
+                error!("Filtering by ids requires a single primary key");
+                return Err(Error::QueryParams);
+            };
+            push_where(&mut builder, &mut where_term);
+            builder.push(format!(" CAST({} AS TEXT) IN (", quote_ident(pk)?));
+            let mut separated = builder.separated(",");
+            for id in ids {
+                separated.push_bind(id);
+            }
+            builder.push(")");
+        }
+        if let Some(geojson) = &filter.intersects {
+            let geom: Value = serde_json::from_str(geojson).map_err(|_| Error::QueryParams)?;
+            let bbox = geojson_bbox(&geom).ok_or(Error::QueryParams)?;
+            if let (Some(rtree), Some(pk)) = (&self.rtree_table, &self.pk_column) {
+                // Envelope intersection only, since SQLite has no geometry functions
+                push_where(&mut builder, &mut where_term);
+                push_rtree_filter(&mut builder, rtree, pk, bbox, self.geometry_format);
+            } else {
+                warn!("Ignoring intersects filter (no spatial index)");
+            }
         }
+        unsupported_param("crs", filter.crs.is_some())?;
+        unsupported_param("filter", filter.filter.is_some())?;
         let limit = filter.limit_or_default();
         if limit > 0 {
-            sql.push_str(&format!(" LIMIT {limit}"));
+            builder.push(" LIMIT ");
+            builder.push_bind(limit as i64);
         }
         if let Some(offset) = filter.offset {
-            sql.push_str(&format!(" OFFSET {offset}"));
+            builder.push(" OFFSET ");
+            builder.push_bind(offset as i64);
         }
-        let rows = sqlx::query(&sql).fetch_all(&self.ds.pool).await?;
+        debug!("SQL: {}", builder.sql());
+        let rows = builder.build().fetch_all(&self.ds.pool).await?;
         let number_matched = if let Some(row) = rows.first() {
             row.try_get::<u32, _>("__total_cnt")? as u64
         } else {
@@ -203,11 +419,18 @@ impl CollectionSource for GpkgCollectionSource {
             features: items,
             number_matched,
             number_returned,
+            paging: Paging::Offset,
         };
         Ok(result)
     }
 
-    async fn item(&self, collection_id: &str, feature_id: &str) -> Result<Option<CoreFeature>> {
+    async fn item(
+        &self,
+        collection_id: &str,
+        feature_id: &str,
+        crs: Option<&Crs>,
+    ) -> Result<Option<CoreFeature>> {
+        unsupported_param("crs", crs.is_some())?;
         let Some(pk) = &self.pk_column else {
             warn!("Ignoring error getting item for {collection_id} without single primary key");
             return Ok(None);
@@ -216,7 +439,8 @@ impl CollectionSource for GpkgCollectionSource {
             "
             WITH query AS ({sql})
             SELECT * FROM query WHERE {pk} = ?",
-            sql = &self.sql
+            sql = &self.sql,
+            pk = quote_ident(pk)?
         );
         if let Some(row) = sqlx::query(&sql)
             .bind(feature_id)
@@ -248,8 +472,110 @@ impl CollectionSource for GpkgCollectionSource {
         }
     }
 
-    async fn queryables(&self, _collection_id: &str) -> Result<Option<Queryables>> {
-        Ok(None)
+    fn temporal_properties(&self) -> Option<(&str, Option<&str>)> {
+        self.temporal_column
+            .as_ref()
+            .map(|col| (col.as_str(), self.temporal_end_column.as_deref()))
+    }
+    async fn queryables(&self, collection_id: &str) -> Result<Option<Queryables>> {
+        let properties: HashMap<String, QueryableProperty> = self
+            .other_columns
+            .iter()
+            .map(|(name, type_)| {
+                (
+                    name.clone(),
+                    QueryableProperty {
+                        title: Some(name.clone()),
+                        type_: Some(type_.clone()),
+                        format: None,
+                        enum_: None,
+                        minimum: None,
+                        maximum: None,
+                    },
+                )
+            })
+            .collect();
+        Ok(Some(Queryables {
+            id: format!("/collections/{collection_id}/queryables"),
+            title: Some(collection_id.to_string()),
+            schema: "http://json-schema.org/draft/2019-09/schema".to_string(),
+            type_: "object".to_string(),
+            properties,
+        }))
+    }
+}
+
+fn push_where(builder: &mut QueryBuilder<Sqlite>, where_term: &mut bool) {
+    if *where_term {
+        builder.push(" AND ");
+    } else {
+        builder.push(" WHERE ");
+        *where_term = true;
+    }
+}
+
+fn quote_ident(name: &str) -> Result<String> {
+    Ok(SqlIdent::new(name)?.quoted())
+}
+
+/// Select features with an envelope intersecting `bbox` ([xmin, ymin, xmax, ymax]) using the R-tree index.
+/// The R-tree id column contains the rowid of the feature table, which is aliased by the primary key.
+fn push_rtree_filter(
+    builder: &mut QueryBuilder<Sqlite>,
+    rtree: &str,
+    pk: &str,
+    bbox: [f64; 4],
+    format: GeometryFormat,
+) {
+    let [id, minx, maxx, miny, maxy] = if format == GeometryFormat::Spatialite {
+        ["pkid", "xmin", "xmax", "ymin", "ymax"]
+    } else {
+        ["id", "minx", "maxx", "miny", "maxy"]
+    };
+    builder.push(format!(
+        r#" {pk} IN (SELECT {id} FROM "{rtree}" WHERE {minx} <= "#
+    ));
+    builder.push_bind(bbox[2]);
+    builder.push(format!(" AND {maxx} >= "));
+    builder.push_bind(bbox[0]);
+    builder.push(format!(" AND {miny} <= "));
+    builder.push_bind(bbox[3]);
+    builder.push(format!(" AND {maxy} >= "));
+    builder.push_bind(bbox[1]);
+    builder.push(")");
+}
+
+/// Bounding box of a GeoJSON geometry
+pub(crate) fn geojson_bbox(geom: &Value) -> Option<[f64; 4]> {
+    fn extend(coords: &Value, bbox: &mut [f64; 4]) {
+        match coords {
+            Value::Array(arr) if arr.first().map_or(false, Value::is_number) => {
+                if let (Some(x), Some(y)) = (
+                    arr.first().and_then(Value::as_f64),
+                    arr.get(1).and_then(Value::as_f64),
+                ) {
+                    bbox[0] = bbox[0].min(x);
+                    bbox[1] = bbox[1].min(y);
+                    bbox[2] = bbox[2].max(x);
+                    bbox[3] = bbox[3].max(y);
+                }
+            }
+            Value::Array(arr) => arr.iter().for_each(|c| extend(c, bbox)),
+            _ => {}
+        }
+    }
+    let mut bbox = [f64::MAX, f64::MAX, f64::MIN, f64::MIN];
+    if let Some(geometries) = geom.get("geometries").and_then(Value::as_array) {
+        for g in geometries {
+            extend(&g["coordinates"], &mut bbox);
+        }
+    } else {
+        extend(&geom["coordinates"], &mut bbox);
+    }
+    if bbox[0] <= bbox[2] && bbox[1] <= bbox[3] {
+        Some(bbox)
+    } else {
+        None
     }
 }
 
@@ -279,14 +605,13 @@ fn row_to_feature(row: &SqliteRow, table_info: &GpkgCollectionSource) -> Result<
             }
         }
     }
-    let wkb: wkb::Decode<geojson::GeoJsonString> =
-        row.try_get(table_info.geometry_column.as_str())?;
-    let geom = wkb.geometry.ok_or(error::Error::GeometryFormatError)?;
+    let geom = geometry_json(row, &table_info.geometry_column, table_info.geometry_format)?;
 
     let item = CoreFeature {
         type_: "Feature".to_string(),
+        bbox: None,
         id,
-        geometry: serde_json::from_str(&geom.0).map_err(|_| error::Error::GeometryFormatError)?,
+        geometry: serde_json::from_str(&geom).map_err(|_| error::Error::GeometryFormatError)?,
         properties: Some(properties),
         links: vec![],
     };
@@ -294,6 +619,20 @@ fn row_to_feature(row: &SqliteRow, table_info: &GpkgCollectionSource) -> Result<
     Ok(item)
 }
 
+/// Decode geometry column into a GeoJSON string
+fn geometry_json(row: &SqliteRow, column: &str, format: GeometryFormat) -> Result<String> {
+    let json = match format {
+        GeometryFormat::Gpkg => {
+            let wkb: wkb::Decode<geojson::GeoJsonString> = row.try_get(column)?;
+            return Ok(wkb.geometry.ok_or(error::Error::GeometryFormatError)?.0);
+        }
+        GeometryFormat::Spatialite => wkb::SpatiaLiteWkb(row.try_get(column)?).to_json(),
+        GeometryFormat::Wkb => wkb::Wkb(row.try_get(column)?).to_json(),
+        GeometryFormat::Wkt => wkt::WktStr(row.try_get(column)?).to_json(),
+    };
+    json.map_err(|_| error::Error::GeometryFormatError)
+}
+
 async fn detect_pk(ds: &SqliteDatasource, table: &str) -> Result<Option<String>> {
     let sql = r#"
         SELECT
@@ -314,22 +653,110 @@ async fn detect_pk(ds: &SqliteDatasource, table: &str) -> Result<Option<String>>
     Ok(pk_column)
 }
 
-async fn detect_geometry(ds: &SqliteDatasource, table: &str) -> Result<String> {
-    let sql = r#"
-        SELECT column_name, geometry_type_name
-        FROM gpkg_geometry_columns
-        WHERE table_name = ?
-    "#;
-    let row = sqlx::query(sql)
+async fn table_exists(ds: &SqliteDatasource, table: &str) -> Result<bool> {
+    let sql = "SELECT count(*) FROM sqlite_master WHERE type = 'table' AND name = ?";
+    let cnt: i64 = sqlx::query_scalar(sql)
         .bind(table)
-        // We take the first result only
         .fetch_one(&ds.pool)
         .await?;
-    let geometry_column: String = row.try_get("column_name")?;
-    let _geometry_type_name: String = row.try_get("geometry_type_name")?;
+    Ok(cnt > 0)
+}
+
+/// Geometry format from GeoPackage or SpatiaLite metadata tables
+async fn detect_geometry_format(ds: &SqliteDatasource) -> Result<GeometryFormat> {
+    if table_exists(ds, "gpkg_geometry_columns").await? {
+        Ok(GeometryFormat::Gpkg)
+    } else if table_exists(ds, "geometry_columns").await? {
+        Ok(GeometryFormat::Spatialite)
+    } else {
+        Ok(GeometryFormat::Wkb)
+    }
+}
+
+async fn detect_geometry(
+    ds: &SqliteDatasource,
+    table: &str,
+    format: GeometryFormat,
+) -> Result<Option<String>> {
+    let sql = match format {
+        GeometryFormat::Gpkg => {
+            r#"
+            SELECT column_name
+            FROM gpkg_geometry_columns
+            WHERE table_name = ?
+            "#
+        }
+        GeometryFormat::Spatialite => {
+            r#"
+            SELECT f_geometry_column
+            FROM geometry_columns
+            WHERE lower(f_table_name) = lower(?)
+            "#
+        }
+        GeometryFormat::Wkb | GeometryFormat::Wkt => return Ok(None),
+    };
+    // We take the first result only
+    let geometry_column: Option<String> = sqlx::query_scalar(sql)
+        .bind(table)
+        .fetch_optional(&ds.pool)
+        .await?;
     Ok(geometry_column)
 }
 
+async fn detect_rtree(
+    ds: &SqliteDatasource,
+    table: &str,
+    geometry_column: &str,
+    format: GeometryFormat,
+) -> Result<Option<String>> {
+    let rtree_table = match format {
+        GeometryFormat::Gpkg => format!("rtree_{table}_{geometry_column}"),
+        GeometryFormat::Spatialite => format!("idx_{table}_{geometry_column}"),
+        GeometryFormat::Wkb | GeometryFormat::Wkt => return Ok(None),
+    };
+    Ok(if table_exists(ds, &rtree_table).await? {
+        Some(rtree_table)
+    } else {
+        None
+    })
+}
+
+/// Queryable types of `fields` in query result
+async fn get_column_info(
+    ds: &SqliteDatasource,
+    sql: &str,
+    fields: &[String],
+) -> Result<HashMap<String, QueryableType>> {
+    let mut types = HashMap::new();
+    if fields.is_empty() {
+        return Ok(types);
+    }
+    let row = sqlx::query(&format!("SELECT * FROM ({sql}) LIMIT 1"))
+        .fetch_one(&ds.pool)
+        .await?;
+    for field in fields {
+        let Some(col) = row.columns().iter().find(|col| col.name() == field) else {
+            return Err(Error::DatasourceSetupError(format!(
+                "Queryable field `{field}` not found"
+            )));
+        };
+        let queryable_type = match col.type_info().name() {
+            "TEXT" => QueryableType::String,
+            "INTEGER" => QueryableType::Integer,
+            "REAL" | "NUMERIC" => QueryableType::Number,
+            "BOOLEAN" => QueryableType::Bool,
+            "DATE" | "DATETIME" => QueryableType::Datetime,
+            ty => {
+                return Err(Error::DatasourceSetupError(format!(
+                    "{field} has a SQLite type {ty} which is not currently handled and can't be used a queryable"
+                )))
+            }
+        };
+        types.insert(field.clone(), queryable_type);
+    }
+    Ok(types)
+}
+
 async fn check_query(ds: &SqliteDatasource, sql: String) -> Result<String> {
     debug!("Collection query: {sql}");
     // TODO: prepare only
@@ -374,9 +801,143 @@ mod tests {
             ds,
             sql: "SELECT * FROM ne_10m_lakes".to_string(),
             geometry_column: "geom".to_string(),
+            geometry_format: GeometryFormat::Gpkg,
             pk_column: Some("fid".to_string()),
+            rtree_table: None,
+            temporal_column: None,
+            temporal_end_column: None,
+            other_columns: HashMap::new(),
         };
         let items = source.items(&filter).await.unwrap();
         assert_eq!(items.features.len(), filter.limit_or_default() as usize);
     }
+
+    #[tokio::test]
+    async fn gpkg_filters() {
+        let mut ds = SqliteDatasource::new_pool("../assets/ne_extracts.gpkg")
+            .await
+            .unwrap();
+        let cfg = ConfiguredCollectionCfg {
+            source: CollectionSourceCfg::Gpkg(GpkgCollectionCfg {
+                table_name: Some("ne_10m_populated_places".to_string()),
+                queryable_fields: vec!["NAME".to_string(), "SCALERANK".to_string()],
+                ..Default::default()
+            }),
+            name: "populated_places".to_string(),
+            title: None,
+            description: None,
+            attribution: None,
+            license: None,
+            downloads: Vec::new(),
+        };
+        let fc = ds.setup_collection(&cfg, None).await.unwrap();
+        let source = fc.source;
+
+        let filter = FilterParams {
+            bbox: Some("5.6,45.8,10.9,47.6".to_string()),
+            ..Default::default()
+        };
+        let items = source.items(&filter).await.unwrap();
+        assert_eq!(items.number_matched, 32);
+
+        let filter = FilterParams {
+            intersects: Some(
+                r#"{"type":"Polygon","coordinates":[[[5.6,45.8],[10.9,45.8],[10.9,47.6],[5.6,47.6],[5.6,45.8]]]}"#
+                    .to_string(),
+            ),
+            ..Default::default()
+        };
+        let items = source.items(&filter).await.unwrap();
+        assert_eq!(items.number_matched, 32);
+
+        let filter = FilterParams {
+            ids: Some("18,88".to_string()),
+            ..Default::default()
+        };
+        let items = source.items(&filter).await.unwrap();
+        assert_eq!(items.number_returned, 2);
+
+        let filter = FilterParams {
+            filters: [("name".to_string(), "Bern".to_string())].into(),
+            ..Default::default()
+        };
+        let items = source.items(&filter).await.unwrap();
+        assert_eq!(items.number_matched, 1);
+
+        let filter = FilterParams {
+            filters: [("SCALERANK".to_string(), "0".to_string())].into(),
+            ..Default::default()
+        };
+        let items = source.items(&filter).await.unwrap();
+        assert_eq!(items.number_matched, 27);
+
+        let filter = FilterParams {
+            filters: [("FEATURECLA".to_string(), "x".to_string())].into(),
+            ..Default::default()
+        };
+        assert!(source.items(&filter).await.is_err());
+
+        let queryables = source
+            .queryables("populated_places")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(queryables.properties.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn sqlite_wkt_table() {
+        let file = tempfile::Builder::new()
+            .suffix(".sqlite")
+            .tempfile()
+            .unwrap();
+        let path = file.path().to_str().unwrap();
+        let pool = SqlitePool::connect_with(SqliteConnectOptions::new().filename(path))
+            .await
+            .unwrap();
+        sqlx::query("CREATE TABLE places (id INTEGER PRIMARY KEY, name TEXT, wkt TEXT)")
+            .execute(&pool)
+            .await
+            .unwrap();
+        sqlx::query(
+            "INSERT INTO places (name, wkt) VALUES ('Bern', 'POINT(7.44 46.95)'), ('Zurich', 'POINT(8.54 47.37)')",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        pool.close().await;
+
+        let mut ds = SqliteDatasource::new_pool(path).await.unwrap();
+        let cfg = ConfiguredCollectionCfg {
+            source: CollectionSourceCfg::Gpkg(GpkgCollectionCfg {
+                table_name: Some("places".to_string()),
+                geometry_field: Some("wkt".to_string()),
+                geometry_format: Some(GeometryFormat::Wkt),
+                ..Default::default()
+            }),
+            name: "places".to_string(),
+            title: None,
+            description: None,
+            attribution: None,
+            license: None,
+            downloads: Vec::new(),
+        };
+        let fc = ds.setup_collection(&cfg, None).await.unwrap();
+        let items = fc.source.items(&FilterParams::default()).await.unwrap();
+        assert_eq!(items.number_matched, 2);
+        assert_eq!(items.features[0].id.as_deref(), Some("1"));
+        assert_eq!(
+            items.features[0].geometry,
+            json!({"type": "Point", "coordinates": [7.44, 46.95]})
+        );
+    }
+
+    #[test]
+    fn geometry_bbox() {
+        let geom = json!({"type": "LineString", "coordinates": [[7.5, 47.2], [8.5, 46.9]]});
+        assert_eq!(geojson_bbox(&geom), Some([7.5, 46.9, 8.5, 47.2]));
+        let geom = json!({"type": "Point", "coordinates": [7.5, 47.2]});
+        assert_eq!(geojson_bbox(&geom), Some([7.5, 47.2, 7.5, 47.2]));
+        assert_eq!(geojson_bbox(&json!({"type": "Point"})), None);
+    }
 }
@@ -0,0 +1,182 @@
+//! Collection source for datasources which are unavailable during setup.
+
+use crate::crs::Crs;
+use crate::datasource::{
+    CollectionDatasource, CollectionSource, CollectionSourceCfg, ConfiguredCollectionCfg, IfMatch,
+    ItemsResult, ItemsStream,
+};
+use crate::error::{Error, Result};
+use crate::filter_params::{AggregateParams, FilterParams};
+use async_trait::async_trait;
+use bbox_core::backoff::Backoff;
+use bbox_core::ogcapi::{CoreFeature, Queryables};
+use futures::lock::Mutex;
+use log::{info, warn};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// Collection source retrying its setup with backoff until the datasource is reachable
+#[derive(Clone)]
+pub struct LazyCollectionSource {
+    cfg: ConfiguredCollectionCfg,
+    state: Arc<Mutex<LazyState>>,
+    available: Arc<AtomicBool>,
+}
+
+struct LazyState {
+    ds: Box<dyn CollectionDatasource + Send>,
+    source: Option<Box<dyn CollectionSource>>,
+    backoff: Backoff,
+}
+
+impl LazyCollectionSource {
+    pub fn new(ds: Box<dyn CollectionDatasource + Send>, cfg: &ConfiguredCollectionCfg) -> Self {
+        let mut backoff = Backoff::default();
+        // Setup just failed
+        backoff.failed();
+        let lazy_source = LazyCollectionSource {
+            cfg: cfg.clone(),
+            state: Arc::new(Mutex::new(LazyState {
+                ds,
+                source: None,
+                backoff,
+            })),
+            available: Arc::new(AtomicBool::new(false)),
+        };
+        lazy_source.retry_in_background();
+        lazy_source
+    }
+    /// Return wrapped source, setting it up if the retry delay has elapsed
+    async fn source(&self) -> Result<Box<dyn CollectionSource>> {
+        setup(&self.state, &self.cfg, &self.available).await
+    }
+    /// Retry setup until the collection is available.
+    /// Stops when the source is dropped, e.g. after a configuration reload.
+    fn retry_in_background(&self) {
+        let state = Arc::downgrade(&self.state);
+        let cfg = self.cfg.clone();
+        let available = self.available.clone();
+        tokio::spawn(async move {
+            loop {
+                let wait = match state.upgrade() {
+                    Some(state) => state.lock().await.backoff.remaining(),
+                    None => break,
+                };
+                tokio::time::sleep(wait).await;
+                let Some(state) = state.upgrade() else {
+                    break;
+                };
+                if setup(&state, &cfg, &available).await.is_ok() {
+                    break;
+                }
+            }
+        });
+    }
+}
+
+async fn setup(
+    state: &Mutex<LazyState>,
+    cfg: &ConfiguredCollectionCfg,
+    available: &AtomicBool,
+) -> Result<Box<dyn CollectionSource>> {
+    let mut state = state.lock().await;
+    if let Some(source) = &state.source {
+        return Ok(source.clone());
+    }
+    let id = &cfg.name;
+    if !state.backoff.ready() {
+        return Err(Error::CollectionUnavailable(id.clone()));
+    }
+    match state.ds.setup_collection(cfg, None).await {
+        Ok(fc) => {
+            info!("Collection `{id}` available");
+            state.backoff.reset();
+            state.source = Some(fc.source.clone());
+            available.store(true, Ordering::Relaxed);
+            Ok(fc.source)
+        }
+        Err(e) => {
+            let retry_delay = state.backoff.delay();
+            state.backoff.failed();
+            warn!(
+                "Collection `{id}` unavailable - retrying in {}s: {e}",
+                retry_delay.as_secs()
+            );
+            Err(Error::CollectionUnavailable(id.clone()))
+        }
+    }
+}
+
+#[async_trait]
+impl CollectionSource for LazyCollectionSource {
+    async fn items(&self, filter: &FilterParams) -> Result<ItemsResult> {
+        self.source().await?.items(filter).await
+    }
+    async fn items_stream(&self, filter: &FilterParams) -> Result<Option<ItemsStream>> {
+        self.source().await?.items_stream(filter).await
+    }
+    async fn aggregate(
+        &self,
+        filter: &FilterParams,
+        params: &AggregateParams,
+    ) -> Result<Option<Vec<serde_json::Value>>> {
+        self.source().await?.aggregate(filter, params).await
+    }
+    async fn item(
+        &self,
+        collection_id: &str,
+        feature_id: &str,
+        crs: Option<&Crs>,
+    ) -> Result<Option<CoreFeature>> {
+        self.source()
+            .await?
+            .item(collection_id, feature_id, crs)
+            .await
+    }
+    async fn queryables(&self, collection_id: &str) -> Result<Option<Queryables>> {
+        self.source().await?.queryables(collection_id).await
+    }
+    async fn properties_schema(&self, collection_id: &str) -> Result<Option<Queryables>> {
+        self.source().await?.properties_schema(collection_id).await
+    }
+    fn temporal_properties(&self) -> Option<(&str, Option<&str>)> {
+        self.cfg.source.temporal_properties()
+    }
+    fn available(&self) -> bool {
+        self.available.load(Ordering::Relaxed)
+    }
+    fn writable(&self) -> bool {
+        matches!(&self.cfg.source, CollectionSourceCfg::Postgis(cfg) if cfg.writable)
+    }
+    fn searchable(&self) -> bool {
+        matches!(&self.cfg.source, CollectionSourceCfg::Postgis(cfg) if !cfg.search_fields.is_empty())
+    }
+    async fn create_item(&self, feature: &serde_json::Value) -> Result<String> {
+        self.source().await?.create_item(feature).await
+    }
+    async fn replace_item(
+        &self,
+        feature_id: &str,
+        feature: &serde_json::Value,
+        if_match: Option<&IfMatch>,
+    ) -> Result<bool> {
+        self.source()
+            .await?
+            .replace_item(feature_id, feature, if_match)
+            .await
+    }
+    async fn update_item(
+        &self,
+        feature_id: &str,
+        feature: &serde_json::Value,
+        if_match: Option<&IfMatch>,
+    ) -> Result<bool> {
+        self.source()
+            .await?
+            .update_item(feature_id, feature, if_match)
+            .await
+    }
+    async fn delete_item(&self, feature_id: &str, if_match: Option<&IfMatch>) -> Result<bool> {
+        self.source().await?.delete_item(feature_id, if_match).await
+    }
+}
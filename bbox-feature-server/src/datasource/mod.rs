@@ -1,17 +1,29 @@
 //! Feature source implementations.
 
 use crate::config::{CollectionSourceCfg, ConfiguredCollectionCfg};
+use crate::crs::Crs;
 use crate::error::{Error, Result};
-use crate::filter_params::FilterParams;
+use crate::filter_params::{AggregateParams, FilterParams};
 use crate::inventory::FeatureCollection;
 use async_trait::async_trait;
 use bbox_core::config::{DatasourceCfg, NamedDatasourceCfg};
-use bbox_core::ogcapi::{CoreExtent, CoreFeature, Queryables};
+use bbox_core::health::DependencyHealth;
+use bbox_core::ogcapi::{
+    ApiLink, CoreCollection, CoreExtent, CoreExtentTemporal, CoreFeature, Queryables,
+};
 use bbox_core::NamedObjectStore;
 use dyn_clone::{clone_trait_object, DynClone};
+use futures::stream::BoxStream;
+use log::warn;
 use std::env;
 
+pub mod custom;
+pub mod elasticsearch;
+pub mod fgb;
+pub mod geoparquet;
 pub mod gpkg;
+pub mod lazy;
+pub mod mysql;
 pub mod postgis;
 
 #[async_trait]
@@ -31,8 +43,82 @@ pub trait AutoscanCollectionDatasource {
 #[async_trait]
 pub trait CollectionSource: DynClone + Sync + Send {
     async fn items(&self, filter: &FilterParams) -> Result<ItemsResult>;
-    async fn item(&self, collection_id: &str, feature_id: &str) -> Result<Option<CoreFeature>>;
+    /// Items fetched incrementally. None if the source doesn't support streaming.
+    async fn items_stream(&self, _filter: &FilterParams) -> Result<Option<ItemsStream>> {
+        Ok(None)
+    }
+    /// Aggregated values of the filtered items, one object per group.
+    /// None if the source doesn't support aggregation.
+    async fn aggregate(
+        &self,
+        _filter: &FilterParams,
+        _params: &AggregateParams,
+    ) -> Result<Option<Vec<serde_json::Value>>> {
+        Ok(None)
+    }
+    /// Single item with geometry in `crs` (Default: storage CRS)
+    async fn item(
+        &self,
+        collection_id: &str,
+        feature_id: &str,
+        crs: Option<&Crs>,
+    ) -> Result<Option<CoreFeature>>;
     async fn queryables(&self, collection_id: &str) -> Result<Option<Queryables>>;
+    /// JSON Schema of the item properties
+    async fn properties_schema(&self, _collection_id: &str) -> Result<Option<Queryables>> {
+        Ok(None)
+    }
+    /// Properties with the feature time instant or the start and end of a time interval
+    fn temporal_properties(&self) -> Option<(&str, Option<&str>)> {
+        None
+    }
+    /// Time interval of all features, computed from the temporal properties
+    async fn temporal_extent(&self) -> Result<Option<CoreExtentTemporal>> {
+        Ok(None)
+    }
+    /// Property with the time of the last feature change
+    fn updated_property(&self) -> Option<&str> {
+        None
+    }
+    /// Source is connected to its datasource
+    fn available(&self) -> bool {
+        true
+    }
+    /// Source supports creating, updating and deleting features
+    fn writable(&self) -> bool {
+        false
+    }
+    /// Source supports text search with the `q` parameter
+    fn searchable(&self) -> bool {
+        false
+    }
+    /// Insert a GeoJSON feature and return its id
+    async fn create_item(&self, _feature: &serde_json::Value) -> Result<String> {
+        Err(Error::CollectionReadOnly)
+    }
+    /// Replace geometry and all properties of a feature. Returns `false` if the feature doesn't exist.
+    /// The precondition is checked in the same transaction as the update.
+    async fn replace_item(
+        &self,
+        _feature_id: &str,
+        _feature: &serde_json::Value,
+        _if_match: Option<&IfMatch>,
+    ) -> Result<bool> {
+        Err(Error::CollectionReadOnly)
+    }
+    /// Update geometry and properties contained in a partial feature. Returns `false` if the feature doesn't exist.
+    async fn update_item(
+        &self,
+        _feature_id: &str,
+        _feature: &serde_json::Value,
+        _if_match: Option<&IfMatch>,
+    ) -> Result<bool> {
+        Err(Error::CollectionReadOnly)
+    }
+    /// Delete a feature. Returns `false` if the feature doesn't exist.
+    async fn delete_item(&self, _feature_id: &str, _if_match: Option<&IfMatch>) -> Result<bool> {
+        Err(Error::CollectionReadOnly)
+    }
 }
 
 clone_trait_object!(CollectionSource);
@@ -42,68 +128,312 @@ clone_trait_object!(CollectionSource);
 pub struct Datasources {
     pg_datasources: NamedObjectStore<postgis::Datasource>,
     gpkg_datasources: NamedObjectStore<gpkg::Datasource>,
+    mysql_datasources: NamedObjectStore<mysql::Datasource>,
+    custom_datasources: NamedObjectStore<Box<dyn custom::CustomDatasource>>,
+    /// Keep collections of unavailable datasources and retry their setup
+    resilient: bool,
 }
 
 impl Datasources {
-    pub async fn create(datasources: &Vec<NamedDatasourceCfg>) -> Result<Self> {
+    /// Setup datasource connection pools.
+    /// In resilient mode, unreachable datasources get a pool connecting on first use.
+    pub async fn create(datasources: &Vec<NamedDatasourceCfg>, resilient: bool) -> Result<Self> {
         // TODO: setup referenced datasources only (?)
-        let mut ds_handler = Datasources::default();
+        let mut ds_handler = Datasources {
+            resilient,
+            ..Default::default()
+        };
         for named_ds in datasources {
             // TODO: check duplicate names
             // TODO: move into core, combined with tile-server Datasource
-            let envar = env::var(format!("BBOX_DATASOURCE_{}", &named_ds.name.to_uppercase())).ok();
+            let name = &named_ds.name;
+            let envar = env::var(format!("BBOX_DATASOURCE_{}", name.to_uppercase())).ok();
             match &named_ds.datasource {
                 DatasourceCfg::Postgis(cfg) => {
-                    let ds = postgis::Datasource::from_config(cfg, envar)
-                        .await
-                        .map_err(|e| Error::DatasourceSetupError(e.to_string()))?;
-                    ds_handler.pg_datasources.add(&named_ds.name, ds);
+                    let ds = match postgis::Datasource::from_config(cfg, envar.clone()).await {
+                        Err(e) if resilient => {
+                            warn!("Datasource `{name}` unavailable - connecting on demand: {e}");
+                            postgis::Datasource::new_lazy_pool(&envar.unwrap_or(cfg.url.clone()))
+                        }
+                        result => result,
+                    }
+                    .map_err(|e| Error::DatasourceSetupError(e.to_string()))?;
+                    ds_handler.pg_datasources.add(name, ds);
                 }
                 DatasourceCfg::Gpkg(cfg) => {
-                    let ds = gpkg::Datasource::from_config(cfg).await?;
-                    ds_handler.gpkg_datasources.add(&named_ds.name, ds);
+                    let ds = match gpkg::Datasource::from_config(cfg).await {
+                        Err(e) if resilient => {
+                            warn!("Datasource `{name}` unavailable - connecting on demand: {e}");
+                            gpkg::Datasource::new_lazy_pool(&cfg.path.to_string_lossy())
+                        }
+                        result => result?,
+                    };
+                    ds_handler.gpkg_datasources.add(name, ds);
+                }
+                DatasourceCfg::Mysql(cfg) => {
+                    let ds = match mysql::Datasource::from_config(cfg, envar.clone()).await {
+                        Err(e) if resilient => {
+                            warn!("Datasource `{name}` unavailable - connecting on demand: {e}");
+                            mysql::Datasource::new_lazy_pool(&envar.unwrap_or(cfg.url.clone()))?
+                        }
+                        result => result?,
+                    };
+                    ds_handler.mysql_datasources.add(name, ds);
+                }
+                DatasourceCfg::Custom(cfg) => {
+                    let ds = custom::create_datasource(&cfg.driver, &cfg.params).await?;
+                    ds_handler.custom_datasources.add(name, ds);
                 }
                 _ => { /* ignore others */ }
             }
         }
         Ok(ds_handler)
     }
+    /// Check connectivity of all datasources
+    pub async fn check(&self) -> Vec<DependencyHealth> {
+        let mut checks = Vec::new();
+        for (name, ds) in self.pg_datasources.iter() {
+            let result = ds.check_connection().await;
+            checks.push(DependencyHealth::from_result("postgis", name, result));
+        }
+        for (name, ds) in self.gpkg_datasources.iter() {
+            let result = ds.check_connection().await;
+            checks.push(DependencyHealth::from_result("gpkg", name, result));
+        }
+        for (name, ds) in self.mysql_datasources.iter() {
+            let result = ds.check_connection().await;
+            checks.push(DependencyHealth::from_result("mysql", name, result));
+        }
+        checks
+    }
     pub async fn setup_collection(
-        &mut self,
+        &self,
         collection: &ConfiguredCollectionCfg,
     ) -> Result<FeatureCollection> {
-        match &collection.source {
+        let mut retry = self.resilient;
+        let mut ds: Box<dyn CollectionDatasource + Send> = match &collection.source {
             CollectionSourceCfg::Postgis(cfg) => {
-                let source = self
+                let ds = self
                     .pg_datasources
-                    .get_or_default_mut(cfg.datasource.as_deref())
-                    .ok_or(Error::DatasourceNotFound(
-                        cfg.datasource
-                            .as_ref()
-                            .unwrap_or(&"(default)".to_string())
-                            .clone(),
-                    ))?;
-                source.setup_collection(collection, None).await
+                    .get_or_default(cfg.datasource.as_deref())
+                    .ok_or(datasource_not_found(&cfg.datasource))?;
+                retry |= ds.is_lazy();
+                Box::new(ds.clone())
             }
-            CollectionSourceCfg::Gpkg(ref cfg) => {
-                let source = self
-                    .gpkg_datasources
-                    .get_or_default_mut(cfg.datasource.as_deref())
-                    .ok_or(Error::DatasourceNotFound(
-                        cfg.datasource
-                            .as_ref()
-                            .unwrap_or(&"(default)".to_string())
+            CollectionSourceCfg::Gpkg(ref cfg) => Box::new(
+                self.gpkg_datasources
+                    .get_or_default(cfg.datasource.as_deref())
+                    .ok_or(datasource_not_found(&cfg.datasource))?
+                    .clone(),
+            ),
+            CollectionSourceCfg::Mysql(ref cfg) => Box::new(
+                self.mysql_datasources
+                    .get_or_default(cfg.datasource.as_deref())
+                    .ok_or(datasource_not_found(&cfg.datasource))?
+                    .clone(),
+            ),
+            CollectionSourceCfg::Custom(ref cfg) => {
+                if let Some(ds) = custom::collection_datasource(collection).await? {
+                    Box::new(ds)
+                } else {
+                    Box::new(
+                        self.custom_datasources
+                            .get_or_default(cfg.datasource.as_deref())
+                            .ok_or(datasource_not_found(&cfg.datasource))?
                             .clone(),
-                    ))?;
-                source.setup_collection(collection, None).await
+                    )
+                }
+            }
+            CollectionSourceCfg::Geoparquet(_)
+            | CollectionSourceCfg::Flatgeobuf(_)
+            | CollectionSourceCfg::Elasticsearch(_) => Box::new(UnpooledDatasource),
+        };
+        match ds.setup_collection(collection, None).await {
+            Err(e) if retry => {
+                warn!(
+                    "Collection `{}` unavailable - setup will be retried: {e}",
+                    collection.name
+                );
+                Ok(unavailable_collection(ds, collection))
+            }
+            result => result,
+        }
+    }
+}
+
+/// Reject a query parameter which is not supported by a datasource
+pub(crate) fn unsupported_param(param: &'static str, present: bool) -> Result<()> {
+    if present {
+        Err(Error::UnsupportedParam(param))
+    } else {
+        Ok(())
+    }
+}
+
+fn datasource_not_found(name: &Option<String>) -> Error {
+    Error::DatasourceNotFound(name.clone().unwrap_or("(default)".to_string()))
+}
+
+/// Setup of collection sources without shared connection pool
+struct UnpooledDatasource;
+
+#[async_trait]
+impl CollectionDatasource for UnpooledDatasource {
+    async fn setup_collection(
+        &mut self,
+        cfg: &ConfiguredCollectionCfg,
+        _extent: Option<CoreExtent>,
+    ) -> Result<FeatureCollection> {
+        match &cfg.source {
+            CollectionSourceCfg::Geoparquet(_) => {
+                geoparquet::GeoparquetCollectionSource::setup_collection(cfg).await
+            }
+            CollectionSourceCfg::Flatgeobuf(_) => {
+                fgb::FlatgeobufCollectionSource::setup_collection(cfg).await
             }
+            CollectionSourceCfg::Elasticsearch(_) => {
+                elasticsearch::ElasticsearchCollectionSource::setup_collection(cfg).await
+            }
+            _ => Err(Error::DatasourceSetupError(format!(
+                "Collection `{}`: datasource connection pool required",
+                cfg.name
+            ))),
         }
     }
 }
 
+/// Collection with placeholder metadata, connecting on first access
+fn unavailable_collection(
+    ds: Box<dyn CollectionDatasource + Send>,
+    cfg: &ConfiguredCollectionCfg,
+) -> FeatureCollection {
+    let id = &cfg.name;
+    let collection = CoreCollection {
+        id: id.clone(),
+        title: cfg.title.clone().or(Some(id.clone())),
+        description: cfg.description.clone(),
+        attribution: cfg.attribution.clone(),
+        license: cfg.license.clone(),
+        extent: None,
+        item_type: None,
+        crs: vec![],
+        summaries: None,
+        links: vec![ApiLink {
+            href: format!("/collections/{id}/items"),
+            rel: Some("items".to_string()),
+            type_: Some("application/geo+json".to_string()),
+            title: Some(id.clone()),
+            hreflang: None,
+            length: None,
+        }],
+    };
+    FeatureCollection {
+        collection,
+        source: Box::new(lazy::LazyCollectionSource::new(ds, cfg)),
+    }
+}
+
 #[derive(Debug)]
 pub struct ItemsResult {
     pub features: Vec<CoreFeature>,
+    /// Ignored for keyset pagination
     pub number_matched: u64,
     pub number_returned: u64,
+    pub paging: Paging,
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub enum Paging {
+    #[default]
+    Offset,
+    /// Keyset pagination with primary key of the last returned feature, if more features follow
+    Cursor(Option<String>),
+}
+
+pub struct ItemsStream {
+    pub features: BoxStream<'static, Result<CoreFeature>>,
+    pub number_matched: u64,
+}
+
+/// Entity tag of a feature for optimistic concurrency control.
+/// Computed from id, geometry and properties, independent of the links.
+pub fn feature_etag(feature: &CoreFeature) -> String {
+    let content = serde_json::to_vec(&(&feature.id, &feature.geometry, &feature.properties))
+        .unwrap_or_default();
+    format!("\"{:08x}-{:x}\"", crc32fast::hash(&content), content.len())
+}
+
+/// `If-Match` precondition of a feature transaction
+#[derive(Clone, Debug)]
+pub struct IfMatch(Vec<String>);
+
+impl IfMatch {
+    pub fn from_header(value: &str) -> Self {
+        IfMatch(value.split(',').map(|tag| tag.trim().to_string()).collect())
+    }
+    /// Check the precondition against the current state of a feature
+    pub fn matches(&self, feature: &CoreFeature) -> bool {
+        let etag = feature_etag(feature);
+        self.0.iter().any(|tag| tag == "*" || *tag == etag)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::FlatgeobufCollectionCfg;
+    use crate::inventory::Inventory;
+
+    #[tokio::test]
+    async fn unavailable_at_startup() {
+        let cfg = ConfiguredCollectionCfg {
+            name: "places".to_string(),
+            title: None,
+            description: None,
+            attribution: None,
+            license: None,
+            downloads: Vec::new(),
+            source: CollectionSourceCfg::Flatgeobuf(FlatgeobufCollectionCfg {
+                path: "../data/missing.fgb".to_string(),
+                fid_field: None,
+                queryable_fields: Vec::new(),
+            }),
+        };
+        let mut sources = Datasources::create(&Vec::new(), false).await.unwrap();
+        assert!(sources.setup_collection(&cfg).await.is_err());
+
+        let mut sources = Datasources::create(&Vec::new(), true).await.unwrap();
+        let fc = sources.setup_collection(&cfg).await.unwrap();
+        assert!(!fc.source.available());
+        assert!(matches!(
+            fc.source.items(&FilterParams::default()).await,
+            Err(Error::CollectionUnavailable(_))
+        ));
+
+        let mut inventory = Inventory::new();
+        inventory.add_collection(fc);
+        assert!(inventory.collections().is_empty());
+        assert!(!inventory.collection_available("places"));
+        inventory.list_unavailable = true;
+        assert_eq!(inventory.collections().len(), 1);
+    }
+
+    #[test]
+    fn if_match_precondition() {
+        let feature = |name: &str| CoreFeature {
+            type_: "Feature".to_string(),
+            bbox: None,
+            geometry: serde_json::json!({"type": "Point", "coordinates": [7.0, 46.0]}),
+            properties: Some(serde_json::json!({ "name": name })),
+            id: Some("1".to_string()),
+            links: Vec::new(),
+        };
+        let etag = feature_etag(&feature("Bern"));
+        assert_eq!(etag, feature_etag(&feature("Bern")));
+        assert!(IfMatch::from_header(&etag).matches(&feature("Bern")));
+        assert!(IfMatch::from_header(&format!("\"other\", {etag}")).matches(&feature("Bern")));
+        assert!(IfMatch::from_header("*").matches(&feature("Bern")));
+        assert!(!IfMatch::from_header("\"other\"").matches(&feature("Bern")));
+        assert!(!IfMatch::from_header(&etag).matches(&feature("Zurich")));
+    }
 }
@@ -0,0 +1,472 @@
+//! Export of feature collections into a GeoPackage.
+
+use crate::error::{Error, Result};
+use crate::filter_params::FilterParams;
+use crate::inventory::Inventory;
+use bbox_core::ogcapi::{CoreFeature, QueryableProperty, QueryableType};
+use bbox_core::sql::SqlIdent;
+use futures::stream::{self, BoxStream, Stream};
+use futures::{StreamExt, TryStreamExt};
+use geozero::{geojson::GeoJsonString, CoordDimensions, ToWkb};
+use log::info;
+use serde::Deserialize;
+use serde_json::Value;
+use sqlx::sqlite::{SqliteConnectOptions, SqliteConnection};
+use sqlx::{ConnectOptions, Connection, Executor};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// Number of features read per query
+const PAGE_SIZE: u32 = 1000;
+
+/// GeoPackage metadata tables with WGS 84 spatial reference system
+const GPKG_SCHEMA: &str = r#"
+PRAGMA application_id = 1196444487;
+PRAGMA user_version = 10200;
+CREATE TABLE gpkg_spatial_ref_sys (
+  srs_name TEXT NOT NULL,
+  srs_id INTEGER NOT NULL PRIMARY KEY,
+  organization TEXT NOT NULL,
+  organization_coordsys_id INTEGER NOT NULL,
+  definition TEXT NOT NULL,
+  description TEXT
+);
+INSERT INTO gpkg_spatial_ref_sys VALUES
+  ('Undefined cartesian SRS', -1, 'NONE', -1, 'undefined', 'undefined cartesian coordinate reference system'),
+  ('Undefined geographic SRS', 0, 'NONE', 0, 'undefined', 'undefined geographic coordinate reference system'),
+  ('WGS 84 geodetic', 4326, 'EPSG', 4326, 'GEOGCS["WGS 84",DATUM["WGS_1984",SPHEROID["WGS 84",6378137,298.257223563,AUTHORITY["EPSG","7030"]],AUTHORITY["EPSG","6326"]],PRIMEM["Greenwich",0,AUTHORITY["EPSG","8901"]],UNIT["degree",0.0174532925199433,AUTHORITY["EPSG","9122"]],AXIS["Latitude",NORTH],AXIS["Longitude",EAST],AUTHORITY["EPSG","4326"]]', 'longitude/latitude coordinates in decimal degrees on the WGS 84 spheroid');
+CREATE TABLE gpkg_contents (
+  table_name TEXT NOT NULL PRIMARY KEY,
+  data_type TEXT NOT NULL,
+  identifier TEXT UNIQUE,
+  description TEXT DEFAULT '',
+  last_change DATETIME NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ','now')),
+  min_x DOUBLE, min_y DOUBLE, max_x DOUBLE, max_y DOUBLE,
+  srs_id INTEGER,
+  CONSTRAINT fk_gc_r_srs_id FOREIGN KEY (srs_id) REFERENCES gpkg_spatial_ref_sys(srs_id)
+);
+CREATE TABLE gpkg_geometry_columns (
+  table_name TEXT NOT NULL,
+  column_name TEXT NOT NULL,
+  geometry_type_name TEXT NOT NULL,
+  srs_id INTEGER NOT NULL,
+  z TINYINT NOT NULL,
+  m TINYINT NOT NULL,
+  CONSTRAINT pk_geom_cols PRIMARY KEY (table_name, column_name),
+  CONSTRAINT fk_gc_tn FOREIGN KEY (table_name) REFERENCES gpkg_contents(table_name),
+  CONSTRAINT fk_gc_srs FOREIGN KEY (srs_id) REFERENCES gpkg_spatial_ref_sys (srs_id)
+);
+"#;
+
+/// Filters applied to exported features, like the corresponding API query parameters
+#[derive(Deserialize, Debug, Default)]
+pub struct DumpParams {
+    pub bbox: Option<String>,
+    pub datetime: Option<String>,
+    /// CQL2 text filter expression
+    pub filter: Option<String>,
+}
+
+/// SQLite column type of a property value
+fn column_type(value: &Value) -> &'static str {
+    match value {
+        Value::Bool(_) => "BOOLEAN",
+        Value::Number(n) if n.is_i64() => "INTEGER",
+        Value::Number(_) => "REAL",
+        _ => "TEXT",
+    }
+}
+
+/// SQLite column type of a property schema
+fn schema_column_type(property: &QueryableProperty) -> &'static str {
+    match (&property.type_, property.format.as_deref()) {
+        (Some(QueryableType::Bool), _) => "BOOLEAN",
+        (Some(QueryableType::Integer), _) => "INTEGER",
+        (Some(QueryableType::Number), _) => "REAL",
+        (Some(QueryableType::Datetime), _) | (_, Some("date-time")) => "DATETIME",
+        (_, Some("date")) => "DATE",
+        _ => "TEXT",
+    }
+}
+
+/// Attribute columns with SQLite type from the properties schema of a collection.
+/// Without schema, the columns are derived from the queryables and all properties of the first features.
+async fn property_columns(
+    inventory: &Inventory,
+    collection_id: &str,
+    features: &[CoreFeature],
+) -> BTreeMap<String, &'static str> {
+    if let Some(schema) = inventory.collection_properties_schema(collection_id).await {
+        return schema
+            .properties
+            .iter()
+            .map(|(name, property)| (name.clone(), schema_column_type(property)))
+            .collect();
+    }
+    let mut columns = BTreeMap::new();
+    if let Some(queryables) = inventory.collection_queryables(collection_id).await {
+        for (name, property) in &queryables.properties {
+            columns.insert(name.clone(), schema_column_type(property));
+        }
+    }
+    let properties = features
+        .iter()
+        .filter_map(|f| f.properties.as_ref().and_then(Value::as_object));
+    for (name, value) in properties.clone().flatten() {
+        if !value.is_null() {
+            columns.entry(name.clone()).or_insert(column_type(value));
+        }
+    }
+    // Properties without any value
+    for name in properties.flat_map(|p| p.keys()) {
+        columns.entry(name.clone()).or_insert("TEXT");
+    }
+    columns
+}
+
+fn quote_ident(ident: &str) -> Result<String> {
+    Ok(SqlIdent::new(ident)?.quoted())
+}
+
+/// Check for Z values in GeoJSON coordinates
+fn coords_have_z(coords: &Value) -> bool {
+    match coords.as_array().map(Vec::as_slice) {
+        Some([Value::Number(_), Value::Number(_), Value::Number(_), ..]) => true,
+        Some([Value::Number(_), ..]) => false,
+        Some(parts) => parts.iter().any(coords_have_z),
+        None => false,
+    }
+}
+
+fn geometry_has_z(geometry: &Value) -> bool {
+    if let Some(coords) = geometry.get("coordinates") {
+        coords_have_z(coords)
+    } else if let Some(geometries) = geometry.get("geometries").and_then(Value::as_array) {
+        geometries.iter().any(geometry_has_z)
+    } else {
+        false
+    }
+}
+
+/// Bounding box of GeoJSON coordinates
+fn extend_bbox(coords: &Value, bbox: &mut Option<[f64; 4]>) {
+    match coords.as_array().map(Vec::as_slice) {
+        Some([Value::Number(x), Value::Number(y), ..]) => {
+            let (x, y) = (x.as_f64().unwrap_or(0.0), y.as_f64().unwrap_or(0.0));
+            let b = bbox.get_or_insert([x, y, x, y]);
+            *b = [b[0].min(x), b[1].min(y), b[2].max(x), b[3].max(y)];
+        }
+        Some(parts) => parts.iter().for_each(|part| extend_bbox(part, bbox)),
+        None => {}
+    }
+}
+
+fn geometry_bbox(geometry: &Value, bbox: &mut Option<[f64; 4]>) {
+    if let Some(coords) = geometry.get("coordinates") {
+        extend_bbox(coords, bbox);
+    } else if let Some(geometries) = geometry.get("geometries").and_then(Value::as_array) {
+        geometries.iter().for_each(|geom| geometry_bbox(geom, bbox));
+    }
+}
+
+struct GpkgWriter {
+    conn: SqliteConnection,
+    table: String,
+    columns: Vec<String>,
+    bbox: Option<[f64; 4]>,
+    /// Geometries with and without Z values written
+    z_geometries: bool,
+    xy_geometries: bool,
+}
+
+impl GpkgWriter {
+    async fn create(
+        path: &Path,
+        table: &str,
+        column_types: &BTreeMap<String, &'static str>,
+    ) -> Result<Self> {
+        if path.exists() {
+            return Err(Error::OutputFileExists(path.display().to_string()));
+        }
+        let mut conn = SqliteConnectOptions::new()
+            .filename(path)
+            .create_if_missing(true)
+            .connect()
+            .await?;
+        conn.execute(GPKG_SCHEMA).await?;
+        let columns: Vec<String> = column_types
+            .keys()
+            .filter(|name| !name.eq_ignore_ascii_case("fid") && *name != "geom")
+            .cloned()
+            .collect();
+        let mut create = format!(
+            "CREATE TABLE {} (fid INTEGER PRIMARY KEY AUTOINCREMENT, geom GEOMETRY",
+            quote_ident(table)?
+        );
+        for name in &columns {
+            create.push_str(&format!(", {} {}", quote_ident(name)?, column_types[name]));
+        }
+        create.push(')');
+        sqlx::query(&create).execute(&mut conn).await?;
+        sqlx::query("INSERT INTO gpkg_contents (table_name, data_type, identifier, srs_id) VALUES (?, 'features', ?, 4326)")
+            .bind(table)
+            .bind(table)
+            .execute(&mut conn)
+            .await?;
+        sqlx::query("INSERT INTO gpkg_geometry_columns VALUES (?, 'geom', 'GEOMETRY', 4326, 0, 0)")
+            .bind(table)
+            .execute(&mut conn)
+            .await?;
+        Ok(GpkgWriter {
+            conn,
+            table: table.to_string(),
+            columns,
+            bbox: None,
+            z_geometries: false,
+            xy_geometries: false,
+        })
+    }
+
+    async fn write_features(&mut self, features: &[CoreFeature]) -> Result<()> {
+        let placeholders = vec!["?"; self.columns.len() + 2].join(", ");
+        let names = self
+            .columns
+            .iter()
+            .map(|c| quote_ident(c))
+            .collect::<Result<Vec<_>>>()?;
+        let sql = format!(
+            "INSERT INTO {} (fid, geom{}{}) VALUES ({placeholders})",
+            quote_ident(&self.table)?,
+            if names.is_empty() { "" } else { ", " },
+            names.join(", ")
+        );
+        let mut tx = self.conn.begin().await?;
+        for feature in features {
+            let fid = feature.id.as_ref().and_then(|id| id.parse::<i64>().ok());
+            let geom = if feature.geometry.is_null() {
+                None
+            } else {
+                geometry_bbox(&feature.geometry, &mut self.bbox);
+                let dims = if geometry_has_z(&feature.geometry) {
+                    self.z_geometries = true;
+                    CoordDimensions::xyz()
+                } else {
+                    self.xy_geometries = true;
+                    CoordDimensions::xy()
+                };
+                Some(GeoJsonString(feature.geometry.to_string()).to_gpkg_wkb(
+                    dims,
+                    Some(4326),
+                    Vec::new(),
+                )?)
+            };
+            let mut query = sqlx::query(&sql).bind(fid).bind(geom);
+            for column in &self.columns {
+                let value = feature.properties.as_ref().and_then(|p| p.get(column));
+                query = match value {
+                    None | Some(Value::Null) => query.bind(None::<String>),
+                    Some(Value::Bool(v)) => query.bind(*v),
+                    Some(Value::Number(n)) if n.is_i64() => query.bind(n.as_i64()),
+                    Some(Value::Number(n)) => query.bind(n.as_f64()),
+                    Some(Value::String(s)) => query.bind(s.clone()),
+                    Some(v) => query.bind(v.to_string()),
+                };
+            }
+            query.execute(&mut *tx).await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn finish(mut self) -> Result<()> {
+        if let Some([min_x, min_y, max_x, max_y]) = self.bbox {
+            sqlx::query("UPDATE gpkg_contents SET min_x = ?, min_y = ?, max_x = ?, max_y = ? WHERE table_name = ?")
+                .bind(min_x)
+                .bind(min_y)
+                .bind(max_x)
+                .bind(max_y)
+                .bind(&self.table)
+                .execute(&mut self.conn)
+                .await?;
+        }
+        // Z values prohibited (0), mandatory (1) or optional (2)
+        let z = match (self.z_geometries, self.xy_geometries) {
+            (false, _) => 0,
+            (true, false) => 1,
+            (true, true) => 2,
+        };
+        sqlx::query("UPDATE gpkg_geometry_columns SET z = ? WHERE table_name = ?")
+            .bind(z)
+            .bind(&self.table)
+            .execute(&mut self.conn)
+            .await?;
+        self.conn.close().await?;
+        Ok(())
+    }
+}
+
+/// Pages of collection items read with an offset
+fn paged_items<'a>(
+    inventory: &'a Inventory,
+    collection_id: &'a str,
+    filter: FilterParams,
+) -> impl Stream<Item = Result<Vec<CoreFeature>>> + 'a {
+    stream::try_unfold(Some(0), move |offset: Option<u32>| {
+        let filter = offset.map(|offset| filter.with_offset(offset));
+        async move {
+            let Some(filter) = filter else {
+                return Ok(None);
+            };
+            let page = inventory
+                .collection_items(collection_id, &filter)
+                .await?
+                .ok_or_else(|| Error::CollectionUnavailable(collection_id.to_string()))?;
+            if page.features.is_empty() {
+                return Ok(None);
+            }
+            let count = page.features.len() as u32;
+            let next = (count >= PAGE_SIZE).then_some(filter.offset.unwrap_or(0) + count);
+            Ok(Some((page.features, next)))
+        }
+    })
+}
+
+/// Write the features of a collection matching the API filter parameters into a new GeoPackage.
+/// Returns the number of exported features.
+///
+/// Features are read with a single query from sources supporting streaming, which guarantees that
+/// every feature is exported exactly once. Other sources are read in pages of `PAGE_SIZE` features.
+pub async fn dump_collection(
+    inventory: &Inventory,
+    collection_id: &str,
+    params: &DumpParams,
+    output: &Path,
+) -> Result<u64> {
+    if inventory.core_collection(collection_id).is_none() {
+        return Err(Error::CollectionNotFound(collection_id.to_string()));
+    }
+    if output.exists() {
+        return Err(Error::OutputFileExists(output.display().to_string()));
+    }
+    let filter = FilterParams {
+        limit: Some(PAGE_SIZE),
+        bbox: params.bbox.clone(),
+        datetime: params.datetime.clone(),
+        filter: params.filter.clone(),
+        ..Default::default()
+    };
+    filter.cql2_filter().map_err(|_| Error::QueryParams)?;
+    let unlimited = FilterParams {
+        limit: Some(0),
+        ..filter.clone()
+    };
+    let mut pages: BoxStream<'_, Result<Vec<CoreFeature>>> = match inventory
+        .collection_items_stream(collection_id, &unlimited)
+        .await?
+    {
+        Some((_, features)) => features
+            .try_chunks(PAGE_SIZE as usize)
+            .map_err(|e| e.1)
+            .boxed(),
+        None => paged_items(inventory, collection_id, filter).boxed(),
+    };
+    let mut writer: Option<GpkgWriter> = None;
+    let mut count = 0;
+    while let Some(features) = pages.try_next().await? {
+        if writer.is_none() {
+            let columns = property_columns(inventory, collection_id, &features).await;
+            writer = Some(GpkgWriter::create(output, collection_id, &columns).await?);
+        }
+        if let Some(writer) = writer.as_mut() {
+            writer.write_features(&features).await?;
+        }
+        count += features.len() as u64;
+        info!("{count} features of `{collection_id}` exported");
+    }
+    let writer = match writer {
+        Some(writer) => writer,
+        None => {
+            let columns = property_columns(inventory, collection_id, &[]).await;
+            GpkgWriter::create(output, collection_id, &columns).await?
+        }
+    };
+    writer.finish().await?;
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::CollectionsCfg;
+    use sqlx::Row;
+
+    #[test]
+    fn geometry_extent() {
+        let mut bbox = None;
+        let geom =
+            serde_json::json!({"type": "LineString", "coordinates": [[1.0, 5.0], [3.0, -2.0]]});
+        geometry_bbox(&geom, &mut bbox);
+        assert_eq!(bbox, Some([1.0, -2.0, 3.0, 5.0]));
+    }
+
+    #[test]
+    fn geometry_dimensions() {
+        let geom = serde_json::json!({"type": "Point", "coordinates": [1.0, 5.0]});
+        assert!(!geometry_has_z(&geom));
+        let geom = serde_json::json!({"type": "LineString", "coordinates": [[1.0, 5.0, 2.0], [3.0, -2.0, 4.0]]});
+        assert!(geometry_has_z(&geom));
+        let geom = serde_json::json!({"type": "GeometryCollection", "geometries": [
+            {"type": "Point", "coordinates": [1.0, 5.0]},
+            {"type": "MultiPolygon", "coordinates": [[], [[[0.0, 0.0, 1.0], [1.0, 0.0, 1.0], [0.0, 1.0, 1.0], [0.0, 0.0, 1.0]]]]}
+        ]});
+        assert!(geometry_has_z(&geom));
+    }
+
+    #[tokio::test]
+    async fn dump_gpkg() {
+        let inventory = Inventory::scan(&CollectionsCfg::from_path("../assets")).await;
+        let dir = tempfile::tempdir().unwrap();
+        let output = dir.path().join("lakes.gpkg");
+        let params = DumpParams {
+            bbox: Some("-10,40,10,60".to_string()),
+            ..Default::default()
+        };
+        let count = dump_collection(&inventory, "ne_10m_lakes", &params, &output)
+            .await
+            .unwrap();
+        assert!(count > 0 && count < 1355);
+
+        let mut conn = SqliteConnectOptions::new()
+            .filename(&output)
+            .connect()
+            .await
+            .unwrap();
+        let row = sqlx::query("SELECT count(*) AS cnt FROM ne_10m_lakes")
+            .fetch_one(&mut conn)
+            .await
+            .unwrap();
+        assert_eq!(row.get::<i64, _>("cnt") as u64, count);
+
+        let columns: Vec<String> =
+            sqlx::query("SELECT name FROM pragma_table_info('ne_10m_lakes')")
+                .fetch_all(&mut conn)
+                .await
+                .unwrap()
+                .iter()
+                .map(|row| row.get("name"))
+                .collect();
+        assert!(columns.contains(&"name".to_string()));
+        assert!(columns.contains(&"scalerank".to_string()));
+
+        // Existing files are not overwritten
+        assert!(matches!(
+            dump_collection(&inventory, "ne_10m_lakes", &params, &output).await,
+            Err(Error::OutputFileExists(_))
+        ));
+        assert!(
+            dump_collection(&inventory, "unknown", &params, &dir.path().join("x.gpkg"))
+                .await
+                .is_err()
+        );
+    }
+}
@@ -1,20 +1,43 @@
-use crate::filter_params::FilterParams;
+use crate::config::DownloadsCfg;
+use crate::crs::Crs;
+use crate::datasource::{feature_etag, IfMatch};
+use crate::dump::{dump_collection, DumpParams};
+use crate::error::Error as FeatureError;
+use crate::filter_params::{AggregateParams, ChangesParams, FilterParams};
 use crate::inventory::Inventory;
+use crate::output::{
+    flatgeobuf, geobuf, jsonfg_feature, jsonfg_features, shapefile_zip, OutputFormat, WGS84_PRJ,
+};
+use crate::search::ItemSearch;
 use crate::service::FeatureService;
-use actix_web::{web, Error, HttpRequest, HttpResponse};
+use actix_files::NamedFile;
+use actix_web::http::header::HeaderValue;
+use actix_web::web::Bytes;
+use actix_web::{
+    http::header, web, Error, FromRequest, HttpRequest, HttpResponse, HttpResponseBuilder,
+};
 use bbox_core::api::OgcApiInventory;
+use bbox_core::auth::access::{authorized_identity, AdminGuard};
 use bbox_core::endpoints::absurl;
-use bbox_core::ogcapi::{ApiLink, CoreCollections};
+use bbox_core::jobs::JobQueue;
+use bbox_core::ogcapi::{ApiLink, CoreCollection, CoreCollections, CoreFeature, CoreFeatures};
+use bbox_core::reload::{Current, Reloadable};
 use bbox_core::service::ServiceEndpoints;
 use bbox_core::templates::{create_env_embedded, html_accepted, render_endpoint};
+use futures::stream::{self, BoxStream};
+use futures::{future, Stream, StreamExt};
+use log::warn;
 use minijinja::{context, Environment};
 use once_cell::sync::Lazy;
+use rand::Rng;
 use std::collections::HashMap;
+use std::path::Path;
+use std::time::Duration;
 
 /// the feature collections in the dataset
 async fn collections(
     _ogcapi: web::Data<OgcApiInventory>,
-    inventory: web::Data<Inventory>,
+    inventory: Current<Inventory>,
     req: HttpRequest,
 ) -> Result<HttpResponse, Error> {
     let collections = CoreCollections {
@@ -43,11 +66,13 @@ async fn collections(
 
 /// describe the feature collection with id `collectionId`
 async fn collection(
-    inventory: web::Data<Inventory>,
+    inventory: Current<Inventory>,
     req: HttpRequest,
     collection_id: web::Path<String>,
 ) -> Result<HttpResponse, Error> {
-    if let Some(collection) = inventory.core_collection(&collection_id) {
+    if !inventory.collection_available(&collection_id) {
+        Ok(not_found_or_unavailable(&inventory, &collection_id))
+    } else if let Some(collection) = inventory.core_collection(&collection_id) {
         if html_accepted(&req).await {
             render_endpoint(
                 &TEMPLATES,
@@ -65,7 +90,7 @@ async fn collection(
 
 /// describe the queryables available in the collection with id `collectionId`
 async fn queryables(
-    inventory: web::Data<Inventory>,
+    inventory: Current<Inventory>,
     req: HttpRequest,
     collection_id: web::Path<String>,
 ) -> Result<HttpResponse, Error> {
@@ -83,13 +108,129 @@ async fn queryables(
                 .json(queryables))
         }
     } else {
-        Ok(HttpResponse::NotFound().finish())
+        Ok(not_found_or_unavailable(&inventory, &collection_id))
+    }
+}
+
+/// JSON Schema of the item properties of the collection with id `collectionId`
+async fn properties_schema(
+    inventory: Current<Inventory>,
+    collection_id: web::Path<String>,
+) -> Result<HttpResponse, Error> {
+    if let Some(schema) = inventory.collection_properties_schema(&collection_id).await {
+        Ok(HttpResponse::Ok()
+            .content_type("application/schema+json")
+            .json(schema))
+    } else {
+        Ok(not_found_or_unavailable(&inventory, &collection_id))
+    }
+}
+
+/// Item filter of `/items` request parameters, without output format
+fn items_filter(
+    collection: &CoreCollection,
+    mut filters: HashMap<String, String>,
+) -> std::result::Result<FilterParams, HttpResponse> {
+    let bbox = filters.remove("bbox");
+    let datetime = filters.remove("datetime");
+    let filter = filters.remove("filter");
+    let crs = filters.remove("crs");
+    let bbox_crs = filters.remove("bbox-crs");
+    let cursor = filters.remove("cursor");
+    let properties = filters.remove("properties");
+    let sortby = filters.remove("sortby");
+    let q = filters.remove("q");
+    let skip_geometry = match filters.remove("skipgeometry").as_deref() {
+        None => None,
+        Some("true") => Some(true),
+        Some("false") => Some(false),
+        Some(v) => {
+            return Err(HttpResponse::BadRequest().body(format!("Invalid skipGeometry `{v}`")))
+        }
+    };
+    let zoom = non_negative_param(&mut filters, "zoom")?;
+    let scale = non_negative_param(&mut filters, "scale")?;
+    for uri in [&crs, &bbox_crs] {
+        if let Err(e) = check_crs(collection, uri.as_deref()) {
+            return Err(HttpResponse::BadRequest().body(e));
+        }
+    }
+    if let Some(lang) = filters.remove("filter-lang") {
+        if lang != "cql2-text" {
+            return Err(
+                HttpResponse::BadRequest().body(format!("Unsupported filter-lang `{lang}`"))
+            );
+        }
+    }
+
+    let offset = if let Some(offset_str) = filters.get("offset") {
+        match offset_str.parse::<u32>() {
+            Ok(o) => {
+                filters.remove("offset");
+                Some(o)
+            }
+            Err(_e) => return Err(HttpResponse::BadRequest().finish()),
+        }
+    } else {
+        None
+    };
+    let limit = if let Some(limit_str) = filters.get("limit") {
+        match limit_str.parse::<u32>() {
+            Ok(o) => {
+                filters.remove("limit");
+                Some(o)
+            }
+            Err(_e) => return Err(HttpResponse::BadRequest().finish()),
+        }
+    } else {
+        None
+    };
+
+    let fp = FilterParams {
+        offset,
+        cursor,
+        limit,
+        bbox,
+        datetime,
+        filter,
+        ids: None,
+        intersects: None,
+        crs,
+        bbox_crs,
+        properties,
+        skip_geometry,
+        sortby,
+        q,
+        zoom,
+        scale,
+        filters,
+    };
+    if let Err(e) = fp.cql2_filter() {
+        return Err(HttpResponse::BadRequest().body(format!("Invalid filter: {e}")));
+    }
+    if let Err(e) = fp.cursor_key() {
+        return Err(HttpResponse::BadRequest().body(e.to_string()));
+    }
+    Ok(fp)
+}
+
+/// Remove and parse a non-negative number parameter
+fn non_negative_param(
+    filters: &mut HashMap<String, String>,
+    name: &str,
+) -> std::result::Result<Option<f64>, HttpResponse> {
+    let Some(value) = filters.remove(name) else {
+        return Ok(None);
+    };
+    match value.parse::<f64>() {
+        Ok(v) if v.is_finite() && v >= 0.0 => Ok(Some(v)),
+        _ => Err(HttpResponse::BadRequest().body(format!("Invalid {name} `{value}`"))),
     }
 }
 
 /// fetch features
 async fn features(
-    inventory: web::Data<Inventory>,
+    inventory: Current<Inventory>,
     req: HttpRequest,
     collection_id: web::Path<String>,
 ) -> Result<HttpResponse, Error> {
@@ -103,88 +244,491 @@ async fn features(
                 Err(_e) => return Ok(HttpResponse::BadRequest().finish()),
             };
 
-        let bbox = filters.remove("bbox");
-        let datetime = filters.remove("datetime");
-
-        let offset = if let Some(offset_str) = filters.get("offset") {
-            match offset_str.parse::<u32>() {
-                Ok(o) => {
-                    filters.remove("offset");
-                    Some(o)
-                }
-                Err(_e) => return Ok(HttpResponse::BadRequest().finish()),
-            }
-        } else {
-            None
-        };
-        let limit = if let Some(limit_str) = filters.get("limit") {
-            match limit_str.parse::<u32>() {
-                Ok(o) => {
-                    filters.remove("limit");
-                    Some(o)
+        let format = match filters.remove("f") {
+            Some(f) => match OutputFormat::from_param(&f) {
+                Some(format) => format,
+                None => {
+                    return Ok(HttpResponse::BadRequest().body(format!("Unsupported format `{f}`")))
                 }
-                Err(_e) => return Ok(HttpResponse::BadRequest().finish()),
-            }
-        } else {
-            None
+            },
+            None => items_format(&req).await,
         };
-
-        let fp = FilterParams {
-            offset,
-            limit,
-            bbox,
-            datetime,
-            filters,
+        let fp = match items_filter(collection, filters) {
+            Ok(fp) => fp,
+            Err(response) => return Ok(response),
         };
+        if fp.q.is_some() && !inventory.collection_searchable(&collection_id) {
+            return Ok(HttpResponse::BadRequest().body("Collection has no search fields"));
+        }
+        if fp.skip_geometry() && format == OutputFormat::FlatGeobuf {
+            return Ok(HttpResponse::BadRequest().body("skipGeometry not supported for FlatGeobuf"));
+        }
 
-        if let Some(features) = inventory.collection_items(&collection_id, &fp).await {
-            if html_accepted(&req).await {
-                render_endpoint(
-                    &TEMPLATES,
-                    "features.html",
-                    context!(cur_menu=>"Collections", collection => &collection, features => &features),
-                ).await
-            } else {
-                Ok(HttpResponse::Ok()
-                    .content_type("application/geo+json")
-                    .json(features))
+        if format == OutputFormat::FlatGeobuf {
+            let features = match inventory.collection_items_stream(&collection_id, &fp).await {
+                Ok(Some((_, stream))) => stream,
+                Ok(None) => match inventory.collection_items(&collection_id, &fp).await {
+                    Ok(Some(features)) => {
+                        stream::iter(features.features.into_iter().map(Ok)).boxed()
+                    }
+                    Ok(None) => return Ok(not_found_or_unavailable(&inventory, &collection_id)),
+                    Err(e) => return Ok(query_error(e)),
+                },
+                Err(e) => return Ok(query_error(e)),
+            };
+            return match flatgeobuf(&collection_id, features).await {
+                Ok(buf) => Ok(items_response(collection, &fp, format).body(buf)),
+                Err(e) => Ok(query_error(e)),
+            };
+        }
+        if format == OutputFormat::GeoJson {
+            match inventory.collection_items_stream(&collection_id, &fp).await {
+                Ok(Some((features, stream))) => {
+                    let body = match geojson_stream(&features, stream) {
+                        Ok(body) => body,
+                        Err(e) => return Ok(query_error(e)),
+                    };
+                    return Ok(items_response(collection, &fp, format).streaming(body));
+                }
+                Ok(None) => {}
+                Err(e) => return Ok(query_error(e)),
             }
-        } else {
-            Ok(HttpResponse::NotFound().finish())
+        }
+        match inventory.collection_items(&collection_id, &fp).await {
+            Ok(Some(features)) => {
+                if format == OutputFormat::Html {
+                    // Filter form fields
+                    let queryables = inventory.collection_queryables(&collection_id).await;
+                    render_endpoint(
+                        &TEMPLATES,
+                        "features.html",
+                        context!(cur_menu=>"Collections", collection => &collection, features => &features, map => inventory.html_map,
+                            queryables => &queryables, filters => &fp.filters, bbox => &fp.bbox, limit => fp.limit, args => fp.as_args()),
+                    ).await
+                } else if format == OutputFormat::Geobuf {
+                    match geobuf(&features.features) {
+                        Ok(buf) => Ok(items_response(collection, &fp, format).body(buf)),
+                        Err(e) => Ok(query_error(e)),
+                    }
+                } else if format == OutputFormat::Shapefile {
+                    // Only WGS84 longitude/latitude output has a known .prj
+                    let prj = match fp.crs() {
+                        Ok(Some(crs)) if crs.srid != 4326 || crs.lat_lon => None,
+                        _ => Some(WGS84_PRJ),
+                    };
+                    match shapefile_zip(&collection_id, &features.features, prj) {
+                        Ok(buf) => Ok(items_response(collection, &fp, format)
+                            .insert_header((
+                                header::CONTENT_DISPOSITION,
+                                format!("attachment; filename=\"{collection_id}.zip\""),
+                            ))
+                            .body(buf)),
+                        Err(e) => Ok(query_error(e)),
+                    }
+                } else if format == OutputFormat::JsonFg {
+                    let temporal = inventory.collection_temporal_properties(&collection_id);
+                    let coord_ref_sys =
+                        output_crs(collection, fp.crs.as_deref()).unwrap_or_default();
+                    match jsonfg_features(&features, &coord_ref_sys, temporal) {
+                        Ok(json) => Ok(items_response(collection, &fp, format).json(json)),
+                        Err(e) => Ok(query_error(e)),
+                    }
+                } else {
+                    Ok(items_response(collection, &fp, format).json(features))
+                }
+            }
+            Ok(None) => Ok(not_found_or_unavailable(&inventory, &collection_id)),
+            Err(e) => Ok(query_error(e)),
         }
     } else {
         Ok(HttpResponse::NotFound().finish())
     }
 }
 
+/// Statistics of the items matching the `/items` filters
+// /collections/{collectionId}/aggregate
+async fn aggregate(
+    inventory: Current<Inventory>,
+    req: HttpRequest,
+    collection_id: web::Path<String>,
+) -> HttpResponse {
+    let Some(collection) = inventory.core_collection(&collection_id) else {
+        return HttpResponse::NotFound().finish();
+    };
+    let mut filters: HashMap<String, String> =
+        match serde_urlencoded::from_str::<Vec<(String, String)>>(req.query_string()) {
+            Ok(f) => f
+                .iter()
+                .map(|k| (k.0.to_lowercase(), k.1.to_owned()))
+                .collect(),
+            Err(_e) => return HttpResponse::BadRequest().finish(),
+        };
+    let params = AggregateParams {
+        functions: filters.remove("functions"),
+        fields: filters.remove("fields"),
+        groupby: filters.remove("groupby"),
+    };
+    if let Err(e) = params.functions() {
+        return HttpResponse::BadRequest().body(e.to_string());
+    }
+    let fp = match items_filter(collection, filters) {
+        Ok(fp) => fp,
+        Err(response) => return response,
+    };
+    if fp.q.is_some() && !inventory.collection_searchable(&collection_id) {
+        return HttpResponse::BadRequest().body("Collection has no search fields");
+    }
+    match inventory
+        .collection_aggregate(&collection_id, &fp, &params)
+        .await
+    {
+        Ok(Some(json)) => HttpResponse::Ok().json(json),
+        Ok(None) => HttpResponse::BadRequest().body("Collection doesn't support aggregation"),
+        Err(e) => query_error(e),
+    }
+}
+
+/// Features created or updated since a timestamp, for incremental replication
+// /collections/{collectionId}/changes
+async fn changes(
+    inventory: Current<Inventory>,
+    collection_id: web::Path<String>,
+    params: web::Query<ChangesParams>,
+) -> HttpResponse {
+    match inventory.collection_changes(&collection_id, &params).await {
+        Ok(Some(features)) => HttpResponse::Ok()
+            .content_type("application/geo+json")
+            .json(features),
+        Ok(None) => HttpResponse::NotFound().finish(),
+        Err(e) => query_error(e),
+    }
+}
+
 /// fetch a single feature
 async fn feature(
-    inventory: web::Data<Inventory>,
+    inventory: Current<Inventory>,
     req: HttpRequest,
     path: web::Path<(String, String)>,
 ) -> Result<HttpResponse, Error> {
     let (collection_id, feature_id) = path.into_inner();
     if let Some(collection) = inventory.core_collection(&collection_id) {
-        if let Some(feature) = inventory.collection_item(&collection_id, &feature_id).await {
-            if html_accepted(&req).await {
-                render_endpoint(
-                    &TEMPLATES,
-                    "feature.html",
-                    context!(cur_menu=>"Collections", collection => &collection, feature => &feature),
-                ).await
-            } else {
-                Ok(HttpResponse::Ok()
-                    .content_type("application/geo+json")
-                    .json(feature))
+        let mut params = serde_urlencoded::from_str::<HashMap<String, String>>(req.query_string())
+            .unwrap_or_default();
+        let crs_param = params.remove("crs");
+        let jsonfg = match params.remove("f") {
+            Some(f) => OutputFormat::from_param(&f) == Some(OutputFormat::JsonFg),
+            None => feature_format(&req).await == Some(OutputFormat::JsonFg),
+        };
+        if let Err(e) = check_crs(collection, crs_param.as_deref()) {
+            return Ok(HttpResponse::BadRequest().body(e));
+        }
+        let crs = crs_param.as_deref().and_then(|uri| Crs::from_uri(uri).ok());
+        let feature = match inventory
+            .collection_item(&collection_id, &feature_id, crs.as_ref())
+            .await
+        {
+            Ok(Some(feature)) => feature,
+            Ok(None) => return Ok(not_found_or_unavailable(&inventory, &collection_id)),
+            Err(e) => return Ok(query_error(e)),
+        };
+        if jsonfg {
+            let temporal = inventory.collection_temporal_properties(&collection_id);
+            let coord_ref_sys = output_crs(collection, crs_param.as_deref()).unwrap_or_default();
+            match jsonfg_feature(&feature, &coord_ref_sys, temporal) {
+                Ok(json) => Ok(HttpResponse::Ok()
+                    .content_type(OutputFormat::JsonFg.content_type())
+                    .insert_header((header::ETAG, feature_etag(&feature)))
+                    .insert_header(("Content-Crs", format!("<{coord_ref_sys}>")))
+                    .json(json)),
+                Err(e) => Ok(query_error(e)),
             }
+        } else if html_accepted(&req).await {
+            render_endpoint(
+                &TEMPLATES,
+                "feature.html",
+                context!(cur_menu=>"Collections", collection => &collection, feature => &feature, map => inventory.html_map),
+            ).await
         } else {
-            Ok(HttpResponse::NotFound().finish())
+            let mut response = HttpResponse::Ok();
+            response
+                .content_type("application/geo+json")
+                .insert_header((header::ETAG, feature_etag(&feature)));
+            if let Some(content_crs) = content_crs(collection, crs_param.as_deref()) {
+                response.insert_header(("Content-Crs", content_crs));
+            }
+            Ok(response.json(feature))
         }
     } else {
         Ok(HttpResponse::NotFound().finish())
     }
 }
 
+/// add a new feature to the collection with id `collectionId`
+async fn create_feature(
+    inventory: Current<Inventory>,
+    req: HttpRequest,
+    collection_id: web::Path<String>,
+    feature: web::Json<serde_json::Value>,
+) -> Result<HttpResponse, Error> {
+    if let Err(response) = check_transaction(&inventory, &req, &collection_id) {
+        return Ok(response);
+    }
+    match inventory.create_item(&collection_id, &feature).await {
+        Ok(feature_id) => Ok(HttpResponse::Created()
+            .insert_header((
+                header::LOCATION,
+                absurl(
+                    &req,
+                    &format!("/collections/{collection_id}/items/{feature_id}"),
+                ),
+            ))
+            .finish()),
+        Err(e) => Ok(transaction_error(e)),
+    }
+}
+
+/// replace the feature with id `featureId`
+async fn replace_feature(
+    inventory: Current<Inventory>,
+    req: HttpRequest,
+    path: web::Path<(String, String)>,
+    feature: web::Json<serde_json::Value>,
+) -> Result<HttpResponse, Error> {
+    let (collection_id, feature_id) = path.into_inner();
+    let if_match = match check_transaction(&inventory, &req, &collection_id) {
+        Ok(if_match) => if_match,
+        Err(response) => return Ok(response),
+    };
+    let result = inventory
+        .replace_item(&collection_id, &feature_id, &feature, if_match.as_ref())
+        .await;
+    Ok(transaction_response(result))
+}
+
+/// update properties and geometry of the feature with id `featureId`
+async fn update_feature(
+    inventory: Current<Inventory>,
+    req: HttpRequest,
+    path: web::Path<(String, String)>,
+    feature: web::Json<serde_json::Value>,
+) -> Result<HttpResponse, Error> {
+    let (collection_id, feature_id) = path.into_inner();
+    let if_match = match check_transaction(&inventory, &req, &collection_id) {
+        Ok(if_match) => if_match,
+        Err(response) => return Ok(response),
+    };
+    let result = inventory
+        .update_item(&collection_id, &feature_id, &feature, if_match.as_ref())
+        .await;
+    Ok(transaction_response(result))
+}
+
+/// delete the feature with id `featureId`
+async fn delete_feature(
+    inventory: Current<Inventory>,
+    req: HttpRequest,
+    path: web::Path<(String, String)>,
+) -> Result<HttpResponse, Error> {
+    let (collection_id, feature_id) = path.into_inner();
+    let if_match = match check_transaction(&inventory, &req, &collection_id) {
+        Ok(if_match) => if_match,
+        Err(response) => return Ok(response),
+    };
+    let result = inventory
+        .delete_item(&collection_id, &feature_id, if_match.as_ref())
+        .await;
+    Ok(transaction_response(result))
+}
+
+/// Check write access and parse the `If-Match` precondition, which is checked within the transaction.
+/// Returns an error response if the transaction is not allowed.
+/// Transactions require a user authorized by an access rule of the collection.
+fn check_transaction(
+    inventory: &Inventory,
+    req: &HttpRequest,
+    collection_id: &str,
+) -> Result<Option<IfMatch>, HttpResponse> {
+    if inventory.core_collection(collection_id).is_none() {
+        return Err(HttpResponse::NotFound().finish());
+    }
+    if !inventory.collection_writable(collection_id) {
+        return Err(HttpResponse::MethodNotAllowed().finish());
+    }
+    if authorized_identity(req).is_none() {
+        return Err(HttpResponse::Unauthorized()
+            .insert_header((header::WWW_AUTHENTICATE, "Bearer"))
+            .finish());
+    }
+    match req
+        .headers()
+        .get(header::IF_MATCH)
+        .map(|value| value.to_str())
+    {
+        None => Ok(None),
+        Some(Ok(tags)) => Ok(Some(IfMatch::from_header(tags))),
+        Some(Err(_)) => Err(HttpResponse::PreconditionFailed().finish()),
+    }
+}
+
+fn transaction_response(result: crate::error::Result<bool>) -> HttpResponse {
+    match result {
+        Ok(true) => HttpResponse::NoContent().finish(),
+        Ok(false) => HttpResponse::NotFound().finish(),
+        Err(e) => transaction_error(e),
+    }
+}
+
+fn transaction_error(e: FeatureError) -> HttpResponse {
+    e.error_response()
+}
+
+/// search items of multiple collections
+async fn search(inventory: Current<Inventory>, req: HttpRequest) -> Result<HttpResponse, Error> {
+    match ItemSearch::from_query(req.query_string()) {
+        Ok(params) => search_items(&inventory, &params).await,
+        Err(e) => Ok(HttpResponse::BadRequest().body(e.to_string())),
+    }
+}
+
+/// search items of multiple collections with ItemSearch JSON body
+async fn search_post(
+    inventory: Current<Inventory>,
+    params: web::Json<ItemSearch>,
+) -> Result<HttpResponse, Error> {
+    search_items(&inventory, &params).await
+}
+
+async fn search_items(inventory: &Inventory, params: &ItemSearch) -> Result<HttpResponse, Error> {
+    let fp = match params.filter_params() {
+        Ok(fp) => fp,
+        Err(e) => return Ok(HttpResponse::BadRequest().body(e.to_string())),
+    };
+    if let Err(e) = fp.cql2_filter() {
+        return Ok(HttpResponse::BadRequest().body(format!("Invalid filter: {e}")));
+    }
+    let mut features = inventory.search(params.collections(), &fp).await;
+    for feature in &mut features.features {
+        params.apply_fields(feature);
+    }
+    let number_returned = features.features.len() as u64;
+    features
+        .links
+        .extend(params.paging_links(features.number_matched, number_returned));
+    Ok(HttpResponse::Ok()
+        .content_type("application/geo+json")
+        .json(features))
+}
+
+/// Output format requested by path suffix or Accept header
+async fn items_format(req: &HttpRequest) -> OutputFormat {
+    if req.path().ends_with(".fgb") {
+        return OutputFormat::FlatGeobuf;
+    }
+    if req.path().ends_with(".jsonfg") {
+        return OutputFormat::JsonFg;
+    }
+    if req.path().ends_with(".pbf") {
+        return OutputFormat::Geobuf;
+    }
+    if req.path().ends_with(".zip") {
+        return OutputFormat::Shapefile;
+    }
+    if html_accepted(req).await {
+        return OutputFormat::Html;
+    }
+    match feature_format(req).await {
+        Some(
+            format @ (OutputFormat::FlatGeobuf
+            | OutputFormat::Geobuf
+            | OutputFormat::Shapefile
+            | OutputFormat::JsonFg),
+        ) => format,
+        _ => OutputFormat::GeoJson,
+    }
+}
+
+/// Preferred format of `Accept` header
+async fn feature_format(req: &HttpRequest) -> Option<OutputFormat> {
+    web::Header::<header::Accept>::extract(req)
+        .await
+        .ok()
+        .and_then(|accept| OutputFormat::from_content_type(&accept.preference().to_string()))
+}
+
+/// Response builder for collection items
+fn items_response(
+    collection: &CoreCollection,
+    filter: &FilterParams,
+    format: OutputFormat,
+) -> HttpResponseBuilder {
+    let mut response = HttpResponse::Ok();
+    response.content_type(format.content_type());
+    if let Some(content_crs) = content_crs(collection, filter.crs.as_deref()) {
+        response.insert_header(("Content-Crs", content_crs));
+    }
+    response
+}
+
+/// FeatureCollection serialized incrementally while features are fetched
+fn geojson_stream(
+    feature_collection: &CoreFeatures,
+    features: BoxStream<'static, Result<CoreFeature, FeatureError>>,
+) -> Result<impl Stream<Item = Result<Bytes, FeatureError>>, FeatureError> {
+    // Features are serialized last, so we can replace the closing brace with the features array
+    let mut head = serde_json::to_vec(feature_collection)?;
+    head.pop();
+    head.extend_from_slice(br#","features":["#);
+    let body = features
+        .enumerate()
+        .map(|(i, feature)| -> Result<Bytes, FeatureError> {
+            let mut bytes = if i == 0 { Vec::new() } else { b",".to_vec() };
+            serde_json::to_writer(&mut bytes, &feature?)?;
+            Ok(Bytes::from(bytes))
+        });
+    Ok(stream::once(future::ready(Ok(Bytes::from(head))))
+        .chain(body)
+        .chain(stream::once(future::ready(Ok(Bytes::from_static(b"]}"))))))
+}
+
+/// Check requested CRS against the CRS supported by the collection
+fn check_crs(collection: &CoreCollection, uri: Option<&str>) -> Result<(), String> {
+    let Some(uri) = uri else {
+        return Ok(());
+    };
+    match Crs::from_uri(uri) {
+        Ok(crs) if crs.is_supported(&collection.crs) => Ok(()),
+        _ => Err(format!("Unsupported CRS `{uri}`")),
+    }
+}
+
+/// `Content-Crs` header value for requested CRS or default output CRS
+fn content_crs(collection: &CoreCollection, uri: Option<&str>) -> Option<String> {
+    Some(format!("<{}>", output_crs(collection, uri)?))
+}
+
+/// URI of requested CRS or default output CRS
+fn output_crs(collection: &CoreCollection, uri: Option<&str>) -> Option<String> {
+    match uri {
+        Some(uri) => Some(Crs::from_uri(uri).ok()?.uri()),
+        None => collection.crs.first().cloned(),
+    }
+}
+
+/// Response for failed item queries
+fn query_error(e: FeatureError) -> HttpResponse {
+    e.error_response()
+}
+
+fn not_found_or_unavailable(inventory: &Inventory, collection_id: &str) -> HttpResponse {
+    if inventory.core_collection(collection_id).is_some()
+        && !inventory.collection_available(collection_id)
+    {
+        HttpResponse::ServiceUnavailable().finish()
+    } else {
+        HttpResponse::NotFound().finish()
+    }
+}
+
 #[cfg(feature = "html")]
 #[derive(rust_embed::RustEmbed)]
 #[folder = "templates/"]
@@ -195,9 +739,145 @@ type Templates = bbox_core::templates::NoTemplates;
 
 static TEMPLATES: Lazy<Environment<'static>> = Lazy::new(create_env_embedded::<Templates>);
 
+/// Job type of download generation
+const DOWNLOAD_JOB: &str = "download";
+/// Default maximal number of queued or running download jobs
+const MAX_DOWNLOAD_JOBS: usize = 2;
+/// Default lifetime of generated downloads in seconds
+const DOWNLOAD_MAX_AGE: u64 = 24 * 3600;
+/// Media type of generated downloads
+const GPKG_MEDIA_TYPE: &str = "application/geopackage+sqlite3";
+
+/// File name of a generated download
+fn download_filename(collection_id: &str, token: &str) -> String {
+    let prefix = collection_id.replace(
+        |c: char| !(c.is_ascii_alphanumeric() || c == '-' || c == '_'),
+        "_",
+    );
+    format!("{prefix}-{token}.gpkg")
+}
+
+/// Random token of a download file name, which is not guessable from the job id
+fn download_token() -> String {
+    format!("{:032x}", rand::thread_rng().gen::<u128>())
+}
+
+/// Remove generated downloads older than `max_age`
+fn remove_expired_downloads(dir: &Path, max_age: Duration) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let expired = path.extension().and_then(|ext| ext.to_str()) == Some("gpkg")
+            && entry
+                .metadata()
+                .and_then(|meta| meta.modified())
+                .ok()
+                .and_then(|modified| modified.elapsed().ok())
+                .map_or(false, |age| age > max_age);
+        if expired {
+            if let Err(e) = std::fs::remove_file(&path) {
+                warn!("Removing expired download `{}` failed: {e}", path.display());
+            }
+        }
+    }
+}
+
+/// Generate a GeoPackage download of a collection in a background job
+// POST /admin/collections/{collectionId}/download
+async fn create_download(
+    inventory: web::Data<Reloadable<Inventory>>,
+    downloads: web::Data<DownloadsCfg>,
+    jobs: web::Data<JobQueue>,
+    collection_id: web::Path<String>,
+    params: web::Query<DumpParams>,
+) -> HttpResponse {
+    let collection_id = collection_id.into_inner();
+    let inventory = inventory.current();
+    if inventory.core_collection(&collection_id).is_none() {
+        return HttpResponse::NotFound().finish();
+    }
+    let pending = jobs
+        .jobs()
+        .iter()
+        .filter(|job| job.kind == DOWNLOAD_JOB && !job.status.is_finished())
+        .count();
+    if pending >= downloads.max_jobs.unwrap_or(MAX_DOWNLOAD_JOBS) {
+        return HttpResponse::TooManyRequests().finish();
+    }
+    let params = params.into_inner();
+    let dir = std::path::PathBuf::from(&downloads.dir);
+    let max_age = Duration::from_secs(downloads.max_age.unwrap_or(DOWNLOAD_MAX_AGE));
+    let token = download_token();
+    let job = jobs.submit(DOWNLOAD_JOB, move |_handle| async move {
+        remove_expired_downloads(&dir, max_age);
+        let path = dir.join(download_filename(&collection_id, &token));
+        let count = dump_collection(&inventory, &collection_id, &params, &path).await?;
+        Ok::<_, FeatureError>(serde_json::json!({
+            "collection": collection_id,
+            "features": count,
+            "links": [{
+                "href": format!("/collections/{collection_id}/download/{token}.gpkg"),
+                "rel": "enclosure",
+                "type": GPKG_MEDIA_TYPE,
+            }]
+        }))
+    });
+    HttpResponse::Created()
+        .insert_header((header::LOCATION, format!("/admin/jobs/{}", job.job_id)))
+        .json(job)
+}
+
+/// Generated download of a collection.
+/// Served below the collection path, so that the access rules of the collection apply.
+// GET /collections/{collectionId}/download/{file}
+async fn download(
+    inventory: Current<Inventory>,
+    downloads: web::Data<DownloadsCfg>,
+    path: web::Path<(String, String)>,
+    req: HttpRequest,
+) -> HttpResponse {
+    let (collection_id, file) = path.into_inner();
+    let token = file
+        .strip_suffix(".gpkg")
+        .filter(|token| token.len() == 32 && token.chars().all(|c| c.is_ascii_hexdigit()));
+    let Some(token) = token else {
+        return HttpResponse::NotFound().finish();
+    };
+    if inventory.core_collection(&collection_id).is_none() {
+        return HttpResponse::NotFound().finish();
+    }
+    let path = Path::new(&downloads.dir).join(download_filename(&collection_id, token));
+    match NamedFile::open(path) {
+        Ok(file) => {
+            let mut response = file.into_response(&req);
+            response.headers_mut().insert(
+                header::CONTENT_TYPE,
+                HeaderValue::from_static(GPKG_MEDIA_TYPE),
+            );
+            response
+        }
+        Err(_) => HttpResponse::NotFound().finish(),
+    }
+}
+
+/// Recompute extents and queryables of a configured collection
+// POST /admin/collections/{collectionId}/refresh
+async fn refresh_collection(
+    service: web::Data<FeatureService>,
+    collection_id: web::Path<String>,
+) -> HttpResponse {
+    match service.refresh_collection(&collection_id).await {
+        Ok(collection) => HttpResponse::Ok().json(collection),
+        Err(e) => query_error(e),
+    }
+}
+
 impl ServiceEndpoints for FeatureService {
     fn register_endpoints(&self, cfg: &mut web::ServiceConfig) {
         cfg.app_data(web::Data::new(self.inventory.clone()))
+            .app_data(web::Data::new(self.clone()))
             .service(web::resource("/collections").route(web::get().to(collections)))
             .service(web::resource("/collections.json").route(web::get().to(collections)))
             .service(
@@ -213,19 +893,73 @@ impl ServiceEndpoints for FeatureService {
                     .route(web::get().to(queryables)),
             )
             .service(
-                web::resource("/collections/{collectionId}/items").route(web::get().to(features)),
+                web::resource("/collections/{collectionId}/schema")
+                    .route(web::get().to(properties_schema)),
+            )
+            .service(
+                web::resource("/collections/{collectionId}/items")
+                    .route(web::get().to(features))
+                    .route(web::post().to(create_feature)),
             )
             .service(
                 web::resource("/collections/{collectionId}/items.json")
                     .route(web::get().to(features)),
             )
+            .service(
+                web::resource("/collections/{collectionId}/items.jsonfg")
+                    .route(web::get().to(features)),
+            )
+            .service(
+                web::resource("/collections/{collectionId}/items.fgb")
+                    .route(web::get().to(features)),
+            )
+            .service(
+                web::resource("/collections/{collectionId}/items.pbf")
+                    .route(web::get().to(features)),
+            )
+            .service(
+                web::resource("/collections/{collectionId}/items.zip")
+                    .route(web::get().to(features)),
+            )
+            .service(
+                web::resource("/collections/{collectionId}/changes").route(web::get().to(changes)),
+            )
+            .service(
+                web::resource("/collections/{collectionId}/aggregate")
+                    .route(web::get().to(aggregate)),
+            )
             .service(
                 web::resource("/collections/{collectionId}/items/{featureId}.json")
                     .route(web::get().to(feature)),
             )
             .service(
                 web::resource("/collections/{collectionId}/items/{featureId}")
-                    .route(web::get().to(feature)),
+                    .route(web::get().to(feature))
+                    .route(web::put().to(replace_feature))
+                    .route(web::patch().to(update_feature))
+                    .route(web::delete().to(delete_feature)),
+            )
+            .service(
+                web::resource("/search")
+                    .route(web::get().to(search))
+                    .route(web::post().to(search_post)),
+            )
+            .service(
+                web::resource("/admin/collections/{collectionId}/refresh")
+                    .guard(AdminGuard)
+                    .route(web::post().to(refresh_collection)),
             );
+        if let Some(downloads) = &self.downloads {
+            cfg.app_data(web::Data::new(downloads.clone()))
+                .service(
+                    web::resource("/admin/collections/{collectionId}/download")
+                        .guard(AdminGuard)
+                        .route(web::post().to(create_download)),
+                )
+                .service(
+                    web::resource("/collections/{collectionId}/download/{file}")
+                        .route(web::get().to(download)),
+                );
+        }
     }
 }
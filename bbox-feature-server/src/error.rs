@@ -1,4 +1,7 @@
 //! Error and Result types.
+use actix_web::http::StatusCode;
+use actix_web::HttpResponse;
+use bbox_core::api_error::{error_response, sqlstate};
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -10,11 +13,122 @@ pub enum Error {
     DatasourceSetupError(String),
     #[error("datasource `{0}` not found")]
     DatasourceNotFound(String),
+    #[error("collection `{0}` not found")]
+    CollectionNotFound(String),
+    #[error("collection `{0}` currently unavailable")]
+    CollectionUnavailable(String),
+    #[error("collection is read-only")]
+    CollectionReadOnly,
+    #[error("feature does not match `If-Match` precondition")]
+    PreconditionFailed,
+    #[error("query timeout exceeded")]
+    QueryTimeout,
+    #[error("output file `{0}` exists")]
+    OutputFileExists(String),
     // Database errors
     #[error(transparent)]
     DbError(#[from] sqlx::Error),
+    #[error(transparent)]
+    ParquetError(#[from] parquet::errors::ParquetError),
+    #[error(transparent)]
+    ArrowError(#[from] arrow_schema::ArrowError),
+    #[error(transparent)]
+    ObjectStoreError(#[from] object_store::Error),
+    #[error(transparent)]
+    FgbError(#[from] flatgeobuf::Error),
+    #[error(transparent)]
+    GeozeroError(#[from] geozero::error::GeozeroError),
+    #[error(transparent)]
+    IoError(#[from] std::io::Error),
+    #[error(transparent)]
+    HttpError(#[from] reqwest::Error),
+    #[error("Elasticsearch error - {0}")]
+    ElasticsearchError(String),
+    #[error(transparent)]
+    JsonError(#[from] serde_json::Error),
+    #[error(transparent)]
+    ZipError(#[from] zip::result::ZipError),
+    #[error("output format error - {0}")]
+    OutputFormatError(String),
     #[error("Query parameters error")]
     QueryParams,
+    #[error("query parameter `{0}` not supported for this collection")]
+    UnsupportedParam(&'static str),
+    #[error(transparent)]
+    InvalidSqlIdent(#[from] bbox_core::sql::InvalidSqlIdent),
 }
 
 pub type Result<T> = std::result::Result<T, Error>;
+
+impl Error {
+    /// HTTP status and stable error code
+    pub fn status(&self) -> (StatusCode, &'static str) {
+        match self {
+            Error::CollectionNotFound(_) => (StatusCode::NOT_FOUND, "NotFound"),
+            Error::CollectionUnavailable(_) => (StatusCode::SERVICE_UNAVAILABLE, "Unavailable"),
+            Error::CollectionReadOnly => (StatusCode::METHOD_NOT_ALLOWED, "ReadOnly"),
+            Error::PreconditionFailed => (StatusCode::PRECONDITION_FAILED, "PreconditionFailed"),
+            Error::QueryTimeout => (StatusCode::GATEWAY_TIMEOUT, "QueryTimeout"),
+            Error::OutputFileExists(_) => (StatusCode::CONFLICT, "OutputExists"),
+            Error::QueryParams
+            | Error::UnsupportedParam(_)
+            | Error::InvalidSqlIdent(_)
+            | Error::GeometryFormatError => (StatusCode::BAD_REQUEST, "InvalidParameter"),
+            Error::DbError(e) => match e {
+                sqlx::Error::PoolTimedOut | sqlx::Error::PoolClosed | sqlx::Error::Io(_) => {
+                    (StatusCode::SERVICE_UNAVAILABLE, "DatasourceUnavailable")
+                }
+                sqlx::Error::Database(_) => match sqlstate(self).as_deref() {
+                    // query_canceled (statement timeout)
+                    Some("57014") => (StatusCode::GATEWAY_TIMEOUT, "QueryTimeout"),
+                    // data_exception, e.g. invalid input syntax
+                    Some(code) if code.starts_with("22") => {
+                        (StatusCode::BAD_REQUEST, "InvalidParameter")
+                    }
+                    _ => (StatusCode::INTERNAL_SERVER_ERROR, "DatasourceError"),
+                },
+                _ => (StatusCode::INTERNAL_SERVER_ERROR, "DatasourceError"),
+            },
+            Error::HttpError(_)
+            | Error::ElasticsearchError(_)
+            | Error::ObjectStoreError(_)
+            | Error::FgbError(_) => (StatusCode::BAD_GATEWAY, "DatasourceError"),
+            Error::DatasourceSetupError(_) | Error::DatasourceNotFound(_) => {
+                (StatusCode::INTERNAL_SERVER_ERROR, "ConfigurationError")
+            }
+            _ => (StatusCode::INTERNAL_SERVER_ERROR, "InternalError"),
+        }
+    }
+
+    /// JSON error response
+    pub fn error_response(&self) -> HttpResponse {
+        let (status, code) = self.status();
+        let description = match self {
+            Error::QueryTimeout => "The query took too long. Please restrict the request, e.g. with a smaller bbox or a simpler filter geometry.",
+            _ => status.canonical_reason().unwrap_or("Error"),
+        };
+        error_response(status, code, description, self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn status_codes() {
+        assert_eq!(
+            Error::CollectionNotFound("x".to_string()).status(),
+            (StatusCode::NOT_FOUND, "NotFound")
+        );
+        assert_eq!(Error::QueryTimeout.status().0, StatusCode::GATEWAY_TIMEOUT);
+        assert_eq!(
+            Error::DbError(sqlx::Error::PoolTimedOut).status(),
+            (StatusCode::SERVICE_UNAVAILABLE, "DatasourceUnavailable")
+        );
+        assert_eq!(
+            Error::JsonError(serde_json::from_str::<u8>("x").unwrap_err()).status(),
+            (StatusCode::INTERNAL_SERVER_ERROR, "InternalError")
+        );
+    }
+}
@@ -1,17 +1,56 @@
+use crate::cql2::{self, Cql2Error, Expr};
+use crate::crs::{Crs, UnknownCrs};
 use serde::Deserialize;
 use std::collections::HashMap;
+use thiserror::Error;
+
+/// Resolution of WebMercatorQuad zoom level 0 in meters per pixel
+const WEB_MERCATOR_RESOLUTION_Z0: f64 = 156543.03392804097;
+/// Standardized rendering pixel size of 0.28mm (OGC WMTS)
+const STANDARDIZED_PIXEL_SIZE: f64 = 0.00028;
 
 #[derive(Debug, Deserialize, Default, Clone)]
 pub struct FilterParams {
     // Pagination
     pub limit: Option<u32>,
     pub offset: Option<u32>,
+    /// Opaque position of keyset pagination
+    pub cursor: Option<String>,
     // Filters
     pub bbox: Option<String>,
     pub datetime: Option<String>,
+    /// CQL2 text filter expression
+    pub filter: Option<String>,
+    /// Comma separated feature ids
+    pub ids: Option<String>,
+    /// GeoJSON geometry
+    pub intersects: Option<String>,
+    /// Output CRS URI
+    pub crs: Option<String>,
+    /// CRS URI of `bbox` coordinates
+    pub bbox_crs: Option<String>,
+    /// Comma separated names of returned properties
+    pub properties: Option<String>,
+    /// Omit feature geometries
+    pub skip_geometry: Option<bool>,
+    /// Comma separated sort properties, prefixed with `-` for descending order
+    pub sortby: Option<String>,
+    /// Text searched in the `search_fields` of the collection
+    pub q: Option<String>,
+    /// Zoom level of the WebMercatorQuad grid for simplifying geometries
+    pub zoom: Option<f64>,
+    /// Map scale denominator for simplifying geometries
+    pub scale: Option<f64>,
     pub filters: HashMap<String, String>,
 }
 
+/// Sort property of `sortby`
+#[derive(Debug, Clone, PartialEq)]
+pub struct SortKey {
+    pub property: String,
+    pub descending: bool,
+}
+
 #[derive(Debug)]
 pub enum TemporalType {
     DateTime(chrono::DateTime<chrono::FixedOffset>),
@@ -45,13 +84,42 @@ impl FilterParams {
             None
         }
     }
+    /// Parameters of next page for keyset pagination
+    pub fn with_cursor(&self, key: &str) -> FilterParams {
+        let mut params = self.clone();
+        params.offset = None;
+        params.cursor = Some(encode_cursor(key));
+        params
+    }
+    /// Primary key value of the last feature of the previous page
+    pub fn cursor_key(&self) -> Result<Option<String>, InvalidCursor> {
+        self.cursor.as_deref().map(decode_cursor).transpose()
+    }
     pub fn as_args(&self) -> String {
         let mut args = vec![
             Some("".to_string()),
             self.limit.map(|v| format!("limit={v}")),
             self.offset.map(|v| format!("offset={v}")),
+            self.cursor.as_ref().map(|v| format!("cursor={v}")),
             self.bbox.as_ref().map(|v| format!("bbox={v}")),
             self.datetime.as_ref().map(|v| format!("datetime={v}")),
+            self.filter
+                .as_ref()
+                .and_then(|v| serde_urlencoded::to_string([("filter", v)]).ok()),
+            self.ids.as_ref().map(|v| format!("ids={v}")),
+            self.intersects
+                .as_ref()
+                .and_then(|v| serde_urlencoded::to_string([("intersects", v)]).ok()),
+            self.crs.as_ref().map(|v| format!("crs={v}")),
+            self.bbox_crs.as_ref().map(|v| format!("bbox-crs={v}")),
+            self.properties.as_ref().map(|v| format!("properties={v}")),
+            self.skip_geometry.map(|v| format!("skipGeometry={v}")),
+            self.sortby.as_ref().map(|v| format!("sortby={v}")),
+            self.q
+                .as_ref()
+                .and_then(|v| serde_urlencoded::to_string([("q", v)]).ok()),
+            self.zoom.map(|v| format!("zoom={v}")),
+            self.scale.map(|v| format!("scale={v}")),
         ]
         .into_iter()
         .flatten()
@@ -98,22 +166,294 @@ impl FilterParams {
         }
         Ok(None)
     }
+    pub fn ids(&self) -> Option<Vec<String>> {
+        self.ids
+            .as_ref()
+            .map(|ids| ids.split(',').map(str::to_string).collect())
+    }
+    /// Selected properties, None for all properties
+    pub fn properties(&self) -> Option<Vec<String>> {
+        self.properties.as_ref().map(|props| {
+            props
+                .split(',')
+                .map(str::trim)
+                .filter(|prop| !prop.is_empty())
+                .map(str::to_string)
+                .collect()
+        })
+    }
+    pub fn skip_geometry(&self) -> bool {
+        self.skip_geometry.unwrap_or(false)
+    }
+    /// Sort order, empty for the natural order of the collection
+    pub fn sortby(&self) -> Vec<SortKey> {
+        self.sortby
+            .as_deref()
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|key| !key.is_empty())
+            .map(|key| match key.strip_prefix('-') {
+                Some(property) => SortKey {
+                    property: property.to_string(),
+                    descending: true,
+                },
+                None => SortKey {
+                    property: key.trim_start_matches('+').to_string(),
+                    descending: false,
+                },
+            })
+            .collect()
+    }
+    pub fn crs(&self) -> Result<Option<Crs>, UnknownCrs> {
+        self.crs.as_deref().map(Crs::from_uri).transpose()
+    }
+    pub fn bbox_crs(&self) -> Result<Option<Crs>, UnknownCrs> {
+        self.bbox_crs.as_deref().map(Crs::from_uri).transpose()
+    }
+    pub fn cql2_filter(&self) -> Result<Option<Expr>, Cql2Error> {
+        self.filter.as_deref().map(cql2::parse_text).transpose()
+    }
+    /// Ground resolution in meters per pixel of the requested `zoom` level or `scale` denominator
+    pub fn resolution(&self) -> Option<f64> {
+        if let Some(zoom) = self.zoom {
+            return Some(WEB_MERCATOR_RESOLUTION_Z0 / 2f64.powf(zoom));
+        }
+        self.scale.map(|scale| scale * STANDARDIZED_PIXEL_SIZE)
+    }
     pub fn other_params(&self) -> Result<&HashMap<String, String>, Box<dyn std::error::Error>> {
         Ok(&self.filters)
     }
 }
 
+/// Parameters of `/collections/{collectionId}/changes`
+#[derive(Debug, Deserialize, Clone)]
+pub struct ChangesParams {
+    /// RFC 3339 timestamp of the oldest returned change
+    pub since: String,
+    pub limit: Option<u32>,
+    pub offset: Option<u32>,
+}
+
+impl ChangesParams {
+    /// Item filter selecting features changed since `since`, ordered by `updated_property`
+    pub fn filter_params(
+        &self,
+        updated_property: &str,
+    ) -> Result<FilterParams, chrono::ParseError> {
+        let since = chrono::DateTime::parse_from_rfc3339(&self.since)?;
+        Ok(FilterParams {
+            limit: self.limit,
+            offset: self.offset,
+            filter: Some(format!(
+                "\"{updated_property}\" >= TIMESTAMP('{}')",
+                since.to_rfc3339()
+            )),
+            sortby: Some(updated_property.to_string()),
+            ..Default::default()
+        })
+    }
+    pub fn with_offset(&self, offset: u32) -> ChangesParams {
+        let mut params = self.clone();
+        params.offset = Some(offset);
+        params
+    }
+    pub fn as_args(&self) -> String {
+        let mut args = vec![("since", self.since.clone())];
+        if let Some(limit) = self.limit {
+            args.push(("limit", limit.to_string()));
+        }
+        if let Some(offset) = self.offset {
+            args.push(("offset", offset.to_string()));
+        }
+        format!("?{}", serde_urlencoded::to_string(args).unwrap_or_default())
+    }
+}
+
+/// Parameters of `/collections/{collectionId}/aggregate`
+#[derive(Debug, Default, Clone)]
+pub struct AggregateParams {
+    /// Comma separated aggregate functions (Default: `count`)
+    pub functions: Option<String>,
+    /// Comma separated properties aggregated with functions other than `count`
+    pub fields: Option<String>,
+    /// Property grouping the aggregated items
+    pub groupby: Option<String>,
+}
+
+/// Aggregate function of `functions`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AggregateFunction {
+    Count,
+    Min,
+    Max,
+    Avg,
+    Sum,
+}
+
+impl AggregateFunction {
+    pub fn name(&self) -> &'static str {
+        match self {
+            AggregateFunction::Count => "count",
+            AggregateFunction::Min => "min",
+            AggregateFunction::Max => "max",
+            AggregateFunction::Avg => "avg",
+            AggregateFunction::Sum => "sum",
+        }
+    }
+}
+
+impl AggregateParams {
+    pub fn functions(&self) -> Result<Vec<AggregateFunction>, InvalidAggregate> {
+        let Some(functions) = &self.functions else {
+            return Ok(vec![AggregateFunction::Count]);
+        };
+        let functions = functions
+            .split(',')
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .map(|name| match name.to_lowercase().as_str() {
+                "count" => Ok(AggregateFunction::Count),
+                "min" => Ok(AggregateFunction::Min),
+                "max" => Ok(AggregateFunction::Max),
+                "avg" => Ok(AggregateFunction::Avg),
+                "sum" => Ok(AggregateFunction::Sum),
+                _ => Err(InvalidAggregate(format!("unknown function `{name}`"))),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let needs_fields = functions.iter().any(|f| *f != AggregateFunction::Count);
+        if needs_fields && self.fields().is_empty() {
+            return Err(InvalidAggregate(
+                "`fields` required for functions other than `count`".to_string(),
+            ));
+        }
+        Ok(functions)
+    }
+    pub fn fields(&self) -> Vec<String> {
+        self.fields
+            .as_deref()
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .map(str::to_string)
+            .collect()
+    }
+}
+
+#[derive(Error, Debug)]
+#[error("invalid aggregation - {0}")]
+pub struct InvalidAggregate(pub String);
+
+#[derive(Error, Debug)]
+#[error("invalid cursor `{0}`")]
+pub struct InvalidCursor(pub String);
+
+/// Hex encoded key, to keep clients from depending on the cursor content
+fn encode_cursor(key: &str) -> String {
+    key.bytes().map(|b| format!("{b:02x}")).collect()
+}
+
+fn decode_cursor(cursor: &str) -> Result<String, InvalidCursor> {
+    let invalid = || InvalidCursor(cursor.to_string());
+    if cursor.len() % 2 != 0 || !cursor.is_ascii() {
+        return Err(invalid());
+    }
+    let bytes = (0..cursor.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&cursor[i..i + 2], 16))
+        .collect::<Result<Vec<u8>, _>>()
+        .map_err(|_| invalid())?;
+    String::from_utf8(bytes).map_err(|_| invalid())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn aggregate_params() {
+        let params = AggregateParams::default();
+        assert_eq!(params.functions().unwrap(), vec![AggregateFunction::Count]);
+        let params = AggregateParams {
+            functions: Some("count,MIN,avg".to_string()),
+            fields: Some("pop_max, scalerank".to_string()),
+            groupby: None,
+        };
+        assert_eq!(params.functions().unwrap().len(), 3);
+        assert_eq!(params.fields(), vec!["pop_max", "scalerank"]);
+        let params = AggregateParams {
+            functions: Some("median".to_string()),
+            ..params
+        };
+        assert!(params.functions().is_err());
+        let params = AggregateParams {
+            functions: Some("max".to_string()),
+            ..Default::default()
+        };
+        assert!(params.functions().is_err());
+    }
+
+    #[test]
+    fn resolution() {
+        assert_eq!(FilterParams::default().resolution(), None);
+        let filter = FilterParams {
+            zoom: Some(2.0),
+            ..Default::default()
+        };
+        assert_eq!(filter.resolution(), Some(39135.75848201024));
+        let filter = FilterParams {
+            scale: Some(500000.0),
+            ..Default::default()
+        };
+        assert_eq!(filter.resolution(), Some(140.0));
+        assert!(filter.as_args().ends_with("scale=500000"));
+    }
+
+    #[test]
+    fn changes_filter() {
+        let params = ChangesParams {
+            since: "2024-03-01T12:00:00+01:00".to_string(),
+            limit: Some(100),
+            offset: None,
+        };
+        let filter = params.filter_params("updated_at").unwrap();
+        assert_eq!(
+            filter.filter.as_deref(),
+            Some("\"updated_at\" >= TIMESTAMP('2024-03-01T12:00:00+01:00')")
+        );
+        assert_eq!(filter.sortby.as_deref(), Some("updated_at"));
+        assert!(filter.cql2_filter().is_ok());
+        assert_eq!(
+            params.with_offset(100).as_args(),
+            "?since=2024-03-01T12%3A00%3A00%2B01%3A00&limit=100&offset=100"
+        );
+        let invalid = ChangesParams {
+            since: "yesterday".to_string(),
+            ..params
+        };
+        assert!(invalid.filter_params("updated_at").is_err());
+    }
+
     #[test]
     fn filter_to_args() {
         let filter = FilterParams {
             limit: Some(10),
             offset: Some(20),
+            cursor: None,
             bbox: Some("1.0,2.2,3.33,4.444".to_string()),
             datetime: None,
+            filter: None,
+            ids: None,
+            intersects: None,
+            crs: None,
+            bbox_crs: None,
+            properties: None,
+            skip_geometry: None,
+            sortby: None,
+            q: None,
+            zoom: None,
+            scale: None,
             filters: HashMap::new(),
         };
         assert_eq!(
@@ -124,8 +464,20 @@ mod tests {
         let filter = FilterParams {
             limit: None,
             offset: Some(20),
+            cursor: None,
             bbox: None,
             datetime: None,
+            filter: None,
+            ids: None,
+            intersects: None,
+            crs: None,
+            bbox_crs: None,
+            properties: None,
+            skip_geometry: None,
+            sortby: None,
+            q: None,
+            zoom: None,
+            scale: None,
             filters: HashMap::new(),
         };
         assert_eq!(filter.as_args(), "?offset=20");
@@ -133,8 +485,20 @@ mod tests {
         let filter = FilterParams {
             limit: None,
             offset: None,
+            cursor: None,
             bbox: None,
             datetime: None,
+            filter: None,
+            ids: None,
+            intersects: None,
+            crs: None,
+            bbox_crs: None,
+            properties: None,
+            skip_geometry: None,
+            sortby: None,
+            q: None,
+            zoom: None,
+            scale: None,
             filters: HashMap::new(),
         };
         assert_eq!(filter.as_args(), "");
@@ -142,8 +506,20 @@ mod tests {
         let filter = FilterParams {
             limit: None,
             offset: None,
+            cursor: None,
             bbox: None,
             datetime: Some("2024-01-01T00:00:00Z".to_string()),
+            filter: None,
+            ids: None,
+            intersects: None,
+            crs: None,
+            bbox_crs: None,
+            properties: None,
+            skip_geometry: None,
+            sortby: None,
+            q: None,
+            zoom: None,
+            scale: None,
             filters: HashMap::new(),
         };
         assert_eq!(filter.as_args(), "?datetime=2024-01-01T00:00:00Z");
@@ -153,11 +529,45 @@ mod tests {
         let filter = FilterParams {
             limit: None,
             offset: None,
+            cursor: None,
             bbox: None,
             datetime: None,
+            filter: None,
+            ids: None,
+            intersects: None,
+            crs: None,
+            bbox_crs: None,
+            properties: None,
+            skip_geometry: None,
+            sortby: None,
+            q: None,
+            zoom: None,
+            scale: None,
             filters: hm,
         };
         assert_eq!(filter.as_args(), "?ArbitraryField=Something");
+
+        let filter = FilterParams {
+            limit: None,
+            offset: None,
+            cursor: None,
+            bbox: None,
+            datetime: None,
+            filter: Some("name = 'Rhein'".to_string()),
+            ids: None,
+            intersects: None,
+            crs: None,
+            bbox_crs: None,
+            properties: None,
+            skip_geometry: None,
+            sortby: None,
+            q: None,
+            zoom: None,
+            scale: None,
+            filters: HashMap::new(),
+        };
+        assert_eq!(filter.as_args(), "?filter=name+%3D+%27Rhein%27");
+        assert!(filter.cql2_filter().unwrap().is_some());
     }
 
     #[test]
@@ -165,8 +575,20 @@ mod tests {
         let filter = FilterParams {
             limit: Some(10),
             offset: Some(20),
+            cursor: None,
             bbox: None,
             datetime: None,
+            filter: None,
+            ids: None,
+            intersects: None,
+            crs: None,
+            bbox_crs: None,
+            properties: None,
+            skip_geometry: None,
+            sortby: None,
+            q: None,
+            zoom: None,
+            scale: None,
             filters: HashMap::new(),
         };
         assert_eq!(filter.prev().unwrap().offset, Some(10));
@@ -177,8 +599,20 @@ mod tests {
         let filter = FilterParams {
             limit: Some(10),
             offset: Some(10),
+            cursor: None,
             bbox: None,
             datetime: None,
+            filter: None,
+            ids: None,
+            intersects: None,
+            crs: None,
+            bbox_crs: None,
+            properties: None,
+            skip_geometry: None,
+            sortby: None,
+            q: None,
+            zoom: None,
+            scale: None,
             filters: HashMap::new(),
         };
         assert_eq!(filter.prev().unwrap().offset, Some(0));
@@ -187,8 +621,20 @@ mod tests {
         let filter = FilterParams {
             limit: Some(10),
             offset: None,
+            cursor: None,
             bbox: None,
             datetime: None,
+            filter: None,
+            ids: None,
+            intersects: None,
+            crs: None,
+            bbox_crs: None,
+            properties: None,
+            skip_geometry: None,
+            sortby: None,
+            q: None,
+            zoom: None,
+            scale: None,
             filters: HashMap::new(),
         };
         assert!(filter.prev().is_none());
@@ -201,8 +647,20 @@ mod tests {
             FilterParams {
                 limit: None,
                 offset: None,
+                cursor: None,
                 bbox: Some("1.0,2.2,3.33,4.444".to_string()),
                 datetime: None,
+                filter: None,
+                ids: None,
+                intersects: None,
+                crs: None,
+                bbox_crs: None,
+                properties: None,
+                skip_geometry: None,
+                sortby: None,
+                q: None,
+                zoom: None,
+                scale: None,
                 filters: HashMap::new(),
             }
             .bbox()
@@ -214,8 +672,20 @@ mod tests {
             FilterParams {
                 limit: None,
                 offset: None,
+                cursor: None,
                 bbox: Some("1.0,2.2,3.33,4.444,5,6".to_string()),
                 datetime: None,
+                filter: None,
+                ids: None,
+                intersects: None,
+                crs: None,
+                bbox_crs: None,
+                properties: None,
+                skip_geometry: None,
+                sortby: None,
+                q: None,
+                zoom: None,
+                scale: None,
                 filters: HashMap::new(),
             }
             .bbox()
@@ -226,8 +696,20 @@ mod tests {
         assert!(FilterParams {
             limit: None,
             offset: None,
+            cursor: None,
             bbox: Some("1.0, 2.2, 3.33, 4.444".to_string()),
             datetime: None,
+            filter: None,
+            ids: None,
+            intersects: None,
+            crs: None,
+            bbox_crs: None,
+            properties: None,
+            skip_geometry: None,
+            sortby: None,
+            q: None,
+            zoom: None,
+            scale: None,
             filters: HashMap::new(),
         }
         .bbox()
@@ -237,8 +719,20 @@ mod tests {
             FilterParams {
                 limit: None,
                 offset: None,
+                cursor: None,
                 bbox: Some("1,2,3".to_string()),
                 datetime: None,
+                filter: None,
+                ids: None,
+                intersects: None,
+                crs: None,
+                bbox_crs: None,
+                properties: None,
+                skip_geometry: None,
+                sortby: None,
+                q: None,
+                zoom: None,
+                scale: None,
                 filters: HashMap::new(),
             }
             .bbox()
@@ -246,4 +740,77 @@ mod tests {
             None // should be Err
         );
     }
+
+    #[test]
+    fn keyset_cursor() {
+        let filter = FilterParams {
+            limit: Some(10),
+            offset: Some(20),
+            ..Default::default()
+        };
+        let next = filter.with_cursor("ne_10m_lakes.42");
+        assert_eq!(next.offset, None);
+        assert_eq!(
+            next.as_args(),
+            "?limit=10&cursor=6e655f31306d5f6c616b65732e3432"
+        );
+        assert_eq!(
+            next.cursor_key().unwrap(),
+            Some("ne_10m_lakes.42".to_string())
+        );
+
+        for cursor in ["6e6", "zz", "ff"] {
+            let filter = FilterParams {
+                cursor: Some(cursor.to_string()),
+                ..Default::default()
+            };
+            assert!(filter.cursor_key().is_err());
+        }
+    }
+
+    #[test]
+    fn property_selection() {
+        let filter = FilterParams {
+            properties: Some("name, scalerank,".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            filter.properties(),
+            Some(vec!["name".to_string(), "scalerank".to_string()])
+        );
+        let filter = FilterParams {
+            properties: Some("name,scalerank".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(filter.as_args(), "?properties=name,scalerank");
+        let filter = FilterParams {
+            skip_geometry: Some(true),
+            ..Default::default()
+        };
+        assert!(filter.skip_geometry());
+        assert_eq!(filter.as_args(), "?skipGeometry=true");
+        assert_eq!(FilterParams::default().properties(), None);
+    }
+
+    #[test]
+    fn sort_keys() {
+        let filter = FilterParams {
+            sortby: Some(" name,-scalerank".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            filter.sortby(),
+            vec![
+                SortKey {
+                    property: "name".to_string(),
+                    descending: false
+                },
+                SortKey {
+                    property: "scalerank".to_string(),
+                    descending: true
+                }
+            ]
+        );
+        assert!(FilterParams::default().sortby().is_empty());
+    }
 }
@@ -1,11 +1,21 @@
-use crate::config::CollectionsCfg;
-use crate::datasource::{gpkg::SqliteDatasource, AutoscanCollectionDatasource, CollectionSource};
-use crate::filter_params::FilterParams;
+use crate::config::{CollectionsCfg, ConfiguredCollectionCfg};
+use crate::crs::Crs;
+use crate::datasource::{
+    fgb::FlatgeobufCollectionSource, gpkg::SqliteDatasource, AutoscanCollectionDatasource,
+    CollectionSource, IfMatch, Paging,
+};
+use crate::error::{Error, Result};
+use crate::filter_params::{AggregateParams, ChangesParams, FilterParams};
+use crate::output::OutputFormat;
+use crate::s3_sync;
+use bbox_core::events::{publish, Event, EventKind};
 use bbox_core::file_search;
 use bbox_core::ogcapi::*;
 use bbox_core::pg_ds::PgDatasource;
+use futures::stream::BoxStream;
 use log::{info, warn};
 use std::collections::HashMap;
+use std::path::Path;
 
 // ┌──────────────┐      ┌─────────────┐
 // │              │1    n│             │
@@ -25,6 +35,12 @@ use std::collections::HashMap;
 pub struct Inventory {
     // Key: collection_id
     feat_collections: HashMap<String, FeatureCollection>,
+    /// List collections with unavailable datasource
+    pub(crate) list_unavailable: bool,
+    /// Show items on a map in HTML pages
+    pub(crate) html_map: bool,
+    /// Configuration of configured and derived collections for refreshing their metadata
+    pub(crate) collection_cfgs: HashMap<String, ConfiguredCollectionCfg>,
 }
 
 #[derive(Clone)]
@@ -38,6 +54,9 @@ impl Inventory {
     pub fn new() -> Self {
         Inventory {
             feat_collections: HashMap::new(),
+            list_unavailable: false,
+            html_map: true,
+            collection_cfgs: HashMap::new(),
         }
     }
 
@@ -49,22 +68,25 @@ impl Inventory {
             let files = file_search::search(base_dir, "*.gpkg");
             info!("Found {} matching file(s)", files.len());
             for path in files {
-                let pathstr = path.as_os_str().to_string_lossy();
-                match SqliteDatasource::new_pool(&pathstr).await {
-                    Ok(mut ds) => {
-                        info!("Scanning '{pathstr}' for feature collections");
-                        match ds.collections().await {
-                            Ok(collections) => inventory.add_collections(collections),
-                            Err(e) => {
-                                warn!("Failed to scan feature collections for '{pathstr}': {e}")
-                            }
-                        }
-                    }
-                    Err(e) => {
-                        warn!("Failed to create connection pool for '{pathstr}': {e}");
-                        continue;
+                inventory.scan_gpkg(&path).await;
+            }
+            let files = file_search::search(base_dir, "*.fgb");
+            info!("Found {} FlatGeobuf file(s)", files.len());
+            for path in files {
+                match FlatgeobufCollectionSource::file_collection(&path).await {
+                    Ok(fc) => inventory.add_collection(fc),
+                    Err(e) => warn!("Failed to read FlatGeobuf file '{}': {e}", path.display()),
+                }
+            }
+        }
+        for cfg in &config.s3 {
+            match s3_sync::sync_geopackages(cfg).await {
+                Ok(files) => {
+                    for path in files {
+                        inventory.scan_gpkg(&path).await;
                     }
                 }
+                Err(e) => warn!("Failed to synchronize GeoPackages from '{}': {e}", cfg.url),
             }
         }
         for cfg in &config.postgis {
@@ -89,8 +111,43 @@ impl Inventory {
         inventory
     }
 
-    pub fn add_collection(&mut self, fc: FeatureCollection) {
+    async fn scan_gpkg(&mut self, path: &Path) {
+        let pathstr = path.as_os_str().to_string_lossy();
+        match SqliteDatasource::new_pool(&pathstr).await {
+            Ok(mut ds) => {
+                info!("Scanning '{pathstr}' for feature collections");
+                match ds.collections().await {
+                    Ok(collections) => self.add_collections(collections),
+                    Err(e) => warn!("Failed to scan feature collections for '{pathstr}': {e}"),
+                }
+            }
+            Err(e) => warn!("Failed to create connection pool for '{pathstr}': {e}"),
+        }
+    }
+
+    pub fn add_collection(&mut self, mut fc: FeatureCollection) {
         let id = fc.collection.id.clone();
+        let title = fc.collection.title.clone();
+        // JSON-FG adds information not contained in GeoJSON
+        let native_crs = fc
+            .collection
+            .crs
+            .iter()
+            .any(|uri| Crs::from_uri(uri).map_or(false, |crs| crs.srid != 4326));
+        if native_crs || fc.source.temporal_properties().is_some() {
+            fc.collection
+                .links
+                .push(OutputFormat::JsonFg.items_link(&id, title.clone()));
+        }
+        fc.collection
+            .links
+            .push(OutputFormat::FlatGeobuf.items_link(&id, title.clone()));
+        fc.collection
+            .links
+            .push(OutputFormat::Geobuf.items_link(&id, title.clone()));
+        fc.collection
+            .links
+            .push(OutputFormat::Shapefile.items_link(&id, title));
         // TODO: Handle name collisions
         self.feat_collections.insert(id, fc);
     }
@@ -101,10 +158,11 @@ impl Inventory {
         }
     }
 
-    /// Return all collections as vector
+    /// Return all collections as vector. Unavailable collections are omitted by default.
     pub fn collections(&self) -> Vec<CoreCollection> {
         self.feat_collections
             .values()
+            .filter(|fc| self.list_unavailable || fc.source.available())
             .map(|fc| fc.collection.clone())
             .collect()
     }
@@ -119,88 +177,384 @@ impl Inventory {
         self.feat_collections.get(collection_id)
     }
 
+    /// Compute the temporal extent of a collection from its data
+    pub async fn query_temporal_extent(
+        &self,
+        collection_id: &str,
+    ) -> Result<Option<CoreExtentTemporal>> {
+        let fc = self
+            .collection(collection_id)
+            .ok_or(Error::CollectionNotFound(collection_id.to_string()))?;
+        fc.source.temporal_extent().await
+    }
+
+    /// Replace the temporal extent of a collection
+    pub fn set_temporal_extent(
+        &mut self,
+        collection_id: &str,
+        temporal: Option<CoreExtentTemporal>,
+    ) {
+        if let Some(extent) = self
+            .feat_collections
+            .get_mut(collection_id)
+            .and_then(|fc| fc.collection.extent.as_mut())
+        {
+            extent.temporal = temporal;
+        }
+    }
+
+    /// Check whether the datasource of a collection is reachable
+    pub fn collection_available(&self, collection_id: &str) -> bool {
+        self.collection(collection_id)
+            .map(|fc| fc.source.available())
+            .unwrap_or(false)
+    }
+
+    /// Collection items. Query timeouts and unsupported parameters are returned as error,
+    /// other errors are ignored.
     pub async fn collection_items(
         &self,
         collection_id: &str,
         filter: &FilterParams,
-    ) -> Option<CoreFeatures> {
+    ) -> Result<Option<CoreFeatures>> {
         let Some(fc) = self.collection(collection_id) else {
             warn!("Ignoring error getting collection {collection_id}");
-            return None;
+            return Ok(None);
         };
-        let items = match fc.source.items(filter).await {
+        let mut items = match fc.source.items(filter).await {
             Ok(items) => items,
+            Err(e @ (Error::QueryTimeout | Error::UnsupportedParam(_))) => return Err(e),
             Err(e) => {
                 warn!("Ignoring error getting collection items for {collection_id}: {e}");
-                return None;
+                return Ok(None);
             }
         };
-        let mut features = CoreFeatures {
+        // Sources without native support return all properties and geometries
+        if let Some(names) = filter.properties() {
+            for feature in &mut items.features {
+                select_properties(feature, &names);
+            }
+        }
+        if filter.skip_geometry() {
+            for feature in &mut items.features {
+                feature.geometry = serde_json::Value::Null;
+            }
+        }
+        Ok(Some(features_response(
+            collection_id,
+            filter,
+            items.number_matched,
+            items.number_returned,
+            items.features,
+            &items.paging,
+        )))
+    }
+
+    /// Features created or updated since a point in time, ordered by the time of their last change.
+    /// None if the collection has no property with the update time.
+    pub async fn collection_changes(
+        &self,
+        collection_id: &str,
+        params: &ChangesParams,
+    ) -> Result<Option<CoreFeatures>> {
+        let Some(fc) = self.collection(collection_id) else {
+            return Err(Error::CollectionNotFound(collection_id.to_string()));
+        };
+        let Some(updated_property) = fc.source.updated_property() else {
+            return Ok(None);
+        };
+        let filter = params
+            .filter_params(updated_property)
+            .map_err(|_| Error::QueryParams)?;
+        let items = fc.source.items(&filter).await?;
+        let link = |params: &ChangesParams, rel: &str| ApiLink {
+            href: format!("/collections/{collection_id}/changes{}", params.as_args()),
+            rel: Some(rel.to_string()),
+            type_: Some("application/geo+json".to_string()),
+            title: Some(rel.to_string()),
+            hreflang: None,
+            length: None,
+        };
+        let mut links = vec![link(params, "self")];
+        if let Some(next_offset) = filter.next(items.number_matched).and_then(|f| f.offset) {
+            links.push(link(&params.with_offset(next_offset), "next"));
+        }
+        Ok(Some(CoreFeatures {
             type_: "FeatureCollection".to_string(),
-            links: vec![
-                ApiLink {
-                    href: format!("/collections/{collection_id}/items"),
-                    rel: Some("self".to_string()),
-                    type_: Some("text/html".to_string()),
-                    title: Some("this document".to_string()),
-                    hreflang: None,
-                    length: None,
-                },
-                ApiLink {
-                    href: format!("/collections/{collection_id}/items.json"),
-                    rel: Some("self".to_string()),
-                    type_: Some("application/geo+json".to_string()),
-                    title: Some("this document".to_string()),
-                    hreflang: None,
-                    length: None,
-                },
-            ],
-            time_stamp: None, // time when the response was generated
+            links,
+            time_stamp: Some(chrono::Utc::now().to_rfc3339()),
             number_matched: Some(items.number_matched),
             number_returned: Some(items.number_returned),
             features: items.features,
+        }))
+    }
+
+    /// Collection items fetched incrementally. The returned collection contains no features.
+    /// None if the collection source doesn't support streaming.
+    #[allow(clippy::type_complexity)]
+    pub async fn collection_items_stream(
+        &self,
+        collection_id: &str,
+        filter: &FilterParams,
+    ) -> Result<Option<(CoreFeatures, BoxStream<'static, Result<CoreFeature>>)>> {
+        let Some(fc) = self.collection(collection_id) else {
+            return Ok(None);
+        };
+        let items = match fc.source.items_stream(filter).await {
+            Ok(Some(items)) => items,
+            Ok(None) => return Ok(None),
+            Err(e @ (Error::QueryTimeout | Error::UnsupportedParam(_))) => return Err(e),
+            Err(e) => {
+                warn!("Ignoring error getting collection items for {collection_id}: {e}");
+                return Ok(None);
+            }
         };
-        if items.number_matched > items.number_returned {
-            let mut add_link = |link: FilterParams, rel: &str| {
-                let params = link.as_args();
-                features.links.push(ApiLink {
-                    href: format!("/collections/{collection_id}/items{params}"),
-                    rel: Some(rel.to_string()),
-                    type_: Some("text/html".to_string()),
-                    title: Some(rel.to_string()),
+        let offset = filter.offset.unwrap_or(0) as u64;
+        let limit = filter.limit_or_default() as u64;
+        let mut number_returned = items.number_matched.saturating_sub(offset);
+        if limit > 0 {
+            number_returned = number_returned.min(limit);
+        }
+        let features = features_response(
+            collection_id,
+            filter,
+            items.number_matched,
+            number_returned,
+            Vec::new(),
+            &Paging::Offset,
+        );
+        Ok(Some((features, items.features)))
+    }
+
+    /// Statistics of the items matching `filter`. None if the collection source doesn't support aggregation.
+    pub async fn collection_aggregate(
+        &self,
+        collection_id: &str,
+        filter: &FilterParams,
+        params: &AggregateParams,
+    ) -> Result<Option<serde_json::Value>> {
+        let fc = self
+            .collection(collection_id)
+            .ok_or(Error::CollectionNotFound(collection_id.to_string()))?;
+        let Some(groups) = fc.source.aggregate(filter, params).await? else {
+            return Ok(None);
+        };
+        Ok(Some(serde_json::json!({
+            "collection": collection_id,
+            "groupby": params.groupby,
+            "groups": groups,
+        })))
+    }
+
+    /// Search items in multiple collections (all collections if `collection_ids` is empty)
+    pub async fn search(&self, collection_ids: &[String], filter: &FilterParams) -> CoreFeatures {
+        let mut ids: Vec<&String> = if collection_ids.is_empty() {
+            self.feat_collections.keys().collect()
+        } else {
+            collection_ids.iter().collect()
+        };
+        ids.sort();
+        let mut features = CoreFeatures {
+            type_: "FeatureCollection".to_string(),
+            links: vec![ApiLink {
+                href: "/search".to_string(),
+                rel: Some("self".to_string()),
+                type_: Some("application/geo+json".to_string()),
+                title: Some("this document".to_string()),
+                hreflang: None,
+                length: None,
+            }],
+            time_stamp: None,
+            number_matched: Some(0),
+            number_returned: Some(0),
+            features: Vec::new(),
+        };
+        let mut remaining = filter.limit_or_default();
+        // Offset into the items of all collections
+        let mut skip = filter.offset.unwrap_or(0);
+        for collection_id in ids {
+            let Some(fc) = self.collection(collection_id) else {
+                warn!("Ignoring unknown collection {collection_id} in search");
+                continue;
+            };
+            if filter.q.is_some() && !fc.source.searchable() {
+                // Text search of collections with `search_fields` only
+                continue;
+            }
+            if remaining == 0 {
+                // Page is complete, count matches of the remaining collections
+                let number_matched = count_matches(fc, filter).await;
+                features.number_matched = features
+                    .number_matched
+                    .zip(number_matched)
+                    .map(|(total, cnt)| total + cnt);
+                continue;
+            }
+            let mut params = filter.clone();
+            params.limit = Some(remaining);
+            params.offset = (skip > 0).then_some(skip);
+            let items = match fc.source.items(&params).await {
+                Ok(items) => items,
+                Err(e) => {
+                    warn!("Ignoring error searching collection items for {collection_id}: {e}");
+                    continue;
+                }
+            };
+            remaining = remaining.saturating_sub(items.number_returned as u32);
+            let number_matched = match items.paging {
+                // All matches skipped, count them without offset
+                Paging::Offset if skip > 0 && items.number_returned == 0 => {
+                    count_matches(fc, filter).await
+                }
+                Paging::Offset => Some(items.number_matched),
+                Paging::Cursor(_) => None,
+            };
+            // Without number of matches, the offset is applied to the first collection only
+            skip = number_matched
+                .map(|cnt| skip.saturating_sub(cnt as u32))
+                .unwrap_or(0);
+            features.number_matched = features
+                .number_matched
+                .zip(number_matched)
+                .map(|(total, cnt)| total + cnt);
+            for mut feature in items.features {
+                // Sources without native support return all properties and geometries
+                if let Some(names) = filter.properties() {
+                    select_properties(&mut feature, &names);
+                }
+                if filter.skip_geometry() {
+                    feature.geometry = serde_json::Value::Null;
+                }
+                feature.links.push(ApiLink {
+                    href: format!("/collections/{collection_id}"),
+                    rel: Some("collection".to_string()),
+                    type_: Some("application/json".to_string()),
+                    title: Some(collection_id.to_string()),
                     hreflang: None,
                     length: None,
                 });
-            };
-
-            if let Some(prev) = filter.prev() {
-                add_link(prev, "prev");
-            }
-            if let Some(next) = filter.next(items.number_matched) {
-                add_link(next, "next");
+                features.features.push(feature);
             }
         }
-        Some(features)
+        features.number_returned = Some(features.features.len() as u64);
+        features
     }
 
     pub async fn collection_item(
         &self,
         collection_id: &str,
         feature_id: &str,
-    ) -> Option<CoreFeature> {
+        crs: Option<&Crs>,
+    ) -> Result<Option<CoreFeature>> {
         let Some(fc) = self.collection(collection_id) else {
             warn!("Ignoring error getting collection {collection_id}");
-            return None;
+            return Ok(None);
         };
-        match fc.source.item(collection_id, feature_id).await {
-            Ok(item) => item,
+        match fc.source.item(collection_id, feature_id, crs).await {
+            Ok(item) => Ok(item),
+            Err(e @ Error::UnsupportedParam(_)) => Err(e),
             Err(e) => {
                 warn!("Ignoring error getting collection item for {collection_id}: {e}");
-                None
+                Ok(None)
             }
         }
     }
 
+    /// Properties with the feature time, see [CollectionSource::temporal_properties]
+    pub fn collection_temporal_properties(
+        &self,
+        collection_id: &str,
+    ) -> Option<(&str, Option<&str>)> {
+        self.collection(collection_id)?.source.temporal_properties()
+    }
+
+    /// Check whether a collection supports feature transactions
+    pub fn collection_writable(&self, collection_id: &str) -> bool {
+        self.collection(collection_id)
+            .map(|fc| fc.source.writable())
+            .unwrap_or(false)
+    }
+
+    /// Check whether items of a collection can be searched with `q`
+    pub fn collection_searchable(&self, collection_id: &str) -> bool {
+        self.collection(collection_id)
+            .map(|fc| fc.source.searchable())
+            .unwrap_or(false)
+    }
+
+    fn writable_source(&self, collection_id: &str) -> Result<&dyn CollectionSource> {
+        let fc = self
+            .collection(collection_id)
+            .ok_or(Error::CollectionNotFound(collection_id.to_string()))?;
+        if !fc.source.writable() {
+            return Err(Error::CollectionReadOnly);
+        }
+        Ok(fc.source.as_ref())
+    }
+
+    pub async fn create_item(
+        &self,
+        collection_id: &str,
+        feature: &serde_json::Value,
+    ) -> Result<String> {
+        let id = self
+            .writable_source(collection_id)?
+            .create_item(feature)
+            .await?;
+        publish_feature_event(EventKind::FeatureCreated, collection_id, &id);
+        Ok(id)
+    }
+
+    pub async fn replace_item(
+        &self,
+        collection_id: &str,
+        feature_id: &str,
+        feature: &serde_json::Value,
+        if_match: Option<&IfMatch>,
+    ) -> Result<bool> {
+        let replaced = self
+            .writable_source(collection_id)?
+            .replace_item(feature_id, feature, if_match)
+            .await?;
+        if replaced {
+            publish_feature_event(EventKind::FeatureUpdated, collection_id, feature_id);
+        }
+        Ok(replaced)
+    }
+
+    pub async fn update_item(
+        &self,
+        collection_id: &str,
+        feature_id: &str,
+        feature: &serde_json::Value,
+        if_match: Option<&IfMatch>,
+    ) -> Result<bool> {
+        let updated = self
+            .writable_source(collection_id)?
+            .update_item(feature_id, feature, if_match)
+            .await?;
+        if updated {
+            publish_feature_event(EventKind::FeatureUpdated, collection_id, feature_id);
+        }
+        Ok(updated)
+    }
+
+    pub async fn delete_item(
+        &self,
+        collection_id: &str,
+        feature_id: &str,
+        if_match: Option<&IfMatch>,
+    ) -> Result<bool> {
+        let deleted = self
+            .writable_source(collection_id)?
+            .delete_item(feature_id, if_match)
+            .await?;
+        if deleted {
+            publish_feature_event(EventKind::FeatureDeleted, collection_id, feature_id);
+        }
+        Ok(deleted)
+    }
+
     pub async fn collection_queryables(&self, collection_id: &str) -> Option<Queryables> {
         let Some(fc) = self.collection(collection_id) else {
             warn!("Ignoring error getting collection {collection_id}");
@@ -214,6 +568,113 @@ impl Inventory {
             }
         }
     }
+
+    /// JSON Schema of the item properties of a collection
+    pub async fn collection_properties_schema(&self, collection_id: &str) -> Option<Queryables> {
+        let fc = self.collection(collection_id)?;
+        match fc.source.properties_schema(collection_id).await {
+            Ok(schema) => schema,
+            Err(e) => {
+                warn!("Ignoring error getting properties schema of {collection_id}: {e}");
+                None
+            }
+        }
+    }
+}
+
+/// Number of items matching `filter`, None if the source doesn't count matches
+async fn count_matches(fc: &FeatureCollection, filter: &FilterParams) -> Option<u64> {
+    let mut params = filter.clone();
+    params.limit = Some(1);
+    params.offset = None;
+    params.skip_geometry = Some(true);
+    let items = fc.source.items(&params).await.ok()?;
+    match items.paging {
+        Paging::Offset => Some(items.number_matched),
+        Paging::Cursor(_) => None,
+    }
+}
+
+fn select_properties(feature: &mut CoreFeature, names: &[String]) {
+    if let Some(serde_json::Value::Object(properties)) = &mut feature.properties {
+        properties.retain(|name, _| names.contains(name));
+    }
+}
+
+fn publish_feature_event(kind: EventKind, collection_id: &str, feature_id: &str) {
+    publish(Event::collection(
+        kind,
+        collection_id,
+        serde_json::json!({ "id": feature_id }),
+    ));
+}
+
+/// Items response with self and paging links
+fn features_response(
+    collection_id: &str,
+    filter: &FilterParams,
+    number_matched: u64,
+    number_returned: u64,
+    features: Vec<CoreFeature>,
+    paging: &Paging,
+) -> CoreFeatures {
+    let mut features = CoreFeatures {
+        type_: "FeatureCollection".to_string(),
+        links: vec![
+            ApiLink {
+                href: format!("/collections/{collection_id}/items"),
+                rel: Some("self".to_string()),
+                type_: Some("text/html".to_string()),
+                title: Some("this document".to_string()),
+                hreflang: None,
+                length: None,
+            },
+            ApiLink {
+                href: format!("/collections/{collection_id}/items.json"),
+                rel: Some("self".to_string()),
+                type_: Some("application/geo+json".to_string()),
+                title: Some("this document".to_string()),
+                hreflang: None,
+                length: None,
+            },
+        ],
+        time_stamp: None, // time when the response was generated
+        number_matched: match paging {
+            Paging::Offset => Some(number_matched),
+            Paging::Cursor(_) => None,
+        },
+        number_returned: Some(number_returned),
+        features,
+    };
+    let mut add_link = |link: FilterParams, rel: &str| {
+        let params = link.as_args();
+        features.links.push(ApiLink {
+            href: format!("/collections/{collection_id}/items{params}"),
+            rel: Some(rel.to_string()),
+            type_: Some("text/html".to_string()),
+            title: Some(rel.to_string()),
+            hreflang: None,
+            length: None,
+        });
+    };
+    match paging {
+        Paging::Offset if number_matched > number_returned => {
+            if let Some(prev) = filter.prev() {
+                add_link(prev, "prev");
+            }
+            if let Some(next) = filter.next(number_matched) {
+                add_link(next, "next");
+            }
+        }
+        Paging::Offset => {}
+        // Keyset pagination is forward only
+        Paging::Cursor(next_key) => {
+            if let Some(key) = next_key {
+                add_link(filter.with_cursor(key), "next");
+            }
+        }
+    }
+    features
 }
 
 #[cfg(test)]
@@ -231,5 +692,101 @@ mod tests {
                 .map(|col| col.id.clone()),
             Some("ne_10m_lakes".to_string())
         );
+
+        let mut inventory = inventory;
+        let temporal = CoreExtentTemporal {
+            interval: vec![vec![Some("2020-01-01T00:00:00Z".to_string()), None]],
+            trs: None,
+        };
+        inventory.set_temporal_extent("ne_10m_lakes", Some(temporal));
+        let extent = inventory
+            .core_collection("ne_10m_lakes")
+            .and_then(|col| col.extent.as_ref())
+            .unwrap();
+        assert_eq!(extent.temporal.as_ref().unwrap().interval[0][1], None);
+    }
+
+    #[tokio::test]
+    async fn search_paging() {
+        let inventory = Inventory::scan(&CollectionsCfg::from_path("../assets")).await;
+        let collections = [
+            "ne_10m_rivers_lake_centerlines".to_string(),
+            "ne_10m_lakes".to_string(),
+        ];
+        let filter = FilterParams {
+            limit: Some(10),
+            ..Default::default()
+        };
+        let features = inventory.search(&collections, &filter).await;
+        assert_eq!(features.number_returned, Some(10));
+        assert_eq!(features.number_matched, Some(1355 + 1473));
+
+        // Page across the collection boundary
+        let features = inventory
+            .search(&collections, &filter.with_offset(1350))
+            .await;
+        assert_eq!(features.number_returned, Some(10));
+        assert_eq!(features.number_matched, Some(1355 + 1473));
+        let collection_ids: Vec<_> = features
+            .features
+            .iter()
+            .map(|feature| feature.links.last().unwrap().title.clone().unwrap())
+            .collect();
+        assert_eq!(collection_ids[4], "ne_10m_lakes");
+        assert_eq!(collection_ids[5], "ne_10m_rivers_lake_centerlines");
+
+        // Lakes skipped completely
+        let features = inventory
+            .search(&collections, &filter.with_offset(2820))
+            .await;
+        assert_eq!(features.number_returned, Some(8));
+        assert_eq!(features.number_matched, Some(1355 + 1473));
+    }
+
+    #[test]
+    fn cursor_links() {
+        let filter = FilterParams {
+            limit: Some(2),
+            ..Default::default()
+        };
+        let features = features_response(
+            "places",
+            &filter,
+            0,
+            2,
+            Vec::new(),
+            &Paging::Cursor(Some("7".to_string())),
+        );
+        assert_eq!(features.number_matched, None);
+        let links: Vec<_> = features
+            .links
+            .iter()
+            .map(|l| (l.rel.as_deref(), l.href.as_str()))
+            .collect();
+        assert_eq!(
+            links[2],
+            (Some("next"), "/collections/places/items?limit=2&cursor=37")
+        );
+
+        let features =
+            features_response("places", &filter, 0, 1, Vec::new(), &Paging::Cursor(None));
+        assert_eq!(features.links.len(), 2);
+    }
+
+    #[test]
+    fn property_selection() {
+        let mut feature = CoreFeature {
+            type_: "Feature".to_string(),
+            bbox: None,
+            id: Some("1".to_string()),
+            geometry: serde_json::Value::Null,
+            properties: Some(serde_json::json!({"name": "Rhein", "scalerank": 2})),
+            links: Vec::new(),
+        };
+        select_properties(&mut feature, &["name".to_string(), "unknown".to_string()]);
+        assert_eq!(
+            feature.properties,
+            Some(serde_json::json!({"name": "Rhein"}))
+        );
     }
 }
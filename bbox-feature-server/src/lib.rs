@@ -1,9 +1,23 @@
+pub mod builder;
+pub mod cli;
 pub mod config;
+mod cql2;
+mod crs;
 pub mod datasource;
+mod dump;
 mod endpoints;
 mod error;
 mod filter_params;
 mod inventory;
+mod openapi;
+mod output;
+#[cfg(feature = "map-server")]
+mod qgis_collections;
+mod s3_sync;
+mod search;
 pub mod service;
 
+pub use builder::FeatureServiceBuilder;
+pub use error::Error;
+pub use inventory::{FeatureCollection, Inventory};
 pub use service::*;
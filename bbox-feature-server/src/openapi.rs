@@ -0,0 +1,247 @@
+//! OpenAPI paths of the configured collections.
+
+use crate::inventory::Inventory;
+use crate::output::OutputFormat;
+use async_trait::async_trait;
+use bbox_core::api::OpenApiProvider;
+use bbox_core::ogcapi::{CoreCollection, QueryableProperty, QueryableType};
+use bbox_core::reload::Reloadable;
+use serde_json::{json, Map, Value};
+use std::sync::{Arc, Mutex, Weak};
+
+/// OpenAPI paths generated from the inventory.
+/// The document is cached until the inventory is replaced by a configuration reload.
+pub struct InventoryOpenApi {
+    inventory: Reloadable<Inventory>,
+    cache: Mutex<Option<(Weak<Inventory>, Value)>>,
+}
+
+impl InventoryOpenApi {
+    pub fn new(inventory: Reloadable<Inventory>) -> Self {
+        InventoryOpenApi {
+            inventory,
+            cache: Mutex::new(None),
+        }
+    }
+}
+
+#[async_trait]
+impl OpenApiProvider for InventoryOpenApi {
+    async fn openapi_doc(&self) -> Value {
+        let inventory = self.inventory.current();
+        {
+            let cache = self.cache.lock().unwrap();
+            if let Some((cached, doc)) = cache.as_ref() {
+                if Weak::ptr_eq(cached, &Arc::downgrade(&inventory)) {
+                    return doc.clone();
+                }
+            }
+        }
+        let doc = inventory_doc(&inventory).await;
+        *self.cache.lock().unwrap() = Some((Arc::downgrade(&inventory), doc.clone()));
+        doc
+    }
+}
+
+/// Paths of all collections with their queryables and output formats
+async fn inventory_doc(inventory: &Inventory) -> Value {
+    let mut paths = Map::new();
+    for collection in inventory.collections() {
+        let id = &collection.id;
+        let title = collection.title.as_deref().unwrap_or(id);
+        let mut parameters: Vec<Value> = [
+            "limit",
+            "bbox",
+            "datetime",
+            "filter",
+            "filter-lang",
+            "crs",
+            "bbox-crs",
+            "f",
+        ]
+        .iter()
+        .map(|name| json!({ "$ref": format!("#/components/parameters/{name}") }))
+        .collect();
+        if let Some(queryables) = inventory.collection_queryables(id).await {
+            let mut properties: Vec<_> = queryables.properties.iter().collect();
+            properties.sort_by(|a, b| a.0.cmp(b.0));
+            parameters.extend(
+                properties
+                    .into_iter()
+                    .map(|(name, property)| queryable_parameter(name, property)),
+            );
+        }
+        let formats = output_formats(&collection);
+        paths.insert(
+            format!("/collections/{id}"),
+            json!({
+                "get": {
+                    "tags": ["Features"],
+                    "summary": format!("describe the feature collection '{title}'"),
+                    "operationId": format!("describeCollection.{id}"),
+                    "responses": {
+                        "200": { "$ref": "#/components/responses/Collection" },
+                        "404": { "$ref": "#/components/responses/NotFound" },
+                        "500": { "$ref": "#/components/responses/ServerError" }
+                    }
+                }
+            }),
+        );
+        paths.insert(
+            format!("/collections/{id}/items"),
+            json!({
+                "get": {
+                    "tags": ["Features"],
+                    "summary": format!("fetch features of '{title}'"),
+                    "operationId": format!("getFeatures.{id}"),
+                    "parameters": parameters,
+                    "responses": {
+                        "200": {
+                            "description": format!("Features of '{title}'"),
+                            "content": content(&formats, "featureCollectionGeoJSON")
+                        },
+                        "400": { "$ref": "#/components/responses/InvalidParameter" },
+                        "404": { "$ref": "#/components/responses/NotFound" },
+                        "500": { "$ref": "#/components/responses/ServerError" }
+                    }
+                }
+            }),
+        );
+        let feature_formats: Vec<_> = formats
+            .into_iter()
+            .filter(|format| {
+                !matches!(
+                    format,
+                    OutputFormat::FlatGeobuf | OutputFormat::Geobuf | OutputFormat::Shapefile
+                )
+            })
+            .collect();
+        paths.insert(
+            format!("/collections/{id}/items/{{featureId}}"),
+            json!({
+                "get": {
+                    "tags": ["Features"],
+                    "summary": format!("fetch a single feature of '{title}'"),
+                    "operationId": format!("getFeature.{id}"),
+                    "parameters": [
+                        { "$ref": "#/components/parameters/featureId" },
+                        { "$ref": "#/components/parameters/crs" }
+                    ],
+                    "responses": {
+                        "200": {
+                            "description": format!("Feature of '{title}'"),
+                            "content": content(&feature_formats, "featureGeoJSON")
+                        },
+                        "404": { "$ref": "#/components/responses/NotFound" },
+                        "500": { "$ref": "#/components/responses/ServerError" }
+                    }
+                }
+            }),
+        );
+    }
+    json!({ "paths": paths })
+}
+
+/// Output formats with an items link in the collection
+fn output_formats(collection: &CoreCollection) -> Vec<OutputFormat> {
+    let mut formats = vec![OutputFormat::GeoJson];
+    formats.extend(
+        [
+            OutputFormat::JsonFg,
+            OutputFormat::FlatGeobuf,
+            OutputFormat::Geobuf,
+            OutputFormat::Shapefile,
+        ]
+        .into_iter()
+        .filter(|format| {
+            collection
+                .links
+                .iter()
+                .any(|link| link.type_.as_deref() == Some(format.content_type()))
+        }),
+    );
+    if cfg!(feature = "html") {
+        formats.push(OutputFormat::Html);
+    }
+    formats
+}
+
+/// Response content by media type
+fn content(formats: &[OutputFormat], geojson_schema: &str) -> Value {
+    let content: Map<String, Value> = formats
+        .iter()
+        .map(|format| {
+            let schema = match format {
+                OutputFormat::GeoJson => {
+                    json!({ "$ref": format!("#/components/schemas/{geojson_schema}") })
+                }
+                OutputFormat::JsonFg => json!({ "type": "object" }),
+                OutputFormat::FlatGeobuf | OutputFormat::Geobuf | OutputFormat::Shapefile => {
+                    json!({ "type": "string", "format": "binary" })
+                }
+                OutputFormat::Html => json!({ "type": "string" }),
+            };
+            (
+                format.content_type().to_string(),
+                json!({ "schema": schema }),
+            )
+        })
+        .collect();
+    content.into()
+}
+
+/// Query parameter for filtering by a queryable property
+fn queryable_parameter(name: &str, property: &QueryableProperty) -> Value {
+    let schema = match property.type_ {
+        Some(QueryableType::Integer) => json!({ "type": "integer" }),
+        Some(QueryableType::Number) => json!({ "type": "number" }),
+        Some(QueryableType::Bool) => json!({ "type": "boolean" }),
+        Some(QueryableType::Datetime) => json!({ "type": "string", "format": "date-time" }),
+        Some(QueryableType::String) | None => json!({ "type": "string" }),
+    };
+    json!({
+        "name": name,
+        "in": "query",
+        "description": property.title.clone().unwrap_or(format!("Filter by `{name}` (`*` as wildcard)")),
+        "required": false,
+        "schema": schema,
+        "style": "form",
+        "explode": false
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::CollectionsCfg;
+
+    #[tokio::test]
+    async fn collection_paths() {
+        let inventory = Inventory::scan(&CollectionsCfg::from_path("../assets")).await;
+        let openapi = InventoryOpenApi::new(Reloadable::new(inventory));
+        let doc = openapi.openapi_doc().await;
+        let items = &doc["paths"]["/collections/ne_10m_lakes/items"]["get"];
+        assert_eq!(items["operationId"], "getFeatures.ne_10m_lakes");
+        assert!(items["responses"]["200"]["content"]["application/flatgeobuf"].is_object());
+        assert!(items["responses"]["200"]["content"]["application/geobuf"].is_object());
+        assert!(doc["paths"]["/collections/ne_10m_lakes/items/{featureId}"].is_object());
+        // Cached until reload
+        assert!(openapi.cache.lock().unwrap().is_some());
+        assert_eq!(openapi.openapi_doc().await, doc);
+    }
+
+    #[test]
+    fn queryable_schema() {
+        let property = QueryableProperty {
+            type_: Some(QueryableType::Datetime),
+            title: None,
+            format: None,
+            enum_: None,
+            minimum: None,
+            maximum: None,
+        };
+        let param = queryable_parameter("updated", &property);
+        assert_eq!(param["in"], "query");
+        assert_eq!(param["schema"]["format"], "date-time");
+    }
+}
@@ -0,0 +1,287 @@
+//! Output formats of collection items.
+
+use crate::crs::CRS84;
+use crate::error::{Error, Result};
+use bbox_core::ogcapi::{ApiLink, CoreFeature, CoreFeatures};
+use flatgeobuf::{FgbWriter, GeometryType};
+use futures::stream::BoxStream;
+use futures::TryStreamExt;
+use geozero::geojson::read_geojson;
+use serde_json::{json, Value};
+
+mod geobuf;
+mod shapefile;
+pub use geobuf::geobuf;
+pub use shapefile::{shapefile_zip, WGS84_PRJ};
+
+/// JSON-FG core conformance class
+pub const JSONFG_CORE: &str = "http://www.opengis.net/spec/json-fg-1/0.2/conf/core";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutputFormat {
+    GeoJson,
+    JsonFg,
+    FlatGeobuf,
+    Geobuf,
+    Shapefile,
+    Html,
+}
+
+impl OutputFormat {
+    /// Format from `f` query parameter
+    pub fn from_param(value: &str) -> Option<Self> {
+        Some(match value.to_ascii_lowercase().as_str() {
+            "json" | "geojson" => Self::GeoJson,
+            "jsonfg" => Self::JsonFg,
+            "fgb" | "flatgeobuf" => Self::FlatGeobuf,
+            "geobuf" | "pbf" => Self::Geobuf,
+            "shapefile" | "shp" => Self::Shapefile,
+            "html" => Self::Html,
+            _ => None?,
+        })
+    }
+
+    pub fn from_content_type(mime: &str) -> Option<Self> {
+        Some(match mime {
+            "application/geo+json" | "application/json" => Self::GeoJson,
+            "application/vnd.ogc.fg+json" => Self::JsonFg,
+            "application/flatgeobuf" => Self::FlatGeobuf,
+            "application/geobuf" => Self::Geobuf,
+            "application/x-zipped-shp" => Self::Shapefile,
+            "text/html" => Self::Html,
+            _ => None?,
+        })
+    }
+
+    pub fn file_suffix(&self) -> &str {
+        match *self {
+            Self::GeoJson => "json",
+            Self::JsonFg => "jsonfg",
+            Self::FlatGeobuf => "fgb",
+            Self::Geobuf => "pbf",
+            Self::Shapefile => "zip",
+            Self::Html => "html",
+        }
+    }
+
+    pub fn content_type(&self) -> &str {
+        match *self {
+            Self::GeoJson => "application/geo+json",
+            Self::JsonFg => "application/vnd.ogc.fg+json",
+            Self::FlatGeobuf => "application/flatgeobuf",
+            Self::Geobuf => "application/geobuf",
+            Self::Shapefile => "application/x-zipped-shp",
+            Self::Html => "text/html",
+        }
+    }
+
+    /// Link to the items of a collection in this format
+    pub fn items_link(&self, collection_id: &str, title: Option<String>) -> ApiLink {
+        ApiLink {
+            href: format!("/collections/{collection_id}/items.{}", self.file_suffix()),
+            rel: Some("items".to_string()),
+            type_: Some(self.content_type().to_string()),
+            title,
+            hreflang: None,
+            length: None,
+        }
+    }
+}
+
+/// Write features into a FlatGeobuf file.
+///
+/// FlatGeobuf has a header with the feature count, so the features are written
+/// into a temporary file by the writer before being returned.
+pub async fn flatgeobuf(
+    name: &str,
+    mut features: BoxStream<'_, Result<CoreFeature>>,
+) -> Result<Vec<u8>> {
+    let mut fgb = FgbWriter::create(name, GeometryType::Unknown)
+        .map_err(|e| Error::OutputFormatError(e.to_string()))?;
+    while let Some(feature) = features.try_next().await? {
+        let json = serde_json::to_vec(&feature)?;
+        read_geojson(json.as_slice(), &mut fgb)
+            .map_err(|e| Error::OutputFormatError(e.to_string()))?;
+    }
+    let mut buf = Vec::new();
+    fgb.write(&mut buf)
+        .map_err(|e| Error::OutputFormatError(e.to_string()))?;
+    Ok(buf)
+}
+
+/// JSON-FG feature collection with geometries in `coord_ref_sys`.
+///
+/// `temporal` contains the properties with the feature time instant or interval.
+pub fn jsonfg_features(
+    features: &CoreFeatures,
+    coord_ref_sys: &str,
+    temporal: Option<(&str, Option<&str>)>,
+) -> Result<Value> {
+    let mut json = serde_json::to_value(features)?;
+    json["conformsTo"] = json!([JSONFG_CORE]);
+    json["coordRefSys"] = json!(coord_ref_sys);
+    json["features"] = features
+        .features
+        .iter()
+        .map(|feature| jsonfg_feature_members(feature, coord_ref_sys, temporal))
+        .collect::<Result<Vec<_>>>()?
+        .into();
+    Ok(json)
+}
+
+/// JSON-FG feature document with geometry in `coord_ref_sys`.
+pub fn jsonfg_feature(
+    feature: &CoreFeature,
+    coord_ref_sys: &str,
+    temporal: Option<(&str, Option<&str>)>,
+) -> Result<Value> {
+    let mut json = jsonfg_feature_members(feature, coord_ref_sys, temporal)?;
+    json["conformsTo"] = json!([JSONFG_CORE]);
+    json["coordRefSys"] = json!(coord_ref_sys);
+    Ok(json)
+}
+
+/// Feature with `time` and `place` members.
+/// GeoJSON `geometry` is restricted to WGS84, other geometries are returned in `place`.
+fn jsonfg_feature_members(
+    feature: &CoreFeature,
+    coord_ref_sys: &str,
+    temporal: Option<(&str, Option<&str>)>,
+) -> Result<Value> {
+    let mut json = serde_json::to_value(feature)?;
+    json["time"] = jsonfg_time(feature.properties.as_ref(), temporal);
+    if coord_ref_sys == CRS84 {
+        json["place"] = Value::Null;
+    } else {
+        let geometry = json["geometry"].take();
+        json["place"] = geometry;
+    }
+    Ok(json)
+}
+
+fn jsonfg_time(properties: Option<&Value>, temporal: Option<(&str, Option<&str>)>) -> Value {
+    let Some((start, end)) = temporal else {
+        return Value::Null;
+    };
+    let value = |name: &str| {
+        properties
+            .and_then(|props| props.get(name))
+            .filter(|val| !val.is_null())
+            .cloned()
+    };
+    match end {
+        None => value(start).map_or(Value::Null, |instant| json!({ "instant": instant })),
+        Some(end) => match (value(start), value(end)) {
+            (None, None) => Value::Null,
+            // Unbounded intervals are represented with ".."
+            (start, end) => json!({ "interval": [
+                start.unwrap_or_else(|| json!("..")),
+                end.unwrap_or_else(|| json!(".."))
+            ]}),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::{stream, StreamExt};
+    use serde_json::json;
+
+    #[test]
+    fn format_negotiation() {
+        assert_eq!(
+            OutputFormat::from_param("FGB"),
+            Some(OutputFormat::FlatGeobuf)
+        );
+        assert_eq!(
+            OutputFormat::from_content_type("application/flatgeobuf"),
+            Some(OutputFormat::FlatGeobuf)
+        );
+        assert_eq!(
+            OutputFormat::from_content_type("application/vnd.ogc.fg+json"),
+            Some(OutputFormat::JsonFg)
+        );
+        assert_eq!(
+            OutputFormat::from_content_type("application/geobuf"),
+            Some(OutputFormat::Geobuf)
+        );
+        assert_eq!(
+            OutputFormat::from_param("shapefile"),
+            Some(OutputFormat::Shapefile)
+        );
+        assert_eq!(OutputFormat::from_param("csv"), None);
+        assert_eq!(
+            OutputFormat::Geobuf.items_link("lakes", None).href,
+            "/collections/lakes/items.pbf"
+        );
+        assert_eq!(
+            OutputFormat::FlatGeobuf.items_link("lakes", None).href,
+            "/collections/lakes/items.fgb"
+        );
+    }
+
+    #[test]
+    fn jsonfg_output() {
+        let feature = CoreFeature {
+            type_: "Feature".to_string(),
+            bbox: None,
+            id: Some("1".to_string()),
+            geometry: json!({"type": "Point", "coordinates": [2600000.0, 1200000.0]}),
+            properties: Some(json!({"name": "Bern", "start": "2020-01-01", "end": null})),
+            links: vec![],
+        };
+        let json = jsonfg_feature(
+            &feature,
+            "http://www.opengis.net/def/crs/EPSG/0/2056",
+            Some(("start", Some("end"))),
+        )
+        .unwrap();
+        assert_eq!(json["geometry"], Value::Null);
+        assert_eq!(json["place"]["coordinates"][0], 2600000.0);
+        assert_eq!(json["time"], json!({"interval": ["2020-01-01", ".."]}));
+        assert_eq!(json["conformsTo"][0], JSONFG_CORE);
+
+        let json = jsonfg_feature(&feature, CRS84, Some(("start", None))).unwrap();
+        assert_eq!(json["geometry"]["type"], "Point");
+        assert_eq!(json["place"], Value::Null);
+        assert_eq!(json["time"], json!({"instant": "2020-01-01"}));
+        assert_eq!(json["coordRefSys"], CRS84);
+
+        let json = jsonfg_feature(&feature, CRS84, None).unwrap();
+        assert_eq!(json["time"], Value::Null);
+
+        let features = CoreFeatures {
+            type_: "FeatureCollection".to_string(),
+            links: vec![],
+            time_stamp: None,
+            number_matched: None,
+            number_returned: Some(1),
+            features: vec![feature],
+        };
+        let json = jsonfg_features(&features, CRS84, None).unwrap();
+        assert_eq!(json["features"][0]["place"], Value::Null);
+        assert_eq!(json["coordRefSys"], CRS84);
+    }
+
+    #[tokio::test]
+    async fn fgb_output() {
+        let features = (0..3)
+            .map(|i| {
+                Ok(CoreFeature {
+                    type_: "Feature".to_string(),
+                    bbox: None,
+                    id: Some(i.to_string()),
+                    geometry: json!({"type": "Point", "coordinates": [7.5, 47.2]}),
+                    properties: Some(json!({"name": "Bern", "rank": i})),
+                    links: vec![],
+                })
+            })
+            .collect::<Vec<_>>();
+        let buf = flatgeobuf("places", stream::iter(features).boxed())
+            .await
+            .unwrap();
+        // FlatGeobuf magic bytes
+        assert_eq!(&buf[..3], b"fgb");
+    }
+}
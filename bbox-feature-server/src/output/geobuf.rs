@@ -0,0 +1,372 @@
+//! Geobuf encoding of features.
+//!
+//! Geobuf is a compact protobuf encoding of GeoJSON (<https://github.com/mapbox/geobuf>).
+//! Coordinates are stored as delta-encoded integers with the precision of the input coordinates.
+
+use crate::error::{Error, Result};
+use bbox_core::ogcapi::CoreFeature;
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// Maximal number of decimal digits of coordinates
+const MAX_PRECISION: u32 = 6;
+/// Precision assumed by decoders without `precision` field
+const DEFAULT_PRECISION: u32 = 6;
+
+// Protobuf wire types
+const VARINT: u64 = 0;
+const FIXED64: u64 = 1;
+const LENGTH_DELIMITED: u64 = 2;
+
+/// Encode features as Geobuf FeatureCollection.
+pub fn geobuf(features: &[CoreFeature]) -> Result<Vec<u8>> {
+    let encoder = GeobufEncoder::analyze(features);
+    let mut fc = PbfWriter::default();
+    for feature in features {
+        fc.message_field(1, encoder.feature(feature)?);
+    }
+    let mut data = PbfWriter::default();
+    for key in &encoder.keys {
+        data.string_field(1, key);
+    }
+    if encoder.dim != 2 {
+        data.varint_field(2, encoder.dim as u64);
+    }
+    if encoder.precision != DEFAULT_PRECISION {
+        data.varint_field(3, encoder.precision as u64);
+    }
+    data.message_field(4, fc);
+    Ok(data.buf)
+}
+
+/// Minimal protobuf message writer
+#[derive(Default)]
+struct PbfWriter {
+    buf: Vec<u8>,
+}
+
+impl PbfWriter {
+    fn varint(&mut self, mut value: u64) {
+        while value >= 0x80 {
+            self.buf.push((value as u8 & 0x7f) | 0x80);
+            value >>= 7;
+        }
+        self.buf.push(value as u8);
+    }
+    fn tag(&mut self, field: u64, wire_type: u64) {
+        self.varint(field << 3 | wire_type);
+    }
+    fn varint_field(&mut self, field: u64, value: u64) {
+        self.tag(field, VARINT);
+        self.varint(value);
+    }
+    fn svarint_field(&mut self, field: u64, value: i64) {
+        self.varint_field(field, zigzag(value));
+    }
+    fn double_field(&mut self, field: u64, value: f64) {
+        self.tag(field, FIXED64);
+        self.buf.extend_from_slice(&value.to_le_bytes());
+    }
+    fn bytes_field(&mut self, field: u64, bytes: &[u8]) {
+        self.tag(field, LENGTH_DELIMITED);
+        self.varint(bytes.len() as u64);
+        self.buf.extend_from_slice(bytes);
+    }
+    fn string_field(&mut self, field: u64, value: &str) {
+        self.bytes_field(field, value.as_bytes());
+    }
+    fn message_field(&mut self, field: u64, message: PbfWriter) {
+        self.bytes_field(field, &message.buf);
+    }
+    fn packed_varint_field(&mut self, field: u64, values: impl Iterator<Item = u64>) {
+        let mut packed = PbfWriter::default();
+        values.for_each(|value| packed.varint(value));
+        if !packed.buf.is_empty() {
+            self.message_field(field, packed);
+        }
+    }
+}
+
+fn zigzag(value: i64) -> u64 {
+    ((value << 1) ^ (value >> 63)) as u64
+}
+
+/// Keys and coordinate encoding shared by all features
+struct GeobufEncoder {
+    keys: Vec<String>,
+    key_index: HashMap<String, u64>,
+    dim: usize,
+    precision: u32,
+    factor: f64,
+}
+
+impl GeobufEncoder {
+    fn analyze(features: &[CoreFeature]) -> Self {
+        let mut keys = Vec::new();
+        let mut key_index = HashMap::new();
+        let mut dim = 2;
+        let mut precision = 0;
+        for feature in features {
+            if let Some(Value::Object(properties)) = &feature.properties {
+                for key in properties.keys() {
+                    if !key_index.contains_key(key) {
+                        key_index.insert(key.clone(), keys.len() as u64);
+                        keys.push(key.clone());
+                    }
+                }
+            }
+            visit_positions(&feature.geometry, &mut |position| {
+                dim = dim.max(position.len());
+                for coord in position.iter().filter_map(Value::as_f64) {
+                    precision = precision.max(decimals(coord));
+                }
+            });
+        }
+        GeobufEncoder {
+            keys,
+            key_index,
+            dim,
+            precision,
+            factor: 10f64.powi(precision as i32),
+        }
+    }
+
+    fn feature(&self, feature: &CoreFeature) -> Result<PbfWriter> {
+        let mut pbf = PbfWriter::default();
+        if !feature.geometry.is_null() {
+            pbf.message_field(1, self.geometry(&feature.geometry)?);
+        }
+        if let Some(id) = &feature.id {
+            match id.parse::<i64>() {
+                Ok(int_id) if int_id.to_string() == *id => pbf.svarint_field(12, int_id),
+                _ => pbf.string_field(11, id),
+            }
+        }
+        if let Some(Value::Object(properties)) = &feature.properties {
+            let mut indexes = Vec::with_capacity(properties.len() * 2);
+            for (i, (key, value)) in properties.iter().enumerate() {
+                pbf.message_field(13, property_value(value));
+                indexes.push(self.key_index[key]);
+                indexes.push(i as u64);
+            }
+            pbf.packed_varint_field(14, indexes.into_iter());
+        }
+        Ok(pbf)
+    }
+
+    fn geometry(&self, geometry: &Value) -> Result<PbfWriter> {
+        let geometry_type = geometry["type"].as_str().unwrap_or_default();
+        let coordinates = &geometry["coordinates"];
+        let mut pbf = PbfWriter::default();
+        let mut lengths = Vec::new();
+        let mut coords = Vec::new();
+        match geometry_type {
+            "Point" => {
+                pbf.varint_field(1, 0);
+                self.push_line(&mut coords, std::slice::from_ref(coordinates), false);
+            }
+            "MultiPoint" | "LineString" => {
+                pbf.varint_field(1, if geometry_type == "MultiPoint" { 1 } else { 2 });
+                self.push_line(&mut coords, array(coordinates)?, false);
+            }
+            "MultiLineString" | "Polygon" => {
+                let closed = geometry_type == "Polygon";
+                pbf.varint_field(1, if closed { 4 } else { 3 });
+                let lines = array(coordinates)?;
+                for line in lines {
+                    let line = array(line)?;
+                    if lines.len() != 1 {
+                        lengths.push(line.len().saturating_sub(closed as usize) as u64);
+                    }
+                    self.push_line(&mut coords, line, closed);
+                }
+            }
+            "MultiPolygon" => {
+                pbf.varint_field(1, 5);
+                let polygons = array(coordinates)?;
+                lengths.push(polygons.len() as u64);
+                for polygon in polygons {
+                    let rings = array(polygon)?;
+                    lengths.push(rings.len() as u64);
+                    for ring in rings {
+                        let ring = array(ring)?;
+                        lengths.push(ring.len().saturating_sub(1) as u64);
+                        self.push_line(&mut coords, ring, true);
+                    }
+                }
+                // Lengths of a single polygon without holes are omitted
+                if lengths.len() == 3 && lengths[..2] == [1, 1] {
+                    lengths.clear();
+                }
+            }
+            "GeometryCollection" => {
+                pbf.varint_field(1, 6);
+                for geometry in array(&geometry["geometries"])? {
+                    pbf.message_field(4, self.geometry(geometry)?);
+                }
+            }
+            _ => {
+                return Err(Error::OutputFormatError(format!(
+                    "Unsupported geometry type `{geometry_type}`"
+                )))
+            }
+        }
+        pbf.packed_varint_field(2, lengths.into_iter());
+        pbf.packed_varint_field(3, coords.into_iter().map(zigzag));
+        Ok(pbf)
+    }
+
+    /// Append delta-encoded positions. The closing position of rings is omitted.
+    fn push_line(&self, coords: &mut Vec<i64>, line: &[Value], closed: bool) {
+        let len = line.len().saturating_sub(closed as usize);
+        let mut sum = vec![0; self.dim];
+        for position in &line[..len] {
+            for (j, sum) in sum.iter_mut().enumerate() {
+                let coord = position.get(j).and_then(Value::as_f64).unwrap_or(0.0);
+                let n = (coord * self.factor).round() as i64 - *sum;
+                coords.push(n);
+                *sum += n;
+            }
+        }
+    }
+}
+
+fn array(value: &Value) -> Result<&[Value]> {
+    value
+        .as_array()
+        .map(Vec::as_slice)
+        .ok_or_else(|| Error::OutputFormatError("Invalid GeoJSON coordinates".to_string()))
+}
+
+/// Call `f` for each position of a GeoJSON geometry
+fn visit_positions(value: &Value, f: &mut impl FnMut(&[Value])) {
+    match value {
+        Value::Object(geometry) => {
+            if let Some(coordinates) = geometry.get("coordinates") {
+                visit_positions(coordinates, f);
+            }
+            if let Some(Value::Array(geometries)) = geometry.get("geometries") {
+                geometries
+                    .iter()
+                    .for_each(|geometry| visit_positions(geometry, f));
+            }
+        }
+        Value::Array(values) if matches!(values.first(), Some(Value::Number(_))) => f(values),
+        Value::Array(values) => values.iter().for_each(|value| visit_positions(value, f)),
+        _ => {}
+    }
+}
+
+/// Number of decimal digits required to represent `coord`
+fn decimals(coord: f64) -> u32 {
+    let mut precision = 0;
+    let mut e = 1.0;
+    while precision < MAX_PRECISION && (coord * e).round() / e != coord {
+        precision += 1;
+        e *= 10.0;
+    }
+    precision
+}
+
+/// Geobuf Value message. Arrays and objects are stored as JSON.
+fn property_value(value: &Value) -> PbfWriter {
+    let mut pbf = PbfWriter::default();
+    match value {
+        Value::Null => {}
+        Value::String(s) => pbf.string_field(1, s),
+        Value::Bool(b) => pbf.varint_field(5, *b as u64),
+        Value::Number(n) => {
+            if let Some(v) = n.as_u64() {
+                pbf.varint_field(3, v);
+            } else if let Some(v) = n.as_i64() {
+                pbf.varint_field(4, v.unsigned_abs());
+            } else {
+                pbf.double_field(2, n.as_f64().unwrap_or_default());
+            }
+        }
+        Value::Array(_) | Value::Object(_) => pbf.string_field(6, &value.to_string()),
+    }
+    pbf
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn feature(id: &str, geometry: Value, properties: Value) -> CoreFeature {
+        CoreFeature {
+            type_: "Feature".to_string(),
+            bbox: None,
+            id: Some(id.to_string()),
+            geometry,
+            properties: Some(properties),
+            links: vec![],
+        }
+    }
+
+    #[test]
+    fn point_feature() {
+        let features = [feature(
+            "1",
+            json!({"type": "Point", "coordinates": [7.5, 47.2]}),
+            json!({"name": "Bern"}),
+        )];
+        let buf = geobuf(&features).unwrap();
+        // keys: ["name"], precision: 1
+        assert_eq!(&buf[..8], &[0x0a, 4, b'n', b'a', b'm', b'e', 0x18, 1]);
+        // Point with coordinates [75, 472]
+        let geometry = [0x0a, 8, 0x08, 0, 0x1a, 4, 0x96, 0x01, 0xb0, 0x07];
+        assert!(buf.windows(geometry.len()).any(|w| w == geometry));
+        // int_id 1, Value "Bern", properties [0, 0]
+        let tail = [
+            0x60, 2, 0x6a, 6, 0x0a, 4, b'B', b'e', b'r', b'n', 0x72, 2, 0, 0,
+        ];
+        assert!(buf.ends_with(&tail));
+    }
+
+    #[test]
+    fn polygon_features() {
+        let ring = json!([[0, 0], [10, 0], [10, 10], [0, 0]]);
+        let features = [
+            feature(
+                "a",
+                json!({"type": "Polygon", "coordinates": [ring]}),
+                json!({"rank": -1, "area": 0.5, "tags": ["x"], "valid": null}),
+            ),
+            feature(
+                "b",
+                json!({"type": "MultiPolygon", "coordinates": [[ring], [ring, ring]]}),
+                json!({"rank": 2}),
+            ),
+        ];
+        let encoder = GeobufEncoder::analyze(&features);
+        let mut keys = encoder.keys.clone();
+        keys.sort();
+        assert_eq!(keys, vec!["area", "rank", "tags", "valid"]);
+        assert_eq!((encoder.dim, encoder.precision), (2, 0));
+
+        let polygon = encoder.geometry(&features[0].geometry).unwrap();
+        // Closing position omitted, delta-encoded (0,0), (10,0), (0,10)
+        assert_eq!(polygon.buf, [0x08, 4, 0x1a, 6, 0, 0, 20, 0, 0, 20]);
+        let multipolygon = encoder.geometry(&features[1].geometry).unwrap();
+        assert_eq!(
+            &multipolygon.buf[..10],
+            &[0x08, 5, 0x12, 6, 2, 1, 3, 2, 3, 3]
+        );
+
+        let buf = geobuf(&features).unwrap();
+        assert!(buf.windows(3).any(|w| w == [0x5a, 1, b'a']));
+        let curve = json!({"type": "Curve", "coordinates": []});
+        assert!(encoder.geometry(&curve).is_err());
+    }
+
+    #[test]
+    fn coordinate_precision() {
+        assert_eq!(decimals(7.0), 0);
+        assert_eq!(decimals(7.25), 2);
+        assert_eq!(decimals(7.123456789), 6);
+        assert_eq!(zigzag(-1), 1);
+        assert_eq!(zigzag(1), 2);
+    }
+}
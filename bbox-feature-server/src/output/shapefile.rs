@@ -0,0 +1,723 @@
+//! ESRI Shapefile export of features as zip archive.
+//!
+//! A shapefile contains a single geometry type, so features with different geometry types
+//! are written into one shapefile per type. Z coordinates are not exported.
+//! Attribute names are truncated to the 10 characters supported by dBase.
+
+use crate::error::{Error, Result};
+use bbox_core::ogcapi::CoreFeature;
+use chrono::Datelike;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::io::{Cursor, Write};
+use zip::write::{FileOptions, ZipWriter};
+
+/// WGS84 coordinate system in ESRI WKT
+pub const WGS84_PRJ: &str = r#"GEOGCS["GCS_WGS_1984",DATUM["D_WGS_1984",SPHEROID["WGS_1984",6378137.0,298.257223563]],PRIMEM["Greenwich",0.0],UNIT["Degree",0.0174532925199433]]"#;
+
+/// Maximal length of dBase field names
+const MAX_FIELD_NAME_LEN: usize = 10;
+/// Maximal length of dBase character fields
+const MAX_CHARACTER_LEN: usize = 254;
+/// Maximal length of dBase numeric fields
+const MAX_NUMERIC_LEN: usize = 24;
+/// Maximal number of decimals of dBase numeric fields
+const MAX_DECIMALS: usize = 15;
+
+/// Shapefiles of features as zip archive.
+///
+/// `prj` is the WKT of the coordinate reference system, if known.
+pub fn shapefile_zip(name: &str, features: &[CoreFeature], prj: Option<&str>) -> Result<Vec<u8>> {
+    let mut shapes = Vec::with_capacity(features.len());
+    for feature in features {
+        shapes.push(Shape::from_geojson(&feature.geometry)?);
+    }
+    let dbf = DbfTable::analyze(features);
+
+    // Features of each shape type, null shapes are added to the first group
+    let mut groups: Vec<(ShapeType, Vec<usize>)> = Vec::new();
+    for shape_type in [
+        ShapeType::Point,
+        ShapeType::MultiPoint,
+        ShapeType::PolyLine,
+        ShapeType::Polygon,
+    ] {
+        let indexes: Vec<_> = (0..shapes.len())
+            .filter(|i| shapes[*i].shape_type() == shape_type)
+            .collect();
+        if !indexes.is_empty() {
+            groups.push((shape_type, indexes));
+        }
+    }
+    let nulls = (0..shapes.len()).filter(|i| shapes[*i].shape_type() == ShapeType::Null);
+    match groups.first_mut() {
+        Some((_, indexes)) => {
+            indexes.extend(nulls);
+            indexes.sort_unstable();
+        }
+        None => groups.push((ShapeType::Null, nulls.collect())),
+    }
+
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    let options = FileOptions::default();
+    let single = groups.len() == 1;
+    for (shape_type, indexes) in &groups {
+        let basename = if single {
+            name.to_string()
+        } else {
+            format!("{name}_{}", shape_type.suffix())
+        };
+        let mut shp = ShapeWriter::new(*shape_type);
+        for i in indexes {
+            shp.add(&shapes[*i]);
+        }
+        let (shp, shx) = shp.finish();
+        let records: Vec<_> = indexes.iter().map(|i| &features[*i]).collect();
+        let mut files = vec![
+            ("shp", shp),
+            ("shx", shx),
+            ("dbf", dbf.write(&records)),
+            ("cpg", b"UTF-8".to_vec()),
+        ];
+        if let Some(prj) = prj {
+            files.push(("prj", prj.as_bytes().to_vec()));
+        }
+        for (ext, content) in files {
+            zip.start_file(format!("{basename}.{ext}"), options)?;
+            zip.write_all(&content)?;
+        }
+    }
+    Ok(zip.finish()?.into_inner())
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ShapeType {
+    Null = 0,
+    Point = 1,
+    PolyLine = 3,
+    Polygon = 5,
+    MultiPoint = 8,
+}
+
+impl ShapeType {
+    /// File name suffix of shapefiles with mixed geometry types
+    fn suffix(&self) -> &str {
+        match self {
+            ShapeType::Null => "null",
+            ShapeType::Point => "points",
+            ShapeType::PolyLine => "lines",
+            ShapeType::Polygon => "polygons",
+            ShapeType::MultiPoint => "multipoints",
+        }
+    }
+}
+
+type Point = [f64; 2];
+
+#[derive(Debug, PartialEq)]
+enum Shape {
+    Null,
+    Point(Point),
+    MultiPoint(Vec<Point>),
+    PolyLine(Vec<Vec<Point>>),
+    /// Outer rings clockwise, holes counter-clockwise
+    Polygon(Vec<Vec<Point>>),
+}
+
+impl Shape {
+    fn from_geojson(geometry: &Value) -> Result<Self> {
+        if geometry.is_null() {
+            return Ok(Shape::Null);
+        }
+        let geometry_type = geometry["type"].as_str().unwrap_or_default();
+        let coordinates = &geometry["coordinates"];
+        let shape = match geometry_type {
+            "Point" => match coordinates.as_array() {
+                Some(position) if !position.is_empty() => Shape::Point(point(coordinates)?),
+                _ => Shape::Null,
+            },
+            "MultiPoint" => Shape::MultiPoint(points(coordinates)?),
+            "LineString" => Shape::PolyLine(vec![points(coordinates)?]),
+            "MultiLineString" => Shape::PolyLine(lines(coordinates)?),
+            "Polygon" => Shape::Polygon(oriented_rings(lines(coordinates)?)),
+            "MultiPolygon" => {
+                let mut rings = Vec::new();
+                for polygon in array(coordinates)? {
+                    rings.extend(oriented_rings(lines(polygon)?));
+                }
+                Shape::Polygon(rings)
+            }
+            _ => {
+                return Err(Error::OutputFormatError(format!(
+                    "Geometry type `{geometry_type}` not supported in shapefiles"
+                )))
+            }
+        };
+        Ok(shape)
+    }
+
+    fn shape_type(&self) -> ShapeType {
+        match self {
+            Shape::Null => ShapeType::Null,
+            Shape::Point(_) => ShapeType::Point,
+            Shape::MultiPoint(points) if points.is_empty() => ShapeType::Null,
+            Shape::MultiPoint(_) => ShapeType::MultiPoint,
+            Shape::PolyLine(parts) if parts.is_empty() => ShapeType::Null,
+            Shape::PolyLine(_) => ShapeType::PolyLine,
+            Shape::Polygon(parts) if parts.is_empty() => ShapeType::Null,
+            Shape::Polygon(_) => ShapeType::Polygon,
+        }
+    }
+
+    fn points(&self) -> Box<dyn Iterator<Item = &Point> + '_> {
+        match self {
+            Shape::Null => Box::new(std::iter::empty()),
+            Shape::Point(point) => Box::new(std::iter::once(point)),
+            Shape::MultiPoint(points) => Box::new(points.iter()),
+            Shape::PolyLine(parts) | Shape::Polygon(parts) => Box::new(parts.iter().flatten()),
+        }
+    }
+
+    fn bbox(&self) -> Option<[f64; 4]> {
+        self.points().fold(None, |bbox, [x, y]| {
+            Some(match bbox {
+                None => [*x, *y, *x, *y],
+                Some([xmin, ymin, xmax, ymax]) => {
+                    [xmin.min(*x), ymin.min(*y), xmax.max(*x), ymax.max(*y)]
+                }
+            })
+        })
+    }
+
+    /// Record content in little endian byte order
+    fn content(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend_from_slice(&(self.shape_type() as i32).to_le_bytes());
+        let bbox = self.bbox().unwrap_or_default();
+        match self {
+            Shape::Point([x, y]) => {
+                buf.extend_from_slice(&x.to_le_bytes());
+                buf.extend_from_slice(&y.to_le_bytes());
+            }
+            Shape::MultiPoint(points) if !points.is_empty() => {
+                bbox.iter()
+                    .for_each(|v| buf.extend_from_slice(&v.to_le_bytes()));
+                buf.extend_from_slice(&(points.len() as i32).to_le_bytes());
+                points
+                    .iter()
+                    .flatten()
+                    .for_each(|v| buf.extend_from_slice(&v.to_le_bytes()));
+            }
+            Shape::PolyLine(parts) | Shape::Polygon(parts) if !parts.is_empty() => {
+                bbox.iter()
+                    .for_each(|v| buf.extend_from_slice(&v.to_le_bytes()));
+                let num_points: usize = parts.iter().map(Vec::len).sum();
+                buf.extend_from_slice(&(parts.len() as i32).to_le_bytes());
+                buf.extend_from_slice(&(num_points as i32).to_le_bytes());
+                let mut start = 0;
+                for part in parts {
+                    buf.extend_from_slice(&(start as i32).to_le_bytes());
+                    start += part.len();
+                }
+                parts
+                    .iter()
+                    .flatten()
+                    .flatten()
+                    .for_each(|v| buf.extend_from_slice(&v.to_le_bytes()));
+            }
+            _ => {}
+        }
+        buf
+    }
+}
+
+fn array(value: &Value) -> Result<&Vec<Value>> {
+    value
+        .as_array()
+        .ok_or_else(|| Error::OutputFormatError("Invalid GeoJSON coordinates".to_string()))
+}
+
+fn point(value: &Value) -> Result<Point> {
+    let position = array(value)?;
+    match (
+        position.first().and_then(Value::as_f64),
+        position.get(1).and_then(Value::as_f64),
+    ) {
+        (Some(x), Some(y)) => Ok([x, y]),
+        _ => Err(Error::OutputFormatError(
+            "Invalid GeoJSON position".to_string(),
+        )),
+    }
+}
+
+fn points(value: &Value) -> Result<Vec<Point>> {
+    array(value)?.iter().map(point).collect()
+}
+
+fn lines(value: &Value) -> Result<Vec<Vec<Point>>> {
+    array(value)?.iter().map(points).collect()
+}
+
+/// Orient the outer ring of a polygon clockwise and holes counter-clockwise
+fn oriented_rings(mut rings: Vec<Vec<Point>>) -> Vec<Vec<Point>> {
+    for (i, ring) in rings.iter_mut().enumerate() {
+        let clockwise = signed_area(ring) < 0.0;
+        if clockwise != (i == 0) {
+            ring.reverse();
+        }
+    }
+    rings
+}
+
+/// Shoelace formula, positive for counter-clockwise rings
+fn signed_area(ring: &[Point]) -> f64 {
+    ring.windows(2)
+        .map(|w| w[0][0] * w[1][1] - w[1][0] * w[0][1])
+        .sum::<f64>()
+        / 2.0
+}
+
+/// Writer of .shp and .shx files
+struct ShapeWriter {
+    shape_type: ShapeType,
+    shp: Vec<u8>,
+    shx: Vec<u8>,
+    bbox: Option<[f64; 4]>,
+}
+
+/// Size of .shp and .shx headers in bytes
+const HEADER_LEN: usize = 100;
+
+impl ShapeWriter {
+    fn new(shape_type: ShapeType) -> Self {
+        ShapeWriter {
+            shape_type,
+            shp: vec![0; HEADER_LEN],
+            shx: vec![0; HEADER_LEN],
+            bbox: None,
+        }
+    }
+    fn add(&mut self, shape: &Shape) {
+        let record_number = (self.shx.len() - HEADER_LEN) / 8 + 1;
+        let content = shape.content();
+        // Offsets and lengths are counted in 16-bit words
+        let offset = (self.shp.len() / 2) as i32;
+        let len = (content.len() / 2) as i32;
+        self.shp
+            .extend_from_slice(&(record_number as i32).to_be_bytes());
+        self.shp.extend_from_slice(&len.to_be_bytes());
+        self.shp.extend_from_slice(&content);
+        self.shx.extend_from_slice(&offset.to_be_bytes());
+        self.shx.extend_from_slice(&len.to_be_bytes());
+        if let Some([xmin, ymin, xmax, ymax]) = shape.bbox() {
+            self.bbox = Some(match self.bbox {
+                None => [xmin, ymin, xmax, ymax],
+                Some(b) => [
+                    b[0].min(xmin),
+                    b[1].min(ymin),
+                    b[2].max(xmax),
+                    b[3].max(ymax),
+                ],
+            });
+        }
+    }
+    /// Contents of .shp and .shx file
+    fn finish(mut self) -> (Vec<u8>, Vec<u8>) {
+        let shp_header = self.header(self.shp.len());
+        self.shp[..HEADER_LEN].copy_from_slice(&shp_header);
+        let shx_header = self.header(self.shx.len());
+        self.shx[..HEADER_LEN].copy_from_slice(&shx_header);
+        (self.shp, self.shx)
+    }
+    fn header(&self, file_len: usize) -> Vec<u8> {
+        let mut header = Vec::with_capacity(HEADER_LEN);
+        header.extend_from_slice(&9994i32.to_be_bytes());
+        header.extend_from_slice(&[0; 20]);
+        header.extend_from_slice(&((file_len / 2) as i32).to_be_bytes());
+        header.extend_from_slice(&1000i32.to_le_bytes());
+        header.extend_from_slice(&(self.shape_type as i32).to_le_bytes());
+        // Bounding box followed by unused Z and M ranges
+        let bbox = self.bbox.unwrap_or_default();
+        bbox.iter()
+            .chain(&[0.0; 4])
+            .for_each(|v| header.extend_from_slice(&v.to_le_bytes()));
+        header
+    }
+}
+
+/// dBase field type
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum FieldType {
+    /// Only null values
+    Unknown,
+    Logical,
+    Integer,
+    Float,
+    Date,
+    Character,
+}
+
+impl FieldType {
+    fn of(value: &Value) -> Self {
+        match value {
+            Value::Null => FieldType::Unknown,
+            Value::Bool(_) => FieldType::Logical,
+            Value::Number(n) if n.is_f64() => FieldType::Float,
+            Value::Number(_) => FieldType::Integer,
+            Value::String(s) if is_date(s) => FieldType::Date,
+            _ => FieldType::Character,
+        }
+    }
+    fn merge(self, other: FieldType) -> Self {
+        match (self, other) {
+            (a, b) if a == b => a,
+            (FieldType::Unknown, t) | (t, FieldType::Unknown) => t,
+            (FieldType::Integer, FieldType::Float) | (FieldType::Float, FieldType::Integer) => {
+                FieldType::Float
+            }
+            _ => FieldType::Character,
+        }
+    }
+}
+
+/// ISO 8601 date without time
+fn is_date(s: &str) -> bool {
+    s.len() == 10
+        && s.char_indices().all(|(i, c)| match i {
+            4 | 7 => c == '-',
+            _ => c.is_ascii_digit(),
+        })
+}
+
+/// Attribute table column
+#[derive(Debug)]
+struct DbfField {
+    /// Feature property name
+    key: String,
+    /// Truncated dBase field name
+    name: String,
+    field_type: FieldType,
+    len: usize,
+    decimals: usize,
+}
+
+impl DbfField {
+    fn type_char(&self) -> u8 {
+        match self.field_type {
+            FieldType::Logical => b'L',
+            FieldType::Integer | FieldType::Float => b'N',
+            FieldType::Date => b'D',
+            FieldType::Unknown | FieldType::Character => b'C',
+        }
+    }
+    /// Field value padded to the field length
+    fn format(&self, value: &Value) -> Vec<u8> {
+        let text = match (self.field_type, value) {
+            (_, Value::Null) => String::new(),
+            (FieldType::Logical, Value::Bool(true)) => "T".to_string(),
+            (FieldType::Logical, Value::Bool(false)) => "F".to_string(),
+            (FieldType::Integer, _) => format!("{:>len$}", value.to_string(), len = self.len),
+            (FieldType::Float, Value::Number(n)) => format!(
+                "{:>len$.decimals$}",
+                n.as_f64().unwrap_or_default(),
+                len = self.len,
+                decimals = self.decimals
+            ),
+            (FieldType::Date, Value::String(s)) => s.replace('-', ""),
+            (_, value) => truncate(&text_value(value), self.len).to_string(),
+        };
+        let mut bytes = text.into_bytes();
+        bytes.resize(self.len, b' ');
+        bytes
+    }
+}
+
+fn text_value(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        _ => value.to_string(),
+    }
+}
+
+/// Truncate at a character boundary
+fn truncate(s: &str, max_len: usize) -> &str {
+    let mut end = s.len().min(max_len);
+    while !s.is_char_boundary(end) {
+        end -= 1;
+    }
+    &s[..end]
+}
+
+/// Number of digits before and after the decimal point
+fn float_digits(value: f64) -> (usize, usize) {
+    let text = value.to_string();
+    match text.split_once('.') {
+        Some((int, fraction)) => (int.len(), fraction.len()),
+        None => (text.len(), 0),
+    }
+}
+
+/// dBase attribute table with columns of all feature properties
+struct DbfTable {
+    fields: Vec<DbfField>,
+}
+
+impl DbfTable {
+    fn analyze(features: &[CoreFeature]) -> Self {
+        let mut fields: Vec<DbfField> = Vec::new();
+        let mut field_index: HashMap<String, usize> = HashMap::new();
+        let mut int_digits: Vec<usize> = Vec::new();
+        for feature in features {
+            let Some(Value::Object(properties)) = &feature.properties else {
+                continue;
+            };
+            for (key, value) in properties {
+                let i = *field_index.entry(key.clone()).or_insert_with(|| {
+                    fields.push(DbfField {
+                        key: key.clone(),
+                        name: field_name(key, &fields),
+                        field_type: FieldType::Unknown,
+                        len: 1,
+                        decimals: 0,
+                    });
+                    int_digits.push(1);
+                    fields.len() - 1
+                });
+                let field = &mut fields[i];
+                field.field_type = field.field_type.merge(FieldType::of(value));
+                match value {
+                    Value::Null => {}
+                    Value::Number(n) => {
+                        let (int, decimals) = float_digits(n.as_f64().unwrap_or_default());
+                        let int = if n.is_f64() { int } else { n.to_string().len() };
+                        int_digits[i] = int_digits[i].max(int);
+                        field.decimals = field.decimals.max(decimals.min(MAX_DECIMALS));
+                        field.len = field.len.max(text_value(value).len());
+                    }
+                    _ => field.len = field.len.max(text_value(value).len()),
+                }
+            }
+        }
+        for (field, int_digits) in fields.iter_mut().zip(int_digits) {
+            match field.field_type {
+                FieldType::Logical => field.len = 1,
+                FieldType::Date => field.len = 8,
+                FieldType::Integer => field.decimals = 0,
+                FieldType::Float => {
+                    field.len = int_digits + 1 + field.decimals.max(1);
+                    field.decimals = field.decimals.max(1);
+                }
+                FieldType::Unknown | FieldType::Character => {}
+            }
+            if matches!(field.field_type, FieldType::Integer | FieldType::Float)
+                && field.len > MAX_NUMERIC_LEN
+            {
+                field.field_type = FieldType::Character;
+            }
+            if !matches!(field.field_type, FieldType::Integer | FieldType::Float) {
+                field.decimals = 0;
+            }
+            field.len = field.len.min(MAX_CHARACTER_LEN);
+        }
+        DbfTable { fields }
+    }
+
+    /// dBase III file with attributes of `features`
+    fn write(&self, features: &[&CoreFeature]) -> Vec<u8> {
+        let record_len = 1 + self.fields.iter().map(|f| f.len).sum::<usize>();
+        let header_len = 32 + 32 * self.fields.len() + 1;
+        let mut buf = Vec::with_capacity(header_len + record_len * features.len() + 1);
+        let today = chrono::Utc::now().date_naive();
+        buf.push(0x03);
+        buf.extend_from_slice(&[
+            (today.year() - 1900) as u8,
+            today.month() as u8,
+            today.day() as u8,
+        ]);
+        buf.extend_from_slice(&(features.len() as u32).to_le_bytes());
+        buf.extend_from_slice(&(header_len as u16).to_le_bytes());
+        buf.extend_from_slice(&(record_len as u16).to_le_bytes());
+        buf.extend_from_slice(&[0; 20]);
+        for field in &self.fields {
+            let mut name = field.name.as_bytes().to_vec();
+            name.resize(11, 0);
+            buf.extend_from_slice(&name);
+            buf.push(field.type_char());
+            buf.extend_from_slice(&[0; 4]);
+            buf.push(field.len as u8);
+            buf.push(field.decimals as u8);
+            buf.extend_from_slice(&[0; 14]);
+        }
+        buf.push(0x0d);
+        for feature in features {
+            // Deletion flag
+            buf.push(b' ');
+            for field in &self.fields {
+                let value = feature
+                    .properties
+                    .as_ref()
+                    .and_then(|props| props.get(&field.key))
+                    .unwrap_or(&Value::Null);
+                buf.extend_from_slice(&field.format(value));
+            }
+        }
+        buf.push(0x1a);
+        buf
+    }
+}
+
+/// Unique dBase field name with at most 10 ASCII characters
+fn field_name(key: &str, fields: &[DbfField]) -> String {
+    let mut base: String = key
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .take(MAX_FIELD_NAME_LEN)
+        .collect();
+    if base.is_empty() {
+        base = "field".to_string();
+    }
+    let mut name = base.clone();
+    let mut i = 1;
+    while fields.iter().any(|f| f.name.eq_ignore_ascii_case(&name)) {
+        let suffix = format!("_{i}");
+        let len = base.len().min(MAX_FIELD_NAME_LEN - suffix.len());
+        name = format!("{}{suffix}", &base[..len]);
+        i += 1;
+    }
+    name
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn feature(geometry: Value, properties: Value) -> CoreFeature {
+        CoreFeature {
+            type_: "Feature".to_string(),
+            bbox: None,
+            id: None,
+            geometry,
+            properties: Some(properties),
+            links: vec![],
+        }
+    }
+
+    #[test]
+    fn shape_records() {
+        let polygon = json!({"type": "Polygon", "coordinates": [
+            [[0, 0], [10, 0], [10, 10], [0, 0]],
+            [[2, 1], [8, 7], [8, 1], [2, 1]]
+        ]});
+        let Shape::Polygon(rings) = Shape::from_geojson(&polygon).unwrap() else {
+            panic!("polygon expected");
+        };
+        assert!(signed_area(&rings[0]) < 0.0);
+        assert!(signed_area(&rings[1]) > 0.0);
+
+        let mut writer = ShapeWriter::new(ShapeType::Point);
+        writer.add(&Shape::Point([7.5, 47.2]));
+        writer.add(&Shape::Null);
+        let (shp, shx) = writer.finish();
+        // Header, point record with 20 bytes content, null record
+        assert_eq!(shp.len(), 100 + 8 + 20 + 8 + 4);
+        assert_eq!(&shp[24..28], &(shp.len() as i32 / 2).to_be_bytes());
+        assert_eq!(&shp[32..36], &1i32.to_le_bytes());
+        assert_eq!(&shp[36..44], &7.5f64.to_le_bytes());
+        assert_eq!(shx.len(), 100 + 2 * 8);
+        assert_eq!(&shx[108..112], &((100 + 8 + 20) / 2i32).to_be_bytes());
+
+        let collection = json!({"type": "GeometryCollection", "geometries": []});
+        assert!(Shape::from_geojson(&collection).is_err());
+    }
+
+    #[test]
+    fn attribute_table() {
+        let features = [
+            feature(
+                Value::Null,
+                json!({"population_total": 1000, "population_urban": 2.5, "name": "Bern", "capital": true, "founded": "1191-01-01"}),
+            ),
+            feature(
+                Value::Null,
+                json!({"population_total": -20, "population_urban": null, "name": "Zürich", "capital": false, "founded": null}),
+            ),
+        ];
+        let table = DbfTable::analyze(&features);
+        let field = |key: &str| table.fields.iter().find(|f| f.key == key).unwrap();
+        assert_eq!(field("population_total").name, "population");
+        assert_eq!(field("population_urban").name, "populati_1");
+        assert_eq!(field("population_total").type_char(), b'N');
+        assert_eq!(
+            (
+                field("population_total").len,
+                field("population_total").decimals
+            ),
+            (4, 0)
+        );
+        assert_eq!(
+            (
+                field("population_urban").len,
+                field("population_urban").decimals
+            ),
+            (3, 1)
+        );
+        assert_eq!(field("name").len, "Zürich".len());
+        assert_eq!(field("capital").type_char(), b'L');
+        assert_eq!(field("founded").type_char(), b'D');
+        assert_eq!(
+            field("population_urban").format(&json!(2.5)),
+            b"2.5".to_vec()
+        );
+        assert_eq!(
+            field("population_total").format(&json!(-20)),
+            b" -20".to_vec()
+        );
+        assert_eq!(
+            field("founded").format(&json!("1191-01-01")),
+            b"11910101".to_vec()
+        );
+
+        let records: Vec<_> = features.iter().collect();
+        let dbf = table.write(&records);
+        let header_len = 32 + 32 * 5 + 1;
+        assert_eq!(&dbf[4..8], &2u32.to_le_bytes());
+        assert_eq!(dbf.len(), header_len + 2 * (1 + 4 + 3 + 7 + 1 + 8) + 1);
+    }
+
+    #[test]
+    fn zip_archive() {
+        let features = [
+            feature(
+                json!({"type": "Point", "coordinates": [7.5, 47.2]}),
+                json!({"name": "Bern"}),
+            ),
+            feature(
+                json!({"type": "LineString", "coordinates": [[7.5, 47.2], [8.5, 47.4]]}),
+                json!({"name": "Bern-Zürich"}),
+            ),
+            feature(Value::Null, json!({"name": null})),
+        ];
+        let buf = shapefile_zip("places", &features, None).unwrap();
+        let archive = zip::ZipArchive::new(Cursor::new(buf)).unwrap();
+        let mut names: Vec<_> = archive.file_names().collect();
+        names.sort();
+        assert_eq!(
+            names,
+            vec![
+                "places_lines.cpg",
+                "places_lines.dbf",
+                "places_lines.shp",
+                "places_lines.shx",
+                "places_points.cpg",
+                "places_points.dbf",
+                "places_points.shp",
+                "places_points.shx"
+            ]
+        );
+        let buf = shapefile_zip("places", &features[..1], Some("GEOGCS[]")).unwrap();
+        let mut archive = zip::ZipArchive::new(Cursor::new(buf)).unwrap();
+        assert_eq!(archive.len(), 5);
+        assert!(archive.by_name("places.prj").is_ok());
+    }
+}
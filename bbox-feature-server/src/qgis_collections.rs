@@ -0,0 +1,130 @@
+//! Feature collections derived from vector layers of QGIS projects.
+
+use crate::config::*;
+use bbox_core::config::{DatasourceCfg, DsGpkgCfg, DsPostgisCfg, NamedDatasourceCfg};
+use bbox_map_server::qgis_project::{LayerSource, ProjectLayer};
+use log::warn;
+use std::collections::HashMap;
+
+/// Collections and datasources of QGIS project layers.
+/// Collection ids are composed of the WMS service id and the layer name.
+pub fn project_collections(projects: &[(String, Vec<ProjectLayer>)]) -> DerivedCollectionsCfg {
+    let mut derived = DerivedCollectionsCfg::default();
+    // Datasource names by connection URL or file path
+    let mut ds_names: HashMap<String, String> = HashMap::new();
+    let mut datasource_name = |key: String, ds: DatasourceCfg| {
+        ds_names
+            .entry(key)
+            .or_insert_with(|| {
+                let name = format!("qgis_{}", derived.datasources.len());
+                derived.datasources.push(NamedDatasourceCfg {
+                    name: name.clone(),
+                    datasource: ds,
+                });
+                name
+            })
+            .clone()
+    };
+    let mut collections = Vec::new();
+    for (project, layers) in projects {
+        for layer in layers {
+            let source = match &layer.source {
+                LayerSource::Postgis {
+                    url,
+                    table_schema,
+                    table_name,
+                    geometry_field,
+                    fid_field,
+                } => {
+                    let ds = DatasourceCfg::Postgis(DsPostgisCfg {
+                        url: url.clone(),
+                        lazy: true,
+                        ..Default::default()
+                    });
+                    CollectionSourceCfg::Postgis(PostgisCollectionCfg {
+                        datasource: Some(datasource_name(url.clone(), ds)),
+                        table_schema: table_schema.clone(),
+                        table_name: Some(table_name.clone()),
+                        fid_field: fid_field.clone(),
+                        geometry_field: geometry_field.clone(),
+                        ..Default::default()
+                    })
+                }
+                LayerSource::Gpkg { path, table_name } => {
+                    if !path.is_file() {
+                        warn!("GeoPackage '{}' not found", path.display());
+                        continue;
+                    }
+                    let ds = DatasourceCfg::Gpkg(DsGpkgCfg { path: path.clone() });
+                    let key = path.to_string_lossy().to_string();
+                    CollectionSourceCfg::Gpkg(GpkgCollectionCfg {
+                        datasource: Some(datasource_name(key, ds)),
+                        table_name: Some(table_name.clone()),
+                        ..Default::default()
+                    })
+                }
+                LayerSource::Flatgeobuf { path } => {
+                    CollectionSourceCfg::Flatgeobuf(FlatgeobufCollectionCfg {
+                        path: path.to_string_lossy().to_string(),
+                        ..Default::default()
+                    })
+                }
+            };
+            collections.push(ConfiguredCollectionCfg {
+                name: collection_id(project, &layer.name),
+                title: Some(layer.name.clone()),
+                description: None,
+                attribution: None,
+                license: None,
+                downloads: Vec::new(),
+                source,
+            });
+        }
+    }
+    derived.collections = collections;
+    derived
+}
+
+fn collection_id(project: &str, layer: &str) -> String {
+    let layer: String = layer
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    format!("{project}_{layer}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shared_datasources() {
+        let layer = |name: &str, table: &str| ProjectLayer {
+            name: name.to_string(),
+            source: LayerSource::Postgis {
+                url: "postgresql://db/ne".to_string(),
+                table_schema: None,
+                table_name: table.to_string(),
+                geometry_field: None,
+                fid_field: None,
+            },
+        };
+        let projects = vec![(
+            "ne".to_string(),
+            vec![
+                layer("populated places", "places"),
+                layer("rivers", "rivers"),
+            ],
+        )];
+        let derived = project_collections(&projects);
+        assert_eq!(derived.datasources.len(), 1);
+        assert_eq!(derived.collections.len(), 2);
+        assert_eq!(derived.collections[0].name, "ne_populated_places");
+    }
+}
@@ -0,0 +1,147 @@
+//! Synchronization of GeoPackages in S3 buckets into a local cache directory.
+
+use crate::config::DsS3Cfg;
+use crate::error::{Error, Result};
+use bbox_core::file_search;
+use futures::{future, TryStreamExt};
+use log::{info, warn};
+use object_store::{aws::AmazonS3Builder, path::Path as ObjectPath, ObjectMeta, ObjectStore};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::SystemTime;
+
+/// Object store and prefix of S3 URL.
+/// S3 credentials and region are read from the `AWS_*` environment variables.
+fn object_store(cfg: &DsS3Cfg) -> Result<(Arc<dyn ObjectStore>, Option<ObjectPath>)> {
+    let url = url::Url::parse(&cfg.url)
+        .map_err(|e| Error::DatasourceSetupError(format!("Invalid URL `{}`: {e}", cfg.url)))?;
+    if url.scheme() != "s3" {
+        return Err(Error::DatasourceSetupError(format!(
+            "Invalid S3 URL `{}`",
+            cfg.url
+        )));
+    }
+    let store = AmazonS3Builder::from_env().with_url(&cfg.url).build()?;
+    let prefix = url.path().trim_matches('/');
+    let prefix = if prefix.is_empty() {
+        None
+    } else {
+        Some(ObjectPath::parse(prefix).map_err(|e| Error::DatasourceSetupError(e.to_string()))?)
+    };
+    Ok((Arc::new(store), prefix))
+}
+
+/// GeoPackages in bucket
+pub async fn list_geopackages(cfg: &DsS3Cfg) -> Result<Vec<ObjectMeta>> {
+    let (store, prefix) = object_store(cfg)?;
+    list(store.as_ref(), prefix.as_ref()).await
+}
+
+/// Download new or changed GeoPackages and remove local copies of deleted objects.
+/// Returns the local paths of all GeoPackages.
+pub async fn sync_geopackages(cfg: &DsS3Cfg) -> Result<Vec<PathBuf>> {
+    info!("Synchronizing GeoPackages from '{}'", cfg.url);
+    let (store, prefix) = object_store(cfg)?;
+    let objects = list(store.as_ref(), prefix.as_ref()).await?;
+    sync(store.as_ref(), &objects, Path::new(&cfg.cache_dir)).await
+}
+
+async fn list(store: &dyn ObjectStore, prefix: Option<&ObjectPath>) -> Result<Vec<ObjectMeta>> {
+    let mut objects: Vec<ObjectMeta> = store
+        .list(prefix)
+        .await?
+        .try_filter(|meta| future::ready(meta.location.as_ref().ends_with(".gpkg")))
+        .try_collect()
+        .await?;
+    objects.sort_by(|a, b| a.location.cmp(&b.location));
+    Ok(objects)
+}
+
+async fn sync(
+    store: &dyn ObjectStore,
+    objects: &[ObjectMeta],
+    cache_dir: &Path,
+) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for meta in objects {
+        let path = cache_dir.join(meta.location.as_ref());
+        if !is_current(&path, meta) {
+            info!("Downloading '{}'", meta.location);
+            if let Err(e) = download(store, meta, &path).await {
+                warn!("Failed to download '{}': {e}", meta.location);
+                if !path.exists() {
+                    continue;
+                }
+            }
+        }
+        files.push(path);
+    }
+    for path in file_search::search(cache_dir, "*.gpkg") {
+        if !files.contains(&path) {
+            info!("Removing '{}'", path.display());
+            std::fs::remove_file(&path)?;
+        }
+    }
+    Ok(files)
+}
+
+/// Local copy has the same size and is newer than the object
+fn is_current(path: &Path, meta: &ObjectMeta) -> bool {
+    let Ok(file) = std::fs::metadata(path) else {
+        return false;
+    };
+    file.len() == meta.size as u64
+        && file.modified().map_or(false, |modified| {
+            modified >= SystemTime::from(meta.last_modified)
+        })
+}
+
+async fn download(store: &dyn ObjectStore, meta: &ObjectMeta, path: &Path) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    // The previous version is served until the download is complete
+    let part = path.with_extension("gpkg.part");
+    let mut file = std::fs::File::create(&part)?;
+    let mut stream = store.get(&meta.location).await?.into_stream();
+    while let Some(chunk) = stream.try_next().await? {
+        file.write_all(&chunk)?;
+    }
+    file.sync_all()?;
+    std::fs::rename(&part, path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use object_store::memory::InMemory;
+
+    #[tokio::test]
+    async fn sync_bucket() {
+        let store = InMemory::new();
+        for key in ["data/countries.gpkg", "data/README.md", "other/rivers.gpkg"] {
+            store
+                .put(&ObjectPath::from(key), b"gpkg".to_vec().into())
+                .await
+                .unwrap();
+        }
+        let prefix = ObjectPath::from("data");
+        let objects = list(&store, Some(&prefix)).await.unwrap();
+        assert_eq!(objects.len(), 1);
+
+        let cache_dir = tempfile::tempdir().unwrap();
+        let files = sync(&store, &objects, cache_dir.path()).await.unwrap();
+        let path = cache_dir.path().join("data/countries.gpkg");
+        assert_eq!(files, vec![path.clone()]);
+        assert_eq!(std::fs::read(&path).unwrap(), b"gpkg");
+        assert!(is_current(&path, &objects[0]));
+
+        store.delete(&objects[0].location).await.unwrap();
+        let objects = list(&store, Some(&prefix)).await.unwrap();
+        let files = sync(&store, &objects, cache_dir.path()).await.unwrap();
+        assert!(files.is_empty());
+        assert!(!path.exists());
+    }
+}
@@ -0,0 +1,560 @@
+//! STAC item search.
+
+use crate::error::{Error, Result};
+use crate::filter_params::FilterParams;
+use bbox_core::ogcapi::{ApiLink, CoreFeature};
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// Maximal number of items of a search page
+const MAX_LIMIT: u32 = 10_000;
+
+/// STAC ItemSearch parameters (<https://github.com/radiantearth/stac-api-spec/tree/main/item-search>)
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct ItemSearch {
+    pub bbox: Option<Vec<f64>>,
+    pub datetime: Option<String>,
+    /// GeoJSON geometry
+    pub intersects: Option<Value>,
+    pub ids: Option<Vec<String>>,
+    pub collections: Option<Vec<String>>,
+    pub limit: Option<u32>,
+    /// Query extension: `{"property": {"op": value}}`
+    pub query: Option<HashMap<String, HashMap<String, Value>>>,
+    /// CQL2 text filter expression
+    pub filter: Option<String>,
+    #[serde(rename = "filter-lang")]
+    pub filter_lang: Option<String>,
+    /// Sort extension
+    pub sortby: Option<Vec<SortBy>>,
+    /// Fields extension
+    pub fields: Option<Fields>,
+    /// Free-text search terms
+    pub q: Option<String>,
+    /// Position of the page, taken from `next` and `prev` links
+    pub token: Option<String>,
+}
+
+/// Sort property (<https://github.com/stac-api-extensions/sort>)
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct SortBy {
+    pub field: String,
+    #[serde(default)]
+    pub direction: SortDirection,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum SortDirection {
+    #[default]
+    Asc,
+    Desc,
+}
+
+/// Included and excluded fields (<https://github.com/stac-api-extensions/fields>)
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct Fields {
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
+}
+
+impl ItemSearch {
+    /// Parse GET request parameters.
+    pub fn from_query(query_string: &str) -> Result<Self> {
+        let params = serde_urlencoded::from_str::<Vec<(String, String)>>(query_string)
+            .map_err(|_| Error::QueryParams)?;
+        let mut search = ItemSearch::default();
+        let list = |v: &str| v.split(',').map(str::to_string).collect::<Vec<_>>();
+        for (key, val) in params {
+            match key.to_lowercase().as_str() {
+                "bbox" => {
+                    search.bbox = Some(
+                        val.split(',')
+                            .map(|v| v.parse())
+                            .collect::<std::result::Result<Vec<f64>, _>>()
+                            .map_err(|_| Error::QueryParams)?,
+                    )
+                }
+                "datetime" => search.datetime = Some(val),
+                "intersects" => {
+                    search.intersects =
+                        Some(serde_json::from_str(&val).map_err(|_| Error::QueryParams)?)
+                }
+                "ids" => search.ids = Some(list(&val)),
+                "collections" => search.collections = Some(list(&val)),
+                "limit" => search.limit = Some(val.parse().map_err(|_| Error::QueryParams)?),
+                "query" => {
+                    search.query = Some(serde_json::from_str(&val).map_err(|_| Error::QueryParams)?)
+                }
+                "filter" => search.filter = Some(val),
+                "filter-lang" => search.filter_lang = Some(val),
+                "sortby" => {
+                    search.sortby = Some(
+                        val.split(',')
+                            .map(str::trim)
+                            .filter(|field| !field.is_empty())
+                            .map(|field| match field.strip_prefix('-') {
+                                Some(field) => SortBy {
+                                    field: field.to_string(),
+                                    direction: SortDirection::Desc,
+                                },
+                                None => SortBy {
+                                    field: field.trim_start_matches('+').to_string(),
+                                    direction: SortDirection::Asc,
+                                },
+                            })
+                            .collect(),
+                    )
+                }
+                "fields" => {
+                    let mut fields = Fields::default();
+                    for field in val.split(',').map(str::trim) {
+                        if let Some(field) = field.strip_prefix('-') {
+                            fields.exclude.push(field.to_string());
+                        } else if !field.is_empty() {
+                            fields
+                                .include
+                                .push(field.trim_start_matches('+').to_string());
+                        }
+                    }
+                    search.fields = Some(fields);
+                }
+                "q" => search.q = Some(val),
+                "token" => search.token = Some(val),
+                _ => return Err(Error::QueryParams),
+            }
+        }
+        Ok(search)
+    }
+
+    /// Searched collections. Empty if all collections should be searched.
+    pub fn collections(&self) -> &[String] {
+        self.collections.as_deref().unwrap_or_default()
+    }
+
+    /// Filter parameters applied to each collection.
+    pub fn filter_params(&self) -> Result<FilterParams> {
+        if let Some(bbox) = &self.bbox {
+            if bbox.len() != 4 && bbox.len() != 6 {
+                return Err(Error::QueryParams);
+            }
+        }
+        if let Some(lang) = &self.filter_lang {
+            if lang != "cql2-text" {
+                return Err(Error::QueryParams);
+            }
+        }
+        let query = self.query.as_ref().map(query_to_cql2).transpose()?;
+        let filter = match (&self.filter, query) {
+            (Some(filter), Some(query)) => Some(format!("({filter}) AND {query}")),
+            (Some(filter), None) => Some(filter.clone()),
+            (None, query) => query,
+        };
+        let sortby = self.sortby.as_ref().map(|sortby| {
+            sortby
+                .iter()
+                .map(|key| {
+                    let field = property_name(&key.field);
+                    match key.direction {
+                        SortDirection::Asc => field.to_string(),
+                        SortDirection::Desc => format!("-{field}"),
+                    }
+                })
+                .collect::<Vec<_>>()
+                .join(",")
+        });
+        let fields = self.fields.clone().unwrap_or_default();
+        // Restrict returned properties, unless all properties are included
+        let properties = if fields.include.iter().any(|field| field == "properties") {
+            None
+        } else {
+            let names: Vec<_> = fields
+                .include
+                .iter()
+                .filter_map(|field| field.strip_prefix("properties."))
+                .collect();
+            (!names.is_empty()).then(|| names.join(","))
+        };
+        let skip_geometry = fields
+            .exclude
+            .iter()
+            .any(|field| field == "geometry")
+            .then_some(true);
+        Ok(FilterParams {
+            limit: self.limit(),
+            offset: self.offset()?,
+            cursor: None,
+            bbox: self.bbox.as_ref().map(|bbox| {
+                bbox.iter()
+                    .map(f64::to_string)
+                    .collect::<Vec<_>>()
+                    .join(",")
+            }),
+            datetime: self.datetime.clone(),
+            filter,
+            ids: self.ids.as_ref().map(|ids| ids.join(",")),
+            intersects: self.intersects.as_ref().map(Value::to_string),
+            crs: None,
+            bbox_crs: None,
+            properties,
+            skip_geometry,
+            sortby,
+            q: self.q.clone(),
+            zoom: None,
+            scale: None,
+            filters: HashMap::new(),
+        })
+    }
+
+    /// Page size, limited to [MAX_LIMIT]
+    fn limit(&self) -> Option<u32> {
+        self.limit.map(|limit| limit.clamp(1, MAX_LIMIT))
+    }
+
+    /// Item offset of the requested page
+    fn offset(&self) -> Result<Option<u32>> {
+        let Some(token) = &self.token else {
+            return Ok(None);
+        };
+        let offset = token
+            .strip_prefix("next:")
+            .or_else(|| token.strip_prefix("prev:"))
+            .and_then(|offset| offset.parse().ok())
+            .ok_or(Error::QueryParams)?;
+        Ok(Some(offset))
+    }
+
+    /// Remove excluded properties of the fields extension.
+    /// Included properties and excluded geometries are handled by the filter parameters.
+    pub fn apply_fields(&self, feature: &mut CoreFeature) {
+        let Some(fields) = &self.fields else {
+            return;
+        };
+        for field in &fields.exclude {
+            if field == "properties" {
+                feature.properties = None;
+            } else if let Some(name) = field.strip_prefix("properties.") {
+                if let Some(Value::Object(properties)) = &mut feature.properties {
+                    properties.remove(name);
+                }
+            }
+        }
+    }
+
+    /// `next` and `prev` links with a paging token
+    pub fn paging_links(&self, number_matched: Option<u64>, number_returned: u64) -> Vec<ApiLink> {
+        let mut links = Vec::new();
+        let offset = self.offset().ok().flatten().unwrap_or(0);
+        let limit = FilterParams {
+            limit: self.limit(),
+            ..Default::default()
+        }
+        .limit_or_default();
+        let next = offset as u64 + number_returned;
+        let has_next = match number_matched {
+            Some(matched) => next < matched,
+            None => limit > 0 && number_returned >= limit as u64,
+        };
+        if has_next && number_returned > 0 {
+            links.push(self.page_link("next", format!("next:{next}")));
+        }
+        if offset > 0 {
+            let prev = offset.saturating_sub(limit);
+            links.push(self.page_link("prev", format!("prev:{prev}")));
+        }
+        links
+    }
+
+    fn page_link(&self, rel: &str, token: String) -> ApiLink {
+        let mut search = self.clone();
+        search.token = Some(token);
+        ApiLink {
+            href: format!("/search?{}", search.as_query()),
+            rel: Some(rel.to_string()),
+            type_: Some("application/geo+json".to_string()),
+            title: None,
+            hreflang: None,
+            length: None,
+        }
+    }
+
+    /// Parameters as GET query string
+    pub fn as_query(&self) -> String {
+        let join = |values: &[String]| values.join(",");
+        let mut params: Vec<(&str, String)> = Vec::new();
+        if let Some(bbox) = &self.bbox {
+            let bbox: Vec<_> = bbox.iter().map(f64::to_string).collect();
+            params.push(("bbox", join(&bbox)));
+        }
+        if let Some(datetime) = &self.datetime {
+            params.push(("datetime", datetime.clone()));
+        }
+        if let Some(intersects) = &self.intersects {
+            params.push(("intersects", intersects.to_string()));
+        }
+        if let Some(ids) = &self.ids {
+            params.push(("ids", join(ids)));
+        }
+        if let Some(collections) = &self.collections {
+            params.push(("collections", join(collections)));
+        }
+        if let Some(limit) = self.limit {
+            params.push(("limit", limit.to_string()));
+        }
+        if let Some(query) = &self.query {
+            params.push(("query", serde_json::to_string(query).unwrap_or_default()));
+        }
+        if let Some(filter) = &self.filter {
+            params.push(("filter", filter.clone()));
+        }
+        if let Some(lang) = &self.filter_lang {
+            params.push(("filter-lang", lang.clone()));
+        }
+        if let Some(sortby) = &self.sortby {
+            let keys: Vec<_> = sortby
+                .iter()
+                .map(|key| match key.direction {
+                    SortDirection::Asc => format!("+{}", key.field),
+                    SortDirection::Desc => format!("-{}", key.field),
+                })
+                .collect();
+            params.push(("sortby", join(&keys)));
+        }
+        if let Some(fields) = &self.fields {
+            let fields: Vec<_> = fields
+                .include
+                .iter()
+                .cloned()
+                .chain(fields.exclude.iter().map(|field| format!("-{field}")))
+                .collect();
+            params.push(("fields", join(&fields)));
+        }
+        if let Some(q) = &self.q {
+            params.push(("q", q.clone()));
+        }
+        if let Some(token) = &self.token {
+            params.push(("token", token.clone()));
+        }
+        serde_urlencoded::to_string(params).unwrap_or_default()
+    }
+}
+
+/// Property name of a STAC field like `properties.name`
+fn property_name(field: &str) -> &str {
+    field.strip_prefix("properties.").unwrap_or(field)
+}
+
+/// Convert query extension expression into CQL2 text.
+fn query_to_cql2(query: &HashMap<String, HashMap<String, Value>>) -> Result<String> {
+    let mut terms = Vec::new();
+    // Sort for reproducible expressions
+    let mut props: Vec<_> = query.iter().collect();
+    props.sort_by_key(|(prop, _)| *prop);
+    for (prop, ops) in props {
+        if prop.contains('"') {
+            return Err(Error::QueryParams);
+        }
+        let mut ops: Vec<_> = ops.iter().collect();
+        ops.sort_by_key(|(op, _)| *op);
+        for (op, val) in ops {
+            let term = match op.as_str() {
+                "eq" => format!(r#""{prop}" = {}"#, cql2_literal(val)?),
+                "neq" => format!(r#""{prop}" <> {}"#, cql2_literal(val)?),
+                "lt" => format!(r#""{prop}" < {}"#, cql2_literal(val)?),
+                "lte" => format!(r#""{prop}" <= {}"#, cql2_literal(val)?),
+                "gt" => format!(r#""{prop}" > {}"#, cql2_literal(val)?),
+                "gte" => format!(r#""{prop}" >= {}"#, cql2_literal(val)?),
+                "startsWith" => format!(r#""{prop}" LIKE {}"#, like_pattern(val, "", "%")?),
+                "endsWith" => format!(r#""{prop}" LIKE {}"#, like_pattern(val, "%", "")?),
+                "contains" => format!(r#""{prop}" LIKE {}"#, like_pattern(val, "%", "%")?),
+                "in" => {
+                    let Value::Array(values) = val else {
+                        return Err(Error::QueryParams);
+                    };
+                    let list = values
+                        .iter()
+                        .map(cql2_literal)
+                        .collect::<Result<Vec<_>>>()?;
+                    format!(r#""{prop}" IN ({})"#, list.join(", "))
+                }
+                _ => return Err(Error::QueryParams),
+            };
+            terms.push(term);
+        }
+    }
+    Ok(terms.join(" AND "))
+}
+
+fn cql2_literal(val: &Value) -> Result<String> {
+    match val {
+        Value::String(s) => Ok(format!("'{}'", s.replace('\'', "''"))),
+        Value::Number(n) => Ok(n.to_string()),
+        Value::Bool(b) => Ok(b.to_string()),
+        _ => Err(Error::QueryParams),
+    }
+}
+
+fn like_pattern(val: &Value, prefix: &str, suffix: &str) -> Result<String> {
+    let Value::String(s) = val else {
+        return Err(Error::QueryParams);
+    };
+    Ok(format!("'{prefix}{}{suffix}'", s.replace('\'', "''")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn post_body() {
+        let search: ItemSearch = serde_json::from_value(json!({
+            "collections": ["ne_10m_rivers_lake_centerlines"],
+            "bbox": [5.6, 45.8, 10.9, 47.6],
+            "limit": 10,
+            "query": {"name": {"eq": "Rhein"}, "scalerank": {"gte": 4, "lt": 8}}
+        }))
+        .unwrap();
+        assert_eq!(search.collections(), ["ne_10m_rivers_lake_centerlines"]);
+        let fp = search.filter_params().unwrap();
+        assert_eq!(fp.limit, Some(10));
+        assert_eq!(fp.bbox, Some("5.6,45.8,10.9,47.6".to_string()));
+        assert_eq!(
+            fp.filter,
+            Some(r#""name" = 'Rhein' AND "scalerank" >= 4 AND "scalerank" < 8"#.to_string())
+        );
+        assert!(fp.cql2_filter().unwrap().is_some());
+
+        let search: ItemSearch = serde_json::from_value(json!({
+            "ids": ["1", "2"],
+            "intersects": {"type": "Point", "coordinates": [7.5, 47.2]}
+        }))
+        .unwrap();
+        assert!(search.collections().is_empty());
+        let fp = search.filter_params().unwrap();
+        assert_eq!(fp.ids, Some("1,2".to_string()));
+        let geom: Value = serde_json::from_str(&fp.intersects.unwrap()).unwrap();
+        assert_eq!(geom["type"], "Point");
+        assert_eq!(fp.filter, None);
+
+        let search: ItemSearch = serde_json::from_value(json!({
+            "filter": "name = 'Rhein' OR name = 'Rhône'",
+            "filter-lang": "cql2-text",
+            "query": {"scalerank": {"eq": 4}}
+        }))
+        .unwrap();
+        assert_eq!(
+            search.filter_params().unwrap().filter,
+            Some(r#"(name = 'Rhein' OR name = 'Rhône') AND "scalerank" = 4"#.to_string())
+        );
+
+        let search: ItemSearch =
+            serde_json::from_value(json!({"filter": "name = 'Rhein'", "filter-lang": "cql2-json"}))
+                .unwrap();
+        assert!(search.filter_params().is_err());
+    }
+
+    #[test]
+    fn get_params() {
+        let search = ItemSearch::from_query(
+            "collections=a,b&bbox=1,2,3,4&datetime=2024-01-01T00:00:00Z&limit=5&ids=x&q=Bern",
+        )
+        .unwrap();
+        assert_eq!(search.collections(), ["a", "b"]);
+        assert_eq!(search.bbox, Some(vec![1.0, 2.0, 3.0, 4.0]));
+        assert_eq!(search.datetime, Some("2024-01-01T00:00:00Z".to_string()));
+        assert_eq!(search.limit, Some(5));
+        assert_eq!(search.ids, Some(vec!["x".to_string()]));
+        assert_eq!(search.filter_params().unwrap().q, Some("Bern".to_string()));
+
+        assert!(ItemSearch::from_query("bbox=1,2,x,4").is_err());
+        assert!(ItemSearch::from_query("unknown=1").is_err());
+    }
+
+    #[test]
+    fn sort_and_fields() {
+        let search: ItemSearch = serde_json::from_value(json!({
+            "sortby": [
+                {"field": "properties.scalerank", "direction": "desc"},
+                {"field": "name"}
+            ],
+            "fields": {"include": ["properties.name"], "exclude": ["geometry"]}
+        }))
+        .unwrap();
+        let fp = search.filter_params().unwrap();
+        assert_eq!(fp.sortby, Some("-scalerank,name".to_string()));
+        assert_eq!(fp.properties, Some("name".to_string()));
+        assert!(fp.skip_geometry());
+
+        let get = ItemSearch::from_query(
+            "sortby=%2Bname,-scalerank&fields=properties,-properties.scalerank",
+        )
+        .unwrap();
+        let fp = get.filter_params().unwrap();
+        assert_eq!(fp.sortby, Some("name,-scalerank".to_string()));
+        assert_eq!(fp.properties, None);
+        let mut feature = CoreFeature {
+            type_: "Feature".to_string(),
+            bbox: None,
+            geometry: Value::Null,
+            properties: Some(json!({"name": "Rhein", "scalerank": 4})),
+            id: None,
+            links: Vec::new(),
+        };
+        get.apply_fields(&mut feature);
+        assert_eq!(feature.properties, Some(json!({"name": "Rhein"})));
+        assert_eq!(ItemSearch::from_query(&get.as_query()).unwrap(), get);
+    }
+
+    #[test]
+    fn paging_token() {
+        let search = ItemSearch::from_query("limit=10&collections=a,b").unwrap();
+        let links = search.paging_links(Some(25), 10);
+        assert_eq!(links.len(), 1);
+        assert_eq!(links[0].rel, Some("next".to_string()));
+        let next = ItemSearch::from_query(links[0].href.strip_prefix("/search?").unwrap()).unwrap();
+        assert_eq!(next.filter_params().unwrap().offset, Some(10));
+        assert_eq!(next.collections(), ["a", "b"]);
+
+        let links = next.paging_links(Some(25), 10);
+        assert_eq!(
+            links
+                .iter()
+                .map(|l| l.rel.as_deref().unwrap())
+                .collect::<Vec<_>>(),
+            ["next", "prev"]
+        );
+        // Last page with unknown number of matches
+        let last = ItemSearch::from_query("limit=10&token=next:20").unwrap();
+        let links = last.paging_links(None, 5);
+        assert_eq!(links.len(), 1);
+        assert_eq!(links[0].href, "/search?limit=10&token=prev%3A10");
+        assert!(ItemSearch::from_query("token=20")
+            .unwrap()
+            .filter_params()
+            .is_err());
+
+        for (limit, page_limit) in [("0", 1), ("100000", MAX_LIMIT)] {
+            let search = ItemSearch::from_query(&format!("limit={limit}")).unwrap();
+            assert_eq!(search.filter_params().unwrap().limit, Some(page_limit));
+        }
+    }
+
+    #[test]
+    fn query_extension() {
+        let query = serde_json::from_value(json!({
+            "name": {"startsWith": "Rh", "in": ["Rhein", "Rhône"]},
+            "navigable": {"eq": true}
+        }))
+        .unwrap();
+        assert_eq!(
+            query_to_cql2(&query).unwrap(),
+            r#""name" IN ('Rhein', 'Rhône') AND "name" LIKE 'Rh%' AND "navigable" = true"#
+        );
+        let query = serde_json::from_value(json!({"name": {"like": "Rh"}})).unwrap();
+        assert!(query_to_cql2(&query).is_err());
+    }
+}
@@ -1,38 +1,378 @@
-use crate::config::FeatureServiceCfg;
+use crate::cli::Commands;
+use crate::config::{CollectionSourceCfg, DerivedCollectionsCfg, DownloadsCfg, FeatureServiceCfg};
 use crate::datasource::Datasources;
+use crate::dump::{dump_collection, DumpParams};
+use crate::error::{Error, Result};
 use crate::inventory::Inventory;
+use crate::openapi::InventoryOpenApi;
+use crate::output::JSONFG_CORE;
+use crate::s3_sync::list_geopackages;
 use async_trait::async_trait;
-use bbox_core::cli::{NoArgs, NoCommands};
-use bbox_core::config::{error_exit, CoreServiceCfg};
+use bbox_core::api::OpenApiProvider;
+use bbox_core::cli::NoArgs;
+use bbox_core::config::{config_reader, error_exit, CoreServiceCfg};
+use bbox_core::config_check::ConfigReport;
+use bbox_core::health::{DependencyHealth, HealthCheck};
 use bbox_core::metrics::{no_metrics, NoMetrics};
 use bbox_core::ogcapi::{ApiLink, CoreCollection};
+use bbox_core::reload::{config_watcher, DirectoryWatcher, Reloadable};
 use bbox_core::service::OgcApiService;
+use clap::{ArgMatches, FromArgMatches};
+use log::{info, warn};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
 
 #[derive(Clone)]
 pub struct FeatureService {
-    pub inventory: Inventory,
+    pub inventory: Reloadable<Inventory>,
+    datasources: Reloadable<Datasources>,
+    /// Collections derived from QGIS projects, kept when reloading the configuration
+    derived: Reloadable<DerivedCollectionsCfg>,
+    pub(crate) downloads: Option<DownloadsCfg>,
 }
+
+impl FeatureService {
+    pub(crate) fn from_parts(
+        datasources: Datasources,
+        inventory: Inventory,
+        derived: DerivedCollectionsCfg,
+        downloads: Option<DownloadsCfg>,
+    ) -> Self {
+        FeatureService {
+            inventory: Reloadable::new(inventory),
+            datasources: Reloadable::new(datasources),
+            derived: Reloadable::new(derived),
+            downloads,
+        }
+    }
+    /// Setup datasources and collections
+    pub(crate) async fn load_inventory(
+        config: &FeatureServiceCfg,
+        derived: &DerivedCollectionsCfg,
+    ) -> Result<(Datasources, Inventory)> {
+        let datasources: Vec<_> = config
+            .datasources
+            .iter()
+            .chain(&derived.datasources)
+            .cloned()
+            .collect();
+        let sources = Datasources::create(&datasources, config.resilience.enabled).await?;
+        let mut inventory = Inventory::scan(&config.auto_collections).await;
+        inventory.list_unavailable = config.resilience.list_unavailable;
+        inventory.html_map = config.html_map.unwrap_or(true);
+        for cfg in &config.collections {
+            let cfg = cfg.with_query_timeout(config.query_timeout);
+            let mut collection = sources.setup_collection(&cfg).await?;
+            collection.collection.links.extend(cfg.download_links());
+            inventory.add_collection(collection);
+            inventory.collection_cfgs.insert(cfg.name.clone(), cfg);
+        }
+        for cfg in &derived.collections {
+            // Configured collections take precedence
+            if inventory.core_collection(&cfg.name).is_some() {
+                continue;
+            }
+            match sources.setup_collection(cfg).await {
+                Ok(collection) => {
+                    inventory.add_collection(collection);
+                    inventory
+                        .collection_cfgs
+                        .insert(cfg.name.clone(), cfg.clone());
+                }
+                Err(e) => warn!("Collection `{}` not published: {e}", cfg.name),
+            }
+        }
+        Ok((sources, inventory))
+    }
+    /// Publish vector layers of QGIS projects registered in the map service
+    #[cfg(feature = "map-server")]
+    pub async fn set_map_service(
+        &self,
+        service: &bbox_map_server::MapService,
+        config: &FeatureServiceCfg,
+    ) {
+        let derived = crate::qgis_collections::project_collections(&service.project_layers());
+        if derived.collections.is_empty() {
+            return;
+        }
+        match Self::load_inventory(config, &derived).await {
+            Ok((datasources, inventory)) => {
+                info!(
+                    "Publishing {} collection(s) of QGIS projects",
+                    derived.collections.len()
+                );
+                self.inventory.replace(inventory);
+                self.datasources.replace(datasources);
+                self.derived.replace(derived);
+            }
+            Err(e) => warn!("Publishing collections of QGIS projects failed: {e}"),
+        }
+    }
+    /// Replace inventory after configuration changes or changes in watched directories.
+    /// The current inventory is kept on errors.
+    fn reload_on_change(&self, config: &FeatureServiceCfg) {
+        let inventory = self.inventory.clone();
+        let datasources = self.datasources.clone();
+        let derived = self.derived.clone();
+        let (reload_tx, mut reload_rx) = mpsc::unbounded_channel();
+        let mut changes = config_watcher().subscribe();
+        let tx = reload_tx.clone();
+        tokio::spawn(async move {
+            while changes.changed().await.is_ok() {
+                if tx.send(()).is_err() {
+                    break;
+                }
+            }
+        });
+        for dir_ds in config
+            .auto_collections
+            .directory
+            .iter()
+            .filter(|ds| ds.watch)
+        {
+            let debounce = Duration::from_secs(dir_ds.debounce.unwrap_or(2));
+            let tx = reload_tx.clone();
+            DirectoryWatcher::new(&dir_ds.dir, &["*.gpkg", "*.fgb"], debounce).spawn(
+                Duration::from_secs(1),
+                move |_changes| {
+                    let _ = tx.send(());
+                    std::future::ready(())
+                },
+            );
+        }
+        for s3_cfg in &config.auto_collections.s3 {
+            let Some(refresh) = s3_cfg.refresh else {
+                continue;
+            };
+            let cfg = s3_cfg.clone();
+            let tx = reload_tx.clone();
+            tokio::spawn(async move {
+                let mut ticks = tokio::time::interval(Duration::from_secs(refresh.max(1)));
+                let mut objects = None;
+                loop {
+                    ticks.tick().await;
+                    match list_geopackages(&cfg).await {
+                        Ok(current) => {
+                            // Reload, when objects changed since last refresh
+                            if objects.as_ref().map_or(false, |prev| *prev != current)
+                                && tx.send(()).is_err()
+                            {
+                                break;
+                            }
+                            objects = Some(current);
+                        }
+                        Err(e) => warn!("Failed to list GeoPackages in '{}': {e}", cfg.url),
+                    }
+                }
+            });
+        }
+        drop(reload_tx);
+        let read_config = config_reader();
+        tokio::spawn(async move {
+            while reload_rx.recv().await.is_some() {
+                let config: FeatureServiceCfg = match read_config().extract() {
+                    Ok(config) => config,
+                    Err(e) => {
+                        warn!("Invalid configuration - feature collections not reloaded: {e}");
+                        continue;
+                    }
+                };
+                match Self::load_inventory(&config, &derived.current()).await {
+                    Ok((new_datasources, new_inventory)) => {
+                        info!(
+                            "Reloaded {} feature collections",
+                            new_inventory.collections().len()
+                        );
+                        inventory.replace(new_inventory);
+                        datasources.replace(new_datasources);
+                    }
+                    Err(e) => warn!("Reloading feature collections failed: {e}"),
+                }
+            }
+        });
+    }
+    /// Recompute temporal extents of PostGIS collections with `temporal_extent_refresh` periodically
+    fn refresh_temporal_extents(&self, config: &FeatureServiceCfg) {
+        for cfg in &config.collections {
+            let CollectionSourceCfg::Postgis(pg_cfg) = &cfg.source else {
+                continue;
+            };
+            let Some(refresh) = pg_cfg.temporal_extent_refresh else {
+                continue;
+            };
+            let inventory = self.inventory.clone();
+            let collection_id = cfg.name.clone();
+            tokio::spawn(async move {
+                let mut ticks = tokio::time::interval(Duration::from_secs(refresh.max(1)));
+                // The first tick completes immediately, the extent is computed at startup
+                ticks.tick().await;
+                loop {
+                    ticks.tick().await;
+                    let current = inventory.current();
+                    if current.core_collection(&collection_id).is_none() {
+                        // Removed by a configuration reload
+                        continue;
+                    }
+                    match current.query_temporal_extent(&collection_id).await {
+                        Ok(temporal) => inventory.update(|inventory| {
+                            let mut inventory = inventory.clone();
+                            inventory.set_temporal_extent(&collection_id, temporal);
+                            inventory
+                        }),
+                        Err(e) => {
+                            warn!("Collection `{collection_id}`: Refreshing temporal extent failed - {e}")
+                        }
+                    }
+                }
+            });
+        }
+    }
+    /// Setup a configured collection again and replace it in the inventory.
+    /// The current collection is kept, when the setup fails or its datasource is unavailable.
+    pub async fn refresh_collection(&self, collection_id: &str) -> Result<CoreCollection> {
+        let Some(cfg) = self
+            .inventory
+            .current()
+            .collection_cfgs
+            .get(collection_id)
+            .cloned()
+        else {
+            return Err(Error::CollectionNotFound(collection_id.to_string()));
+        };
+        let mut collection = self.datasources.current().setup_collection(&cfg).await?;
+        if !collection.source.available() {
+            return Err(Error::CollectionUnavailable(collection_id.to_string()));
+        }
+        collection.collection.links.extend(cfg.download_links());
+        let mut refreshed = None;
+        self.inventory.update(|inventory| {
+            let mut inventory = inventory.clone();
+            // Skip collections removed by a configuration reload in the meantime
+            if inventory.collection_cfgs.contains_key(collection_id) {
+                inventory.add_collection(collection);
+                refreshed = inventory.core_collection(collection_id).cloned();
+            }
+            inventory
+        });
+        refreshed.ok_or(Error::CollectionNotFound(collection_id.to_string()))
+    }
+    /// Refresh metadata of all configured collections with interval `metadata_refresh`
+    fn refresh_metadata(&self, config: &FeatureServiceCfg) {
+        let Some(refresh) = config.metadata_refresh else {
+            return;
+        };
+        let service = self.clone();
+        tokio::spawn(async move {
+            let mut ticks = tokio::time::interval(Duration::from_secs(refresh.max(1)));
+            // The first tick completes immediately, metadata is computed at startup
+            ticks.tick().await;
+            loop {
+                ticks.tick().await;
+                let collection_ids: Vec<String> = service
+                    .inventory
+                    .current()
+                    .collection_cfgs
+                    .keys()
+                    .cloned()
+                    .collect();
+                for collection_id in collection_ids {
+                    if let Err(e) = service.refresh_collection(&collection_id).await {
+                        warn!("Collection `{collection_id}`: Refreshing metadata failed - {e}");
+                    }
+                }
+            }
+        });
+    }
+}
+
+struct DatasourceHealth(Reloadable<Datasources>);
+
+#[async_trait]
+impl HealthCheck for DatasourceHealth {
+    fn name(&self) -> &str {
+        "feature-server"
+    }
+    async fn check(&self) -> Vec<DependencyHealth> {
+        self.0.current().check().await
+    }
+}
+
 #[async_trait]
 impl OgcApiService for FeatureService {
     type Config = FeatureServiceCfg;
-    type CliCommands = NoCommands;
+    type CliCommands = Commands;
     type CliArgs = NoArgs;
     type Metrics = NoMetrics;
 
     async fn create(config: &Self::Config, _core_cfg: &CoreServiceCfg) -> Self {
-        let mut sources = Datasources::create(&config.datasources)
+        if let Some(downloads) = &config.downloads {
+            std::fs::create_dir_all(&downloads.dir).unwrap_or_else(error_exit);
+        }
+        let derived = DerivedCollectionsCfg::default();
+        let (datasources, inventory) = Self::load_inventory(config, &derived)
             .await
             .unwrap_or_else(error_exit);
-
-        let mut inventory = Inventory::scan(&config.auto_collections).await;
+        let service =
+            FeatureService::from_parts(datasources, inventory, derived, config.downloads.clone());
+        service.reload_on_change(config);
+        service.refresh_temporal_extents(config);
+        service.refresh_metadata(config);
+        service
+    }
+    async fn check_config(config: &Self::Config, report: &mut ConfigReport) {
+        const SERVICE: &str = "feature-server";
+        for dir_ds in &config.auto_collections.directory {
+            let result =
+                std::fs::read_dir(&dir_ds.dir).map_err(|e| format!("`{}`: {e}", dir_ds.dir));
+            report.check(SERVICE, format!("directory `{}`", dir_ds.dir), &result);
+        }
+        if let Some(downloads) = &config.downloads {
+            report.check_writable_dir(SERVICE, "downloads.dir", Path::new(&downloads.dir));
+        }
+        let sources = match Datasources::create(&config.datasources, false).await {
+            Ok(sources) => {
+                report.ok(SERVICE, "datasources");
+                sources
+            }
+            Err(e) => {
+                report.error(SERVICE, "datasources", e);
+                return;
+            }
+        };
+        // Checks datasource, `check_query` and queryable type mappings
         for cfg in &config.collections {
-            let collection = sources
-                .setup_collection(cfg)
+            let cfg = cfg.with_query_timeout(config.query_timeout);
+            let result = sources.setup_collection(&cfg).await;
+            report.check(SERVICE, format!("collection `{}`", cfg.name), &result);
+        }
+    }
+    async fn cli_run(&self, cli: &ArgMatches) -> bool {
+        match Commands::from_arg_matches(cli) {
+            Ok(Commands::Dump(args)) => {
+                let params = DumpParams {
+                    bbox: args.bbox,
+                    datetime: args.datetime,
+                    filter: args.filter,
+                };
+                let count = dump_collection(
+                    &self.inventory.current(),
+                    &args.collection,
+                    &params,
+                    &args.output,
+                )
                 .await
                 .unwrap_or_else(error_exit);
-            inventory.add_collection(collection);
+                info!(
+                    "{count} features of `{}` written to `{}`",
+                    args.collection,
+                    args.output.display()
+                );
+                true
+            }
+            _ => false,
         }
-        FeatureService { inventory }
     }
     fn conformance_classes(&self) -> Vec<String> {
         let mut classes = vec![
@@ -40,8 +380,34 @@ impl OgcApiService for FeatureService {
             "http://www.opengis.net/spec/ogcapi-features-1/1.0/conf/core".to_string(),
             "http://www.opengis.net/spec/ogcapi-features-1/1.0/conf/geojson".to_string(),
             "http://www.opengis.net/spec/ogcapi-features-1/1.0/conf/oas30".to_string(),
-            // "http://www.opengis.net/spec/ogcapi-features-2/1.0/conf/crs".to_string(),
+            "http://www.opengis.net/spec/ogcapi-features-3/1.0/conf/filter".to_string(),
+            "http://www.opengis.net/spec/ogcapi-features-3/1.0/conf/features-filter".to_string(),
+            "http://www.opengis.net/spec/cql2/1.0/conf/cql2-text".to_string(),
+            "http://www.opengis.net/spec/cql2/1.0/conf/basic-cql2".to_string(),
+            "http://www.opengis.net/spec/cql2/1.0/conf/advanced-comparison-operators".to_string(),
+            "http://www.opengis.net/spec/cql2/1.0/conf/basic-spatial-functions".to_string(),
+            "http://www.opengis.net/spec/cql2/1.0/conf/spatial-functions".to_string(),
+            "https://api.stacspec.org/v1.0.0/item-search".to_string(),
+            "https://api.stacspec.org/v1.0.0/item-search#query".to_string(),
+            "https://api.stacspec.org/v1.0.0/item-search#sort".to_string(),
+            "https://api.stacspec.org/v1.0.0/item-search#fields".to_string(),
+            "https://api.stacspec.org/v1.0.0-rc.2/item-search#filter".to_string(),
+            "http://www.opengis.net/spec/ogcapi-features-2/1.0/conf/crs".to_string(),
+            JSONFG_CORE.to_string(),
         ];
+        let inventory = self.inventory.current();
+        let writable = inventory
+            .collections()
+            .iter()
+            .any(|collection| inventory.collection_writable(&collection.id));
+        if writable {
+            classes.extend(vec![
+                "http://www.opengis.net/spec/ogcapi-features-4/1.0/conf/create-replace-delete"
+                    .to_string(),
+                "http://www.opengis.net/spec/ogcapi-features-4/1.0/conf/update".to_string(),
+                "http://www.opengis.net/spec/ogcapi-features-4/1.0/conf/features".to_string(),
+            ]);
+        }
         if cfg!(feature = "html") {
             classes.extend(vec![
                 "http://www.opengis.net/spec/ogcapi-features-1/1.0/conf/html".to_string(),
@@ -50,22 +416,38 @@ impl OgcApiService for FeatureService {
         classes
     }
     fn landing_page_links(&self, _api_base: &str) -> Vec<ApiLink> {
-        vec![ApiLink {
-            href: "/collections".to_string(),
-            rel: Some("data".to_string()),
-            type_: Some("application/json".to_string()),
-            title: Some("Information about the feature collections".to_string()),
-            hreflang: None,
-            length: None,
-        }]
+        vec![
+            ApiLink {
+                href: "/collections".to_string(),
+                rel: Some("data".to_string()),
+                type_: Some("application/json".to_string()),
+                title: Some("Information about the feature collections".to_string()),
+                hreflang: None,
+                length: None,
+            },
+            ApiLink {
+                href: "/search".to_string(),
+                rel: Some("search".to_string()),
+                type_: Some("application/geo+json".to_string()),
+                title: Some("STAC item search".to_string()),
+                hreflang: None,
+                length: None,
+            },
+        ]
     }
     fn collections(&self) -> Vec<CoreCollection> {
-        self.inventory.collections()
+        self.inventory.current().collections()
     }
     fn openapi_yaml(&self) -> Option<&str> {
         Some(include_str!("openapi.yaml"))
     }
+    fn openapi_provider(&self) -> Option<Arc<dyn OpenApiProvider>> {
+        Some(Arc::new(InventoryOpenApi::new(self.inventory.clone())))
+    }
     fn metrics(&self) -> &'static Self::Metrics {
         no_metrics()
     }
+    fn health_check(&self) -> Option<Arc<dyn HealthCheck>> {
+        Some(Arc::new(DatasourceHealth(self.datasources.clone())))
+    }
 }
@@ -126,6 +126,12 @@ async fn scalar_html() -> Result<HttpResponse, Error> {
 }
 
 pub fn register(cfg: &mut web::ServiceConfig) {
+    // Registered before MapLibre static files
+    #[cfg(feature = "tile-server")]
+    cfg.service(
+        resource("/maplibre/config/{tileset}.json")
+            .route(get().to(crate::maplibre_config::tileset_config)),
+    );
     cfg.service(resource("/").route(get().to(index)))
         .service(
             resource(r#"/frontend/{filename:.*}"#).route(get().to(embedded::<FrontendStatics>)),
@@ -146,6 +152,11 @@ pub fn register(cfg: &mut web::ServiceConfig) {
             .service(resource("/qwc2_map/{id}/themes.json").route(get().to(qwc2_theme)))
             .service(resource(r#"/qwc2_map/{id}/{filename:.*}"#).route(get().to(qwc2_map)));
     }
+    #[cfg(feature = "map-server")]
+    cfg.service(resource("/legends/{theme}").route(get().to(crate::legends::theme_legend)))
+        .service(
+            resource("/legends/{theme}/{layer}").route(get().to(crate::legends::layer_legend)),
+        );
     if cfg!(not(feature = "map-server")) {
         cfg.app_data(web::Data::new(MapInventory::default()));
     }
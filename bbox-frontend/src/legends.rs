@@ -0,0 +1,117 @@
+//! Legend graphics of map themes, proxied from the map backend.
+
+use crate::MapInventory;
+use actix_web::{web, Error, HttpRequest, HttpResponse};
+use bbox_core::endpoints::abs_req_baseurl;
+use log::warn;
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// Maximal number of cached legend graphics
+const MAX_CACHE_ENTRIES: usize = 1000;
+
+/// Legend graphics with content type by theme, layer and request parameters
+static LEGEND_CACHE: Lazy<Mutex<HashMap<String, (String, Vec<u8>)>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Request parameters passed to the backend. `LAYER`, `FORMAT` and the WMS request parameters are set by the proxy.
+fn passthrough_params(query: &str) -> String {
+    let mut params: Vec<&str> = query
+        .split('&')
+        .filter(|param| {
+            let key = param.split('=').next().unwrap_or_default().to_uppercase();
+            !param.is_empty()
+                && !["SERVICE", "VERSION", "REQUEST", "LAYER", "LAYERS", "FORMAT"]
+                    .contains(&key.as_str())
+        })
+        .collect();
+    params.sort_unstable();
+    params.join("&")
+}
+
+/// Layer name of `/legends/{theme}?LAYER=...` requests
+fn layer_param(query: &str) -> Option<String> {
+    let params = web::Query::<HashMap<String, String>>::from_query(query).ok()?;
+    params
+        .into_inner()
+        .into_iter()
+        .find_map(|(key, value)| key.eq_ignore_ascii_case("LAYER").then_some(value))
+}
+
+async fn legend_response(
+    inventory: &MapInventory,
+    req: &HttpRequest,
+    theme: &str,
+    layer: &str,
+) -> Result<HttpResponse, Error> {
+    let Some(wms) = inventory.wms_services.iter().find(|wms| wms.id == theme) else {
+        return Ok(HttpResponse::NotFound().finish());
+    };
+    let params = passthrough_params(req.query_string());
+    let key = format!("{theme}/{layer}?{params}");
+    if let Some((content_type, body)) = LEGEND_CACHE.lock().unwrap().get(&key) {
+        return Ok(HttpResponse::Ok()
+            .content_type(content_type.as_str())
+            .body(body.clone()));
+    }
+    match wms
+        .legend_graphic(&abs_req_baseurl(req), layer, &params)
+        .await
+    {
+        Ok((content_type, body)) => {
+            let mut cache = LEGEND_CACHE.lock().unwrap();
+            if cache.len() >= MAX_CACHE_ENTRIES {
+                cache.clear();
+            }
+            cache.insert(key, (content_type.clone(), body.clone()));
+            Ok(HttpResponse::Ok().content_type(content_type).body(body))
+        }
+        Err(e) => {
+            warn!("Legend of layer `{layer}` in theme `{theme}` failed: {e}");
+            Ok(HttpResponse::BadGateway().finish())
+        }
+    }
+}
+
+/// Legend graphic of a theme layer
+// /legends/{theme}/{layer}
+pub async fn layer_legend(
+    path: web::Path<(String, String)>,
+    inventory: web::Data<MapInventory>,
+    req: HttpRequest,
+) -> Result<HttpResponse, Error> {
+    let (theme, layer) = path.into_inner();
+    legend_response(&inventory, &req, &theme, &layer).await
+}
+
+/// Legend graphic requested with a `LAYER` parameter, used as QWC2 theme `legendUrl`
+// /legends/{theme}
+pub async fn theme_legend(
+    theme: web::Path<String>,
+    inventory: web::Data<MapInventory>,
+    req: HttpRequest,
+) -> Result<HttpResponse, Error> {
+    let Some(layer) = layer_param(req.query_string()) else {
+        return Ok(HttpResponse::BadRequest().body("LAYER parameter missing"));
+    };
+    legend_response(&inventory, &req, &theme, &layer).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn legend_params() {
+        let query =
+            "SERVICE=WMS&REQUEST=GetLegendGraphic&LAYER=country&SCALE=50000&FORMAT=image/png";
+        assert_eq!(passthrough_params(query), "SCALE=50000");
+        assert_eq!(layer_param(query).as_deref(), Some("country"));
+        assert_eq!(
+            layer_param("layer=populated%20places").as_deref(),
+            Some("populated places")
+        );
+        assert_eq!(layer_param("SCALE=50000"), None);
+    }
+}
@@ -1,5 +1,9 @@
 pub mod endpoints;
 #[cfg(feature = "map-server")]
+mod legends;
+#[cfg(feature = "tile-server")]
+mod maplibre_config;
+#[cfg(feature = "map-server")]
 mod qwc2_config;
 
 #[cfg(feature = "map-server")]
@@ -0,0 +1,203 @@
+//! MapLibre viewer configuration generated from tilesets.
+
+use actix_web::{web, Error, HttpRequest, HttpResponse};
+use bbox_core::endpoints::abs_req_baseurl;
+use bbox_tile_server::datasource::{LayerInfo, SourceType};
+use bbox_tile_server::TileService;
+use log::warn;
+use serde_json::{json, Value};
+
+/// Colors assigned to layers without configured style
+const PALETTE: [&str; 8] = [
+    "#1f77b4", "#ff7f0e", "#2ca02c", "#d62728", "#9467bd", "#8c564b", "#e377c2", "#17becf",
+];
+
+/// Default layer styles of a vector tile layer, depending on its geometry type
+fn vector_layer_styles(tileset: &str, layer: &LayerInfo, color: &str) -> Vec<Value> {
+    let base = |id: String, layer_type: &str, paint: Value| {
+        json!({
+            "id": id,
+            "type": layer_type,
+            "source": tileset,
+            "source-layer": layer.name,
+            "paint": paint
+        })
+    };
+    let name = &layer.name;
+    let geometry_type = layer
+        .geometry_type
+        .as_deref()
+        .unwrap_or_default()
+        .to_uppercase();
+    match geometry_type.as_str() {
+        "POINT" | "MULTIPOINT" | "CIRCLE" => vec![base(
+            name.clone(),
+            "circle",
+            json!({"circle-color": color, "circle-radius": 4, "circle-stroke-color": "#ffffff", "circle-stroke-width": 1}),
+        )],
+        "LINESTRING" | "MULTILINESTRING" | "COMPOUNDCURVE" | "LINE" => vec![base(
+            name.clone(),
+            "line",
+            json!({"line-color": color, "line-width": 1.5}),
+        )],
+        "POLYGON" | "MULTIPOLYGON" | "CURVEPOLYGON" | "FILL" => vec![
+            base(
+                name.clone(),
+                "fill",
+                json!({"fill-color": color, "fill-opacity": 0.4}),
+            ),
+            base(
+                format!("{name}-outline"),
+                "line",
+                json!({"line-color": color, "line-width": 1}),
+            ),
+        ],
+        // Unknown geometry type: render all geometries with a filter per type
+        _ => vec![
+            json!({
+                "id": format!("{name}-polygon"), "type": "fill", "source": tileset, "source-layer": name,
+                "filter": ["==", ["geometry-type"], "Polygon"],
+                "paint": {"fill-color": color, "fill-opacity": 0.4, "fill-outline-color": color}
+            }),
+            json!({
+                "id": format!("{name}-line"), "type": "line", "source": tileset, "source-layer": name,
+                "filter": ["==", ["geometry-type"], "LineString"],
+                "paint": {"line-color": color, "line-width": 1.5}
+            }),
+            json!({
+                "id": format!("{name}-point"), "type": "circle", "source": tileset, "source-layer": name,
+                "filter": ["==", ["geometry-type"], "Point"],
+                "paint": {"circle-color": color, "circle-radius": 4}
+            }),
+        ],
+    }
+}
+
+/// MapLibre style referencing the TileJSON of a tileset
+pub(crate) fn maplibre_style(
+    tileset: &str,
+    source_type: SourceType,
+    layers: &[LayerInfo],
+    base_url: &str,
+) -> Value {
+    let (source, layer_styles) = match source_type {
+        SourceType::Vector => {
+            let mut styles = vec![json!({
+                "id": "background_",
+                "type": "background",
+                "paint": {"background-color": "rgba(255, 255, 255, 1)"}
+            })];
+            for (i, layer) in layers.iter().enumerate() {
+                if let Some(style) = &layer.style {
+                    // Configured style replaces generated defaults
+                    let mut layerjson = json!({"id": layer.name, "type": "line", "source": tileset, "source-layer": layer.name});
+                    if let (Some(obj), Some(style)) = (layerjson.as_object_mut(), style.as_object())
+                    {
+                        obj.extend(style.clone());
+                    }
+                    styles.push(layerjson);
+                } else {
+                    let color = PALETTE[i % PALETTE.len()];
+                    styles.extend(vector_layer_styles(tileset, layer, color));
+                }
+            }
+            (
+                json!({"type": "vector", "url": format!("{base_url}/xyz/{tileset}.json")}),
+                styles,
+            )
+        }
+        SourceType::Raster => (
+            json!({"type": "raster", "url": format!("{base_url}/xyz/{tileset}.json"), "tileSize": 256}),
+            vec![json!({"id": tileset, "type": "raster", "source": tileset})],
+        ),
+    };
+    json!({
+        "version": 8,
+        "name": tileset,
+        "glyphs": format!("{base_url}/fonts/{{fontstack}}/{{range}}.pbf"),
+        "sources": {
+            tileset: source
+        },
+        "layers": layer_styles
+    })
+}
+
+/// MapLibre style with default layer styling
+// /maplibre/config/{tileset}.json
+pub async fn tileset_config(
+    tileset: web::Path<String>,
+    service: web::Data<TileService>,
+    req: HttpRequest,
+) -> Result<HttpResponse, Error> {
+    let Some(ts) = service.tileset(&tileset) else {
+        return Ok(HttpResponse::NotFound().finish());
+    };
+    let layers = match ts.source.layers().await {
+        Ok(layers) => layers,
+        Err(e) => {
+            warn!("Reading layers of tileset `{tileset}` failed: {e}");
+            return Ok(HttpResponse::InternalServerError().finish());
+        }
+    };
+    let style = maplibre_style(
+        &tileset,
+        ts.source.source_type(),
+        &layers,
+        &abs_req_baseurl(&req),
+    );
+    Ok(HttpResponse::Ok().json(style))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn layer(name: &str, geometry_type: Option<&str>) -> LayerInfo {
+        LayerInfo {
+            name: name.to_string(),
+            geometry_type: geometry_type.map(str::to_string),
+            style: None,
+        }
+    }
+
+    #[test]
+    fn default_vector_style() {
+        let layers = vec![
+            layer("places", Some("POINT")),
+            layer("countries", Some("MULTIPOLYGON")),
+            layer("other", None),
+        ];
+        let style = maplibre_style("ne", SourceType::Vector, &layers, "http://localhost:8080");
+        assert_eq!(
+            style["sources"]["ne"]["url"],
+            "http://localhost:8080/xyz/ne.json"
+        );
+        let ids: Vec<&str> = style["layers"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|l| l["id"].as_str().unwrap())
+            .collect();
+        assert_eq!(
+            ids,
+            [
+                "background_",
+                "places",
+                "countries",
+                "countries-outline",
+                "other-polygon",
+                "other-line",
+                "other-point"
+            ]
+        );
+        assert_eq!(style["layers"][1]["type"], "circle");
+        assert_eq!(style["layers"][2]["source-layer"], "countries");
+    }
+
+    #[test]
+    fn raster_style() {
+        let style = maplibre_style("dem", SourceType::Raster, &[], "http://localhost:8080");
+        assert_eq!(style["sources"]["dem"]["type"], "raster");
+        assert_eq!(style["layers"][0]["type"], "raster");
+    }
+}
@@ -107,6 +107,8 @@ pub struct Layer {
     pub sublayers: Option<Vec<Layer>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub expanded: Option<bool>,
+    #[serde(rename = "legendUrl", skip_serializing_if = "Option::is_none")]
+    pub legend_url: Option<String>,
 }
 
 #[derive(Debug, Serialize, Clone)]
@@ -174,14 +176,22 @@ pub struct BackgroundLayer {
 impl ThemesJson {
     pub fn from_capabilities(
         caps: Vec<(&WmsService, ogc::WmsCapabilities, String)>,
+        base_url: &str,
         default_theme: Option<&str>,
     ) -> Self {
         let themes: Vec<Theme> = caps
             .iter()
-            .map(|(wms, c, url)| Theme::from_capabilities(wms.id.to_string(), c, url).unwrap())
+            .filter_map(|(wms, c, url)| {
+                let legend_url = format!("{base_url}/legends/{}", wms.id);
+                let theme = Theme::from_capabilities(wms.id.to_string(), c, url, &legend_url);
+                if theme.is_none() {
+                    warn!("Skipping theme `{}` without layers", wms.id);
+                }
+                theme
+            })
             .collect();
         let default_theme = default_theme
-            .or(caps.first().map(|(wms, _, _)| wms.id.as_str()))
+            .or(themes.first().map(|theme| theme.id.as_str()))
             .unwrap_or("")
             .to_string();
         ThemesJson {
@@ -204,6 +214,7 @@ impl Theme {
         id: String,
         caps: &ogc::WmsCapabilities,
         url: &String,
+        legend_url: &str,
     ) -> Option<Self> {
         if caps.capability.layers.is_empty() {
             return None;
@@ -211,7 +222,7 @@ impl Theme {
         if caps.capability.layers.len() > 1 {
             warn!("Ignoring root layers after first one in WMS capabilitiies")
         }
-        let layers = parse_layer_tree(&caps.capability.layers);
+        let layers = parse_layer_tree(&caps.capability.layers, legend_url);
 
         let root_layer_ogc = &caps.capability.layers[0];
         let _root_layer = &layers[0];
@@ -320,7 +331,8 @@ impl Theme {
             additional_mouse_crs: vec![],                      // make configurable
             map_crs: map_crs.unwrap_or("EPSG:3857".to_string()),
             drawing_order: vec![], // (capabilities.Capability.LayerDrawingOrder || "").split(",").map(title => title in titleNameMap ? titleNameMap[title] : title);
-            legend_url: format!("{url}?"),
+            // Legends are proxied and cached by the frontend
+            legend_url: format!("{legend_url}?"),
             feature_info_url: format!("{url}?"),
             print_url: format!("{url}?"),
             skip_empty_feature_attributes: true,
@@ -331,13 +343,13 @@ impl Theme {
     }
 }
 
-fn parse_layer_tree(ogc_layers: &[ogc::Layer]) -> Vec<Layer> {
+fn parse_layer_tree(ogc_layers: &[ogc::Layer], legend_url: &str) -> Vec<Layer> {
     let layers: Vec<Layer> = ogc_layers
         .iter()
         // skip layers without geometry
         //TODO: layer.$.geometryType == "WKBNoGeometry" || layer.$.geometryType == "NoGeometry") {
         .map(|l| {
-            let sublayers = parse_layer_tree(&l.layers);
+            let sublayers = parse_layer_tree(&l.layers, legend_url);
             let expanded = if sublayers.is_empty() {
                 None
             } else {
@@ -349,6 +361,10 @@ fn parse_layer_tree(ogc_layers: &[ogc::Layer]) -> Vec<Layer> {
                 Some(sublayers)
             };
 
+            let legend_url = match &l.name {
+                Some(name) if sublayers.is_none() => Some(format!("{legend_url}/{name}")),
+                _ => None,
+            };
             let layer = Layer {
                 name: l.name.as_ref().unwrap_or(&"".to_string()).clone(),
                 title: l.title.as_ref().unwrap_or(&"".to_string()).clone(),
@@ -380,6 +396,7 @@ fn parse_layer_tree(ogc_layers: &[ogc::Layer]) -> Vec<Layer> {
                 mutually_exclusive: l.mutually_exclusive,
                 sublayers,
                 expanded,
+                legend_url,
             };
             layer
         })
@@ -396,7 +413,7 @@ pub async fn themes_json(
     for wms in wms_services {
         caps.push((wms, wms.capabilities(&base_url).await, wms.url(&base_url)));
     }
-    ThemesJson::from_capabilities(caps, default_theme)
+    ThemesJson::from_capabilities(caps, &base_url, default_theme)
 }
 
 static THEMES_JSON: Lazy<ThemesJson> = Lazy::new(|| {
@@ -518,6 +535,7 @@ static THEMES_JSON: Lazy<ThemesJson> = Lazy::new(|| {
                         mutually_exclusive: None,
                         sublayers: None,
                         expanded: None,
+                        legend_url: None,
                     },
                     Layer {
                         name: "state".to_string(),
@@ -554,6 +572,7 @@ static THEMES_JSON: Lazy<ThemesJson> = Lazy::new(|| {
                         mutually_exclusive: None,
                         sublayers: None,
                         expanded: None,
+                        legend_url: None,
                     },
                     Layer {
                         name: "country".to_string(),
@@ -590,6 +609,7 @@ static THEMES_JSON: Lazy<ThemesJson> = Lazy::new(|| {
                         mutually_exclusive: None,
                         sublayers: None,
                         expanded: None,
+                        legend_url: None,
                     },
                     Layer {
                         name: "geo-lines".to_string(),
@@ -635,6 +655,7 @@ static THEMES_JSON: Lazy<ThemesJson> = Lazy::new(|| {
                                     mutually_exclusive: None,
                                     sublayers: None,
                                     expanded: None,
+                                    legend_url: None,
                                 },
                                 Layer {
                                     name: "ne_50m_geographic_lines".to_string(),
@@ -661,12 +682,14 @@ static THEMES_JSON: Lazy<ThemesJson> = Lazy::new(|| {
                                     mutually_exclusive: None,
                                     sublayers: None,
                                     expanded: None,
+                                    legend_url: None,
                                 },
                             ],
                         ),
                         expanded: Some(
                             true,
                         ),
+                        legend_url: None,
                     },
                 ],
                 expanded: true,
@@ -20,6 +20,7 @@ use crate::wms_fcgi_backend::FcgiBackendType;
 use async_process::{Child as ChildProcess, Command, Stdio};
 use async_trait::async_trait;
 use bbox_core::config::Loglevel;
+use bbox_core::health::DependencyHealth;
 use bufstream::BufStream;
 use fastcgi_client::Client;
 use log::{debug, error, info, warn};
@@ -203,6 +204,9 @@ impl FcgiProcessPool {
         let dispatcher = Dispatcher::new(&config, &pools);
         FcgiDispatcher {
             backend_name: self.backend_name.clone(),
+            socket_paths: (0..self.num_processes)
+                .map(|no| self.socket_path(no))
+                .collect(),
             pools,
             dispatcher,
             suffixes: self.suffixes.clone(),
@@ -299,6 +303,8 @@ pub type FcgiClientPool = deadpool::managed::Pool<FcgiClientHandler>;
 /// FCGI client dispatcher
 pub struct FcgiDispatcher {
     backend_name: String,
+    /// Socket of each FCGI process
+    socket_paths: Vec<String>,
     /// Client pool for each FCGI process
     pools: Vec<FcgiClientPool>,
     /// Mode-dependent dispatcher
@@ -311,6 +317,18 @@ impl FcgiDispatcher {
     pub fn backend_name(&self) -> &str {
         &self.backend_name
     }
+    /// Check whether FCGI processes accept connections
+    pub fn check_processes(&self) -> Vec<DependencyHealth> {
+        self.socket_paths
+            .iter()
+            .enumerate()
+            .map(|(no, socket_path)| {
+                let result = UnixStream::connect(socket_path).map(|_| ());
+                let name = format!("{}[{no}]", self.backend_name);
+                DependencyHealth::from_result("fcgi", &name, result)
+            })
+            .collect()
+    }
     /// Select FCGI process
     /// Returns process index and FCGI client pool
     pub fn select(&self, query_str: &str) -> (usize, &FcgiClientPool) {
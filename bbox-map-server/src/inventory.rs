@@ -1,6 +1,8 @@
+use crate::qgis_project::url_encode;
 use crate::wms_capabilities::*;
 use serde::Serialize;
 use serde_xml_rs::from_reader;
+use std::path::PathBuf;
 
 #[derive(Serialize, Clone, Default, Debug)]
 pub struct Inventory {
@@ -13,6 +15,9 @@ pub struct WmsService {
     /// WMS base path like `/qgis/ne`
     pub wms_path: String,
     pub cap_type: CapType,
+    /// Project file
+    #[serde(skip)]
+    pub project_file: PathBuf,
 }
 
 #[derive(Serialize, Clone, PartialEq, Debug)]
@@ -53,4 +58,49 @@ impl WmsService {
         let cap: WmsCapabilities = from_reader(body.as_ref()).unwrap();
         cap
     }
+    /// GetLegendGraphic response of `layer` with content type.
+    /// `params` are additional encoded request parameters like `SCALE=50000`.
+    pub async fn legend_graphic(
+        &self,
+        base_url: &str,
+        layer: &str,
+        params: &str,
+    ) -> Result<(String, Vec<u8>), LegendError> {
+        let mut url = format!(
+            "{}?SERVICE=WMS&VERSION=1.3.0&REQUEST=GetLegendGraphic&FORMAT=image/png&LAYER={}",
+            &self.url(base_url),
+            url_encode(layer)
+        );
+        if !params.is_empty() {
+            url.push('&');
+            url.push_str(params);
+        }
+        let mut response = awc::Client::default()
+            .get(url)
+            .send()
+            .await
+            .map_err(|e| LegendError::RequestError(e.to_string()))?;
+        if !response.status().is_success() {
+            return Err(LegendError::BackendStatus(response.status().as_u16()));
+        }
+        let content_type = response
+            .headers()
+            .get("content-type")
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or("image/png")
+            .to_string();
+        let body = response
+            .body()
+            .await
+            .map_err(|e| LegendError::RequestError(e.to_string()))?;
+        Ok((content_type, body.to_vec()))
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum LegendError {
+    #[error("GetLegendGraphic request failed: {0}")]
+    RequestError(String),
+    #[error("GetLegendGraphic returned status {0}")]
+    BackendStatus(u16),
 }
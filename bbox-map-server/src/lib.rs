@@ -4,6 +4,7 @@ pub mod endpoints;
 pub mod fcgi_process;
 pub mod inventory;
 pub mod metrics;
+pub mod qgis_project;
 pub mod service;
 pub mod wms_capabilities;
 mod wms_fcgi_backend;
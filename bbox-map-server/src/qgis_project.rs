@@ -0,0 +1,329 @@
+//! Vector layers of QGIS projects.
+
+use log::{info, warn};
+use serde::Deserialize;
+use serde_xml_rs::from_reader;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::{Path, PathBuf};
+
+#[derive(thiserror::Error, Debug)]
+pub enum ProjectError {
+    #[error(transparent)]
+    IoError(#[from] std::io::Error),
+    #[error(transparent)]
+    XmlError(#[from] serde_xml_rs::Error),
+    #[error(transparent)]
+    ZipError(#[from] zip::result::ZipError),
+    #[error("no .qgs file found in QGIS project archive")]
+    QgsMissing,
+}
+
+/// Vector layer of a QGIS project
+#[derive(Clone, PartialEq, Debug)]
+pub struct ProjectLayer {
+    /// Layer name
+    pub name: String,
+    pub source: LayerSource,
+}
+
+/// Data source of a vector layer
+#[derive(Clone, PartialEq, Debug)]
+pub enum LayerSource {
+    Postgis {
+        /// Connection URL
+        url: String,
+        table_schema: Option<String>,
+        table_name: String,
+        geometry_field: Option<String>,
+        fid_field: Option<String>,
+    },
+    Gpkg {
+        path: PathBuf,
+        table_name: String,
+    },
+    Flatgeobuf {
+        path: PathBuf,
+    },
+}
+
+#[derive(Deserialize, Debug)]
+struct QgisXml {
+    #[serde(default)]
+    projectlayers: ProjectLayersXml,
+}
+
+#[derive(Deserialize, Default, Debug)]
+struct ProjectLayersXml {
+    #[serde(default)]
+    maplayer: Vec<MapLayerXml>,
+}
+
+#[derive(Deserialize, Debug)]
+struct MapLayerXml {
+    #[serde(rename = "type")]
+    layer_type: Option<String>,
+    layername: Option<String>,
+    datasource: Option<String>,
+    provider: Option<ProviderXml>,
+}
+
+#[derive(Deserialize, Debug)]
+struct ProviderXml {
+    #[serde(rename = "$value")]
+    name: String,
+}
+
+/// Read vector layers of a `.qgs` or `.qgz` project file.
+/// Layers with unsupported providers or filters are skipped.
+pub fn read_project_layers(path: &Path) -> Result<Vec<ProjectLayer>, ProjectError> {
+    let basedir = path.parent().unwrap_or(Path::new("."));
+    if path.extension().map_or(false, |ext| ext == "qgz") {
+        let mut archive = zip::ZipArchive::new(File::open(path)?)?;
+        let qgs_name = archive
+            .file_names()
+            .find(|name| name.ends_with(".qgs"))
+            .map(str::to_string)
+            .ok_or(ProjectError::QgsMissing)?;
+        let mut qgs = String::new();
+        archive.by_name(&qgs_name)?.read_to_string(&mut qgs)?;
+        project_layers(qgs.as_bytes(), basedir)
+    } else {
+        project_layers(BufReader::new(File::open(path)?), basedir)
+    }
+}
+
+fn project_layers<R: Read>(reader: R, basedir: &Path) -> Result<Vec<ProjectLayer>, ProjectError> {
+    let xml: QgisXml = from_reader(reader)?;
+    let mut layers = Vec::new();
+    for layer in xml.projectlayers.maplayer {
+        if layer.layer_type.as_deref() != Some("vector") {
+            continue;
+        }
+        let (Some(name), Some(datasource), Some(provider)) =
+            (layer.layername, layer.datasource, layer.provider)
+        else {
+            continue;
+        };
+        let source = match provider.name.as_str() {
+            "postgres" => pg_layer_source(&datasource),
+            "ogr" => ogr_layer_source(&datasource, basedir),
+            _ => None,
+        };
+        match source {
+            Some(source) => layers.push(ProjectLayer { name, source }),
+            None => info!("Layer `{name}` with {} source not published", provider.name),
+        }
+    }
+    Ok(layers)
+}
+
+/// Source of PostgreSQL layer, e.g. `dbname='ne' host=localhost user='ne' table="public"."places" (geom)`
+fn pg_layer_source(datasource: &str) -> Option<LayerSource> {
+    let params = parse_pg_datasource(datasource);
+    if params.get("sql").map_or(false, |sql| !sql.is_empty()) {
+        // Publishing unfiltered data of filtered layers could expose hidden features
+        return None;
+    }
+    if params.contains_key("service") && !params.contains_key("dbname") {
+        warn!("PostgreSQL service connections are not supported");
+        return None;
+    }
+    let credentials = match (params.get("user"), params.get("password")) {
+        (Some(user), Some(password)) => {
+            format!("{}:{}@", url_encode(user), url_encode(password))
+        }
+        (Some(user), None) => format!("{}@", url_encode(user)),
+        _ => String::new(),
+    };
+    let mut url = format!(
+        "postgresql://{credentials}{}:{}/{}",
+        params.get("host").map_or("localhost", String::as_str),
+        params.get("port").map_or("5432", String::as_str),
+        params.get("dbname")?
+    );
+    if let Some(sslmode) = params.get("sslmode") {
+        url.push_str(&format!("?sslmode={sslmode}"));
+    }
+    Some(LayerSource::Postgis {
+        url,
+        table_schema: params.get("schema").cloned(),
+        table_name: params.get("table")?.clone(),
+        geometry_field: params.get("geometry").cloned(),
+        fid_field: params.get("key").cloned(),
+    })
+}
+
+/// Key-value pairs of a PostgreSQL layer datasource.
+/// The table is split into `schema` and `table`, the geometry column is returned as `geometry`.
+fn parse_pg_datasource(datasource: &str) -> HashMap<String, String> {
+    let mut params = HashMap::new();
+    let mut chars = datasource.trim().chars().peekable();
+    loop {
+        while chars.peek() == Some(&' ') {
+            chars.next();
+        }
+        let key: String = chars.by_ref().take_while(|c| *c != '=').collect();
+        if key.is_empty() {
+            break;
+        }
+        match key.as_str() {
+            "sql" => {
+                params.insert(key, chars.by_ref().collect::<String>().trim().to_string());
+                break;
+            }
+            "table" => {
+                let mut idents = Vec::new();
+                while chars.peek() == Some(&'"') {
+                    chars.next();
+                    idents.push(chars.by_ref().take_while(|c| *c != '"').collect::<String>());
+                    if chars.peek() == Some(&'.') {
+                        chars.next();
+                    }
+                }
+                if let Some(table) = idents.pop() {
+                    params.insert(key, table);
+                }
+                if let Some(schema) = idents.pop() {
+                    params.insert("schema".to_string(), schema);
+                }
+                while chars.peek() == Some(&' ') {
+                    chars.next();
+                }
+                if chars.peek() == Some(&'(') {
+                    chars.next();
+                    let geom: String = chars.by_ref().take_while(|c| *c != ')').collect();
+                    params.insert("geometry".to_string(), geom);
+                }
+            }
+            _ => {
+                let value = if chars.peek() == Some(&'\'') {
+                    chars.next();
+                    let mut value = String::new();
+                    while let Some(c) = chars.next() {
+                        match c {
+                            '\\' => value.extend(chars.next()),
+                            '\'' => break,
+                            _ => value.push(c),
+                        }
+                    }
+                    value
+                } else {
+                    chars.by_ref().take_while(|c| *c != ' ').collect()
+                };
+                params.insert(key, value);
+            }
+        }
+    }
+    params
+}
+
+/// Source of OGR layer, e.g. `./data/ne.gpkg|layername=places`
+fn ogr_layer_source(datasource: &str, basedir: &Path) -> Option<LayerSource> {
+    let mut parts = datasource.split('|');
+    let path = basedir.join(parts.next()?);
+    let mut layername = None;
+    for option in parts {
+        match option.split_once('=') {
+            Some(("layername", name)) => layername = Some(name.to_string()),
+            Some(("subset", subset)) if !subset.is_empty() => return None,
+            _ => {}
+        }
+    }
+    match path.extension()?.to_str()? {
+        "gpkg" => Some(LayerSource::Gpkg {
+            path,
+            table_name: layername?,
+        }),
+        "fgb" => Some(LayerSource::Flatgeobuf { path }),
+        _ => None,
+    }
+}
+
+pub(crate) fn url_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| {
+            if b.is_ascii_alphanumeric() || b"-._~".contains(&b) {
+                (b as char).to_string()
+            } else {
+                format!("%{b:02X}")
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn project_xml() {
+        let qgs = r#"<qgis projectname="" version="3.28.0">
+  <title></title>
+  <projectlayers>
+    <maplayer type="vector" geometry="Point" hasScaleBasedVisibilityFlag="0">
+      <extent><xmin>-180</xmin><ymin>-90</ymin><xmax>180</xmax><ymax>90</ymax></extent>
+      <id>places_7a0c</id>
+      <datasource>./ne.gpkg|layername=places</datasource>
+      <layername>populated places</layername>
+      <provider encoding="UTF-8">ogr</provider>
+    </maplayer>
+    <maplayer type="raster">
+      <id>relief_d3f1</id>
+      <datasource>./relief.tif</datasource>
+      <layername>relief</layername>
+      <provider>gdal</provider>
+    </maplayer>
+  </projectlayers>
+</qgis>"#;
+        let layers = project_layers(qgs.as_bytes(), Path::new("/projects")).unwrap();
+        assert_eq!(layers.len(), 1);
+        assert_eq!(layers[0].name, "populated places");
+        assert_eq!(
+            layers[0].source,
+            LayerSource::Gpkg {
+                path: PathBuf::from("/projects/./ne.gpkg"),
+                table_name: "places".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn postgres_datasource() {
+        let source = pg_layer_source(
+            r#"dbname='ne' host=db port=5433 user='ne' password='p@ss\'word' sslmode=disable key='fid' srid=3857 type=Point checkPrimaryKeyUnicity='1' table="public"."places" (wkb_geometry)"#,
+        );
+        assert_eq!(
+            source,
+            Some(LayerSource::Postgis {
+                url: "postgresql://ne:p%40ss%27word@db:5433/ne?sslmode=disable".to_string(),
+                table_schema: Some("public".to_string()),
+                table_name: "places".to_string(),
+                geometry_field: Some("wkb_geometry".to_string()),
+                fid_field: Some("fid".to_string()),
+            })
+        );
+
+        let filtered =
+            pg_layer_source(r#"dbname='ne' table="public"."places" (geom) sql="scalerank" < 3"#);
+        assert_eq!(filtered, None);
+    }
+
+    #[test]
+    fn ogr_datasource() {
+        let source = ogr_layer_source("./data/ne.gpkg|layername=places", Path::new("/projects"));
+        assert_eq!(
+            source,
+            Some(LayerSource::Gpkg {
+                path: PathBuf::from("/projects/./data/ne.gpkg"),
+                table_name: "places".to_string(),
+            })
+        );
+        assert_eq!(
+            ogr_layer_source("ne.shp|layername=places", Path::new("/projects")),
+            None
+        );
+    }
+}
@@ -1,16 +1,20 @@
 use crate::config::MapServiceCfg;
 use crate::fcgi_process::FcgiDispatcher;
+use crate::inventory::CapType;
 use crate::inventory::Inventory;
 use crate::metrics::{register_metrics, wms_metrics, WmsMetrics};
+use crate::qgis_project::{read_project_layers, ProjectLayer};
 use crate::wms_fcgi_backend::detect_backends;
 use actix_web::web;
 use async_trait::async_trait;
 use bbox_core::cli::{NoArgs, NoCommands};
 use bbox_core::config::CoreServiceCfg;
+use bbox_core::health::{DependencyHealth, HealthCheck};
 use bbox_core::service::OgcApiService;
-use log::error;
+use log::{error, info, warn};
 use prometheus::Registry;
 use std::collections::HashMap;
+use std::sync::Arc;
 
 #[derive(Clone)]
 pub struct MapService {
@@ -122,6 +126,9 @@ impl OgcApiService for MapService {
     fn openapi_yaml(&self) -> Option<&str> {
         Some(include_str!("openapi.yaml"))
     }
+    fn health_check(&self) -> Option<Arc<dyn HealthCheck>> {
+        Some(Arc::new(BackendHealth(self.fcgi_clients.clone())))
+    }
     fn add_metrics(&self, prometheus: &Registry) {
         register_metrics(prometheus, self.metrics());
     }
@@ -130,7 +137,47 @@ impl OgcApiService for MapService {
     }
 }
 
+struct BackendHealth(Vec<web::Data<FcgiDispatcher>>);
+
+#[async_trait]
+impl HealthCheck for BackendHealth {
+    fn name(&self) -> &str {
+        "map-server"
+    }
+    async fn check(&self) -> Vec<DependencyHealth> {
+        self.0
+            .iter()
+            .flat_map(|dispatcher| dispatcher.check_processes())
+            .collect()
+    }
+}
+
 impl MapService {
+    /// Vector layers of registered QGIS projects by WMS service id
+    pub fn project_layers(&self) -> Vec<(String, Vec<ProjectLayer>)> {
+        self.inventory
+            .wms_services
+            .iter()
+            .filter(|wms| wms.cap_type == CapType::Qgis)
+            .filter_map(|wms| match read_project_layers(&wms.project_file) {
+                Ok(layers) => {
+                    info!(
+                        "Found {} vector layer(s) in QGIS project '{}'",
+                        layers.len(),
+                        wms.project_file.display()
+                    );
+                    Some((wms.id.clone(), layers))
+                }
+                Err(e) => {
+                    warn!(
+                        "Failed to read QGIS project '{}': {e}",
+                        wms.project_file.display()
+                    );
+                    None
+                }
+            })
+            .collect()
+    }
     #[allow(dead_code)]
     pub fn fcgi_dispatcher(&self, suffix: &str) -> Option<&FcgiDispatcher> {
         self.suffix_fcgi
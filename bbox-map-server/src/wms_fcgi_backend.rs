@@ -255,6 +255,7 @@ pub fn detect_backends(
                                     id,
                                     wms_path,
                                     cap_type,
+                                    project_file: p.clone(),
                                 }
                             }),
                     );
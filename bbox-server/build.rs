@@ -0,0 +1,6 @@
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // Requires `protoc` (https://grpc.io/docs/protoc-installation/)
+    #[cfg(feature = "grpc")]
+    tonic_build::compile_protos("proto/admin.proto")?;
+    Ok(())
+}
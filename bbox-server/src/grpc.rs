@@ -0,0 +1,313 @@
+//! gRPC administration and introspection API.
+
+use bbox_core::config::from_config_opt_or_exit;
+use bbox_core::health::{HealthChecks, HealthStatus};
+use bbox_core::jobs::{JobInfo, JobQueue};
+use bbox_feature_server::FeatureService;
+use bbox_tile_server::cli::InvalidateArgs;
+use bbox_tile_server::metrics::tile_metrics;
+use bbox_tile_server::{SeedParams, TileService};
+use log::{info, warn};
+use serde::Deserialize;
+use serde_json::json;
+use std::net::SocketAddr;
+use tonic::{transport::Server, Request, Response, Status};
+
+pub mod proto {
+    tonic::include_proto!("bbox.admin.v1");
+}
+
+use proto::admin_server::{Admin, AdminServer};
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct GrpcCfg {
+    /// IP address and port of the gRPC server (e.g. 127.0.0.1:50051)
+    pub server_addr: String,
+    /// Bearer token required in the `authorization` metadata.
+    /// Mandatory unless the server is bound to a loopback address.
+    pub token: Option<String>,
+}
+
+impl GrpcCfg {
+    pub fn from_config() -> Option<Self> {
+        from_config_opt_or_exit("grpc")
+    }
+}
+
+#[derive(Clone)]
+pub struct AdminService {
+    feature_service: FeatureService,
+    tile_service: TileService,
+    jobs: JobQueue,
+    health: HealthChecks,
+}
+
+impl AdminService {
+    pub fn new(
+        feature_service: FeatureService,
+        tile_service: TileService,
+        jobs: JobQueue,
+        health: HealthChecks,
+    ) -> Self {
+        AdminService {
+            feature_service,
+            tile_service,
+            jobs,
+            health,
+        }
+    }
+
+    /// Start gRPC server in the background
+    pub fn spawn(self, cfg: &GrpcCfg) {
+        let addr: SocketAddr = match cfg.server_addr.parse() {
+            Ok(addr) => addr,
+            Err(e) => {
+                warn!("Invalid gRPC server address `{}`: {e}", cfg.server_addr);
+                return;
+            }
+        };
+        if cfg.token.is_none() && !addr.ip().is_loopback() {
+            warn!(
+                "gRPC server at {addr} not started: a token is required for non-loopback addresses"
+            );
+            return;
+        }
+        let token = cfg.token.as_ref().map(|token| format!("Bearer {token}"));
+        let service = AdminServer::with_interceptor(self, move |req: Request<()>| {
+            check_token(&req, token.as_deref())?;
+            Ok(req)
+        });
+        info!("Starting gRPC server at {addr}");
+        actix_web::rt::spawn(async move {
+            if let Err(e) = Server::builder().add_service(service).serve(addr).await {
+                warn!("gRPC server failed: {e}");
+            }
+        });
+    }
+}
+
+fn check_token<T>(req: &Request<T>, token: Option<&str>) -> Result<(), Status> {
+    let Some(token) = token else {
+        return Ok(());
+    };
+    match req.metadata().get("authorization") {
+        Some(value) if constant_time_eq(value.as_bytes(), token.as_bytes()) => Ok(()),
+        _ => Err(Status::unauthenticated("Invalid or missing token")),
+    }
+}
+
+/// Compare secrets without exiting early on the first differing byte
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+fn zoom_level(level: Option<u32>) -> Result<Option<u8>, Status> {
+    level
+        .map(|z| u8::try_from(z).map_err(|_| Status::invalid_argument("Invalid zoom level")))
+        .transpose()
+}
+
+impl From<JobInfo> for proto::Job {
+    fn from(job: JobInfo) -> Self {
+        let status = json!(job.status).as_str().unwrap_or_default().to_string();
+        proto::Job {
+            job_id: job.job_id,
+            kind: job.kind,
+            status,
+            message: job.message,
+            progress: job.progress,
+            result: job.result.map(|result| result.to_string()),
+        }
+    }
+}
+
+#[tonic::async_trait]
+impl Admin for AdminService {
+    async fn list_collections(
+        &self,
+        _request: Request<proto::ListCollectionsRequest>,
+    ) -> Result<Response<proto::ListCollectionsResponse>, Status> {
+        let mut collections: Vec<_> = self
+            .feature_service
+            .inventory
+            .current()
+            .collections()
+            .into_iter()
+            .map(|collection| proto::Collection {
+                bbox: collection
+                    .extent
+                    .and_then(|extent| extent.spatial)
+                    .and_then(|spatial| spatial.bbox.into_iter().next())
+                    .unwrap_or_default(),
+                id: collection.id,
+                title: collection.title,
+                description: collection.description,
+            })
+            .collect();
+        collections.sort_by(|a, b| a.id.cmp(&b.id));
+        Ok(Response::new(proto::ListCollectionsResponse {
+            collections,
+        }))
+    }
+
+    async fn list_tilesets(
+        &self,
+        _request: Request<proto::ListTilesetsRequest>,
+    ) -> Result<Response<proto::ListTilesetsResponse>, Status> {
+        let mut tilesets: Vec<_> = self
+            .tile_service
+            .tilesets()
+            .iter()
+            .map(|(name, ts)| proto::Tileset {
+                name: name.clone(),
+                tms: ts.tms.clone(),
+                format: ts.tile_format().file_suffix().to_string(),
+                cached: ts.cache_config().is_some(),
+            })
+            .collect();
+        tilesets.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(Response::new(proto::ListTilesetsResponse { tilesets }))
+    }
+
+    async fn get_tileset_stats(
+        &self,
+        request: Request<proto::GetTilesetStatsRequest>,
+    ) -> Result<Response<proto::TilesetStats>, Status> {
+        let tileset = request.into_inner().tileset;
+        if self.tile_service.tileset(&tileset).is_none() {
+            return Err(Status::not_found(format!("Tileset `{tileset}` not found")));
+        }
+        let stats = tile_metrics().tileset_stats(&tileset);
+        Ok(Response::new(proto::TilesetStats {
+            tileset,
+            cache_hits: stats.cache_hits,
+            cache_misses: stats.cache_misses,
+            tiles_rendered: stats.tiles_rendered,
+            render_seconds: stats.render_seconds,
+            rendered_bytes: stats.rendered_bytes,
+        }))
+    }
+
+    async fn invalidate_cache(
+        &self,
+        request: Request<proto::InvalidateCacheRequest>,
+    ) -> Result<Response<proto::InvalidateCacheResponse>, Status> {
+        let req = request.into_inner();
+        let Some(ts) = self.tile_service.tileset(&req.tileset) else {
+            return Err(Status::not_found(format!(
+                "Tileset `{}` not found",
+                req.tileset
+            )));
+        };
+        if ts.cache_config().is_none() {
+            return Err(Status::failed_precondition(
+                "Tileset has no cache configuration",
+            ));
+        }
+        let args = InvalidateArgs {
+            tileset: req.tileset,
+            minzoom: zoom_level(req.minzoom)?,
+            maxzoom: zoom_level(req.maxzoom)?,
+            extent: req.extent,
+            geometry: None,
+            tile_path: None,
+            s3_path: None,
+        };
+        self.tile_service
+            .invalidate(&args)
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?;
+        Ok(Response::new(proto::InvalidateCacheResponse {}))
+    }
+
+    async fn seed_tileset(
+        &self,
+        request: Request<proto::SeedTilesetRequest>,
+    ) -> Result<Response<proto::Job>, Status> {
+        let req = request.into_inner();
+        let Some(ts) = self.tile_service.tileset(&req.tileset) else {
+            return Err(Status::not_found(format!(
+                "Tileset `{}` not found",
+                req.tileset
+            )));
+        };
+        if ts.cache_config().is_none() {
+            return Err(Status::failed_precondition(
+                "Tileset has no cache configuration",
+            ));
+        }
+        let params = SeedParams {
+            bbox: req.extent,
+            minzoom: zoom_level(req.minzoom)?,
+            maxzoom: zoom_level(req.maxzoom)?,
+            overwrite: req.overwrite,
+        };
+        let args = params
+            .seed_args(&req.tileset)
+            .map_err(Status::invalid_argument)?;
+        let service = self.tile_service.clone();
+        let job = self.jobs.submit("seed", move |handle| async move {
+            let tiles = service.seed_by_grid(&args, Some(handle)).await?;
+            Ok::<_, anyhow::Error>(json!({ "tileset": args.tileset, "tiles": tiles }))
+        });
+        Ok(Response::new(job.into()))
+    }
+
+    async fn get_job(
+        &self,
+        request: Request<proto::GetJobRequest>,
+    ) -> Result<Response<proto::Job>, Status> {
+        let job_id = request.into_inner().job_id;
+        match self.jobs.job(&job_id) {
+            Some(job) => Ok(Response::new(job.into())),
+            None => Err(Status::not_found(format!("Job `{job_id}` not found"))),
+        }
+    }
+
+    async fn health(
+        &self,
+        _request: Request<proto::HealthRequest>,
+    ) -> Result<Response<proto::HealthResponse>, Status> {
+        let report = self.health.readiness().await;
+        Ok(Response::new(proto::HealthResponse {
+            up: report.status == HealthStatus::Up,
+            checks: report
+                .checks
+                .into_iter()
+                .map(|check| proto::DependencyHealth {
+                    kind: check.kind,
+                    name: check.name,
+                    up: check.status == HealthStatus::Up,
+                    error: check.error,
+                })
+                .collect(),
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn token_check() {
+        let mut req = Request::new(());
+        assert!(check_token(&req, None).is_ok());
+        assert!(check_token(&req, Some("Bearer secret")).is_err());
+        req.metadata_mut()
+            .insert("authorization", "Bearer secret".parse().unwrap());
+        assert!(check_token(&req, Some("Bearer secret")).is_ok());
+        assert!(check_token(&req, Some("Bearer other")).is_err());
+        assert!(check_token(&req, Some("Bearer secret2")).is_err());
+        assert!(constant_time_eq(b"", b""));
+        assert!(!constant_time_eq(b"secret", b"secreT"));
+    }
+
+    #[test]
+    fn zoom_levels() {
+        assert_eq!(zoom_level(Some(14)).unwrap(), Some(14));
+        assert_eq!(zoom_level(None).unwrap(), None);
+        assert!(zoom_level(Some(300)).is_err());
+    }
+}
@@ -1,10 +1,18 @@
-use actix_web::{middleware, middleware::Condition, App, HttpServer};
+use actix_web::{middleware, middleware::Condition, web, App, HttpServer};
+use bbox_core::access_log::AccessLog;
 use bbox_core::cli::CliArgs;
 use bbox_core::config::CoreServiceCfg;
+use bbox_core::config_check::{
+    check_config_requested, check_service_config, exit_with_report, ConfigReport,
+};
 use bbox_core::service::{CoreService, OgcApiService, ServiceConfig, ServiceEndpoints};
+use bbox_core::tenant::Tenant;
 use log::info;
 use std::path::Path;
 
+#[cfg(feature = "grpc")]
+mod grpc;
+
 #[cfg(feature = "asset-server")]
 use bbox_asset_server::{config::AssetServiceCfg, AssetService};
 #[cfg(feature = "feature-server")]
@@ -45,6 +53,17 @@ async fn run_service() -> std::io::Result<()> {
     let matches = cli.cli_matches();
 
     let core_cfg = CoreServiceCfg::initialize(&matches).unwrap();
+    if check_config_requested(&matches) {
+        let mut report = ConfigReport::default();
+        CoreService::check_config(&core_cfg, &mut report).await;
+        check_service_config::<MapService>(&matches, &mut report).await;
+        check_service_config::<TileService>(&matches, &mut report).await;
+        check_service_config::<AssetService>(&matches, &mut report).await;
+        check_service_config::<FeatureService>(&matches, &mut report).await;
+        check_service_config::<ProcessesService>(&matches, &mut report).await;
+        check_service_config::<RoutingService>(&matches, &mut report).await;
+        exit_with_report(&report);
+    }
     let mut core = CoreService::create(&core_cfg, &core_cfg).await;
 
     let cfg = MapServiceCfg::initialize(&matches).unwrap();
@@ -62,6 +81,8 @@ async fn run_service() -> std::io::Result<()> {
 
     let cfg = FeatureServiceCfg::initialize(&matches).unwrap();
     let feature_service = FeatureService::create(&cfg, &core_cfg).await;
+    #[cfg(all(feature = "feature-server", feature = "map-server"))]
+    feature_service.set_map_service(&map_service, &cfg).await;
     core.add_service(&feature_service);
 
     let cfg = ProcessesServiceCfg::initialize(&matches).unwrap();
@@ -94,6 +115,35 @@ async fn run_service() -> std::io::Result<()> {
         return Ok(());
     }
 
+    #[cfg(feature = "grpc")]
+    if let Some(grpc_cfg) = grpc::GrpcCfg::from_config() {
+        grpc::AdminService::new(
+            feature_service.clone(),
+            tile_service.clone(),
+            core.jobs().clone(),
+            core.health_checks().clone(),
+        )
+        .spawn(&grpc_cfg);
+    }
+
+    // Tenants with own feature collections and tilesets
+    let tenant_matches = cli.matches_without_args();
+    let mut tenants = Vec::new();
+    for tenant_cfg in &core_cfg.tenants {
+        let tenant = Tenant::from_config(tenant_cfg).await;
+        let cfg = tenant
+            .service_config::<TileServiceCfg>(&tenant_matches)
+            .unwrap();
+        let tile_service = tenant.scope(TileService::create(&cfg, &core_cfg)).await;
+        let cfg = tenant
+            .service_config::<FeatureServiceCfg>(&tenant_matches)
+            .unwrap();
+        let feature_service = tenant
+            .scope(FeatureService::create(&cfg, &core_cfg))
+            .await;
+        tenants.push((tenant, tile_service, feature_service));
+    }
+
     #[cfg(feature = "map-server")]
     let project = map_service.default_project.clone();
     #[cfg(not(feature = "map-server"))]
@@ -102,13 +152,30 @@ async fn run_service() -> std::io::Result<()> {
     let server_addr = core.server_addr().to_string();
     let tls_config = core.tls_config();
     let mut server = HttpServer::new(move || {
-        #[allow(unused_mut)]
         let mut app = App::new()
+            .wrap(Condition::new(
+                core.has_cache_control(),
+                core.cache_control(),
+            ))
+            // Always active for protecting admin endpoints
+            .wrap(core.access_control())
+            .wrap(Condition::new(core.has_api_keys(), core.api_key_auth()))
             .wrap(Condition::new(core.has_cors(), core.cors()))
             .wrap(Condition::new(core.has_metrics(), core.middleware()))
             .wrap(Condition::new(core.has_metrics(), core.metrics().clone()))
-            .wrap(middleware::Logger::default())
-            .wrap(middleware::Compress::default())
+            .wrap(AccessLog)
+            .wrap(middleware::Compress::default());
+
+        for (tenant, tenant_tiles, tenant_features) in &tenants {
+            app = app.service(
+                web::scope(&tenant.path_prefix())
+                    .wrap(tenant.access_control())
+                    .configure(|cfg| tenant_tiles.register_endpoints(cfg))
+                    .configure(|cfg| tenant_features.register_endpoints(cfg)),
+            );
+        }
+
+        app = app
             .configure(|cfg| core.register_endpoints(cfg))
             .configure(bbox_core::static_assets::register_endpoints)
             .configure(|cfg| map_service.register_endpoints(cfg))
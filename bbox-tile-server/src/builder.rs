@@ -0,0 +1,116 @@
+//! Programmatic setup of a tile service, e.g. for embedding into other actix applications.
+//! Endpoints are added with `App::new().configure(|cfg| service.register_endpoints(cfg))`.
+
+use crate::config::{
+    GridCfg, SourceParamCfg, TileCacheProviderCfg, TileDirectoryCfg, TileServiceCfg, TileSetCfg,
+    TileStoreCfg,
+};
+use crate::service::{ServiceError, TileService};
+use bbox_core::config::{CoreServiceCfg, DatasourceCfg, DsPostgisCfg, NamedDatasourceCfg};
+use bbox_core::config_check::ConfigReport;
+use bbox_core::service::OgcApiService;
+
+/// Tile service configured without configuration files
+#[derive(Default)]
+pub struct TileServiceBuilder {
+    config: TileServiceCfg,
+}
+
+impl TileServiceBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Start with a deserialized or otherwise prepared configuration
+    pub fn from_config(config: TileServiceCfg) -> Self {
+        TileServiceBuilder { config }
+    }
+    pub fn add_grid(mut self, grid: GridCfg) -> Self {
+        self.config.grids.push(grid);
+        self
+    }
+    pub fn add_datasource(mut self, name: &str, datasource: DatasourceCfg) -> Self {
+        self.config.datasources.push(NamedDatasourceCfg {
+            name: name.to_string(),
+            datasource,
+        });
+        self
+    }
+    pub fn add_postgis_datasource(self, name: &str, url: &str) -> Self {
+        let cfg = DsPostgisCfg {
+            url: url.to_string(),
+            ..Default::default()
+        };
+        self.add_datasource(name, DatasourceCfg::Postgis(cfg))
+    }
+    pub fn add_tilestore(mut self, name: &str, cache: TileStoreCfg) -> Self {
+        self.config.tilestores.push(TileCacheProviderCfg {
+            name: name.to_string(),
+            compression: None,
+            cache,
+        });
+        self
+    }
+    pub fn add_tileset(mut self, tileset: TileSetCfg) -> Self {
+        self.config.tilesets.push(tileset);
+        self
+    }
+    /// Add tileset with source and optional cache name
+    pub fn add_source(self, name: &str, source: SourceParamCfg, cache: Option<&str>) -> Self {
+        let mut tileset = TileSetCfg::new(name, source);
+        tileset.cache = cache.map(str::to_string);
+        self.add_tileset(tileset)
+    }
+    /// Publish MBTiles or PMTiles archive, named after the file
+    pub fn add_archive(self, file_or_url: &str) -> Result<Self, ServiceError> {
+        let tileset = TileSetCfg::from_archive(file_or_url)
+            .ok_or_else(|| ServiceError::UnknownFormat(file_or_url.to_string()))?;
+        Ok(self.add_tileset(tileset))
+    }
+    /// Publish MBTiles and PMTiles archives found in directory
+    pub fn add_directory(mut self, dir: &str) -> Self {
+        self.config.directories.push(TileDirectoryCfg {
+            dir: dir.to_string(),
+            watch: false,
+            debounce: None,
+        });
+        self
+    }
+    /// Validate configuration like `serve --check-config` and create service.
+    /// Configuration errors are returned instead of terminating the process.
+    pub async fn build(self) -> Result<TileService, ServiceError> {
+        let mut report = ConfigReport::default();
+        TileService::check_config(&self.config, &mut report).await;
+        if report.has_errors() {
+            return Err(ServiceError::InvalidConfig(report.to_text()));
+        }
+        Ok(TileService::create(&self.config, &CoreServiceCfg::default()).await)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn mbtiles_service() {
+        let service = TileServiceBuilder::new()
+            .add_archive("../assets/liechtenstein.mbtiles")
+            .unwrap()
+            .build()
+            .await
+            .unwrap();
+        assert!(service.tileset("liechtenstein").is_some());
+
+        let result = TileServiceBuilder::new()
+            .add_source(
+                "missing_cache",
+                TileSetCfg::from_archive("../assets/liechtenstein.mbtiles")
+                    .unwrap()
+                    .source,
+                Some("tilecache"),
+            )
+            .build()
+            .await;
+        assert!(matches!(result, Err(ServiceError::InvalidConfig(_))));
+    }
+}
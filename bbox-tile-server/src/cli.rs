@@ -29,6 +29,15 @@ pub enum Commands {
     /// Upload tiles
     #[command(arg_required_else_help = true)]
     Upload(UploadArgs),
+    /// Delete cached tiles
+    #[command(arg_required_else_help = true)]
+    Invalidate(InvalidateArgs),
+    /// Delete or regenerate tiles of an osm2pgsql or imposm expire list
+    #[command(arg_required_else_help = true)]
+    Expire(ExpireArgs),
+    /// Report statistics and missing tiles of a tile cache
+    #[command(arg_required_else_help = true)]
+    Stats(StatsArgs),
 }
 
 #[derive(Debug, Args)]
@@ -45,6 +54,18 @@ pub struct SeedArgs {
     /// Extent minx,miny,maxx,maxy (in grid reference system)
     #[arg(long)]
     pub extent: Option<String>,
+    /// Seed only tiles intersecting a polygon from a GeoJSON file, GeoJSON string or WKT string (in grid reference system)
+    #[arg(long, conflicts_with = "extent")]
+    pub geometry: Option<String>,
+    /// Seed only tiles affected by changes from an osm2pgsql expire list, a GeoJSON or WKT file (in grid reference system)
+    #[arg(long, conflicts_with_all = ["extent", "geometry"])]
+    pub changes: Option<String>,
+    /// Seed only tiles affected by changed geometries in a table of the tileset datasource
+    #[arg(long, conflicts_with_all = ["extent", "geometry", "changes"])]
+    pub changes_table: Option<String>,
+    /// Geometry column of changes table
+    #[arg(long, default_value = "geom", requires = "changes_table")]
+    pub changes_geometry_field: String,
     /// Base directory for file store
     #[arg(long, group = "store")]
     pub tile_path: Option<String>,
@@ -60,19 +81,115 @@ pub struct SeedArgs {
     /// No tile store (for read benchmarks)
     #[arg(long, group = "store")]
     pub no_store: bool,
-    /// Number of threads to use, defaults to number of logical cores
-    #[arg(short, long)]
-    pub threads: Option<usize>,
-    /// Size of tasks queue for parallel processing
+    /// Output path of tile store (directory, S3 path, MBTiles or PMTiles file)
+    #[arg(long, group = "store")]
+    pub output: Option<String>,
+    /// Store format of output (Default: detected from output path)
+    #[arg(long, value_enum, requires = "output")]
+    pub format: Option<StoreFormat>,
+    /// Number of tiles rendered and stored concurrently, defaults to number of logical cores
+    #[arg(short = 't', long, alias = "threads")]
+    pub concurrency: Option<usize>,
+    /// Number of concurrent S3 uploads (Default: 256)
     #[arg(long)]
     pub tasks: Option<usize>,
-    /// Overwrite previously cached tiles
+    /// Maximum number of tiles stored per second (e.g. to respect S3 request rate limits)
+    #[arg(long)]
+    pub rate_limit: Option<f64>,
+    /// Overwrite previously cached tiles (Default: true)
     #[arg(long)]
     pub overwrite: Option<bool>,
+    /// Continue an interrupted seeding run from its checkpoint
+    #[arg(long)]
+    pub resume: bool,
+    /// Checkpoint file for resuming (Default: seed-<tileset>.json)
+    #[arg(long)]
+    pub checkpoint: Option<std::path::PathBuf>,
     /// Read tiles from file or URL
     pub file_or_url: Option<String>,
 }
 
+#[derive(Debug, Args)]
+pub struct InvalidateArgs {
+    /// tile set name
+    #[arg(long)]
+    pub tileset: String,
+    /// Minimum zoom level
+    #[arg(long)]
+    pub minzoom: Option<u8>,
+    /// Maximum zoom level
+    #[arg(long)]
+    pub maxzoom: Option<u8>,
+    /// Extent minx,miny,maxx,maxy (in grid reference system)
+    #[arg(long)]
+    pub extent: Option<String>,
+    /// Delete only tiles intersecting a polygon from a GeoJSON file, GeoJSON string or WKT string (in grid reference system)
+    #[arg(long, conflicts_with = "extent")]
+    pub geometry: Option<String>,
+    /// Base directory for file store
+    #[arg(long, group = "store")]
+    pub tile_path: Option<String>,
+    /// S3 path of tile store (e.g. s3://tiles)
+    #[arg(long, group = "store")]
+    pub s3_path: Option<String>,
+}
+
+#[derive(Debug, Args)]
+pub struct ExpireArgs {
+    /// tile set name
+    #[arg(long)]
+    pub tileset: String,
+    /// osm2pgsql expire list or imposm expire directory
+    #[arg(long)]
+    pub file: std::path::PathBuf,
+    /// Minimum zoom level (Default: lowest level of expire list)
+    #[arg(long)]
+    pub minzoom: Option<u8>,
+    /// Maximum zoom level (Default: highest level of expire list)
+    #[arg(long)]
+    pub maxzoom: Option<u8>,
+    /// Render expired tiles again instead of deleting them only
+    #[arg(long)]
+    pub regenerate: bool,
+    /// Number of tiles rendered concurrently, defaults to number of logical cores
+    #[arg(short = 't', long)]
+    pub concurrency: Option<usize>,
+}
+
+#[derive(Debug, Args)]
+pub struct StatsArgs {
+    /// tile set name
+    #[arg(long)]
+    pub tileset: String,
+    /// Minimum zoom level
+    #[arg(long)]
+    pub minzoom: Option<u8>,
+    /// Maximum zoom level
+    #[arg(long)]
+    pub maxzoom: u8,
+    /// Extent of expected tiles minx,miny,maxx,maxy (in grid reference system)
+    #[arg(long)]
+    pub extent: Option<String>,
+    /// Expect only tiles intersecting a polygon from a GeoJSON file, GeoJSON string or WKT string (in grid reference system)
+    #[arg(long, conflicts_with = "extent")]
+    pub geometry: Option<String>,
+    /// Base directory for file store
+    #[arg(long, group = "store")]
+    pub tile_path: Option<String>,
+    /// S3 path of tile store (e.g. s3://tiles)
+    #[arg(long, group = "store")]
+    pub s3_path: Option<String>,
+    /// MBTiles path of tile store
+    #[arg(long, group = "store")]
+    pub mb_path: Option<String>,
+    /// Number of concurrent tile lookups for stores without tile listing
+    #[arg(short = 't', long)]
+    pub concurrency: Option<usize>,
+    /// Output report as JSON
+    #[arg(long)]
+    pub json: bool,
+}
+
 #[derive(Debug, Args)]
 pub struct UploadArgs {
     /// Base directory of input files
@@ -92,6 +209,29 @@ pub struct UploadArgs {
     pub tasks: Option<usize>,
 }
 
+#[derive(clap::ValueEnum, Clone, Copy, PartialEq, Debug)]
+pub enum StoreFormat {
+    Files,
+    S3,
+    Mbtiles,
+    Pmtiles,
+}
+
+impl StoreFormat {
+    /// Store format detected from output path
+    pub fn from_path(path: &str) -> Self {
+        if path.starts_with("s3://") {
+            StoreFormat::S3
+        } else if path.ends_with(".mbtiles") {
+            StoreFormat::Mbtiles
+        } else if path.ends_with(".pmtiles") {
+            StoreFormat::Pmtiles
+        } else {
+            StoreFormat::Files
+        }
+    }
+}
+
 #[derive(clap::ValueEnum, Clone, Debug)]
 pub enum Mode {
     Sequential,
@@ -0,0 +1,97 @@
+//! Coalescing of concurrent requests for the same tile.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use tokio::sync::OnceCell;
+
+/// Single-flight execution: concurrent calls with the same key wait for one running
+/// computation and share its result.
+///
+/// Errors are not shared. If the running computation fails or its request is cancelled,
+/// the next waiter runs its own computation.
+pub struct RequestCoalescer<T> {
+    in_flight: Mutex<HashMap<String, Arc<OnceCell<T>>>>,
+}
+
+impl<T: Clone> RequestCoalescer<T> {
+    pub fn new() -> Self {
+        RequestCoalescer {
+            in_flight: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Run `f` unless a computation for `key` is already in progress
+    pub async fn run<E, F, Fut>(&self, key: String, f: F) -> Result<T, E>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+    {
+        let cell = self
+            .in_flight
+            .lock()
+            .unwrap()
+            .entry(key.clone())
+            .or_default()
+            .clone();
+        let result = cell.get_or_try_init(f).await.cloned();
+        let mut in_flight = self.in_flight.lock().unwrap();
+        if matches!(in_flight.get(&key), Some(current) if Arc::ptr_eq(current, &cell)) {
+            // Later requests start a new computation, e.g. after a cache update
+            in_flight.remove(&key);
+        }
+        result
+    }
+
+    /// Number of computations in progress
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.lock().unwrap().len()
+    }
+}
+
+impl<T: Clone> Default for RequestCoalescer<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn single_flight() {
+        let coalescer = Arc::new(RequestCoalescer::<u32>::new());
+        let calls = Arc::new(AtomicUsize::new(0));
+        let tasks = (0..10).map(|_| {
+            let coalescer = coalescer.clone();
+            let calls = calls.clone();
+            tokio::spawn(async move {
+                coalescer
+                    .run("ts/0/0/0.pbf".to_string(), || async {
+                        calls.fetch_add(1, Ordering::SeqCst);
+                        tokio::time::sleep(Duration::from_millis(50)).await;
+                        Ok::<_, String>(42)
+                    })
+                    .await
+            })
+        });
+        for result in futures::future::join_all(tasks).await {
+            assert_eq!(result.unwrap(), Ok(42));
+        }
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        assert_eq!(coalescer.in_flight(), 0);
+
+        // Failed computations are retried by the next request
+        let result = coalescer
+            .run("ts/0/0/0.pbf".to_string(), || async { Err("failed") })
+            .await;
+        assert_eq!(result, Err("failed"));
+        let result = coalescer
+            .run("ts/0/0/0.pbf".to_string(), || async { Ok::<_, &str>(1) })
+            .await;
+        assert_eq!(result, Ok(1));
+    }
+}
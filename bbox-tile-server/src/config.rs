@@ -1,4 +1,4 @@
-use crate::cli::Commands;
+use crate::cli::{Commands, StoreFormat};
 use crate::config_t_rex as t_rex;
 use crate::datasource::source_config_from_cli_arg;
 use bbox_core::cli::CommonCommands;
@@ -11,10 +11,13 @@ use clap::{ArgMatches, FromArgMatches};
 use log::{info, warn};
 use regex::Regex;
 use serde::{Deserialize, Serialize};
+use serde_json::json;
 use std::collections::{HashMap, HashSet};
 use std::convert::From;
 use std::num::NonZeroU16;
 use std::path::{Path, PathBuf};
+use tile_grid::TileMatrixSet;
+use tilejson::TileJSON;
 
 #[derive(Deserialize, Serialize, Default, Debug)]
 #[serde(default)]
@@ -29,6 +32,21 @@ pub struct TileServiceCfg {
     pub tilesets: Vec<TileSetCfg>,
     #[serde(rename = "tilestore")]
     pub tilestores: Vec<TileCacheProviderCfg>,
+    /// Directories with MBTiles and PMTiles archives published as tilesets
+    #[serde(rename = "directory")]
+    pub directories: Vec<TileDirectoryCfg>,
+}
+
+/// Directory with tile archives
+#[derive(Deserialize, Serialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct TileDirectoryCfg {
+    pub dir: String,
+    /// Register and deregister tilesets when archives are added or removed
+    #[serde(default)]
+    pub watch: bool,
+    /// Wait time in seconds after the last detected change (Default: 2)
+    pub debounce: Option<u64>,
 }
 
 /// Tileset configuration
@@ -41,6 +59,10 @@ pub struct TileSetCfg {
     // pub format: Option<TileFormatCfg>,
     /// Tile matrix set identifier (Default: `WebMercatorQuad`)
     pub tms: Option<String>,
+    /// Acknowledgment of ownership, authorship or copyright. Overrides the attribution of the tile source.
+    pub attribution: Option<String>,
+    /// License of tileset data, e.g. SPDX identifier or URL
+    pub license: Option<String>,
     /// Tile source
     #[serde(flatten)]
     pub source: SourceParamCfg,
@@ -50,14 +72,129 @@ pub struct TileSetCfg {
     pub cache_format: Option<String>,
     /// Optional limits of zoom levels which should be cached. Tiles in other zoom levels are served from live data.
     pub cache_limits: Option<CacheLimitCfg>,
+    /// Optional expiration of cached tiles and HTTP cache control
+    pub cache_control: Option<CacheControlCfg>,
+    /// In-memory cache for frequently requested tiles
+    pub memory_cache: Option<MemoryCacheCfg>,
+    /// Output formats of raster tiles, converted after rendering
+    pub encoding: Option<RasterEncodingCfg>,
+    /// Processing of rendered tiles before caching
+    #[serde(default, rename = "postprocess", skip_serializing_if = "Vec::is_empty")]
+    pub post_processors: Vec<PostProcessCfg>,
+}
+
+/// Tile post-processor
+#[derive(Deserialize, Serialize, Clone, Debug)]
+#[serde(untagged)]
+pub enum PostProcessCfg {
+    /// Processor registered by application
+    #[serde(deny_unknown_fields)]
+    Registered { processor: String },
+    /// WebAssembly module
+    #[serde(deny_unknown_fields)]
+    Wasm {
+        /// Module file path
+        wasm: PathBuf,
+    },
 }
 
 /// Custom grid definition
 #[derive(Deserialize, Serialize, Debug)]
+#[serde(untagged)]
+pub enum GridCfg {
+    /// OGC TileMatrixSet JSON file
+    #[serde(deny_unknown_fields)]
+    Json {
+        /// Grid JSON file path
+        json: String,
+    },
+    /// Grid parameters
+    Custom(CustomGridCfg),
+}
+
+/// Custom grid parameters
+#[derive(Deserialize, Serialize, Clone, Debug)]
 #[serde(deny_unknown_fields)]
-pub struct GridCfg {
-    /// Grid JSON file path
-    pub json: String,
+pub struct CustomGridCfg {
+    /// Tile matrix set identifier
+    pub id: String,
+    pub title: Option<String>,
+    /// Spatial reference system (EPSG code)
+    pub srid: i32,
+    /// Grid units (Default: m)
+    #[serde(default)]
+    pub units: GridUnitsCfg,
+    /// Grid extent. The upper left corner (minx, maxy) is the origin of all tile matrices.
+    pub extent: ExtentCfg,
+    /// Resolutions in units per pixel, ordered from zoom level 0 (largest) to the highest zoom level
+    pub resolutions: Vec<f64>,
+    /// Width and height of tile (Default: 256)
+    pub tile_size: Option<NonZeroU16>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, Default, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum GridUnitsCfg {
+    /// Meters
+    #[default]
+    M,
+    /// Decimal degrees
+    Dd,
+    /// Feet
+    Ft,
+}
+
+impl GridUnitsCfg {
+    pub fn meters_per_unit(&self) -> f64 {
+        match self {
+            GridUnitsCfg::M => 1.0,
+            // Equatorial circumference / 360
+            GridUnitsCfg::Dd => 6378137.0 * 2.0 * std::f64::consts::PI / 360.0,
+            GridUnitsCfg::Ft => 0.3048,
+        }
+    }
+}
+
+impl CustomGridCfg {
+    /// OGC TileMatrixSet with one tile matrix per resolution
+    pub fn tile_matrix_set(&self) -> Result<TileMatrixSet, serde_json::Error> {
+        // Standardized rendering pixel size of 0.28mm
+        const PIXEL_SIZE: f64 = 0.00028;
+        let tile_size = self.tile_size.map(|s| s.get()).unwrap_or(256) as f64;
+        let ext = &self.extent;
+        let tile_matrices = self
+            .resolutions
+            .iter()
+            .enumerate()
+            .map(|(z, res)| {
+                let tile_extent = res * tile_size;
+                // Tolerate rounding errors of extents covered exactly
+                let matrix_size = |len: f64| ((len / tile_extent) - 1e-6).ceil().max(1.0) as u64;
+                json!({
+                    "id": z.to_string(),
+                    "scaleDenominator": res * self.units.meters_per_unit() / PIXEL_SIZE,
+                    "cellSize": res,
+                    "pointOfOrigin": [ext.minx, ext.maxy],
+                    "tileWidth": tile_size as u64,
+                    "tileHeight": tile_size as u64,
+                    "matrixWidth": matrix_size(ext.maxx - ext.minx),
+                    "matrixHeight": matrix_size(ext.maxy - ext.miny),
+                })
+            })
+            .collect::<Vec<_>>();
+        let mut tms = json!({
+            "id": self.id,
+            "crs": format!("http://www.opengis.net/def/crs/EPSG/0/{}", self.srid),
+            "tileMatrices": tile_matrices,
+        });
+        if let Some(title) = &self.title {
+            tms["title"] = json!(title);
+        }
+        if self.units != GridUnitsCfg::Dd {
+            tms["orderedAxes"] = json!(["E", "N"]);
+        }
+        serde_json::from_value(tms)
+    }
 }
 
 /// Tile sources
@@ -79,6 +216,25 @@ pub enum SourceParamCfg {
     /// Tiles from PMTile archive
     #[serde(rename = "pmtiles")]
     Pmtiles(PmtilesStoreCfg),
+    /// Raster tiles from GeoTIFF / Cloud Optimized GeoTIFF
+    #[serde(rename = "geotiff")]
+    Geotiff(GeotiffSourceParamsCfg),
+    /// Raster tiles reprojected from a tileset in another grid
+    #[serde(rename = "reproject")]
+    Reproject(ReprojectSourceParamsCfg),
+    /// Tiles from a registered tile source driver
+    #[serde(rename = "custom")]
+    Custom(CustomSourceParamsCfg),
+}
+
+/// Tile source of a driver registered by an application embedding bbox
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct CustomSourceParamsCfg {
+    /// Name of registered driver
+    pub driver: String,
+    /// Driver specific parameters
+    #[serde(flatten)]
+    pub params: serde_json::Map<String, serde_json::Value>,
 }
 
 /// Raster tiles from external WMS
@@ -87,7 +243,14 @@ pub enum SourceParamCfg {
 pub struct WmsHttpSourceParamsCfg {
     /// Name of `wms_proxy` datasource
     pub source: String,
+    /// Comma separated list of WMS layers
     pub layers: String,
+    /// Image format requested from the WMS (Default: format of datasource)
+    pub format: Option<String>,
+    /// Additional WMS params like transparent=true
+    pub params: Option<String>,
+    /// Width and height of tile. Defaults to grid tile size (usually 256x256)
+    pub tile_size: Option<NonZeroU16>,
 }
 
 /// Raster tiles from map service
@@ -104,6 +267,44 @@ pub struct WmsFcgiSourceParamsCfg {
     pub tile_size: Option<NonZeroU16>,
 }
 
+/// Raster tiles from GeoTIFF / Cloud Optimized GeoTIFF
+#[derive(Deserialize, Serialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct GeotiffSourceParamsCfg {
+    /// File path or HTTP(S) URL
+    pub path: String,
+    /// Value range scaled to 0-255 for non 8-bit data like elevations
+    pub value_range: Option<(f64, f64)>,
+    /// Transparent pixel value (Default: GDAL nodata tag)
+    pub nodata: Option<f64>,
+    /// Width and height of tile. Defaults to grid tile size (usually 256x256)
+    pub tile_size: Option<NonZeroU16>,
+}
+
+/// Raster tiles reprojected from a tileset in another grid
+#[derive(Deserialize, Serialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct ReprojectSourceParamsCfg {
+    /// Name of the raster tileset with source tiles
+    pub tileset: String,
+    /// Resampling method (Default: bilinear)
+    #[serde(default)]
+    pub resampling: ResamplingCfg,
+    /// Width and height of tile. Defaults to grid tile size (usually 256x256)
+    pub tile_size: Option<NonZeroU16>,
+}
+
+/// Resampling method of reprojected raster tiles
+#[derive(Deserialize, Serialize, Clone, Copy, Default, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum ResamplingCfg {
+    /// Nearest neighbour
+    Nearest,
+    /// Bilinear interpolation
+    #[default]
+    Bilinear,
+}
+
 /// PostGIS tile datasource
 #[derive(Deserialize, Serialize, Clone, Debug)]
 #[serde(deny_unknown_fields)]
@@ -131,11 +332,39 @@ pub struct PostgisSourceParamsCfg {
     pub postgis2: bool,
     /// Add diagnostics layer
     pub diagnostics: Option<TileDiagnosticsCfg>,
+    /// Request parameters for `!<name>!` variables in layer queries.
+    ///
+    /// If declared, only these parameters are accepted. Otherwise all variables are passed as text.
+    #[serde(default, rename = "query_param")]
+    pub query_params: Vec<QueryParamCfg>,
     /// Layer definitions
     #[serde(rename = "layer")]
     pub layers: Vec<VectorLayerCfg>,
 }
 
+/// Request parameter of layer queries
+#[derive(Deserialize, Serialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct QueryParamCfg {
+    /// Name of URL query parameter and query variable `!<name>!`
+    pub name: String,
+    /// Parameter type (Default: `text`)
+    #[serde(rename = "type", default)]
+    pub type_: QueryParamTypeCfg,
+    /// Value for requests without this parameter. Parameters without default are required.
+    pub default: Option<String>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, Default, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum QueryParamTypeCfg {
+    #[default]
+    Text,
+    Integer,
+    Number,
+    Boolean,
+}
+
 #[derive(Deserialize, Serialize, Clone, Debug)]
 #[serde(deny_unknown_fields)]
 pub struct ExtentCfg {
@@ -145,7 +374,7 @@ pub struct ExtentCfg {
     pub maxy: f64,
 }
 
-#[derive(Deserialize, Serialize, Clone, Debug)]
+#[derive(Deserialize, Serialize, Clone, Debug, Default)]
 #[serde(deny_unknown_fields)]
 pub struct TileDiagnosticsCfg {
     /// Maximal tile size (uncompressed)
@@ -171,6 +400,8 @@ pub struct VectorLayerCfg {
     pub no_transform: bool,
     /// Name of feature ID field
     pub fid_field: Option<String>,
+    /// Field with a unique feature value. Features with duplicate values within a tile are omitted.
+    pub dedupe_by: Option<String>,
     /// Select all fields from table (either table or `query` is required)
     pub table_name: Option<String>,
     /// Custom queries
@@ -182,10 +413,18 @@ pub struct VectorLayerCfg {
     pub maxzoom: Option<u8>,
     /// Maximal number of features to read for a single tile (Default: unlimited).
     pub query_limit: Option<u32>,
+    /// Maximal number of features of the layer in a tile (Default: unlimited).
+    pub max_features_per_tile: Option<u32>,
+    /// Maximal size of the encoded layer in a tile in bytes (Default: unlimited).
+    pub max_tile_bytes: Option<u64>,
+    /// Handling of tiles exceeding `max_features_per_tile` or `max_tile_bytes`.
+    #[serde(default)]
+    pub overflow: TileOverflowCfg,
     /// Width and height of the tile (Default: 4096. Grid default size is 256)
     #[serde(default = "default_tile_size")]
     pub tile_size: u32,
     /// Tile buffer size in pixels (None: no clipping)
+    #[serde(alias = "buffer")]
     pub buffer_size: Option<u32>,
     /// Simplify geometry (lines and polygons). (Default: false)
     ///
@@ -200,11 +439,85 @@ pub struct VectorLayerCfg {
     /// Remark: Clipping step does also fix invalid geometries.
     #[serde(default)]
     pub make_valid: bool,
+    /// Minimal area of polygons, e.g. `16*!pixel_width!*!pixel_width!`. Smaller polygons are omitted.
+    ///
+    /// Applied to PostGIS sources only.
+    pub min_area: Option<String>,
+    /// Handling of invalid source geometries before clipping and encoding. (Default: keep)
+    ///
+    /// Applied to PostGIS sources only.
+    #[serde(default)]
+    pub invalid_geometries: InvalidGeometriesCfg,
+    /// Minimal zoom level of attributes (field name -> zoom level).
+    ///
+    /// Attributes are omitted in tiles with lower zoom levels.
+    #[serde(default)]
+    pub attributes_minzoom: HashMap<String, u8>,
+    /// Attribute lists for zoom ranges.
+    ///
+    /// The list with the highest `minzoom` up to the tile zoom level applies.
+    /// Attributes are not limited in tiles below the lowest `minzoom`.
+    #[serde(default)]
+    pub attributes: Vec<ZoomAttributesCfg>,
+    /// Add a point layer `<name>_label` with a label position for each polygon. (Default: false)
+    ///
+    /// Label points have the same attributes as the polygons. Applied to PostGIS sources only.
+    #[serde(default)]
+    pub label_points: bool,
+    /// Placement of label points
+    #[serde(default)]
+    pub label_position: LabelPositionCfg,
     /// Apply ST_Shift_Longitude to (transformed) bbox. (Default: false)
     #[serde(default)]
     pub shift_longitude: bool,
 }
 
+/// Attributes included in tiles from a zoom level on
+#[derive(Deserialize, Serialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct ZoomAttributesCfg {
+    pub minzoom: u8,
+    /// Included attribute fields
+    pub fields: Vec<String>,
+}
+
+/// Handling of invalid geometries
+#[derive(Deserialize, Serialize, Clone, Copy, Default, PartialEq, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub enum InvalidGeometriesCfg {
+    /// Encode geometries without validity check
+    #[default]
+    Keep,
+    /// Repair invalid geometries with `ST_MakeValid`
+    Repair,
+    /// Omit features with invalid geometries
+    Drop,
+}
+
+/// Placement of polygon label points
+#[derive(Deserialize, Serialize, Clone, Copy, Default, PartialEq, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub enum LabelPositionCfg {
+    /// Point guaranteed to lie on the polygon (`ST_PointOnSurface`)
+    #[default]
+    PointOnSurface,
+    /// Center of the maximum inscribed circle (`ST_MaximumInscribedCircle`, PostGIS 3.1+)
+    PoleOfInaccessibility,
+}
+
+/// Handling of layers exceeding the tile limits
+#[derive(Deserialize, Serialize, Clone, Copy, Default, PartialEq, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub enum TileOverflowCfg {
+    /// Omit the smallest features (polygons with the smallest area, shortest lines)
+    #[default]
+    DropSmallest,
+    /// Return an empty response (HTTP status 204)
+    NoContent,
+    /// Return a tile with diagnostics layers only
+    Diagnostics,
+}
+
 fn default_tile_size() -> u32 {
     4096
 }
@@ -227,6 +540,12 @@ pub struct VectorLayerQueryCfg {
     pub simplify: Option<bool>,
     /// Simplification tolerance (override layer default setting)
     pub tolerance: Option<String>,
+    /// Fix invalid geometries after simplification (override layer default setting)
+    pub make_valid: Option<bool>,
+    /// Minimal area of polygons (override layer default setting)
+    pub min_area: Option<String>,
+    /// Select all fields from table, e.g. a generalized table for low zoom levels (override layer default setting)
+    pub table_name: Option<String>,
     /// User defined SQL query.
     ///
     /// The following variables are replaced at runtime:
@@ -248,6 +567,35 @@ pub struct CacheLimitCfg {
     pub maxzoom: Option<u8>,
 }
 
+/// Tile cache expiration
+#[derive(Deserialize, Serialize, Clone, Debug, Default)]
+#[serde(deny_unknown_fields)]
+pub struct CacheControlCfg {
+    /// Maximal age of cached tiles in seconds. Expired tiles are re-rendered (Default: no expiration)
+    pub max_age: Option<u64>,
+    /// Time in seconds after expiration, during which a stale tile is delivered while being re-rendered in the background
+    #[serde(default)]
+    pub stale_while_revalidate: u64,
+}
+
+/// In-memory LRU tile cache
+#[derive(Deserialize, Serialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct MemoryCacheCfg {
+    /// Maximal size of cached tiles in MB
+    pub max_size: u64,
+}
+
+/// Output formats of raster tiles
+#[derive(Deserialize, Serialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct RasterEncodingCfg {
+    /// Offered tile formats (`png`, `jpeg`, `webp` or `avif`). The first format is the default.
+    pub formats: Vec<String>,
+    /// Quality of lossy formats from 1 to 100 (Default: lossless WebP, JPEG and AVIF with quality 85)
+    pub quality: Option<u8>,
+}
+
 #[derive(Deserialize, Serialize, Clone, Debug)]
 #[serde(deny_unknown_fields)]
 pub struct TileCacheProviderCfg {
@@ -269,7 +617,8 @@ pub enum StoreCompressionCfg {
     None,
     /// Gzip compression. Default for MBTiles and PMTiles.
     Gzip,
-    // Brotli,
+    /// Brotli compression
+    Brotli,
     // Zstd,
 }
 
@@ -289,6 +638,12 @@ pub enum TileStoreCfg {
     /// PMTile archive
     #[serde(rename = "pmtiles")]
     Pmtiles(PmtilesStoreCfg),
+    /// Redis store
+    #[serde(rename = "redis")]
+    Redis(RedisStoreCfg),
+    /// Local file cache in front of another store
+    #[serde(rename = "tiered")]
+    Tiered(TieredStoreCfg),
     /// Disable tile cache
     #[serde(rename = "nostore")]
     NoStore,
@@ -322,10 +677,70 @@ pub struct PmtilesStoreCfg {
     pub path: PathBuf,
 }
 
+#[derive(Deserialize, Serialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct RedisStoreCfg {
+    /// Connection URL like `redis://127.0.0.1:6379`
+    pub url: String,
+    /// Connect to a Redis cluster with `url` as one of its nodes
+    #[serde(default)]
+    pub cluster: bool,
+    /// Prefix of tile keys
+    #[serde(default = "default_redis_prefix")]
+    pub prefix: String,
+    /// Expiration time of stored tiles in seconds
+    pub ttl: Option<u64>,
+}
+
+fn default_redis_prefix() -> String {
+    "bbox".to_string()
+}
+
+/// Two cache layers: tiles are read from `l1` first, then from `l2` and written into both
+#[derive(Deserialize, Serialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct TieredStoreCfg {
+    /// Local file store
+    pub l1: FileStoreCfg,
+    /// Maximal size of tiles in the local store in MB. Least recently used tiles are evicted.
+    pub l1_max_size: Option<u64>,
+    /// Backing store, e.g. S3
+    pub l2: Box<TileStoreCfg>,
+}
+
 impl TileStoreCfg {
     pub fn from_cli_args(cli: &ArgMatches) -> Option<Self> {
-        let Ok(Commands::Seed(args)) = Commands::from_arg_matches(cli) else {
-            return None;
+        let args = match Commands::from_arg_matches(cli) {
+            Ok(Commands::Seed(args)) => args,
+            Ok(Commands::Invalidate(args)) => {
+                return if let Some(path) = &args.tile_path {
+                    Some(TileStoreCfg::Files(FileStoreCfg {
+                        base_dir: path.into(),
+                    }))
+                } else {
+                    args.s3_path.as_ref().map(|s3_path| {
+                        TileStoreCfg::S3(S3StoreCfg {
+                            path: s3_path.to_string(),
+                        })
+                    })
+                };
+            }
+            Ok(Commands::Stats(args)) => {
+                return if let Some(path) = &args.tile_path {
+                    Some(TileStoreCfg::Files(FileStoreCfg {
+                        base_dir: path.into(),
+                    }))
+                } else if let Some(s3_path) = &args.s3_path {
+                    Some(TileStoreCfg::S3(S3StoreCfg {
+                        path: s3_path.to_string(),
+                    }))
+                } else {
+                    args.mb_path
+                        .as_ref()
+                        .map(|path| TileStoreCfg::Mbtiles(MbtilesStoreCfg { path: path.into() }))
+                };
+            }
+            _ => return None,
         };
         if let Some(path) = &args.tile_path {
             let cache_cfg = TileStoreCfg::Files(FileStoreCfg {
@@ -343,6 +758,21 @@ impl TileStoreCfg {
         } else if let Some(path) = &args.pm_path {
             let cache_cfg = TileStoreCfg::Pmtiles(PmtilesStoreCfg { path: path.into() });
             Some(cache_cfg)
+        } else if let Some(output) = &args.output {
+            let path = output.to_string();
+            let cache_cfg = match args.format.unwrap_or(StoreFormat::from_path(output)) {
+                StoreFormat::Files => TileStoreCfg::Files(FileStoreCfg {
+                    base_dir: path.into(),
+                }),
+                StoreFormat::S3 => TileStoreCfg::S3(S3StoreCfg { path }),
+                StoreFormat::Mbtiles => {
+                    TileStoreCfg::Mbtiles(MbtilesStoreCfg { path: path.into() })
+                }
+                StoreFormat::Pmtiles => {
+                    TileStoreCfg::Pmtiles(PmtilesStoreCfg { path: path.into() })
+                }
+            };
+            Some(cache_cfg)
         } else if args.no_store {
             Some(TileStoreCfg::NoStore)
         } else {
@@ -382,21 +812,8 @@ impl ServiceConfig for TileServiceCfg {
                 None
             };
         if let Some(file_or_url) = file_or_url {
-            if let Some(source_cfg) = source_config_from_cli_arg(&file_or_url) {
-                let name = if let Some(name) = Path::new(&file_or_url).file_stem() {
-                    name.to_string_lossy().to_string()
-                } else {
-                    file_or_url.to_string()
-                };
-                info!("Adding tileset `{name}`");
-                let ts = TileSetCfg {
-                    name,
-                    tms: None,
-                    source: source_cfg,
-                    cache: None,
-                    cache_format: None,
-                    cache_limits: None,
-                };
+            if let Some(ts) = TileSetCfg::from_archive(&file_or_url) {
+                info!("Adding tileset `{}`", ts.name);
                 cfg.tilesets.push(ts);
             }
         }
@@ -404,6 +821,45 @@ impl ServiceConfig for TileServiceCfg {
     }
 }
 
+impl TileSetCfg {
+    /// Tileset in default grid without cache
+    pub fn new(name: &str, source: SourceParamCfg) -> Self {
+        TileSetCfg {
+            name: name.to_string(),
+            tms: None,
+            attribution: None,
+            license: None,
+            source,
+            cache: None,
+            cache_format: None,
+            cache_limits: None,
+            cache_control: None,
+            memory_cache: None,
+            encoding: None,
+            post_processors: Vec::new(),
+        }
+    }
+    /// Add configured attribution and license to TileJSON metadata
+    pub fn apply_metadata(&self, tilejson: &mut TileJSON) {
+        if let Some(attribution) = &self.attribution {
+            tilejson.attribution = Some(attribution.clone());
+        }
+        if let Some(license) = &self.license {
+            tilejson.other.insert("license".to_string(), json!(license));
+        }
+    }
+    /// Tileset publishing an MBTiles or PMTiles archive, named after the file
+    pub fn from_archive(file_or_url: &str) -> Option<Self> {
+        let source = source_config_from_cli_arg(file_or_url)?;
+        let name = if let Some(name) = Path::new(file_or_url).file_stem() {
+            name.to_string_lossy().to_string()
+        } else {
+            file_or_url.to_string()
+        };
+        Some(TileSetCfg::new(&name, source))
+    }
+}
+
 impl TileServiceCfg {
     pub fn as_toml(&self) -> String {
         toml::to_string(&self).unwrap()
@@ -425,6 +881,7 @@ impl From<t_rex::ApplicationCfg> for TileServiceCfg {
             .map(|ds| {
                 let datasource = DatasourceCfg::Postgis(DsPostgisCfg {
                     url: ds.dbconn.expect("dbconn"),
+                    ..Default::default()
                 });
                 NamedDatasourceCfg {
                     name: ds.name.unwrap_or("default".to_string()),
@@ -433,10 +890,28 @@ impl From<t_rex::ApplicationCfg> for TileServiceCfg {
             })
             .collect();
         let grids = if let Some(g) = &t_rex_config.grid.user {
-            warn!("User defined grid has to be configured manually");
-            vec![GridCfg {
-                json: format!("{}.json", g.srid),
-            }]
+            if g.origin != "TopLeft" {
+                warn!("Grid origin `{}` is converted to top left origin", g.origin);
+            }
+            let units = match g.units.to_lowercase().as_str() {
+                "dd" => GridUnitsCfg::Dd,
+                "ft" => GridUnitsCfg::Ft,
+                _ => GridUnitsCfg::M,
+            };
+            vec![GridCfg::Custom(CustomGridCfg {
+                id: format!("{}", g.srid),
+                title: None,
+                srid: g.srid,
+                units,
+                extent: ExtentCfg {
+                    minx: g.extent.minx,
+                    miny: g.extent.miny,
+                    maxx: g.extent.maxx,
+                    maxy: g.extent.maxy,
+                },
+                resolutions: g.resolutions.clone(),
+                tile_size: NonZeroU16::new(g.width),
+            })]
         } else {
             Vec::new()
         };
@@ -501,6 +976,9 @@ impl From<t_rex::ApplicationCfg> for TileServiceCfg {
                                 maxzoom: q.maxzoom,
                                 simplify: q.simplify,
                                 tolerance: q.tolerance,
+                                make_valid: None,
+                                min_area: None,
+                                table_name: None,
                                 sql: q.sql,
                             })
                             .collect::<Vec<_>>();
@@ -516,6 +994,9 @@ impl From<t_rex::ApplicationCfg> for TileServiceCfg {
                                         maxzoom: None,
                                         simplify: Some(l.simplify),
                                         tolerance: Some(l.tolerance.clone()),
+                                        make_valid: None,
+                                        min_area: None,
+                                        table_name: None,
                                         sql,
                                     },
                                 );
@@ -529,8 +1010,12 @@ impl From<t_rex::ApplicationCfg> for TileServiceCfg {
                             srid: l.srid,
                             no_transform: l.no_transform,
                             fid_field: l.fid_field,
+                            dedupe_by: None,
                             table_name,
                             query_limit: l.query_limit,
+                            max_features_per_tile: None,
+                            max_tile_bytes: None,
+                            overflow: TileOverflowCfg::default(),
                             queries,
                             minzoom: l.minzoom,
                             maxzoom: l.maxzoom,
@@ -539,6 +1024,12 @@ impl From<t_rex::ApplicationCfg> for TileServiceCfg {
                             tolerance: l.tolerance,
                             buffer_size: l.buffer_size,
                             make_valid: l.make_valid,
+                            min_area: None,
+                            invalid_geometries: InvalidGeometriesCfg::default(),
+                            attributes_minzoom: HashMap::new(),
+                            attributes: Vec::new(),
+                            label_points: false,
+                            label_position: LabelPositionCfg::default(),
                             shift_longitude: l.shift_longitude,
                         }
                     })
@@ -558,11 +1049,14 @@ impl From<t_rex::ApplicationCfg> for TileServiceCfg {
                     attribution: ts.attribution,
                     postgis2: false,
                     diagnostics: None,
+                    query_params: Vec::new(),
                     layers,
                 };
                 TileSetCfg {
                     name: ts.name,
                     tms: tms.clone(),
+                    attribution: None,
+                    license: None,
                     source: SourceParamCfg::Postgis(pgcfg),
                     cache: cache_name.clone(),
                     cache_format: None,
@@ -570,6 +1064,10 @@ impl From<t_rex::ApplicationCfg> for TileServiceCfg {
                         minzoom: l.minzoom,
                         maxzoom: l.maxzoom,
                     }),
+                    cache_control: None,
+                    memory_cache: None,
+                    encoding: None,
+                    post_processors: Vec::new(),
                 }
             })
             .collect();
@@ -578,6 +1076,7 @@ impl From<t_rex::ApplicationCfg> for TileServiceCfg {
             datasources,
             tilesets,
             tilestores,
+            directories: Vec::new(),
         }
     }
 }
@@ -631,10 +1130,14 @@ impl VectorLayerCfg {
         let mut zoom_steps = self
             .queries
             .iter()
-            .filter(|q| q.sql.is_some())
-            .map(|q| q.minzoom)
+            // Overrides start at minzoom and end after maxzoom
+            .flat_map(|q| [Some(q.minzoom), q.maxzoom.and_then(|z| z.checked_add(1))])
+            .flatten()
+            // Steps for overrides without SQL query need a table or an enclosing query
+            .filter(|z| self.table(*z).is_some() || self.query(*z).is_some())
             .collect::<Vec<_>>();
         zoom_steps.sort();
+        zoom_steps.dedup();
         if zoom_steps.is_empty() {
             zoom_steps.push(self.minzoom());
         } else if self.minzoom() < zoom_steps[0] {
@@ -669,9 +1172,16 @@ impl VectorLayerCfg {
     }
     /// SQL query for zoom level
     pub fn query(&self, level: u8) -> Option<&String> {
-        let query_cfg = self.query_cfg(level, |q| q.sql.is_some());
+        let query_cfg = self.query_cfg(level, |q| q.sql.is_some() || q.table_name.is_some());
         query_cfg.and_then(|q| q.sql.as_ref())
     }
+    /// Table for zoom level, if not selected with an SQL query
+    pub fn table(&self, level: u8) -> Option<&String> {
+        let query_cfg = self.query_cfg(level, |q| q.sql.is_some() || q.table_name.is_some());
+        query_cfg
+            .and_then(|q| q.table_name.as_ref())
+            .or(self.table_name.as_ref())
+    }
     /// simplify config for zoom level
     pub fn simplify(&self, level: u8) -> bool {
         let query_cfg = self.query_cfg(level, |q| q.simplify.is_some());
@@ -684,6 +1194,20 @@ impl VectorLayerCfg {
             .and_then(|q| q.tolerance.as_ref())
             .unwrap_or(&self.tolerance)
     }
+    /// make_valid config for zoom level
+    pub fn make_valid(&self, level: u8) -> bool {
+        let query_cfg = self.query_cfg(level, |q| q.make_valid.is_some());
+        query_cfg
+            .and_then(|q| q.make_valid)
+            .unwrap_or(self.make_valid)
+    }
+    /// min_area config for zoom level
+    pub fn min_area(&self, level: u8) -> Option<&String> {
+        let query_cfg = self.query_cfg(level, |q| q.min_area.is_some());
+        query_cfg
+            .and_then(|q| q.min_area.as_ref())
+            .or(self.min_area.as_ref())
+    }
 }
 
 // Mapproxy Yaml:
@@ -918,3 +1442,106 @@ impl VectorLayerCfg {
 //     <timeout>300</timeout>
 //   </locker>
 // </mapcache>
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tile_grid::{tms, Tms, Xyz};
+
+    #[test]
+    fn custom_grid() {
+        let cfg: TileServiceCfg = toml::from_str(
+            r#"
+            [[grid]]
+            id = "LV95"
+            srid = 2056
+            extent = { minx = 2420000, miny = 1030000, maxx = 2900000, maxy = 1350000 }
+            resolutions = [4000, 3750, 3500, 3250, 3000, 2750, 2500, 2250, 2000, 1750, 1500, 1250, 1000, 750, 650, 500, 250, 100, 50, 20, 10, 5, 2.5, 2, 1.5, 1, 0.5]
+            "#,
+        )
+        .unwrap();
+        let GridCfg::Custom(grid) = &cfg.grids[0] else {
+            panic!("custom grid expected");
+        };
+        let custom = serde_json::to_value(grid.tile_matrix_set().unwrap()).unwrap();
+        let reference = TileMatrixSet::from_json_file("../assets/custom-grid-lv95.json").unwrap();
+        let reference = serde_json::to_value(reference).unwrap();
+        assert_eq!(custom["crs"], reference["crs"]);
+        for (matrix, expected) in custom["tileMatrices"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .zip(reference["tileMatrices"].as_array().unwrap())
+        {
+            for key in ["id", "pointOfOrigin", "matrixWidth", "matrixHeight"] {
+                assert_eq!(matrix[key], expected[key], "{key}");
+            }
+            let scale = matrix["scaleDenominator"].as_f64().unwrap();
+            let expected_scale = expected["scaleDenominator"].as_f64().unwrap();
+            assert!((scale - expected_scale).abs() < 1e-6);
+        }
+
+        let mut grids = tms().clone();
+        grids
+            .register(vec![grid.tile_matrix_set().unwrap()], true)
+            .unwrap();
+        let tms: Tms = grids.lookup("LV95").unwrap();
+        let bounds = tms.xy_bounds(&Xyz::new(0, 0, 0));
+        assert_eq!((bounds.left, bounds.top), (2420000.0, 1350000.0));
+
+        let json_grid: TileServiceCfg =
+            toml::from_str(r#"grid = [{ json = "../assets/custom-grid-lv95.json" }]"#).unwrap();
+        assert!(matches!(json_grid.grids[0], GridCfg::Json { .. }));
+    }
+
+    #[test]
+    fn generalization_overrides() {
+        let layer: VectorLayerCfg = toml::from_str(
+            r#"
+            name = "lakes"
+            table_name = "lakes"
+            minzoom = 0
+            min_area = "16*!pixel_width!*!pixel_width!"
+            attributes_minzoom = { name = 8 }
+            [[attributes]]
+            minzoom = 6
+            fields = ["scalerank"]
+            [[query]]
+            minzoom = 10
+            make_valid = true
+            min_area = "0"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(layer.zoom_steps(), vec![0, 10]);
+        assert!(!layer.make_valid(9));
+        assert!(layer.make_valid(10));
+        assert_eq!(layer.min_area(9).unwrap(), "16*!pixel_width!*!pixel_width!");
+        assert_eq!(layer.min_area(12).unwrap(), "0");
+        assert_eq!(layer.attributes_minzoom["name"], 8);
+        assert_eq!(layer.attributes[0].fields, vec!["scalerank"]);
+    }
+
+    #[test]
+    fn tileset_attribution() {
+        let ts: TileSetCfg = toml::from_str(
+            r#"
+            name = "osm"
+            attribution = "© OpenStreetMap contributors"
+            license = "ODbL-1.0"
+            [mbtiles]
+            path = "osm.mbtiles"
+            "#,
+        )
+        .unwrap();
+        let mut tilejson: TileJSON =
+            serde_json::from_value(json!({"tilejson": "3.0.0", "tiles": []})).unwrap();
+        tilejson.attribution = Some("source attribution".to_string());
+        ts.apply_metadata(&mut tilejson);
+        assert_eq!(
+            tilejson.attribution.as_deref(),
+            Some("© OpenStreetMap contributors")
+        );
+        assert_eq!(tilejson.other["license"], "ODbL-1.0");
+    }
+}
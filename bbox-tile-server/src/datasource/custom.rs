@@ -0,0 +1,61 @@
+//! Tile sources implemented outside of bbox.
+//!
+//! Applications embedding the tile service register a driver, which creates tile sources
+//! from the parameters of tilesets configured with `[tileset.custom]`:
+//!
+//! ```toml
+//! [[tileset]]
+//! name = "heatmap"
+//! [tileset.custom]
+//! driver = "heatmap"
+//! layer = "visits"
+//! ```
+
+use crate::config::CustomSourceParamsCfg;
+use crate::datasource::{TileRead, TileSourceError};
+use async_trait::async_trait;
+use log::info;
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use tile_grid::Tms;
+
+/// Driver specific configuration parameters
+pub type DriverParams = serde_json::Map<String, serde_json::Value>;
+
+#[async_trait]
+pub trait TileSourceDriver: Send + Sync {
+    /// Create tile source for a tileset in grid `tms`
+    async fn create_source(
+        &self,
+        params: &DriverParams,
+        tms: &Tms,
+    ) -> Result<Box<dyn TileRead>, TileSourceError>;
+}
+
+static DRIVERS: Lazy<RwLock<HashMap<String, Arc<dyn TileSourceDriver>>>> =
+    Lazy::new(Default::default);
+
+/// Register driver for `[tileset.custom]` configurations.
+/// Has to be called before the tile service is created.
+pub fn register_driver(name: &str, driver: impl TileSourceDriver + 'static) {
+    info!("Registering tile source driver `{name}`");
+    DRIVERS
+        .write()
+        .expect("driver registry")
+        .insert(name.to_string(), Arc::new(driver));
+}
+
+/// Create tile source with registered driver
+pub(crate) async fn create_source(
+    cfg: &CustomSourceParamsCfg,
+    tms: &Tms,
+) -> Result<Box<dyn TileRead>, TileSourceError> {
+    let driver = DRIVERS
+        .read()
+        .expect("driver registry")
+        .get(&cfg.driver)
+        .cloned()
+        .ok_or_else(|| TileSourceError::TileSourceNotFound(cfg.driver.clone()))?;
+    driver.create_source(&cfg.params, tms).await
+}
@@ -0,0 +1,558 @@
+//! Raster tiles rendered from GeoTIFF and Cloud Optimized GeoTIFF files.
+//!
+//! Remote files are read with HTTP range requests. Overviews stored in the
+//! TIFF are selected according to the resolution of the requested tile.
+
+use crate::config::GeotiffSourceParamsCfg;
+use crate::datasource::{
+    wms_fcgi::HttpRequestParams, LayerInfo, SourceType, TileRead, TileSourceError,
+};
+use crate::filter_params::FilterParams;
+use crate::raster_encoding::encode_rgba;
+use crate::service::TileService;
+use async_trait::async_trait;
+use bbox_core::{Format, TileResponse};
+use log::{info, warn};
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufReader, Cursor, Read, Seek, SeekFrom};
+use std::num::NonZeroU16;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use tiff::decoder::{Decoder, DecodingResult};
+use tiff::tags::Tag;
+use tile_grid::{BoundingBox, Xyz};
+use tilejson::{tilejson, TileJSON};
+
+/// Size of blocks fetched with HTTP range requests
+const BLOCK_SIZE: u64 = 64 * 1024;
+/// Maximal number of cached blocks of a remote file
+const MAX_CACHED_BLOCKS: usize = 1024;
+
+/// Blocking HTTP client. Only used in blocking tasks and never dropped.
+static HTTP_CLIENT: Lazy<reqwest::blocking::Client> = Lazy::new(reqwest::blocking::Client::new);
+
+#[derive(Clone, Debug)]
+pub struct GeotiffSource {
+    file: RasterFile,
+    /// Full resolution image followed by overviews
+    levels: Vec<ImageLevel>,
+    /// Upper left corner of the full resolution image
+    origin: (f64, f64),
+    /// Pixel size of the full resolution image
+    pixel_size: (f64, f64),
+    nodata: Option<f64>,
+    value_range: Option<(f64, f64)>,
+    tile_size: Option<NonZeroU16>,
+}
+
+/// Image of a TIFF file (IFD)
+#[derive(Clone, Debug)]
+struct ImageLevel {
+    ifd: usize,
+    width: u32,
+    height: u32,
+}
+
+impl GeotiffSource {
+    pub async fn from_config(
+        cfg: &GeotiffSourceParamsCfg,
+        srid: i32,
+    ) -> Result<Self, TileSourceError> {
+        let cfg = cfg.clone();
+        tokio::task::spawn_blocking(move || Self::open(&cfg, srid))
+            .await
+            .map_err(|e| TileSourceError::RasterError(e.to_string()))?
+    }
+
+    fn open(cfg: &GeotiffSourceParamsCfg, srid: i32) -> Result<Self, TileSourceError> {
+        let file = RasterFile::open(&cfg.path)?;
+        let mut decoder = Decoder::new(file.reader()?)?;
+
+        let scale = decoder.get_tag_f64_vec(Tag::ModelPixelScaleTag)?;
+        let tiepoint = decoder.get_tag_f64_vec(Tag::ModelTiepointTag)?;
+        if scale.len() < 2 || tiepoint.len() < 6 {
+            return Err(TileSourceError::RasterError(
+                "invalid GeoTIFF georeferencing".to_string(),
+            ));
+        }
+        let pixel_size = (scale[0], scale[1]);
+        let mut origin = (
+            tiepoint[3] - tiepoint[0] * pixel_size.0,
+            tiepoint[4] + tiepoint[1] * pixel_size.1,
+        );
+        let geokeys = match decoder.find_tag(Tag::GeoKeyDirectoryTag)? {
+            Some(value) => GeoKeys::from_directory(&value.into_u32_vec()?),
+            None => GeoKeys::default(),
+        };
+        if geokeys.pixel_is_point {
+            origin = (origin.0 - pixel_size.0 / 2.0, origin.1 + pixel_size.1 / 2.0);
+        }
+        match geokeys.epsg {
+            Some(epsg) if epsg as i32 != srid => warn!(
+                "{}: EPSG:{epsg} doesn't match grid EPSG:{srid} - raster is not reprojected",
+                cfg.path
+            ),
+            None => warn!("{}: unknown spatial reference system", cfg.path),
+            _ => {}
+        }
+        let nodata = match cfg.nodata {
+            Some(nodata) => Some(nodata),
+            None => match decoder.find_tag(Tag::GdalNodata)? {
+                Some(value) => value.into_string()?.trim_matches('\0').parse().ok(),
+                None => None,
+            },
+        };
+
+        let mut levels = Vec::new();
+        let mut ifd = 0;
+        loop {
+            let subfile_type = match decoder.find_tag(Tag::NewSubfileType)? {
+                Some(value) => value.into_u32()?,
+                None => 0,
+            };
+            // Skip transparency masks
+            if subfile_type & 4 == 0 {
+                let (width, height) = decoder.dimensions()?;
+                levels.push(ImageLevel { ifd, width, height });
+            }
+            if !decoder.more_images() {
+                break;
+            }
+            decoder.next_image()?;
+            ifd += 1;
+        }
+        info!("{}: {} image levels", cfg.path, levels.len());
+
+        Ok(GeotiffSource {
+            file,
+            levels,
+            origin,
+            pixel_size,
+            nodata,
+            value_range: cfg.value_range,
+            tile_size: cfg.tile_size,
+        })
+    }
+
+    /// Pixel size of an image level
+    fn level_pixel_size(&self, level: &ImageLevel) -> (f64, f64) {
+        let base = &self.levels[0];
+        (
+            self.pixel_size.0 * base.width as f64 / level.width as f64,
+            self.pixel_size.1 * base.height as f64 / level.height as f64,
+        )
+    }
+
+    /// Select the overview with the lowest resolution which is at least as detailed as requested.
+    fn select_level(&self, resolution: f64) -> &ImageLevel {
+        self.levels
+            .iter()
+            .rev()
+            .find(|level| self.level_pixel_size(level).0 <= resolution * 1.0001)
+            .unwrap_or(&self.levels[0])
+    }
+
+    /// Render extent into an image of the requested format
+    pub fn render(
+        &self,
+        extent: &BoundingBox,
+        width: u32,
+        height: u32,
+        format: &Format,
+    ) -> Result<Vec<u8>, TileSourceError> {
+        let rgba = self.resample(extent, width, height)?;
+        encode_rgba(&rgba, width, height, format, None)
+    }
+
+    /// Nearest neighbour resampling of the best matching image level into RGBA pixels
+    fn resample(
+        &self,
+        extent: &BoundingBox,
+        width: u32,
+        height: u32,
+    ) -> Result<Vec<u8>, TileSourceError> {
+        let res_x = (extent.right - extent.left) / width as f64;
+        let res_y = (extent.top - extent.bottom) / height as f64;
+        let level = self.select_level(res_x);
+        let (level_res_x, level_res_y) = self.level_pixel_size(level);
+
+        let mut decoder = Decoder::new(self.file.reader()?)?;
+        for _ in 0..level.ifd {
+            decoder.next_image()?;
+        }
+        let (chunk_width, chunk_height) = decoder.chunk_dimensions();
+        let chunks_across = (level.width + chunk_width - 1) / chunk_width;
+        let mut chunks: HashMap<u32, Chunk> = HashMap::new();
+
+        let mut rgba = vec![0; (width * height * 4) as usize];
+        for row in 0..height {
+            let y = extent.top - (row as f64 + 0.5) * res_y;
+            let src_row = ((self.origin.1 - y) / level_res_y).floor();
+            if src_row < 0.0 || src_row >= level.height as f64 {
+                continue;
+            }
+            let src_row = src_row as u32;
+            for col in 0..width {
+                let x = extent.left + (col as f64 + 0.5) * res_x;
+                let src_col = ((x - self.origin.0) / level_res_x).floor();
+                if src_col < 0.0 || src_col >= level.width as f64 {
+                    continue;
+                }
+                let src_col = src_col as u32;
+                let chunk_index = (src_row / chunk_height) * chunks_across + src_col / chunk_width;
+                if !chunks.contains_key(&chunk_index) {
+                    let (data_width, data_height) = decoder.chunk_data_dimensions(chunk_index);
+                    let data = decoder.read_chunk(chunk_index)?;
+                    let samples = sample_count(&data) / (data_width * data_height) as usize;
+                    chunks.insert(
+                        chunk_index,
+                        Chunk {
+                            data,
+                            width: data_width,
+                            samples,
+                        },
+                    );
+                }
+                let chunk = &chunks[&chunk_index];
+                let offset = (((src_row % chunk_height) * chunk.width + src_col % chunk_width)
+                    as usize)
+                    * chunk.samples;
+                let pixel = (0..chunk.samples.min(4))
+                    .map(|i| sample_value(&chunk.data, offset + i))
+                    .collect::<Vec<_>>();
+                let idx = ((row * width + col) * 4) as usize;
+                rgba[idx..idx + 4].copy_from_slice(&self.rgba_pixel(&chunk.data, &pixel));
+            }
+        }
+        Ok(rgba)
+    }
+
+    fn rgba_pixel(&self, data: &DecodingResult, pixel: &[f64]) -> [u8; 4] {
+        if let Some(nodata) = self.nodata {
+            if pixel.iter().all(|v| *v == nodata) {
+                return [0, 0, 0, 0];
+            }
+        }
+        let byte = |v: f64| {
+            if matches!(data, DecodingResult::U8(_)) {
+                v as u8
+            } else if let Some((min, max)) = self.value_range {
+                ((v - min) / (max - min) * 255.0).clamp(0.0, 255.0) as u8
+            } else {
+                v.clamp(0.0, 255.0) as u8
+            }
+        };
+        match pixel {
+            [gray] => [byte(*gray), byte(*gray), byte(*gray), 255],
+            [gray, alpha] => [byte(*gray), byte(*gray), byte(*gray), byte(*alpha)],
+            [r, g, b] => [byte(*r), byte(*g), byte(*b), 255],
+            [r, g, b, a, ..] => [byte(*r), byte(*g), byte(*b), byte(*a)],
+            [] => [0, 0, 0, 0],
+        }
+    }
+}
+
+/// Decoded TIFF tile or strip
+struct Chunk {
+    data: DecodingResult,
+    width: u32,
+    samples: usize,
+}
+
+fn sample_count(data: &DecodingResult) -> usize {
+    match data {
+        DecodingResult::U8(v) => v.len(),
+        DecodingResult::U16(v) => v.len(),
+        DecodingResult::U32(v) => v.len(),
+        DecodingResult::U64(v) => v.len(),
+        DecodingResult::F32(v) => v.len(),
+        DecodingResult::F64(v) => v.len(),
+        DecodingResult::I8(v) => v.len(),
+        DecodingResult::I16(v) => v.len(),
+        DecodingResult::I32(v) => v.len(),
+        DecodingResult::I64(v) => v.len(),
+    }
+}
+
+fn sample_value(data: &DecodingResult, idx: usize) -> f64 {
+    match data {
+        DecodingResult::U8(v) => v[idx] as f64,
+        DecodingResult::U16(v) => v[idx] as f64,
+        DecodingResult::U32(v) => v[idx] as f64,
+        DecodingResult::U64(v) => v[idx] as f64,
+        DecodingResult::F32(v) => v[idx] as f64,
+        DecodingResult::F64(v) => v[idx],
+        DecodingResult::I8(v) => v[idx] as f64,
+        DecodingResult::I16(v) => v[idx] as f64,
+        DecodingResult::I32(v) => v[idx] as f64,
+        DecodingResult::I64(v) => v[idx] as f64,
+    }
+}
+
+/// GeoTIFF keys used for georeferencing
+#[derive(Default, Debug)]
+struct GeoKeys {
+    epsg: Option<u32>,
+    pixel_is_point: bool,
+}
+
+impl GeoKeys {
+    const GT_RASTER_TYPE: u32 = 1025;
+    const GEOGRAPHIC_TYPE: u32 = 2048;
+    const PROJECTED_CS_TYPE: u32 = 3072;
+    const RASTER_PIXEL_IS_POINT: u32 = 2;
+
+    fn from_directory(directory: &[u32]) -> Self {
+        let mut keys = GeoKeys::default();
+        // Header with 4 values followed by entries (key, location, count, value)
+        for entry in directory.chunks_exact(4).skip(1) {
+            // Only values stored directly in the directory are supported
+            if entry[1] != 0 {
+                continue;
+            }
+            match entry[0] {
+                Self::GT_RASTER_TYPE => {
+                    keys.pixel_is_point = entry[3] == Self::RASTER_PIXEL_IS_POINT
+                }
+                Self::PROJECTED_CS_TYPE => keys.epsg = Some(entry[3]),
+                Self::GEOGRAPHIC_TYPE if keys.epsg.is_none() => keys.epsg = Some(entry[3]),
+                _ => {}
+            }
+        }
+        keys
+    }
+}
+
+/// Local or remote raster file
+#[derive(Clone, Debug)]
+enum RasterFile {
+    Local(PathBuf),
+    Remote(HttpRangeReader),
+}
+
+trait ReadSeek: Read + Seek + Send {}
+
+impl<T: Read + Seek + Send> ReadSeek for T {}
+
+impl RasterFile {
+    fn open(path: &str) -> Result<Self, TileSourceError> {
+        if path.starts_with("http://") || path.starts_with("https://") {
+            Ok(RasterFile::Remote(HttpRangeReader::open(path)?))
+        } else {
+            Ok(RasterFile::Local(PathBuf::from(path)))
+        }
+    }
+
+    fn reader(&self) -> tiff::TiffResult<Box<dyn ReadSeek>> {
+        match self {
+            RasterFile::Local(path) => Ok(Box::new(BufReader::new(File::open(path)?))),
+            RasterFile::Remote(reader) => Ok(Box::new(reader.clone())),
+        }
+    }
+}
+
+/// Reader fetching blocks of a remote file with HTTP range requests
+#[derive(Clone, Debug)]
+struct HttpRangeReader {
+    url: String,
+    len: u64,
+    pos: u64,
+    /// Fetched blocks, shared between readers of the same file
+    blocks: Arc<Mutex<HashMap<u64, Arc<Vec<u8>>>>>,
+}
+
+impl HttpRangeReader {
+    fn open(url: &str) -> Result<Self, TileSourceError> {
+        let resp = HTTP_CLIENT.head(url).send()?.error_for_status()?;
+        let len = resp
+            .headers()
+            .get(reqwest::header::CONTENT_LENGTH)
+            .and_then(|len| len.to_str().ok())
+            .and_then(|len| len.parse().ok())
+            .ok_or_else(|| TileSourceError::RasterError(format!("{url}: unknown file size")))?;
+        Ok(HttpRangeReader {
+            url: url.to_string(),
+            len,
+            pos: 0,
+            blocks: Arc::new(Mutex::new(HashMap::new())),
+        })
+    }
+
+    fn block(&self, block_no: u64) -> io::Result<Arc<Vec<u8>>> {
+        if let Some(block) = self.blocks.lock().unwrap().get(&block_no) {
+            return Ok(block.clone());
+        }
+        let start = block_no * BLOCK_SIZE;
+        let end = (start + BLOCK_SIZE).min(self.len) - 1;
+        let block = HTTP_CLIENT
+            .get(&self.url)
+            .header(reqwest::header::RANGE, format!("bytes={start}-{end}"))
+            .send()
+            .and_then(|resp| resp.error_for_status())
+            .and_then(|resp| resp.bytes())
+            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+        let block = Arc::new(block.to_vec());
+        let mut blocks = self.blocks.lock().unwrap();
+        if blocks.len() >= MAX_CACHED_BLOCKS {
+            blocks.clear();
+        }
+        blocks.insert(block_no, block.clone());
+        Ok(block)
+    }
+}
+
+impl Read for HttpRangeReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.pos >= self.len || buf.is_empty() {
+            return Ok(0);
+        }
+        let block = self.block(self.pos / BLOCK_SIZE)?;
+        let offset = (self.pos % BLOCK_SIZE) as usize;
+        let count = buf.len().min(block.len().saturating_sub(offset));
+        buf[..count].copy_from_slice(&block[offset..offset + count]);
+        self.pos += count as u64;
+        Ok(count)
+    }
+}
+
+impl Seek for HttpRangeReader {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let pos = match pos {
+            SeekFrom::Start(pos) => pos as i64,
+            SeekFrom::End(offset) => self.len as i64 + offset,
+            SeekFrom::Current(offset) => self.pos as i64 + offset,
+        };
+        if pos < 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "seek before start of file",
+            ));
+        }
+        self.pos = pos as u64;
+        Ok(self.pos)
+    }
+}
+
+#[async_trait]
+impl TileRead for GeotiffSource {
+    async fn xyz_request(
+        &self,
+        service: &TileService,
+        tms_id: &str,
+        tile: &Xyz,
+        _filter: &FilterParams,
+        format: &Format,
+        _request_params: HttpRequestParams<'_>,
+    ) -> Result<TileResponse, TileSourceError> {
+        let extent_info = service.xyz_extent(tms_id, tile)?;
+        let (width, height) = if let Some(size) = self.tile_size {
+            (size, size)
+        } else {
+            (extent_info.tile_width, extent_info.tile_height)
+        };
+        let source = self.clone();
+        let render_format = *format;
+        let data = tokio::task::spawn_blocking(move || {
+            source.render(
+                &extent_info.extent,
+                width.get() as u32,
+                height.get() as u32,
+                &render_format,
+            )
+        })
+        .await
+        .map_err(|e| TileSourceError::RasterError(e.to_string()))??;
+        let mut response = TileResponse::new();
+        response.set_content_type(format.content_type());
+        Ok(response.with_body(Box::new(Cursor::new(data))))
+    }
+    fn source_type(&self) -> SourceType {
+        SourceType::Raster
+    }
+    async fn tilejson(&self, format: &Format) -> Result<TileJSON, TileSourceError> {
+        let mut tj = tilejson! { tiles: vec![] };
+        tj.other
+            .insert("format".to_string(), format.file_suffix().into());
+        Ok(tj)
+    }
+    async fn layers(&self) -> Result<Vec<LayerInfo>, TileSourceError> {
+        let name = match &self.file {
+            RasterFile::Local(path) => path
+                .file_stem()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_default(),
+            RasterFile::Remote(reader) => reader.url.clone(),
+        };
+        Ok(vec![LayerInfo {
+            name,
+            geometry_type: None,
+            style: None,
+        }])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tiff::encoder::{colortype, TiffEncoder};
+
+    /// Write GeoTIFF with a red 8x8 image and a blue 4x4 overview
+    fn write_geotiff(path: &std::path::Path) {
+        let mut tiff = TiffEncoder::new(File::create(path).unwrap()).unwrap();
+        let mut image = tiff.new_image::<colortype::RGB8>(8, 8).unwrap();
+        let enc = image.encoder();
+        enc.write_tag(Tag::ModelPixelScaleTag, &[10.0f64, 10.0, 0.0][..])
+            .unwrap();
+        enc.write_tag(
+            Tag::ModelTiepointTag,
+            &[0.0f64, 0.0, 0.0, 1000.0, 2000.0, 0.0][..],
+        )
+        .unwrap();
+        // Projected CRS EPSG:2056
+        enc.write_tag(
+            Tag::GeoKeyDirectoryTag,
+            &[1u16, 1, 0, 1, 3072, 0, 1, 2056][..],
+        )
+        .unwrap();
+        image.write_data(&[255, 0, 0].repeat(64)).unwrap();
+        let mut overview = tiff.new_image::<colortype::RGB8>(4, 4).unwrap();
+        overview
+            .encoder()
+            .write_tag(Tag::NewSubfileType, 1u32)
+            .unwrap();
+        overview.write_data(&[0, 0, 255].repeat(16)).unwrap();
+    }
+
+    #[test]
+    fn render_levels() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.tif");
+        write_geotiff(&path);
+        let cfg = GeotiffSourceParamsCfg {
+            path: path.to_string_lossy().to_string(),
+            value_range: None,
+            nodata: None,
+            tile_size: None,
+        };
+        let source = GeotiffSource::open(&cfg, 2056).unwrap();
+        assert_eq!(source.levels.len(), 2);
+        assert_eq!(source.origin, (1000.0, 2000.0));
+
+        // Full resolution: left half of the tile covers the image
+        let extent = BoundingBox::new(960.0, 1920.0, 1040.0, 2000.0);
+        let rgba = source.resample(&extent, 8, 8).unwrap();
+        assert_eq!(&rgba[0..4], &[0, 0, 0, 0]);
+        assert_eq!(&rgba[16..20], &[255, 0, 0, 255]);
+
+        // Overview for lower resolutions
+        let extent = BoundingBox::new(1000.0, 1920.0, 1080.0, 2000.0);
+        let rgba = source.resample(&extent, 4, 4).unwrap();
+        assert_eq!(&rgba[0..4], &[0, 0, 255, 255]);
+
+        let png = source.render(&extent, 4, 4, &Format::Png).unwrap();
+        assert_eq!(&png[1..4], b"PNG");
+        assert!(source.render(&extent, 4, 4, &Format::Mvt).is_err());
+    }
+}
@@ -1,10 +1,13 @@
 //! Tile source implementations.
 
+pub mod custom;
+pub mod geotiff;
 pub mod mbtiles;
 mod mvt;
 pub mod pmtiles;
 pub mod postgis;
 mod postgis_queries;
+pub mod reproject;
 #[cfg(feature = "map-server")]
 pub mod wms_fcgi;
 pub mod wms_http;
@@ -16,13 +19,17 @@ use crate::store::mbtiles::MbtilesStore;
 use crate::store::pmtiles::PmtilesStoreReader;
 use async_trait::async_trait;
 use bbox_core::config::{error_exit, DatasourceCfg, NamedDatasourceCfg};
+use bbox_core::health::DependencyHealth;
 use bbox_core::{Format, NamedObjectStore, TileResponse};
 use dyn_clone::{clone_trait_object, DynClone};
 use geozero::error::GeozeroError;
+use log::warn;
 use martin_mbtiles::Metadata;
 use std::env;
+use std::sync::Arc;
 use tile_grid::{RegistryError, Tms, Xyz};
 use tilejson::TileJSON;
+use tokio::sync::Semaphore;
 
 #[derive(thiserror::Error, Debug)]
 pub enum TileSourceError {
@@ -32,8 +39,14 @@ pub enum TileSourceError {
     TileSourceTypeError(String),
     #[error("missing filter parameter")]
     FilterParamError,
+    #[error("invalid value of filter parameter `{0}`")]
+    FilterParamValueError(String),
     #[error("tile not found / out of bounds")]
     TileXyzError,
+    #[error("layer `{0}` exceeds tile limits")]
+    LayerOverflow(String),
+    #[error("datasource currently unavailable")]
+    SourceUnavailable,
     #[error(transparent)]
     RegistryError(#[from] RegistryError),
     #[error(transparent)]
@@ -52,10 +65,20 @@ pub enum TileSourceError {
     MvtEncodeError, // prost::error::EncodeError
     #[error(transparent)]
     WmsHttpError(#[from] reqwest::Error),
+    #[error("WMS error: {0}")]
+    WmsServiceError(String),
     #[error(transparent)]
     MbtilesError(#[from] martin_mbtiles::MbtError),
     #[error(transparent)]
     PmtilesError(#[from] ::pmtiles::error::Error),
+    #[error(transparent)]
+    GeotiffError(#[from] tiff::TiffError),
+    #[error(transparent)]
+    ImageError(#[from] image::ImageError),
+    #[error("raster error: {0}")]
+    RasterError(String),
+    #[error("tile post-processing error: {0}")]
+    PostProcessError(String),
 }
 
 #[derive(PartialEq, Clone, Debug)]
@@ -125,6 +148,8 @@ pub struct Datasources {
     pg_datasources: NamedObjectStore<postgis::Datasource>,
     // Store config for non-pooled sources
     config_sources: NamedObjectStore<DatasourceCfg>,
+    /// Request limits shared by all tilesets of a WMS
+    wms_limits: NamedObjectStore<Arc<Semaphore>>,
 }
 
 impl Datasources {
@@ -144,16 +169,36 @@ impl Datasources {
                         .await
                         .unwrap_or_else(error_exit),
                 ),
+                DatasourceCfg::WmsHttp(cfg) => {
+                    ds_handler
+                        .wms_limits
+                        .add(&named_ds.name, wms_http::WmsHttpSource::request_limit(cfg));
+                    ds_handler.config_sources.add(&named_ds.name, ds.clone())
+                }
                 _ => ds_handler.config_sources.add(&named_ds.name, ds.clone()),
             }
         }
         ds_handler
     }
+    /// PostGIS datasource with `name` or first PostGIS datasource
+    pub fn pg_datasource(&self, name: Option<&str>) -> Option<&postgis::Datasource> {
+        self.pg_datasources.get_or_default(name)
+    }
+    /// Check connectivity of database datasources
+    pub async fn check(&self) -> Vec<DependencyHealth> {
+        let mut checks = Vec::new();
+        for (name, ds) in self.pg_datasources.iter() {
+            let result = ds.check_connection().await;
+            checks.push(DependencyHealth::from_result("postgis", name, result));
+        }
+        checks
+    }
     /// Setup tile source instance
     pub async fn setup_tile_source(&self, cfg: &SourceParamCfg, tms: &Tms) -> Box<dyn TileRead> {
         // -- raster sources --
         // wms_fcgi::WmsFcgiSource,
         // wms_http::WmsHttpSource,
+        // geotiff::GeotiffSource,
         // // GdalData(GdalSource),
         // // RasterData(GeorasterSource),
         // -- vector sources --
@@ -178,10 +223,16 @@ impl Datasources {
                         "wms_proxy".to_string(),
                     ))
                 };
+                let limit = self
+                    .wms_limits
+                    .get(&cfg.source)
+                    .cloned()
+                    .unwrap_or_else(|| wms_http::WmsHttpSource::request_limit(provider));
                 Box::new(wms_http::WmsHttpSource::from_config(
                     provider,
                     cfg,
                     tms.crs().as_srid(),
+                    limit,
                 ))
             }
             #[cfg(feature = "map-server")]
@@ -205,6 +256,12 @@ impl Datasources {
                                 .clone(),
                         ))
                     });
+                if ds.is_lazy() {
+                    if let Err(e) = ds.check_connection().await {
+                        warn!("PostGIS datasource unavailable - setup will be retried: {e}");
+                        return Box::new(postgis::LazyPgSource::new(ds, pg_cfg, tms));
+                    }
+                }
                 Box::new(postgis::PgSource::create(ds, pg_cfg, tms).await)
             }
             SourceParamCfg::Mbtiles(cfg) => Box::new(
@@ -217,6 +274,18 @@ impl Datasources {
                     .await
                     .unwrap_or_else(error_exit),
             ),
+            SourceParamCfg::Geotiff(cfg) => Box::new(
+                geotiff::GeotiffSource::from_config(cfg, tms.crs().as_srid())
+                    .await
+                    .unwrap_or_else(error_exit),
+            ),
+            SourceParamCfg::Reproject(cfg) => Box::new(
+                reproject::ReprojectSource::from_config(cfg, tms.crs().as_srid())
+                    .unwrap_or_else(error_exit),
+            ),
+            SourceParamCfg::Custom(cfg) => custom::create_source(cfg, tms)
+                .await
+                .unwrap_or_else(error_exit),
         }
     }
 }
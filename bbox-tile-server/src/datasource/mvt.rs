@@ -1,18 +1,23 @@
-use crate::config::TileDiagnosticsCfg;
+use crate::config::{TileDiagnosticsCfg, ZoomAttributesCfg};
 use crate::datasource::TileSourceError;
 use crate::service::QueryExtent;
 use geozero::{mvt, mvt::Message, ToMvt};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
 use tile_grid::Xyz;
 
 /// MVT tile builder helper.
 pub struct MvtBuilder {
     tile: mvt::Tile,
+    /// Repaired and dropped invalid geometries per layer
+    invalid_geometries: Vec<(String, usize, usize)>,
 }
 
 impl MvtBuilder {
     pub fn new() -> Self {
         Self {
             tile: mvt::Tile::default(),
+            invalid_geometries: Vec::new(),
         }
     }
     pub fn new_layer(name: &str, tile_size: u32) -> MvtLayerBuilder {
@@ -25,14 +30,22 @@ impl MvtBuilder {
         MvtLayerBuilder {
             mvt_layer,
             tags: mvt::TagsBuilder::new(),
+            attributes: None,
         }
     }
-    pub fn push_layer(&mut self, layer: MvtLayerBuilder) {
-        let mut mvt_layer = layer.mvt_layer;
-        let (keys, values) = layer.tags.into_tags();
-        mvt_layer.keys = keys;
-        mvt_layer.values = values.into_iter().map(|v| v.into()).collect();
-        self.tile.layers.push(mvt_layer);
+    pub fn push_layer(&mut self, layer: mvt::tile::Layer) {
+        self.tile.layers.push(layer);
+    }
+    /// Record invalid geometries of a layer for the diagnostics layer
+    pub fn add_invalid_geometries(&mut self, layer: &str, repaired: usize, dropped: usize) {
+        self.invalid_geometries
+            .push((layer.to_string(), repaired, dropped));
+    }
+    /// Remove all layers except the diagnostics layers
+    pub fn retain_diagnostics_layers(&mut self) {
+        self.tile
+            .layers
+            .retain(|layer| layer.name.starts_with("diagnostics-"));
     }
     pub fn into_blob(self) -> Result<Vec<u8>, TileSourceError> {
         let mut buf = Vec::new();
@@ -47,9 +60,16 @@ impl MvtBuilder {
 pub struct MvtLayerBuilder {
     mvt_layer: mvt::tile::Layer,
     tags: mvt::TagsBuilder<String>,
+    /// Attributes included at tile zoom level
+    attributes: Option<(Arc<AttributeFilter>, u8)>,
 }
 
 impl MvtLayerBuilder {
+    /// Include only attributes of `filter` for zoom level `zoom`
+    pub fn with_attribute_filter(mut self, filter: Arc<AttributeFilter>, zoom: u8) -> Self {
+        self.attributes = Some((filter, zoom));
+        self
+    }
     /// Add key/value to feature and layer tag collection
     pub fn add_feature_attribute(
         &mut self,
@@ -57,6 +77,11 @@ impl MvtLayerBuilder {
         key: &str,
         mvt_value: mvt::tile::Value,
     ) -> Result<(), TileSourceError> {
+        if let Some((filter, zoom)) = &self.attributes {
+            if !filter.includes(key, *zoom) {
+                return Ok(());
+            }
+        }
         let (key_idx, val_idx) = self.tags.insert(
             key.to_string(),
             mvt_value
@@ -70,6 +95,179 @@ impl MvtLayerBuilder {
     pub fn push_feature(&mut self, mvt_feature: mvt::tile::Feature) {
         self.mvt_layer.features.push(mvt_feature);
     }
+    /// Layer with collected tags
+    pub fn into_layer(self) -> mvt::tile::Layer {
+        let mut mvt_layer = self.mvt_layer;
+        let (keys, values) = self.tags.into_tags();
+        mvt_layer.keys = keys;
+        mvt_layer.values = values.into_iter().map(|v| v.into()).collect();
+        mvt_layer
+    }
+}
+
+/// Zoom dependent inclusion of attributes
+#[derive(Clone, Debug, Default)]
+pub struct AttributeFilter {
+    /// Minimal zoom level of attributes
+    minzoom: HashMap<String, u8>,
+    /// Included attributes, ordered by minimal zoom level
+    lists: Vec<(u8, HashSet<String>)>,
+}
+
+impl AttributeFilter {
+    pub fn new(minzoom: &HashMap<String, u8>, lists: &[ZoomAttributesCfg]) -> Self {
+        let mut lists: Vec<_> = lists
+            .iter()
+            .map(|cfg| (cfg.minzoom, cfg.fields.iter().cloned().collect()))
+            .collect();
+        lists.sort_by_key(|(minzoom, _)| *minzoom);
+        AttributeFilter {
+            minzoom: minzoom.clone(),
+            lists,
+        }
+    }
+    pub fn is_empty(&self) -> bool {
+        self.minzoom.is_empty() && self.lists.is_empty()
+    }
+    /// Attribute `key` is included in tiles of zoom level `zoom`
+    pub fn includes(&self, key: &str, zoom: u8) -> bool {
+        if let Some(minzoom) = self.minzoom.get(key) {
+            if zoom < *minzoom {
+                return false;
+            }
+        }
+        self.lists
+            .iter()
+            .rev()
+            .find(|(minzoom, _)| zoom >= *minzoom)
+            .map(|(_, fields)| fields.contains(key))
+            .unwrap_or(true)
+    }
+}
+
+/// Feature count and size limits of a tile layer
+#[derive(Clone, Debug, Default)]
+pub struct LayerLimits {
+    pub max_features: Option<usize>,
+    pub max_bytes: Option<usize>,
+}
+
+impl LayerLimits {
+    pub fn exceeded(&self, layer: &mvt::tile::Layer) -> bool {
+        self.max_features
+            .map(|max| layer.features.len() > max)
+            .unwrap_or(false)
+            || self
+                .max_bytes
+                .map(|max| layer.encoded_len() > max)
+                .unwrap_or(false)
+    }
+    /// Remove the smallest features until the layer is within the limits.
+    /// Returns the number of removed features.
+    pub fn drop_smallest(&self, layer: &mut mvt::tile::Layer) -> usize {
+        let count = layer.features.len();
+        // Feature indices, largest first
+        let mut order: Vec<(usize, f64)> = layer
+            .features
+            .iter()
+            .map(feature_size)
+            .enumerate()
+            .collect();
+        order.sort_by(|a, b| b.1.total_cmp(&a.1));
+        order.truncate(self.max_features.unwrap_or(count));
+        if let Some(max_bytes) = self.max_bytes {
+            let mut size = layer.encoded_len()
+                - layer
+                    .features
+                    .iter()
+                    .map(feature_encoded_len)
+                    .sum::<usize>();
+            let mut keep = 0;
+            for (idx, _) in &order {
+                let feature_size = feature_encoded_len(&layer.features[*idx]);
+                if size + feature_size > max_bytes {
+                    break;
+                }
+                size += feature_size;
+                keep += 1;
+            }
+            order.truncate(keep);
+        }
+        // Keep the original feature order
+        let mut keep = vec![false; count];
+        for (idx, _) in order {
+            keep[idx] = true;
+        }
+        let mut idx = 0;
+        layer.features.retain(|_| {
+            idx += 1;
+            keep[idx - 1]
+        });
+        count - layer.features.len()
+    }
+}
+
+/// Encoded length of a feature within a layer (tag, length and message)
+fn feature_encoded_len(feature: &mvt::tile::Feature) -> usize {
+    let len = feature.encoded_len();
+    let varint_len = ((64 - (len as u64 | 1).leading_zeros() + 6) / 7) as usize;
+    1 + varint_len + len
+}
+
+/// Size of a feature geometry: area of polygons, length of lines or number of points
+fn feature_size(feature: &mvt::tile::Feature) -> f64 {
+    let zigzag = |v: u32| ((v >> 1) as i64) ^ -((v & 1) as i64);
+    let mut parts: Vec<Vec<(f64, f64)>> = Vec::new();
+    let (mut x, mut y) = (0i64, 0i64);
+    let geometry = &feature.geometry;
+    let mut i = 0;
+    while i < geometry.len() {
+        let command = geometry[i] & 0x7;
+        let count = (geometry[i] >> 3) as usize;
+        i += 1;
+        if command != 1 && command != 2 {
+            // ClosePath
+            continue;
+        }
+        for _ in 0..count {
+            let (Some(dx), Some(dy)) = (geometry.get(i), geometry.get(i + 1)) else {
+                break;
+            };
+            x += zigzag(*dx);
+            y += zigzag(*dy);
+            i += 2;
+            if command == 1 {
+                parts.push(Vec::new());
+            }
+            if let Some(part) = parts.last_mut() {
+                part.push((x as f64, y as f64));
+            }
+        }
+    }
+    match feature.r#type {
+        // Polygon: Sum of signed ring areas, holes have the opposite sign
+        Some(3) => parts
+            .iter()
+            .map(|ring| {
+                ring.iter()
+                    .zip(ring.iter().cycle().skip(1))
+                    .map(|(a, b)| a.0 * b.1 - b.0 * a.1)
+                    .sum::<f64>()
+                    / 2.0
+            })
+            .sum::<f64>()
+            .abs(),
+        // LineString
+        Some(2) => parts
+            .iter()
+            .map(|line| {
+                line.windows(2)
+                    .map(|w| ((w[1].0 - w[0].0).powi(2) + (w[1].1 - w[0].1).powi(2)).sqrt())
+                    .sum::<f64>()
+            })
+            .sum(),
+        _ => parts.iter().map(Vec::len).sum::<usize>() as f64,
+    }
 }
 
 impl MvtBuilder {
@@ -135,9 +333,21 @@ impl MvtBuilder {
                 mvt::TileValue::Uint(tl.2 as u64).into(),
             )?;
         }
+        for (name, repaired, dropped) in &self.invalid_geometries {
+            layer.add_feature_attribute(
+                &mut feat,
+                &format!("layer-{name}-repaired"),
+                mvt::TileValue::Uint(*repaired as u64).into(),
+            )?;
+            layer.add_feature_attribute(
+                &mut feat,
+                &format!("layer-{name}-dropped"),
+                mvt::TileValue::Uint(*dropped as u64).into(),
+            )?;
+        }
 
         layer.push_feature(feat);
-        self.push_layer(layer);
+        self.push_layer(layer.into_layer());
 
         let mut layer = MvtBuilder::new_layer("diagnostics-label", SIZE);
         let geom: geo_types::Geometry<f64> = geo_types::Point::new(SIZE_F / 2., SIZE_F / 2.).into();
@@ -168,8 +378,99 @@ impl MvtBuilder {
             mvt::TileValue::Double(extent.right).into(),
         )?;
         layer.push_feature(feat);
-        self.push_layer(layer);
+        self.push_layer(layer.into_layer());
 
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn square(size: f64) -> mvt::tile::Feature {
+        let geom: geo_types::Geometry<f64> = geo_types::Polygon::new(
+            geo_types::LineString::from(vec![
+                (0., 0.),
+                (0., size),
+                (size, size),
+                (size, 0.),
+                (0., 0.),
+            ]),
+            vec![],
+        )
+        .into();
+        geom.to_mvt_unscaled().unwrap()
+    }
+
+    #[test]
+    fn layer_limits() {
+        let mut layer = MvtBuilder::new_layer("squares", 4096);
+        for size in [10., 100., 1., 50.] {
+            layer.push_feature(square(size));
+        }
+        let mut layer = layer.into_layer();
+        assert_eq!(feature_size(&layer.features[1]), 10000.);
+
+        let limits = LayerLimits {
+            max_features: Some(2),
+            max_bytes: None,
+        };
+        assert!(limits.exceeded(&layer));
+        assert_eq!(limits.drop_smallest(&mut layer), 2);
+        assert!(!limits.exceeded(&layer));
+        // Largest features in original order
+        let sizes: Vec<_> = layer.features.iter().map(feature_size).collect();
+        assert_eq!(sizes, [10000., 2500.]);
+
+        let limits = LayerLimits {
+            max_features: None,
+            max_bytes: Some(layer.encoded_len() - 1),
+        };
+        assert!(limits.exceeded(&layer));
+        assert_eq!(limits.drop_smallest(&mut layer), 1);
+        assert!(!limits.exceeded(&layer));
+        assert_eq!(feature_size(&layer.features[0]), 10000.);
+    }
+
+    #[test]
+    fn zoom_attributes() {
+        let minzoom = HashMap::from([("name_de".to_string(), 10)]);
+        let lists = [
+            ZoomAttributesCfg {
+                minzoom: 12,
+                fields: vec![
+                    "class".to_string(),
+                    "name".to_string(),
+                    "name_de".to_string(),
+                ],
+            },
+            ZoomAttributesCfg {
+                minzoom: 4,
+                fields: vec!["class".to_string()],
+            },
+        ];
+        let filter = Arc::new(AttributeFilter::new(&minzoom, &lists));
+        assert!(filter.includes("name", 2));
+        assert!(!filter.includes("name", 11));
+        assert!(filter.includes("class", 11));
+        assert!(filter.includes("name", 12));
+        assert!(!filter.includes("name_de", 2));
+
+        let mut layer = MvtBuilder::new_layer("places", 4096).with_attribute_filter(filter, 8);
+        let mut feature = square(1.);
+        for key in ["class", "name"] {
+            let value = mvt::tile::Value {
+                string_value: Some(key.to_string()),
+                ..Default::default()
+            };
+            layer
+                .add_feature_attribute(&mut feature, key, value)
+                .unwrap();
+        }
+        layer.push_feature(feature);
+        let layer = layer.into_layer();
+        assert_eq!(layer.keys, vec!["class"]);
+        assert_eq!(layer.features[0].tags.len(), 2);
+    }
+}
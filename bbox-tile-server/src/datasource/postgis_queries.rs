@@ -1,10 +1,17 @@
-use crate::config::VectorLayerCfg;
+use crate::config::{
+    InvalidGeometriesCfg, LabelPositionCfg, QueryParamCfg, QueryParamTypeCfg, VectorLayerCfg,
+};
 use crate::datasource::postgis::{FieldInfo, FieldTypeInfo};
 use log::{info, warn};
 use regex::Regex;
 use sqlx::{postgres::PgTypeInfo, TypeInfo};
 use std::collections::BTreeSet;
 
+/// Column name of polygon label points
+pub const LABEL_POINT_COLUMN: &str = "__label_point";
+/// Column name of source geometry validity flag
+pub const INVALID_GEOMETRY_COLUMN: &str = "__invalid_geometry";
+
 #[derive(Clone, Debug)]
 pub struct SqlQuery {
     pub sql: String,
@@ -24,7 +31,11 @@ pub enum QueryParam {
 
 impl SqlQuery {
     /// Initial select query for column type detection
-    pub fn build_field_query(layer: &VectorLayerCfg, user_query: Option<&String>) -> Self {
+    pub fn build_field_query(
+        layer: &VectorLayerCfg,
+        zoom: u8,
+        user_query: Option<&String>,
+    ) -> Self {
         if let Some(sql) = user_query {
             // Replace vars with valid SQL
             let bbox_expr = "ST_MakeEnvelope($1,$2,$3,$4,3857)";
@@ -32,10 +43,7 @@ impl SqlQuery {
         } else {
             let sql = format!(
                 "SELECT * FROM {}",
-                layer
-                    .table_name
-                    .as_ref()
-                    .expect("query and table_name undefined")
+                layer.table(zoom).expect("query and table_name undefined")
             );
             SqlQuery {
                 sql,
@@ -60,8 +68,19 @@ impl SqlQuery {
         } else {
             build_geom_expr(layer, geom_name, grid_srid, zoom)
         };
-        let select_list = build_select_list(geom_expr, data_columns);
-        let intersect_clause = format!(" WHERE {geom_name} && !bbox!");
+        let mut select_list = build_select_list(geom_expr, data_columns);
+        if layer.label_points {
+            select_list.push(',');
+            select_list.push_str(&build_label_expr(layer, geom_name, grid_srid, postgis2));
+        }
+        if layer.invalid_geometries != InvalidGeometriesCfg::Keep {
+            let source_geom = linearize_curves(layer, geom_name);
+            select_list.push_str(&format!(
+                ",NOT ST_IsValid({source_geom}) AS {INVALID_GEOMETRY_COLUMN}"
+            ));
+        }
+        let intersect_condition = format!("{geom_name} && !bbox!");
+        let mut conditions = Vec::new();
 
         if let Some(user_query) = user_query {
             // user query
@@ -70,18 +89,23 @@ impl SqlQuery {
                 && user_query.contains("!y!")
                 && user_query.contains("!zoom!");
             if !user_query.contains("!bbox!") && !is_xyz_query {
-                sqlquery.push_str(&intersect_clause);
+                conditions.push(intersect_condition);
             }
         } else {
             // automatic query
             sqlquery = format!(
-                "SELECT {select_list} FROM {}{intersect_clause}",
-                layer
-                    .table_name
-                    .as_ref()
-                    .expect("query and table_name undefined")
+                "SELECT {select_list} FROM {}",
+                layer.table(zoom).expect("query and table_name undefined")
             );
+            conditions.push(intersect_condition);
         };
+        if let Some(min_area) = layer.min_area(zoom) {
+            conditions.extend(build_area_condition(layer, geom_name, grid_srid, min_area));
+        }
+        if !conditions.is_empty() {
+            sqlquery.push_str(" WHERE ");
+            sqlquery.push_str(&conditions.join(" AND "));
+        }
 
         let bbox_expr = build_bbox_expr(layer, grid_srid, layer.buffer_size);
         // !bbox_unbuffered! replacement expression for ST_AsMVTGeom
@@ -138,7 +162,8 @@ impl SqlQuery {
         SqlQuery { sql, params }
     }
 
-    pub fn param_types(&self) -> Vec<PgTypeInfo> {
+    /// Types of query parameters. Variables without declaration are passed as text.
+    pub fn param_types(&self, query_params: &[QueryParamCfg]) -> Vec<PgTypeInfo> {
         self.params
             .iter()
             .flat_map(|param| match param {
@@ -154,28 +179,70 @@ impl SqlQuery {
                 QueryParam::PixelWidth | QueryParam::ScaleDenominator => {
                     vec![PgTypeInfo::with_name("FLOAT8")]
                 }
-                QueryParam::QueryField(_) => vec![PgTypeInfo::with_name("VARCHAR")],
+                QueryParam::QueryField(field) => {
+                    let type_ = query_params
+                        .iter()
+                        .find(|param| &param.name == field)
+                        .map(|param| param.type_)
+                        .unwrap_or_default();
+                    vec![PgTypeInfo::with_name(match type_ {
+                        QueryParamTypeCfg::Text => "VARCHAR",
+                        QueryParamTypeCfg::Integer => "INT8",
+                        QueryParamTypeCfg::Number => "FLOAT8",
+                        QueryParamTypeCfg::Boolean => "BOOL",
+                    })]
+                }
             })
             .collect()
     }
 }
 
-/// Build geometry selection expression for feature query.
-fn build_geom_expr(layer: &VectorLayerCfg, geom_name: &str, grid_srid: i32, zoom: u8) -> String {
-    let layer_srid = layer.srid.unwrap_or(0);
-    let mut geom_expr = String::from(geom_name as &str);
-
-    // Convert special geometry types like curves
+/// Convert special geometry types like curves.
+fn linearize_curves(layer: &VectorLayerCfg, geom_name: &str) -> String {
     match layer
         .geometry_type
         .as_ref()
         .unwrap_or(&"GEOMETRY".to_string()) as &str
     {
-        "CURVEPOLYGON" | "COMPOUNDCURVE" => {
-            geom_expr = format!("ST_CurveToLine({geom_expr})");
+        "CURVEPOLYGON" | "COMPOUNDCURVE" => format!("ST_CurveToLine({geom_name})"),
+        _ => geom_name.to_string(),
+    }
+}
+
+/// Check validity of source geometry and repair or omit invalid geometries.
+fn build_valid_geom_expr(layer: &VectorLayerCfg, geom_expr: String) -> String {
+    match layer.invalid_geometries {
+        InvalidGeometriesCfg::Keep => geom_expr,
+        InvalidGeometriesCfg::Repair => {
+            // Keep geometry type of layer
+            let repaired = match layer
+                .geometry_type
+                .as_ref()
+                .unwrap_or(&"GEOMETRY".to_string()) as &str
+            {
+                "POINT" | "MULTIPOINT" => {
+                    format!("ST_CollectionExtract(ST_MakeValid({geom_expr}),1)")
+                }
+                "LINESTRING" | "MULTILINESTRING" | "COMPOUNDCURVE" => {
+                    format!("ST_CollectionExtract(ST_MakeValid({geom_expr}),2)")
+                }
+                "POLYGON" | "MULTIPOLYGON" | "CURVEPOLYGON" => {
+                    format!("ST_CollectionExtract(ST_MakeValid({geom_expr}),3)")
+                }
+                _ => format!("ST_MakeValid({geom_expr})"),
+            };
+            format!("CASE WHEN ST_IsValid({geom_expr}) THEN {geom_expr} ELSE {repaired} END")
         }
-        _ => {}
-    };
+        InvalidGeometriesCfg::Drop => {
+            format!("CASE WHEN ST_IsValid({geom_expr}) THEN {geom_expr} END")
+        }
+    }
+}
+
+/// Build geometry selection expression for feature query.
+fn build_geom_expr(layer: &VectorLayerCfg, geom_name: &str, grid_srid: i32, zoom: u8) -> String {
+    let layer_srid = layer.srid.unwrap_or(0);
+    let mut geom_expr = build_valid_geom_expr(layer, linearize_curves(layer, geom_name));
 
     // Transform geometry to grid SRID
     if layer_srid <= 0 {
@@ -209,7 +276,7 @@ fn build_geom_expr(layer: &VectorLayerCfg, geom_name: &str, grid_srid: i32, zoom
                 layer.tolerance(zoom)
             ),
             "POLYGON" | "MULTIPOLYGON" | "CURVEPOLYGON" => {
-                if layer.make_valid {
+                if layer.make_valid(zoom) {
                     format!(
                     "ST_CollectionExtract(ST_Multi(ST_MakeValid(ST_SnapToGrid({geom_expr}, {}))),3)::geometry(MULTIPOLYGON,{layer_srid})",
                     layer.tolerance(zoom)
@@ -245,23 +312,11 @@ fn build_geom_expr_postgis2(
     zoom: u8,
 ) -> String {
     let layer_srid = layer.srid.unwrap_or(0);
-    let mut geom_expr = String::from(geom_name as &str);
-
-    // Convert special geometry types like curves
-    match layer
-        .geometry_type
-        .as_ref()
-        .unwrap_or(&"GEOMETRY".to_string()) as &str
-    {
-        "CURVEPOLYGON" | "COMPOUNDCURVE" => {
-            geom_expr = format!("ST_CurveToLine({geom_expr})");
-        }
-        _ => {}
-    };
+    let mut geom_expr = build_valid_geom_expr(layer, linearize_curves(layer, geom_name));
 
     // Clipping
     if layer.buffer_size.is_some() {
-        let valid_geom = if layer.make_valid {
+        let valid_geom = if layer.make_valid(zoom) {
             format!("ST_MakeValid({geom_expr})")
         } else {
             geom_expr.clone()
@@ -309,7 +364,7 @@ fn build_geom_expr_postgis2(
                 layer.tolerance(zoom)
             ),
             "POLYGON" | "MULTIPOLYGON" | "CURVEPOLYGON" => {
-                if layer.make_valid {
+                if layer.make_valid(zoom) {
                     format!(
                     "ST_CollectionExtract(ST_Multi(ST_MakeValid(ST_SnapToGrid({geom_expr}, {}))),3)::geometry(MULTIPOLYGON,{layer_srid})",
                     layer.tolerance(zoom)
@@ -345,13 +400,48 @@ fn build_geom_expr_postgis2(
         }
     }
 
-    if geom_expr.starts_with("ST_") || geom_expr.starts_with("COALESCE") {
+    if geom_expr != geom_name {
         geom_expr = format!("{geom_expr} AS {geom_name}");
     }
 
     geom_expr
 }
 
+/// Build label point expression for feature query.
+///
+/// Label points outside the tile are NULL, to avoid duplicate labels in neighbouring tiles.
+fn build_label_expr(
+    layer: &VectorLayerCfg,
+    geom_name: &str,
+    grid_srid: i32,
+    postgis2: bool,
+) -> String {
+    let layer_srid = layer.srid.unwrap_or(0);
+    let mut label_expr = linearize_curves(layer, geom_name);
+    label_expr = match layer.label_position {
+        LabelPositionCfg::PointOnSurface => format!("ST_PointOnSurface({label_expr})"),
+        LabelPositionCfg::PoleOfInaccessibility => {
+            format!("(ST_MaximumInscribedCircle({label_expr})).center")
+        }
+    };
+    // Transform label point to grid SRID
+    if layer_srid <= 0 || (layer_srid != grid_srid && layer.no_transform) {
+        label_expr = format!("ST_SetSRID({label_expr},{grid_srid})");
+    } else if layer_srid != grid_srid {
+        label_expr = format!("ST_Transform({label_expr},{grid_srid})");
+    }
+    if postgis2 {
+        format!(
+            "CASE WHEN ST_Intersects({label_expr}, !bbox_unbuffered!) THEN {label_expr} END AS {LABEL_POINT_COLUMN}"
+        )
+    } else {
+        let tile_size = layer.tile_size;
+        format!(
+            "ST_AsMvtGeom({label_expr}, !bbox_unbuffered!, {tile_size}, 0, true) AS {LABEL_POINT_COLUMN}"
+        )
+    }
+}
+
 /// Build select list expressions for feature query.
 fn build_select_list(geom_expr: String, data_columns: &[FieldInfo]) -> String {
     let cols: Vec<String> = data_columns
@@ -375,6 +465,35 @@ fn build_select_list(geom_expr: String, data_columns: &[FieldInfo]) -> String {
     cols.join(",")
 }
 
+/// Build polygon area filter for feature query.
+fn build_area_condition(
+    layer: &VectorLayerCfg,
+    geom_name: &str,
+    grid_srid: i32,
+    min_area: &str,
+) -> Option<String> {
+    let layer_srid = layer.srid.unwrap_or(0);
+    // Area in grid units
+    let area_geom = if layer_srid > 0 && layer_srid != grid_srid && !layer.no_transform {
+        format!("ST_Transform({geom_name},{grid_srid})")
+    } else {
+        geom_name.to_string()
+    };
+    match layer
+        .geometry_type
+        .as_ref()
+        .unwrap_or(&"GEOMETRY".to_string()) as &str
+    {
+        "POLYGON" | "MULTIPOLYGON" | "CURVEPOLYGON" => {
+            Some(format!("ST_Area({area_geom}) >= {min_area}"))
+        }
+        "GEOMETRY" => Some(format!(
+            "(ST_Dimension({geom_name}) < 2 OR ST_Area({area_geom}) >= {min_area})"
+        )),
+        _ => None, // Points and lines have no area
+    }
+}
+
 /// Build !bbox! replacement expression for feature query.
 fn build_bbox_expr(layer: &VectorLayerCfg, grid_srid: i32, buffer_size: Option<u32>) -> String {
     let layer_srid = layer.srid.unwrap_or(grid_srid); // we assume grid srid as default
@@ -404,7 +523,8 @@ fn build_bbox_expr(layer: &VectorLayerCfg, grid_srid: i32, buffer_size: Option<u
 #[cfg(test)]
 mod test {
     use super::*;
-    use crate::config::VectorLayerQueryCfg;
+    use crate::config::{TileOverflowCfg, VectorLayerQueryCfg};
+    use std::collections::HashMap;
 
     fn layer_cfg() -> (VectorLayerCfg, Vec<FieldInfo>) {
         let layer = VectorLayerCfg {
@@ -414,8 +534,12 @@ mod test {
             srid: Some(3857),
             no_transform: false,
             fid_field: None,
+            dedupe_by: None,
             table_name: Some("osm_place_point".to_string()),
             query_limit: None,
+            max_features_per_tile: None,
+            max_tile_bytes: None,
+            overflow: TileOverflowCfg::default(),
             queries: Vec::new(),
             minzoom: None,
             maxzoom: None,
@@ -424,6 +548,12 @@ mod test {
             tolerance: "!pixel_width!/2".to_string(),
             buffer_size: None,
             make_valid: false,
+            min_area: None,
+            invalid_geometries: InvalidGeometriesCfg::default(),
+            attributes_minzoom: HashMap::new(),
+            attributes: Vec::new(),
+            label_points: false,
+            label_position: LabelPositionCfg::default(),
             shift_longitude: false,
         };
         let fields = vec![FieldInfo {
@@ -561,6 +691,39 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_generalization() {
+        let (mut layer, fields) = layer_cfg();
+        let postgis2 = false;
+        layer.geometry_type = Some("POLYGON".to_string());
+        layer.min_area = Some("16*!pixel_width!*!pixel_width!".to_string());
+        assert_eq!(SqlQuery::build_tile_query(&layer, "geometry", &fields, 3857, 10, None, postgis2).sql,
+               "SELECT ST_AsMvtGeom(geometry, ST_MakeEnvelope($1,$2,$3,$4,3857), 256, 0, false) AS geometry FROM osm_place_point WHERE geometry && ST_MakeEnvelope($1,$2,$3,$4,3857) AND ST_Area(geometry) >= 16*$5::FLOAT8*$5::FLOAT8");
+        layer.queries = vec![VectorLayerQueryCfg {
+            minzoom: 12,
+            maxzoom: None,
+            simplify: Some(true),
+            tolerance: None,
+            make_valid: Some(true),
+            min_area: Some("4".to_string()),
+            table_name: None,
+            sql: None,
+        }];
+        assert_eq!(SqlQuery::build_tile_query(&layer, "geometry", &fields, 3857, 14, None, postgis2).sql,
+               "SELECT ST_AsMvtGeom(ST_CollectionExtract(ST_Multi(ST_MakeValid(ST_SnapToGrid(geometry, $5::FLOAT8/2))),3)::geometry(MULTIPOLYGON,3857), ST_MakeEnvelope($1,$2,$3,$4,3857), 256, 0, false) AS geometry FROM osm_place_point WHERE geometry && ST_MakeEnvelope($1,$2,$3,$4,3857) AND ST_Area(geometry) >= 4");
+        layer.geometry_type = None;
+        layer.srid = Some(2056);
+        assert_eq!(SqlQuery::build_tile_query(&layer, "geometry", &fields, 3857, 14, None, postgis2).sql,
+               "SELECT ST_AsMvtGeom(ST_Transform(geometry,3857), ST_MakeEnvelope($1,$2,$3,$4,3857), 256, 0, false) AS geometry FROM osm_place_point WHERE geometry && ST_Transform(ST_Segmentize(ST_MakeEnvelope($1,$2,$3,$4,3857), $5::FLOAT8), 2056) AND (ST_Dimension(geometry) < 2 OR ST_Area(ST_Transform(geometry,3857)) >= 4)");
+        layer.geometry_type = Some("LINESTRING".to_string());
+        layer.srid = Some(3857);
+        layer.queries.clear();
+        assert_eq!(
+            SqlQuery::build_tile_query(&layer, "geometry", &fields, 3857, 10, None, postgis2).sql,
+            "SELECT ST_AsMvtGeom(geometry, ST_MakeEnvelope($1,$2,$3,$4,3857), 256, 0, false) AS geometry FROM osm_place_point WHERE geometry && ST_MakeEnvelope($1,$2,$3,$4,3857)"
+        );
+    }
+
     #[test]
     fn test_clipping_pg2() {
         let (mut layer, fields) = layer_cfg();
@@ -584,6 +747,35 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_label_points() {
+        let (mut layer, fields) = layer_cfg();
+        layer.geometry_type = Some("POLYGON".to_string());
+        layer.label_points = true;
+        assert_eq!(SqlQuery::build_tile_query(&layer, "geometry", &fields, 3857, 10, None, false).sql,
+               "SELECT ST_AsMvtGeom(geometry, ST_MakeEnvelope($1,$2,$3,$4,3857), 256, 0, false) AS geometry,ST_AsMvtGeom(ST_PointOnSurface(geometry), ST_MakeEnvelope($1,$2,$3,$4,3857), 256, 0, true) AS __label_point FROM osm_place_point WHERE geometry && ST_MakeEnvelope($1,$2,$3,$4,3857)");
+        layer.srid = Some(2056);
+        layer.label_position = LabelPositionCfg::PoleOfInaccessibility;
+        assert_eq!(SqlQuery::build_tile_query(&layer, "geometry", &fields, 3857, 10, None, true).sql,
+               "SELECT ST_Transform(ST_Multi(geometry),3857) AS geometry,CASE WHEN ST_Intersects(ST_Transform((ST_MaximumInscribedCircle(geometry)).center,3857), ST_MakeEnvelope($1,$2,$3,$4,3857)) THEN ST_Transform((ST_MaximumInscribedCircle(geometry)).center,3857) END AS __label_point FROM osm_place_point WHERE geometry && ST_Transform(ST_Segmentize(ST_MakeEnvelope($1,$2,$3,$4,3857), $5::FLOAT8), 2056)");
+    }
+
+    #[test]
+    fn test_invalid_geometries() {
+        let (mut layer, fields) = layer_cfg();
+        layer.geometry_type = Some("POLYGON".to_string());
+        layer.invalid_geometries = InvalidGeometriesCfg::Repair;
+        assert_eq!(SqlQuery::build_tile_query(&layer, "geometry", &fields, 3857, 10, None, false).sql,
+               "SELECT ST_AsMvtGeom(CASE WHEN ST_IsValid(geometry) THEN geometry ELSE ST_CollectionExtract(ST_MakeValid(geometry),3) END, ST_MakeEnvelope($1,$2,$3,$4,3857), 256, 0, false) AS geometry,NOT ST_IsValid(geometry) AS __invalid_geometry FROM osm_place_point WHERE geometry && ST_MakeEnvelope($1,$2,$3,$4,3857)");
+        layer.invalid_geometries = InvalidGeometriesCfg::Drop;
+        assert_eq!(SqlQuery::build_tile_query(&layer, "geometry", &fields, 3857, 10, None, true).sql,
+               "SELECT ST_Multi(CASE WHEN ST_IsValid(geometry) THEN geometry END) AS geometry,NOT ST_IsValid(geometry) AS __invalid_geometry FROM osm_place_point WHERE geometry && ST_MakeEnvelope($1,$2,$3,$4,3857)");
+        layer.geometry_type = None;
+        layer.buffer_size = Some(0);
+        assert_eq!(SqlQuery::build_tile_query(&layer, "geometry", &fields, 3857, 10, None, true).sql,
+               "SELECT ST_Intersection(CASE WHEN ST_IsValid(geometry) THEN geometry END,ST_MakeEnvelope($1,$2,$3,$4,3857)) AS geometry,NOT ST_IsValid(geometry) AS __invalid_geometry FROM osm_place_point WHERE geometry && ST_MakeEnvelope($1,$2,$3,$4,3857)");
+    }
+
     #[test]
     fn test_user_queries() {
         let (mut layer, fields) = layer_cfg();
@@ -592,6 +784,9 @@ mod test {
             maxzoom: Some(22),
             simplify: None,
             tolerance: None,
+            make_valid: None,
+            min_area: None,
+            table_name: None,
             sql: Some(String::from("SELECT geometry FROM osm_place_point")),
         }];
         let postgis2 = false;
@@ -604,6 +799,9 @@ mod test {
             maxzoom: Some(22),
             simplify: None,
             tolerance: None,
+            make_valid: None,
+            min_area: None,
+            table_name: None,
             sql: Some(String::from(
                 "SELECT * FROM osm_place_point WHERE name='Bern'",
             )),
@@ -621,6 +819,9 @@ mod test {
             maxzoom: Some(22),
             simplify: None,
             tolerance: None,
+            make_valid: None,
+            min_area: None,
+            table_name: None,
             sql: Some(String::from(
                 "SELECT geom FROM prepared_tiles WHERE x=!x! AND y=!y! and z=!zoom!",
             )),
@@ -640,6 +841,9 @@ mod test {
             maxzoom: Some(22),
             simplify: None,
             tolerance: None,
+            make_valid: None,
+            min_area: None,
+            table_name: None,
             sql: Some(String::from(
                 "SELECT geometry FROM osm_place_point WHERE col1=!colval1! AND col2=!colval2!",
             )),
@@ -653,6 +857,9 @@ mod test {
             maxzoom: Some(22),
             simplify: None,
             tolerance: None,
+            make_valid: None,
+            min_area: None,
+            table_name: None,
             sql: Some(String::from(
                 "SELECT geometry FROM osm_place_point WHERE col1=!colval! OR col2=!colval!",
             )),
@@ -661,4 +868,87 @@ mod test {
                    .sql,
                "SELECT ST_AsMvtGeom(geometry, ST_MakeEnvelope($1,$2,$3,$4,3857), 256, 0, false) AS geometry FROM (SELECT geometry FROM osm_place_point WHERE col1=$5 OR col2=$5) AS _q WHERE geometry && ST_MakeEnvelope($1,$2,$3,$4,3857)");
     }
+
+    #[test]
+    fn test_query_param_types() {
+        let (mut layer, fields) = layer_cfg();
+        layer.queries = vec![VectorLayerQueryCfg {
+            minzoom: 0,
+            maxzoom: Some(22),
+            simplify: None,
+            tolerance: None,
+            make_valid: None,
+            min_area: None,
+            table_name: None,
+            sql: Some(String::from(
+                "SELECT geometry FROM osm_place_point WHERE category=!category! AND population>=!population!",
+            )),
+        }];
+        let query = SqlQuery::build_tile_query(
+            &layer,
+            "geometry",
+            &fields,
+            3857,
+            10,
+            layer.queries[0].sql.as_ref(),
+            false,
+        );
+        let query_params = vec![QueryParamCfg {
+            name: "population".to_string(),
+            type_: QueryParamTypeCfg::Integer,
+            default: Some("0".to_string()),
+        }];
+        let types: Vec<_> = query
+            .param_types(&query_params)
+            .iter()
+            .map(|t| t.name().to_string())
+            .collect();
+        assert_eq!(
+            types,
+            ["FLOAT8", "FLOAT8", "FLOAT8", "FLOAT8", "VARCHAR", "INT8"]
+        );
+    }
+
+    #[test]
+    fn test_generalized_tables() {
+        let (mut layer, fields) = layer_cfg();
+        let query_cfg =
+            |minzoom, maxzoom, table_name: Option<&str>, sql: Option<&str>| VectorLayerQueryCfg {
+                minzoom,
+                maxzoom,
+                simplify: None,
+                tolerance: None,
+                make_valid: None,
+                min_area: None,
+                table_name: table_name.map(str::to_string),
+                sql: sql.map(str::to_string),
+            };
+        layer.queries = vec![
+            query_cfg(0, Some(2), Some("ne_110m_places"), None),
+            query_cfg(3, Some(4), None, Some("SELECT geometry FROM ne_50m_places")),
+            query_cfg(5, Some(8), Some("ne_10m_places"), None),
+        ];
+        assert_eq!(layer.zoom_steps(), vec![0, 3, 5, 9]);
+        assert_eq!(layer.table(1), Some(&"ne_110m_places".to_string()));
+        assert!(layer.query(1).is_none());
+        assert!(layer.query(4).is_some());
+        assert_eq!(layer.table(6), Some(&"ne_10m_places".to_string()));
+        assert!(layer.query(6).is_none());
+        // Full resolution table above configured zoom ranges
+        assert_eq!(layer.table(12), Some(&"osm_place_point".to_string()));
+
+        let tile_query = |zoom| {
+            let user_query = layer.query(zoom);
+            SqlQuery::build_tile_query(&layer, "geometry", &fields, 3857, zoom, user_query, false)
+                .sql
+        };
+        assert!(tile_query(0).contains(" FROM ne_110m_places WHERE "));
+        assert!(tile_query(3).contains(" FROM (SELECT geometry FROM ne_50m_places) AS _q "));
+        assert!(tile_query(5).contains(" FROM ne_10m_places WHERE "));
+        assert!(tile_query(9).contains(" FROM osm_place_point WHERE "));
+        assert_eq!(
+            SqlQuery::build_field_query(&layer, 5, layer.query(5)).sql,
+            "SELECT * FROM ne_10m_places"
+        );
+    }
 }
@@ -0,0 +1,526 @@
+//! Raster tiles reprojected from a tileset in another grid.
+//!
+//! Source tiles are requested with cache lookup and warped into the grid of
+//! the reprojected tileset. Coordinates are transformed with built-in formulas
+//! for WGS 84, Web Mercator and the Swiss reference systems LV95 and LV03.
+
+use crate::config::{ReprojectSourceParamsCfg, ResamplingCfg};
+use crate::datasource::{
+    wms_fcgi::HttpRequestParams, LayerInfo, SourceType, TileRead, TileSourceError,
+};
+use crate::filter_params::FilterParams;
+use crate::raster_encoding::encode_rgba;
+use crate::service::{ServiceError, TileService};
+use async_trait::async_trait;
+use bbox_core::{Compression, Format, TileResponse};
+use std::f64::consts::PI;
+use std::io::Cursor;
+use std::num::NonZeroU16;
+use tile_grid::{BoundingBox, Tms, Xyz};
+use tilejson::{tilejson, TileJSON};
+
+/// Spatial reference systems supported for reprojection
+const SUPPORTED_SRIDS: [i32; 4] = [4326, 3857, 2056, 21781];
+/// Maximal number of source tiles used for one reprojected tile
+const MAX_SOURCE_TILES: usize = 64;
+/// Number of sample points per tile edge for computing the source extent
+const EDGE_SAMPLES: u32 = 8;
+/// Earth radius of Web Mercator
+const EARTH_RADIUS: f64 = 6378137.0;
+/// Latitude limit of Web Mercator
+const MAX_LAT: f64 = 85.051129;
+
+#[derive(Clone, Debug)]
+pub struct ReprojectSource {
+    /// Name of source tileset
+    tileset: String,
+    resampling: ResamplingCfg,
+    tile_size: Option<NonZeroU16>,
+}
+
+impl ReprojectSource {
+    pub fn from_config(cfg: &ReprojectSourceParamsCfg, srid: i32) -> Result<Self, TileSourceError> {
+        check_srid(srid)?;
+        Ok(ReprojectSource {
+            tileset: cfg.tileset.clone(),
+            resampling: cfg.resampling,
+            tile_size: cfg.tile_size,
+        })
+    }
+}
+
+fn check_srid(srid: i32) -> Result<(), TileSourceError> {
+    if SUPPORTED_SRIDS.contains(&srid) {
+        Ok(())
+    } else {
+        Err(TileSourceError::RasterError(format!(
+            "reprojection of EPSG:{srid} not supported"
+        )))
+    }
+}
+
+/// Transform geographic coordinates (longitude, latitude in degrees) into `srid`
+fn from_wgs84(srid: i32, lon: f64, lat: f64) -> (f64, f64) {
+    match srid {
+        3857 => (
+            EARTH_RADIUS * lon.to_radians(),
+            EARTH_RADIUS
+                * (PI / 4.0 + lat.clamp(-MAX_LAT, MAX_LAT).to_radians() / 2.0)
+                    .tan()
+                    .ln(),
+        ),
+        2056 | 21781 => {
+            // Approximate formulas of swisstopo (accuracy about 1m)
+            let phi = (lat * 3600.0 - 169028.66) / 10000.0;
+            let lambda = (lon * 3600.0 - 26782.5) / 10000.0;
+            let e = 2600072.37 + 211455.93 * lambda
+                - 10938.51 * lambda * phi
+                - 0.36 * lambda * phi.powi(2)
+                - 44.54 * lambda.powi(3);
+            let n = 1200147.07 + 308807.95 * phi + 3745.25 * lambda.powi(2) + 76.63 * phi.powi(2)
+                - 194.56 * lambda.powi(2) * phi
+                + 119.79 * phi.powi(3);
+            if srid == 21781 {
+                (e - 2000000.0, n - 1000000.0)
+            } else {
+                (e, n)
+            }
+        }
+        _ => (lon, lat),
+    }
+}
+
+/// Transform coordinates in `srid` into geographic coordinates (longitude, latitude in degrees)
+fn to_wgs84(srid: i32, x: f64, y: f64) -> (f64, f64) {
+    match srid {
+        3857 => (
+            (x / EARTH_RADIUS).to_degrees(),
+            (2.0 * (y / EARTH_RADIUS).exp().atan() - PI / 2.0).to_degrees(),
+        ),
+        2056 | 21781 => {
+            let (e, n) = if srid == 21781 {
+                (x + 2000000.0, y + 1000000.0)
+            } else {
+                (x, y)
+            };
+            let y = (e - 2600000.0) / 1000000.0;
+            let x = (n - 1200000.0) / 1000000.0;
+            let lambda = 2.6779094 + 4.728982 * y + 0.791484 * y * x + 0.1306 * y * x.powi(2)
+                - 0.0436 * y.powi(3);
+            let phi = 16.9023892 + 3.238272 * x
+                - 0.270978 * y.powi(2)
+                - 0.002528 * x.powi(2)
+                - 0.0447 * y.powi(2) * x
+                - 0.0140 * x.powi(3);
+            (lambda * 100.0 / 36.0, phi * 100.0 / 36.0)
+        }
+        _ => (x, y),
+    }
+}
+
+/// Transform coordinates between supported spatial reference systems
+fn transform(from: i32, to: i32, x: f64, y: f64) -> (f64, f64) {
+    if from == to {
+        return (x, y);
+    }
+    let (lon, lat) = to_wgs84(from, x, y);
+    from_wgs84(to, lon, lat)
+}
+
+/// Bounding box of an extent transformed into another spatial reference system
+fn transform_extent(from: i32, to: i32, extent: &BoundingBox) -> BoundingBox {
+    let mut bbox = BoundingBox::new(f64::MAX, f64::MAX, f64::MIN, f64::MIN);
+    for i in 0..=EDGE_SAMPLES {
+        let fx = extent.left + (extent.right - extent.left) * i as f64 / EDGE_SAMPLES as f64;
+        let fy = extent.bottom + (extent.top - extent.bottom) * i as f64 / EDGE_SAMPLES as f64;
+        for (x, y) in [
+            (fx, extent.bottom),
+            (fx, extent.top),
+            (extent.left, fy),
+            (extent.right, fy),
+        ] {
+            let (x, y) = transform(from, to, x, y);
+            bbox.left = bbox.left.min(x);
+            bbox.bottom = bbox.bottom.min(y);
+            bbox.right = bbox.right.max(x);
+            bbox.top = bbox.top.max(y);
+        }
+    }
+    bbox
+}
+
+/// Geographic bounds of an extent in `srid`, if the reference system is supported
+pub(crate) fn wgs84_bounds(srid: i32, extent: &BoundingBox) -> Option<BoundingBox> {
+    check_srid(srid).ok()?;
+    Some(transform_extent(srid, 4326, extent))
+}
+
+/// Source tiles covering an extent
+struct TileRange {
+    z: u8,
+    cols: (u64, u64),
+    rows: (u64, u64),
+}
+
+impl TileRange {
+    fn len(&self) -> usize {
+        ((self.cols.1 - self.cols.0 + 1) * (self.rows.1 - self.rows.0 + 1)) as usize
+    }
+}
+
+/// Select the lowest source zoom level which is at least as detailed as `resolution`
+/// and covers `extent` with a limited number of tiles.
+fn source_tiles(tms: &Tms, extent: &BoundingBox, resolution: f64) -> TileRange {
+    let level_resolution = |z: u8| {
+        let bounds = tms.xy_bounds(&Xyz::new(0, 0, z));
+        let tile_width = tms.matrix(z).as_ref().tile_width.get() as f64;
+        (bounds.right - bounds.left) / tile_width
+    };
+    let maxzoom = tms.maxzoom();
+    let mut z = (0..=maxzoom)
+        .find(|z| level_resolution(*z) <= resolution * 1.0001)
+        .unwrap_or(maxzoom);
+    loop {
+        let origin = tms.xy_bounds(&Xyz::new(0, 0, z));
+        let span_x = origin.right - origin.left;
+        let span_y = origin.top - origin.bottom;
+        let index = |v: f64| v.max(0.0).floor() as u64;
+        let range = TileRange {
+            z,
+            cols: (
+                index((extent.left - origin.left) / span_x),
+                index((extent.right - origin.left) / span_x),
+            ),
+            rows: (
+                index((origin.top - extent.top) / span_y),
+                index((origin.top - extent.bottom) / span_y),
+            ),
+        };
+        if range.len() <= MAX_SOURCE_TILES || z == 0 {
+            return range;
+        }
+        z -= 1;
+    }
+}
+
+/// RGBA image composed of source tiles
+struct Mosaic {
+    rgba: Vec<u8>,
+    width: u32,
+    height: u32,
+    /// Upper left corner in source coordinates
+    origin: (f64, f64),
+    /// Pixel size in source coordinates
+    pixel_size: (f64, f64),
+}
+
+impl Mosaic {
+    fn pixel(&self, col: i64, row: i64) -> [u8; 4] {
+        if col < 0 || row < 0 || col >= self.width as i64 || row >= self.height as i64 {
+            return [0, 0, 0, 0];
+        }
+        let idx = ((row as u32 * self.width + col as u32) * 4) as usize;
+        [
+            self.rgba[idx],
+            self.rgba[idx + 1],
+            self.rgba[idx + 2],
+            self.rgba[idx + 3],
+        ]
+    }
+
+    /// Sample pixel at source coordinates
+    fn sample(&self, x: f64, y: f64, resampling: ResamplingCfg) -> [u8; 4] {
+        let col = (x - self.origin.0) / self.pixel_size.0;
+        let row = (self.origin.1 - y) / self.pixel_size.1;
+        match resampling {
+            ResamplingCfg::Nearest => self.pixel(col.floor() as i64, row.floor() as i64),
+            ResamplingCfg::Bilinear => {
+                // Interpolate between pixel centers with premultiplied alpha
+                let (col, row) = (col - 0.5, row - 0.5);
+                let (col0, row0) = (col.floor(), row.floor());
+                let (fx, fy) = (col - col0, row - row0);
+                let mut color = [0.0; 3];
+                let mut alpha = 0.0;
+                for (dc, dr, weight) in [
+                    (0, 0, (1.0 - fx) * (1.0 - fy)),
+                    (1, 0, fx * (1.0 - fy)),
+                    (0, 1, (1.0 - fx) * fy),
+                    (1, 1, fx * fy),
+                ] {
+                    let px = self.pixel(col0 as i64 + dc, row0 as i64 + dr);
+                    let a = px[3] as f64 * weight;
+                    for i in 0..3 {
+                        color[i] += px[i] as f64 * a;
+                    }
+                    alpha += a;
+                }
+                if alpha <= 0.0 {
+                    return [0, 0, 0, 0];
+                }
+                [
+                    (color[0] / alpha).round() as u8,
+                    (color[1] / alpha).round() as u8,
+                    (color[2] / alpha).round() as u8,
+                    alpha.round() as u8,
+                ]
+            }
+        }
+    }
+}
+
+/// Warp source mosaic into target extent
+fn warp(
+    mosaic: &Mosaic,
+    source_srid: i32,
+    target_srid: i32,
+    extent: &BoundingBox,
+    width: u32,
+    height: u32,
+    resampling: ResamplingCfg,
+) -> Vec<u8> {
+    let res_x = (extent.right - extent.left) / width as f64;
+    let res_y = (extent.top - extent.bottom) / height as f64;
+    let mut rgba = vec![0; (width * height * 4) as usize];
+    for row in 0..height {
+        let y = extent.top - (row as f64 + 0.5) * res_y;
+        for col in 0..width {
+            let x = extent.left + (col as f64 + 0.5) * res_x;
+            let (sx, sy) = transform(target_srid, source_srid, x, y);
+            let idx = ((row * width + col) * 4) as usize;
+            rgba[idx..idx + 4].copy_from_slice(&mosaic.sample(sx, sy, resampling));
+        }
+    }
+    rgba
+}
+
+fn service_error(e: ServiceError) -> TileSourceError {
+    match e {
+        ServiceError::TileSourceError(e) => e,
+        e => TileSourceError::RasterError(e.to_string()),
+    }
+}
+
+#[async_trait]
+impl TileRead for ReprojectSource {
+    async fn xyz_request(
+        &self,
+        service: &TileService,
+        tms_id: &str,
+        tile: &Xyz,
+        filter: &FilterParams,
+        format: &Format,
+        request_params: HttpRequestParams<'_>,
+    ) -> Result<TileResponse, TileSourceError> {
+        let extent_info = service.xyz_extent(tms_id, tile)?;
+        let (width, height) = if let Some(size) = self.tile_size {
+            (size, size)
+        } else {
+            (extent_info.tile_width, extent_info.tile_height)
+        };
+        let (width, height) = (width.get() as u32, height.get() as u32);
+        let source_ts = service
+            .tileset(&self.tileset)
+            .ok_or(TileSourceError::TileSourceNotFound(self.tileset.clone()))?;
+        let source_tms = service.grid(&source_ts.tms)?;
+        let source_srid = source_tms.crs().as_srid();
+        check_srid(source_srid)?;
+        let target_srid = extent_info.srid;
+
+        let source_extent = transform_extent(target_srid, source_srid, &extent_info.extent);
+        let resolution = ((source_extent.right - source_extent.left) / width as f64)
+            .min((source_extent.top - source_extent.bottom) / height as f64);
+        let range = source_tiles(source_tms, &source_extent, resolution);
+
+        let mut tiles = Vec::with_capacity(range.len());
+        for row in range.rows.0..=range.rows.1 {
+            for col in range.cols.0..=range.cols.1 {
+                let xyz = Xyz::new(col, row, range.z);
+                if !source_tms.is_valid(&xyz) {
+                    continue;
+                }
+                let params = HttpRequestParams {
+                    scheme: request_params.scheme,
+                    host: request_params.host,
+                    req_path: request_params.req_path,
+                    metrics: request_params.metrics,
+                };
+                let response = service
+                    .tile_cached(
+                        &self.tileset,
+                        &xyz,
+                        filter,
+                        source_ts.tile_format(),
+                        Compression::None,
+                        params,
+                    )
+                    .await
+                    .map_err(service_error)?;
+                if let Some(response) = response {
+                    let data = response
+                        .read_bytes(&Compression::None)
+                        .map_err(|e| TileSourceError::RasterError(e.to_string()))?;
+                    tiles.push((col - range.cols.0, row - range.rows.0, data.body));
+                }
+            }
+        }
+
+        let origin = source_tms.xy_bounds(&Xyz::new(range.cols.0, range.rows.0, range.z));
+        let tile_span = (origin.right - origin.left, origin.top - origin.bottom);
+        let across = (range.cols.1 - range.cols.0 + 1) as u32;
+        let down = (range.rows.1 - range.rows.0 + 1) as u32;
+        let resampling = self.resampling;
+        let target_extent = extent_info.extent;
+        let render_format = *format;
+        let data = tokio::task::spawn_blocking(move || {
+            let images = tiles
+                .iter()
+                .map(|(col, row, data)| {
+                    Ok((
+                        *col as u32,
+                        *row as u32,
+                        image::load_from_memory(data)?.to_rgba8(),
+                    ))
+                })
+                .collect::<Result<Vec<_>, TileSourceError>>()?;
+            // Source tile size in pixels, which may differ from the grid tile size
+            let (tile_width, tile_height) = images
+                .first()
+                .map(|(_, _, img)| img.dimensions())
+                .unwrap_or((1, 1));
+            let mut mosaic = Mosaic {
+                rgba: vec![0; (across * tile_width * down * tile_height * 4) as usize],
+                width: across * tile_width,
+                height: down * tile_height,
+                origin: (origin.left, origin.top),
+                pixel_size: (
+                    tile_span.0 / tile_width as f64,
+                    tile_span.1 / tile_height as f64,
+                ),
+            };
+            for (col, row, img) in &images {
+                if img.dimensions() != (tile_width, tile_height) {
+                    return Err(TileSourceError::RasterError(
+                        "source tiles with different sizes".to_string(),
+                    ));
+                }
+                for (y, line) in img
+                    .as_raw()
+                    .chunks_exact((tile_width * 4) as usize)
+                    .enumerate()
+                {
+                    let start = (((row * tile_height + y as u32) * mosaic.width + col * tile_width)
+                        * 4) as usize;
+                    mosaic.rgba[start..start + line.len()].copy_from_slice(line);
+                }
+            }
+            let rgba = warp(
+                &mosaic,
+                source_srid,
+                target_srid,
+                &target_extent,
+                width,
+                height,
+                resampling,
+            );
+            encode_rgba(&rgba, width, height, &render_format, None)
+        })
+        .await
+        .map_err(|e| TileSourceError::RasterError(e.to_string()))??;
+        let mut response = TileResponse::new();
+        response.set_content_type(format.content_type());
+        Ok(response.with_body(Box::new(Cursor::new(data))))
+    }
+    fn source_type(&self) -> SourceType {
+        SourceType::Raster
+    }
+    async fn tilejson(&self, format: &Format) -> Result<TileJSON, TileSourceError> {
+        let mut tj = tilejson! { tiles: vec![] };
+        tj.other
+            .insert("format".to_string(), format.file_suffix().into());
+        Ok(tj)
+    }
+    async fn layers(&self) -> Result<Vec<LayerInfo>, TileSourceError> {
+        Ok(vec![LayerInfo {
+            name: self.tileset.clone(),
+            geometry_type: None,
+            style: None,
+        }])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn transformations() {
+        // Zimmerwald observatory
+        let (e, n) = from_wgs84(2056, 7.438632, 46.951083);
+        assert!((e - 2600000.0).abs() < 2.0, "{e}");
+        assert!((n - 1200000.0).abs() < 2.0, "{n}");
+        let (lon, lat) = to_wgs84(2056, 2600000.0, 1200000.0);
+        assert!((lon - 7.438632).abs() < 1e-4, "{lon}");
+        assert!((lat - 46.951083).abs() < 1e-4, "{lat}");
+        let (x, y) = transform(2056, 21781, 2600000.0, 1200000.0);
+        assert!((x - 600000.0).abs() < 1e-6 && (y - 200000.0).abs() < 1e-6);
+
+        let (x, y) = from_wgs84(3857, 180.0, 0.0);
+        assert!((x - 20037508.34).abs() < 0.01 && y.abs() < 1e-6);
+        let (x, y) = transform(3857, 3857, 1.0, 2.0);
+        assert_eq!((x, y), (1.0, 2.0));
+        let (lon, lat) = transform(2056, 3857, 2600000.0, 1200000.0);
+        let (lon, lat) = to_wgs84(3857, lon, lat);
+        assert!((lon - 7.438632).abs() < 1e-4 && (lat - 46.951083).abs() < 1e-4);
+
+        assert!(check_srid(2056).is_ok());
+        assert!(check_srid(32632).is_err());
+    }
+
+    #[test]
+    fn resampling() {
+        // 2x1 pixels: opaque red and transparent
+        let mosaic = Mosaic {
+            rgba: vec![255, 0, 0, 255, 0, 0, 0, 0],
+            width: 2,
+            height: 1,
+            origin: (0.0, 1.0),
+            pixel_size: (1.0, 1.0),
+        };
+        assert_eq!(
+            mosaic.sample(0.9, 0.5, ResamplingCfg::Nearest),
+            [255, 0, 0, 255]
+        );
+        assert_eq!(
+            mosaic.sample(1.1, 0.5, ResamplingCfg::Nearest),
+            [0, 0, 0, 0]
+        );
+        assert_eq!(
+            mosaic.sample(5.0, 0.5, ResamplingCfg::Nearest),
+            [0, 0, 0, 0]
+        );
+        // Color is kept at the border of transparent pixels
+        assert_eq!(
+            mosaic.sample(0.5, 0.5, ResamplingCfg::Bilinear),
+            [255, 0, 0, 255]
+        );
+        let px = mosaic.sample(1.0, 0.5, ResamplingCfg::Bilinear);
+        assert_eq!(&px[0..3], &[255, 0, 0]);
+        assert_eq!(px[3], 128);
+    }
+
+    #[test]
+    fn warp_identity() {
+        let mosaic = Mosaic {
+            rgba: [[10, 20, 30, 255], [40, 50, 60, 255]].concat(),
+            width: 2,
+            height: 1,
+            origin: (0.0, 10.0),
+            pixel_size: (10.0, 10.0),
+        };
+        let extent = BoundingBox::new(0.0, 0.0, 20.0, 10.0);
+        let rgba = warp(&mosaic, 3857, 3857, &extent, 2, 1, ResamplingCfg::Nearest);
+        assert_eq!(rgba, mosaic.rgba);
+        let extent = transform_extent(3857, 3857, &extent);
+        assert_eq!((extent.left, extent.top), (0.0, 10.0));
+    }
+}
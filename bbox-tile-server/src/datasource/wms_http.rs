@@ -5,19 +5,32 @@ use crate::datasource::{
     wms_fcgi::HttpRequestParams, LayerInfo, SourceType, TileRead, TileSourceError,
 };
 use crate::filter_params::FilterParams;
-use crate::service::TileService;
+use crate::service::{QueryExtent, TileService};
 use async_trait::async_trait;
+use bbox_core::backoff::Backoff;
 use bbox_core::config::WmsHttpSourceProviderCfg;
 use bbox_core::{Format, TileResponse};
-use log::debug;
+use log::{debug, warn};
 use std::io::Cursor;
-use tile_grid::{BoundingBox, Xyz};
+use std::num::NonZeroU16;
+use std::sync::Arc;
+use std::time::Duration;
+use tile_grid::Xyz;
 use tilejson::{tilejson, TileJSON};
+use tokio::sync::Semaphore;
+
+const DEFAULT_MAX_CONCURRENT_REQUESTS: usize = 8;
+const DEFAULT_RETRIES: u32 = 2;
+const DEFAULT_TIMEOUT: u64 = 30;
 
 #[derive(Clone, Debug)]
 pub struct WmsHttpSource {
     client: reqwest::Client,
     pub req_url: String,
+    tile_size: Option<NonZeroU16>,
+    /// Concurrent requests to the WMS server
+    limit: Arc<Semaphore>,
+    retries: u32,
 }
 
 impl WmsHttpSource {
@@ -25,43 +38,103 @@ impl WmsHttpSource {
         provider: &WmsHttpSourceProviderCfg,
         params: &WmsHttpSourceParamsCfg,
         srid: i32,
+        limit: Arc<Semaphore>,
     ) -> Self {
-        let client = reqwest::Client::new();
-        let req_url = format!(
-            "{}&SERVICE=WMS&REQUEST=GetMap&CRS=EPSG:{}&WIDTH={}&HEIGHT={}&LAYERS={}&STYLES=&FORMAT={}",
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(
+                provider.timeout.unwrap_or(DEFAULT_TIMEOUT),
+            ))
+            .build()
+            .unwrap_or_default();
+        let mut req_url = format!(
+            "{}&SERVICE=WMS&REQUEST=GetMap&CRS=EPSG:{}&LAYERS={}&STYLES=&FORMAT={}",
             provider.baseurl,
             srid,
-            256, //grid.width,
-            256, //grid.height,
             params.layers,
-            provider.format,
+            params.format.as_ref().unwrap_or(&provider.format),
         );
-        WmsHttpSource { client, req_url }
+        if let Some(wms_params) = &params.params {
+            req_url.push('&');
+            req_url.push_str(wms_params);
+        }
+        WmsHttpSource {
+            client,
+            req_url,
+            tile_size: params.tile_size,
+            limit,
+            retries: provider.retries.unwrap_or(DEFAULT_RETRIES),
+        }
     }
-    fn get_map_request(&self, extent: &BoundingBox) -> String {
+
+    /// Request limit for a WMS provider
+    pub fn request_limit(provider: &WmsHttpSourceProviderCfg) -> Arc<Semaphore> {
+        Arc::new(Semaphore::new(
+            provider
+                .max_concurrent_requests
+                .unwrap_or(DEFAULT_MAX_CONCURRENT_REQUESTS),
+        ))
+    }
+
+    fn get_map_request(&self, extent_info: &QueryExtent) -> String {
+        let (width, height) = if let Some(size) = self.tile_size {
+            (size, size)
+        } else {
+            (extent_info.tile_width, extent_info.tile_height)
+        };
+        let extent = &extent_info.extent;
         format!(
-            "{}&BBOX={},{},{},{}",
+            "{}&WIDTH={width}&HEIGHT={height}&BBOX={},{},{},{}",
             self.req_url, extent.left, extent.bottom, extent.right, extent.top
         )
     }
 
+    /// GetMap request, retried with exponential backoff on temporary failures
     pub async fn get_map_response(
         &self,
-        extent: &BoundingBox,
+        extent_info: &QueryExtent,
     ) -> Result<reqwest::Response, TileSourceError> {
-        let req = self.get_map_request(extent);
-        debug!("Request {req}");
-        self.client.get(req).send().await.map_err(Into::into)
+        let req = self.get_map_request(extent_info);
+        let mut backoff = Backoff::new(Duration::from_millis(200), Duration::from_secs(5));
+        let mut attempt = 0;
+        loop {
+            debug!("Request {req}");
+            let result = {
+                let _permit = self.limit.acquire().await.expect("semaphore closed");
+                self.client
+                    .get(&req)
+                    .send()
+                    .await
+                    .and_then(|resp| resp.error_for_status())
+            };
+            match result {
+                Err(e) if attempt < self.retries && is_temporary(&e) => {
+                    let delay = backoff.delay();
+                    backoff.failed();
+                    attempt += 1;
+                    warn!("WMS request failed - retry {attempt} in {delay:?}: {e}");
+                    tokio::time::sleep(delay).await;
+                }
+                result => return result.map_err(Into::into),
+            }
+        }
     }
 
-    async fn bbox_request(&self, extent: &BoundingBox) -> Result<TileResponse, TileSourceError> {
-        let wms_resp = self.get_map_response(extent).await?;
+    async fn bbox_request(
+        &self,
+        extent_info: &QueryExtent,
+    ) -> Result<TileResponse, TileSourceError> {
+        let wms_resp = self.get_map_response(extent_info).await?;
         let mut response = TileResponse::new();
         if let Some(content_type) = wms_resp
             .headers()
             .get("content-type")
-            .map(|ct| ct.to_str().expect("invalid content-type"))
+            .and_then(|ct| ct.to_str().ok())
         {
+            // WMS errors are reported as XML service exceptions
+            if !content_type.starts_with("image/") {
+                let body = wms_resp.text().await?;
+                return Err(TileSourceError::WmsServiceError(body));
+            }
             response.set_content_type(content_type);
         }
         let body = Box::new(Cursor::new(wms_resp.bytes().await?));
@@ -69,6 +142,15 @@ impl WmsHttpSource {
     }
 }
 
+/// Connection problems, timeouts and server overload
+fn is_temporary(e: &reqwest::Error) -> bool {
+    e.is_timeout()
+        || e.is_connect()
+        || e.status()
+            .map(|status| status.is_server_error() || status.as_u16() == 429)
+            .unwrap_or(false)
+}
+
 #[async_trait]
 impl TileRead for WmsHttpSource {
     async fn xyz_request(
@@ -81,7 +163,7 @@ impl TileRead for WmsHttpSource {
         _request_params: HttpRequestParams<'_>,
     ) -> Result<TileResponse, TileSourceError> {
         let extent_info = service.xyz_extent(tms_id, tile)?;
-        self.bbox_request(&extent_info.extent).await
+        self.bbox_request(&extent_info).await
     }
     fn source_type(&self) -> SourceType {
         SourceType::Raster
@@ -100,3 +182,92 @@ impl TileRead for WmsHttpSource {
         }])
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tile_grid::BoundingBox;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    /// Minimal WMS answering the first `failures` requests with 503
+    async fn wms_server(failures: usize, content_type: &'static str) -> (String, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let requests = Arc::new(AtomicUsize::new(0));
+        let counter = requests.clone();
+        tokio::spawn(async move {
+            loop {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let mut buf = [0; 4096];
+                let _ = stream.read(&mut buf).await;
+                let (status, body) = if counter.fetch_add(1, Ordering::SeqCst) < failures {
+                    ("503 Service Unavailable", "")
+                } else {
+                    ("200 OK", "data")
+                };
+                let resp = format!(
+                    "HTTP/1.1 {status}\r\ncontent-type: {content_type}\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{body}",
+                    body.len()
+                );
+                let _ = stream.write_all(resp.as_bytes()).await;
+            }
+        });
+        (format!("http://{addr}/wms?VERSION=1.3.0"), requests)
+    }
+
+    fn wms_source(baseurl: String, retries: u32) -> WmsHttpSource {
+        let provider = WmsHttpSourceProviderCfg {
+            baseurl,
+            format: "image/png".to_string(),
+            max_concurrent_requests: Some(2),
+            retries: Some(retries),
+            timeout: None,
+        };
+        let params = WmsHttpSourceParamsCfg {
+            source: "wms".to_string(),
+            layers: "a,b".to_string(),
+            format: None,
+            params: Some("TRANSPARENT=true".to_string()),
+            tile_size: None,
+        };
+        let limit = WmsHttpSource::request_limit(&provider);
+        WmsHttpSource::from_config(&provider, &params, 2056, limit)
+    }
+
+    fn extent_info() -> QueryExtent {
+        QueryExtent {
+            extent: BoundingBox::new(2600000.0, 1200000.0, 2610000.0, 1210000.0),
+            srid: 2056,
+            tile_width: NonZeroU16::new(256).unwrap(),
+            tile_height: NonZeroU16::new(256).unwrap(),
+        }
+    }
+
+    #[tokio::test]
+    async fn retry_requests() {
+        let (url, requests) = wms_server(2, "image/png").await;
+        let source = wms_source(url.clone(), 2);
+        let req = source.get_map_request(&extent_info());
+        assert!(req.contains("LAYERS=a,b&STYLES=&FORMAT=image/png&TRANSPARENT=true&WIDTH=256"));
+
+        let resp = source.bbox_request(&extent_info()).await.unwrap();
+        assert_eq!(resp.content_type().unwrap(), "image/png");
+        assert_eq!(requests.load(Ordering::SeqCst), 3);
+
+        let (url, _) = wms_server(2, "image/png").await;
+        let source = wms_source(url, 1);
+        assert!(source.bbox_request(&extent_info()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn service_exception() {
+        let (url, _) = wms_server(0, "text/xml").await;
+        let source = wms_source(url, 2);
+        assert!(matches!(
+            source.bbox_request(&extent_info()).await,
+            Err(TileSourceError::WmsServiceError(_))
+        ));
+    }
+}
@@ -1,12 +1,22 @@
+use crate::cli::SeedArgs;
 use crate::datasource::wms_fcgi::{HttpRequestParams, WmsMetrics};
+use crate::datasource::{SourceType, TileSourceError};
 use crate::filter_params::FilterParams;
-use crate::service::{ServiceError, TileService};
-use actix_web::{guard, http::header, web, Error, FromRequest, HttpRequest, HttpResponse};
+use crate::seed_changes::DataChanges;
+use crate::service::{ServiceError, TileService, TileSet as ServiceTileSet};
+use actix_web::{
+    guard, http::header, web, Error, FromRequest, HttpMessage, HttpRequest, HttpResponse,
+};
+use bbox_core::auth::access::AdminGuard;
 use bbox_core::endpoints::{abs_req_baseurl, req_parent_path};
+use bbox_core::jobs::JobQueue;
 use bbox_core::service::ServiceEndpoints;
 use bbox_core::{Compression, Format};
 use log::error;
+use serde::Deserialize;
+use serde_json::json;
 use std::collections::HashMap;
+use std::time::{SystemTime, UNIX_EPOCH};
 use tile_grid::{
     Crs, DataType, Link, TileSet, TileSetItem, TileSets, TitleDescriptionKeywords, Xyz,
 };
@@ -24,6 +34,11 @@ async fn xyz(
         .tileset(&tileset)
         .ok_or(ServiceError::TilesetNotFound(tileset.clone()))?;
     let format = Format::from_suffix(&format).unwrap_or(*ts.tile_format());
+    if let Some(formats) = ts.output_formats() {
+        if !formats.contains(&format) {
+            return Ok(HttpResponse::NotFound().finish());
+        }
+    }
     tile_request(service, &tileset, x, y, z, &format, metrics, req).await
 }
 
@@ -78,27 +93,36 @@ async fn map_tile(
     req: HttpRequest,
 ) -> Result<HttpResponse, Error> {
     let (tileset, z, x, y) = params.into_inner();
-    let source = service
-        .source(&tileset)
+    let ts = service
+        .tileset(&tileset)
         .ok_or(ServiceError::TilesetNotFound(tileset.clone()))?;
-    let format = format_accept_header(&req, source.default_format()).await;
+    let format = format_accept_header(&req, ts.output_formats(), ts.source.default_format()).await;
     tile_request(service, &tileset, x, y, z, &format, metrics, req).await
 }
 
-async fn format_accept_header(req: &HttpRequest, default: &Format) -> Format {
-    let mut format_mime = web::Header::<header::Accept>::extract(req)
-        .await
-        .map(|accept| accept.preference().to_string())
-        .ok();
+/// Tile format requested with Accept header.
+/// Configured output formats are negotiated in the order of client preference.
+async fn format_accept_header(
+    req: &HttpRequest,
+    offered: Option<&[Format]>,
+    default: &Format,
+) -> Format {
+    let Ok(accept) = web::Header::<header::Accept>::extract(req).await else {
+        return *default;
+    };
+    if let Some(offered) = offered {
+        return accept
+            .ranked()
+            .iter()
+            .filter_map(|mime| Format::from_content_type(mime.essence_str()))
+            .find(|format| offered.contains(format))
+            .unwrap_or(*default);
+    }
     // override invalid request formats (TODO: check against available formats)
-    if let Some("image/avif") = format_mime.as_deref() {
-        format_mime = None;
+    match Format::from_content_type(&accept.preference().to_string()) {
+        Some(Format::Avif) | None => *default,
+        Some(format) => format,
     }
-    let format = format_mime
-        .as_deref()
-        .and_then(Format::from_content_type)
-        .unwrap_or(*default);
-    format
 }
 
 #[allow(clippy::too_many_arguments)]
@@ -123,18 +147,17 @@ async fn tile_request(
         };
 
     let datetime = filters.remove("datetime");
-    let fp = FilterParams { datetime, filters };
-    let compression = req
-        .headers()
-        .get(header::ACCEPT_ENCODING)
-        .and_then(|headerval| {
-            headerval
-                .to_str()
-                .ok()
-                .filter(|headerstr| headerstr.contains("gzip"))
-                .map(|_| Compression::Gzip)
-        })
+    let layers = filters.remove("layers");
+    let fp = FilterParams {
+        datetime,
+        layers,
+        filters,
+    };
+    let stored_compression = service
+        .tileset(tileset)
+        .map(|ts| ts.cache_compression())
         .unwrap_or(Compression::None);
+    let compression = response_compression(&req, stored_compression);
     let conn_info = req.connection_info().clone();
     let request_params = HttpRequestParams {
         scheme: conn_info.scheme(),
@@ -147,7 +170,30 @@ async fn tile_request(
         .await
     {
         Ok(Some(tile_resp)) => {
+            let cache_control = service
+                .tileset(tileset)
+                .and_then(|ts| ts.cache_control_header());
+            if not_modified(&req, tile_resp.etag().as_ref(), tile_resp.modified()) {
+                let mut r = HttpResponse::NotModified();
+                for name in [header::ETAG, header::LAST_MODIFIED] {
+                    if let Some(value) = tile_resp.headers().get(&name) {
+                        r.insert_header((name, value.clone()));
+                    }
+                }
+                if let Some(cache_control) = cache_control {
+                    r.insert_header((header::CACHE_CONTROL, cache_control));
+                }
+                return Ok(r.finish());
+            }
             let mut r = HttpResponse::Ok();
+            if service
+                .tileset(tileset)
+                .map_or(false, |ts| ts.output_formats().is_some())
+            {
+                r.insert_header((header::VARY, "Accept, Accept-Encoding"));
+            } else {
+                r.insert_header((header::VARY, "Accept-Encoding"));
+            }
             if let Some(content_type) = tile_resp.content_type() {
                 r.content_type(content_type);
             }
@@ -155,11 +201,21 @@ async fn tile_request(
                 r.insert_header((key, value));
                 // TODO: use append_header for "Server-Timing" and others?
             }
-            // let cache_max_age = service.webserver.cache_control_max_age.unwrap_or(300);
-            // r.insert_header((header::CACHE_CONTROL, format!("max-age={}", cache_max_age)));
+            if let Some(cache_control) = cache_control {
+                r.insert_header((header::CACHE_CONTROL, cache_control));
+            }
             Ok(r.streaming(tile_resp.into_stream()))
         }
         Ok(None) => Ok(HttpResponse::NoContent().finish()),
+        Err(ServiceError::TileSourceError(TileSourceError::SourceUnavailable)) => {
+            Ok(HttpResponse::ServiceUnavailable().finish())
+        }
+        Err(ServiceError::TileSourceError(TileSourceError::LayerOverflow(_))) => {
+            Ok(HttpResponse::NoContent().finish())
+        }
+        Err(ServiceError::TileSourceError(
+            e @ (TileSourceError::FilterParamError | TileSourceError::FilterParamValueError(_)),
+        )) => Ok(HttpResponse::BadRequest().body(e.to_string())),
         Err(e) => {
             error!("Tile creation error: {e}");
             Ok(HttpResponse::InternalServerError().finish())
@@ -167,70 +223,137 @@ async fn tile_request(
     }
 }
 
+/// Content encoding of tile response.
+/// Tiles are delivered in the stored encoding, if accepted by the client.
+/// Otherwise they are recompressed with gzip or delivered uncompressed.
+fn response_compression(req: &HttpRequest, stored: Compression) -> Compression {
+    let accepted = req
+        .headers()
+        .get(header::ACCEPT_ENCODING)
+        .and_then(|headerval| headerval.to_str().ok())
+        .map(|headerstr| {
+            headerstr
+                .split(',')
+                .filter_map(|encoding| encoding.split(';').next())
+                .map(|encoding| Compression::from_content_encoding(encoding.trim()))
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    if stored != Compression::None && accepted.contains(&stored) {
+        stored
+    } else if accepted.contains(&Compression::Gzip) {
+        Compression::Gzip
+    } else {
+        Compression::None
+    }
+}
+
+/// Check conditional request headers against tile entity tag and modification time
+fn not_modified(
+    req: &HttpRequest,
+    etag: Option<&header::EntityTag>,
+    modified: Option<SystemTime>,
+) -> bool {
+    // If-Modified-Since is ignored, when If-None-Match is present
+    if let Some(if_none_match) = req.get_header::<header::IfNoneMatch>() {
+        return match (if_none_match, etag) {
+            (header::IfNoneMatch::Any, Some(_)) => true,
+            (header::IfNoneMatch::Items(items), Some(etag)) => {
+                items.iter().any(|item| item.weak_eq(etag))
+            }
+            _ => false,
+        };
+    }
+    if let (Some(header::IfModifiedSince(since)), Some(modified)) =
+        (req.get_header::<header::IfModifiedSince>(), modified)
+    {
+        // HTTP dates have a resolution of seconds
+        let secs = |time: SystemTime| {
+            time.duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0)
+        };
+        return secs(modified) <= secs(since.into());
+    }
+    false
+}
+
+/// OGC API data type of a tileset
+fn tileset_data_type(ts: &ServiceTileSet) -> DataType {
+    match ts.source.source_type() {
+        SourceType::Vector => DataType::Vector,
+        SourceType::Raster => DataType::Map,
+    }
+}
+
+/// Links of a tileset metadata document
+fn tileset_links(tileset: &str, ts: &ServiceTileSet) -> Vec<Link> {
+    let format = ts.tile_format();
+    let tile_type = if *format == Format::Mvt {
+        "application/vnd.mapbox-vector-tile"
+    } else {
+        format.content_type()
+    };
+    vec![
+        Link {
+            rel: "self".to_string(),
+            r#type: Some("application/json".to_string()),
+            title: Some(format!("Tileset metadata for {tileset} (as JSON)")),
+            href: format!("/tiles/{tileset}"),
+            hreflang: None,
+            length: None,
+        },
+        Link {
+            rel: "alternate".to_string(),
+            r#type: Some("application/json+tilejson".to_string()),
+            title: Some(format!(
+                "Tileset metadata for {tileset} (in TileJSON format)"
+            )),
+            href: format!("/xyz/{tileset}.json"),
+            hreflang: None,
+            length: None,
+        },
+        Link {
+            rel: "http://www.opengis.net/def/rel/ogc/1.0/tiling-scheme".to_string(),
+            r#type: Some("application/json".to_string()),
+            title: Some(format!("{} TileMatrixSet definition (as JSON)", ts.tms)),
+            href: format!("/tileMatrixSets/{}", ts.tms),
+            hreflang: None,
+            length: None,
+        },
+        Link {
+            rel: "item".to_string(),
+            r#type: Some(tile_type.to_string()),
+            title: Some(format!("Tiles for {tileset} (as {format})")),
+            href: format!("/map/tiles/{tileset}/{{tileMatrix}}/{{tileRow}}/{{tileCol}}"),
+            hreflang: None,
+            length: None,
+        },
+    ]
+}
+
 /// list of available tilesets
 // tiles
 async fn get_tile_sets_list(service: web::Data<TileService>) -> HttpResponse {
-    let tile_set_items: Vec<TileSetItem> = service
-        .tilesets
+    let mut tile_set_items: Vec<TileSetItem> = service
+        .tilesets()
         .iter()
-        .map(|(tile_matrix_set_id, tileset)| {
+        .map(|(name, tileset)| {
             let mut ts_item = TileSetItem {
-                title: Some(tile_matrix_set_id.to_string()),
-                data_type: DataType::Vector,
+                title: Some(name.to_string()),
+                data_type: tileset_data_type(tileset),
                 crs: Crs::from_epsg(3857),
                 tile_matrix_set_uri: None,
-                links: vec![
-                    Link {
-                        rel: "self".to_string(),
-                        r#type: Some("application/json".to_string()),
-                        title: Some(format!(
-                            "Tileset metadata for {tile_matrix_set_id} (as JSON)"
-                        )),
-                        href: format!("/tiles/{tile_matrix_set_id}"),
-                        hreflang: None,
-                        length: None,
-                    },
-                    Link {
-                        rel: "self".to_string(),
-                        r#type: Some("application/json+tilejson".to_string()),
-                        title: Some(format!(
-                            "Tileset metadata for {tile_matrix_set_id} (in TileJSON format)"
-                        )),
-                        href: format!("/xyz/{tile_matrix_set_id}.json"),
-                        hreflang: None,
-                        length: None,
-                    },
-                    Link {
-                        rel: "item".to_string(),
-                        r#type: Some("application/vnd.mapbox-vector-tile".to_string()),
-                        title: Some(format!("Tiles for {tile_matrix_set_id} (as MVT)")),
-                        href: format!(
-                        "/map/tiles/{tile_matrix_set_id}/{{tileMatrix}}/{{tileRow}}/{{tileCol}}"
-                    ),
-                        hreflang: None,
-                        length: None,
-                    },
-                ],
+                links: tileset_links(name, tileset),
             };
             if let Ok(grid) = service.grid(&tileset.tms) {
                 ts_item.crs = grid.tms.crs.clone();
                 ts_item.tile_matrix_set_uri = grid.tms.uri.clone();
-                if grid.tms.id == "WebMercatorQuad" {
-                    ts_item.links.push(Link {
-                        rel: "http://www.opengis.net/def/rel/ogc/1.0/tiling-scheme".to_string(),
-                        r#type: Some("application/json".to_string()),
-                        title: Some(
-                            "WebMercatorQuadTileMatrixSet definition (as JSON)".to_string(),
-                        ),
-                        href: "/tileMatrixSets/WebMercatorQuad".to_string(),
-                        hreflang: None,
-                        length: None,
-                    });
-                }
             }
             ts_item
         })
         .collect();
+    tile_set_items.sort_by(|a, b| a.title.cmp(&b.title));
     let tilesets = TileSets {
         tilesets: tile_set_items,
         links: None,
@@ -240,67 +363,235 @@ async fn get_tile_sets_list(service: web::Data<TileService>) -> HttpResponse {
 
 /// tileset metadata
 // tiles/{tileMatrixSetId}
-async fn get_tile_set(tile_matrix_set_id: web::Path<String>) -> HttpResponse {
-    // hardcoded TileSet, required for core conformance test
+async fn get_tile_set(service: web::Data<TileService>, tileset: web::Path<String>) -> HttpResponse {
+    let (data_type, tms_id, links, license) = if let Some(ts) = service.tileset(&tileset) {
+        (
+            tileset_data_type(&ts),
+            ts.tms.clone(),
+            tileset_links(&tileset, &ts),
+            ts.license().map(str::to_string),
+        )
+    } else if service.grid(&tileset).is_ok() {
+        // Tile matrix set identifier used by OGC API Tiles clients
+        let links = vec![Link {
+            rel: "http://www.opengis.net/def/rel/ogc/1.0/tiling-scheme".to_string(),
+            r#type: Some("application/json".to_string()),
+            title: Some(format!("{tileset} TileMatrixSet definition (as JSON)")),
+            href: format!("/tileMatrixSets/{tileset}"),
+            hreflang: None,
+            length: None,
+        }];
+        (DataType::Vector, tileset.to_string(), links, None)
+    } else {
+        return HttpResponse::NotFound().finish();
+    };
+    let Ok(grid) = service.grid(&tms_id) else {
+        return HttpResponse::InternalServerError().finish();
+    };
     let tileset = TileSet {
         title_description_keywords: TitleDescriptionKeywords {
-            title: Some(tile_matrix_set_id.to_string()),
+            title: Some(tileset.to_string()),
             description: None,
             keywords: None,
         },
-        data_type: DataType::Vector,
-        tile_matrix_set_uri: Some(
-            "http://www.opengis.net/def/tilematrixset/OGC/1.0/WebMercatorQuad".to_string(),
-        ),
+        data_type,
+        tile_matrix_set_uri: grid.tms.uri.clone(),
         tile_matrix_set_limits: None,
-        crs: Crs::from_epsg(3857),
+        crs: grid.tms.crs.clone(),
         epoch: None,
         layers: None,
         bounding_box: None,
         style: None,
         center_point: None,
-        license: None,
+        license,
         access_constraints: None,
         version: None,
         created: None,
         updated: None,
         point_of_contact: None,
         media_types: None,
-        links: vec![
-            Link {
-                rel: "self".to_string(),
-                r#type: Some("application/json".to_string()),
-                title: Some(format!(
-                    "Tileset metadata for {tile_matrix_set_id} (as JSON)"
-                )),
-                href: format!("/tiles/{tile_matrix_set_id}"),
-                hreflang: None,
-                length: None,
-            },
-            Link {
-                rel: "http://www.opengis.net/def/rel/ogc/1.0/tiling-scheme".to_string(),
-                r#type: Some("application/json".to_string()),
-                title: Some("WebMercatorQuadTileMatrixSet definition (as JSON)".to_string()),
-                href: "/tileMatrixSets/WebMercatorQuad".to_string(),
-                hreflang: None,
-                length: None,
-            },
-            Link {
-                rel: "item".to_string(),
-                r#type: Some("application/vnd.mapbox-vector-tile".to_string()),
-                title: Some(format!("Tiles for {tile_matrix_set_id} (as MVT)")),
-                href: format!(
-                    "/map/tiles/{tile_matrix_set_id}/{{tileMatrix}}/{{tileRow}}/{{tileCol}}"
-                ),
-                hreflang: None,
-                length: None,
-                // TODO ??: "templated": true
-            },
-        ],
+        links,
     };
     HttpResponse::Ok().json(tileset)
 }
 
+/// list of available tiling schemes
+// tileMatrixSets
+async fn get_tile_matrix_sets_list(service: web::Data<TileService>) -> HttpResponse {
+    let mut grids = service
+        .grids
+        .values()
+        .map(|grid| {
+            json!({
+                "id": grid.tms.id,
+                "uri": grid.tms.uri,
+                "links": [Link {
+                    rel: "self".to_string(),
+                    r#type: Some("application/json".to_string()),
+                    title: Some(format!("{} TileMatrixSet definition (as JSON)", grid.tms.id)),
+                    href: format!("/tileMatrixSets/{}", grid.tms.id),
+                    hreflang: None,
+                    length: None,
+                }]
+            })
+        })
+        .collect::<Vec<_>>();
+    grids.sort_by(|a, b| a["id"].as_str().cmp(&b["id"].as_str()));
+    HttpResponse::Ok().json(json!({ "tileMatrixSets": grids }))
+}
+
+/// tiling scheme definition
+// tileMatrixSets/{tileMatrixSetId}
+async fn get_tile_matrix_set(
+    service: web::Data<TileService>,
+    tms_id: web::Path<String>,
+) -> HttpResponse {
+    if let Ok(grid) = service.grid(&tms_id) {
+        HttpResponse::Ok().json(&grid.tms)
+    } else {
+        HttpResponse::NotFound().finish()
+    }
+}
+
+/// Seeding parameters
+/// Parameters of seeding jobs
+#[derive(Deserialize, Default, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct SeedParams {
+    /// Extent minx,miny,maxx,maxy (in grid reference system)
+    pub bbox: Option<String>,
+    pub minzoom: Option<u8>,
+    pub maxzoom: Option<u8>,
+    /// Overwrite previously cached tiles (Default: true)
+    pub overwrite: Option<bool>,
+}
+
+impl SeedParams {
+    pub fn seed_args(&self, tileset: &str) -> Result<SeedArgs, String> {
+        if let Some(bbox) = &self.bbox {
+            let coords = bbox
+                .split(',')
+                .map(|v| v.trim().parse::<f64>())
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|_| format!("Invalid bbox `{bbox}`"))?;
+            if coords.len() != 4 {
+                return Err("Invalid bbox (minx,miny,maxx,maxy)".to_string());
+            }
+        }
+        if let (Some(minzoom), Some(maxzoom)) = (self.minzoom, self.maxzoom) {
+            if minzoom > maxzoom {
+                return Err("minzoom is greater than maxzoom".to_string());
+            }
+        }
+        Ok(SeedArgs {
+            tileset: tileset.to_string(),
+            minzoom: self.minzoom,
+            maxzoom: self.maxzoom,
+            extent: self.bbox.clone(),
+            geometry: None,
+            changes: None,
+            changes_table: None,
+            changes_geometry_field: "geom".to_string(),
+            tile_path: None,
+            s3_path: None,
+            mb_path: None,
+            pm_path: None,
+            no_store: false,
+            output: None,
+            format: None,
+            concurrency: None,
+            tasks: None,
+            rate_limit: None,
+            overwrite: self.overwrite,
+            resume: false,
+            checkpoint: None,
+            file_or_url: None,
+        })
+    }
+}
+
+/// Start seeding job
+// admin/tilesets/{tileset}/seed
+async fn seed(
+    service: web::Data<TileService>,
+    jobs: web::Data<JobQueue>,
+    tileset: web::Path<String>,
+    params: web::Query<SeedParams>,
+) -> HttpResponse {
+    let tileset = tileset.into_inner();
+    let Some(ts) = service.tileset(&tileset) else {
+        return HttpResponse::NotFound().finish();
+    };
+    if ts.cache_config().is_none() || ts.store_writer.is_none() {
+        return HttpResponse::Conflict().body("Tileset has no cache");
+    }
+    let args = match params.seed_args(&tileset) {
+        Ok(args) => args,
+        Err(e) => return HttpResponse::BadRequest().body(e),
+    };
+    let service = service.get_ref().clone();
+    let job = jobs.submit("seed", move |handle| async move {
+        let tiles = service.seed_by_grid(&args, Some(handle)).await?;
+        Ok::<_, anyhow::Error>(json!({ "tileset": args.tileset, "tiles": tiles }))
+    });
+    HttpResponse::Created()
+        .insert_header((header::LOCATION, format!("/admin/jobs/{}", job.job_id)))
+        .json(job)
+}
+
+/// Parameters of tile expiry jobs
+#[derive(Deserialize, Default, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct ExpireParams {
+    /// Minimum zoom level (Default: lowest level of expire list)
+    pub minzoom: Option<u8>,
+    /// Maximum zoom level (Default: highest level of expire list)
+    pub maxzoom: Option<u8>,
+    /// Render expired tiles again (Default: false)
+    pub regenerate: Option<bool>,
+}
+
+/// Start job expiring the tiles of an osm2pgsql or imposm expire list in the request body
+// admin/tilesets/{tileset}/expire
+async fn expire(
+    service: web::Data<TileService>,
+    jobs: web::Data<JobQueue>,
+    tileset: web::Path<String>,
+    params: web::Query<ExpireParams>,
+    body: String,
+) -> HttpResponse {
+    let tileset = tileset.into_inner();
+    let Some(ts) = service.tileset(&tileset) else {
+        return HttpResponse::NotFound().finish();
+    };
+    if ts.cache_config().is_none() {
+        return HttpResponse::BadRequest().body("Tileset has no cache configuration");
+    }
+    let changes = match DataChanges::from_expire_list(&body) {
+        Ok(changes) => changes,
+        Err(e) => return HttpResponse::BadRequest().body(e.to_string()),
+    };
+    let params = params.into_inner();
+    let service = service.get_ref().clone();
+    let job = jobs.submit("expire", move |handle| async move {
+        let tiles = service
+            .expire_tiles(
+                &tileset,
+                &changes,
+                params.minzoom,
+                params.maxzoom,
+                params.regenerate.unwrap_or(false),
+                None,
+                Some(handle),
+            )
+            .await?;
+        Ok::<_, anyhow::Error>(json!({ "tileset": tileset, "tiles": tiles }))
+    });
+    HttpResponse::Created()
+        .insert_header((header::LOCATION, format!("/admin/jobs/{}", job.job_id)))
+        .json(job)
+}
+
 impl ServiceEndpoints for TileService {
     fn register_endpoints(&self, cfg: &mut web::ServiceConfig) {
         cfg.app_data(web::Data::new(self.clone()))
@@ -321,9 +612,146 @@ impl ServiceEndpoints for TileService {
                     .route(web::get().to(map_tile)),
             )
             .service(web::resource("/tiles/{tileMatrixSetId}").route(web::get().to(get_tile_set)))
-            .service(web::resource("/tiles").route(web::get().to(get_tile_sets_list)));
+            .service(web::resource("/tiles").route(web::get().to(get_tile_sets_list)))
+            .service(
+                web::resource("/tileMatrixSets/{tileMatrixSetId}")
+                    .route(web::get().to(get_tile_matrix_set)),
+            )
+            .service(
+                web::resource("/tileMatrixSets").route(web::get().to(get_tile_matrix_sets_list)),
+            )
+            .service(
+                web::resource("/admin/tilesets/{tileset}/seed")
+                    .guard(AdminGuard)
+                    .route(web::post().to(seed)),
+            )
+            .service(
+                web::resource("/admin/tilesets/{tileset}/expire")
+                    .guard(AdminGuard)
+                    .route(web::post().to(expire)),
+            );
         if cfg!(not(feature = "map-server")) {
             cfg.app_data(web::Data::new(WmsMetrics::default()));
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test;
+    use bbox_core::content_etag;
+    use std::time::Duration;
+
+    #[actix_web::test]
+    async fn encoding_negotiation() {
+        let req = test::TestRequest::default()
+            .insert_header((header::ACCEPT_ENCODING, "gzip, deflate, br;q=0.9"))
+            .to_http_request();
+        assert_eq!(
+            response_compression(&req, Compression::Brotli),
+            Compression::Brotli
+        );
+        assert_eq!(
+            response_compression(&req, Compression::None),
+            Compression::Gzip
+        );
+        let req = test::TestRequest::default()
+            .insert_header((header::ACCEPT_ENCODING, "gzip"))
+            .to_http_request();
+        assert_eq!(
+            response_compression(&req, Compression::Brotli),
+            Compression::Gzip
+        );
+        let req = test::TestRequest::default().to_http_request();
+        assert_eq!(
+            response_compression(&req, Compression::Brotli),
+            Compression::None
+        );
+    }
+
+    #[actix_web::test]
+    async fn format_negotiation() {
+        let req = test::TestRequest::default()
+            .insert_header((
+                header::ACCEPT,
+                "image/avif,image/webp;q=0.9,image/png;q=0.8,*/*;q=0.5",
+            ))
+            .to_http_request();
+        let offered = [Format::Png, Format::Webp];
+        assert_eq!(
+            format_accept_header(&req, Some(&offered[..]), &Format::Png).await,
+            Format::Webp
+        );
+        assert_eq!(
+            format_accept_header(&req, Some(&[Format::Jpeg][..]), &Format::Jpeg).await,
+            Format::Jpeg
+        );
+        // AVIF is not rendered by tile sources
+        assert_eq!(
+            format_accept_header(&req, None, &Format::Png).await,
+            Format::Png
+        );
+        let req = test::TestRequest::default().to_http_request();
+        assert_eq!(
+            format_accept_header(&req, Some(&offered[..]), &Format::Png).await,
+            Format::Png
+        );
+    }
+
+    #[test]
+    fn seed_params() {
+        let params = SeedParams {
+            bbox: Some("-20037508.34,-20037508.34,20037508.34,20037508.34".to_string()),
+            minzoom: Some(2),
+            maxzoom: Some(6),
+            overwrite: Some(false),
+        };
+        let args = params.seed_args("ne_countries").unwrap();
+        assert_eq!(args.tileset, "ne_countries");
+        assert_eq!(args.extent, params.bbox);
+        assert_eq!((args.minzoom, args.maxzoom), (Some(2), Some(6)));
+        assert_eq!(args.overwrite, Some(false));
+        assert!(!args.resume);
+
+        let params = SeedParams {
+            bbox: Some("0,0,1".to_string()),
+            ..Default::default()
+        };
+        assert!(params.seed_args("ne_countries").is_err());
+        let params = SeedParams {
+            minzoom: Some(6),
+            maxzoom: Some(2),
+            ..Default::default()
+        };
+        assert!(params.seed_args("ne_countries").is_err());
+    }
+
+    #[actix_web::test]
+    async fn conditional_request() {
+        let etag = content_etag(&[1, 0, 1]);
+        let modified = UNIX_EPOCH + Duration::from_secs(1700000000);
+
+        let req = test::TestRequest::default()
+            .insert_header(header::IfNoneMatch::Items(vec![etag.clone()]))
+            .to_http_request();
+        assert!(not_modified(&req, Some(&etag), Some(modified)));
+        assert!(!not_modified(
+            &req,
+            Some(&content_etag(&[1, 1, 1])),
+            Some(modified)
+        ));
+
+        let since = header::IfModifiedSince((modified + Duration::from_millis(500)).into());
+        let req = test::TestRequest::default()
+            .insert_header(since)
+            .to_http_request();
+        assert!(not_modified(&req, None, Some(modified)));
+        assert!(!not_modified(
+            &req,
+            None,
+            Some(modified + Duration::from_secs(1))
+        ));
+        assert!(!not_modified(&req, None, None));
+    }
+}
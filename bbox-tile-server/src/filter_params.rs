@@ -4,6 +4,8 @@ use std::collections::HashMap;
 #[derive(Debug, Deserialize, Default, Clone)]
 pub struct FilterParams {
     pub datetime: Option<String>,
+    /// Comma separated names of requested layers, None for all layers
+    pub layers: Option<String>,
     pub filters: HashMap<String, String>,
 }
 
@@ -18,13 +20,17 @@ impl FilterParams {
         let mut args = vec![
             Some("".to_string()),
             self.datetime.as_ref().map(|v| format!("datetime={v}")),
+            self.layers.as_ref().map(|v| format!("layers={v}")),
         ]
         .into_iter()
         .flatten()
         .collect::<Vec<String>>()
         .join("&");
 
-        for (key, val) in &self.filters {
+        // Sorted for identical arguments of equal filters
+        let mut filters = self.filters.iter().collect::<Vec<_>>();
+        filters.sort();
+        for (key, val) in filters {
             args.push_str(&format!("&{key}={val}"))
         }
         if !args.is_empty() {
@@ -33,6 +39,17 @@ impl FilterParams {
         }
         args
     }
+    /// Check whether the tile is requested without filters or layer selection
+    pub fn is_empty(&self) -> bool {
+        self.datetime.is_none() && self.layers.is_none() && self.filters.is_empty()
+    }
+    /// Check whether a layer is included in the requested layers
+    pub fn layer_requested(&self, name: &str) -> bool {
+        match &self.layers {
+            Some(layers) => layers.split(',').any(|layer| layer.trim() == name),
+            None => true,
+        }
+    }
     pub fn temporal(&self) -> Result<Option<Vec<TemporalType>>, Box<dyn std::error::Error>> {
         if let Some(dt) = &self.datetime {
             let parts: Vec<&str> = dt.split('/').collect();
@@ -55,3 +72,23 @@ impl FilterParams {
         Ok(&self.filters)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn layer_selection() {
+        let filter = FilterParams {
+            layers: Some("roads, buildings".to_string()),
+            ..Default::default()
+        };
+        assert!(filter.layer_requested("roads"));
+        assert!(filter.layer_requested("buildings"));
+        assert!(!filter.layer_requested("water"));
+        assert_eq!(filter.as_args(), "?layers=roads, buildings");
+        assert!(FilterParams::default().layer_requested("water"));
+        assert!(!filter.is_empty());
+        assert!(FilterParams::default().is_empty());
+    }
+}
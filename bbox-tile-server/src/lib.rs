@@ -1,12 +1,24 @@
+pub mod builder;
 pub mod cli;
+mod coalesce;
 pub mod config;
 pub mod config_t_rex;
 pub mod datasource;
 mod endpoints;
 mod filter_params;
 mod mbtiles_ds;
+mod memory_cache;
+pub mod metrics;
+pub mod postprocess;
+pub mod raster_encoding;
 pub mod seed;
+mod seed_area;
+mod seed_changes;
+mod seed_progress;
+mod stats;
 pub mod service;
 pub mod store;
 
+pub use builder::TileServiceBuilder;
+pub use endpoints::SeedParams;
 pub use service::*;
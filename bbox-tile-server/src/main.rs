@@ -1,4 +1,5 @@
 use actix_web::{middleware, middleware::Condition, App, HttpServer};
+use bbox_core::access_log::AccessLog;
 use bbox_core::cli::CliArgs;
 use bbox_core::config::CoreServiceCfg;
 use bbox_core::service::{CoreService, OgcApiService, ServiceConfig, ServiceEndpoints};
@@ -59,10 +60,17 @@ async fn run_service() -> std::io::Result<()> {
     let tls_config = core.tls_config();
     let mut server = HttpServer::new(move || {
         App::new()
+            .wrap(Condition::new(
+                core.has_cache_control(),
+                core.cache_control(),
+            ))
+            // Always active for protecting admin endpoints
+            .wrap(core.access_control())
+            .wrap(Condition::new(core.has_api_keys(), core.api_key_auth()))
             .wrap(Condition::new(core.has_cors(), core.cors()))
             .wrap(Condition::new(core.has_metrics(), core.middleware()))
             .wrap(Condition::new(core.has_metrics(), core.metrics().clone()))
-            .wrap(middleware::Logger::default())
+            .wrap(AccessLog)
             .wrap(middleware::Compress::default())
             .configure(|cfg| core.register_endpoints(cfg))
             .configure(bbox_core::static_assets::register_endpoints)
@@ -158,6 +158,42 @@ impl MbtilesDatasource {
         Ok(())
     }
 
+    /// Update metadata values describing the tile content
+    pub async fn update_metadata(&self, metadata: &Metadata) -> MbtResult<()> {
+        let mut conn = self.pool.acquire().await?;
+        if let Some(bounds) = metadata.tilejson.bounds {
+            self.mbtiles
+                .set_metadata_value(&mut *conn, "bounds", bounds)
+                .await?;
+        }
+        if let Some(center) = metadata.tilejson.center {
+            self.mbtiles
+                .set_metadata_value(&mut *conn, "center", center)
+                .await?;
+        }
+        if let Some(minzoom) = metadata.tilejson.minzoom {
+            self.mbtiles
+                .set_metadata_value(&mut *conn, "minzoom", minzoom)
+                .await?;
+        }
+        if let Some(maxzoom) = metadata.tilejson.maxzoom {
+            self.mbtiles
+                .set_metadata_value(&mut *conn, "maxzoom", maxzoom)
+                .await?;
+        }
+        if let Some(json) = &metadata.json {
+            self.mbtiles
+                .set_metadata_value(&mut *conn, "json", json)
+                .await?;
+        } else if let Some(vector_layers) = &metadata.tilejson.vector_layers {
+            let json = json!({"vector_layers": vector_layers});
+            self.mbtiles
+                .set_metadata_value(&mut *conn, "json", json)
+                .await?;
+        }
+        Ok(())
+    }
+
     pub async fn get_metadata(&self) -> MbtResult<Metadata> {
         let mut conn = self.pool.acquire().await?;
         self.mbtiles.get_metadata(&mut *conn).await
@@ -167,4 +203,26 @@ impl MbtilesDatasource {
         let mut conn = self.pool.acquire().await?;
         self.mbtiles.get_tile(&mut *conn, z, x, y).await
     }
+    /// Tiles of zoom level `z` as (x, y, size in bytes)
+    pub async fn tile_sizes(&self, z: u8) -> MbtResult<Vec<(u32, u32, u64)>> {
+        let mut conn = self.pool.acquire().await?;
+        let rows = sqlx::query(
+            "SELECT tile_column, tile_row, length(tile_data) FROM tiles WHERE zoom_level = ?",
+        )
+        .bind(z)
+        .fetch_all(&mut *conn)
+        .await?;
+        // Rows are numbered from the bottom (TMS scheme)
+        let max_row = (1u32 << z) - 1;
+        let tiles = rows
+            .iter()
+            .map(|row| {
+                let x: u32 = row.get(0);
+                let y: u32 = row.get(1);
+                let size: i64 = row.get(2);
+                (x, max_row - y, size as u64)
+            })
+            .collect();
+        Ok(tiles)
+    }
 }
@@ -0,0 +1,121 @@
+//! In-memory LRU tile cache.
+
+use bbox_core::TileResponseData;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+use tile_grid::Xyz;
+
+type TileKey = (u8, u64, u64);
+
+/// Size-bounded tile cache, evicting least recently used tiles
+pub struct MemoryTileCache {
+    max_size: usize,
+    inner: Mutex<LruTiles>,
+}
+
+#[derive(Default)]
+struct LruTiles {
+    tiles: HashMap<TileKey, (TileResponseData, u64)>,
+    /// Tiles ordered by last access
+    access: BTreeMap<u64, TileKey>,
+    tick: u64,
+    size: usize,
+}
+
+impl MemoryTileCache {
+    /// Create cache with maximal size of tile data in bytes
+    pub fn new(max_size: usize) -> Self {
+        MemoryTileCache {
+            max_size,
+            inner: Mutex::new(LruTiles::default()),
+        }
+    }
+
+    pub fn get(&self, xyz: &Xyz) -> Option<TileResponseData> {
+        let key = (xyz.z, xyz.x, xyz.y);
+        let mut lru = self.inner.lock().unwrap();
+        lru.tick += 1;
+        let tick = lru.tick;
+        let (data, last_access) = lru.tiles.get_mut(&key)?;
+        let data = data.clone();
+        let prev_access = std::mem::replace(last_access, tick);
+        lru.access.remove(&prev_access);
+        lru.access.insert(tick, key);
+        Some(data)
+    }
+
+    pub fn put(&self, xyz: &Xyz, data: TileResponseData) {
+        let size = data.body.len();
+        if size > self.max_size {
+            return;
+        }
+        let key = (xyz.z, xyz.x, xyz.y);
+        let mut lru = self.inner.lock().unwrap();
+        lru.remove(&key);
+        while lru.size + size > self.max_size {
+            let Some(&oldest) = lru.access.values().next() else {
+                break;
+            };
+            lru.remove(&oldest);
+        }
+        lru.tick += 1;
+        let tick = lru.tick;
+        lru.tiles.insert(key, (data, tick));
+        lru.access.insert(tick, key);
+        lru.size += size;
+    }
+
+    pub fn remove(&self, xyz: &Xyz) {
+        self.inner.lock().unwrap().remove(&(xyz.z, xyz.x, xyz.y));
+    }
+
+    /// Size of cached tile data in bytes
+    pub fn size(&self) -> usize {
+        self.inner.lock().unwrap().size
+    }
+}
+
+impl LruTiles {
+    fn remove(&mut self, key: &TileKey) {
+        if let Some((data, last_access)) = self.tiles.remove(key) {
+            self.access.remove(&last_access);
+            self.size -= data.body.len();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tile(size: usize) -> TileResponseData {
+        let mut data = TileResponseData::new(vec![0; size]);
+        data.set_content_etag();
+        data
+    }
+
+    #[test]
+    fn lru_eviction() {
+        let cache = MemoryTileCache::new(100);
+        cache.put(&Xyz::new(0, 0, 1), tile(40));
+        cache.put(&Xyz::new(1, 0, 1), tile(40));
+        assert_eq!(cache.size(), 80);
+        // Access first tile, second tile is least recently used
+        assert!(cache.get(&Xyz::new(0, 0, 1)).is_some());
+        cache.put(&Xyz::new(0, 1, 1), tile(40));
+        assert_eq!(cache.size(), 80);
+        assert!(cache.get(&Xyz::new(1, 0, 1)).is_none());
+        assert!(cache.get(&Xyz::new(0, 0, 1)).is_some());
+        assert!(cache.get(&Xyz::new(0, 1, 1)).is_some());
+
+        // Replace tile
+        cache.put(&Xyz::new(0, 1, 1), tile(10));
+        assert_eq!(cache.size(), 50);
+        // Tiles larger than cache are not stored
+        cache.put(&Xyz::new(1, 1, 1), tile(200));
+        assert_eq!(cache.size(), 50);
+
+        cache.remove(&Xyz::new(0, 0, 1));
+        assert_eq!(cache.size(), 10);
+    }
+}
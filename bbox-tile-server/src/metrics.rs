@@ -0,0 +1,226 @@
+use once_cell::sync::OnceCell;
+use prometheus::core::Collector;
+use prometheus::proto::Metric;
+use prometheus::{HistogramOpts, HistogramVec, IntCounterVec, Registry};
+use std::time::Duration;
+
+/// Tile request metrics per tileset and zoom level
+#[derive(Clone)]
+pub struct TileMetrics {
+    pub cache_hits: IntCounterVec,
+    pub cache_misses: IntCounterVec,
+    pub render_duration_seconds: HistogramVec,
+    pub tile_size_bytes: HistogramVec,
+    pub layer_overflows: IntCounterVec,
+    pub geometries_repaired: IntCounterVec,
+    pub geometries_dropped: IntCounterVec,
+}
+
+/// Tile request statistics of a tileset, summed over all zoom levels
+#[derive(Default, PartialEq, Debug)]
+pub struct TilesetStats {
+    pub cache_hits: u64,
+    pub cache_misses: u64,
+    pub tiles_rendered: u64,
+    pub render_seconds: f64,
+    pub rendered_bytes: u64,
+}
+
+pub fn tile_metrics() -> &'static TileMetrics {
+    static METRICS: OnceCell<TileMetrics> = OnceCell::new();
+    METRICS.get_or_init(|| {
+        let labels = &["tileset", "zoom"];
+        let opts = prometheus::opts!("cache_hits_total", "Tiles delivered from cache")
+            .namespace("bbox_tile");
+        let cache_hits = IntCounterVec::new(opts, labels).unwrap();
+        let opts = prometheus::opts!("cache_misses_total", "Tiles not found in cache or expired")
+            .namespace("bbox_tile");
+        let cache_misses = IntCounterVec::new(opts, labels).unwrap();
+        let opts = HistogramOpts::new("render_duration_seconds", "Tile generation time")
+            .namespace("bbox_tile")
+            .buckets(vec![
+                0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+            ]);
+        let render_duration_seconds = HistogramVec::new(opts, labels).unwrap();
+        let opts = HistogramOpts::new("tile_size_bytes", "Size of generated tiles")
+            .namespace("bbox_tile")
+            .buckets(prometheus::exponential_buckets(256.0, 4.0, 8).unwrap());
+        let tile_size_bytes = HistogramVec::new(opts, labels).unwrap();
+        let opts = prometheus::opts!(
+            "layer_overflows_total",
+            "Tile layers exceeding feature count or size limits"
+        )
+        .namespace("bbox_tile");
+        let layer_overflows = IntCounterVec::new(opts, &["layer", "zoom"]).unwrap();
+        let opts = prometheus::opts!("geometries_repaired_total", "Repaired invalid geometries")
+            .namespace("bbox_tile");
+        let geometries_repaired = IntCounterVec::new(opts, &["layer", "zoom"]).unwrap();
+        let opts = prometheus::opts!(
+            "geometries_dropped_total",
+            "Features omitted because of invalid geometries"
+        )
+        .namespace("bbox_tile");
+        let geometries_dropped = IntCounterVec::new(opts, &["layer", "zoom"]).unwrap();
+        TileMetrics {
+            cache_hits,
+            cache_misses,
+            render_duration_seconds,
+            tile_size_bytes,
+            layer_overflows,
+            geometries_repaired,
+            geometries_dropped,
+        }
+    })
+}
+
+impl TileMetrics {
+    pub fn cache_hit(&self, tileset: &str, zoom: u8) {
+        self.cache_hits
+            .with_label_values(&[tileset, &zoom.to_string()])
+            .inc();
+    }
+    pub fn cache_miss(&self, tileset: &str, zoom: u8) {
+        self.cache_misses
+            .with_label_values(&[tileset, &zoom.to_string()])
+            .inc();
+    }
+    pub fn rendered(&self, tileset: &str, zoom: u8, duration: Duration) {
+        self.render_duration_seconds
+            .with_label_values(&[tileset, &zoom.to_string()])
+            .observe(duration.as_secs_f64());
+    }
+    pub fn tile_size(&self, tileset: &str, zoom: u8, size: usize) {
+        self.tile_size_bytes
+            .with_label_values(&[tileset, &zoom.to_string()])
+            .observe(size as f64);
+    }
+    pub fn layer_overflow(&self, layer: &str, zoom: u8) {
+        self.layer_overflows
+            .with_label_values(&[layer, &zoom.to_string()])
+            .inc();
+    }
+    pub fn invalid_geometries(&self, layer: &str, zoom: u8, repaired: usize, dropped: usize) {
+        let labels = [layer, &zoom.to_string()];
+        self.geometries_repaired
+            .with_label_values(&labels)
+            .inc_by(repaired as u64);
+        self.geometries_dropped
+            .with_label_values(&labels)
+            .inc_by(dropped as u64);
+    }
+    /// Statistics of a tileset since server start
+    pub fn tileset_stats(&self, tileset: &str) -> TilesetStats {
+        fn tileset_metrics<C: Collector>(collector: &C, tileset: &str) -> Vec<Metric> {
+            collector
+                .collect()
+                .into_iter()
+                .flat_map(|family| family.get_metric().to_vec())
+                .filter(|metric| {
+                    metric
+                        .get_label()
+                        .iter()
+                        .any(|label| label.get_name() == "tileset" && label.get_value() == tileset)
+                })
+                .collect()
+        }
+        let counter_sum = |counter: &IntCounterVec| -> u64 {
+            tileset_metrics(counter, tileset)
+                .iter()
+                .map(|metric| metric.get_counter().get_value() as u64)
+                .sum()
+        };
+        let render = tileset_metrics(&self.render_duration_seconds, tileset);
+        let sizes = tileset_metrics(&self.tile_size_bytes, tileset);
+        TilesetStats {
+            cache_hits: counter_sum(&self.cache_hits),
+            cache_misses: counter_sum(&self.cache_misses),
+            tiles_rendered: render
+                .iter()
+                .map(|metric| metric.get_histogram().get_sample_count())
+                .sum(),
+            render_seconds: render
+                .iter()
+                .map(|metric| metric.get_histogram().get_sample_sum())
+                .sum(),
+            rendered_bytes: sizes
+                .iter()
+                .map(|metric| metric.get_histogram().get_sample_sum() as u64)
+                .sum(),
+        }
+    }
+}
+
+pub fn register_metrics(prometheus: &Registry, metrics: &TileMetrics) {
+    prometheus
+        .register(Box::new(metrics.cache_hits.clone()))
+        .unwrap();
+    prometheus
+        .register(Box::new(metrics.cache_misses.clone()))
+        .unwrap();
+    prometheus
+        .register(Box::new(metrics.render_duration_seconds.clone()))
+        .unwrap();
+    prometheus
+        .register(Box::new(metrics.tile_size_bytes.clone()))
+        .unwrap();
+    prometheus
+        .register(Box::new(metrics.layer_overflows.clone()))
+        .unwrap();
+    prometheus
+        .register(Box::new(metrics.geometries_repaired.clone()))
+        .unwrap();
+    prometheus
+        .register(Box::new(metrics.geometries_dropped.clone()))
+        .unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tileset_zoom_labels() {
+        let metrics = tile_metrics();
+        metrics.cache_miss("ne_countries", 3);
+        metrics.cache_hit("ne_countries", 3);
+        metrics.cache_hit("ne_countries", 3);
+        metrics.tile_size("ne_countries", 3, 1000);
+        assert_eq!(
+            metrics
+                .cache_hits
+                .with_label_values(&["ne_countries", "3"])
+                .get(),
+            2
+        );
+        assert_eq!(
+            metrics
+                .tile_size_bytes
+                .with_label_values(&["ne_countries", "3"])
+                .get_sample_sum(),
+            1000.0
+        );
+
+        metrics.tile_size("ne_countries", 4, 500);
+        let stats = metrics.tileset_stats("ne_countries");
+        assert_eq!(stats.cache_hits, 2);
+        assert_eq!(stats.cache_misses, 1);
+        assert_eq!(stats.rendered_bytes, 1500);
+        assert_eq!(metrics.tileset_stats("unknown"), TilesetStats::default());
+
+        metrics.invalid_geometries("lakes", 8, 2, 1);
+        assert_eq!(
+            metrics
+                .geometries_repaired
+                .with_label_values(&["lakes", "8"])
+                .get(),
+            2
+        );
+
+        let registry = Registry::new();
+        register_metrics(&registry, metrics);
+        assert!(registry
+            .gather()
+            .iter()
+            .any(|mf| mf.get_name() == "bbox_tile_cache_hits_total"));
+    }
+}
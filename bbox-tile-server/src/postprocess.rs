@@ -0,0 +1,199 @@
+//! Post-processing of rendered tiles before caching.
+//!
+//! Processors are either registered by applications embedding the tile service or
+//! loaded from WebAssembly modules (requires the `wasm` feature).
+
+use crate::config::PostProcessCfg;
+use crate::datasource::TileSourceError;
+use bbox_core::Format;
+use log::info;
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use tile_grid::Xyz;
+
+/// Modification of rendered tiles, e.g. removing attributes or adding a watermark
+pub trait TilePostProcessor: Send + Sync {
+    /// Process uncompressed tile data in output `format`
+    fn process(
+        &self,
+        tileset: &str,
+        xyz: &Xyz,
+        format: &Format,
+        data: Vec<u8>,
+    ) -> Result<Vec<u8>, TileSourceError>;
+}
+
+static PROCESSORS: Lazy<RwLock<HashMap<String, Arc<dyn TilePostProcessor>>>> =
+    Lazy::new(Default::default);
+
+/// Register processor for `[[tileset.postprocess]]` configurations.
+/// Has to be called before the tile service is created.
+pub fn register_post_processor(name: &str, processor: impl TilePostProcessor + 'static) {
+    info!("Registering tile post-processor `{name}`");
+    PROCESSORS
+        .write()
+        .expect("processor registry")
+        .insert(name.to_string(), Arc::new(processor));
+}
+
+/// Processor instances of tileset configuration
+pub fn post_processors(
+    configs: &[PostProcessCfg],
+) -> Result<Vec<Arc<dyn TilePostProcessor>>, TileSourceError> {
+    configs
+        .iter()
+        .map(|cfg| match cfg {
+            PostProcessCfg::Registered { processor } => PROCESSORS
+                .read()
+                .expect("processor registry")
+                .get(processor)
+                .cloned()
+                .ok_or_else(|| {
+                    TileSourceError::PostProcessError(format!(
+                        "processor `{processor}` not registered"
+                    ))
+                }),
+            PostProcessCfg::Wasm { wasm } => {
+                #[cfg(feature = "wasm")]
+                {
+                    let processor = wasm_runner::WasmPostProcessor::from_file(wasm)?;
+                    Ok(Arc::new(processor) as Arc<dyn TilePostProcessor>)
+                }
+                #[cfg(not(feature = "wasm"))]
+                Err(TileSourceError::PostProcessError(format!(
+                    "Cannot load `{}` - WebAssembly support is not active",
+                    wasm.display()
+                )))
+            }
+        })
+        .collect()
+}
+
+/// Apply processors in configuration order
+pub fn apply(
+    processors: &[Arc<dyn TilePostProcessor>],
+    tileset: &str,
+    xyz: &Xyz,
+    format: &Format,
+    mut data: Vec<u8>,
+) -> Result<Vec<u8>, TileSourceError> {
+    for processor in processors {
+        data = processor.process(tileset, xyz, format, data)?;
+    }
+    Ok(data)
+}
+
+/// WebAssembly plugin runner.
+///
+/// The module exports its `memory`, an allocation function `alloc(len: u32) -> u32` and
+/// `process(ptr: u32, len: u32, z: u32, x: u32, y: u32) -> u64`, returning the pointer
+/// of the processed tile in the upper and its length in the lower 32 bits.
+#[cfg(feature = "wasm")]
+mod wasm_runner {
+    use super::TilePostProcessor;
+    use crate::datasource::TileSourceError;
+    use anyhow::Context;
+    use bbox_core::Format;
+    use std::path::Path;
+    use tile_grid::Xyz;
+    use wasmtime::{Engine, Instance, Module, Store};
+
+    pub struct WasmPostProcessor {
+        engine: Engine,
+        module: Module,
+    }
+
+    impl WasmPostProcessor {
+        pub fn from_file(path: &Path) -> Result<Self, TileSourceError> {
+            let engine = Engine::default();
+            let module = Module::from_file(&engine, path).map_err(|e| {
+                TileSourceError::PostProcessError(format!("`{}`: {e}", path.display()))
+            })?;
+            Ok(WasmPostProcessor { engine, module })
+        }
+        fn run(&self, xyz: &Xyz, data: &[u8]) -> anyhow::Result<Vec<u8>> {
+            // A new instance per tile keeps tiles isolated and allows concurrent processing
+            let mut store = Store::new(&self.engine, ());
+            let instance = Instance::new(&mut store, &self.module, &[])?;
+            let memory = instance
+                .get_memory(&mut store, "memory")
+                .context("missing `memory` export")?;
+            let alloc = instance.get_typed_func::<u32, u32>(&mut store, "alloc")?;
+            let process =
+                instance.get_typed_func::<(u32, u32, u32, u32, u32), u64>(&mut store, "process")?;
+            let len = u32::try_from(data.len())?;
+            let ptr = alloc.call(&mut store, len)?;
+            memory.write(&mut store, ptr as usize, data)?;
+            let result = process.call(
+                &mut store,
+                (ptr, len, xyz.z as u32, xyz.x as u32, xyz.y as u32),
+            )?;
+            let (out_ptr, out_len) = ((result >> 32) as usize, (result & 0xffff_ffff) as usize);
+            let mut out = vec![0; out_len];
+            memory.read(&store, out_ptr, &mut out)?;
+            Ok(out)
+        }
+    }
+
+    impl TilePostProcessor for WasmPostProcessor {
+        fn process(
+            &self,
+            _tileset: &str,
+            xyz: &Xyz,
+            _format: &Format,
+            data: Vec<u8>,
+        ) -> Result<Vec<u8>, TileSourceError> {
+            self.run(xyz, &data)
+                .map_err(|e| TileSourceError::PostProcessError(e.to_string()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Suffix;
+
+    impl TilePostProcessor for Suffix {
+        fn process(
+            &self,
+            tileset: &str,
+            _xyz: &Xyz,
+            _format: &Format,
+            mut data: Vec<u8>,
+        ) -> Result<Vec<u8>, TileSourceError> {
+            data.extend_from_slice(tileset.as_bytes());
+            Ok(data)
+        }
+    }
+
+    #[test]
+    fn registered_processors() {
+        register_post_processor("suffix", Suffix);
+        let configs = vec![
+            PostProcessCfg::Registered {
+                processor: "suffix".to_string(),
+            },
+            PostProcessCfg::Registered {
+                processor: "suffix".to_string(),
+            },
+        ];
+        let processors = post_processors(&configs).unwrap();
+        let data = apply(
+            &processors,
+            "ts",
+            &Xyz::new(0, 0, 0),
+            &Format::Mvt,
+            b"tile".to_vec(),
+        )
+        .unwrap();
+        assert_eq!(data, b"tiletsts");
+
+        let configs = vec![PostProcessCfg::Registered {
+            processor: "unknown".to_string(),
+        }];
+        assert!(post_processors(&configs).is_err());
+    }
+}
@@ -0,0 +1,162 @@
+//! Output format conversion of raster tiles.
+
+use crate::config::RasterEncodingCfg;
+use crate::datasource::TileSourceError;
+use bbox_core::Format;
+#[cfg(feature = "avif")]
+use image::codecs::avif::AvifEncoder;
+use image::codecs::{
+    jpeg::JpegEncoder,
+    png::PngEncoder,
+    webp::{WebPEncoder, WebPQuality},
+};
+use image::{ColorType, ImageEncoder};
+
+/// Quality of lossy formats, if not configured
+const DEFAULT_QUALITY: u8 = 85;
+/// AVIF encoder speed (1: slowest, 10: fastest)
+#[cfg(feature = "avif")]
+const AVIF_SPEED: u8 = 8;
+
+/// Offered raster tile formats
+#[derive(Clone, Debug)]
+pub struct RasterEncoding {
+    /// Tile formats, starting with the default format
+    formats: Vec<Format>,
+    quality: Option<u8>,
+}
+
+impl RasterEncoding {
+    pub fn from_config(cfg: &RasterEncodingCfg) -> Result<Self, TileSourceError> {
+        let formats = cfg
+            .formats
+            .iter()
+            .map(|suffix| match Format::from_suffix(suffix) {
+                Some(format @ (Format::Png | Format::Jpeg | Format::Webp)) => Ok(format),
+                #[cfg(feature = "avif")]
+                Some(Format::Avif) => Ok(Format::Avif),
+                _ => Err(TileSourceError::RasterError(format!(
+                    "unsupported raster tile format `{suffix}`"
+                ))),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        if formats.is_empty() {
+            return Err(TileSourceError::RasterError(
+                "no raster tile format configured".to_string(),
+            ));
+        }
+        if let Some(quality) = cfg.quality {
+            if !(1..=100).contains(&quality) {
+                return Err(TileSourceError::RasterError(format!(
+                    "invalid quality {quality}"
+                )));
+            }
+        }
+        Ok(RasterEncoding {
+            formats,
+            quality: cfg.quality,
+        })
+    }
+    pub fn formats(&self) -> &[Format] {
+        &self.formats
+    }
+    pub fn default_format(&self) -> &Format {
+        &self.formats[0]
+    }
+    /// Convert image into `format`
+    pub fn convert(&self, data: &[u8], format: &Format) -> Result<Vec<u8>, TileSourceError> {
+        let image = image::load_from_memory(data)?.to_rgba8();
+        let (width, height) = image.dimensions();
+        encode_rgba(image.as_raw(), width, height, format, self.quality)
+    }
+}
+
+/// Encode RGBA pixels into an image of the requested format.
+///
+/// Without `quality`, WebP is encoded lossless.
+pub(crate) fn encode_rgba(
+    rgba: &[u8],
+    width: u32,
+    height: u32,
+    format: &Format,
+    quality: Option<u8>,
+) -> Result<Vec<u8>, TileSourceError> {
+    let mut buf = Vec::new();
+    match format {
+        Format::Png => {
+            PngEncoder::new(&mut buf).write_image(rgba, width, height, ColorType::Rgba8)?
+        }
+        Format::Webp => {
+            match quality {
+                None => WebPEncoder::new_lossless(&mut buf).write_image(
+                    rgba,
+                    width,
+                    height,
+                    ColorType::Rgba8,
+                )?,
+                Some(quality) => {
+                    // Lossy encoding is only available with libwebp
+                    #[allow(deprecated)]
+                    WebPEncoder::new_with_quality(&mut buf, WebPQuality::lossy(quality))
+                        .write_image(rgba, width, height, ColorType::Rgba8)?
+                }
+            }
+        }
+        Format::Jpeg => {
+            let rgb = rgba
+                .chunks_exact(4)
+                .flat_map(|px| [px[0], px[1], px[2]])
+                .collect::<Vec<_>>();
+            JpegEncoder::new_with_quality(&mut buf, quality.unwrap_or(DEFAULT_QUALITY))
+                .write_image(&rgb, width, height, ColorType::Rgb8)?
+        }
+        #[cfg(feature = "avif")]
+        Format::Avif => AvifEncoder::new_with_speed_quality(
+            &mut buf,
+            AVIF_SPEED,
+            quality.unwrap_or(DEFAULT_QUALITY),
+        )
+        .write_image(rgba, width, height, ColorType::Rgba8)?,
+        _ => {
+            return Err(TileSourceError::RasterError(format!(
+                "unsupported raster format `{}`",
+                format.file_suffix()
+            )))
+        }
+    }
+    Ok(buf)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bbox_core::TileInfo;
+
+    fn encoding(formats: &[&str], quality: Option<u8>) -> Result<RasterEncoding, TileSourceError> {
+        RasterEncoding::from_config(&RasterEncodingCfg {
+            formats: formats.iter().map(|f| f.to_string()).collect(),
+            quality,
+        })
+    }
+
+    #[test]
+    fn format_conversion() {
+        let encoding = encoding(&["webp", "jpeg", "png"], Some(75)).unwrap();
+        assert_eq!(encoding.default_format(), &Format::Webp);
+        assert_eq!(encoding.formats().len(), 3);
+
+        let rgba = [255, 0, 0, 255].repeat(16);
+        let png = encode_rgba(&rgba, 4, 4, &Format::Png, None).unwrap();
+        for format in encoding.formats() {
+            let data = encoding.convert(&png, format).unwrap();
+            assert_eq!(TileInfo::detect(&data).unwrap().format, *format);
+        }
+        let lossless = encode_rgba(&rgba, 4, 4, &Format::Webp, None).unwrap();
+        assert_eq!(TileInfo::detect(&lossless).unwrap().format, Format::Webp);
+
+        assert!(encoding(&[], None).is_err());
+        assert!(encoding(&["pbf"], None).is_err());
+        assert!(encoding(&["png"], Some(0)).is_err());
+        assert!(encode_rgba(&rgba, 4, 4, &Format::Mvt, None).is_err());
+    }
+}
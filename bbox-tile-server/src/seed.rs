@@ -1,23 +1,55 @@
 use crate::cli::*;
-use crate::config::TileStoreCfg;
+use crate::config::{MbtilesStoreCfg, SourceParamCfg, StoreCompressionCfg, TileStoreCfg};
+use crate::datasource::reproject::wgs84_bounds;
 use crate::filter_params::FilterParams;
-use crate::service::{ServiceError, TileService};
-use crate::store::{s3putfiles, CacheLayout};
+use crate::seed_area::SeedArea;
+use crate::seed_changes::DataChanges;
+use crate::seed_progress::{default_checkpoint_path, SeedCheckpoint, SeedProgress};
+use crate::service::{ServiceError, TileService, TileSet};
+use crate::store::mbtiles::MbtilesStore;
+use crate::store::{s3putfiles, TileWriter};
+use bbox_core::events::{publish, Event, EventKind};
+use bbox_core::jobs::JobHandle;
+use bbox_core::Compression;
 use futures::{prelude::*, stream};
-use indicatif::{ProgressBar, ProgressStyle};
-use log::info;
+use log::{info, warn};
 use par_stream::prelude::*;
-use std::path::PathBuf;
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
 use std::sync::Arc;
-use tile_grid::BoundingBox;
-
-fn progress_bar() -> ProgressBar {
-    let progress = ProgressBar::new_spinner();
-    progress.set_style(
-        ProgressStyle::default_spinner()
-            .template("{elapsed_precise} ({per_sec}) {spinner} {pos} {msg}"),
-    );
-    progress
+use std::time::Duration;
+use tile_grid::{BoundingBox, Tms, Xyz};
+use tilejson::Bounds;
+use tokio::sync::Mutex;
+use tokio::time::Instant;
+
+/// Number of expired tiles deleted with one request
+const EXPIRE_BATCH_SIZE: usize = 1000;
+
+/// Limits the number of operations per second
+struct RateLimiter {
+    interval: Duration,
+    next: Mutex<Instant>,
+}
+
+impl RateLimiter {
+    fn new(rate: f64) -> Self {
+        RateLimiter {
+            interval: Duration::from_secs_f64(1.0 / rate),
+            next: Mutex::new(Instant::now()),
+        }
+    }
+    /// Wait until the next operation is allowed
+    async fn acquire(&self) {
+        let mut next = self.next.lock().await;
+        let now = Instant::now();
+        if *next > now {
+            tokio::time::sleep_until(*next).await;
+        }
+        *next = (*next).max(now) + self.interval;
+    }
 }
 
 /*
@@ -52,10 +84,12 @@ By-Feature (https://github.com/onthegomap/planetiler/blob/main/ARCHITECTURE.md):
 */
 
 impl TileService {
-    pub async fn seed_by_grid(&self, args: &SeedArgs) -> anyhow::Result<()> {
-        let progress = progress_bar();
-        let progress_main = progress.clone();
-
+    /// Seed tiles of a tileset. Returns the number of completed tiles.
+    pub async fn seed_by_grid(
+        &self,
+        args: &SeedArgs,
+        job: Option<JobHandle>,
+    ) -> anyhow::Result<u64> {
         let tileset_name = Arc::new(args.tileset.clone());
         let tileset = self
             .tileset(&args.tileset)
@@ -64,127 +98,429 @@ impl TileService {
         let service = Arc::new(self.clone());
         let tms = self.grid(&tileset.tms)?;
 
-        let bbox = if let Some(numlist) = &args.extent {
-            let arr: Vec<f64> = numlist
-                .split(',')
-                .map(|v| {
-                    v.parse()
-                        .expect("Error parsing 'extent' as list of float values")
-                })
-                .collect();
-            if arr.len() != 4 {
-                anyhow::bail!("Invalid extent (minx,miny,maxx,maxy)");
+        let (bbox, area) = tile_area(tms, &args.extent, &args.geometry)?;
+        let changes = self.data_changes(args, &tileset, tms).await?;
+
+        let (Some(cache_cfg), Some(mut tile_writer)) =
+            (tileset.cache_config(), tileset.store_writer.clone())
+        else {
+            return Err(ServiceError::TilesetWithoutCache(args.tileset.clone()).into());
+        };
+        let overwrite = args.overwrite.unwrap_or(true);
+
+        // Number of concurrent tasks (size >= #cores).
+        let concurrency = args.concurrency.unwrap_or(num_cpus::get());
+        let rate_limiter = match args.rate_limit {
+            Some(rate) if rate > 0.0 => Some(Arc::new(RateLimiter::new(rate))),
+            Some(_) => anyhow::bail!("Invalid rate limit (tiles per second > 0)"),
+            None => None,
+        };
+
+        let minzoom = args.minzoom.unwrap_or(0);
+        let maxzoom = args.maxzoom.unwrap_or(tms.maxzoom());
+        info!(
+            "Seeding tiles from level {minzoom} to {maxzoom} with {concurrency} concurrent tasks"
+        );
+        // Tiles affected by data changes, instead of all tiles in the seeding area
+        let changed_tiles = changes.map(|changes| changes.affected_tiles(tms, minzoom, maxzoom));
+        if let Some(tiles) = &changed_tiles {
+            info!("{} tiles affected by data changes", tiles.len());
+            if matches!(cache_cfg, TileStoreCfg::Pmtiles(_)) {
+                anyhow::bail!("Seeding changed tiles into a PMTiles archive is not supported");
             }
-            BoundingBox::new(arr[0], arr[1], arr[2], arr[3])
+        }
+
+        // Archive metadata describing the seeded tiles
+        let mut metadata = self.mbtiles_metadata(&args.tileset).await?;
+        metadata.tilejson.minzoom = Some(minzoom);
+        metadata.tilejson.maxzoom = Some(maxzoom);
+        if let Some(bounds) = wgs84_bounds(tms.crs().as_srid(), &bbox) {
+            metadata.tilejson.bounds = Some(Bounds::new(
+                bounds.left,
+                bounds.bottom,
+                bounds.right,
+                bounds.top,
+            ));
+        }
+        let compression = tile_writer.compression();
+        // PMTiles archives are written after seeding. Tiles are collected in an MBTiles file,
+        // which allows resuming interrupted runs.
+        let pmtiles_output = if let TileStoreCfg::Pmtiles(cfg) = cache_cfg {
+            let staging = staging_store(&cfg.path, &compression, args.resume, &metadata).await?;
+            let mut pmtiles_writer = std::mem::replace(&mut tile_writer, Box::new(staging.clone()));
+            pmtiles_writer.update_metadata(&metadata).await?;
+            Some((staging, pmtiles_writer))
         } else {
-            tms.xy_bbox()
+            None
         };
+        // Archive metadata describes a complete seeding run
+        if changed_tiles.is_none() {
+            tile_writer.update_metadata(&metadata).await?;
+        }
+        let tile_writer = Arc::new(tile_writer);
+
+        let checkpoint_path = args
+            .checkpoint
+            .clone()
+            .unwrap_or_else(|| default_checkpoint_path(&args.tileset));
+        let mut checkpoint = SeedCheckpoint::new(args, minzoom, maxzoom);
+        if args.resume {
+            match SeedCheckpoint::load(&checkpoint_path)? {
+                Some(saved) if saved.matches(&checkpoint) => {
+                    info!(
+                        "Resuming from {} ({} tiles completed)",
+                        checkpoint_path.display(),
+                        saved.total_completed()
+                    );
+                    checkpoint = saved;
+                }
+                Some(_) => anyhow::bail!(
+                    "Checkpoint {} was written with different seeding parameters",
+                    checkpoint_path.display()
+                ),
+                None => warn!(
+                    "Checkpoint {} not found - seeding all tiles",
+                    checkpoint_path.display()
+                ),
+            }
+        }
 
-        let Some(cache_cfg) = tileset.cache_config() else {
-            return Err(
-                ServiceError::TilesetNotFound("Cache configuration not found".to_string()).into(),
-            );
+        // Tiles within bbox intersecting the seeding geometry
+        let in_area = {
+            let tms = tms.clone();
+            move |xyz: &Xyz| {
+                area.as_ref()
+                    .map(|area| area.intersects(&tms.xy_bounds(xyz)))
+                    .unwrap_or(true)
+            }
         };
-        let tile_writer = Arc::new(tileset.store_writer.clone().unwrap());
-        let compression = tile_writer.compression();
 
-        // Number of worker threads (size >= #cores).
-        let threads = args.threads.unwrap_or(num_cpus::get());
+        let total = match &changed_tiles {
+            Some(tiles) => tiles.len() as u64,
+            None => tms
+                .xyz_iterator(&bbox, minzoom, maxzoom)
+                .filter(in_area.clone())
+                .count() as u64,
+        };
+        let mut progress = SeedProgress::new(checkpoint.clone(), checkpoint_path, total);
+        if let Some(job) = job {
+            progress = progress.with_job(job);
+        }
 
-        let minzoom = args.minzoom.unwrap_or(0);
-        let maxzoom = args.maxzoom.unwrap_or(tms.maxzoom());
-        let griditer = tms.xyz_iterator(&bbox, minzoom, maxzoom);
-        info!("Seeding tiles from level {minzoom} to {maxzoom}");
+        // Skip tiles completed in a previous run. Tiles are completed in grid iteration order.
+        let mut zoom_index: BTreeMap<u8, u64> = BTreeMap::new();
+        let tiles: Box<dyn Iterator<Item = Xyz> + Send + '_> = match changed_tiles {
+            Some(tiles) => Box::new(tiles.into_iter()),
+            None => Box::new(
+                tms.xyz_iterator(&bbox, minzoom, maxzoom)
+                    .filter(in_area.clone()),
+            ),
+        };
+        let griditer = tiles.filter(move |xyz| {
+            let index = zoom_index.entry(xyz.z).or_insert(0);
+            *index += 1;
+            *index > checkpoint.completed_tiles(xyz.z)
+        });
 
         // We setup different pipelines for certain scenarios.
         // Examples:
         // map service source -> tile store writer
         // map service source -> batch collector -> mbtiles store writer
 
-        let iter = griditer.map(move |xyz| {
-            let path = CacheLayout::Zxy.path_string(&PathBuf::new(), &xyz, &format);
-            progress.set_message(path.clone());
-            progress.inc(1);
-            xyz
-        });
-        let par_stream = stream::iter(iter).par_then(threads, move |xyz| {
-            let tileset = tileset_name.clone();
-            let filter = FilterParams::default();
-            let service = service.clone();
-            let compression = compression.clone();
-            async move {
-                let tile = service
-                    .read_tile(&tileset, &xyz, &filter, &format, compression)
-                    .await
-                    .unwrap();
-                (xyz, tile)
+        // Tiles already in the store are skipped without overwrite
+        let par_stream = stream::iter(griditer).par_then(concurrency, {
+            let tile_writer = tile_writer.clone();
+            move |xyz| {
+                let tileset = tileset_name.clone();
+                let filter = FilterParams::default();
+                let service = service.clone();
+                let compression = compression.clone();
+                let tile_writer = tile_writer.clone();
+                async move {
+                    if !overwrite && tile_writer.exists(&xyz).await {
+                        return (xyz, None);
+                    }
+                    let tile = service
+                        .read_tile(&tileset, &xyz, &filter, &format, compression)
+                        .await
+                        .unwrap();
+                    (xyz, Some(tile))
+                }
             }
         });
 
-        match cache_cfg {
-            TileStoreCfg::Files(_cfg) => {
-                par_stream
-                    .par_then(threads, move |(xyz, tile)| {
-                        let tile_writer = tile_writer.clone();
-                        async move {
+        // Stream of stored tiles, in grid iteration order
+        let tiles_done = match cache_cfg {
+            TileStoreCfg::Files(_) | TileStoreCfg::Tiered(_) => par_stream
+                .par_then(concurrency, move |(xyz, tile)| {
+                    let tile_writer = tile_writer.clone();
+                    let rate_limiter = rate_limiter.clone();
+                    async move {
+                        if let Some(tile) = tile {
+                            if let Some(rate_limiter) = rate_limiter {
+                                rate_limiter.acquire().await;
+                            }
                             let _ = tile_writer.put_tile(&xyz, tile).await;
                         }
-                    })
-                    .count()
-                    .await;
-            }
+                        xyz
+                    }
+                })
+                .boxed(),
             TileStoreCfg::S3(cfg) => {
                 info!("Writing tiles to {}", &cfg.path);
                 let s3_writer_thread_count = args.tasks.unwrap_or(256);
                 par_stream
                     .par_then(s3_writer_thread_count, move |(xyz, tile)| {
                         let s3_writer = tile_writer.clone();
+                        let rate_limiter = rate_limiter.clone();
                         async move {
-                            let _ = s3_writer.put_tile(&xyz, tile).await;
+                            if let Some(tile) = tile {
+                                if let Some(rate_limiter) = rate_limiter {
+                                    rate_limiter.acquire().await;
+                                }
+                                let _ = s3_writer.put_tile(&xyz, tile).await;
+                            }
+                            xyz
                         }
                     })
-                    .count()
-                    .await;
+                    .boxed()
             }
-            TileStoreCfg::Mbtiles(_) | TileStoreCfg::Pmtiles(_) => {
-                let tile_writer = tileset.store_writer.clone().unwrap();
+            TileStoreCfg::Mbtiles(_) | TileStoreCfg::Pmtiles(_) | TileStoreCfg::Redis(_) => {
+                let tile_writer = tile_writer.as_ref().clone();
+                if rate_limiter.is_some() {
+                    warn!("Rate limit ignored for MBTiles, PMTiles and Redis stores");
+                }
+                // Redis tiles are written with pipelined requests
                 let batch_size = 200; // For MBTiles, create the largest prepared statement supported by SQLite (999 parameters)
                 par_stream
                     .stateful_batching(tile_writer, |mut tile_writer, mut stream| async move {
                         let mut batch = Vec::with_capacity(batch_size);
+                        let mut tiles = Vec::with_capacity(batch_size);
                         while let Some((xyz, tile)) = stream.next().await {
-                            batch.push((xyz.z, xyz.x as u32, xyz.y as u32, tile));
+                            if let Some(tile) = tile {
+                                batch.push((xyz.z, xyz.x as u32, xyz.y as u32, tile));
+                            }
+                            tiles.push(xyz);
                             // let _ = tile_writer.put_tile_mut(&xyz, tile).await;
                             // batch.push((xyz.z, xyz.x as u32, xyz.y as u32, Vec::<u8>::new()));
-                            if batch.len() >= batch.capacity() {
+                            if tiles.len() >= batch_size {
                                 break;
                             }
                         }
-                        let empty = batch.is_empty();
-                        let _ = tile_writer.put_tiles(&batch).await;
+                        let empty = tiles.is_empty();
+                        if !batch.is_empty() {
+                            let _ = tile_writer.put_tiles(&batch).await;
+                        }
                         if empty {
                             let _ = tile_writer.finalize();
                         }
-                        (!empty).then_some(((), tile_writer, stream))
+                        (!empty).then_some((tiles, tile_writer, stream))
                     })
-                    .count()
-                    .await;
+                    .flat_map(stream::iter)
+                    .boxed()
             }
-            TileStoreCfg::NoStore => {
-                par_stream.count().await;
+            TileStoreCfg::NoStore => par_stream.map(|(xyz, _tile)| xyz).boxed(),
+        };
+
+        let progress = tiles_done
+            .fold(progress, |mut progress, xyz| async move {
+                progress.tile_done(&xyz);
+                progress
+            })
+            .await;
+        let tiles = progress.finish();
+        if let Some((staging, mut writer)) = pmtiles_output {
+            info!("Writing PMTiles archive");
+            for xyz in tms.xyz_iterator(&bbox, minzoom, maxzoom).filter(in_area) {
+                let tile = staging
+                    .mbt
+                    .get_tile(xyz.z, xyz.x as u32, xyz.y as u32)
+                    .await?;
+                if let Some(tile) = tile {
+                    writer.put_tile_mut(&xyz, tile).await?;
+                }
             }
+            writer.finalize()?;
+            staging.mbt.pool.close().await;
+            fs::remove_file(staging.mbt.mbtiles.filepath())?;
+        }
+        publish(Event::tileset(
+            EventKind::SeedCompleted,
+            &args.tileset,
+            json!({ "minzoom": args.minzoom, "maxzoom": args.maxzoom, "extent": args.extent, "tiles": tiles }),
+        ));
+        Ok(tiles)
+    }
+
+    /// Changes from `--changes` or `--changes-table` argument
+    async fn data_changes(
+        &self,
+        args: &SeedArgs,
+        tileset: &TileSet,
+        tms: &Tms,
+    ) -> anyhow::Result<Option<DataChanges>> {
+        if let Some(path) = &args.changes {
+            return Ok(Some(DataChanges::from_file(path)?));
+        }
+        let Some(table) = &args.changes_table else {
+            return Ok(None);
+        };
+        let datasource = match tileset.source_config() {
+            SourceParamCfg::Postgis(cfg) => cfg.datasource.as_deref(),
+            _ => None,
         };
+        let Some(ds) = self.datasources.pg_datasource(datasource) else {
+            anyhow::bail!("PostGIS datasource for changes table `{table}` not found");
+        };
+        let changes =
+            DataChanges::from_table(ds, table, &args.changes_geometry_field, tms.crs().as_srid())
+                .await?;
+        Ok(Some(changes))
+    }
 
-        progress_main.set_style(
-            ProgressStyle::default_spinner().template("{elapsed_precise} ({per_sec}) {msg}"),
-        );
-        let cnt = progress_main.position() + 1;
-        let elapsed = progress_main.elapsed().as_millis() as f64 / 1000.0;
-        progress_main.finish_with_message(format!("{cnt} tiles generated in {elapsed:.2}s"));
+    /// Delete cached tiles
+    pub async fn invalidate(&self, args: &InvalidateArgs) -> anyhow::Result<()> {
+        let tileset = self
+            .tileset(&args.tileset)
+            .ok_or(ServiceError::TilesetNotFound(args.tileset.clone()))?;
+        let Some(tile_writer) = &tileset.store_writer else {
+            return Err(ServiceError::TilesetWithoutCache(args.tileset.clone()).into());
+        };
+        let tms = self.grid(&tileset.tms)?;
+        let minzoom = args.minzoom.unwrap_or(0);
+        let maxzoom = args.maxzoom.unwrap_or(tms.maxzoom());
+
+        let event = || {
+            Event::tileset(
+                EventKind::TilesInvalidated,
+                &args.tileset,
+                json!({ "minzoom": minzoom, "maxzoom": maxzoom, "extent": args.extent }),
+            )
+        };
+
+        if args.extent.is_none() && args.geometry.is_none() {
+            info!("Deleting cached tiles from level {minzoom} to {maxzoom}");
+            for z in minzoom..=maxzoom {
+                tile_writer.delete_prefix(&z.to_string()).await?;
+            }
+            publish(event());
+            return Ok(());
+        }
+
+        let (bbox, area) = tile_area(tms, &args.extent, &args.geometry)?;
+        info!("Deleting cached tiles within area from level {minzoom} to {maxzoom}");
+        let mut griditer = tms.xyz_iterator(&bbox, minzoom, maxzoom).filter(|xyz| {
+            area.as_ref()
+                .map(|area| area.intersects(&tms.xy_bounds(xyz)))
+                .unwrap_or(true)
+        });
+        let mut cnt = 0;
+        loop {
+            let batch = griditer.by_ref().take(1000).collect::<Vec<_>>();
+            if batch.is_empty() {
+                break;
+            }
+            tile_writer.delete_tiles(&batch).await?;
+            cnt += batch.len();
+        }
+        info!("{cnt} tiles deleted");
+        publish(event());
+        Ok(())
+    }
 
+    /// Expire tiles of an osm2pgsql expire list or imposm expire directory
+    pub async fn expire(&self, args: &ExpireArgs) -> anyhow::Result<()> {
+        let changes = DataChanges::from_expire_path(&args.file)?;
+        let tiles = self
+            .expire_tiles(
+                &args.tileset,
+                &changes,
+                args.minzoom,
+                args.maxzoom,
+                args.regenerate,
+                args.concurrency,
+                None,
+            )
+            .await?;
+        info!("{tiles} tiles expired");
         Ok(())
     }
 
+    /// Delete expired tiles from cache and optionally render them again.
+    /// Returns the number of expired tiles.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn expire_tiles(
+        &self,
+        tileset_name: &str,
+        changes: &DataChanges,
+        minzoom: Option<u8>,
+        maxzoom: Option<u8>,
+        regenerate: bool,
+        concurrency: Option<usize>,
+        job: Option<JobHandle>,
+    ) -> anyhow::Result<u64> {
+        let tileset = self
+            .tileset(tileset_name)
+            .ok_or(ServiceError::TilesetNotFound(tileset_name.to_string()))?;
+        let Some(tile_writer) = &tileset.store_writer else {
+            return Err(ServiceError::TilesetWithoutCache(tileset_name.to_string()).into());
+        };
+        let tms = self.grid(&tileset.tms)?;
+        let Some((list_minzoom, list_maxzoom)) = changes.zoom_range() else {
+            info!("No expired tiles");
+            return Ok(0);
+        };
+        let minzoom = minzoom.unwrap_or(list_minzoom);
+        let maxzoom = maxzoom.unwrap_or(list_maxzoom).min(tms.maxzoom());
+        let tiles = changes.affected_tiles(tms, minzoom, maxzoom);
+        let total = tiles.len();
+        info!("Expiring {total} tiles from level {minzoom} to {maxzoom}");
+
+        let format = *tileset.tile_format();
+        let compression = tile_writer.compression();
+        let filter = FilterParams::default();
+        let concurrency = concurrency.unwrap_or(num_cpus::get());
+        let mut done = 0;
+        for batch in tiles.chunks(EXPIRE_BATCH_SIZE) {
+            if job.as_ref().map(|job| job.is_cancelled()).unwrap_or(false) {
+                anyhow::bail!("Tile expiry cancelled after {done} tiles");
+            }
+            tile_writer.delete_tiles(batch).await?;
+            for xyz in batch {
+                if let Some(memory_cache) = tileset.memory_cache_at(xyz.z) {
+                    memory_cache.remove(xyz);
+                }
+            }
+            if regenerate {
+                stream::iter(batch.iter().filter(|xyz| tileset.is_cachable_at(xyz.z)))
+                    .map(|xyz| {
+                        let (filter, compression) = (&filter, compression.clone());
+                        async move {
+                            let tile = self
+                                .read_tile(tileset_name, xyz, filter, &format, compression)
+                                .await?;
+                            tile_writer.put_tile(xyz, tile).await?;
+                            Ok::<_, anyhow::Error>(())
+                        }
+                    })
+                    .buffer_unordered(concurrency)
+                    .try_collect::<Vec<_>>()
+                    .await?;
+            }
+            done += batch.len();
+            if let Some(job) = &job {
+                let percent = done as f32 * 100.0 / total as f32;
+                job.set_progress(percent, Some(format!("{done}/{total} tiles")));
+            }
+        }
+        publish(Event::tileset(
+            EventKind::TilesInvalidated,
+            tileset_name,
+            json!({ "minzoom": minzoom, "maxzoom": maxzoom, "tiles": total }),
+        ));
+        Ok(total as u64)
+    }
+
     pub async fn upload(&self, args: &UploadArgs) -> anyhow::Result<()> {
         match args.mode {
             Mode::Sequential => s3putfiles::put_files_seq(args).await,
@@ -193,3 +529,56 @@ impl TileService {
         }
     }
 }
+
+/// MBTiles file collecting tiles of a PMTiles archive during seeding
+async fn staging_store(
+    pmtiles_path: &Path,
+    compression: &Compression,
+    resume: bool,
+    metadata: &martin_mbtiles::Metadata,
+) -> anyhow::Result<MbtilesStore> {
+    let cfg = MbtilesStoreCfg {
+        path: pmtiles_path.with_extension("seeding.mbtiles"),
+    };
+    if !resume && cfg.path.exists() {
+        fs::remove_file(&cfg.path)?;
+    }
+    let compression = match compression {
+        Compression::None => StoreCompressionCfg::None,
+        Compression::Gzip => StoreCompressionCfg::Gzip,
+        Compression::Brotli => StoreCompressionCfg::Brotli,
+    };
+    let store =
+        MbtilesStore::from_config_writable(&cfg, &Some(compression), metadata.clone()).await?;
+    Ok(store)
+}
+
+/// Tile bounding box and optional polygon from `extent` or `geometry` argument
+pub(crate) fn tile_area(
+    tms: &Tms,
+    extent: &Option<String>,
+    geometry: &Option<String>,
+) -> anyhow::Result<(BoundingBox, Option<SeedArea>)> {
+    let area = geometry.as_deref().map(SeedArea::from_arg).transpose()?;
+    let bbox = if let Some(area) = &area {
+        let Some(bbox) = area.bbox() else {
+            anyhow::bail!("Empty seeding geometry");
+        };
+        bbox
+    } else if let Some(numlist) = extent {
+        let arr: Vec<f64> = numlist
+            .split(',')
+            .map(|v| {
+                v.parse()
+                    .expect("Error parsing 'extent' as list of float values")
+            })
+            .collect();
+        if arr.len() != 4 {
+            anyhow::bail!("Invalid extent (minx,miny,maxx,maxy)");
+        }
+        BoundingBox::new(arr[0], arr[1], arr[2], arr[3])
+    } else {
+        tms.xy_bbox()
+    };
+    Ok((bbox, area))
+}
@@ -0,0 +1,135 @@
+//! Seeding area defined by a polygon geometry.
+
+use geo::{BoundingRect, Intersects};
+use geo_types::{coord, Geometry, GeometryCollection, Rect};
+use geozero::{geojson::GeoJson, wkt::WktStr, ToGeo};
+use serde_json::Value;
+use std::fs;
+use std::path::Path;
+use tile_grid::BoundingBox;
+
+/// Geometry limiting the tiles to seed (in grid reference system)
+#[derive(Clone, Debug)]
+pub struct SeedArea {
+    geometry: Geometry<f64>,
+}
+
+impl SeedArea {
+    /// Read geometry from a GeoJSON file, a GeoJSON string or a WKT string
+    pub fn from_arg(arg: &str) -> anyhow::Result<Self> {
+        let text = if Path::new(arg).is_file() {
+            fs::read_to_string(arg)?
+        } else {
+            arg.to_string()
+        };
+        let geometry = if text.trim_start().starts_with('{') {
+            geojson_geometry(&serde_json::from_str(&text)?)?
+        } else {
+            WktStr(&text).to_geo()?
+        };
+        Ok(SeedArea { geometry })
+    }
+
+    pub fn from_geometry(geometry: Geometry<f64>) -> Self {
+        SeedArea { geometry }
+    }
+
+    /// Single geometries of a collection or multi-geometry, keeping separate areas small
+    pub fn parts(&self) -> Vec<SeedArea> {
+        match &self.geometry {
+            Geometry::GeometryCollection(collection) => collection
+                .iter()
+                .flat_map(|geometry| SeedArea::from_geometry(geometry.clone()).parts())
+                .collect(),
+            Geometry::MultiPoint(multi) => multi
+                .iter()
+                .map(|point| SeedArea::from_geometry(Geometry::Point(*point)))
+                .collect(),
+            Geometry::MultiLineString(multi) => multi
+                .iter()
+                .map(|line| SeedArea::from_geometry(Geometry::LineString(line.clone())))
+                .collect(),
+            Geometry::MultiPolygon(multi) => multi
+                .iter()
+                .map(|polygon| SeedArea::from_geometry(Geometry::Polygon(polygon.clone())))
+                .collect(),
+            _ => vec![self.clone()],
+        }
+    }
+
+    /// Bounding box of the area
+    pub fn bbox(&self) -> Option<BoundingBox> {
+        self.geometry
+            .bounding_rect()
+            .map(|rect| BoundingBox::new(rect.min().x, rect.min().y, rect.max().x, rect.max().y))
+    }
+
+    /// Check whether tile bounds intersect the area
+    pub fn intersects(&self, bounds: &BoundingBox) -> bool {
+        let rect = Rect::new(
+            coord! { x: bounds.left, y: bounds.bottom },
+            coord! { x: bounds.right, y: bounds.top },
+        );
+        self.geometry.intersects(&rect)
+    }
+}
+
+/// Geometry of a GeoJSON geometry, feature or feature collection
+fn geojson_geometry(json: &Value) -> anyhow::Result<Geometry<f64>> {
+    match json["type"].as_str() {
+        Some("FeatureCollection") => {
+            let Some(features) = json["features"].as_array() else {
+                anyhow::bail!("Invalid GeoJSON FeatureCollection");
+            };
+            let geometries = features
+                .iter()
+                .map(geojson_geometry)
+                .collect::<anyhow::Result<Vec<_>>>()?;
+            Ok(Geometry::GeometryCollection(GeometryCollection(geometries)))
+        }
+        Some("Feature") => geojson_geometry(&json["geometry"]),
+        Some(_) => Ok(GeoJson(&json.to_string()).to_geo()?),
+        None => anyhow::bail!("Invalid GeoJSON geometry"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tile_grid::{tms, Xyz};
+
+    #[test]
+    fn polygon_coverage() {
+        let tms = tms().lookup("WebMercatorQuad").unwrap();
+        // Triangle in the north-western quadrant
+        let area = SeedArea::from_arg(
+            "POLYGON((-15000000 1000000,-1000000 1000000,-1000000 15000000,-15000000 1000000))",
+        )
+        .unwrap();
+        let bbox = area.bbox().unwrap();
+        assert_eq!(bbox.left, -15000000.0);
+        assert!(area.intersects(&tms.xy_bounds(&Xyz::new(0, 0, 1))));
+        assert!(!area.intersects(&tms.xy_bounds(&Xyz::new(1, 1, 1))));
+
+        let tiles = tms
+            .xyz_iterator(&bbox, 2, 2)
+            .filter(|xyz| area.intersects(&tms.xy_bounds(xyz)))
+            .count();
+        // Upper left tile of the bbox is outside of the triangle
+        assert_eq!(tms.xyz_iterator(&bbox, 2, 2).count(), 4);
+        assert_eq!(tiles, 3);
+    }
+
+    #[test]
+    fn geojson_area() {
+        let fc = r#"{"type": "FeatureCollection", "features": [
+            {"type": "Feature", "properties": {}, "geometry": {"type": "Polygon", "coordinates": [[[0,0],[10,0],[10,10],[0,0]]]}},
+            {"type": "Feature", "properties": {}, "geometry": {"type": "Point", "coordinates": [20,20]}}
+        ]}"#;
+        let area = SeedArea::from_arg(fc).unwrap();
+        let bbox = area.bbox().unwrap();
+        assert_eq!((bbox.right, bbox.top), (20.0, 20.0));
+        assert!(area.intersects(&BoundingBox::new(5.0, 1.0, 6.0, 2.0)));
+        assert!(!area.intersects(&BoundingBox::new(1.0, 5.0, 2.0, 6.0)));
+    }
+}
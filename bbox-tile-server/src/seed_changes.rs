@@ -0,0 +1,213 @@
+//! Tiles affected by data changes for differential seeding.
+
+use crate::seed_area::SeedArea;
+use bbox_core::pg_ds::PgDatasource;
+use geozero::wkb;
+use sqlx::Row;
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tile_grid::{Tms, Xyz};
+
+/// Changed data since the last seeding run
+pub enum DataChanges {
+    /// Tiles expired by osm2pgsql (`z/x/y` per line)
+    ExpireList(Vec<Xyz>),
+    /// Changed geometries (in grid reference system)
+    Geometries(Vec<SeedArea>),
+}
+
+impl DataChanges {
+    /// Read changes from an osm2pgsql expire list, a GeoJSON or WKT file
+    pub fn from_file(path: &str) -> anyhow::Result<Self> {
+        let text = fs::read_to_string(path)?;
+        if let Some(tiles) = parse_expire_list(&text) {
+            Ok(DataChanges::ExpireList(tiles))
+        } else {
+            let area = SeedArea::from_arg(&text)?;
+            Ok(DataChanges::Geometries(area.parts()))
+        }
+    }
+
+    /// Read an osm2pgsql expire list or all imposm expire lists (`*.tiles`) in a directory
+    pub fn from_expire_path(path: &Path) -> anyhow::Result<Self> {
+        let mut files = Vec::new();
+        collect_expire_files(path, &mut files)?;
+        files.sort();
+        let mut tiles = Vec::new();
+        for file in files {
+            let Some(expired) = parse_expire_list(&fs::read_to_string(&file)?) else {
+                anyhow::bail!("Invalid expire list {}", file.display());
+            };
+            tiles.extend(expired);
+        }
+        Ok(DataChanges::ExpireList(tiles))
+    }
+
+    /// Read tiles of an expire list (`z/x/y` per line)
+    pub fn from_expire_list(text: &str) -> anyhow::Result<Self> {
+        let Some(tiles) = parse_expire_list(text) else {
+            anyhow::bail!("Invalid expire list (z/x/y per line expected)");
+        };
+        Ok(DataChanges::ExpireList(tiles))
+    }
+
+    /// Zoom levels of expired tiles
+    pub fn zoom_range(&self) -> Option<(u8, u8)> {
+        let DataChanges::ExpireList(tiles) = self else {
+            return None;
+        };
+        let minzoom = tiles.iter().map(|xyz| xyz.z).min()?;
+        let maxzoom = tiles.iter().map(|xyz| xyz.z).max()?;
+        Some((minzoom, maxzoom))
+    }
+
+    /// Read changed geometries from a PostGIS table
+    pub async fn from_table(
+        ds: &PgDatasource,
+        table: &str,
+        geometry_field: &str,
+        srid: i32,
+    ) -> anyhow::Result<Self> {
+        let sql = format!(
+            r#"SELECT ST_Transform("{geometry_field}", {srid}) AS geom FROM {table} WHERE "{geometry_field}" IS NOT NULL"#
+        );
+        let mut areas = Vec::new();
+        for row in sqlx::query(&sql).fetch_all(&ds.pool).await? {
+            let wkb: wkb::Decode<geo_types::Geometry<f64>> = row.try_get("geom")?;
+            if let Some(geometry) = wkb.geometry {
+                areas.extend(SeedArea::from_geometry(geometry).parts());
+            }
+        }
+        Ok(DataChanges::Geometries(areas))
+    }
+
+    /// Affected tiles from level `minzoom` to `maxzoom`, ordered by zoom level
+    pub fn affected_tiles(&self, tms: &Tms, minzoom: u8, maxzoom: u8) -> Vec<Xyz> {
+        let mut tiles = BTreeSet::new();
+        match self {
+            DataChanges::ExpireList(expired) => {
+                for xyz in expired {
+                    for z in minzoom..=maxzoom {
+                        tiles.extend(tiles_at_zoom(xyz, z));
+                    }
+                }
+            }
+            DataChanges::Geometries(areas) => {
+                for area in areas {
+                    let Some(bbox) = area.bbox() else {
+                        continue;
+                    };
+                    tiles.extend(
+                        tms.xyz_iterator(&bbox, minzoom, maxzoom)
+                            .filter(|xyz| area.intersects(&tms.xy_bounds(xyz)))
+                            .map(|xyz| (xyz.z, xyz.y, xyz.x)),
+                    );
+                }
+            }
+        }
+        tiles
+            .into_iter()
+            .map(|(z, y, x)| Xyz::new(x, y, z))
+            .collect()
+    }
+}
+
+/// Tiles of an expire list, `None` if the text is not an expire list
+fn parse_expire_list(text: &str) -> Option<Vec<Xyz>> {
+    let mut tiles = Vec::new();
+    for line in text.lines().map(str::trim).filter(|line| !line.is_empty()) {
+        let mut parts = line.split('/');
+        let z = parts.next()?.parse().ok()?;
+        let x = parts.next()?.parse().ok()?;
+        let y = parts.next()?.parse().ok()?;
+        if parts.next().is_some() {
+            return None;
+        }
+        tiles.push(Xyz::new(x, y, z));
+    }
+    Some(tiles)
+}
+
+fn collect_expire_files(path: &Path, files: &mut Vec<PathBuf>) -> anyhow::Result<()> {
+    if path.is_dir() {
+        for entry in fs::read_dir(path)? {
+            let path = entry?.path();
+            if path.is_dir() || path.extension().map(|ext| ext == "tiles").unwrap_or(false) {
+                collect_expire_files(&path, files)?;
+            }
+        }
+    } else {
+        files.push(path.to_path_buf());
+    }
+    Ok(())
+}
+
+/// Parent tile or child tiles of `xyz` at zoom level `z` as (z, y, x)
+fn tiles_at_zoom(xyz: &Xyz, z: u8) -> Vec<(u8, u64, u64)> {
+    if z <= xyz.z {
+        let shift = xyz.z - z;
+        vec![(z, xyz.y >> shift, xyz.x >> shift)]
+    } else {
+        let shift = z - xyz.z;
+        let (x0, y0) = (xyz.x << shift, xyz.y << shift);
+        let n = 1u64 << shift;
+        (y0..y0 + n)
+            .flat_map(|y| (x0..x0 + n).map(move |x| (z, y, x)))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tile_grid::tms;
+
+    fn zxy(tiles: &[Xyz]) -> Vec<(u8, u64, u64)> {
+        tiles.iter().map(|xyz| (xyz.z, xyz.x, xyz.y)).collect()
+    }
+
+    #[test]
+    fn expire_list() {
+        let tiles = parse_expire_list("2/1/1\n2/2/1\n").unwrap();
+        assert_eq!(tiles.len(), 2);
+        assert!(parse_expire_list("POINT(0 0)").is_none());
+        assert_eq!(parse_expire_list("").unwrap().len(), 0);
+
+        let tms = tms().lookup("WebMercatorQuad").unwrap();
+        let changes = DataChanges::ExpireList(tiles);
+        let affected = zxy(&changes.affected_tiles(&tms, 1, 3));
+        // Two parent tiles, two tiles at level 2, eight child tiles
+        assert_eq!(affected.len(), 2 + 2 + 8);
+        assert_eq!(affected[0], (1, 0, 0));
+        assert!(affected.contains(&(3, 5, 3)));
+        assert_eq!(changes.zoom_range(), Some((2, 2)));
+    }
+
+    #[test]
+    fn imposm_expire_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        let day = dir.path().join("20240101");
+        fs::create_dir(&day).unwrap();
+        fs::write(day.join("120000.tiles"), "14/8514/5816\n").unwrap();
+        fs::write(day.join("120100.tiles"), "14/8515/5816\n15/17030/11632\n").unwrap();
+        fs::write(day.join("README"), "ignored").unwrap();
+        let changes = DataChanges::from_expire_path(dir.path()).unwrap();
+        assert_eq!(changes.zoom_range(), Some((14, 15)));
+    }
+
+    #[test]
+    fn changed_geometries() {
+        let tms = tms().lookup("WebMercatorQuad").unwrap();
+        // Points in the north-western and south-eastern quadrant
+        let area = SeedArea::from_arg(
+            "GEOMETRYCOLLECTION(POINT(-10000000 10000000),POINT(10000000 -10000000))",
+        )
+        .unwrap();
+        let changes = DataChanges::Geometries(area.parts());
+        let affected = zxy(&changes.affected_tiles(&tms, 0, 2));
+        assert_eq!(affected.len(), 1 + 2 + 2);
+        assert!(affected.contains(&(1, 0, 0)));
+        assert!(!affected.contains(&(1, 1, 0)));
+    }
+}
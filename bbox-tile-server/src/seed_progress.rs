@@ -0,0 +1,223 @@
+//! Seeding progress reporting and checkpoints for resuming interrupted seeds.
+
+use crate::cli::SeedArgs;
+use bbox_core::jobs::JobHandle;
+use indicatif::{ProgressBar, ProgressDrawTarget, ProgressStyle};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tile_grid::Xyz;
+
+/// Number of completed tiles between checkpoint updates
+const CHECKPOINT_INTERVAL: u64 = 1000;
+/// Number of completed tiles between job progress updates
+const JOB_PROGRESS_INTERVAL: u64 = 100;
+
+/// Seeding state persisted in checkpoint file
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct SeedCheckpoint {
+    pub tileset: String,
+    pub minzoom: u8,
+    pub maxzoom: u8,
+    pub extent: Option<String>,
+    #[serde(default)]
+    pub geometry: Option<String>,
+    /// Changes file or table of differential seeding
+    #[serde(default)]
+    pub changes: Option<String>,
+    /// Number of completed tiles per zoom level, in grid iteration order
+    pub completed: BTreeMap<u8, u64>,
+}
+
+impl SeedCheckpoint {
+    pub fn new(args: &SeedArgs, minzoom: u8, maxzoom: u8) -> Self {
+        SeedCheckpoint {
+            tileset: args.tileset.clone(),
+            minzoom,
+            maxzoom,
+            extent: args.extent.clone(),
+            geometry: args.geometry.clone(),
+            changes: args.changes.clone().or(args.changes_table.clone()),
+            completed: BTreeMap::new(),
+        }
+    }
+
+    pub fn load(path: &Path) -> anyhow::Result<Option<Self>> {
+        if !path.exists() {
+            return Ok(None);
+        }
+        let json = fs::read_to_string(path)?;
+        Ok(Some(serde_json::from_str(&json)?))
+    }
+
+    /// Write checkpoint atomically
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        let tmp_path = path.with_extension("tmp");
+        fs::write(&tmp_path, serde_json::to_vec_pretty(self)?)?;
+        fs::rename(tmp_path, path)?;
+        Ok(())
+    }
+
+    /// Checkpoint was written for the same seeding parameters
+    pub fn matches(&self, other: &SeedCheckpoint) -> bool {
+        self.tileset == other.tileset
+            && self.minzoom == other.minzoom
+            && self.maxzoom == other.maxzoom
+            && self.extent == other.extent
+            && self.geometry == other.geometry
+            && self.changes == other.changes
+    }
+
+    pub fn completed_tiles(&self, zoom: u8) -> u64 {
+        self.completed.get(&zoom).copied().unwrap_or(0)
+    }
+
+    pub fn total_completed(&self) -> u64 {
+        self.completed.values().sum()
+    }
+}
+
+/// Default checkpoint file of a tileset
+pub fn default_checkpoint_path(tileset: &str) -> PathBuf {
+    PathBuf::from(format!("seed-{tileset}.json"))
+}
+
+/// Progress of a seeding run with periodically saved checkpoint
+pub struct SeedProgress {
+    checkpoint: SeedCheckpoint,
+    path: PathBuf,
+    bar: ProgressBar,
+    unsaved: u64,
+    job: Option<JobHandle>,
+}
+
+impl SeedProgress {
+    /// Create progress for seeding `total` tiles. Completed tiles of `checkpoint` are counted as done.
+    pub fn new(checkpoint: SeedCheckpoint, path: PathBuf, total: u64) -> Self {
+        let bar = ProgressBar::new(total);
+        bar.set_style(
+            ProgressStyle::default_bar()
+                .template("{elapsed_precise} ({per_sec}, ETA {eta}) {bar:40} {pos}/{len} {msg}"),
+        );
+        bar.set_position(checkpoint.total_completed());
+        bar.reset_eta();
+        SeedProgress {
+            checkpoint,
+            path,
+            bar,
+            unsaved: 0,
+            job: None,
+        }
+    }
+
+    /// Report progress to a background job instead of drawing a progress bar
+    pub fn with_job(mut self, job: JobHandle) -> Self {
+        self.bar.set_draw_target(ProgressDrawTarget::hidden());
+        self.job = Some(job);
+        self
+    }
+
+    pub fn tile_done(&mut self, xyz: &Xyz) {
+        *self.checkpoint.completed.entry(xyz.z).or_insert(0) += 1;
+        self.bar
+            .set_message(format!("{}/{}/{}", xyz.z, xyz.x, xyz.y));
+        self.bar.inc(1);
+        self.unsaved += 1;
+        if self.unsaved >= CHECKPOINT_INTERVAL {
+            self.save();
+        }
+        if let Some(job) = &self.job {
+            if self.bar.position() % JOB_PROGRESS_INTERVAL == 0 {
+                let (pos, len) = (self.bar.position(), self.bar.length());
+                let percent = if len > 0 {
+                    pos as f32 * 100.0 / len as f32
+                } else {
+                    0.0
+                };
+                job.set_progress(percent, Some(format!("{pos}/{len} tiles")));
+            }
+        }
+    }
+
+    fn save(&mut self) {
+        if let Err(e) = self.checkpoint.save(&self.path) {
+            warn!("Writing checkpoint {} failed: {e}", self.path.display());
+        }
+        self.unsaved = 0;
+    }
+
+    /// Print statistics and remove checkpoint of completed seeding run. Returns the number of completed tiles.
+    pub fn finish(self) -> u64 {
+        let cnt = self.bar.position();
+        let elapsed = self.bar.elapsed().as_secs_f64();
+        let rate = if elapsed > 0.0 {
+            cnt as f64 / elapsed
+        } else {
+            0.0
+        };
+        self.bar.set_style(
+            ProgressStyle::default_spinner().template("{elapsed_precise} ({per_sec}) {msg}"),
+        );
+        let message = format!("{cnt} tiles generated in {elapsed:.2}s ({rate:.1} tiles/s)");
+        if let Some(job) = &self.job {
+            job.set_progress(100.0, Some(message.clone()));
+        }
+        self.bar.finish_with_message(message);
+        if self.path.exists() {
+            if let Err(e) = fs::remove_file(&self.path) {
+                warn!("Removing checkpoint {} failed: {e}", self.path.display());
+            }
+        }
+        cnt
+    }
+}
+
+impl Drop for SeedProgress {
+    fn drop(&mut self) {
+        // Persist progress of interrupted runs
+        if !self.bar.is_finished() && self.unsaved > 0 {
+            self.save();
+            info!("Seeding progress saved to {}", self.path.display());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checkpoint_roundtrip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("seed-test.json");
+        assert_eq!(SeedCheckpoint::load(&path).unwrap(), None);
+
+        let mut checkpoint = SeedCheckpoint {
+            tileset: "test".to_string(),
+            minzoom: 0,
+            maxzoom: 3,
+            extent: None,
+            geometry: None,
+            changes: None,
+            completed: BTreeMap::new(),
+        };
+        checkpoint.completed.insert(0, 1);
+        checkpoint.completed.insert(1, 3);
+        checkpoint.save(&path).unwrap();
+
+        let loaded = SeedCheckpoint::load(&path).unwrap().unwrap();
+        assert_eq!(loaded, checkpoint);
+        assert_eq!(loaded.completed_tiles(1), 3);
+        assert_eq!(loaded.completed_tiles(2), 0);
+        assert_eq!(loaded.total_completed(), 4);
+
+        let other = SeedCheckpoint {
+            maxzoom: 4,
+            ..checkpoint.clone()
+        };
+        assert!(!loaded.matches(&other));
+        assert!(loaded.matches(&checkpoint));
+    }
+}
@@ -1,37 +1,61 @@
 use crate::cli::Commands;
+use crate::coalesce::RequestCoalescer;
 use crate::config::*;
 use crate::datasource::wms_fcgi::{HttpRequestParams, MapService, WmsMetrics};
 use crate::datasource::{Datasources, SourceType, TileRead, TileSourceError};
 use crate::filter_params::FilterParams;
+use crate::memory_cache::MemoryTileCache;
+use crate::metrics::{register_metrics, tile_metrics, TileMetrics};
+use crate::postprocess::{self, post_processors, TilePostProcessor};
+use crate::raster_encoding::RasterEncoding;
+use crate::store::mbtiles::MbtilesStore;
+use crate::store::pmtiles::PmtilesStoreReader;
 use crate::store::{
-    store_reader_from_config, store_writer_from_config, TileReader, TileStoreError, TileWriter,
+    store_reader_from_config, store_writer_from_config, CacheState, TileReader, TileStoreError,
+    TileWriter,
 };
 use async_trait::async_trait;
-use bbox_core::config::{error_exit, CoreServiceCfg};
-use bbox_core::metrics::{no_metrics, NoMetrics};
+use bbox_core::config::{error_exit, CoreServiceCfg, DatasourceCfg, DsPostgisCfg};
+use bbox_core::config_check::ConfigReport;
+use bbox_core::file_search;
+use bbox_core::health::{DependencyHealth, HealthCheck};
+use bbox_core::metrics::start_span;
 use bbox_core::ogcapi::ApiLink;
+use bbox_core::pg_ds::PgDatasource;
+use bbox_core::reload::{DirChanges, DirectoryWatcher, Reloadable};
 use bbox_core::service::OgcApiService;
-use bbox_core::{Compression, Format, TileResponse};
+use bbox_core::{Compression, Format, TileResponse, TileResponseData};
 use clap::{ArgMatches, Args, FromArgMatches};
-use log::debug;
+use log::{debug, info, warn};
 use martin_mbtiles::Metadata;
 use once_cell::sync::OnceCell;
+use opentelemetry::{trace::Span, KeyValue};
+use prometheus::Registry;
 use serde_json::json;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::io::Cursor;
 use std::num::NonZeroU16;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
 use tile_grid::{tms, BoundingBox, RegistryError, TileMatrixSet, Tms, Xyz};
 use tilejson::TileJSON;
 
 #[derive(Clone)]
 pub struct TileService {
-    pub(crate) tilesets: Tilesets,
-    grids: HashMap<String, Tms>,
+    pub(crate) tilesets: Reloadable<Tilesets>,
+    pub(crate) grids: HashMap<String, Tms>,
+    pub(crate) datasources: Arc<Datasources>,
     // Map service backend
     pub(crate) map_service: Option<MapService>,
+    /// Tiles currently rendered
+    rendering: Arc<RequestCoalescer<TileResponseData>>,
 }
 
-pub type Tilesets = HashMap<String, TileSet>;
+pub type Tilesets = HashMap<String, Arc<TileSet>>;
+
+/// File patterns of tile archives published from directories
+const ARCHIVE_PATTERNS: [&str; 2] = ["*.mbtiles", "*.pmtiles"];
 
 #[derive(Clone)]
 pub struct TileSet {
@@ -44,21 +68,183 @@ pub struct TileSet {
     config: TileSetCfg,
     cache_cfg: Option<TileStoreCfg>,
     cache_limits: Option<CacheLimitCfg>,
+    memory_cache: Option<Arc<MemoryTileCache>>,
+    /// Output formats of raster tiles
+    encoding: Option<RasterEncoding>,
+    /// Tile stores of additional output formats
+    format_stores: HashMap<Format, (Box<dyn TileReader>, Box<dyn TileWriter>)>,
+    /// Processing of rendered tiles before caching
+    post_processors: Vec<Arc<dyn TilePostProcessor>>,
 }
 
 impl TileSet {
+    /// Tileset publishing the tiles of an MBTiles or PMTiles archive
+    async fn from_archive(path: &Path) -> Result<TileSet, ServiceError> {
+        let file = path.to_string_lossy();
+        let config = TileSetCfg::from_archive(&file)
+            .ok_or_else(|| ServiceError::UnknownFormat(file.to_string()))?;
+        let source: Box<dyn TileRead> = match &config.source {
+            SourceParamCfg::Mbtiles(cfg) => Box::new(
+                MbtilesStore::from_config(cfg)
+                    .await
+                    .map_err(TileStoreError::from)?,
+            ),
+            SourceParamCfg::Pmtiles(cfg) => Box::new(PmtilesStoreReader::from_config(cfg).await?),
+            _ => return Err(ServiceError::UnknownFormat(file.to_string())),
+        };
+        Ok(TileSet {
+            tms: "WebMercatorQuad".to_string(),
+            format: *source.default_format(),
+            source,
+            store_reader: None,
+            store_writer: None,
+            config,
+            cache_cfg: None,
+            cache_limits: None,
+            memory_cache: None,
+            encoding: None,
+            format_stores: HashMap::new(),
+            post_processors: Vec::new(),
+        })
+    }
+    /// Archive file of a tileset registered from a directory
+    fn archive_path(&self) -> Option<&Path> {
+        match &self.config.source {
+            SourceParamCfg::Mbtiles(cfg) => Some(cfg.path.as_path()),
+            SourceParamCfg::Pmtiles(cfg) => Some(cfg.path.as_path()),
+            _ => None,
+        }
+    }
+    pub fn name(&self) -> &str {
+        &self.config.name
+    }
+    /// Configured license of tileset data
+    pub fn license(&self) -> Option<&str> {
+        self.config.license.as_deref()
+    }
     pub fn tile_format(&self) -> &Format {
         &self.format
     }
+    pub fn source_config(&self) -> &SourceParamCfg {
+        &self.config.source
+    }
+    /// Configured output formats, starting with the default format
+    pub fn output_formats(&self) -> Option<&[Format]> {
+        self.encoding.as_ref().map(|encoding| encoding.formats())
+    }
+    /// Tiles in `format` are stored in the default tile store and memory cache
+    fn is_default_format(&self, format: &Format) -> bool {
+        self.encoding.is_none() || *format == self.format
+    }
+    fn format_reader(&self, format: &Format) -> Option<&dyn TileReader> {
+        if self.is_default_format(format) {
+            self.store_reader.as_deref()
+        } else {
+            self.format_stores
+                .get(format)
+                .map(|(reader, _)| reader.as_ref())
+        }
+    }
+    fn format_writer(&self, format: &Format) -> Option<&dyn TileWriter> {
+        if self.is_default_format(format) {
+            self.store_writer.as_deref()
+        } else {
+            self.format_stores
+                .get(format)
+                .map(|(_, writer)| writer.as_ref())
+        }
+    }
+    /// Request tile from source, convert it into the requested output format and apply post-processors
+    pub async fn render_tile(
+        &self,
+        service: &TileService,
+        xyz: &Xyz,
+        filter: &FilterParams,
+        format: &Format,
+        request_params: HttpRequestParams<'_>,
+    ) -> Result<TileResponse, TileSourceError> {
+        let tile = self
+            .render_source_tile(service, xyz, filter, format, request_params)
+            .await?;
+        if self.post_processors.is_empty() {
+            return Ok(tile);
+        }
+        let mut data = tile
+            .read_bytes(&Compression::None)
+            .map_err(|e| TileSourceError::PostProcessError(e.to_string()))?;
+        let processors = self.post_processors.clone();
+        let tileset = self.name().to_string();
+        let xyz = Xyz::new(xyz.x, xyz.y, xyz.z);
+        let output_format = *format;
+        let body = std::mem::take(&mut data.body);
+        data.body = tokio::task::spawn_blocking(move || {
+            postprocess::apply(&processors, &tileset, &xyz, &output_format, body)
+        })
+        .await
+        .map_err(|e| TileSourceError::PostProcessError(e.to_string()))??;
+        Ok(data.as_response(&Compression::None))
+    }
+    /// Request tile from source and convert it into the requested output format
+    async fn render_source_tile(
+        &self,
+        service: &TileService,
+        xyz: &Xyz,
+        filter: &FilterParams,
+        format: &Format,
+        request_params: HttpRequestParams<'_>,
+    ) -> Result<TileResponse, TileSourceError> {
+        let Some(encoding) = &self.encoding else {
+            return self
+                .source
+                .xyz_request(service, &self.tms, xyz, filter, format, request_params)
+                .await;
+        };
+        let render_format = *self.source.default_format();
+        let tile = self
+            .source
+            .xyz_request(
+                service,
+                &self.tms,
+                xyz,
+                filter,
+                &render_format,
+                request_params,
+            )
+            .await?;
+        if *format == render_format {
+            return Ok(tile);
+        }
+        let data = tile
+            .read_bytes(&Compression::None)
+            .map_err(|e| TileSourceError::RasterError(e.to_string()))?;
+        let encoding = encoding.clone();
+        let output_format = *format;
+        let body =
+            tokio::task::spawn_blocking(move || encoding.convert(&data.body, &output_format))
+                .await
+                .map_err(|e| TileSourceError::RasterError(e.to_string()))??;
+        let mut response = TileResponse::new();
+        response.set_content_type(format.content_type());
+        Ok(response.with_body(Box::new(Cursor::new(body))))
+    }
     pub fn is_cachable_at(&self, zoom: u8) -> bool {
         if self.store_reader.is_none() {
             return false;
         }
+        self.in_cache_limits(zoom)
+    }
+    fn in_cache_limits(&self, zoom: u8) -> bool {
         match self.cache_limits {
             Some(ref cl) => cl.minzoom <= zoom && cl.maxzoom.unwrap_or(99) >= zoom,
             None => true,
         }
     }
+    /// In-memory cache, if enabled for zoom level
+    pub fn memory_cache_at(&self, zoom: u8) -> Option<&MemoryTileCache> {
+        self.memory_cache
+            .as_deref()
+            .filter(|_| self.in_cache_limits(zoom))
+    }
     pub fn cache_config(&self) -> Option<&TileStoreCfg> {
         self.cache_cfg.as_ref()
     }
@@ -68,6 +254,26 @@ impl TileSet {
             .map(|s| s.compression())
             .unwrap_or(Compression::None)
     }
+    /// Expiration state of a tile read from cache
+    pub fn cache_state(&self, tile: &TileResponse) -> CacheState {
+        match self.config.cache_control {
+            Some(ref cfg) => CacheState::of_tile(cfg, tile.modified(), SystemTime::now()),
+            None => CacheState::Fresh,
+        }
+    }
+    /// HTTP Cache-Control header value
+    pub fn cache_control_header(&self) -> Option<String> {
+        let cfg = self.config.cache_control.as_ref()?;
+        let max_age = cfg.max_age?;
+        if cfg.stale_while_revalidate > 0 {
+            Some(format!(
+                "max-age={max_age}, stale-while-revalidate={}",
+                cfg.stale_while_revalidate
+            ))
+        } else {
+            Some(format!("max-age={max_age}"))
+        }
+    }
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -76,6 +282,8 @@ pub enum ServiceError {
     TilesetNotFound(String),
     #[error("Cache `{0}` not found")]
     CacheNotFound(String),
+    #[error("Tileset `{0}` has no cache")]
+    TilesetWithoutCache(String),
     #[error("Unknown format `{0}`")]
     UnknownFormat(String),
     #[error(transparent)]
@@ -86,6 +294,8 @@ pub enum ServiceError {
     TileStoreError(#[from] TileStoreError),
     #[error(transparent)]
     IoError(#[from] std::io::Error),
+    #[error("Invalid configuration:\n{0}")]
+    InvalidConfig(String),
 }
 
 impl actix_web::error::ResponseError for ServiceError {}
@@ -114,8 +324,75 @@ impl OgcApiService for TileService {
     type Config = TileServiceCfg;
     type CliCommands = Commands;
     type CliArgs = ServiceArgs;
-    type Metrics = NoMetrics;
+    type Metrics = TileMetrics;
 
+    async fn check_config(config: &Self::Config, report: &mut ConfigReport) {
+        const SERVICE: &str = "tile-server";
+        let mut grids = tms().clone();
+        for (i, grid) in config.grids.iter().enumerate() {
+            let custom = match grid {
+                GridCfg::Json { json } => {
+                    TileMatrixSet::from_json_file(json).map_err(|e| e.to_string())
+                }
+                GridCfg::Custom(cfg) => cfg.tile_matrix_set().map_err(|e| e.to_string()),
+            };
+            let result =
+                custom.and_then(|tms| grids.register(vec![tms], true).map_err(|e| e.to_string()));
+            report.check(SERVICE, format!("grid #{}", i + 1), &result);
+        }
+        for ds in &config.datasources {
+            let item = format!("datasource `{}`", ds.name);
+            match &ds.datasource {
+                DatasourceCfg::Postgis(cfg) => {
+                    let cfg = DsPostgisCfg {
+                        lazy: false,
+                        ..cfg.clone()
+                    };
+                    let result = PgDatasource::from_config(&cfg, None).await;
+                    report.check(SERVICE, item, &result);
+                }
+                DatasourceCfg::Gpkg(cfg) => {
+                    let result = std::fs::metadata(&cfg.path)
+                        .map_err(|e| format!("`{}`: {e}", cfg.path.display()));
+                    report.check(SERVICE, item, &result);
+                }
+                _ => {}
+            }
+        }
+        for store in &config.tilestores {
+            let item = format!("tilestore `{}`", store.name);
+            match &store.cache {
+                TileStoreCfg::Files(cfg) => report.check_writable_dir(SERVICE, item, &cfg.base_dir),
+                TileStoreCfg::Tiered(cfg) => {
+                    report.check_writable_dir(SERVICE, item, &cfg.l1.base_dir)
+                }
+                TileStoreCfg::Mbtiles(MbtilesStoreCfg { path })
+                | TileStoreCfg::Pmtiles(PmtilesStoreCfg { path }) => {
+                    let dir = path.parent().unwrap_or(Path::new("."));
+                    report.check_writable_dir(SERVICE, item, dir)
+                }
+                _ => {}
+            }
+        }
+        for ts in &config.tilesets {
+            let item = format!("tileset `{}`", ts.name);
+            let tms_id = ts.tms.as_deref().unwrap_or("WebMercatorQuad");
+            if let Err(e) = grids.lookup(tms_id) {
+                report.error(SERVICE, item, e);
+                continue;
+            }
+            match &ts.cache {
+                Some(name) if !config.tilestores.iter().any(|store| &store.name == name) => {
+                    report.error(SERVICE, item, ServiceError::CacheNotFound(name.clone()))
+                }
+                _ => report.ok(SERVICE, item),
+            }
+        }
+        for dir in &config.directories {
+            let result = std::fs::read_dir(&dir.dir).map_err(|e| format!("`{}`: {e}", dir.dir));
+            report.check(SERVICE, format!("directory `{}`", dir.dir), &result);
+        }
+    }
     async fn create(config: &Self::Config, _core_cfg: &CoreServiceCfg) -> Self {
         let mut tilesets = HashMap::new();
         let mut service_grids = HashMap::new();
@@ -123,7 +400,12 @@ impl OgcApiService for TileService {
         // Register custom grids
         let mut grids = tms().clone();
         for grid in &config.grids {
-            let custom = TileMatrixSet::from_json_file(&grid.json).unwrap_or_else(error_exit);
+            let custom = match grid {
+                GridCfg::Json { json } => {
+                    TileMatrixSet::from_json_file(json).unwrap_or_else(error_exit)
+                }
+                GridCfg::Custom(cfg) => cfg.tile_matrix_set().unwrap_or_else(error_exit),
+            };
             grids
                 .register(vec![custom], true)
                 .unwrap_or_else(error_exit);
@@ -142,15 +424,31 @@ impl OgcApiService for TileService {
             let tms_id = ts.tms.clone().unwrap_or("WebMercatorQuad".to_string());
             let tms = grids.lookup(&tms_id).unwrap_or_else(error_exit);
             let source = datasources.setup_tile_source(&ts.source, &tms).await;
-            let format = ts
-                .cache_format
+            let encoding = ts
+                .encoding
                 .as_ref()
-                .and_then(|suffix| Format::from_suffix(suffix))
-                .unwrap_or(*source.default_format()); // TODO: emit warning or error
-            let metadata = source
+                .map(|cfg| RasterEncoding::from_config(cfg).unwrap_or_else(error_exit));
+            let format = if let Some(encoding) = &encoding {
+                *encoding.default_format()
+            } else {
+                ts.cache_format
+                    .as_ref()
+                    .and_then(|suffix| Format::from_suffix(suffix))
+                    .unwrap_or(*source.default_format()) // TODO: emit warning or error
+            };
+            // Tiles of multiple output formats are stored in sub-paths
+            let store_name = |format: &Format| {
+                if encoding.is_some() {
+                    format!("{}/{}", ts.name, format.file_suffix())
+                } else {
+                    ts.name.clone()
+                }
+            };
+            let mut metadata = source
                 .mbtiles_metadata(ts, &format)
                 .await
                 .unwrap_or_else(error_exit);
+            ts.apply_metadata(&mut metadata.tilejson);
             let cache_cfg = stores
                 .get("<cli>")
                 .or(ts
@@ -168,7 +466,7 @@ impl OgcApiService for TileService {
                     store_writer_from_config(
                         &config.cache,
                         &config.compression,
-                        &ts.name,
+                        &store_name(&format),
                         &format,
                         metadata,
                     )
@@ -179,12 +477,53 @@ impl OgcApiService for TileService {
             };
             let store_reader = if let Some(config) = &cache_cfg {
                 Some(
-                    store_reader_from_config(&config.cache, &config.compression, &ts.name, &format)
-                        .await,
+                    store_reader_from_config(
+                        &config.cache,
+                        &config.compression,
+                        &store_name(&format),
+                        &format,
+                    )
+                    .await,
                 )
             } else {
                 None
             };
+            let mut format_stores = HashMap::new();
+            if let (Some(encoding), Some(config)) = (&encoding, &cache_cfg) {
+                // MBTiles and PMTiles archives contain tiles of the default format only
+                if matches!(
+                    config.cache,
+                    TileStoreCfg::Files(_)
+                        | TileStoreCfg::S3(_)
+                        | TileStoreCfg::Redis(_)
+                        | TileStoreCfg::Tiered(_)
+                ) {
+                    for tile_format in &encoding.formats()[1..] {
+                        let mut metadata = source
+                            .mbtiles_metadata(ts, tile_format)
+                            .await
+                            .unwrap_or_else(error_exit);
+                        ts.apply_metadata(&mut metadata.tilejson);
+                        let name = store_name(tile_format);
+                        let writer = store_writer_from_config(
+                            &config.cache,
+                            &config.compression,
+                            &name,
+                            tile_format,
+                            metadata,
+                        )
+                        .await;
+                        let reader = store_reader_from_config(
+                            &config.cache,
+                            &config.compression,
+                            &name,
+                            tile_format,
+                        )
+                        .await;
+                        format_stores.insert(*tile_format, (reader, writer));
+                    }
+                }
+            }
             let tileset = TileSet {
                 tms: tms_id.clone(),
                 source,
@@ -194,21 +533,50 @@ impl OgcApiService for TileService {
                 config: ts.clone(),
                 cache_cfg: cache_cfg.map(|cfg| cfg.cache),
                 cache_limits: ts.cache_limits.clone(),
+                memory_cache: ts
+                    .memory_cache
+                    .as_ref()
+                    .map(|cfg| Arc::new(MemoryTileCache::new(cfg.max_size as usize * 1024 * 1024))),
+                encoding,
+                format_stores,
+                post_processors: post_processors(&ts.post_processors).unwrap_or_else(error_exit),
             };
-            tilesets.insert(ts.name.clone(), tileset);
+            tilesets.insert(ts.name.clone(), Arc::new(tileset));
             service_grids.insert(tms_id, tms);
         }
-        TileService {
-            tilesets,
+
+        if !config.directories.is_empty() {
+            let tms_id = "WebMercatorQuad";
+            let tms = grids.lookup(tms_id).unwrap_or_else(error_exit);
+            service_grids.insert(tms_id.to_string(), tms);
+        }
+        for dir_cfg in &config.directories {
+            info!("Scanning '{}' for tile archives", dir_cfg.dir);
+            let changes = DirChanges {
+                added: ARCHIVE_PATTERNS
+                    .iter()
+                    .flat_map(|pattern| file_search::search(&dir_cfg.dir, pattern))
+                    .collect(),
+                ..Default::default()
+            };
+            register_archives(&mut tilesets, changes).await;
+        }
+
+        let service = TileService {
+            tilesets: Reloadable::new(tilesets),
             grids: service_grids,
+            datasources: Arc::new(datasources),
             map_service: None, // Assigned in run_service
-        }
+            rendering: Arc::new(RequestCoalescer::new()),
+        };
+        service.watch_directories(&config.directories);
+        service
     }
 
     async fn cli_run(&self, cli: &ArgMatches) -> bool {
         match Commands::from_arg_matches(cli) {
             Ok(Commands::Seed(seedargs)) => {
-                self.seed_by_grid(&seedargs)
+                self.seed_by_grid(&seedargs, None)
                     .await
                     .unwrap_or_else(error_exit);
                 true
@@ -217,6 +585,23 @@ impl OgcApiService for TileService {
                 self.upload(&uploadargs).await.unwrap_or_else(error_exit);
                 true
             }
+            Ok(Commands::Invalidate(args)) => {
+                self.invalidate(&args).await.unwrap_or_else(error_exit);
+                true
+            }
+            Ok(Commands::Expire(args)) => {
+                self.expire(&args).await.unwrap_or_else(error_exit);
+                true
+            }
+            Ok(Commands::Stats(args)) => {
+                let stats = self.tile_stats(&args).await.unwrap_or_else(error_exit);
+                if args.json {
+                    println!("{}", serde_json::to_string_pretty(&stats).unwrap());
+                } else {
+                    print!("{stats}");
+                }
+                true
+            }
             _ => false,
         }
     }
@@ -239,6 +624,14 @@ impl OgcApiService for TileService {
                 hreflang: None,
                 length: None,
             },
+            ApiLink {
+                href: "/tileMatrixSets".to_string(),
+                rel: Some("http://www.opengis.net/def/rel/ogc/1.0/tiling-schemes".to_string()),
+                type_: Some("application/json".to_string()),
+                title: Some("List of available tiling schemes".to_string()),
+                hreflang: None,
+                length: None,
+            },
         ]
     }
     fn conformance_classes(&self) -> Vec<String> {
@@ -246,9 +639,9 @@ impl OgcApiService for TileService {
             // Core
             "http://www.opengis.net/spec/ogcapi-tiles-1/1.0/conf/core".to_string(),
             // TileSet
-            // "http://www.opengis.net/spec/ogcapi-tiles-1/1.0/conf/tileset".to_string(),
+            "http://www.opengis.net/spec/ogcapi-tiles-1/1.0/conf/tileset".to_string(),
             // Tilesets list
-            // "http://www.opengis.net/spec/ogcapi-tiles-1/1.0/conf/tilesets-list".to_string(),
+            "http://www.opengis.net/spec/ogcapi-tiles-1/1.0/conf/tilesets-list".to_string(),
             // Dataset tilesets
             // "http://www.opengis.net/spec/ogcapi-tiles-1/1.0/conf/dataset-tilesets".to_string(),
             // Geodata tilesets
@@ -278,8 +671,81 @@ impl OgcApiService for TileService {
     fn openapi_yaml(&self) -> Option<&str> {
         Some(include_str!("openapi.yaml"))
     }
+    fn health_check(&self) -> Option<Arc<dyn HealthCheck>> {
+        Some(Arc::new(TileHealth {
+            datasources: self.datasources.clone(),
+            stores: self
+                .tilesets
+                .current()
+                .values()
+                .filter_map(|ts| ts.store_writer.clone())
+                .collect(),
+        }))
+    }
+    fn add_metrics(&self, prometheus: &Registry) {
+        register_metrics(prometheus, self.metrics());
+    }
     fn metrics(&self) -> &'static Self::Metrics {
-        no_metrics()
+        tile_metrics()
+    }
+}
+
+struct TileHealth {
+    datasources: Arc<Datasources>,
+    stores: Vec<Box<dyn TileWriter>>,
+}
+
+#[async_trait]
+impl HealthCheck for TileHealth {
+    fn name(&self) -> &str {
+        "tile-server"
+    }
+    async fn check(&self) -> Vec<DependencyHealth> {
+        let mut checks = self.datasources.check().await;
+        // Tilesets often share the same store
+        let mut checked = HashSet::new();
+        for store in &self.stores {
+            if let Some(health) = store.check().await {
+                if checked.insert((health.kind.clone(), health.name.clone())) {
+                    checks.push(health);
+                }
+            }
+        }
+        checks
+    }
+}
+
+/// Register tilesets of added or modified archives and remove tilesets of removed archives.
+/// Configured tilesets with the same name are kept.
+async fn register_archives(tilesets: &mut Tilesets, changes: DirChanges) {
+    for path in &changes.removed {
+        let name = tilesets
+            .iter()
+            .find(|(_, ts)| ts.archive_path() == Some(path.as_path()))
+            .map(|(name, _)| name.clone());
+        if let Some(name) = name {
+            info!("Removing tileset `{name}`");
+            tilesets.remove(&name);
+        }
+    }
+    for path in changes.added.iter().chain(&changes.modified) {
+        match TileSet::from_archive(path).await {
+            Ok(ts) => {
+                let name = ts.name().to_string();
+                if let Some(existing) = tilesets.get(&name) {
+                    if existing.archive_path() != Some(path.as_path()) {
+                        warn!(
+                            "Tileset `{name}` already exists - skipping `{}`",
+                            path.display()
+                        );
+                        continue;
+                    }
+                }
+                info!("Adding tileset `{name}`");
+                tilesets.insert(name, Arc::new(ts));
+            }
+            Err(e) => warn!("Failed to add tileset from `{}`: {e}", path.display()),
+        }
     }
 }
 
@@ -294,11 +760,33 @@ impl TileService {
     pub fn set_map_service(&mut self, service: &MapService) {
         self.map_service = Some(service.clone());
     }
-    pub fn tileset(&self, tileset: &str) -> Option<&TileSet> {
-        self.tilesets.get(tileset)
+    pub fn tileset(&self, tileset: &str) -> Option<Arc<TileSet>> {
+        self.tilesets.current().get(tileset).cloned()
     }
-    pub fn source(&self, tileset: &str) -> Option<&dyn TileRead> {
-        self.tilesets.source(tileset)
+    /// Currently registered tilesets
+    pub fn tilesets(&self) -> Arc<Tilesets> {
+        self.tilesets.current()
+    }
+    pub fn source(&self, tileset: &str) -> Option<Box<dyn TileRead>> {
+        self.tileset(tileset).map(|ts| ts.source.clone())
+    }
+    /// Update tilesets on changes of watched archive directories
+    fn watch_directories(&self, directories: &[TileDirectoryCfg]) {
+        for dir_cfg in directories.iter().filter(|cfg| cfg.watch) {
+            let tilesets = self.tilesets.clone();
+            let debounce = Duration::from_secs(dir_cfg.debounce.unwrap_or(2));
+            DirectoryWatcher::new(&dir_cfg.dir, &ARCHIVE_PATTERNS, debounce).spawn(
+                Duration::from_secs(1),
+                move |changes| {
+                    let tilesets = tilesets.clone();
+                    async move {
+                        let mut updated = (*tilesets.current()).clone();
+                        register_archives(&mut updated, changes).await;
+                        tilesets.replace(updated);
+                    }
+                },
+            );
+        }
     }
     pub fn grid(&self, tms: &str) -> Result<&Tms, tile_grid::Error> {
         self.grids
@@ -343,8 +831,7 @@ impl TileService {
             metrics,
         };
         let tile = ts
-            .source
-            .xyz_request(self, &ts.tms, xyz, filter, format, request_params)
+            .render_tile(self, xyz, filter, format, request_params)
             .await?;
         let data = tile.read_bytes(&compression)?;
         Ok(data.body)
@@ -360,39 +847,138 @@ impl TileService {
         compression: Compression,
         request_params: HttpRequestParams<'_>,
     ) -> Result<Option<TileResponse>, ServiceError> {
+        let tileset_name = tileset;
         let tileset = self
             .tileset(tileset)
             .ok_or(ServiceError::TilesetNotFound(tileset.to_string()))?;
-        if let Some(cache) = &tileset.store_reader {
+        let key = tile_key(tileset_name, xyz, format, filter);
+        if !filter.is_empty() {
+            // Tiles with request specific content are not cached
+            let response_data = self
+                .rendering
+                .run(key, || async {
+                    let tiledata = tileset
+                        .render_tile(self, xyz, filter, format, request_params)
+                        .await?;
+                    Ok::<_, ServiceError>(tiledata.read_bytes(&Compression::None)?)
+                })
+                .await?;
+            return Ok(Some(response_data.as_response(&compression)));
+        }
+        let memory_cache = tileset
+            .memory_cache_at(xyz.z)
+            .filter(|_| tileset.is_default_format(format));
+        if let Some(tile) = memory_cache.and_then(|cache| cache.get(xyz)) {
+            let tile = tile.as_response(&compression);
+            // Stale tiles are revalidated via tile store
+            if tileset.cache_state(&tile) == CacheState::Fresh {
+                debug!("Delivering tile from memory cache @ {xyz:?}");
+                self.metrics().cache_hit(tileset_name, xyz.z);
+                return Ok(Some(tile));
+            }
+        }
+        if let Some(cache) = tileset.format_reader(format) {
             if tileset.is_cachable_at(xyz.z) {
-                if let Some(tile) = cache.get_tile(xyz).await? {
-                    debug!("Delivering tile from cache @ {xyz:?}");
-                    let response = tile.with_compression(&compression);
-                    //TODO: check returned format
-                    return Ok(Some(response));
+                let mut span = start_span("tile_cache_lookup", tile_attributes(tileset_name, xyz));
+                let cached_tile = cache.get_tile(xyz).await?;
+                span.set_attribute(KeyValue::new("cache_hit", cached_tile.is_some()));
+                drop(span);
+                if let Some(tile) = cached_tile {
+                    match tileset.cache_state(&tile) {
+                        CacheState::Expired => {
+                            debug!("Cached tile expired @ {xyz:?}");
+                            self.metrics().cache_miss(tileset_name, xyz.z);
+                        }
+                        state => {
+                            self.metrics().cache_hit(tileset_name, xyz.z);
+                            if state == CacheState::Stale {
+                                debug!("Revalidating stale tile @ {xyz:?}");
+                                self.revalidate_tile(tileset_name, xyz, filter, format);
+                            }
+                            debug!("Delivering tile from cache @ {xyz:?}");
+                            if let Some(memory_cache) = memory_cache {
+                                let data = tile.read_bytes(&tileset.cache_compression())?;
+                                memory_cache.put(xyz, data.clone());
+                                return Ok(Some(data.as_response(&compression)));
+                            }
+                            let response = tile.with_compression(&compression);
+                            //TODO: check returned format
+                            return Ok(Some(response));
+                        }
+                    }
+                } else {
+                    self.metrics().cache_miss(tileset_name, xyz.z);
                 }
             }
         }
-        // Request tile and write into cache
-        debug!("Request tile from source @ {xyz:?}");
-        let tiledata = tileset
-            .source
-            .xyz_request(self, &tileset.tms, xyz, filter, format, request_params)
+        // Request tile and write into cache.
+        // Concurrent requests of the same tile wait for a single rendering.
+        let response_data = self
+            .rendering
+            .run(key, || async {
+                debug!("Request tile from source @ {xyz:?}");
+                let span = start_span("tile_render", tile_attributes(tileset_name, xyz));
+                let render_start = Instant::now();
+                let tiledata = tileset
+                    .render_tile(self, xyz, filter, format, request_params)
+                    .await?;
+                self.metrics()
+                    .rendered(tileset_name, xyz.z, render_start.elapsed());
+                drop(span);
+                // TODO: if tiledata.empty() { return Ok(None) }
+                // Read tile into memory
+                let mut response_data = tiledata.read_bytes(&tileset.cache_compression())?;
+                if tileset.is_cachable_at(xyz.z) || memory_cache.is_some() {
+                    debug!("Writing tile into cache @ {xyz:?}");
+                    response_data.set_modified(SystemTime::now());
+                    response_data.set_content_etag();
+                    self.metrics()
+                        .tile_size(tileset_name, xyz.z, response_data.body.len());
+                    if tileset.is_cachable_at(xyz.z) {
+                        if let Some(cache) = tileset.format_writer(format) {
+                            cache.put_tile(xyz, response_data.body.clone()).await?;
+                        }
+                    }
+                    if let Some(memory_cache) = memory_cache {
+                        memory_cache.put(xyz, response_data.clone());
+                    }
+                }
+                Ok::<_, ServiceError>(response_data)
+            })
             .await?;
-        // TODO: if tiledata.empty() { return Ok(None) }
-        if tileset.is_cachable_at(xyz.z) {
-            debug!("Writing tile into cache @ {xyz:?}");
-            // Read tile into memory
-            let response_data = tiledata.read_bytes(&tileset.cache_compression())?;
-            if let Some(cache) = &tileset.store_writer {
-                cache.put_tile(xyz, response_data.body.clone()).await?;
+        Ok(Some(response_data.as_response(&compression)))
+    }
+    /// Re-render tile in the background and replace it in the cache
+    fn revalidate_tile(&self, tileset: &str, xyz: &Xyz, filter: &FilterParams, format: &Format) {
+        let service = self.clone();
+        let tileset = tileset.to_string();
+        let xyz = Xyz::new(xyz.x, xyz.y, xyz.z);
+        let filter = filter.clone();
+        let format = *format;
+        actix_web::rt::spawn(async move {
+            let Some(ts) = service.tileset(&tileset) else {
+                return;
+            };
+            let Some(cache) = ts.format_writer(&format) else {
+                return;
+            };
+            let compression = cache.compression();
+            // Requests of a stale tile trigger a single revalidation
+            let key = format!("{}#revalidate", tile_key(&tileset, &xyz, &format, &filter));
+            let result = service
+                .rendering
+                .run(key, || async {
+                    let data = service
+                        .read_tile(&tileset, &xyz, &filter, &format, compression)
+                        .await?;
+                    cache.put_tile(&xyz, data.clone()).await?;
+                    Ok::<_, ServiceError>(TileResponseData::new(data))
+                })
+                .await;
+            if let Err(e) = result {
+                warn!("Revalidating tile {xyz:?} failed: {e}");
             }
-            let response = response_data.as_response(&compression);
-            Ok(Some(response))
-        } else {
-            let response = tiledata.with_compression(&compression);
-            Ok(Some(response))
-        }
+        });
     }
     /// TileJSON layer metadata (<https://github.com/mapbox/tilejson-spec>)
     pub async fn tilejson(&self, tileset: &str, base_url: &str) -> Result<TileJSON, ServiceError> {
@@ -400,6 +986,7 @@ impl TileService {
             .tileset(tileset)
             .ok_or(ServiceError::TilesetNotFound(tileset.to_string()))?;
         let mut tilejson = ts.source.tilejson(&ts.format).await?;
+        ts.config.apply_metadata(&mut tilejson);
         let suffix = tilejson
             .other
             .get("format")
@@ -419,7 +1006,9 @@ impl TileService {
         let ts = self
             .tileset(tileset)
             .ok_or(ServiceError::TilesetNotFound(tileset.to_string()))?;
-        Ok(ts.source.mbtiles_metadata(&ts.config, &ts.format).await?)
+        let mut metadata = ts.source.mbtiles_metadata(&ts.config, &ts.format).await?;
+        ts.config.apply_metadata(&mut metadata.tilejson);
+        Ok(metadata)
     }
 
     /// Autogenerated Style JSON (<https://www.mapbox.com/mapbox-gl-style-spec/>)
@@ -540,3 +1129,90 @@ impl TileService {
         }
     }
 }
+
+/// Key identifying tile requests with identical response
+fn tile_key(tileset: &str, xyz: &Xyz, format: &Format, filter: &FilterParams) -> String {
+    format!(
+        "{tileset}/{}/{}/{}.{}{}",
+        xyz.z,
+        xyz.x,
+        xyz.y,
+        format.file_suffix(),
+        filter.as_args()
+    )
+}
+
+/// Trace span attributes of a tile request
+fn tile_attributes(tileset: &str, xyz: &Xyz) -> Vec<KeyValue> {
+    vec![
+        KeyValue::new("tileset", tileset.to_string()),
+        KeyValue::new("z", xyz.z as i64),
+        KeyValue::new("x", xyz.x as i64),
+        KeyValue::new("y", xyz.y as i64),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use martin_tile_utils::{Encoding as TileEncoding, Format as TileFormat, TileInfo};
+    use tilejson::tilejson;
+
+    async fn create_mbtiles(path: &Path) {
+        let metadata = Metadata {
+            id: "osm".to_string(),
+            tile_info: TileInfo::new(TileFormat::Png, TileEncoding::Uncompressed),
+            tilejson: tilejson! { tiles: vec![] },
+            layer_type: None,
+            json: None,
+            agg_tiles_hash: None,
+        };
+        let cfg = MbtilesStoreCfg {
+            path: path.to_path_buf(),
+        };
+        MbtilesStore::from_config_writable(&cfg, &None, metadata)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn archive_registration() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("osm.mbtiles");
+        create_mbtiles(&path).await;
+
+        let mut tilesets = Tilesets::new();
+        let changes = DirChanges {
+            added: vec![path.clone(), dir.path().join("missing.pmtiles")],
+            ..Default::default()
+        };
+        register_archives(&mut tilesets, changes).await;
+        assert_eq!(tilesets.len(), 1);
+        assert_eq!(tilesets["osm"].archive_path(), Some(path.as_path()));
+
+        // Existing tilesets with the same name are kept
+        let existing = tilesets["osm"].clone();
+        std::fs::create_dir(dir.path().join("other")).unwrap();
+        let other = dir.path().join("other").join("osm.mbtiles");
+        create_mbtiles(&other).await;
+        let changes = DirChanges {
+            added: vec![other.clone()],
+            ..Default::default()
+        };
+        register_archives(&mut tilesets, changes).await;
+        assert!(Arc::ptr_eq(&tilesets["osm"], &existing));
+        let changes = DirChanges {
+            removed: vec![other],
+            ..Default::default()
+        };
+        register_archives(&mut tilesets, changes).await;
+        assert_eq!(tilesets.len(), 1);
+
+        let changes = DirChanges {
+            removed: vec![path],
+            ..Default::default()
+        };
+        register_archives(&mut tilesets, changes).await;
+        assert!(tilesets.is_empty());
+    }
+}
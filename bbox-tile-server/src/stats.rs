@@ -0,0 +1,175 @@
+//! Statistics and coverage audit of cached tiles.
+
+use crate::cli::StatsArgs;
+use crate::seed::tile_area;
+use crate::service::{ServiceError, TileService};
+use crate::store::TileReader;
+use futures::{stream, StreamExt, TryStreamExt};
+use log::info;
+use serde::Serialize;
+use std::collections::HashSet;
+use std::fmt;
+use tile_grid::Xyz;
+
+/// Tile statistics of a zoom level
+#[derive(Serialize, Default, Debug, PartialEq)]
+pub struct ZoomStats {
+    pub zoom: u8,
+    /// Number of stored tiles
+    pub tiles: u64,
+    /// Total size of stored tiles in bytes
+    pub bytes: u64,
+    pub min_size: Option<u64>,
+    pub max_size: Option<u64>,
+    pub avg_size: Option<u64>,
+    /// Number of tiles within the expected coverage
+    pub expected: u64,
+    /// Expected tiles missing in the store
+    pub missing: u64,
+}
+
+impl ZoomStats {
+    fn new(zoom: u8) -> Self {
+        ZoomStats {
+            zoom,
+            ..Default::default()
+        }
+    }
+    fn add_tile(&mut self, size: u64) {
+        self.tiles += 1;
+        self.bytes += size;
+        self.min_size = Some(self.min_size.map_or(size, |min| min.min(size)));
+        self.max_size = Some(self.max_size.map_or(size, |max| max.max(size)));
+        self.avg_size = Some(self.bytes / self.tiles);
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct TileStats {
+    pub tileset: String,
+    pub zoom_levels: Vec<ZoomStats>,
+}
+
+impl fmt::Display for TileStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Tileset {}", self.tileset)?;
+        writeln!(
+            f,
+            "{:>4} {:>12} {:>14} {:>10} {:>10} {:>10} {:>12} {:>12}",
+            "zoom", "tiles", "bytes", "min", "max", "avg", "expected", "missing"
+        )?;
+        let size = |size: Option<u64>| size.map(|s| s.to_string()).unwrap_or("-".to_string());
+        for stats in &self.zoom_levels {
+            writeln!(
+                f,
+                "{:>4} {:>12} {:>14} {:>10} {:>10} {:>10} {:>12} {:>12}",
+                stats.zoom,
+                stats.tiles,
+                stats.bytes,
+                size(stats.min_size),
+                size(stats.max_size),
+                size(stats.avg_size),
+                stats.expected,
+                stats.missing
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl TileService {
+    /// Scan tile store of a tileset and compare it with the expected coverage
+    pub async fn tile_stats(&self, args: &StatsArgs) -> anyhow::Result<TileStats> {
+        let tileset = self
+            .tileset(&args.tileset)
+            .ok_or(ServiceError::TilesetNotFound(args.tileset.clone()))?;
+        let Some(reader) = &tileset.store_reader else {
+            return Err(
+                ServiceError::TilesetNotFound("Cache configuration not found".to_string()).into(),
+            );
+        };
+        let tms = self.grid(&tileset.tms)?;
+        let (bbox, area) = tile_area(tms, &args.extent, &args.geometry)?;
+        let in_area = |xyz: &Xyz| {
+            area.as_ref()
+                .map(|area| area.intersects(&tms.xy_bounds(xyz)))
+                .unwrap_or(true)
+        };
+        let concurrency = args.concurrency.unwrap_or(num_cpus::get());
+
+        let mut zoom_levels = Vec::new();
+        for z in args.minzoom.unwrap_or(0)..=args.maxzoom {
+            info!("Scanning tiles of level {z}");
+            let mut stats = ZoomStats::new(z);
+            let expected = tms.xyz_iterator(&bbox, z, z).filter(in_area);
+            if let Some(stored) = reader.stored_tiles(z).await? {
+                let mut tiles = HashSet::with_capacity(stored.len());
+                for (xyz, size) in stored {
+                    stats.add_tile(size);
+                    tiles.insert((xyz.x, xyz.y));
+                }
+                for xyz in expected {
+                    stats.expected += 1;
+                    if !tiles.contains(&(xyz.x, xyz.y)) {
+                        stats.missing += 1;
+                    }
+                }
+            } else {
+                // Lookup of each expected tile
+                let sizes = stream::iter(expected)
+                    .map(|xyz| async move { tile_size(reader.as_ref(), &xyz).await })
+                    .buffer_unordered(concurrency)
+                    .try_collect::<Vec<_>>()
+                    .await?;
+                for size in sizes {
+                    stats.expected += 1;
+                    match size {
+                        Some(size) => stats.add_tile(size),
+                        None => stats.missing += 1,
+                    }
+                }
+            }
+            zoom_levels.push(stats);
+        }
+        Ok(TileStats {
+            tileset: args.tileset.clone(),
+            zoom_levels,
+        })
+    }
+}
+
+/// Size of a stored tile in bytes
+async fn tile_size(reader: &dyn TileReader, xyz: &Xyz) -> anyhow::Result<Option<u64>> {
+    let Some(tile) = reader.get_tile(xyz).await? else {
+        return Ok(None);
+    };
+    // Keep the stored compression
+    let compression = tile.compression();
+    let data = tile.read_bytes(&compression)?;
+    Ok(Some(data.body.len() as u64))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn zoom_stats() {
+        let mut stats = ZoomStats::new(3);
+        for size in [10, 30, 20] {
+            stats.add_tile(size);
+        }
+        assert_eq!(stats.tiles, 3);
+        assert_eq!(stats.bytes, 60);
+        assert_eq!((stats.min_size, stats.max_size), (Some(10), Some(30)));
+        assert_eq!(stats.avg_size, Some(20));
+
+        let report = TileStats {
+            tileset: "ne".to_string(),
+            zoom_levels: vec![stats, ZoomStats::new(4)],
+        }
+        .to_string();
+        assert!(report.lines().nth(2).unwrap().ends_with(" 0"));
+        assert!(report.lines().nth(3).unwrap().contains(" - "));
+    }
+}
@@ -1,10 +1,10 @@
 use crate::config::{FileStoreCfg, StoreCompressionCfg};
 use crate::store::{CacheLayout, TileReader, TileStoreError, TileWriter};
 use async_trait::async_trait;
-use bbox_core::{Compression, Format, TileResponse};
+use bbox_core::{content_etag, Compression, Format, TileResponse};
 use log::debug;
 use std::fs::{self, File};
-use std::io::{self, BufReader, BufWriter};
+use std::io::{self, BufWriter, Cursor, Read};
 use std::path::PathBuf;
 use tile_grid::Xyz;
 
@@ -34,6 +34,10 @@ impl FileStore {
         let compression = compression.clone().unwrap_or(StoreCompressionCfg::None);
         Self::new(base_dir, compression, *format)
     }
+    /// Path of tile file
+    pub(crate) fn tile_path(&self, xyz: &Xyz) -> PathBuf {
+        CacheLayout::Zxy.path(&self.base_dir, xyz, &self.format)
+    }
     #[allow(dead_code)]
     pub fn remove_dir_all(&self) -> std::io::Result<()> {
         fs::remove_dir_all(self.base_dir.as_path())
@@ -43,10 +47,7 @@ impl FileStore {
 #[async_trait]
 impl TileWriter for FileStore {
     fn compression(&self) -> Compression {
-        match self.compression {
-            StoreCompressionCfg::Gzip => Compression::Gzip,
-            StoreCompressionCfg::None => Compression::None,
-        }
+        Compression::from(&self.compression)
     }
     async fn exists(&self, xyz: &Xyz) -> bool {
         let p = CacheLayout::Zxy.path(&self.base_dir, xyz, &self.format);
@@ -65,21 +66,113 @@ impl TileWriter for FileStore {
             .map_err(|e| TileStoreError::FileError(fullpath.clone(), e))?;
         Ok(())
     }
+    async fn delete_tile(&self, xyz: &Xyz) -> Result<(), TileStoreError> {
+        let fullpath = CacheLayout::Zxy.path(&self.base_dir, xyz, &self.format);
+        debug!("Deleting {}", fullpath.display());
+        match fs::remove_file(&fullpath) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => {
+                Err(TileStoreError::FileError(fullpath, e))
+            }
+            _ => Ok(()),
+        }
+    }
+    async fn delete_prefix(&self, prefix: &str) -> Result<(), TileStoreError> {
+        let fullpath = self.base_dir.join(prefix);
+        debug!("Deleting {}", fullpath.display());
+        match fs::remove_dir_all(&fullpath) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => {
+                Err(TileStoreError::FileError(fullpath, e))
+            }
+            _ => Ok(()),
+        }
+    }
 }
 
 #[async_trait]
 impl TileReader for FileStore {
     async fn get_tile(&self, xyz: &Xyz) -> Result<Option<TileResponse>, TileStoreError> {
         let p = CacheLayout::Zxy.path(&self.base_dir, xyz, &self.format);
-        if let Ok(f) = File::open(p) {
+        if let Ok(mut f) = File::open(&p) {
             let mut response = TileResponse::new();
-            if self.compression == StoreCompressionCfg::Gzip {
-                response.insert_header(("Content-Encoding", "gzip"));
+            if let Some(encoding) = self.compression().content_encoding() {
+                response.insert_header(("Content-Encoding", encoding));
             }
+            if let Ok(modified) = f.metadata().and_then(|m| m.modified()) {
+                response.set_modified(modified);
+            }
+            // Tiles are read into memory for computing the ETag
+            let mut data = Vec::new();
+            f.read_to_end(&mut data)
+                .map_err(|e| TileStoreError::FileError(p, e))?;
+            response.set_etag(content_etag(&data));
             // TODO: Set content_type from `format`
-            Ok(Some(response.with_body(Box::new(BufReader::new(f)))))
+            Ok(Some(response.with_body(Box::new(Cursor::new(data)))))
         } else {
             Ok(None)
         }
     }
+    async fn stored_tiles(&self, z: u8) -> Result<Option<Vec<(Xyz, u64)>>, TileStoreError> {
+        let zoom_dir = self.base_dir.join(z.to_string());
+        let mut tiles = Vec::new();
+        let Ok(x_dirs) = fs::read_dir(&zoom_dir) else {
+            return Ok(Some(tiles));
+        };
+        for x_dir in x_dirs {
+            let x_dir = x_dir?;
+            let Some(x) = x_dir.file_name().to_str().and_then(|x| x.parse().ok()) else {
+                continue;
+            };
+            for entry in fs::read_dir(x_dir.path())? {
+                let entry = entry?;
+                let path = format!("{z}/{x}/{}", entry.file_name().to_string_lossy());
+                if let Some(xyz) = CacheLayout::Zxy.tile_of_path(&path, &self.format) {
+                    tiles.push((xyz, entry.metadata()?.len()));
+                }
+            }
+        }
+        Ok(Some(tiles))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn delete_tiles() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = FileStore::new(dir.path().into(), StoreCompressionCfg::None, Format::Mvt);
+        for xyz in [Xyz::new(0, 0, 1), Xyz::new(1, 0, 1), Xyz::new(0, 0, 2)] {
+            store.put_tile(&xyz, vec![1, 0, 1]).await.unwrap();
+        }
+        store.delete_tile(&Xyz::new(0, 0, 1)).await.unwrap();
+        assert!(!store.exists(&Xyz::new(0, 0, 1)).await);
+        assert!(store.exists(&Xyz::new(1, 0, 1)).await);
+        // Deleting missing tiles succeeds
+        store.delete_tile(&Xyz::new(0, 0, 1)).await.unwrap();
+
+        store.delete_prefix("1").await.unwrap();
+        assert!(!store.exists(&Xyz::new(1, 0, 1)).await);
+        assert!(store.exists(&Xyz::new(0, 0, 2)).await);
+    }
+
+    #[tokio::test]
+    async fn stored_tiles() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = FileStore::new(dir.path().into(), StoreCompressionCfg::None, Format::Mvt);
+        store
+            .put_tile(&Xyz::new(1, 0, 1), vec![1, 0, 1])
+            .await
+            .unwrap();
+        store.put_tile(&Xyz::new(0, 1, 1), vec![1]).await.unwrap();
+        fs::write(dir.path().join("1/0/notes.txt"), "ignored").unwrap();
+        let mut tiles = store.stored_tiles(1).await.unwrap().unwrap();
+        tiles.sort_by_key(|(xyz, _)| xyz.x);
+        let tiles: Vec<_> = tiles
+            .iter()
+            .map(|(xyz, size)| (xyz.x, xyz.y, *size))
+            .collect();
+        assert_eq!(tiles, vec![(0, 1, 1), (1, 0, 3)]);
+        assert!(store.stored_tiles(2).await.unwrap().unwrap().is_empty());
+    }
 }
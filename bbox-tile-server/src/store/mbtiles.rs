@@ -1,8 +1,8 @@
-use crate::config::MbtilesStoreCfg;
+use crate::config::{MbtilesStoreCfg, StoreCompressionCfg};
 use crate::mbtiles_ds::{Error as MbtilesDsError, MbtilesDatasource};
 use crate::store::{TileReader, TileStoreError, TileWriter};
 use async_trait::async_trait;
-use bbox_core::{Compression, TileResponse};
+use bbox_core::{content_etag, Compression, TileResponse};
 use log::info;
 use martin_mbtiles::{CopyDuplicateMode, MbtType, Metadata};
 use martin_tile_utils::{Encoding as TileEncoding, Format as TileFormat};
@@ -25,9 +25,18 @@ impl MbtilesStore {
     }
     pub async fn from_config_writable(
         cfg: &MbtilesStoreCfg,
-        metadata: Metadata,
+        compression: &Option<StoreCompressionCfg>,
+        mut metadata: Metadata,
     ) -> Result<Self, MbtilesDsError> {
         info!("Creating connection pool for {}", &cfg.path.display());
+        // Encoding of tiles in a new MBTiles file
+        metadata.tile_info.encoding = match compression {
+            Some(StoreCompressionCfg::None) => TileEncoding::Uncompressed,
+            Some(StoreCompressionCfg::Gzip) => TileEncoding::Gzip,
+            Some(StoreCompressionCfg::Brotli) => TileEncoding::Brotli,
+            None if metadata.tile_info.format == TileFormat::Mvt => TileEncoding::Gzip,
+            None => TileEncoding::Uncompressed,
+        };
         let mbt = MbtilesDatasource::from_config(cfg, Some(metadata)).await?;
         Ok(MbtilesStore { mbt })
     }
@@ -80,6 +89,10 @@ impl TileWriter for MbtilesStore {
             .await?;
         Ok(())
     }
+    async fn update_metadata(&mut self, metadata: &Metadata) -> Result<(), TileStoreError> {
+        self.mbt.update_metadata(metadata).await?;
+        Ok(())
+    }
 }
 
 #[async_trait]
@@ -90,10 +103,13 @@ impl TileReader for MbtilesStore {
                 let mut response = TileResponse::new();
                 if self.mbt.format_info.format == TileFormat::Mvt {
                     response.set_content_type("application/x-protobuf");
+                } else {
+                    response.set_content_type(self.mbt.format_info.format.content_type());
                 }
                 if let Some(encoding) = self.mbt.format_info.encoding.content_encoding() {
                     response.insert_header(("Content-Encoding", encoding));
                 }
+                response.set_etag(content_etag(&content));
                 let body = Box::new(Cursor::new(content));
                 Some(response.with_body(body))
             } else {
@@ -101,4 +117,60 @@ impl TileReader for MbtilesStore {
             };
         Ok(resp)
     }
+    async fn stored_tiles(&self, z: u8) -> Result<Option<Vec<(Xyz, u64)>>, TileStoreError> {
+        let tiles = self
+            .mbt
+            .tile_sizes(z)
+            .await?
+            .into_iter()
+            .map(|(x, y, size)| (Xyz::new(x as u64, y as u64, z), size))
+            .collect();
+        Ok(Some(tiles))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use martin_tile_utils::TileInfo;
+    use tilejson::tilejson;
+
+    #[tokio::test]
+    async fn mbtiles_roundtrip() {
+        let dir = tempfile::tempdir().unwrap();
+        let cfg = MbtilesStoreCfg {
+            path: dir.path().join("roundtrip.mbtiles"),
+        };
+        let metadata = Metadata {
+            id: "roundtrip".to_string(),
+            tile_info: TileInfo::new(TileFormat::Png, TileEncoding::Uncompressed),
+            tilejson: tilejson! { tiles: vec![] },
+            layer_type: None,
+            json: None,
+            agg_tiles_hash: None,
+        };
+        let store = MbtilesStore::from_config_writable(&cfg, &None, metadata)
+            .await
+            .unwrap();
+        assert_eq!(store.compression(), Compression::None);
+
+        let xyz = Xyz::new(1, 2, 3);
+        assert!(!store.exists(&xyz).await);
+        store.put_tile(&xyz, vec![1, 2, 3]).await.unwrap();
+        assert!(store.exists(&xyz).await);
+
+        let tile = store.get_tile(&xyz).await.unwrap().unwrap();
+        assert_eq!(tile.content_type().unwrap(), "image/png");
+        let data = tile.read_bytes(&Compression::None).unwrap();
+        assert_eq!(data.body, vec![1, 2, 3]);
+
+        let mut store = store;
+        let mut metadata = store.mbt.get_metadata().await.unwrap();
+        metadata.tilejson.minzoom = Some(2);
+        metadata.tilejson.maxzoom = Some(5);
+        store.update_metadata(&metadata).await.unwrap();
+        let metadata = store.mbt.get_metadata().await.unwrap();
+        assert_eq!(metadata.tilejson.minzoom, Some(2));
+        assert_eq!(metadata.tilejson.maxzoom, Some(5));
+    }
 }
@@ -2,22 +2,28 @@
 pub mod files;
 pub mod mbtiles;
 pub mod pmtiles;
+pub mod redis;
 pub mod s3;
 pub mod s3putfiles;
+pub mod tiered;
 
-use crate::config::{StoreCompressionCfg, TileStoreCfg};
+use crate::config::{CacheControlCfg, StoreCompressionCfg, TileStoreCfg};
 use crate::mbtiles_ds::Error as MbtilesDsError;
 use crate::store::files::FileStore;
 use crate::store::mbtiles::MbtilesStore;
 use crate::store::pmtiles::{PmtilesStoreReader, PmtilesStoreWriter};
+use crate::store::redis::RedisStore;
 use crate::store::s3::{S3Store, S3StoreError};
+use crate::store::tiered::TieredStore;
 use async_trait::async_trait;
 use bbox_core::config::error_exit;
+use bbox_core::health::DependencyHealth;
 use bbox_core::{Compression, Format, TileResponse};
 use dyn_clone::{clone_trait_object, DynClone};
 use log::warn;
 use martin_mbtiles::{MbtError, Metadata};
 use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
 use tile_grid::Xyz;
 
 #[derive(thiserror::Error, Debug)]
@@ -28,6 +34,8 @@ pub enum TileStoreError {
     ArgMissing(String),
     #[error("Operation not supported on readonly data store")]
     ReadOnly,
+    #[error("Deleting tiles is not supported by this store")]
+    DeleteUnsupported,
     #[error(transparent)]
     IoError(#[from] std::io::Error),
     #[error(transparent)]
@@ -40,6 +48,8 @@ pub enum TileStoreError {
     MbtError(#[from] MbtError),
     #[error(transparent)]
     PmtilesError(#[from] ::pmtiles::error::Error),
+    #[error(transparent)]
+    RedisError(#[from] ::redis::RedisError),
 }
 
 #[async_trait]
@@ -71,6 +81,29 @@ pub trait TileWriter: DynClone + Send + Sync {
     fn finalize(&mut self) -> Result<(), TileStoreError> {
         Ok(())
     }
+    /// Replace archive metadata like zoom range and bounds. Has to be called before writing tiles.
+    async fn update_metadata(&mut self, _metadata: &Metadata) -> Result<(), TileStoreError> {
+        Ok(())
+    }
+    /// Delete tile from store
+    async fn delete_tile(&self, _xyz: &Xyz) -> Result<(), TileStoreError> {
+        Err(TileStoreError::DeleteUnsupported)
+    }
+    /// Delete multiple tiles from store
+    async fn delete_tiles(&self, tiles: &[Xyz]) -> Result<(), TileStoreError> {
+        for xyz in tiles {
+            self.delete_tile(xyz).await?;
+        }
+        Ok(())
+    }
+    /// Delete all tiles below a path prefix like `{z}` or `{z}/{x}`
+    async fn delete_prefix(&self, _prefix: &str) -> Result<(), TileStoreError> {
+        Err(TileStoreError::DeleteUnsupported)
+    }
+    /// Check reachability of a remote store
+    async fn check(&self) -> Option<DependencyHealth> {
+        None
+    }
 }
 
 clone_trait_object!(TileWriter);
@@ -78,11 +111,56 @@ clone_trait_object!(TileWriter);
 #[async_trait]
 pub trait TileReader: DynClone + Send + Sync {
     /// Lookup tile and return Read stream, if found
+    /// The modification time is returned as `Last-Modified` header, if the store keeps it
     async fn get_tile(&self, xyz: &Xyz) -> Result<Option<TileResponse>, TileStoreError>;
+    /// Stored tiles of zoom level `z` with their size in bytes.
+    /// Returns `None` if the store doesn't support listing tiles.
+    async fn stored_tiles(&self, _z: u8) -> Result<Option<Vec<(Xyz, u64)>>, TileStoreError> {
+        Ok(None)
+    }
 }
 
 clone_trait_object!(TileReader);
 
+impl From<&StoreCompressionCfg> for Compression {
+    fn from(cfg: &StoreCompressionCfg) -> Self {
+        match cfg {
+            StoreCompressionCfg::None => Compression::None,
+            StoreCompressionCfg::Gzip => Compression::Gzip,
+            StoreCompressionCfg::Brotli => Compression::Brotli,
+        }
+    }
+}
+
+/// Expiration state of a cached tile
+#[derive(Debug, PartialEq)]
+pub enum CacheState {
+    /// Deliver tile from cache
+    Fresh,
+    /// Deliver tile from cache and re-render it in the background
+    Stale,
+    /// Re-render tile
+    Expired,
+}
+
+impl CacheState {
+    /// Expiration state of a tile modified at `modified`.
+    /// Tiles without modification time never expire.
+    pub fn of_tile(cfg: &CacheControlCfg, modified: Option<SystemTime>, now: SystemTime) -> Self {
+        let (Some(max_age), Some(modified)) = (cfg.max_age, modified) else {
+            return CacheState::Fresh;
+        };
+        let age = now.duration_since(modified).unwrap_or(Duration::ZERO);
+        if age <= Duration::from_secs(max_age) {
+            CacheState::Fresh
+        } else if age <= Duration::from_secs(max_age + cfg.stale_while_revalidate) {
+            CacheState::Stale
+        } else {
+            CacheState::Expired
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 pub enum CacheLayout {
     Zxy,
@@ -108,6 +186,19 @@ impl CacheLayout {
             .to_string_lossy()
             .to_string()
     }
+    /// Tile coordinates of a path relative to the base directory
+    pub fn tile_of_path(&self, path: &str, format: &Format) -> Option<Xyz> {
+        match self {
+            CacheLayout::Zxy => {
+                let path = path.strip_suffix(&format!(".{}", format.file_suffix()))?;
+                let mut parts = path.split('/');
+                let z = parts.next()?.parse().ok()?;
+                let x = parts.next()?.parse().ok()?;
+                let y = parts.next()?.parse().ok()?;
+                parts.next().is_none().then(|| Xyz::new(x, y, z))
+            }
+        }
+    }
 }
 
 #[derive(Clone)]
@@ -163,6 +254,14 @@ pub async fn store_reader_from_config(
                 Box::new(NoStore)
             }
         }
+        TileStoreCfg::Redis(cfg) => Box::new(
+            RedisStore::from_config(cfg, compression, tileset_name, format)
+                .await
+                .unwrap_or_else(error_exit),
+        ),
+        TileStoreCfg::Tiered(cfg) => {
+            Box::new(TieredStore::reader_from_config(cfg, compression, tileset_name, format).await)
+        }
         TileStoreCfg::NoStore => Box::new(NoStore),
     }
 }
@@ -185,13 +284,48 @@ pub async fn store_writer_from_config(
             Box::new(S3Store::from_config(cfg, compression, format).unwrap_or_else(error_exit))
         }
         TileStoreCfg::Mbtiles(cfg) => Box::new(
-            MbtilesStore::from_config_writable(cfg, metadata)
+            MbtilesStore::from_config_writable(cfg, compression, metadata)
                 .await
                 .unwrap_or_else(error_exit),
         ),
-        TileStoreCfg::Pmtiles(cfg) => {
-            Box::new(PmtilesStoreWriter::from_config(cfg, metadata, format))
-        }
+        TileStoreCfg::Pmtiles(cfg) => Box::new(PmtilesStoreWriter::from_config(
+            cfg,
+            compression,
+            metadata,
+            format,
+        )),
+        TileStoreCfg::Redis(cfg) => Box::new(
+            RedisStore::from_config(cfg, compression, tileset_name, format)
+                .await
+                .unwrap_or_else(error_exit),
+        ),
+        TileStoreCfg::Tiered(cfg) => Box::new(
+            TieredStore::writer_from_config(cfg, compression, tileset_name, format, metadata).await,
+        ),
         TileStoreCfg::NoStore => Box::new(NoStore),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tile_expiration() {
+        let cfg = CacheControlCfg {
+            max_age: Some(3600),
+            stale_while_revalidate: 600,
+        };
+        let now = SystemTime::now();
+        let age = |secs| Some(now - Duration::from_secs(secs));
+        assert_eq!(CacheState::of_tile(&cfg, age(60), now), CacheState::Fresh);
+        assert_eq!(CacheState::of_tile(&cfg, age(3900), now), CacheState::Stale);
+        assert_eq!(
+            CacheState::of_tile(&cfg, age(4300), now),
+            CacheState::Expired
+        );
+        assert_eq!(CacheState::of_tile(&cfg, None, now), CacheState::Fresh);
+        let cfg = CacheControlCfg::default();
+        assert_eq!(CacheState::of_tile(&cfg, age(4300), now), CacheState::Fresh);
+    }
+}
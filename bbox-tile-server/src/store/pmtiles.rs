@@ -1,7 +1,7 @@
-use crate::config::PmtilesStoreCfg;
+use crate::config::{PmtilesStoreCfg, StoreCompressionCfg};
 use crate::store::{TileReader, TileStoreError, TileWriter};
 use async_trait::async_trait;
-use bbox_core::{Compression, Format, TileResponse};
+use bbox_core::{content_etag, Compression, Format, TileResponse};
 use log::{debug, info};
 use martin_mbtiles::Metadata;
 use pmtiles::async_reader::AsyncPmTilesReader;
@@ -40,7 +40,9 @@ impl Clone for PmtilesStoreReader {
 // Custom impl because `Clone` is not implemented for `PMTiles`
 impl Clone for PmtilesStoreWriter {
     fn clone(&self) -> Self {
-        Self::new(self.path.clone(), self.metadata.clone(), &self.format)
+        let mut writer = Self::new(self.path.clone(), self.metadata.clone(), &self.format);
+        writer.set_compression(&self.compression());
+        writer
     }
 }
 
@@ -77,6 +79,7 @@ impl TileReader for PmtilesStoreReader {
             if let Some(encoding) = tile.tile_compression.content_encoding() {
                 response.insert_header(("Content-Encoding", encoding.to_lowercase()));
             }
+            response.set_etag(content_etag(&tile.data));
             Some(response.with_body(Box::new(Cursor::new(tile.data))))
         } else {
             None
@@ -90,6 +93,7 @@ impl TileWriter for PmtilesStoreWriter {
     fn compression(&self) -> Compression {
         match self.archive.as_ref().expect("initialized").tile_compression {
             PmCompression::GZip => Compression::Gzip,
+            PmCompression::Brotli => Compression::Brotli,
             _ => Compression::None,
         }
     }
@@ -111,6 +115,12 @@ impl TileWriter for PmtilesStoreWriter {
         );
         Ok(())
     }
+    async fn update_metadata(&mut self, metadata: &Metadata) -> Result<(), TileStoreError> {
+        let compression = self.compression();
+        *self = Self::new(self.path.clone(), metadata.clone(), &self.format);
+        self.set_compression(&compression);
+        Ok(())
+    }
     fn finalize(&mut self) -> Result<(), TileStoreError> {
         info!("Writing {}", self.path.display());
         let mut file = File::create(&self.path)
@@ -140,6 +150,8 @@ impl PmtilesStoreWriter {
         } else {
             PmCompression::None
         };
+        // Directories and metadata
+        archive.internal_compression = PmCompression::GZip;
         if let Some(minzoom) = metadata.tilejson.minzoom {
             archive.min_zoom = minzoom;
         }
@@ -171,7 +183,69 @@ impl PmtilesStoreWriter {
             archive: Some(archive),
         }
     }
-    pub fn from_config(cfg: &PmtilesStoreCfg, metadata: Metadata, format: &Format) -> Self {
-        Self::new(cfg.path.clone(), metadata, format)
+    pub fn from_config(
+        cfg: &PmtilesStoreCfg,
+        compression: &Option<StoreCompressionCfg>,
+        metadata: Metadata,
+        format: &Format,
+    ) -> Self {
+        let mut writer = Self::new(cfg.path.clone(), metadata, format);
+        if let Some(compression) = compression {
+            writer.set_compression(&compression.into());
+        }
+        writer
+    }
+    /// Set compression of tile entries
+    fn set_compression(&mut self, compression: &Compression) {
+        if let Some(archive) = self.archive.as_mut() {
+            archive.tile_compression = match compression {
+                Compression::Gzip => PmCompression::GZip,
+                Compression::Brotli => PmCompression::Brotli,
+                Compression::None => PmCompression::None,
+            };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tilejson::tilejson;
+
+    #[tokio::test]
+    async fn pmtiles_roundtrip() {
+        let dir = tempfile::tempdir().unwrap();
+        let cfg = PmtilesStoreCfg {
+            path: dir.path().join("roundtrip.pmtiles"),
+        };
+        let metadata = Metadata {
+            id: "roundtrip".to_string(),
+            tile_info: martin_tile_utils::TileInfo::new(
+                martin_tile_utils::Format::Mvt,
+                martin_tile_utils::Encoding::Gzip,
+            ),
+            tilejson: tilejson! { tiles: vec![] },
+            layer_type: None,
+            json: None,
+            agg_tiles_hash: None,
+        };
+        let mut writer = PmtilesStoreWriter::from_config(&cfg, &None, metadata, &Format::Mvt);
+        assert_eq!(writer.compression(), Compression::Gzip);
+        // Tiles are written in non-clustered order
+        for xyz in [Xyz::new(1, 1, 1), Xyz::new(0, 0, 0), Xyz::new(0, 1, 1)] {
+            let tile = TileResponse::new()
+                .with_body(Box::new(Cursor::new(vec![xyz.z, xyz.x as u8, xyz.y as u8])))
+                .read_bytes(&Compression::Gzip)
+                .unwrap();
+            writer.put_tile_mut(&xyz, tile.body).await.unwrap();
+        }
+        writer.finalize().unwrap();
+
+        let reader = PmtilesStoreReader::from_config(&cfg).await.unwrap();
+        let tile = reader.get_tile(&Xyz::new(0, 1, 1)).await.unwrap().unwrap();
+        assert_eq!(tile.compression(), Compression::Gzip);
+        let data = tile.with_compression(&Compression::None);
+        assert_eq!(data.read_bytes(&Compression::None).unwrap().body, vec![1, 0, 1]);
+        assert!(reader.get_tile(&Xyz::new(1, 0, 1)).await.unwrap().is_none());
     }
 }
@@ -0,0 +1,284 @@
+//! Redis tile store shared by multiple server instances.
+
+use crate::config::{RedisStoreCfg, StoreCompressionCfg};
+use crate::store::{CacheLayout, TileReader, TileStoreError, TileWriter};
+use async_trait::async_trait;
+use bbox_core::health::DependencyHealth;
+use bbox_core::{content_etag, Compression, Format, TileResponse};
+use futures::future::try_join_all;
+use log::{debug, info};
+use once_cell::sync::Lazy;
+use redis::aio::{ConnectionLike, ConnectionManager};
+use redis::cluster::ClusterClient;
+use redis::cluster_async::ClusterConnection;
+use redis::{Cmd, Pipeline, RedisFuture, Value};
+use std::collections::HashMap;
+use std::io::Cursor;
+use std::path::PathBuf;
+use tile_grid::Xyz;
+
+/// Number of keys deleted with one request
+const DELETE_BATCH_SIZE: usize = 1000;
+
+#[derive(Clone)]
+pub struct RedisStore {
+    connection: RedisConnection,
+    /// Key prefix including tileset name
+    prefix: PathBuf,
+    ttl: Option<u64>,
+    compression: StoreCompressionCfg,
+    format: Format,
+    name: String,
+}
+
+/// Connection to a single Redis server or a Redis cluster
+#[derive(Clone)]
+enum RedisConnection {
+    Single(ConnectionManager),
+    Cluster(ClusterConnection),
+}
+
+impl ConnectionLike for RedisConnection {
+    fn req_packed_command<'a>(&'a mut self, cmd: &'a Cmd) -> RedisFuture<'a, Value> {
+        match self {
+            RedisConnection::Single(con) => con.req_packed_command(cmd),
+            RedisConnection::Cluster(con) => con.req_packed_command(cmd),
+        }
+    }
+    fn req_packed_commands<'a>(
+        &'a mut self,
+        cmd: &'a Pipeline,
+        offset: usize,
+        count: usize,
+    ) -> RedisFuture<'a, Vec<Value>> {
+        match self {
+            RedisConnection::Single(con) => con.req_packed_commands(cmd, offset, count),
+            RedisConnection::Cluster(con) => con.req_packed_commands(cmd, offset, count),
+        }
+    }
+    fn get_db(&self) -> i64 {
+        match self {
+            RedisConnection::Single(con) => con.get_db(),
+            RedisConnection::Cluster(con) => con.get_db(),
+        }
+    }
+}
+
+/// Connections shared by the stores of all tilesets
+static CONNECTIONS: Lazy<tokio::sync::Mutex<HashMap<String, RedisConnection>>> =
+    Lazy::new(Default::default);
+
+async fn connection(cfg: &RedisStoreCfg) -> Result<RedisConnection, TileStoreError> {
+    let mut connections = CONNECTIONS.lock().await;
+    if let Some(connection) = connections.get(&cfg.url) {
+        return Ok(connection.clone());
+    }
+    let connection = if cfg.cluster {
+        // Further cluster nodes are discovered from the given node
+        info!("Connecting to Redis cluster {}", cfg.url);
+        let client = ClusterClient::new(vec![cfg.url.as_str()])?;
+        RedisConnection::Cluster(client.get_async_connection().await?)
+    } else {
+        info!("Connecting to Redis {}", cfg.url);
+        let client = redis::Client::open(cfg.url.as_str())?;
+        RedisConnection::Single(ConnectionManager::new(client).await?)
+    };
+    connections.insert(cfg.url.clone(), connection.clone());
+    Ok(connection)
+}
+
+impl RedisStore {
+    pub async fn from_config(
+        cfg: &RedisStoreCfg,
+        compression: &Option<StoreCompressionCfg>,
+        tileset_name: &str,
+        format: &Format,
+    ) -> Result<Self, TileStoreError> {
+        let connection = connection(cfg).await?;
+        let prefix = PathBuf::from_iter([cfg.prefix.as_str(), tileset_name]);
+        Ok(RedisStore {
+            connection,
+            prefix,
+            ttl: cfg.ttl,
+            compression: compression.clone().unwrap_or(StoreCompressionCfg::None),
+            format: *format,
+            name: cfg.url.clone(),
+        })
+    }
+    fn key(&self, xyz: &Xyz) -> String {
+        CacheLayout::Zxy.path_string(&self.prefix, xyz, &self.format)
+    }
+    fn set_cmd(&self, xyz: &Xyz, data: &[u8]) -> Cmd {
+        let mut cmd = redis::cmd("SET");
+        cmd.arg(self.key(xyz)).arg(data);
+        if let Some(ttl) = self.ttl {
+            cmd.arg("EX").arg(ttl);
+        }
+        cmd
+    }
+    fn is_cluster(&self) -> bool {
+        matches!(self.connection, RedisConnection::Cluster(_))
+    }
+    async fn delete_keys(&self, keys: Vec<String>) -> Result<(), TileStoreError> {
+        debug!("Deleting {} keys", keys.len());
+        if self.is_cluster() {
+            // Keys of different hash slots can't be deleted with one command
+            let requests = keys.iter().map(|key| {
+                let mut connection = self.connection.clone();
+                async move {
+                    redis::cmd("UNLINK")
+                        .arg(key)
+                        .query_async::<_, ()>(&mut connection)
+                        .await
+                }
+            });
+            try_join_all(requests).await?;
+        } else {
+            let mut connection = self.connection.clone();
+            redis::cmd("UNLINK")
+                .arg(keys)
+                .query_async::<_, ()>(&mut connection)
+                .await?;
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl TileWriter for RedisStore {
+    fn compression(&self) -> Compression {
+        Compression::from(&self.compression)
+    }
+    async fn exists(&self, xyz: &Xyz) -> bool {
+        let mut connection = self.connection.clone();
+        redis::cmd("EXISTS")
+            .arg(self.key(xyz))
+            .query_async(&mut connection)
+            .await
+            .unwrap_or(false)
+    }
+    async fn put_tile(&self, xyz: &Xyz, data: Vec<u8>) -> Result<(), TileStoreError> {
+        let mut connection = self.connection.clone();
+        self.set_cmd(xyz, &data)
+            .query_async::<_, ()>(&mut connection)
+            .await?;
+        Ok(())
+    }
+    async fn put_tiles(&mut self, tiles: &[(u8, u32, u32, Vec<u8>)]) -> Result<(), TileStoreError> {
+        let commands = tiles
+            .iter()
+            .map(|(z, x, y, tile)| self.set_cmd(&Xyz::new(*x as u64, *y as u64, *z), tile));
+        if self.is_cluster() {
+            // Pipelines are restricted to keys of one node
+            let requests = commands.map(|cmd| {
+                let mut connection = self.connection.clone();
+                async move { cmd.query_async::<_, ()>(&mut connection).await }
+            });
+            try_join_all(requests).await?;
+        } else {
+            let mut pipeline = redis::pipe();
+            for cmd in commands {
+                pipeline.add_command(cmd).ignore();
+            }
+            let mut connection = self.connection.clone();
+            pipeline.query_async::<_, ()>(&mut connection).await?;
+        }
+        Ok(())
+    }
+    async fn delete_tile(&self, xyz: &Xyz) -> Result<(), TileStoreError> {
+        self.delete_keys(vec![self.key(xyz)]).await
+    }
+    async fn delete_tiles(&self, tiles: &[Xyz]) -> Result<(), TileStoreError> {
+        let keys = tiles.iter().map(|xyz| self.key(xyz)).collect::<Vec<_>>();
+        for batch in keys.chunks(DELETE_BATCH_SIZE) {
+            self.delete_keys(batch.to_vec()).await?;
+        }
+        Ok(())
+    }
+    async fn delete_prefix(&self, prefix: &str) -> Result<(), TileStoreError> {
+        if self.is_cluster() {
+            // Scanning keys would require a connection to each node
+            return Err(TileStoreError::DeleteUnsupported);
+        }
+        let pattern = format!("{}/{prefix}/*", self.prefix.display());
+        let mut connection = self.connection.clone();
+        let mut cursor = 0u64;
+        loop {
+            let (next, keys): (u64, Vec<String>) = redis::cmd("SCAN")
+                .arg(cursor)
+                .arg("MATCH")
+                .arg(&pattern)
+                .arg("COUNT")
+                .arg(DELETE_BATCH_SIZE)
+                .query_async(&mut connection)
+                .await?;
+            if !keys.is_empty() {
+                self.delete_keys(keys).await?;
+            }
+            if next == 0 {
+                break;
+            }
+            cursor = next;
+        }
+        Ok(())
+    }
+    async fn check(&self) -> Option<DependencyHealth> {
+        let mut connection = self.connection.clone();
+        let result = redis::cmd("PING")
+            .query_async::<_, String>(&mut connection)
+            .await
+            .map(|_| ());
+        Some(DependencyHealth::from_result("redis", &self.name, result))
+    }
+}
+
+#[async_trait]
+impl TileReader for RedisStore {
+    async fn get_tile(&self, xyz: &Xyz) -> Result<Option<TileResponse>, TileStoreError> {
+        let mut connection = self.connection.clone();
+        let data: Option<Vec<u8>> = redis::cmd("GET")
+            .arg(self.key(xyz))
+            .query_async(&mut connection)
+            .await?;
+        let Some(data) = data else {
+            return Ok(None);
+        };
+        let mut response = TileResponse::new();
+        if let Some(encoding) = self.compression().content_encoding() {
+            response.insert_header(("Content-Encoding", encoding));
+        }
+        response.set_etag(content_etag(&data));
+        Ok(Some(response.with_body(Box::new(Cursor::new(data)))))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // docker run --rm -p 6379:6379 redis
+    #[tokio::test]
+    #[ignore]
+    async fn redis_store() {
+        let cfg: RedisStoreCfg = serde_json::from_value(serde_json::json!({
+            "url": "redis://127.0.0.1:6379",
+            "ttl": 60
+        }))
+        .unwrap();
+        let mut store = RedisStore::from_config(&cfg, &None, "test", &Format::Mvt)
+            .await
+            .unwrap();
+        store
+            .put_tiles(&[(1, 0, 0, vec![1, 0, 1]), (1, 1, 0, vec![2, 0, 2])])
+            .await
+            .unwrap();
+        assert!(store.exists(&Xyz::new(1, 0, 1)).await);
+        let tile = store.get_tile(&Xyz::new(0, 0, 1)).await.unwrap().unwrap();
+        let data = tile.read_bytes(&Compression::None).unwrap();
+        assert_eq!(data.body, vec![1, 0, 1]);
+
+        store.delete_prefix("1").await.unwrap();
+        assert!(!store.exists(&Xyz::new(1, 0, 1)).await);
+        assert!(store.get_tile(&Xyz::new(0, 0, 1)).await.unwrap().is_none());
+    }
+}
@@ -1,14 +1,23 @@
 use crate::config::{S3StoreCfg, StoreCompressionCfg};
 use crate::store::{CacheLayout, TileReader, TileStoreError, TileWriter};
+use actix_web::http::header::HttpDate;
 use async_trait::async_trait;
-use bbox_core::{Compression, Format, TileResponse};
-use log::debug;
-use rusoto_s3::{PutObjectError, PutObjectRequest, S3Client, S3};
+use bbox_core::health::DependencyHealth;
+use bbox_core::{content_etag, Compression, Format, TileResponse};
+use futures::TryStreamExt;
+use log::{debug, warn};
+use rusoto_core::RusotoError;
+use rusoto_s3::{
+    Delete, DeleteObjectsRequest, GetObjectError, GetObjectRequest, HeadBucketRequest,
+    ListObjectsV2Error, ListObjectsV2Request, ObjectIdentifier, PutObjectError, PutObjectRequest,
+    S3Client, S3,
+};
 use std::env;
 use std::fs::{self, File};
-use std::io::{BufReader, Read};
+use std::io::{BufReader, Cursor, Read};
 use std::path::Path;
 use std::path::PathBuf;
+use std::time::SystemTime;
 use tile_grid::Xyz;
 
 #[derive(Clone, Debug)]
@@ -17,6 +26,8 @@ pub struct S3Store {
     region: rusoto_core::Region,
     compression: StoreCompressionCfg,
     format: Format,
+    /// Lookup stored tiles
+    lookup: bool,
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -27,8 +38,17 @@ pub enum S3StoreError {
     ReadInputError(#[source] std::io::Error),
     #[error("Upload failed: {0}")]
     UploadFailed(#[source] rusoto_core::RusotoError<PutObjectError>),
+    #[error("Delete failed: {0}")]
+    DeleteFailed(String),
+    #[error("Listing objects failed: {0}")]
+    ListFailed(#[source] rusoto_core::RusotoError<ListObjectsV2Error>),
+    #[error("Download failed: {0}")]
+    DownloadFailed(#[source] rusoto_core::RusotoError<GetObjectError>),
 }
 
+/// Maximal number of keys in a DeleteObjects request
+const DELETE_BATCH_SIZE: usize = 1000;
+
 impl S3Store {
     pub fn from_s3_path(
         s3_path: &str,
@@ -59,6 +79,7 @@ impl S3Store {
             region,
             compression,
             format,
+            lookup: false,
         })
     }
     pub fn from_config(
@@ -68,15 +89,17 @@ impl S3Store {
     ) -> Result<Self, TileStoreError> {
         Self::from_s3_path(&cfg.path, compression, *format).map_err(Into::into)
     }
+    /// Enable reading stored tiles, e.g. as second cache level
+    pub fn with_lookup(mut self) -> Self {
+        self.lookup = true;
+        self
+    }
 }
 
 #[async_trait]
 impl TileWriter for S3Store {
     fn compression(&self) -> Compression {
-        match self.compression {
-            StoreCompressionCfg::Gzip => Compression::Gzip,
-            StoreCompressionCfg::None => Compression::None,
-        }
+        Compression::from(&self.compression)
     }
     async fn exists(&self, _xyz: &Xyz) -> bool {
         // 2nd level cache lookup is not supported
@@ -86,6 +109,58 @@ impl TileWriter for S3Store {
         let key = CacheLayout::Zxy.path_string(&PathBuf::new(), xyz, &self.format);
         self.put_data(key, data).await
     }
+    async fn delete_tile(&self, xyz: &Xyz) -> Result<(), TileStoreError> {
+        self.delete_tiles(&[Xyz::new(xyz.x, xyz.y, xyz.z)]).await
+    }
+    async fn delete_tiles(&self, tiles: &[Xyz]) -> Result<(), TileStoreError> {
+        let keys = tiles
+            .iter()
+            .map(|xyz| CacheLayout::Zxy.path_string(&PathBuf::new(), xyz, &self.format))
+            .collect::<Vec<_>>();
+        for batch in keys.chunks(DELETE_BATCH_SIZE) {
+            self.delete_keys(batch.to_vec()).await?;
+        }
+        Ok(())
+    }
+    async fn delete_prefix(&self, prefix: &str) -> Result<(), TileStoreError> {
+        let client = S3Client::new(self.region.clone());
+        let mut continuation_token = None;
+        loop {
+            let request = ListObjectsV2Request {
+                bucket: self.bucket.clone(),
+                prefix: Some(format!("{prefix}/")),
+                continuation_token,
+                ..Default::default()
+            };
+            let response = client
+                .list_objects_v2(request)
+                .await
+                .map_err(S3StoreError::ListFailed)?;
+            let keys = response
+                .contents
+                .unwrap_or_default()
+                .into_iter()
+                .filter_map(|obj| obj.key)
+                .collect::<Vec<_>>();
+            if !keys.is_empty() {
+                self.delete_keys(keys).await?;
+            }
+            continuation_token = response.next_continuation_token;
+            if continuation_token.is_none() {
+                break;
+            }
+        }
+        Ok(())
+    }
+    async fn check(&self) -> Option<DependencyHealth> {
+        let client = S3Client::new(self.region.clone());
+        let request = HeadBucketRequest {
+            bucket: self.bucket.clone(),
+            ..Default::default()
+        };
+        let result = client.head_bucket(request).await;
+        Some(DependencyHealth::from_result("s3", &self.bucket, result))
+    }
 }
 
 impl S3Store {
@@ -102,15 +177,50 @@ impl S3Store {
                 key,
                 body: Some(data.into()),
                 content_length: Some(content_length),
+                content_encoding: self.compression().content_encoding().map(str::to_string),
                 ..Default::default()
             };
             client.put_object(request).await
         } {
-            eprintln!("Upload failed: {e}");
+            warn!("Upload failed: {e}");
             return Err(S3StoreError::UploadFailed(e).into());
         }
         Ok(())
     }
+    /// Delete objects with a single request (max. 1000 keys)
+    async fn delete_keys(&self, keys: Vec<String>) -> Result<(), TileStoreError> {
+        let client = S3Client::new(self.region.clone());
+        debug!("rm {} objects", keys.len());
+        let request = DeleteObjectsRequest {
+            bucket: self.bucket.clone(),
+            delete: Delete {
+                objects: keys
+                    .into_iter()
+                    .map(|key| ObjectIdentifier {
+                        key,
+                        version_id: None,
+                    })
+                    .collect(),
+                quiet: Some(true),
+            },
+            ..Default::default()
+        };
+        let response = client
+            .delete_objects(request)
+            .await
+            .map_err(|e| S3StoreError::DeleteFailed(e.to_string()))?;
+        if let Some(errors) = response.errors.filter(|errors| !errors.is_empty()) {
+            let first = &errors[0];
+            return Err(S3StoreError::DeleteFailed(format!(
+                "{} objects not deleted ({}: {})",
+                errors.len(),
+                first.key.as_deref().unwrap_or_default(),
+                first.message.as_deref().unwrap_or_default()
+            ))
+            .into());
+        }
+        Ok(())
+    }
     /// Put tile from temporary file
     #[allow(dead_code)]
     pub async fn copy_tile(&self, base_dir: &Path, xyz: &Xyz) -> Result<(), TileStoreError> {
@@ -129,8 +239,75 @@ impl S3Store {
 
 #[async_trait]
 impl TileReader for S3Store {
-    async fn get_tile(&self, _xyz: &Xyz) -> Result<Option<TileResponse>, TileStoreError> {
-        // 2nd level cache lookup is not supported
-        Ok(None)
+    async fn get_tile(&self, xyz: &Xyz) -> Result<Option<TileResponse>, TileStoreError> {
+        if !self.lookup {
+            // Rendering is usually faster than an S3 request
+            return Ok(None);
+        }
+        let client = S3Client::new(self.region.clone());
+        let request = GetObjectRequest {
+            bucket: self.bucket.clone(),
+            key: CacheLayout::Zxy.path_string(&PathBuf::new(), xyz, &self.format),
+            ..Default::default()
+        };
+        let output = match client.get_object(request).await {
+            Ok(output) => output,
+            Err(RusotoError::Service(GetObjectError::NoSuchKey(_))) => return Ok(None),
+            Err(e) => return Err(S3StoreError::DownloadFailed(e).into()),
+        };
+        let mut response = TileResponse::new();
+        if let Some(encoding) = output.content_encoding {
+            response.insert_header(("Content-Encoding", encoding));
+        }
+        if let Some(modified) = output
+            .last_modified
+            .and_then(|date| date.parse::<HttpDate>().ok())
+        {
+            response.set_modified(SystemTime::from(modified));
+        }
+        let data = match output.body {
+            Some(body) => {
+                body.try_fold(Vec::new(), |mut data, chunk| async move {
+                    data.extend_from_slice(&chunk);
+                    Ok(data)
+                })
+                .await?
+            }
+            None => Vec::new(),
+        };
+        response.set_etag(content_etag(&data));
+        Ok(Some(response.with_body(Box::new(Cursor::new(data)))))
+    }
+    async fn stored_tiles(&self, z: u8) -> Result<Option<Vec<(Xyz, u64)>>, TileStoreError> {
+        let client = S3Client::new(self.region.clone());
+        let mut tiles = Vec::new();
+        let mut continuation_token = None;
+        loop {
+            let request = ListObjectsV2Request {
+                bucket: self.bucket.clone(),
+                prefix: Some(format!("{z}/")),
+                continuation_token,
+                ..Default::default()
+            };
+            let response = client
+                .list_objects_v2(request)
+                .await
+                .map_err(S3StoreError::ListFailed)?;
+            tiles.extend(
+                response
+                    .contents
+                    .unwrap_or_default()
+                    .into_iter()
+                    .filter_map(|obj| {
+                        let xyz = CacheLayout::Zxy.tile_of_path(obj.key.as_ref()?, &self.format)?;
+                        Some((xyz, obj.size.unwrap_or(0) as u64))
+                    }),
+            );
+            continuation_token = response.next_continuation_token;
+            if continuation_token.is_none() {
+                break;
+            }
+        }
+        Ok(Some(tiles))
     }
 }
@@ -0,0 +1,360 @@
+//! Local file cache in front of a (remote) tile store.
+//!
+//! Tiles are read from the local store first. Tiles found in the backing store only are copied
+//! into the local store. New tiles are written into both stores.
+
+use crate::config::{StoreCompressionCfg, TieredStoreCfg, TileStoreCfg};
+use crate::store::files::FileStore;
+use crate::store::s3::S3Store;
+use crate::store::{
+    store_reader_from_config, store_writer_from_config, NoStore, TileReader, TileStoreError,
+    TileWriter,
+};
+use async_trait::async_trait;
+use bbox_core::config::error_exit;
+use bbox_core::health::DependencyHealth;
+use bbox_core::{Compression, Format, TileResponse};
+use futures::future::BoxFuture;
+use log::{debug, info, warn};
+use martin_mbtiles::Metadata;
+use once_cell::sync::Lazy;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+use tile_grid::Xyz;
+
+#[derive(Clone)]
+pub struct TieredStore {
+    l1: FileStore,
+    /// Size limit of local store, shared with all stores in the same base directory
+    l1_usage: Option<Arc<DiskUsage>>,
+    l2_reader: Box<dyn TileReader>,
+    l2_writer: Box<dyn TileWriter>,
+}
+
+impl TieredStore {
+    /// Create store with maximal local tile size `l1_max_size` in bytes for all tiles in `l1_root`
+    pub fn new(
+        l1: FileStore,
+        l1_root: &Path,
+        l1_max_size: Option<u64>,
+        l2_reader: Box<dyn TileReader>,
+        l2_writer: Box<dyn TileWriter>,
+    ) -> Self {
+        let l1_usage = l1_max_size.map(|max_size| DiskUsage::shared(l1_root, max_size));
+        TieredStore {
+            l1,
+            l1_usage,
+            l2_reader,
+            l2_writer,
+        }
+    }
+    pub async fn reader_from_config(
+        cfg: &TieredStoreCfg,
+        compression: &Option<StoreCompressionCfg>,
+        tileset_name: &str,
+        format: &Format,
+    ) -> Self {
+        let l2_reader: Box<dyn TileReader> = match cfg.l2.as_ref() {
+            TileStoreCfg::S3(s3_cfg) => Box::new(
+                S3Store::from_config(s3_cfg, compression, format)
+                    .unwrap_or_else(error_exit)
+                    .with_lookup(),
+            ),
+            l2 => l2_reader(l2, compression, tileset_name, format).await,
+        };
+        Self::from_config(
+            cfg,
+            compression,
+            tileset_name,
+            format,
+            l2_reader,
+            Box::new(NoStore),
+        )
+    }
+    pub async fn writer_from_config(
+        cfg: &TieredStoreCfg,
+        compression: &Option<StoreCompressionCfg>,
+        tileset_name: &str,
+        format: &Format,
+        metadata: Metadata,
+    ) -> Self {
+        let l2_writer = l2_writer(&cfg.l2, compression, tileset_name, format, metadata).await;
+        Self::from_config(
+            cfg,
+            compression,
+            tileset_name,
+            format,
+            Box::new(NoStore),
+            l2_writer,
+        )
+    }
+    fn from_config(
+        cfg: &TieredStoreCfg,
+        compression: &Option<StoreCompressionCfg>,
+        tileset_name: &str,
+        format: &Format,
+        l2_reader: Box<dyn TileReader>,
+        l2_writer: Box<dyn TileWriter>,
+    ) -> Self {
+        let l1 = FileStore::from_config(&cfg.l1, compression, tileset_name, format);
+        let l1_max_size = cfg.l1_max_size.map(|mb| mb * 1024 * 1024);
+        Self::new(l1, &cfg.l1.base_dir, l1_max_size, l2_reader, l2_writer)
+    }
+    async fn put_l1(&self, xyz: &Xyz, data: Vec<u8>) -> Result<(), TileStoreError> {
+        let size = data.len() as u64;
+        self.l1.put_tile(xyz, data).await?;
+        if let Some(usage) = &self.l1_usage {
+            for path in usage.insert(self.l1.tile_path(xyz), size) {
+                debug!("Evicting {}", path.display());
+                if let Err(e) = fs::remove_file(&path) {
+                    warn!("Evicting {} failed: {e}", path.display());
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+// Store creation is recursive for nested stores
+fn l2_reader<'a>(
+    cfg: &'a TileStoreCfg,
+    compression: &'a Option<StoreCompressionCfg>,
+    tileset_name: &'a str,
+    format: &'a Format,
+) -> BoxFuture<'a, Box<dyn TileReader>> {
+    Box::pin(store_reader_from_config(
+        cfg,
+        compression,
+        tileset_name,
+        format,
+    ))
+}
+
+fn l2_writer<'a>(
+    cfg: &'a TileStoreCfg,
+    compression: &'a Option<StoreCompressionCfg>,
+    tileset_name: &'a str,
+    format: &'a Format,
+    metadata: Metadata,
+) -> BoxFuture<'a, Box<dyn TileWriter>> {
+    Box::pin(store_writer_from_config(
+        cfg,
+        compression,
+        tileset_name,
+        format,
+        metadata,
+    ))
+}
+
+#[async_trait]
+impl TileWriter for TieredStore {
+    fn compression(&self) -> Compression {
+        self.l1.compression()
+    }
+    async fn exists(&self, xyz: &Xyz) -> bool {
+        self.l1.exists(xyz).await || self.l2_writer.exists(xyz).await
+    }
+    async fn put_tile(&self, xyz: &Xyz, data: Vec<u8>) -> Result<(), TileStoreError> {
+        self.l2_writer.put_tile(xyz, data.clone()).await?;
+        self.put_l1(xyz, data).await
+    }
+    async fn delete_tile(&self, xyz: &Xyz) -> Result<(), TileStoreError> {
+        self.l2_writer.delete_tile(xyz).await?;
+        if let Some(usage) = &self.l1_usage {
+            usage.remove_prefix(&self.l1.tile_path(xyz));
+        }
+        self.l1.delete_tile(xyz).await
+    }
+    async fn delete_prefix(&self, prefix: &str) -> Result<(), TileStoreError> {
+        self.l2_writer.delete_prefix(prefix).await?;
+        if let Some(usage) = &self.l1_usage {
+            usage.remove_prefix(&self.l1.base_dir.join(prefix));
+        }
+        self.l1.delete_prefix(prefix).await
+    }
+    async fn check(&self) -> Option<DependencyHealth> {
+        self.l2_writer.check().await
+    }
+}
+
+#[async_trait]
+impl TileReader for TieredStore {
+    async fn get_tile(&self, xyz: &Xyz) -> Result<Option<TileResponse>, TileStoreError> {
+        if let Some(tile) = self.l1.get_tile(xyz).await? {
+            if let Some(usage) = &self.l1_usage {
+                usage.touch(&self.l1.tile_path(xyz));
+            }
+            return Ok(Some(tile));
+        }
+        let Some(tile) = self.l2_reader.get_tile(xyz).await? else {
+            return Ok(None);
+        };
+        let compression = self.l1.compression();
+        let data = tile.read_bytes(&compression)?;
+        if let Err(e) = self.put_l1(xyz, data.body.clone()).await {
+            warn!("Copying tile {xyz:?} into local store failed: {e}");
+        }
+        Ok(Some(data.as_response(&compression)))
+    }
+    async fn stored_tiles(&self, z: u8) -> Result<Option<Vec<(Xyz, u64)>>, TileStoreError> {
+        // The local store holds a subset of the tiles only
+        self.l2_reader.stored_tiles(z).await
+    }
+}
+
+/// Size of files in a local cache directory, evicting least recently used files
+struct DiskUsage {
+    max_size: u64,
+    inner: Mutex<LruFiles>,
+}
+
+#[derive(Default)]
+struct LruFiles {
+    files: HashMap<PathBuf, (u64, u64)>,
+    /// Files ordered by last access
+    access: BTreeMap<u64, PathBuf>,
+    tick: u64,
+    size: u64,
+}
+
+static DISK_USAGE: Lazy<Mutex<HashMap<PathBuf, Arc<DiskUsage>>>> = Lazy::new(Default::default);
+
+impl DiskUsage {
+    /// Usage of directory `root`, initialized from existing files on first use
+    fn shared(root: &Path, max_size: u64) -> Arc<Self> {
+        DISK_USAGE
+            .lock()
+            .unwrap()
+            .entry(root.to_path_buf())
+            .or_insert_with(|| Arc::new(DiskUsage::scan(root, max_size)))
+            .clone()
+    }
+    fn scan(root: &Path, max_size: u64) -> Self {
+        let mut files = Vec::new();
+        collect_files(root, &mut files);
+        // Oldest files are evicted first
+        files.sort_by_key(|(_, _, modified)| *modified);
+        let mut lru = LruFiles::default();
+        for (path, size, _) in files {
+            lru.insert(path, size);
+        }
+        info!(
+            "Local tile store {}: {} MB in {} tiles",
+            root.display(),
+            lru.size / 1024 / 1024,
+            lru.files.len()
+        );
+        DiskUsage {
+            max_size,
+            inner: Mutex::new(lru),
+        }
+    }
+    fn touch(&self, path: &Path) {
+        let mut lru = self.inner.lock().unwrap();
+        if let Some(size) = lru.remove(path) {
+            lru.insert(path.to_path_buf(), size);
+        }
+    }
+    /// Register file and return files to evict
+    fn insert(&self, path: PathBuf, size: u64) -> Vec<PathBuf> {
+        let mut lru = self.inner.lock().unwrap();
+        lru.remove(&path);
+        let mut evicted = Vec::new();
+        while lru.size + size > self.max_size {
+            let Some(oldest) = lru.access.values().next().cloned() else {
+                break;
+            };
+            lru.remove(&oldest);
+            evicted.push(oldest);
+        }
+        lru.insert(path, size);
+        evicted
+    }
+    /// Unregister file or all files in directory `prefix`
+    fn remove_prefix(&self, prefix: &Path) {
+        let mut lru = self.inner.lock().unwrap();
+        let paths: Vec<PathBuf> = lru
+            .files
+            .keys()
+            .filter(|path| path.starts_with(prefix))
+            .cloned()
+            .collect();
+        for path in paths {
+            lru.remove(&path);
+        }
+    }
+}
+
+impl LruFiles {
+    fn insert(&mut self, path: PathBuf, size: u64) {
+        self.tick += 1;
+        self.access.insert(self.tick, path.clone());
+        self.files.insert(path, (size, self.tick));
+        self.size += size;
+    }
+    fn remove(&mut self, path: &Path) -> Option<u64> {
+        let (size, tick) = self.files.remove(path)?;
+        self.access.remove(&tick);
+        self.size -= size;
+        Some(size)
+    }
+}
+
+fn collect_files(dir: &Path, files: &mut Vec<(PathBuf, u64, SystemTime)>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let Ok(metadata) = entry.metadata() else {
+            continue;
+        };
+        if metadata.is_dir() {
+            collect_files(&entry.path(), files);
+        } else {
+            let modified = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+            files.push((entry.path(), metadata.len(), modified));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn tiered_store() {
+        let l1_dir = tempfile::tempdir().unwrap();
+        let l2_dir = tempfile::tempdir().unwrap();
+        let l1 = FileStore::new(l1_dir.path().into(), StoreCompressionCfg::None, Format::Mvt);
+        let l2 = FileStore::new(l2_dir.path().into(), StoreCompressionCfg::None, Format::Mvt);
+        let store = TieredStore::new(
+            l1.clone(),
+            l1_dir.path(),
+            Some(10),
+            Box::new(l2.clone()),
+            Box::new(l2.clone()),
+        );
+        let tiles = [Xyz::new(0, 0, 1), Xyz::new(1, 0, 1), Xyz::new(0, 1, 1)];
+        for xyz in &tiles {
+            store.put_tile(xyz, vec![1, 0, 1, 0]).await.unwrap();
+        }
+        // First tile is evicted from the local store only
+        assert!(!l1.exists(&tiles[0]).await);
+        assert!(l1.exists(&tiles[2]).await);
+        assert!(l2.exists(&tiles[0]).await);
+
+        // Reading copies the tile into the local store, evicting the least recently used one
+        let tile = store.get_tile(&tiles[0]).await.unwrap().unwrap();
+        let data = tile.read_bytes(&Compression::None).unwrap();
+        assert_eq!(data.body, vec![1, 0, 1, 0]);
+        assert!(l1.exists(&tiles[0]).await);
+        assert!(!l1.exists(&tiles[1]).await);
+
+        store.delete_prefix("1").await.unwrap();
+        assert!(!store.exists(&tiles[0]).await);
+        assert!(store.get_tile(&tiles[2]).await.unwrap().is_none());
+    }
+}